@@ -0,0 +1,62 @@
+//! Counts the allocations the search's hot paths make, run with `cargo bench --features bench`
+//!
+//! Lives in its own binary so the counting allocator doesn't slow down or skew the unit tests
+//!
+//! | | Both attack sets | One move |
+//! |-|-|-|
+//! | Hash sets of squares | 57 | 110 |
+//! | Bitboards | 0 | 30 |
+//!
+//! What's left per move is the move lists, the budget below fails the bench if that grows
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use chess_ai::bench::Position;
+
+/// Measured with bitboard attack sets, building the attack sets shouldn't allocate at all
+const MOVE_BUDGET: usize = 30;
+
+thread_local! {
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+struct Counting;
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        COUNT.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Allocations `f` made on this thread
+fn count(f: impl FnOnce()) -> usize {
+    let before = COUNT.with(Cell::get);
+    f();
+    COUNT.with(Cell::get) - before
+}
+
+fn main() {
+    let position = Position::default();
+    let attacks = count(|| position.attacks());
+
+    let mut played = Position::default();
+    let per_move = count(|| played.play());
+
+    println!("attack sets: {} allocations", attacks);
+    println!(
+        "one move: {} allocations (budget {})",
+        per_move, MOVE_BUDGET
+    );
+    assert!(
+        attacks == 0 && per_move <= MOVE_BUDGET,
+        "over the allocation budget"
+    );
+}
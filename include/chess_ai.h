@@ -0,0 +1,47 @@
+/* C API for the chess-ai engine, see src/ffi.rs */
+
+#ifndef CHESS_AI_H
+#define CHESS_AI_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Result of every function, the values are stable across versions */
+typedef enum ChessError {
+    CHESS_OK = 0,
+    CHESS_NULL_POINTER = 1,
+    CHESS_INVALID_UTF8 = 2,
+    /* Unreadable FEN, or an illegal position */
+    CHESS_INVALID_FEN = 3,
+    CHESS_ILLEGAL_MOVE = 4,
+    CHESS_GAME_OVER = 5,
+    /* The text didn't fit, nothing was written */
+    CHESS_BUFFER_TOO_SMALL = 6,
+    /* The engine hit a bug, the board should be freed */
+    CHESS_INTERNAL = 7,
+} ChessError;
+
+typedef struct ChessBoard ChessBoard;
+
+/* Creates a board from `fen`, or the standard position if it's NULL, free it with chess_board_free */
+ChessError chess_board_new(const char *fen, ChessBoard **out);
+void chess_board_free(ChessBoard *board);
+
+/* Text is written into `out` as NUL terminated UTF-8, moves are in coordinate notation, IE "e2e4" or "e7e8q" */
+ChessError chess_board_fen(ChessBoard *board, char *out, size_t len);
+/* Legal moves of the side to move, separated by spaces */
+ChessError chess_board_moves(ChessBoard *board, char *out, size_t len);
+/* Promotions are always to a queen */
+ChessError chess_board_make_move(ChessBoard *board, const char *move);
+/* The engine's move after thinking for up to `ms` milliseconds */
+ChessError chess_board_best_move(ChessBoard *board, uint32_t ms, char *out, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
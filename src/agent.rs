@@ -1,246 +1,1258 @@
-//! Agents for [Board]. Has a minimax agent and a random agent. Change between agents in the GUI or editing `Board.agent`
+//! Agents for [Board]. Has a minimax agent, simpler teaching agents, and a random agent. Change between agents in the GUI or editing `Board.agent`
 //!
 //! # Minimax
 //!
-//! - Stored openings
+//! - Stored openings, including ones transposed back into
 //! - Alpha-beta pruning
 //! - Sorted move ordering
 //! - Transposition table
+//! - Pauses between positions to search a slice each frame on the web, see [Thinking]
+//! - Avoids shuffling pieces back and forth when ahead
+//! - Resigns hopeless games and offers draws in dead equal endgames, see [Outlook]
+//!
+//! # NNUE
+//!
+//! - Minimax, but scored by the [NNUE network](crate::nnue) when one is loaded
+//!
+//! # Depth 2
+//!
+//! - Plain minimax, two plies deep
+//! - No pruning, openings, or transposition table
+//!
+//! # Greedy
+//!
+//! - Grabs the most material it can this move, ignoring replies
 //!
 //! # Random
 //!
 //! - Just picks a valid move by random
 //!
+//! # MCTS
+//!
+//! - Monte Carlo tree search, see [mcts](crate::mcts)
+//!
+//! # Engine
+//!
+//! - An external UCI engine, see [engine](crate::engine)
+//!
 //! # Control
 //!
 //! - Manually control the agent by clicking on the board
+//!
+//! Each agent is a [MovePicker] registered by name in [AGENTS], which the GUI's agent buttons are made from, see
+//! [AgentId]
 
-use macroquad::prelude::info;
-use macroquad::rand::ChooseRandom;
-use macroquad::time::get_time;
+use std::fmt::{self, Display, Formatter};
+#[cfg(feature = "gui")]
+use std::panic::{catch_unwind, AssertUnwindSafe};
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crossbeam_channel::Sender;
+use derive_new::new;
+use lazy_static::lazy_static;
+use quad_rand::ChooseRandom;
 use rustc_hash::FxHashMap;
+use serde::ser::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::agent_opens::OPENINGS;
-use crate::board::{Board, ChessColor};
+use crate::agent_opens::openings;
+use crate::board::{Board, BoardState, ChessColor};
+#[cfg(feature = "gui")]
+use crate::board_eval::Phase;
+use crate::board_eval::{mate_from_root, EvalBackend};
+#[cfg(not(target_family = "wasm"))]
+use crate::engine::{engine_path, UciEngine};
+use crate::mcts::Mcts;
+use crate::nnue::warn_if_missing;
 use crate::pieces::piece::PieceNames;
-use crate::util::{choose_array, Loc};
+use crate::platform::{info, log_book_hit, now, warn};
+#[cfg(feature = "gui")]
+use crate::rating::searched_rating;
+#[cfg(feature = "gui")]
+use crate::settings::Difficulty;
+use crate::util::Loc;
+use crate::variant::Variant;
 use crate::{color_ternary, hashmap, ternary};
 
 fn random_agent(board: &Board) -> Option<(Loc, Loc)> {
-    let moves = board.moves(board.agent_color);
+    let moves = board.moves(board.turn);
     moves.choose().copied()
 }
 
-const MAX: i32 = i32::MAX - 1;
-const TIMEOUT_SCORE: i32 = i32::MAX - 2;
+/// Material balance of the board for `color`, ignoring piece positions
+fn material(board: &Board, color: ChessColor) -> i32 {
+    board
+        .raw
+        .iter()
+        .flatten()
+        .flatten()
+        .filter(|piece| piece.name != PieceNames::King)
+        .map(|piece| ternary!(piece.color == color, piece.value(), -piece.value()))
+        .sum()
+}
 
-/// Minimax agent with alpha-beta pruning and sorted move ordering
-#[allow(clippy::type_complexity, clippy::too_many_arguments)]
-fn minimax(
-    board: &Board,
-    maximizing: bool,
-    depth: u8,
-    mut alpha: i32,
-    mut beta: i32,
-    trans_table: &mut FxHashMap<u64, (u8, i32, Option<(Loc, Loc)>)>,
-    start_time: f64,
-    antimax: bool,
-) -> (i32, Option<(Loc, Loc)>) {
-    if (!antimax && maximizing) || (antimax && !maximizing) {
-        assert_eq!(board.turn, ChessColor::White);
-    } else {
-        assert_eq!(board.turn, ChessColor::Black);
-    }
+/// Picks randomly between the moves with the highest score
+fn best_moves(scored: Vec<(i32, (Loc, Loc))>) -> Option<(Loc, Loc)> {
+    let best = scored.iter().map(|(score, _)| *score).max()?;
+    let moves: Vec<_> = scored
+        .into_iter()
+        .filter(|(score, _)| *score == best)
+        .map(|(_, m)| m)
+        .collect();
+    moves.choose().copied()
+}
+
+/// 1-ply agent that takes whatever material it can, and mates if it can
+fn greedy_agent(board: &Board) -> Option<(Loc, Loc)> {
+    let color = board.turn;
+    let scored = board
+        .moves(color)
+        .into_iter()
+        .map(|(from, to)| {
+            let mut test_board = board.clone();
+            test_board.move_piece(&from, &to, false);
+            let score = ternary!(
+                test_board.state == BoardState::Checkmate(color.other()),
+                MAX,
+                material(&test_board, color)
+            );
+            (score, (from, to))
+        })
+        .collect();
+    best_moves(scored)
+}
 
-    // Base case
+/// Minimax without any pruning or tables, returns the score for white
+fn plain_minimax(board: &Board, depth: u8) -> i32 {
     if depth == 0 || board.is_over() {
-        return (board.score, None);
+        return board.score;
     }
 
-    if !antimax {
-        // Very first move
-        if board.full_moves() == 0 && board.agent_color == ChessColor::Black {
-            macro_rules! responses {
-                ($($key:expr => $value:expr,)+) => { responses!($($key => $value),+) };
-                ($($key:expr => $value:expr),*) => {
-                    $(
-                        if let Some(piece) = board.get(&Loc::from_notation($key.1)) {
-                            if piece.name == $key.0 {
-                                let m = choose_array(&$value);
-                                info!("First move found!");
-                                return (MAX, Some((Loc::from_notation(m.0), Loc::from_notation(m.1))));
-                            }
-                        }
-                    )*
-                };
-            }
+    let scores = board.moves(board.turn).into_iter().map(|(from, to)| {
+        let mut test_board = board.clone();
+        test_board.move_piece(&from, &to, false);
+        plain_minimax(&test_board, depth - 1)
+    });
+    color_ternary!(board.turn, scores.max(), scores.min()).unwrap_or(board.score)
+}
 
-            responses! {
-                // e4 -> e5, e6, c5
-                (PieceNames::Pawn, "e4") => [("e7", "e5"), ("e7", "e6"), ("c7", "c5")],
-                // d4 -> d5, c6, Nf6, Nc6
-                (PieceNames::Pawn, "d4") => [("d7", "d5"), ("g8", "f6"), ("b8", "c6")],
-                // c4 -> e5, Nf6
-                (PieceNames::Pawn, "c4") => [("e7", "e5"), ("g8", "f6")],
-                // Nf3 -> e5, Nf6
-                (PieceNames::Knight, "f3") => [("e7", "e5"), ("g8", "f6")],
-            };
+/// Agent that looks a fixed number of plies ahead, so it's beatable in a predictable way
+fn fixed_depth_agent(board: &Board, depth: u8) -> Option<(Loc, Loc)> {
+    let color = board.turn;
+    let scored = board
+        .moves(color)
+        .into_iter()
+        .map(|(from, to)| {
+            let mut test_board = board.clone();
+            test_board.move_piece(&from, &to, false);
+            let score = plain_minimax(&test_board, depth - 1);
+            (color_ternary!(color, score, -score), (from, to))
+        })
+        .collect();
+    best_moves(scored)
+}
+
+/// Static score, for the side to move, past which it shouldn't settle for shuffling pieces
+const AHEAD_SCORE: i32 = 150;
+/// Taken off moves that undo the side's last move or repeat a position, while ahead
+const SHUFFLE_PENALTY: i32 = 30;
+
+/// Penalty, for the side to move, for a move that undoes its last one or repeats a position
+/// - Only while ahead, since repeating is fine when a draw is welcome
+/// - `after` is `board` with the move played
+pub(crate) fn shuffle_penalty(board: &Board, after: &Board, from: &Loc, to: &Loc) -> i32 {
+    let color = board.turn;
+    if color_ternary!(color, board.score, -board.score) < AHEAD_SCORE {
+        return 0;
+    }
+
+    let last = color_ternary!(color, board.last_moves.0, board.last_moves.1);
+    let reversed = last == Some((*to, *from));
+    let repeated = after
+        .prev_states
+        .iter()
+        .filter(|hash| **hash == after.hash)
+        .count()
+        >= 2;
+    ternary!(reversed || repeated, SHUFFLE_PENALTY, 0)
+}
+
+/// Score reported for book moves
+pub(crate) const MAX: i32 = i32::MAX - 1;
+const TIMEOUT_SCORE: i32 = i32::MAX - 2;
+
+/// What a search agent reports about its deepest completed search
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SearchInfo {
+    /// `0` for book moves
+    pub(crate) depth: u8,
+    /// Score for white, [MAX] for book moves
+    pub(crate) score: i32,
+    /// Principal variation, starting with the move played
+    pub(crate) pv: Vec<(Loc, Loc)>,
+    /// Positions searched to finish this depth, `0` for book moves
+    pub(crate) nodes: u64,
+    /// Positions in the transposition table once this depth finished, `0` for book moves
+    pub(crate) tt_entries: usize,
+    /// Scores for white and principal variations of the next best moves, best first, see
+    /// [SearchLimits::multi_pv]
+    pub(crate) alternatives: Vec<(i32, Vec<(Loc, Loc)>)>,
+    /// Root moves in the order they were searched, empty unless [SearchLimits::search_tree] is on
+    /// - A boxed slice, since it's rarely used and every [Reply] carries it
+    pub(crate) tree: Box<[RootMove]>,
+}
+
+/// How a move at the root of the search was searched, to see how well the search prunes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct RootMove {
+    pub(crate) mov: (Loc, Loc),
+    /// Score for white
+    pub(crate) score: i32,
+    /// Positions searched under the move
+    pub(crate) nodes: u64,
+    /// (searched, legal) replies to the move
+    pub(crate) replies: (usize, usize),
+    /// Reply that caused a cutoff, so the replies after it weren't searched
+    pub(crate) cutoff: Option<(Loc, Loc)>,
+    /// Wether the position after the move was scored from the transposition table instead of searched
+    pub(crate) transposition: bool,
+}
+
+/// What an agent sends while picking its move, IE over [Game::agent_channel](crate::game::Game::agent_channel)
+/// - Ends with the move, resigning, or an error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum AgentMessage {
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    BestMove(Loc, Loc),
+    /// A depth of the search finished, or the book move as depth `0`
+    Info {
+        depth: u8,
+        /// For white
+        score: i32,
+        pv: Vec<(Loc, Loc)>,
+        nodes: u64,
+        tt_entries: usize,
+        alternatives: Vec<(i32, Vec<(Loc, Loc)>)>,
+        tree: Box<[RootMove]>,
+    },
+    /// The move is from the opening database
+    BookHit { name: String },
+    /// The agent gives up instead of moving
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    Resign,
+    /// The agent couldn't pick a move, or crashed trying to
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    Error(String),
+}
+impl AgentMessage {
+    /// Depth reported by [AgentMessage::Info]
+    #[cfg(feature = "gui")]
+    pub(crate) fn info(&self) -> Option<SearchInfo> {
+        match self {
+            AgentMessage::Info {
+                depth,
+                score,
+                pv,
+                nodes,
+                tt_entries,
+                alternatives,
+                tree,
+            } => Some(SearchInfo {
+                depth: *depth,
+                score: *score,
+                pv: pv.clone(),
+                nodes: *nodes,
+                tt_entries: *tt_entries,
+                alternatives: alternatives.clone(),
+                tree: tree.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+impl From<SearchInfo> for AgentMessage {
+    fn from(info: SearchInfo) -> AgentMessage {
+        AgentMessage::Info {
+            depth: info.depth,
+            score: info.score,
+            pv: info.pv,
+            nodes: info.nodes,
+            tt_entries: info.tt_entries,
+            alternatives: info.alternatives,
+            tree: info.tree,
         }
+    }
+}
+
+/// Limits on how far search based agents search
+#[derive(Clone, Debug, new)]
+pub(crate) struct SearchLimits {
+    /// Seconds the search can take, [f64::INFINITY] to search until stopped
+    pub(crate) max_time: f64,
+    /// Deepest depth searched, `None` to keep deepening until time runs out
+    #[new(value = "None")]
+    pub(crate) max_depth: Option<u8>,
+    /// Set to stop the search, which keeps the deepest depth it finished
+    #[new(value = "None")]
+    pub(crate) stop: Option<Arc<AtomicBool>>,
+    /// Sent [AgentMessage::Info] each depth as it finishes, IE to show the search progressing, and
+    /// [AgentMessage::BookHit] for book moves
+    #[new(value = "None")]
+    pub(crate) progress: Option<Sender<AgentMessage>>,
+    /// Moves scored exactly at the root, the best one and `multi_pv - 1` alternatives, IE to show what else the
+    /// agent considered
+    /// - Above `1` the root prunes less, so each depth takes longer
+    #[new(value = "1")]
+    pub(crate) multi_pv: usize,
+    /// Wether each depth records how its root moves were searched, see [SearchInfo::tree]
+    #[new(value = "false")]
+    pub(crate) search_tree: bool,
+}
+impl SearchLimits {
+    pub(crate) fn stopped(&self) -> bool {
+        self.stop
+            .as_ref()
+            .is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+}
 
-        // Openings
-        if let Some(moves) = OPENINGS.get(&board.hash) {
-            let (opening, name) = choose_array(moves);
-            info!("Opening found! {}", name);
-            return (MAX, Some(*opening));
+/// Moves in a row the agent has to be past the resign score before resigning
+#[cfg(feature = "gui")]
+const RESIGN_MOVES: u32 = 3;
+/// Score, either way, of an endgame the agent considers dead equal
+#[cfg(feature = "gui")]
+const DRAW_SCORE: i32 = 30;
+/// [DRAW_SCORE] of endgames known to be drawish, since their scaled down scores can still show an edge
+#[cfg(feature = "gui")]
+const DRAWISH_SCORE: i32 = 80;
+/// Moves in a row of a dead equal endgame before the agent offers a draw, and between offers
+#[cfg(feature = "gui")]
+const DRAW_OFFER_MOVES: u32 = 5;
+
+/// What the agent wants to do instead of, or along with, its next move
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Proposal {
+    Resign,
+    OfferDraw,
+}
+
+/// Tracks the agent's scores across moves, to decide when to resign or offer a draw
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Outlook {
+    /// Moves in a row the agent has been past the resign score
+    pub(crate) hopeless: u32,
+    /// Moves in a row of a dead equal endgame since the last draw offer
+    pub(crate) equal: u32,
+    /// Latest score, for the agent
+    pub(crate) score: Option<i32>,
+}
+#[cfg(feature = "gui")]
+impl Outlook {
+    /// How close to equal the agent's score has to be for it to want a draw, `None` outside of endgames
+    #[cfg(feature = "gui")]
+    pub(crate) fn draw_score(board: &Board) -> Option<i32> {
+        match (board.endgame_class(), board.phase()) {
+            (Some(_), _) => Some(DRAWISH_SCORE),
+            (None, Phase::Endgame) => Some(DRAW_SCORE),
+            (None, _) => None,
         }
     }
 
-    // Check if the current board state is already stored in the transposition table
-    let stored_data = trans_table.get(&board.hash);
-    let mut greater_depth = false;
-    if let Some((stored_depth, stored_score, stored_best)) = stored_data {
-        if stored_depth >= &depth {
-            return (*stored_score, *stored_best);
+    /// Records the score of the agent's latest search, for the agent
+    /// - `draw_score` is from [Outlook::draw_score]
+    /// - `resign_score` is how far behind the agent resigns at, `None` to never resign
+    #[cfg(feature = "gui")]
+    pub(crate) fn update(
+        &mut self,
+        score: i32,
+        draw_score: Option<i32>,
+        resign_score: Option<i32>,
+    ) -> Option<Proposal> {
+        self.score = Some(score);
+
+        self.hopeless = ternary!(
+            resign_score.is_some_and(|resign| score <= -resign),
+            self.hopeless + 1,
+            0
+        );
+        if self.hopeless >= RESIGN_MOVES {
+            return Some(Proposal::Resign);
+        }
+
+        self.equal = ternary!(
+            draw_score.is_some_and(|draw| score.abs() <= draw),
+            self.equal + 1,
+            0
+        );
+        if self.equal >= DRAW_OFFER_MOVES {
+            self.equal = 0;
+            return Some(Proposal::OfferDraw);
         }
-        greater_depth = true;
+        None
     }
 
-    // Get the sorted legal moves for the current turn
-    let moves = color_ternary!(
-        board.turn,
-        board.sorted_moves(ChessColor::White),
-        board.sorted_moves(ChessColor::Black)
-    );
+    /// Wether the agent takes a draw offered by the player, when the endgame is equal or it's worse off
+    #[cfg(feature = "gui")]
+    pub(crate) fn accepts_draw(&self, draw_score: Option<i32>) -> bool {
+        draw_score.is_some_and(|draw| self.score.is_some_and(|score| score <= draw))
+    }
+}
 
-    let mut best_score = ternary!(maximizing, i32::MIN, i32::MAX);
-    let mut best_move = None;
+/// Score, best move, and principal variation of a searched position
+type Searched = (i32, Option<(Loc, Loc)>, Vec<(Loc, Loc)>);
 
-    // Iterate through the moves and apply minimax
-    for (from, to) in moves.iter() {
-        let mut test_board = board.clone();
-        test_board.move_piece(from, to, false);
+/// Position on [Minimax]'s stack, with the moves it has left to search
+#[derive(Clone)]
+struct Node {
+    board: Board,
+    maximizing: bool,
+    depth: u8,
+    alpha: i32,
+    beta: i32,
+    /// Sorted legal moves, empty for leaves and transposition table hits
+    moves: Vec<(Loc, Loc)>,
+    /// Index into [Node::moves] of the move being searched
+    next: usize,
+    best_score: i32,
+    best_move: Option<(Loc, Loc)>,
+    pv: Vec<(Loc, Loc)>,
+    /// Wether the transposition table had this position at a shallower depth, which this search replaces
+    greater_depth: bool,
+    /// [Minimax::nodes] before this position, to count the positions searched under it
+    first_node: u64,
+    /// Index into [Node::moves] of the move that caused a cutoff
+    cutoff: Option<usize>,
+}
+
+type TransTable = FxHashMap<u64, (u8, i32, Option<(Loc, Loc)>)>;
 
-        let (score, _) = minimax(
-            &test_board,
-            !maximizing,
-            depth - 1,
+/// Minimax search to one depth with alpha-beta pruning and sorted move ordering
+/// - Searches with its own stack instead of recursing, so it can pause between positions and resume later, IE to
+///   search a slice each frame on the web
+#[derive(Clone)]
+pub(crate) struct Minimax {
+    stack: Vec<Node>,
+    antimax: bool,
+    eval: EvalBackend,
+    /// Positions searched so far
+    nodes: u64,
+    /// Lines kept at the root, see [SearchLimits::multi_pv]
+    multi_pv: usize,
+    /// Score and principal variation of the best root moves so far, best first, when [Minimax::multi_pv] is above `1`
+    lines: Vec<(i32, Vec<(Loc, Loc)>)>,
+    /// Root moves searched so far, `None` unless [SearchLimits::search_tree] is on
+    tree: Option<Vec<RootMove>>,
+}
+impl Minimax {
+    /// Starts searching `board` to `depth`
+    /// - `prefer` are moves tried first so they win ties, IE ones transposing into the book
+    fn new(
+        board: &Board,
+        depth: u8,
+        antimax: bool,
+        eval: EvalBackend,
+        prefer: &[(Loc, Loc)],
+        trans_table: &TransTable,
+        multi_pv: usize,
+    ) -> Minimax {
+        let mut search = Minimax {
+            stack: vec![],
+            antimax,
+            eval,
+            nodes: 0,
+            multi_pv,
+            lines: vec![],
+            tree: None,
+        };
+        let maximizing = (board.turn == ChessColor::White) != antimax;
+        let root = search.enter(
+            board.clone(),
+            maximizing,
+            depth,
+            (i32::MIN, i32::MAX),
+            prefer,
+            trans_table,
+        );
+        search.stack.push(root);
+        search
+    }
+
+    /// Scores `board` if it's a leaf or already in the transposition table, otherwise sorts its moves to search
+    fn enter(
+        &mut self,
+        board: Board,
+        maximizing: bool,
+        depth: u8,
+        (alpha, beta): (i32, i32),
+        prefer: &[(Loc, Loc)],
+        trans_table: &TransTable,
+    ) -> Node {
+        assert_eq!(
+            board.turn,
+            ternary!(
+                maximizing != self.antimax,
+                ChessColor::White,
+                ChessColor::Black
+            )
+        );
+        let mut node = Node {
+            board,
+            maximizing,
+            depth,
             alpha,
             beta,
-            trans_table,
-            start_time,
-            antimax,
+            moves: vec![],
+            next: 0,
+            best_score: ternary!(maximizing, i32::MIN, i32::MAX),
+            best_move: None,
+            pv: vec![],
+            greater_depth: false,
+            first_node: self.nodes,
+            cutoff: None,
+        };
+        self.nodes += 1;
+        // Half moves from the root, mates are scored by how far away they are
+        let ply = self.stack.len() as i32;
+
+        // Base case
+        if depth == 0 || node.board.is_over() {
+            node.best_score = mate_from_root(self.eval.evaluate(&node.board), ply);
+            return node;
+        }
+
+        // Check if the current board state is already stored in the transposition table
+        if let Some((stored_depth, stored_score, stored_best)) = trans_table.get(&node.board.tt_key()) {
+            if *stored_depth >= depth {
+                node.best_score = mate_from_root(*stored_score, ply);
+                node.best_move = *stored_best;
+                node.pv.extend(*stored_best);
+                return node;
+            }
+            node.greater_depth = true;
+        }
+
+        // Get the sorted legal moves for the current turn
+        node.moves = node.board.sorted_moves(node.board.turn);
+        // Stable, so the rest keep their order
+        node.moves.sort_by_key(|mov| !prefer.contains(mov));
+        // Mated or stalemated, which the moves played in the search don't look for
+        if node.moves.is_empty() {
+            node.best_score = mate_from_root(node.board.no_moves_score(), ply);
+        }
+        node
+    }
+
+    /// Searches until finished, returning the score for white, best move, and principal variation, or `None` if it
+    /// paused at `pause_at` (from [now]) first
+    /// - Gives up with [TIMEOUT_SCORE] once past `deadline`, or stopped
+    fn run(
+        &mut self,
+        trans_table: &mut TransTable,
+        deadline: f64,
+        limits: &SearchLimits,
+        pause_at: f64,
+    ) -> Option<Searched> {
+        loop {
+            let node = self.stack.last().unwrap();
+
+            // Finished with this position, so hand its score to the one before it
+            if node.next == node.moves.len() {
+                let node = self.stack.pop().unwrap();
+                // Store the data in the transposition table, with mates counted from this position
+                if node.greater_depth {
+                    let ply = self.stack.len() as i32;
+                    trans_table.insert(
+                        node.board.tt_key(),
+                        (node.depth, mate_from_root(node.best_score, -ply), node.best_move),
+                    );
+                }
+                if self.stack.is_empty() {
+                    return Some((node.best_score, node.best_move, node.pv));
+                }
+
+                // Break if taking too long, or stopped
+                if now() > deadline || limits.stopped() {
+                    self.stack.clear();
+                    return Some((TIMEOUT_SCORE, None, vec![]));
+                }
+                self.update_parent(node);
+                continue;
+            }
+
+            let (from, to) = node.moves[node.next];
+            let mut test_board = node.board.clone();
+            test_board.move_piece(&from, &to, false);
+            let child = self.enter(
+                test_board,
+                !node.maximizing,
+                node.depth - 1,
+                (node.alpha, node.beta),
+                &[],
+                trans_table,
+            );
+            self.stack.push(child);
+
+            if now() >= pause_at {
+                return None;
+            }
+        }
+    }
+
+    /// Scores the move to `child` for the position before it, moving on to its next move
+    fn update_parent(&mut self, mut child: Node) {
+        let root = self.stack.len() == 1;
+        let parent = self.stack.last_mut().unwrap();
+        let (from, to) = parent.moves[parent.next];
+
+        let penalty = shuffle_penalty(&parent.board, &child.board, &from, &to);
+        let score = color_ternary!(
+            parent.board.turn,
+            child.best_score.saturating_sub(penalty),
+            child.best_score.saturating_add(penalty)
         );
 
-        if score == MAX {
-            return (score, Some((*from, *to)));
+        // Keeps the best few root moves, and only prunes below the worst of them so their scores are exact
+        let mut bound = score;
+        if root && self.multi_pv > 1 {
+            let mut pv = vec![(from, to)];
+            pv.extend(child.pv.iter().copied());
+            let maximizing = parent.maximizing;
+            let index = self.lines.partition_point(|(line_score, _)| {
+                ternary!(maximizing, *line_score >= score, *line_score <= score)
+            });
+            self.lines.insert(index, (score, pv));
+            self.lines.truncate(self.multi_pv);
+            bound = ternary!(
+                self.lines.len() == self.multi_pv,
+                self.lines.last().unwrap().0,
+                ternary!(maximizing, i32::MIN, i32::MAX)
+            );
         }
 
-        // Break if taking too long
-        if get_time() - start_time > MAX_TIME {
-            return (TIMEOUT_SCORE, None);
+        if root {
+            if let Some(tree) = &mut self.tree {
+                let searched = child.cutoff.map_or(child.moves.len(), |i| i + 1);
+                tree.push(RootMove {
+                    mov: (from, to),
+                    score,
+                    nodes: self.nodes - child.first_node,
+                    replies: (searched, child.moves.len()),
+                    cutoff: child.cutoff.map(|i| child.moves[i]),
+                    transposition: child.depth > 0
+                        && child.moves.is_empty()
+                        && !child.board.is_over(),
+                });
+            }
         }
 
         // Update the best score and best move
-        if ternary!(maximizing, score > best_score, score < best_score) {
-            best_score = score;
-            best_move = Some((*from, *to));
+        if ternary!(
+            parent.maximizing,
+            score > parent.best_score,
+            score < parent.best_score
+        ) {
+            parent.best_score = score;
+            parent.best_move = Some((from, to));
+            parent.pv.clear();
+            parent.pv.push((from, to));
+            parent.pv.append(&mut child.pv);
         }
 
         // Update alpha and beta
-        if maximizing {
-            alpha = alpha.max(score);
+        if parent.maximizing {
+            parent.alpha = parent.alpha.max(bound);
         } else {
-            beta = beta.min(score);
+            parent.beta = parent.beta.min(bound);
         }
 
         // Prune the search if alpha is greater than or equal to beta
-        if alpha >= beta {
-            break;
+        if parent.alpha >= parent.beta {
+            parent.cutoff = Some(parent.next);
+            parent.next = parent.moves.len();
+        } else {
+            parent.next += 1;
         }
     }
+}
 
-    // Store the data in the transposition table
-    if greater_depth {
-        trans_table.insert(board.hash, (depth, best_score, best_move));
-    }
-    (best_score, best_move)
+/// Minimax to a fixed depth with no time limit, returns (score for white, best move)
+/// - Used headlessly, IE by self-play
+pub(crate) fn search_depth(board: &Board, depth: u8) -> (i32, Option<(Loc, Loc)>) {
+    let mut trans_table = hashmap! {};
+    let mut search = Minimax::new(
+        board,
+        depth,
+        false,
+        EvalBackend::Classic,
+        &[],
+        &trans_table,
+        1,
+    );
+    let limits = SearchLimits::new(f64::INFINITY);
+    let (score, best, _) = search
+        .run(&mut trans_table, f64::INFINITY, &limits, f64::INFINITY)
+        .unwrap();
+    (score, best)
 }
 
-const MAX_TIME: f64 = 4.0;
+/// Whether the position is in the opening database, which is only for standard chess
+/// - Fog of war's openings are the same, since a king can't be taken that early
+pub(crate) fn in_book(board: &Board) -> bool {
+    matches!(board.variant, Variant::Standard | Variant::FogOfWar)
+        && openings().contains_key(&board.hash)
+}
 
-/// Wrapper for minimax, using iterative deepening
-fn minimax_agent(board: &Board, antimax: bool) -> Option<(Loc, Loc)> {
-    if board.is_over() {
+/// Moves leading back into the opening database from a position that's out of it
+pub(crate) fn book_transpositions(board: &Board) -> Vec<(Loc, Loc)> {
+    board
+        .moves(board.turn)
+        .into_iter()
+        .filter(|(from, to)| {
+            let mut after = board.clone();
+            after.move_piece(from, to, false);
+            in_book(&after)
+        })
+        .collect()
+}
+
+/// Random move from the opening database for the side to move, and its opening, if the position is in it
+/// - Positions are keyed without the side to move, so moves that aren't legal here are skipped
+fn book_move(board: &Board) -> Option<((Loc, Loc), String)> {
+    if !in_book(board) {
         return None;
     }
+    let legal = board.moves(board.turn);
+    let openings = openings();
+    let moves: Vec<_> = openings
+        .get(&board.hash)?
+        .iter()
+        .filter(|(mov, _)| legal.contains(mov))
+        .collect();
+    let (book, name) = moves.choose()?;
+    log_book_hit(name);
+    Some((*book, name.clone()))
+}
 
-    let mut trans_table = hashmap! {};
-    let start_time = get_time();
-    let mut last_time = start_time;
+/// What an agent picked, and what search based agents found
+pub(crate) type Reply = (Option<(Loc, Loc)>, Option<SearchInfo>);
 
-    let mut best_move = None;
-    let mut i = 0;
-    loop {
-        i += 1;
+/// What [MovePicker::best_move] came back with
+pub(crate) enum AgentResult {
+    /// Search still to run, a slice at a time with [Thinking]
+    Searching(Box<Deepening>),
+    /// Move already picked, or `None` if it didn't pick one
+    Picked(Reply),
+    /// Reason it couldn't pick a move, IE an external engine that didn't start
+    Failed(String),
+}
 
-        let (score, bm) = minimax(
-            board,
-            antimax,
-            i,
-            i32::MIN,
-            i32::MAX,
-            &mut trans_table,
-            start_time,
-            antimax,
-        );
+/// How an agent picks its moves, registered by name in [AGENTS]
+/// - Registered once and shared between threads, so it can keep things between moves, IE an external engine's
+///   process
+pub(crate) trait MovePicker: Send + Sync {
+    /// Starts picking a move for the side to move of `board`, `limits` are only used by agents that search
+    fn best_move(&self, board: &Board, limits: &SearchLimits) -> AgentResult;
 
-        let last_took = get_time() - last_time;
-        last_time = get_time();
-        let time_took = get_time() - start_time;
+    /// Guessed rating searching to `max_depth`, or by time at `difficulty`, `None` if it doesn't play on its own or
+    /// could be anything, see [rating](crate::rating)
+    #[cfg(feature = "gui")]
+    fn rating(&self, _max_depth: Option<u8>, _difficulty: Difficulty) -> Option<f64> {
+        None
+    }
 
-        if time_took > MAX_TIME || score == TIMEOUT_SCORE {
-            info!(" - Timeout at depth {}", i);
-            break;
-        }
+    /// Wether how strong it plays depends on the depth and difficulty it's given
+    #[cfg(feature = "gui")]
+    fn searches(&self) -> bool {
+        false
+    }
+}
 
-        if time_took + last_took * 2.0 > MAX_TIME {
-            info!(" - Last time timeout at depth {}", i);
-            break;
+/// [start_minimax] as a [MovePicker]
+#[derive(new)]
+struct MinimaxPicker {
+    antimax: bool,
+    eval: EvalBackend,
+}
+impl MovePicker for MinimaxPicker {
+    fn best_move(&self, board: &Board, limits: &SearchLimits) -> AgentResult {
+        if self.eval == EvalBackend::Nnue {
+            warn_if_missing();
         }
+        start_minimax(board, self.antimax, limits, self.eval)
+    }
 
-        info!("Depth: {} took {:.3}s (total: {:.3}s)", i, last_took, time_took);
+    #[cfg(feature = "gui")]
+    fn rating(&self, max_depth: Option<u8>, difficulty: Difficulty) -> Option<f64> {
+        let bonus = ternary!(self.eval == EvalBackend::Nnue, 100.0, 0.0);
+        Some(ternary!(
+            self.antimax,
+            100.0,
+            searched_rating(max_depth, difficulty) + bonus
+        ))
+    }
 
-        best_move = bm;
-        if score == MAX {
-            break;
+    #[cfg(feature = "gui")]
+    fn searches(&self) -> bool {
+        !self.antimax
+    }
+}
+
+/// Agents that pick their move straight away without searching, IE [greedy_agent]
+#[derive(new)]
+struct InstantPicker {
+    pick: fn(&Board) -> Option<(Loc, Loc)>,
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    rating: Option<f64>,
+}
+impl MovePicker for InstantPicker {
+    fn best_move(&self, board: &Board, _limits: &SearchLimits) -> AgentResult {
+        AgentResult::Picked(((self.pick)(board), None))
+    }
+
+    #[cfg(feature = "gui")]
+    fn rating(&self, _max_depth: Option<u8>, _difficulty: Difficulty) -> Option<f64> {
+        self.rating
+    }
+}
+
+/// Starts the minimax agent, using iterative deepening
+/// - Already finished for finished games, and moves from the opening database, which it plays while it can except
+///   for antimax which is trying to lose
+/// - Once out of it, moves transposing back in are preferred when they score the same as the best one
+fn start_minimax(
+    board: &Board,
+    antimax: bool,
+    limits: &SearchLimits,
+    eval: EvalBackend,
+) -> AgentResult {
+    if board.is_over() {
+        return AgentResult::Picked((None, None));
+    }
+    if !antimax {
+        if let Some((book, name)) = book_move(board) {
+            let info = SearchInfo {
+                depth: 0,
+                score: MAX,
+                pv: vec![book],
+                nodes: 0,
+                tt_entries: 0,
+                alternatives: vec![],
+                tree: Box::default(),
+            };
+            if let Some(progress) = &limits.progress {
+                progress.send(AgentMessage::BookHit { name }).ok();
+                progress.send(info.clone().into()).ok();
+            }
+            return AgentResult::Picked((Some(book), Some(info)));
         }
     }
 
-    best_move
+    let transpositions = ternary!(antimax, vec![], book_transpositions(board));
+    let trans_table = hashmap! {};
+    let mut search = Minimax::new(
+        board,
+        1,
+        antimax,
+        eval,
+        &transpositions,
+        &trans_table,
+        limits.multi_pv,
+    );
+    search.tree = limits.search_tree.then(Vec::new);
+    AgentResult::Searching(Box::new(Deepening {
+        board: board.clone(),
+        antimax,
+        eval,
+        limits: limits.clone(),
+        transpositions,
+        trans_table,
+        search,
+        depth: 1,
+        start_time: now(),
+        last_time: now(),
+        paused_at: None,
+        best_move: None,
+        search_info: None,
+    }))
+}
+
+/// Iterative deepening of [Minimax] for the minimax agent, see [start_minimax]
+#[derive(Clone)]
+pub(crate) struct Deepening {
+    board: Board,
+    antimax: bool,
+    eval: EvalBackend,
+    limits: SearchLimits,
+    transpositions: Vec<(Loc, Loc)>,
+    trans_table: TransTable,
+    /// Search of the depth being deepened to
+    search: Minimax,
+    depth: u8,
+    start_time: f64,
+    /// When the last depth finished
+    last_time: f64,
+    /// When [Deepening::run] last paused, time spent paused doesn't count towards the time limit
+    paused_at: Option<f64>,
+    best_move: Option<(Loc, Loc)>,
+    search_info: Option<SearchInfo>,
+}
+impl Deepening {
+    /// Searches until out of time or past the deepest depth, or `None` if it paused at `pause_at` (from [now]) first
+    fn run(&mut self, pause_at: f64) -> Option<Reply> {
+        if let Some(paused_at) = self.paused_at.take() {
+            let paused = now() - paused_at;
+            self.start_time += paused;
+            self.last_time += paused;
+        }
+
+        let max_time = self.limits.max_time;
+        let max_depth = self.limits.max_depth.unwrap_or(u8::MAX);
+        loop {
+            let Some((score, bm, pv)) = self.search.run(
+                &mut self.trans_table,
+                self.start_time + max_time,
+                &self.limits,
+                pause_at,
+            ) else {
+                self.paused_at = Some(now());
+                return None;
+            };
+
+            let last_took = now() - self.last_time;
+            self.last_time = now();
+            let time_took = now() - self.start_time;
+
+            if time_took > max_time || score == TIMEOUT_SCORE || self.limits.stopped() {
+                info!(" - Timeout at depth {}", self.depth);
+                break;
+            }
+
+            if time_took + last_took * 2.0 > max_time {
+                info!(" - Last time timeout at depth {}", self.depth);
+                break;
+            }
+
+            info!(
+                "Depth: {} took {:.3}s (total: {:.3}s)",
+                self.depth, last_took, time_took
+            );
+
+            self.best_move = bm;
+            let info = SearchInfo {
+                depth: self.depth,
+                score,
+                pv,
+                nodes: self.search.nodes,
+                tt_entries: self.trans_table.len(),
+                alternatives: self
+                    .search
+                    .lines
+                    .iter()
+                    .filter(|(_, line)| line.first() != bm.as_ref())
+                    .cloned()
+                    .collect(),
+                tree: self
+                    .search
+                    .tree
+                    .take()
+                    .unwrap_or_default()
+                    .into_boxed_slice(),
+            };
+            if let Some(progress) = &self.limits.progress {
+                progress.send(info.clone().into()).ok();
+            }
+            self.search_info = Some(info);
+            if self.depth >= max_depth {
+                break;
+            }
+
+            self.depth += 1;
+            self.search = Minimax::new(
+                &self.board,
+                self.depth,
+                self.antimax,
+                self.eval,
+                &self.transpositions,
+                &self.trans_table,
+                self.limits.multi_pv,
+            );
+            self.search.tree = self.limits.search_tree.then(Vec::new);
+        }
+
+        Some((self.best_move, self.search_info.take()))
+    }
 }
 
-/// List of agents for [Board] to use
+/// An agent, by its name in [AGENTS]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct AgentId(&'static str);
+impl AgentId {
+    pub(crate) const MINIMAX: AgentId = AgentId("Minimax");
+    pub(crate) const ANTIMAX: AgentId = AgentId("Antimax");
+    pub(crate) const CONTROL: AgentId = AgentId("Control");
+    pub(crate) const RANDOM: AgentId = AgentId("Random");
+    pub(crate) const GREEDY: AgentId = AgentId("Greedy");
+    pub(crate) const FIXED_DEPTH: AgentId = AgentId("Depth 2");
+    pub(crate) const NNUE: AgentId = AgentId("NNUE");
+    pub(crate) const MCTS: AgentId = AgentId("MCTS");
+    /// External UCI engine
+    pub(crate) const ENGINE: AgentId = AgentId("Engine");
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn name(self) -> &'static str {
+        self.0
+    }
+
+    fn registered(self) -> Option<&'static Registered> {
+        AGENTS.iter().find(|registered| registered.id == self)
+    }
+
+    /// Starts the agent's search, or its move if it doesn't search or already knows it, see [AgentId::get_move]
+    fn start(&self, board: &Board, limits: &SearchLimits) -> AgentResult {
+        match self.registered() {
+            Some(registered) => registered.picker.best_move(board, limits),
+            None => AgentResult::Failed(format!("{} isn't available in this build", self)),
+        }
+    }
+
+    /// Get the agent's move, and what search based agents found
+    /// - `limits` are only used by search based agents
+    pub(crate) fn get_move(&self, board: &Board, limits: &SearchLimits) -> Reply {
+        match self.start(board, limits) {
+            AgentResult::Searching(mut search) => search.run(f64::INFINITY).unwrap(),
+            AgentResult::Picked(reply) => reply,
+            AgentResult::Failed(reason) => {
+                warn!("{}", reason);
+                (None, None)
+            }
+        }
+    }
+
+    /// Picks the agent's move like [AgentId::get_move], sending what it finds to `messages` as it goes
+    /// - `resign` is a copy of the agent's [Outlook] and how far behind it resigns at, `None` if it can't resign
+    #[cfg(feature = "gui")]
+    #[cfg_attr(target_family = "wasm", allow(dead_code))]
+    pub(crate) fn think(
+        &self,
+        board: &Board,
+        limits: SearchLimits,
+        resign: Option<(Outlook, Option<i32>)>,
+        messages: Sender<AgentMessage>,
+    ) {
+        Thinking::new(*self, board, limits, resign, messages).step(f64::INFINITY);
+    }
+
+    /// See [MovePicker::rating]
+    #[cfg(feature = "gui")]
+    pub(crate) fn rating(self, max_depth: Option<u8>, difficulty: Difficulty) -> Option<f64> {
+        self.registered()?.picker.rating(max_depth, difficulty)
+    }
+
+    /// See [MovePicker::searches]
+    #[cfg(feature = "gui")]
+    pub(crate) fn searches(self) -> bool {
+        self.registered()
+            .is_some_and(|registered| registered.picker.searches())
+    }
+}
+impl Display for AgentId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+impl Serialize for AgentId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (saved, _) = Agent::IDS
+            .iter()
+            .find(|(_, id)| id == self)
+            .ok_or_else(|| S::Error::custom(format!("{} can't be saved", self)))?;
+        saved.serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for AgentId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<AgentId, D::Error> {
+        Agent::deserialize(deserializer).map(AgentId::from)
+    }
+}
+
+/// How an [AgentId] is saved, IE in autosaves, kept from when agents were picked by this enum so older saves still
+/// load
+/// - Agents registered in [AGENTS] need a variant here to be saved
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum Agent {
     Minimax,
     Antimax,
     Control,
     Random,
+    Greedy,
+    FixedDepth,
+    Nnue,
+    Mcts,
+    Engine,
 }
 impl Agent {
-    pub(crate) fn get_move(&self, board: &Board) -> Option<(Loc, Loc)> {
-        match self {
-            Agent::Minimax => minimax_agent(board, false),
-            Agent::Antimax => minimax_agent(board, true),
-            Agent::Random => random_agent(board),
-            Agent::Control => None,
+    /// Every saved agent, and the agent it's saved for
+    const IDS: [(Agent, AgentId); 9] = [
+        (Agent::Minimax, AgentId::MINIMAX),
+        (Agent::Antimax, AgentId::ANTIMAX),
+        (Agent::Control, AgentId::CONTROL),
+        (Agent::Random, AgentId::RANDOM),
+        (Agent::Greedy, AgentId::GREEDY),
+        (Agent::FixedDepth, AgentId::FIXED_DEPTH),
+        (Agent::Nnue, AgentId::NNUE),
+        (Agent::Mcts, AgentId::MCTS),
+        (Agent::Engine, AgentId::ENGINE),
+    ];
+}
+impl From<Agent> for AgentId {
+    fn from(agent: Agent) -> AgentId {
+        Agent::IDS.iter().find(|(saved, _)| *saved == agent).unwrap().1
+    }
+}
+
+/// The agent picking its move a slice at a time, see [AgentId::think]
+/// - Used on the web, where there aren't threads to think on
+#[cfg(feature = "gui")]
+#[derive(Clone)]
+pub(crate) struct Thinking {
+    agent: AgentId,
+    board: Board,
+    limits: SearchLimits,
+    resign: Option<(Outlook, Option<i32>)>,
+    messages: Sender<AgentMessage>,
+    /// `None` until the first slice
+    search: Option<Box<Deepening>>,
+}
+#[cfg(feature = "gui")]
+impl Thinking {
+    #[cfg(feature = "gui")]
+    pub(crate) fn new(
+        agent: AgentId,
+        board: &Board,
+        mut limits: SearchLimits,
+        resign: Option<(Outlook, Option<i32>)>,
+        messages: Sender<AgentMessage>,
+    ) -> Thinking {
+        limits.progress = Some(messages.clone());
+        Thinking {
+            agent,
+            board: board.clone(),
+            limits,
+            resign,
+            messages,
+            search: None,
+        }
+    }
+
+    /// Thinks until `pause_at` (from [now]), returns wether it's finished and sent its move, resignation, or error
+    #[cfg(feature = "gui")]
+    pub(crate) fn step(&mut self, pause_at: f64) -> bool {
+        // Crashes are sent as errors, so the game isn't left waiting on a search that's gone
+        let reply = catch_unwind(AssertUnwindSafe(|| match &mut self.search {
+            Some(search) => search.run(pause_at).map(Ok),
+            None => match self.agent.start(&self.board, &self.limits) {
+                AgentResult::Searching(search) => self.search.insert(search).run(pause_at).map(Ok),
+                AgentResult::Picked(reply) => Some(Ok(reply)),
+                AgentResult::Failed(reason) => Some(Err(reason)),
+            },
+        }));
+        let (mov, info) = match reply {
+            Ok(Some(Ok(reply))) => reply,
+            Ok(Some(Err(reason))) => {
+                self.messages.send(AgentMessage::Error(reason)).ok();
+                return true;
+            }
+            Ok(None) => return false,
+            Err(panic) => {
+                let reason = panic
+                    .downcast_ref::<&str>()
+                    .map(|reason| reason.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown error".to_string());
+                self.messages
+                    .send(AgentMessage::Error(format!(
+                        "{} crashed: {}",
+                        self.agent, reason
+                    )))
+                    .ok();
+                return true;
+            }
+        };
+
+        // Book moves don't have a real score
+        let board = &self.board;
+        let resigns = self
+            .resign
+            .zip(info.filter(|info| info.depth != 0))
+            .is_some_and(|((mut outlook, resign_score), info)| {
+                let score = color_ternary!(board.turn, info.score, -info.score);
+                outlook.update(score, Outlook::draw_score(board), resign_score)
+                    == Some(Proposal::Resign)
+            });
+        let message = match mov {
+            Some(_) if resigns => AgentMessage::Resign,
+            Some((from, to)) => AgentMessage::BestMove(from, to),
+            None if board.is_over() => AgentMessage::Error("The game is over".to_string()),
+            None => AgentMessage::Error(format!("{} didn't pick a move", self.agent)),
+        };
+        // Errors if the game was reset while thinking
+        self.messages.send(message).ok();
+        true
+    }
+}
+
+/// An agent's [MovePicker], and the button picking it in the GUI
+pub(crate) struct Registered {
+    pub(crate) id: AgentId,
+    picker: Box<dyn MovePicker>,
+    /// Wether it can be picked, IE the external engine needs to be given one
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    available: fn() -> bool,
+}
+impl Registered {
+    fn new(id: AgentId, picker: impl MovePicker + 'static) -> Registered {
+        Registered {
+            id,
+            picker: Box::new(picker),
+            available: || true,
         }
     }
+
+    /// Only lets it be picked while `available` returns `true`
+    #[cfg(not(target_family = "wasm"))]
+    fn available(self, available: fn() -> bool) -> Registered {
+        Registered { available, ..self }
+    }
 }
 
-pub(crate) const AGENTS: [(&str, Agent); 4] = [
-    ("Random", Agent::Random),
-    ("Control", Agent::Control),
-    ("Antimax", Agent::Antimax),
-    ("Minimax", Agent::Minimax),
-];
+lazy_static! {
+    /// Every agent, in the order of their buttons with the first at the bottom
+    /// - New agents only need registering here, and a variant of [Agent] to be saved
+    pub(crate) static ref AGENTS: Vec<Registered> = vec![
+        Registered::new(AgentId::RANDOM, InstantPicker::new(random_agent, Some(400.0))),
+        Registered::new(AgentId::CONTROL, InstantPicker::new(|_| None, None)),
+        Registered::new(AgentId::ANTIMAX, MinimaxPicker::new(true, EvalBackend::Classic)),
+        Registered::new(AgentId::GREEDY, InstantPicker::new(greedy_agent, Some(700.0))),
+        Registered::new(
+            AgentId::FIXED_DEPTH,
+            InstantPicker::new(|board| fixed_depth_agent(board, 2), Some(1000.0)),
+        ),
+        Registered::new(AgentId::MCTS, Mcts),
+        Registered::new(AgentId::MINIMAX, MinimaxPicker::new(false, EvalBackend::Classic)),
+        Registered::new(AgentId::NNUE, MinimaxPicker::new(false, EvalBackend::Nnue)),
+        #[cfg(not(target_family = "wasm"))]
+        Registered::new(AgentId::ENGINE, UciEngine::new(engine_path().map(Path::to_path_buf)))
+            .available(|| engine_path().is_some()),
+    ];
+}
+
+/// Agents in [AGENTS] that can be picked
+#[cfg(feature = "gui")]
+pub(crate) fn agents() -> impl DoubleEndedIterator<Item = &'static Registered> {
+    AGENTS.iter().filter(|registered| (registered.available)())
+}
@@ -1,35 +1,292 @@
 //! Parse and store ECO opening database
+//!
+//! The GUI reads it in the background as it starts, on its own thread or a slice each frame on the web, so the window
+//! doesn't stall on it. Anything else reads it the first time it's asked for
+//!
+//! An asset folder can replace the built in book with an updated one, see [assets](crate::assets)
+//!
+//! # Format
+//!
+//! [create_openings] builds the book from `openings/openings.json`, compressed with zlib. Numbers are little endian:
+//!
+//! - [BOOK_MAGIC]
+//! - `u16` count of opening names, then each as a `u8` length and UTF-8
+//! - `u32` count of positions, then each as its `u64` [Board::hash](crate::board::Board::hash), a `u16` count of
+//!   moves, and each move as its from and to squares in a `u8` each (`y * 8 + x`), and the `u16` index of its name
 
+use std::borrow::Cow;
+use std::mem::take;
 use std::str::from_utf8;
+use std::sync::{Arc, Mutex};
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use std::thread::spawn;
 
 use lazy_static::lazy_static;
+#[cfg(test)]
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::decompress_to_vec_zlib;
 use rustc_hash::FxHashMap;
-use serde_json::from_str;
 
+use crate::error::ChessError;
+use crate::hashmap;
+use crate::platform::{now, warn};
 use crate::util::Loc;
 
-type Openings = FxHashMap<u64, Vec<((Loc, Loc), String)>>;
+pub(crate) type Openings = FxHashMap<u64, Vec<((Loc, Loc), String)>>;
+
+/// Start of a book, with the version of the format
+const BOOK_MAGIC: [u8; 5] = *b"BOOK1";
+
+/// Where the book is in the assets, positions are keyed by [Board::hash](crate::board::Board::hash) which depends on
+/// the size of a pointer
+#[cfg(all(feature = "gui", target_pointer_width = "64"))]
+pub(crate) const BOOK_PATH: &str = "assets/openings_64.bin";
+#[cfg(all(feature = "gui", target_pointer_width = "32"))]
+pub(crate) const BOOK_PATH: &str = "assets/openings_32.bin";
 
 #[cfg(target_pointer_width = "64")]
-lazy_static! {
-    pub(crate) static ref OPENINGS: Openings =
-        from_str(from_utf8(include_bytes!("../assets/openings_64.json")).unwrap()).unwrap();
+pub(crate) const BAKED_BOOK: &[u8] = include_bytes!("../assets/openings_64.bin");
+#[cfg(target_pointer_width = "32")]
+pub(crate) const BAKED_BOOK: &[u8] = include_bytes!("../assets/openings_32.bin");
+
+enum Book {
+    Unloaded,
+    /// Being read by [load_in_background]
+    #[cfg(feature = "gui")]
+    Loading,
+    Loaded(Arc<Openings>),
 }
 
-#[cfg(target_pointer_width = "32")]
 lazy_static! {
-    pub(crate) static ref OPENINGS: Openings =
-        from_str(from_utf8(include_bytes!("../assets/openings_32.json")).unwrap()).unwrap();
+    static ref BOOK: Mutex<Book> = Mutex::new(Book::Unloaded);
+}
+
+/// Book being read a slice each frame on the web, see [step_loading]
+#[cfg(all(feature = "gui", target_family = "wasm"))]
+static LOADER: Mutex<Option<BookLoader<'static>>> = Mutex::new(None);
+
+/// The opening book, read from [BAKED_BOOK] if nothing has started reading it yet
+/// - Empty while [load_in_background] is still reading it, so the agent searches from the first move instead
+pub(crate) fn openings() -> Arc<Openings> {
+    let mut book = BOOK.lock().unwrap();
+    match &*book {
+        Book::Loaded(openings) => openings.clone(),
+        #[cfg(feature = "gui")]
+        Book::Loading => Arc::default(),
+        Book::Unloaded => {
+            let openings = Arc::new(load_openings(BAKED_BOOK));
+            *book = Book::Loaded(openings.clone());
+            openings
+        }
+    }
+}
+
+/// Starts reading the book from `bytes` without holding up the caller, falling back to [BAKED_BOOK] if they can't be
+/// read
+/// - On the web the book is only read as [step_loading] is called
+#[cfg(feature = "gui")]
+pub(crate) fn load_in_background(bytes: Cow<'static, [u8]>) {
+    *BOOK.lock().unwrap() = Book::Loading;
+    #[cfg(not(target_family = "wasm"))]
+    spawn(move || finish_loading(BookLoader::new(bytes).step(f64::INFINITY)));
+    #[cfg(target_family = "wasm")]
+    {
+        *LOADER.lock().unwrap() = Some(BookLoader::new(bytes));
+    }
+}
+
+/// Reads the book started with [load_in_background] until `deadline` (from [now])
+#[cfg(all(feature = "gui", target_family = "wasm"))]
+pub(crate) fn step_loading(deadline: f64) {
+    let mut loader = LOADER.lock().unwrap();
+    let Some(read) = loader.as_mut().map(|loader| loader.step(deadline)) else {
+        return;
+    };
+    if matches!(read, Ok(None)) {
+        return;
+    }
+    *loader = None;
+    finish_loading(read);
+}
+
+/// Stores the book [BookLoader] read, or the built in one if it couldn't
+#[cfg(feature = "gui")]
+fn finish_loading(read: Result<Option<Openings>, ChessError>) {
+    let openings = match read {
+        Ok(openings) => openings.unwrap_or_default(),
+        Err(err) => {
+            warn!("{}, using the built in one", err);
+            load_openings(BAKED_BOOK)
+        }
+    };
+    *BOOK.lock().unwrap() = Book::Loaded(Arc::new(openings));
+}
+
+/// Reads a book one position at a time, so it can be spread over several frames
+pub(crate) struct BookLoader<'a> {
+    /// Book as it's stored, until the first step inflates it into [BookLoader::bytes]
+    compressed: Option<Cow<'a, [u8]>>,
+    bytes: Vec<u8>,
+    /// Where the next value starts in [BookLoader::bytes]
+    pos: usize,
+    names: Vec<String>,
+    /// Positions left to read
+    left: u32,
+    openings: Openings,
+}
+impl<'a> BookLoader<'a> {
+    pub(crate) fn new(compressed: Cow<'a, [u8]>) -> BookLoader<'a> {
+        BookLoader {
+            compressed: Some(compressed),
+            bytes: vec![],
+            pos: 0,
+            names: vec![],
+            left: 0,
+            openings: hashmap! {},
+        }
+    }
+
+    /// Reads positions until `deadline` (from [now]), returns the book once all of it is read
+    /// - The first step only inflates the book and reads the names
+    pub(crate) fn step(&mut self, deadline: f64) -> Result<Option<Openings>, ChessError> {
+        if let Some(compressed) = self.compressed.take() {
+            self.bytes = decompress_to_vec_zlib(&compressed)
+                .map_err(|_| ChessError::Book("not a compressed book".to_string()))?;
+            self.read_header()?;
+            if now() >= deadline {
+                return Ok(None);
+            }
+        }
+
+        loop {
+            if self.left == 0 {
+                return Ok(Some(take(&mut self.openings)));
+            }
+            let hash = u64::from_le_bytes(self.take_bytes()?);
+            let count = u16::from_le_bytes(self.take_bytes()?);
+            let mut moves = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                let [from, to] = self.take_bytes()?;
+                let name = u16::from_le_bytes(self.take_bytes()?) as usize;
+                let name = self
+                    .names
+                    .get(name)
+                    .ok_or_else(|| self.error("Invalid name"))?;
+                moves.push(((square(from)?, square(to)?), name.clone()));
+            }
+            self.openings.insert(hash, moves);
+            self.left -= 1;
+
+            if now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Checks the book's version, and reads the names its moves refer to and how many positions it has
+    fn read_header(&mut self) -> Result<(), ChessError> {
+        if self.take_bytes()? != BOOK_MAGIC {
+            return Err(self.error("Not an opening book"));
+        }
+        let count = u16::from_le_bytes(self.take_bytes()?);
+        for _ in 0..count {
+            let [len] = self.take_bytes()?;
+            let end = self.pos + len as usize;
+            let name = self
+                .bytes
+                .get(self.pos..end)
+                .and_then(|name| from_utf8(name).ok())
+                .ok_or_else(|| self.error("Invalid name"))?
+                .to_string();
+            self.names.push(name);
+            self.pos = end;
+        }
+        self.left = u32::from_le_bytes(self.take_bytes()?);
+        Ok(())
+    }
+
+    /// Next `N` bytes, erroring if the book ends before them
+    fn take_bytes<const N: usize>(&mut self) -> Result<[u8; N], ChessError> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + N)
+            .ok_or_else(|| self.error("Unexpected end"))?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn error(&self, message: &str) -> ChessError {
+        ChessError::Book(format!("{} at byte {}", message, self.pos))
+    }
+}
+
+/// Square stored as `y * 8 + x`
+fn square(byte: u8) -> Result<Loc, ChessError> {
+    if byte >= 64 {
+        return Err(ChessError::Book(format!("Invalid square {}", byte)));
+    }
+    Ok(Loc(byte as usize % 8, byte as usize / 8))
+}
+
+/// `openings` in the format described in the [module docs](self), positions in order of their hash so it's the same
+/// each time
+#[cfg(test)]
+pub(crate) fn write_openings(openings: &Openings) -> Vec<u8> {
+    let mut positions: Vec<_> = openings.iter().collect();
+    positions.sort_by_key(|(hash, _)| **hash);
+    let mut names: Vec<&String> = vec![];
+    for (_, moves) in positions.iter() {
+        for (_, name) in moves.iter() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    let mut bytes = BOOK_MAGIC.to_vec();
+    bytes.extend((names.len() as u16).to_le_bytes());
+    for name in names.iter() {
+        let len: u8 = name.len().try_into().expect("Opening name too long");
+        bytes.push(len);
+        bytes.extend(name.as_bytes());
+    }
+    bytes.extend((positions.len() as u32).to_le_bytes());
+    for (hash, moves) in positions {
+        bytes.extend(hash.to_le_bytes());
+        bytes.extend((moves.len() as u16).to_le_bytes());
+        for ((from, to), name) in moves.iter() {
+            bytes.push((from.1 * 8 + from.0) as u8);
+            bytes.push((to.1 * 8 + to.0) as u8);
+            let name = names.iter().position(|other| *other == name).unwrap();
+            bytes.extend((name as u16).to_le_bytes());
+        }
+    }
+    compress_to_vec_zlib(&bytes, 10)
+}
+
+/// Reads an opening database in the format described in the [module docs](self)
+pub(crate) fn parse_openings(bytes: &[u8]) -> Result<Openings, ChessError> {
+    BookLoader::new(Cow::Borrowed(bytes))
+        .step(f64::INFINITY)
+        .map(Option::unwrap_or_default)
+}
+
+/// [parse_openings], or no openings if it can't be read, so the agent searches from the first move instead
+fn load_openings(bytes: &[u8]) -> Openings {
+    parse_openings(bytes).unwrap_or_else(|err| {
+        warn!("{}", err);
+        hashmap! {}
+    })
 }
 
 #[test]
 fn create_openings() {
     use serde::{Deserialize, Serialize};
-    use serde_json::to_string;
+    use serde_json::from_str;
 
     use crate::board::{Board, ChessColor};
     use crate::board_extras::char_to_piece;
-    use crate::conf::FEN;
+    use crate::conf::DEFAULT_FEN;
     use crate::pieces::piece::PieceNames;
     use crate::{color_ternary, hashmap, loc, ternary};
 
@@ -48,7 +305,7 @@ fn create_openings() {
     .unwrap();
 
     for opening in openings.iter() {
-        let mut board = Board::from_fen(FEN);
+        let mut board = Board::from_fen(DEFAULT_FEN);
 
         for (i, raw_ms) in opening.moves.iter().enumerate() {
             let turn = ternary!(i % 2 == 0, ChessColor::White, ChessColor::Black);
@@ -80,7 +337,8 @@ fn create_openings() {
 
                     let mut i = 0;
                     loop {
-                        if let Some(piece) = board.get(&pos.copy_move_i32(0, dir * i).0) {
+                        if let Some(piece) = pos.offset(0, dir * i).and_then(|loc| board.get(&loc))
+                        {
                             if piece.color == turn && piece.name == PieceNames::Pawn {
                                 break 'main (piece.pos, pos);
                             }
@@ -92,7 +350,7 @@ fn create_openings() {
                 // Normal piece moves, ie "Nf3" or "Qe2"
                 if move_string.len() == 3 {
                     let mut chars = move_string.chars();
-                    let name = char_to_piece(&chars.next().unwrap());
+                    let name = char_to_piece(&chars.next().unwrap()).unwrap();
                     let pos = Loc::from_notation(&chars.collect::<String>());
 
                     for mov in legal_moves.iter() {
@@ -112,7 +370,7 @@ fn create_openings() {
                         let pos = loc!(x as usize, 0);
                         let mut i = 0;
                         let killer = loop {
-                            if let Some(piece) = board.get(&pos.copy_move_i32(0, i).0) {
+                            if let Some(piece) = pos.offset(0, i).and_then(|loc| board.get(&loc)) {
                                 if piece.color == turn && piece.name == PieceNames::Pawn {
                                     break piece.pos;
                                 }
@@ -134,7 +392,7 @@ fn create_openings() {
                         }
                     } else {
                         let mut chars = move_string.chars();
-                        let killer = char_to_piece(&chars.next().unwrap());
+                        let killer = char_to_piece(&chars.next().unwrap()).unwrap();
                         chars.next();
 
                         let pos = Loc::from_notation(&chars.collect::<String>());
@@ -153,7 +411,7 @@ fn create_openings() {
                     || move_string.starts_with('R')
                 {
                     let mut chars = move_string.chars();
-                    let name = char_to_piece(&chars.next().unwrap());
+                    let name = char_to_piece(&chars.next().unwrap()).unwrap();
                     let raw = chars.next().unwrap();
                     if raw.is_ascii_digit() {
                         let y = raw.to_digit(10).unwrap() as usize;
@@ -192,12 +450,8 @@ fn create_openings() {
     }
 
     #[cfg(target_pointer_width = "64")]
-    {
-        let path = "assets/openings_64.json";
-        std::fs::write(path, to_string(&new_openings).unwrap().as_bytes()).unwrap();
-    }
+    let path = "assets/openings_64.bin";
     #[cfg(target_pointer_width = "32")]
-    {
-        macroquad::prelude::info!("{}", to_string(&new_openings).unwrap());
-    }
+    let path = "assets/openings_32.bin";
+    std::fs::write(path, write_openings(&new_openings)).unwrap();
 }
@@ -0,0 +1,339 @@
+//! Background analysis of every position reached in the game, used to annotate exported PGN, and live analysis of the
+//! current position
+//!
+//! Each position is searched once by the minimax agent, one at a time on a worker thread on native and a slice each
+//! frame on the web. Live analysis searches until it's stopped, so it's only available on native
+//!
+//! Threats are found the same way, by searching the position as if the side to move passed
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(not(target_family = "wasm"))]
+use std::thread::spawn;
+
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+use derive_new::new;
+
+#[cfg(target_family = "wasm")]
+use crate::agent::Thinking;
+use crate::agent::{AgentId, AgentMessage, SearchInfo, SearchLimits, MAX};
+use crate::board::{Board, ChessColor};
+use crate::board_eval::is_mate;
+use crate::move_cache::MoveCache;
+use crate::util::Loc;
+use crate::{color_ternary, ternary};
+
+/// Seconds spent analyzing each position
+const ANALYSIS_TIME: f64 = 1.0;
+
+/// (score for white, best move, depth) of an analyzed position
+type Found = (i32, Option<(Loc, Loc)>, u8);
+
+/// What's kept of a search, `None` for book moves
+fn found(info: Option<SearchInfo>) -> Option<Found> {
+    info.filter(|info| info.score != MAX)
+        .map(|info| (info.score, info.pv.first().copied(), info.depth))
+}
+
+/// Which search a result is from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Searched {
+    /// The position after `ply` moves
+    Position(usize),
+    /// The position after `ply` moves, with the turn passed to the other side
+    Threat(usize),
+}
+
+/// What the side that isn't to move could do if it were its turn, see [Analysis::threat]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Threat {
+    Mate,
+    /// Centipawns it would gain, over the position's score
+    Gain(i32),
+}
+
+/// `board` with the side to move skipping its turn, `None` if it's in check and can't
+pub(crate) fn passed(board: &Board) -> Option<Board> {
+    let (white_king, black_king) = board.kings();
+    let king = color_ternary!(board.turn, white_king, black_king);
+    if king.is_some_and(|king| board.attacks(board.turn.other()).contains(&king)) {
+        return None;
+    }
+
+    let mut passed = board.clone();
+    passed.turn = board.turn.other();
+    passed.en_passent = None;
+    // Cached moves were worked out for the other side's turn
+    passed.move_cache = MoveCache::new();
+    passed.hash = passed.hash();
+    passed.prev_states.clear();
+    passed.prev_states.push(passed.hash);
+    passed.update_things(true);
+    Some(passed)
+}
+
+#[derive(Clone, new)]
+pub(crate) struct Analysis {
+    /// Score for white of the position after each ply, the first is the starting position
+    /// - `None` for positions that weren't analyzed, or are in the opening book
+    #[new(value = "vec![]")]
+    pub(crate) evals: Vec<Option<i32>>,
+
+    /// Best move found in each position of [Analysis::evals]
+    #[new(value = "vec![]")]
+    pub(crate) best: Vec<Option<(Loc, Loc)>>,
+
+    /// Depth searched to in each position of [Analysis::evals]
+    #[new(value = "vec![]")]
+    pub(crate) depths: Vec<Option<u8>>,
+
+    /// Score for white of the positions of [Analysis::evals] with the turn passed, see [passed]
+    /// - Only searched for positions [Analysis::update] is asked to look for threats in
+    #[new(value = "vec![]")]
+    pub(crate) threats: Vec<Option<i32>>,
+
+    /// Move the side not to move threatens in each position of [Analysis::threats]
+    #[new(value = "vec![]")]
+    pub(crate) threat_moves: Vec<Option<(Loc, Loc)>>,
+
+    /// Ply of the last position sent to be analyzed
+    #[new(value = "None")]
+    requested: Option<usize>,
+
+    /// Ply of the last position sent to be searched for threats
+    #[new(value = "None")]
+    threat_requested: Option<usize>,
+
+    /// (which search, score and best move) of finished searches
+    #[allow(clippy::type_complexity)]
+    #[new(value = "unbounded()")]
+    channel: (
+        Sender<(Searched, Option<Found>)>,
+        Receiver<(Searched, Option<Found>)>,
+    ),
+
+    /// (which search, search, its messages) of positions being analyzed a slice at a time, oldest first
+    #[cfg(target_family = "wasm")]
+    #[new(value = "vec![]")]
+    searches: Vec<(Searched, Thinking, Receiver<AgentMessage>)>,
+
+    /// Thread searching positions, started with the first one
+    #[cfg(not(target_family = "wasm"))]
+    #[new(value = "None")]
+    worker: Option<Worker>,
+}
+impl Analysis {
+    /// Collects finished searches, and starts analyzing `board` if it hasn't been yet
+    /// - `ply` is how many moves into the game `board` is
+    /// - `threats` is wether to also search what the side not to move threatens, see [Analysis::threat]
+    /// - On the web the search runs until `deadline` (from [now](crate::platform::now)) each call
+    #[cfg_attr(not(target_family = "wasm"), allow(unused_variables))]
+    pub(crate) fn update(&mut self, board: &Board, ply: usize, deadline: f64, threats: bool) {
+        #[cfg(target_family = "wasm")]
+        if let Some((searched, thinking, messages)) = self.searches.first_mut() {
+            if thinking.step(deadline) {
+                let info = messages
+                    .try_iter()
+                    .filter_map(|message| message.info())
+                    .last();
+                self.channel.0.send((*searched, found(info))).unwrap();
+                self.searches.remove(0);
+            }
+        }
+
+        for (searched, found) in self.channel.1.try_iter() {
+            let ply = match searched {
+                Searched::Position(ply) | Searched::Threat(ply) => ply,
+            };
+            if self.evals.len() <= ply {
+                self.evals.resize(ply + 1, None);
+                self.best.resize(ply + 1, None);
+                self.depths.resize(ply + 1, None);
+                self.threats.resize(ply + 1, None);
+                self.threat_moves.resize(ply + 1, None);
+            }
+            match searched {
+                Searched::Position(_) => {
+                    self.evals[ply] = found.map(|(score, _, _)| score);
+                    self.best[ply] = found.and_then(|(_, best, _)| best);
+                    self.depths[ply] = found.map(|(_, _, depth)| depth);
+                }
+                Searched::Threat(_) => {
+                    self.threats[ply] = found.map(|(score, _, _)| score);
+                    self.threat_moves[ply] = found.and_then(|(_, best, _)| best);
+                }
+            }
+        }
+
+        if board.is_over() {
+            return;
+        }
+        if self.requested != Some(ply) {
+            self.requested = Some(ply);
+            self.search(Searched::Position(ply), board.clone());
+        }
+        if threats && self.threat_requested != Some(ply) {
+            self.threat_requested = Some(ply);
+            if let Some(passed) = passed(board) {
+                self.search(Searched::Threat(ply), passed);
+            }
+        }
+    }
+
+    /// Queues `board` to be searched, on the worker thread or sliced on the web
+    fn search(&mut self, searched: Searched, board: Board) {
+        #[cfg(target_family = "wasm")]
+        {
+            let (sender, messages) = unbounded();
+            let limits = SearchLimits::new(ANALYSIS_TIME);
+            let thinking = Thinking::new(AgentId::MINIMAX, &board, limits, None, sender);
+            self.searches.push((searched, thinking, messages));
+        }
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let worker = self
+                .worker
+                .get_or_insert_with(|| Worker::start(self.channel.0.clone()));
+            worker.jobs.send((searched, board)).unwrap();
+        }
+    }
+
+    /// What `color` threatens in the position after `ply` moves, where it isn't its turn, once both searches of it
+    /// have finished
+    pub(crate) fn threat(&self, ply: usize, color: ChessColor) -> Option<Threat> {
+        let eval = (*self.evals.get(ply)?)?;
+        let threat = (*self.threats.get(ply)?)?;
+        let (eval, threat) = color_ternary!(color, (eval, threat), (-eval, -threat));
+        Some(ternary!(
+            is_mate(threat) && threat > 0,
+            Threat::Mate,
+            Threat::Gain(threat - eval)
+        ))
+    }
+
+    /// Forgets everything after `ply`, IE after a takeback
+    /// - Searches still running are abandoned, since their positions might not be in the game anymore
+    pub(crate) fn truncate(&mut self, ply: usize) {
+        self.evals.truncate(ply + 1);
+        self.best.truncate(ply + 1);
+        self.depths.truncate(ply + 1);
+        self.threats.truncate(ply + 1);
+        self.threat_moves.truncate(ply + 1);
+        self.requested = None;
+        self.threat_requested = None;
+        self.channel = unbounded();
+        #[cfg(target_family = "wasm")]
+        self.searches.clear();
+        #[cfg(not(target_family = "wasm"))]
+        {
+            self.worker = None;
+        }
+    }
+}
+
+/// Thread that searches queued positions one at a time, stopped once every copy of it is dropped
+#[cfg(not(target_family = "wasm"))]
+#[derive(Clone)]
+struct Worker {
+    jobs: Sender<(Searched, Board)>,
+    /// Only held for stopping the search when dropped
+    _stop: Arc<StopOnDrop>,
+}
+#[cfg(not(target_family = "wasm"))]
+impl Worker {
+    /// Starts the thread, which sends finished searches to `results`
+    fn start(results: Sender<(Searched, Option<Found>)>) -> Worker {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (jobs, queue) = unbounded::<(Searched, Board)>();
+
+        let stopped = stop.clone();
+        spawn(move || {
+            // Ends once the worker is dropped, abandoning whatever is still queued
+            for (searched, board) in queue {
+                let mut limits = SearchLimits::new(ANALYSIS_TIME);
+                limits.stop = Some(stopped.clone());
+                let (_, info) = AgentId::MINIMAX.get_move(&board, &limits);
+                if stopped.load(Ordering::Relaxed) || results.send((searched, found(info))).is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Worker {
+            jobs,
+            _stop: Arc::new(StopOnDrop(stop)),
+        }
+    }
+}
+
+/// Stops a search once every copy of it is dropped, so it doesn't keep going in the background
+pub(crate) struct StopOnDrop(pub(crate) Arc<AtomicBool>);
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Search of the current position that keeps deepening until it's stopped
+#[derive(Clone)]
+pub(crate) struct LiveAnalysis {
+    /// Position being analyzed
+    pub(crate) board: Board,
+    /// Deepest search finished so far
+    pub(crate) latest: Option<SearchInfo>,
+    /// Wether the search thread has finished, after being stopped
+    pub(crate) finished: bool,
+    stop: Arc<StopOnDrop>,
+    progress: Receiver<AgentMessage>,
+}
+impl LiveAnalysis {
+    /// Starts searching `board` on its own thread
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn start(board: &Board) -> LiveAnalysis {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, progress) = unbounded();
+
+        let mut limits = SearchLimits::new(f64::INFINITY);
+        limits.stop = Some(stop.clone());
+        limits.progress = Some(sender);
+        let search_board = board.clone();
+        // The sender is dropped with `limits` once the search ends, which is how it's noticed as finished
+        spawn(move || AgentId::MINIMAX.get_move(&search_board, &limits));
+
+        LiveAnalysis {
+            board: board.clone(),
+            latest: None,
+            finished: false,
+            stop: Arc::new(StopOnDrop(stop)),
+            progress,
+        }
+    }
+
+    /// Collects finished depths, and notices when the search has ended
+    pub(crate) fn update(&mut self) {
+        loop {
+            match self.progress.try_recv() {
+                Ok(message) => {
+                    if let Some(info) = message.info() {
+                        self.latest = Some(info);
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Asks the search to end, it finishes on its own thread soon after
+    pub(crate) fn stop(&self) {
+        self.stop.0.store(true, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stopping(&self) -> bool {
+        self.stop.0.load(Ordering::Relaxed)
+    }
+}
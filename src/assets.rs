@@ -1,14 +1,41 @@
 //! Helper functions for storing and loading `Texture2D`s and `Sound`s in sync code
+//!
+//! Assets are built into the binary, but on desktop a folder laid out like `assets/` can replace any of them, IE a
+//! piece pack in `<folder>/pieces/white_king.png` or an updated opening book. Set it with `--assets <folder>` or
+//! [ASSETS_VAR]
+//!
+//! - Debug builds reload assets from the folder as their files change
 
+use std::borrow::Cow;
+#[cfg(not(target_family = "wasm"))]
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+#[cfg(not(target_family = "wasm"))]
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+#[cfg(not(target_family = "wasm"))]
+use std::time::{Duration, Instant, SystemTime};
 
 use lazy_static::lazy_static;
 use macroquad::audio::{load_sound_from_bytes, Sound};
 use macroquad::prelude::ImageFormat;
+use macroquad::text::{load_ttf_font_from_bytes, Font};
 use macroquad::texture::{FilterMode, Texture2D};
 use rustc_hash::FxHashMap;
 
+use crate::error::ChessError;
 use crate::hashmap;
+#[cfg(not(target_family = "wasm"))]
+use crate::platform::info;
+use crate::platform::warn;
+
+/// Environment variable with the asset folder, when `--assets` isn't given
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const ASSETS_VAR: &str = "CHESS_AI_ASSETS";
+
+/// How often [hot_reload] looks for changed files
+#[cfg(not(target_family = "wasm"))]
+const RELOAD_INTERVAL: Duration = Duration::from_millis(500);
 
 lazy_static! {
     /// Map of images
@@ -17,21 +44,123 @@ lazy_static! {
     static ref AUDIO_MAP: Mutex<FxHashMap<String, Sound>> = Mutex::new(hashmap! {});
 }
 
+#[cfg(not(target_family = "wasm"))]
+lazy_static! {
+    /// Folder whose files replace the built in assets, `None` to only use those
+    static ref ASSET_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+    /// Every asset loaded so far, to reload them when their file changes
+    static ref SOURCES: Mutex<Vec<Source>> = Mutex::new(vec![]);
+    /// When [hot_reload] last looked for changes
+    static ref LAST_RELOAD: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum AssetKind {
+    Image,
+    Sound,
+    Font,
+}
+
+/// An asset that was loaded, and where it came from
+#[cfg(not(target_family = "wasm"))]
+struct Source {
+    path: String,
+    kind: AssetKind,
+    /// Used again if the replacement file is removed
+    baked: &'static [u8],
+    /// Modification time of the replacement file, `None` if the built in asset is used
+    modified: Option<SystemTime>,
+}
+
+/// Asset folder from `--assets <folder>` in `args`, or [ASSETS_VAR]
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn asset_dir(args: &[String]) -> Option<PathBuf> {
+    let flag = args
+        .iter()
+        .position(|arg| arg == "--assets")
+        .and_then(|i| args.get(i + 1));
+    match flag {
+        Some(dir) => Some(PathBuf::from(dir)),
+        None => std::env::var_os(ASSETS_VAR).map(PathBuf::from),
+    }
+}
+
+/// Sets the folder whose files replace the built in assets, before they're loaded
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn set_asset_dir(dir: Option<PathBuf>) {
+    if let Some(dir) = &dir {
+        info!("Loading assets from {}", dir.display());
+    }
+    *ASSET_DIR.lock().unwrap() = dir;
+}
+
+/// File replacing the asset at `path`, IE `assets/pieces/white_king.png` is `<folder>/pieces/white_king.png`
+#[cfg(not(target_family = "wasm"))]
+fn override_path(path: &str) -> Option<PathBuf> {
+    let dir = ASSET_DIR.lock().unwrap().clone()?;
+    Some(dir.join(Path::new(path.strip_prefix("assets/").unwrap_or(path))))
+}
+
+/// Modification time of the file replacing `path`, `None` if there isn't one
+#[cfg(not(target_family = "wasm"))]
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(override_path(path)?).ok()?.modified().ok()
+}
+
+/// Bytes of the asset at `path`, from the asset folder if it has it and `baked` otherwise
+#[cfg_attr(target_family = "wasm", allow(unused_variables))]
+pub(crate) fn asset_bytes(path: &str, baked: &'static [u8]) -> Cow<'static, [u8]> {
+    #[cfg(not(target_family = "wasm"))]
+    if let Some(bytes) = override_path(path).and_then(|file| fs::read(file).ok()) {
+        return Cow::Owned(bytes);
+    }
+    Cow::Borrowed(baked)
+}
+
+/// Remembers where the asset at `path` came from, for [hot_reload]
+#[cfg(not(target_family = "wasm"))]
+fn track(path: &str, kind: AssetKind, baked: &'static [u8]) {
+    let mut sources = SOURCES.lock().unwrap();
+    if sources.iter().all(|source| source.path != path) {
+        sources.push(Source {
+            path: path.to_owned(),
+            kind,
+            baked,
+            modified: modified(path),
+        });
+    }
+}
+#[cfg(target_family = "wasm")]
+fn track(_path: &str, _kind: AssetKind, _baked: &'static [u8]) {}
+
+/// Decodes a PNG, errors instead of panicking if it's invalid
+fn decode_image(path: &str, bytes: &[u8]) -> Result<Texture2D, ChessError> {
+    let texture = catch_unwind(AssertUnwindSafe(|| {
+        Texture2D::from_file_with_format(bytes, Some(ImageFormat::Png))
+    }))
+    .map_err(|_| ChessError::Io(format!("Can't load \"{path}\": not a valid PNG")))?;
+    texture.set_filter(FilterMode::Linear);
+    Ok(texture)
+}
+
 /// Get an previously loaded image from the asset map
-pub(crate) fn get_image(path: &str) -> Texture2D {
+pub(crate) fn get_image(path: &str) -> Result<Texture2D, ChessError> {
     match ASSET_MAP.lock().unwrap().get(path) {
-        Some(texture) => texture.to_owned(),
-        None => panic!("{}", format!("Path \"{path}\" not loaded!")),
+        Some(texture) => Ok(texture.to_owned()),
+        None => Err(ChessError::Io(format!("Path \"{path}\" not loaded!"))),
     }
 }
 
-/// Load image bytes into the asset map
-pub(crate) async fn load_image_from_bytes(path: &str, bytes: &[u8]) -> Texture2D {
-    if ASSET_MAP.lock().unwrap().contains_key(path) {
-        return get_image(path);
+/// Load an image into the asset map, from the asset folder if it has a valid one and `baked` otherwise
+pub(crate) async fn load_image(path: &str, baked: &'static [u8]) -> Texture2D {
+    if let Ok(texture) = get_image(path) {
+        return texture;
     }
-    let resource = Texture2D::from_file_with_format(bytes, Some(ImageFormat::Png));
-    resource.set_filter(FilterMode::Linear);
+    track(path, AssetKind::Image, baked);
+    let resource = decode_image(path, &asset_bytes(path, baked)).unwrap_or_else(|err| {
+        warn!("{}, using the built in one", err);
+        decode_image(path, baked).unwrap()
+    });
     ASSET_MAP
         .lock()
         .unwrap()
@@ -40,22 +169,101 @@ pub(crate) async fn load_image_from_bytes(path: &str, bytes: &[u8]) -> Texture2D
 }
 
 /// Get an previously loaded audio file from the audio map
-pub(crate) fn get_audio(path: &str) -> Sound {
+pub(crate) fn get_audio(path: &str) -> Result<Sound, ChessError> {
     match AUDIO_MAP.lock().unwrap().get(path) {
-        Some(texture) => texture.to_owned(),
-        None => panic!("{}", format!("Path \"{path}\" not loaded!")),
+        Some(sound) => Ok(sound.to_owned()),
+        None => Err(ChessError::Io(format!("Path \"{path}\" not loaded!"))),
     }
 }
 
-/// Load audio bytes into the audio map
-pub(crate) async fn load_audio_from_bytes(path: &str, bytes: &[u8]) -> Sound {
-    if AUDIO_MAP.lock().unwrap().contains_key(path) {
-        return get_audio(path);
+/// Decodes a sound
+async fn decode_audio(path: &str, bytes: &[u8]) -> Result<Sound, ChessError> {
+    load_sound_from_bytes(bytes)
+        .await
+        .map_err(|err| ChessError::Io(format!("Can't load \"{path}\": {err}")))
+}
+
+/// Load audio into the audio map, from the asset folder if it has a valid one and `baked` otherwise
+/// - Errors if neither can be decoded
+pub(crate) async fn load_audio(path: &str, baked: &'static [u8]) -> Result<Sound, ChessError> {
+    if let Ok(sound) = get_audio(path) {
+        return Ok(sound);
     }
-    let resource = load_sound_from_bytes(bytes).await.unwrap();
+    track(path, AssetKind::Sound, baked);
+    let resource = match decode_audio(path, &asset_bytes(path, baked)).await {
+        Ok(sound) => sound,
+        Err(err) => {
+            warn!("{}, using the built in one", err);
+            decode_audio(path, baked).await?
+        }
+    };
     AUDIO_MAP
         .lock()
         .unwrap()
         .insert(path.to_owned(), resource.to_owned());
-    resource
+    Ok(resource)
+}
+
+/// Load a font, from the asset folder if it has a valid one and `baked` otherwise
+pub(crate) fn load_font(path: &str, baked: &'static [u8]) -> Result<Font, ChessError> {
+    track(path, AssetKind::Font, baked);
+    let decode = |bytes: &[u8]| {
+        load_ttf_font_from_bytes(bytes)
+            .map_err(|err| ChessError::Io(format!("Can't load \"{path}\": {err:?}")))
+    };
+    decode(&asset_bytes(path, baked)).or_else(|err| {
+        warn!("{}, using the built in one", err);
+        decode(baked)
+    })
+}
+
+/// Reloads the assets whose file in the asset folder changed, was added, or was removed since they were loaded
+/// - Files that can't be decoded are skipped, keeping the asset as it was
+#[cfg(not(target_family = "wasm"))]
+pub(crate) async fn hot_reload() {
+    {
+        let mut last = LAST_RELOAD.lock().unwrap();
+        if last.is_some_and(|last| last.elapsed() < RELOAD_INTERVAL)
+            || ASSET_DIR.lock().unwrap().is_none()
+        {
+            return;
+        }
+        *last = Some(Instant::now());
+    }
+
+    let changed: Vec<_> = SOURCES
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .filter_map(|source| {
+            let now = modified(&source.path);
+            (now != source.modified).then(|| {
+                source.modified = now;
+                (source.path.clone(), source.kind, source.baked)
+            })
+        })
+        .collect();
+
+    for (path, kind, baked) in changed {
+        let bytes = asset_bytes(&path, baked);
+        let reloaded = match kind {
+            AssetKind::Image => decode_image(&path, &bytes).map(|texture| {
+                if let Some(old) = ASSET_MAP.lock().unwrap().insert(path.clone(), texture) {
+                    old.delete();
+                }
+            }),
+            AssetKind::Sound => decode_audio(&path, &bytes).await.map(|sound| {
+                AUDIO_MAP.lock().unwrap().insert(path.clone(), sound);
+            }),
+            AssetKind::Font => load_ttf_font_from_bytes(&bytes)
+                .map(|font| unsafe {
+                    crate::FONT = Some(font);
+                })
+                .map_err(|err| ChessError::Io(format!("Can't load \"{path}\": {err:?}"))),
+        };
+        match reloaded {
+            Ok(()) => info!("Reloaded {}", path),
+            Err(err) => warn!("{}", err),
+        }
+    }
 }
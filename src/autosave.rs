@@ -0,0 +1,64 @@
+//! Game in progress saved every few moves, so it can be restored after the program crashes
+//!
+//! The save is removed when the game ends or the window is closed, so finding one on startup means the last session
+//! didn't shut down cleanly
+
+use std::fs::{read_to_string, remove_file, write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::AgentId;
+use crate::board::ChessColor;
+use crate::clock::{Clock, TimeControl};
+use crate::settings::Settings;
+use crate::util::Loc;
+use crate::variant::Variant;
+
+/// Moves played between saves
+pub(crate) const AUTOSAVE_INTERVAL: usize = 4;
+
+/// Where the game is saved to, in the temp directory so it doesn't clutter the working directory
+pub(crate) fn autosave_path() -> PathBuf {
+    std::env::temp_dir().join("chess-ai-autosave.json")
+}
+
+/// Enough of a game to start it again and replay its moves, along with the settings it was played with
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct GameRecord {
+    pub(crate) start_fen: String,
+    pub(crate) moves: Vec<(Loc, Loc)>,
+    pub(crate) player_color: ChessColor,
+    pub(crate) agent: AgentId,
+    pub(crate) player_agent: Option<AgentId>,
+    pub(crate) max_depth: Option<u8>,
+    pub(crate) time_control: Option<TimeControl>,
+    pub(crate) variant: Variant,
+    /// Saves from before rated games were added are casual
+    #[serde(default)]
+    pub(crate) rated: bool,
+    /// Wether the moves are from the analysis board, restored as one instead of a game
+    #[serde(default)]
+    pub(crate) analysis_board: bool,
+    /// Times left when saved, `None` for an untimed game
+    pub(crate) clock: Option<Clock>,
+    pub(crate) settings: Settings,
+}
+impl GameRecord {
+    pub(crate) fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|err| err.to_string())?;
+        write(path, json).map_err(|err| format!("Can't write {}: {}", path.display(), err))
+    }
+
+    /// The saved game, `None` if there isn't one or it can't be read
+    pub(crate) fn load(path: &Path) -> Option<GameRecord> {
+        let json = read_to_string(path).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+}
+
+/// Removes the save, once the game it's of has ended or been left
+pub(crate) fn remove_autosave() {
+    // There's nothing to remove if no moves were played
+    let _ = remove_file(autosave_path());
+}
@@ -0,0 +1,188 @@
+//! Batch evaluation of a list of positions, run with `chess-ai eval <file>`, IE to compare versions of the eval over the
+//! same positions
+//!
+//! Reads one FEN or EPD position per line, and prints one line of JSON for each to stdout:
+//!
+//! `{"line":1,"id":"WAC.001","fen":"...","bestmove":"g3g6","san":"Qg6","depth":4,"score":250}`
+//!
+//! - `line` is the line of the file the position is on, `id` is its EPD `id` if it has one
+//! - `score` is in centipawns for white
+//! - With `--ms`, `nodes` is how many positions the deepest depth searched, and book moves have depth `0` and no score
+//! - Positions that can't be read or have no moves are `{"line":3,"fen":"...","error":"..."}` instead
+
+use std::fs::read_to_string;
+use std::io::{read_to_string as read_all, stdin};
+use std::process::exit;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::agent::{search_depth, AgentId, SearchLimits};
+use crate::board::Board;
+use crate::pgn::{coordinate, san};
+use crate::position::{describe_issues, epd_to_fen};
+
+const USAGE: &str = "Usage: chess-ai eval <file> [options]
+
+Reads a FEN or EPD position from each line of <file>, or stdin if it's -, and prints the evaluation and best move of
+each as a line of JSON
+
+Options:
+  --depth <n>  Search depth for every position (default 4)
+  --ms <n>     Search each position for this long instead, deepening as far as it gets";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BatchLimit {
+    /// Fixed depth search, the same every run so results can be compared
+    Depth(u8),
+    /// Milliseconds of iterative deepening, positions in the opening book are answered from it
+    Time(u64),
+}
+
+/// Result for one position, see the module docs
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Evaluation {
+    pub(crate) line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<String>,
+    pub(crate) fen: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) bestmove: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) san: Option<String>,
+    /// `0` for book moves
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) depth: Option<u8>,
+    /// `None` for book moves, which aren't searched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) score: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) nodes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+}
+impl Evaluation {
+    fn error(line: usize, id: Option<String>, fen: &str, error: String) -> Evaluation {
+        Evaluation {
+            line,
+            id,
+            fen: fen.to_string(),
+            bestmove: None,
+            san: None,
+            depth: None,
+            score: None,
+            nodes: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// (file, limit) from the arguments after `eval`
+pub(crate) fn parse_args(args: &[String]) -> Result<(String, BatchLimit), String> {
+    let mut file = None;
+    let mut limit = BatchLimit::Depth(4);
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if file.replace(arg.clone()).is_some() {
+                return Err(format!("Unexpected argument {}", arg));
+            }
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid number for {}: {}", arg, value))
+        };
+        match arg.as_str() {
+            "--depth" => limit = BatchLimit::Depth(number()?.clamp(1, u8::MAX as u64) as u8),
+            "--ms" => limit = BatchLimit::Time(number()?.max(1)),
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+
+    Ok((file.ok_or("Missing file")?, limit))
+}
+
+/// Entry point for `chess-ai eval`, exits on invalid arguments or an unreadable file
+pub(crate) fn run_cli(args: &[String]) {
+    let (file, limit) = parse_args(args).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        exit(1);
+    });
+    let text = match file.as_str() {
+        "-" => read_all(stdin()),
+        _ => read_to_string(&file),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("Can't read {}: {}", file, err);
+        exit(1);
+    });
+
+    let start = Instant::now();
+    let mut count = 0;
+    for evaluation in evaluate_all(&text, limit) {
+        println!("{}", serde_json::to_string(&evaluation).unwrap());
+        count += 1;
+    }
+    eprintln!(
+        "Evaluated {} positions in {:.1}s",
+        count,
+        start.elapsed().as_secs_f64()
+    );
+}
+
+/// Evaluates each position in `text` as it's iterated, skipping blank lines
+pub(crate) fn evaluate_all(text: &str, limit: BatchLimit) -> impl Iterator<Item = Evaluation> + '_ {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(move |(i, line)| evaluate_line(i, line, limit))
+}
+
+/// Evaluates a FEN or EPD `line`, FEN being tried first
+pub(crate) fn evaluate_line(line: usize, text: &str, limit: BatchLimit) -> Evaluation {
+    let (fen, id) = match Board::try_from_fen(text) {
+        Ok(_) => (text.to_string(), None),
+        Err(_) => epd_to_fen(text).unwrap_or_else(|| (text.to_string(), None)),
+    };
+    let board = match Board::try_from_fen(&fen) {
+        Ok(board) => board,
+        Err(issues) => return Evaluation::error(line, id, &fen, describe_issues(&issues)),
+    };
+
+    let (best, depth, score, nodes) = match limit {
+        BatchLimit::Depth(depth) => {
+            let (score, best) = search_depth(&board, depth);
+            (best, depth, Some(score), None)
+        }
+        BatchLimit::Time(ms) => {
+            let (best, info) =
+                AgentId::MINIMAX.get_move(&board, &SearchLimits::new(ms as f64 / 1000.0));
+            let info = info.unwrap_or_default();
+            let score = (info.depth != 0).then_some(info.score);
+            (best, info.depth, score, Some(info.nodes))
+        }
+    };
+    let Some((from, to)) = best else {
+        return Evaluation::error(line, id, &fen, "The game is over".to_string());
+    };
+
+    Evaluation {
+        line,
+        id,
+        bestmove: Some(coordinate(&board, &from, &to)),
+        san: Some(san(&board, &from, &to)),
+        fen,
+        depth: Some(depth),
+        score,
+        nodes,
+        error: None,
+    }
+}
@@ -0,0 +1,36 @@
+//! The search's hot paths, exposed for `benches/allocations.rs` to measure
+//!
+//! Run with `cargo bench --features bench`, everything else in the crate stays private
+
+use std::hint::black_box;
+
+use crate::board::{Board, ChessColor};
+use crate::util::Loc;
+
+/// Italian game after 4. d3, both sides about to castle
+const FEN: &str = "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5";
+
+/// A position to run the hot paths on
+pub struct Position(Board);
+impl Default for Position {
+    fn default() -> Self {
+        Position(Board::from_fen(FEN))
+    }
+}
+impl Position {
+    /// Builds both sides' attack sets, IE what the search does on every node
+    pub fn attacks(&self) {
+        black_box((
+            self.0.attacks(ChessColor::White),
+            self.0.attacks(ChessColor::Black),
+        ));
+    }
+
+    /// Plays white castling short, updating the move lists and attack sets
+    pub fn play(&mut self) {
+        black_box(
+            self.0
+                .move_piece(&Loc::from_notation("e1"), &Loc::from_notation("g1"), true),
+        );
+    }
+}
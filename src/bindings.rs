@@ -0,0 +1,85 @@
+//! JS API for embedding just the engine in other sites, without the macroquad canvas
+//!
+//! Build with `cargo rustc --release --lib --target wasm32-unknown-unknown --no-default-features --features bindings
+//! --crate-type cdylib`, then run the `.wasm` through `wasm-bindgen --target web`
+//!
+//! Keeps one game at a time, squares and moves are in coordinate notation, IE `e2` and `e2e4`
+
+use std::cell::RefCell;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::agent::{AgentId, SearchLimits};
+use crate::board::Board;
+use crate::conf::DEFAULT_FEN;
+use crate::pgn::{coordinate, try_parse_move};
+use crate::position::describe_issues;
+use crate::util::Loc;
+
+thread_local! {
+    /// Game played through the API
+    static BOARD: RefCell<Board> = RefCell::new(Board::from_fen(DEFAULT_FEN));
+}
+
+/// Starts a new game, from the standard position if `fen` is left out
+#[wasm_bindgen]
+pub fn new_game(fen: Option<String>) -> Result<(), String> {
+    let board = match fen {
+        Some(fen) => Board::try_from_fen(&fen).map_err(|issues| describe_issues(&issues))?,
+        None => Board::from_fen(DEFAULT_FEN),
+    };
+    BOARD.with(|current| *current.borrow_mut() = board);
+    Ok(())
+}
+
+/// FEN of the current position, for drawing the board
+#[wasm_bindgen]
+pub fn fen() -> String {
+    BOARD.with(|board| board.borrow().as_fen())
+}
+
+/// Moves the piece on `square` can make, empty if it isn't the side to move's or `square` isn't a square
+#[wasm_bindgen]
+pub fn legal_moves(square: &str) -> Vec<String> {
+    let Ok(from) = Loc::parse_notation(square) else {
+        return vec![];
+    };
+    BOARD.with(|board| {
+        let board = board.borrow();
+        board
+            .moves(board.turn)
+            .iter()
+            .filter(|mov| mov.0 == from)
+            .map(|(from, to)| coordinate(&board, from, to))
+            .collect()
+    })
+}
+
+/// Plays a move for the side to move, IE `e2e4` or `e7e8q`, SAN like `Nf3` works too
+/// - Returns the squares that changed as `square before after`, IE `["e2 P .", "e4 . P"]`, for animating the move
+/// - Promotions are always to a queen, since those are the only ones the board supports
+#[wasm_bindgen]
+pub fn make_move(uci: &str) -> Result<Vec<String>, String> {
+    BOARD.with(|board| {
+        let mut board = board.borrow_mut();
+        if board.is_over() {
+            return Err(format!("Game is over ({:?})", board.state));
+        }
+
+        let (from, to) = try_parse_move(&board, uci).map_err(|err| err.to_string())?;
+        let before = board.clone();
+        board.move_piece(&from, &to, true);
+        Ok(before.diff(&board).iter().map(|c| c.as_text()).collect())
+    })
+}
+
+/// Minimax agent's move for the side to move after thinking for up to `ms` milliseconds, `None` if the game is over
+#[wasm_bindgen]
+pub fn best_move(ms: f64) -> Option<String> {
+    let board = BOARD.with(|board| board.borrow().clone());
+    let limits = SearchLimits::new(ms / 1000.0);
+    AgentId::MINIMAX
+        .get_move(&board, &limits)
+        .0
+        .map(|(from, to)| coordinate(&board, &from, &to))
+}
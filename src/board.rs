@@ -1,16 +1,97 @@
 use derive_new::new;
-use rustc_hash::FxHashSet;
+use serde::{Deserialize, Serialize};
 
+use crate::board_eval::{on_start_square, Material, Phase};
+use crate::move_cache::MoveCache;
+use crate::nnue::Accumulator;
 use crate::pieces::piece::{Piece, PieceNames};
-use crate::util::Loc;
-use crate::{color_ternary, hashset, loc, ternary};
+use crate::util::{Loc, SquareSet};
+use crate::variant::Variant;
+use crate::{color_ternary, loc, ternary};
 
 /// Black or white, the colors of chess
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub(crate) enum ChessColor {
     Black,
     White,
 }
+impl ChessColor {
+    /// The opposing color
+    pub(crate) fn other(&self) -> ChessColor {
+        color_ternary!(*self, ChessColor::Black, ChessColor::White)
+    }
+}
+
+/// Which sides each color can still castle on
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub(crate) struct CastleRights {
+    pub(crate) white_kingside: bool,
+    pub(crate) white_queenside: bool,
+    pub(crate) black_kingside: bool,
+    pub(crate) black_queenside: bool,
+}
+impl CastleRights {
+    /// Rights for a fresh game, where both colors can castle on both sides
+    pub(crate) fn all() -> CastleRights {
+        CastleRights {
+            white_kingside: true,
+            white_queenside: true,
+            black_kingside: true,
+            black_queenside: true,
+        }
+    }
+
+    pub(crate) fn kingside(&self, color: ChessColor) -> bool {
+        color_ternary!(color, self.white_kingside, self.black_kingside)
+    }
+
+    pub(crate) fn queenside(&self, color: ChessColor) -> bool {
+        color_ternary!(color, self.white_queenside, self.black_queenside)
+    }
+
+    /// Removes both rights of a color, IE when the king moves
+    pub(crate) fn remove(&mut self, color: ChessColor) {
+        match color {
+            ChessColor::White => {
+                self.white_kingside = false;
+                self.white_queenside = false;
+            }
+            ChessColor::Black => {
+                self.black_kingside = false;
+                self.black_queenside = false;
+            }
+        }
+    }
+
+    /// Removes the right tied to a rook's starting square, IE when a piece moves from or to it
+    pub(crate) fn remove_square(&mut self, loc: &Loc) {
+        match loc {
+            loc!(0, 0) => self.black_queenside = false,
+            loc!(7, 0) => self.black_kingside = false,
+            loc!(0, 7) => self.white_queenside = false,
+            loc!(7, 7) => self.white_kingside = false,
+            _ => {}
+        }
+    }
+
+    /// Whether no color can castle anymore
+    pub(crate) fn is_empty(&self) -> bool {
+        self == &CastleRights::default()
+    }
+}
+
+/// Why a game was drawn, for the end screen and PGN
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum DrawReason {
+    Repetition,
+    FiftyMove,
+    InsufficientMaterial,
+    /// Offered by one side and accepted by the other
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    Agreement,
+    /// Both kings reached the eighth rank, in racing kings
+    RaceTie,
+}
 
 /// Board state IE (check, checkmate, etc)
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -21,10 +102,27 @@ pub(crate) enum BoardState {
     /// Attached color is who is in checkmate
     Checkmate(ChessColor),
     Stalemate,
-    Draw,
+    Draw(DrawReason),
+    /// Attached color is who ran out of time
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    Timeout(ChessColor),
+    /// Attached color is who resigned
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    Resigned(ChessColor),
+    /// Attached color is who was checked three times, in three-check
+    ThreeChecks(ChessColor),
+    /// Attached color is whose king exploded, in atomic
+    Exploded(ChessColor),
+    /// Attached color is whose king was taken, in fog of war
+    KingCaptured(ChessColor),
+    /// Attached color is whose king didn't reach the eighth rank first, in racing kings
+    Outraced(ChessColor),
+    /// Attached color is who lost every piece, in horde
+    NoPieces(ChessColor),
 }
 impl BoardState {
     /// Returns the endgame message for the board state, panics if the game is not over
+    #[cfg(feature = "gui")]
     pub(crate) fn message(&self, player_color: ChessColor) -> &'static str {
         match self {
             BoardState::Checkmate(color) => ternary!(
@@ -32,8 +130,51 @@ impl BoardState {
                 "Congrats! You won!\nPress \"r\" to restart!",
                 "Dang, you lost\nPress \"r\" to restart!"
             ),
+            BoardState::Timeout(color) => ternary!(
+                &player_color != color,
+                "Congrats! You won on time!\nPress \"r\" to restart!",
+                "Dang, you lost on time\nPress \"r\" to restart!"
+            ),
+            BoardState::Resigned(color) => ternary!(
+                &player_color != color,
+                "Congrats! The agent resigned!\nPress \"r\" to restart!",
+                "You resigned\nPress \"r\" to restart!"
+            ),
+            BoardState::ThreeChecks(color) => ternary!(
+                &player_color != color,
+                "Congrats! You gave three checks!\nPress \"r\" to restart!",
+                "Dang, you were checked three times\nPress \"r\" to restart!"
+            ),
+            BoardState::Exploded(color) => ternary!(
+                &player_color != color,
+                "Congrats! You blew up the king!\nPress \"r\" to restart!",
+                "Dang, your king blew up\nPress \"r\" to restart!"
+            ),
+            BoardState::KingCaptured(color) => ternary!(
+                &player_color != color,
+                "Congrats! You took the king!\nPress \"r\" to restart!",
+                "Dang, your king was taken\nPress \"r\" to restart!"
+            ),
+            BoardState::Outraced(color) => ternary!(
+                &player_color != color,
+                "Congrats! You won the race!\nPress \"r\" to restart!",
+                "Dang, you were outraced\nPress \"r\" to restart!"
+            ),
+            BoardState::NoPieces(color) => ternary!(
+                &player_color != color,
+                "Congrats! You took every piece!\nPress \"r\" to restart!",
+                "Dang, you lost every piece\nPress \"r\" to restart!"
+            ),
             BoardState::Stalemate => "Game over, stalemate\nPress \"r\" to restart!",
-            BoardState::Draw => "Game over, draw\nPress \"r\" to restart!",
+            BoardState::Draw(reason) => match reason {
+                DrawReason::Repetition => "Draw by repetition\nPress \"r\" to restart!",
+                DrawReason::FiftyMove => "Draw by fifty-move rule\nPress \"r\" to restart!",
+                DrawReason::InsufficientMaterial => {
+                    "Draw by insufficient material\nPress \"r\" to restart!"
+                }
+                DrawReason::Agreement => "Draw by agreement\nPress \"r\" to restart!",
+                DrawReason::RaceTie => "Draw, both kings made it\nPress \"r\" to restart!",
+            },
             _ => unreachable!(),
         }
     }
@@ -62,29 +203,25 @@ pub(crate) struct Board {
     #[new(value = "ChessColor::Black")]
     pub(crate) agent_color: ChessColor,
 
-    /// True if black can castle (queen side, king side)
-    #[new(value = "(true, true)")]
-    pub(crate) castle_black: (bool, bool),
-
-    /// True if white can castle (queen side, king side)
-    #[new(value = "(true, true)")]
-    pub(crate) castle_white: (bool, bool),
+    /// Which sides each color can still castle on
+    #[new(value = "CastleRights::all()")]
+    pub(crate) castle: CastleRights,
 
-    /// Last pawn move and color
+    /// En passent target square, IE the square a pawn skipped over when moving two squares last turn
     #[new(value = "None")]
-    pub(crate) en_passent: Option<(Loc, ChessColor)>,
+    pub(crate) en_passent: Option<Loc>,
 
     /// Current score of board, for white
     #[new(value = "0")]
     pub(crate) score: i32,
 
     /// Which squares are under attack by white pieces
-    #[new(value = "hashset! {}")]
-    pub(crate) attacks_white: FxHashSet<Loc>,
+    #[new(value = "SquareSet::default()")]
+    pub(crate) attacks_white: SquareSet,
 
     /// Which squares are under attack by black pieces
-    #[new(value = "hashset! {}")]
-    pub(crate) attacks_black: FxHashSet<Loc>,
+    #[new(value = "SquareSet::default()")]
+    pub(crate) attacks_black: SquareSet,
 
     /// Wether the white king is in check
     #[new(value = "false")]
@@ -95,8 +232,8 @@ pub(crate) struct Board {
     pub(crate) check_black: bool,
 
     /// Pieces that block any attackers
-    #[new(value = "hashset! {}")]
-    pub(crate) blockers: FxHashSet<Loc>,
+    #[new(value = "SquareSet::default()")]
+    pub(crate) blockers: SquareSet,
 
     /// Available moves for white
     #[new(value = "vec![]")]
@@ -106,6 +243,10 @@ pub(crate) struct Board {
     #[new(value = "vec![]")]
     pub(crate) moves_black: Vec<(Loc, Loc)>,
 
+    /// Moves of each piece from the last time [Board::moves_white] and [Board::moves_black] were updated
+    #[new(value = "MoveCache::new()")]
+    pub(crate) move_cache: MoveCache,
+
     /// Number of half moves (+1 per white *or* black turn)
     /// - Use `Board.full_moves()` for full moves
     #[new(value = "0")]
@@ -115,7 +256,7 @@ pub(crate) struct Board {
     #[new(value = "Vec::with_capacity(24)")]
     pub(crate) prev_states: Vec<u64>,
 
-    /// Updates on piece capture or pawn move
+    /// Half moves since the last capture or pawn move
     #[new(value = "0")]
     pub(crate) fifty_rule: u32,
 
@@ -123,14 +264,44 @@ pub(crate) struct Board {
     #[new(value = "false")]
     pub(crate) endgame: bool,
 
+    /// (white, black) last move of each color, to spot moves being undone
+    #[allow(clippy::type_complexity)]
+    #[new(value = "(None, None)")]
+    pub(crate) last_moves: (Option<(Loc, Loc)>, Option<(Loc, Loc)>),
+
+    /// (white, black) moves in the opening by pieces that had already moved, see [Board::phase]
+    #[new(value = "(0, 0)")]
+    pub(crate) repeated_moves: (u32, u32),
+
     /// Hash of the board
     #[new(value = "0")]
     pub(crate) hash: u64,
 
-    /// `((queen knight, king knight), (queen bishop, king bishop)))`
-    /// - `true` if moved before, `false` if not
-    #[new(value = "((false, false), (false, false))")]
-    pub(crate) agent_developments: ((bool, bool), (bool, bool)),
+    /// Zobrist key of only the pawns, for the pawn hash table in [crate::board_eval]
+    #[new(value = "0")]
+    pub(crate) pawn_hash: u64,
+
+    /// Material and piece-square sums for white, kept in sync by [Board::set]
+    /// - Saves [Board::score] walking the board every move, only checks and attacks are recomputed
+    #[new(value = "Material::default()")]
+    pub(crate) material: Material,
+
+    /// NNUE hidden layer for the pieces on the board, kept in sync by [Board::set]
+    #[new(value = "Accumulator::new()")]
+    pub(crate) accumulator: Accumulator,
+
+    /// Rules the board is played by, see [Board::set_variant]
+    #[new(value = "Variant::Standard")]
+    pub(crate) variant: Variant,
+
+    /// (white, black) checks given in three-check
+    #[new(value = "(0, 0)")]
+    pub(crate) checks: (u8, u8),
+
+    /// Piece the next move promotes to, if it's a promotion, set back to a queen after every move
+    /// - Only a player's moves underpromote, the agent always promotes to a queen
+    #[new(value = "PieceNames::Queen")]
+    pub(crate) promote_to: PieceNames,
 }
 impl Board {
     /// Moves the piece in `from` to `to`
@@ -141,33 +312,51 @@ impl Board {
 
         let capture_info = self.is_capture(from, to);
         let capture = capture_info.is_some();
-
-        // Special case where a castle rook is captured
-        if let Some(capture_pos) = capture_info {
-            let piece = self.get(&capture_pos).unwrap();
-            if piece.name == PieceNames::Rook {
-                match piece.color {
-                    ChessColor::White => {
-                        if piece.pos == loc!(0, 7) {
-                            self.castle_white.0 = false;
-                        } else if piece.pos == loc!(7, 7) {
-                            self.castle_white.1 = false;
-                        }
-                    }
-                    ChessColor::Black => {
-                        if piece.pos == loc!(0, 0) {
-                            self.castle_black.0 = false;
-                        } else if piece.pos == loc!(7, 0) {
-                            self.castle_black.1 = false;
-                        }
-                    }
-                }
-            }
+        let pawn_move = self.get(from).unwrap().name == PieceNames::Pawn;
+
+        // Pieces off their starting squares have moved before, recaptures aside
+        let piece = self.get(from).unwrap();
+        if self.phase() == Phase::Opening
+            && !capture
+            && !matches!(piece.name, PieceNames::Pawn | PieceNames::King)
+            && !on_start_square(&piece)
+        {
+            *color_ternary!(
+                piece.color,
+                &mut self.repeated_moves.0,
+                &mut self.repeated_moves.1
+            ) += 1;
         }
+        let before = self.raw;
+        let en_passent = self.en_passent.is_some();
+
+        // Moving a rook, or capturing one, on its starting square loses that side's castle
+        self.castle.remove_square(from);
+        self.castle.remove_square(to);
+
+        *color_ternary!(piece.color, &mut self.last_moves.0, &mut self.last_moves.1) =
+            Some((*from, *to));
 
         // Moving piece
         self.move_actions(from, to);
         self.move_raw(from, to);
+        if capture && self.variant == Variant::Atomic {
+            self.explode(to);
+        }
+
+        // Castling and en passent change more than the two squares
+        let mut changed = 0;
+        for (i, (before, after)) in before
+            .iter()
+            .flatten()
+            .zip(self.raw.iter().flatten())
+            .enumerate()
+        {
+            if before != after {
+                changed |= 1 << i;
+            }
+        }
+        self.invalidate_moves(changed, en_passent || self.en_passent.is_some());
 
         // Update turn
         self.turn = match self.turn {
@@ -176,7 +365,12 @@ impl Board {
         };
         self.half_moves += 1;
 
-        // Set hash (relies on nothing)
+        // Count checks (relies on nothing), before the hash since three-check hashes them
+        if self.variant == Variant::ThreeCheck {
+            self.count_check(piece.color);
+        }
+
+        // Set hash (relies on checks)
         self.hash = self.hash();
 
         // 3fold repetition (relies on hash)
@@ -187,10 +381,8 @@ impl Board {
             self.prev_states.push(self.hash);
         }
 
-        // Fifty move rule (pawn move is done in move_actions)
-        if capture {
-            self.fifty_rule = self.half_moves;
-        }
+        // Fifty move rule
+        self.fifty_rule = ternary!(capture || pawn_move, 0, self.fifty_rule + 1);
 
         // Update other metadata
         self.update_things(check_stale);
@@ -209,7 +401,8 @@ impl Board {
         if let Some(white_king) = white_king {
             self.check_white = self.attacks_black.contains(&white_king);
         } else {
-            self.check_white = true;
+            // White has no king to lose in horde
+            self.check_white = self.variant != Variant::Horde;
         }
         if let Some(black_king) = black_king {
             self.check_black = self.attacks_white.contains(&black_king);
@@ -222,8 +415,8 @@ impl Board {
 
         // Update moves (relies on attacks and blockers)
         if check_stale {
-            self.moves_white = self.moves(ChessColor::White);
-            self.moves_black = self.moves(ChessColor::Black);
+            self.moves_white = self.cached_moves(ChessColor::White);
+            self.moves_black = self.cached_moves(ChessColor::Black);
         }
 
         // Detect state (relies on check and moves)
@@ -251,32 +444,49 @@ impl Board {
 
     /// Detect wether the players are in check, checkmate or stalemate
     fn detect_state(&mut self, check_stale: bool) {
-        // Fifty move rule
-        if self.half_moves - self.fifty_rule >= 50 {
-            self.state = BoardState::Draw;
+        // Variants that end before a king is mated
+        if let Some(color) = [ChessColor::White, ChessColor::Black]
+            .into_iter()
+            .find(|color| self.exploded(*color))
+        {
+            self.state = BoardState::Exploded(color);
             return;
         }
-
-        // 3fold repetition
-        let mut sum = 0;
-        for hash in self.prev_states.iter() {
-            if hash == &self.hash {
-                sum += 1;
-                if sum >= 3 {
-                    self.state = BoardState::Draw;
-                    return;
-                }
-            }
+        if let Some(color) = [ChessColor::White, ChessColor::Black]
+            .into_iter()
+            .find(|color| self.king_captured(*color))
+        {
+            self.state = BoardState::KingCaptured(color);
+            return;
+        }
+        if let Some(color) = self.three_checks() {
+            self.state = BoardState::ThreeChecks(color.other());
+            return;
+        }
+        if let Some(state) = self.race_state() {
+            self.state = state;
+            return;
+        }
+        if self.horde_captured() {
+            self.state = BoardState::NoPieces(ChessColor::White);
+            return;
         }
 
-        // Draw by insufficient material
-        if self.raw.iter().flatten().flatten().count() == 2 {
-            self.state = BoardState::Draw;
+        // Draw by insufficient material, in the variants where the last two pieces are kings
+        if self.raw.iter().flatten().flatten().count() == 2
+            && !matches!(self.variant, Variant::Horde | Variant::RacingKings)
+        {
+            self.state = BoardState::Draw(DrawReason::InsufficientMaterial);
             return;
         }
 
-        // Others
-        match (self.check_white, self.check_black) {
+        // Others, fog of war doesn't tell the player they're in check
+        let checks = ternary!(
+            self.variant == Variant::FogOfWar,
+            (false, false),
+            (self.check_white, self.check_black)
+        );
+        match checks {
             (true, false) => {
                 if self.moves_white.is_empty() {
                     self.state = BoardState::Checkmate(ChessColor::White)
@@ -291,11 +501,7 @@ impl Board {
                     self.state = BoardState::Check(ChessColor::Black)
                 }
             }
-            (false, false) => {
-                if !check_stale {
-                    return;
-                }
-
+            (false, false) if check_stale => {
                 let moves = color_ternary!(self.turn, &self.moves_white, &self.moves_black);
 
                 if moves.is_empty() {
@@ -306,22 +512,45 @@ impl Board {
             }
             _ => {}
         };
+
+        // A mate or stalemate stands even on the move that would draw otherwise
+        if matches!(self.state, BoardState::Checkmate(_) | BoardState::Stalemate) {
+            return;
+        }
+
+        // Fifty move rule (50 moves from each side)
+        if self.fifty_rule >= 100 {
+            self.state = BoardState::Draw(DrawReason::FiftyMove);
+            return;
+        }
+
+        // 3fold repetition
+        let mut sum = 0;
+        for hash in self.prev_states.iter() {
+            if hash == &self.hash {
+                sum += 1;
+                if sum >= 3 {
+                    self.state = BoardState::Draw(DrawReason::Repetition);
+                    return;
+                }
+            }
+        }
     }
 
     /// Updates `self.blockers`
     fn update_blockers(&mut self) {
-        self.blockers = hashset! {};
+        self.blockers = SquareSet::default();
         for loc in self.attacks_white.iter() {
-            if let Some(piece) = self.get(loc) {
+            if let Some(piece) = self.get(&loc) {
                 if piece.color == ChessColor::Black {
-                    self.blockers.insert(*loc);
+                    self.blockers.insert(loc);
                 }
             }
         }
         for loc in self.attacks_black.iter() {
-            if let Some(piece) = self.get(loc) {
+            if let Some(piece) = self.get(&loc) {
                 if piece.color == ChessColor::White {
-                    self.blockers.insert(*loc);
+                    self.blockers.insert(loc);
                 }
             }
         }
@@ -343,22 +572,29 @@ impl Board {
         }
 
         if let Some(piece) = self.get(from) {
-            if piece.name == PieceNames::Pawn && from.0.abs_diff(to.0) == 1 {
-                if let Some((loc, color)) = self.en_passent {
-                    if to.0 == loc.0 && to.1.abs_diff(loc.1) == 1 && piece.color != color {
-                        return Some(loc);
-                    }
-                }
+            if piece.name == PieceNames::Pawn
+                && from.0.abs_diff(to.0) == 1
+                && self.en_passent == Some(*to)
+            {
+                return Some(loc!(to.0, from.1));
             };
         };
 
         None
     }
 
+    /// Wether moving `from` to `to` takes a pawn to the last rank
+    pub(crate) fn is_promotion(&self, from: &Loc, to: &Loc) -> bool {
+        self.get(from)
+            .is_some_and(|piece| piece.name == PieceNames::Pawn && (to.1 == 0 || to.1 == 7))
+    }
+
     /// Special actions that happen when moving a piece
     /// - IE: Castling, en passent, pawn promotion, etc...
     fn move_actions(&mut self, from: &Loc, to: &Loc) {
         let mut set_en_passent = false;
+        let mut promotion = None;
+        let promote_to = std::mem::replace(&mut self.promote_to, PieceNames::Queen);
 
         if let Some(piece) = self.raw[from.1][from.0].as_mut() {
             piece.pos = *to;
@@ -366,10 +602,7 @@ impl Board {
             match piece.name {
                 // Castle check
                 PieceNames::King => {
-                    match piece.color {
-                        ChessColor::Black => self.castle_black = (false, false),
-                        ChessColor::White => self.castle_white = (false, false),
-                    }
+                    self.castle.remove(piece.color);
 
                     if from.0.abs_diff(to.0) == 2 {
                         let (rook_from, rook_to) = match to.0 {
@@ -381,63 +614,35 @@ impl Board {
                         self.move_raw(&rook_from, &rook_to);
                     }
                 }
-                PieceNames::Rook => match from {
-                    loc!(0, 0) => self.castle_black.0 = false,
-                    loc!(7, 0) => self.castle_black.1 = false,
-                    loc!(0, 7) => self.castle_white.0 = false,
-                    loc!(7, 7) => self.castle_white.1 = false,
-                    _ => (),
-                },
                 // En passent check
                 PieceNames::Pawn => {
-                    // Promotion
+                    // Promotion, through `set` so the accumulator sees it
                     if to.1 == 0 || to.1 == 7 {
-                        piece.name = PieceNames::Queen;
+                        promotion = Some(Piece::new(promote_to, piece.color, piece.pos));
                     }
 
-                    // Setting en passent
-                    if from.1.abs_diff(to.1) == 2 {
-                        self.en_passent = Some((*to, piece.color));
+                    // Setting en passent, not for horde pawns pushed from the first rank
+                    if from.1.abs_diff(to.1) == 2 && from.1 == color_ternary!(piece.color, 6, 1) {
+                        self.en_passent = Some(loc!(to.0, (from.1 + to.1) / 2));
                         set_en_passent = true;
                     }
 
                     // En passent capture
-                    if from.0.abs_diff(to.0) == 1 {
-                        if let Some((loc, color)) = self.en_passent {
-                            if to.0 == loc.0 && to.1.abs_diff(loc.1) == 1 && piece.color != color {
-                                self.set(&loc, None);
-                            }
-                        }
+                    if from.0.abs_diff(to.0) == 1 && self.en_passent == Some(*to) {
+                        self.set(&loc!(to.0, from.1), None);
                     }
 
-                    // Fifty move rule
-                    self.fifty_rule = self.half_moves;
-
                     // When a pawn moves, the same board state can't happen again, so we clear the prev_states
                     self.prev_states.clear();
                 }
-                PieceNames::Knight => {
-                    let (kingside, queenside) = &mut self.agent_developments.0;
-                    let y = color_ternary!(piece.color, 7, 0);
-                    if from == &loc!(1, y) {
-                        *queenside = true;
-                    } else if from == &loc!(6, y) {
-                        *kingside = true;
-                    }
-                }
-                PieceNames::Bishop => {
-                    let (kingside, queenside) = &mut self.agent_developments.1;
-                    let y = color_ternary!(piece.color, 7, 0);
-                    if from == &loc!(2, y) {
-                        *queenside = true;
-                    } else if from == &loc!(5, y) {
-                        *kingside = true;
-                    }
-                }
                 _ => {}
             }
         }
 
+        if promotion.is_some() {
+            self.set(from, promotion);
+        }
+
         // Reset en passent if it hasn't been set yet
         if !set_en_passent && self.en_passent.is_some() {
             self.en_passent = None;
@@ -2,14 +2,21 @@
 //!
 //! Contains all the functions related to calculating the score of the board / move. Used for the minimax search
 
+use std::array::from_fn;
+use std::cell::RefCell;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
+
 use lazy_static::lazy_static;
-use macroquad::prelude::warn;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHasher};
 
 use crate::board::{Board, BoardState, ChessColor};
+use crate::nnue::NETWORK;
 use crate::pieces::piece::{Piece, PieceNames};
-use crate::util::Loc;
-use crate::{color_ternary, hashmap, ternary};
+use crate::platform::warn;
+use crate::util::{Loc, SquareSet};
+use crate::variant::Variant;
+use crate::{color_ternary, hashmap, loc, ternary};
 
 #[macro_export]
 macro_rules! rev_arrays {
@@ -99,21 +106,93 @@ lazy_static! {
             [-50,  -30,  -30,  -30,  -30,  -30,  -30,  -50],
         ])
     );
+
+    /// Replaces the queen's table in the opening, so it stays home until the minors are out
+    static ref OPENING_QUEEN_TABLE: (Table, Table) = rev_arrays!([
+        [-40,  -40,  -40,  -40,  -40,  -40,  -40,  -40],
+        [-40,  -40,  -40,  -40,  -40,  -40,  -40,  -40],
+        [-30,  -30,  -30,  -30,  -30,  -30,  -30,  -30],
+        [-30,  -30,  -30,  -30,  -30,  -30,  -30,  -30],
+        [-20,  -20,  -20,  -20,  -20,  -20,  -20,  -20],
+        [-15,  -10,  -10,  -10,  -10,  -10,  -10,  -15],
+        [-10,  0,    0,    0,    0,    0,    0,    -10],
+        [-20,  -10,  -10,  5,    -5,   -10,  -10,  -20],
+    ]);
 }
 
-fn piece_table(piece: &PieceNames, color: &ChessColor, endgame: bool) -> Table {
-    let table = if piece == &PieceNames::King {
-        ternary!(endgame, &KING_TABLE.1, &KING_TABLE.0)
-    } else {
-        &PIECE_TABLES[piece]
+/// Stage of the game, which picks the piece-square tables and opening penalties
+/// - Also the index into [Material::tables]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Phase {
+    Opening = 0,
+    Middlegame = 1,
+    Endgame = 2,
+}
+
+/// Full moves the opening lasts for, unless the board reaches the endgame first
+const OPENING_MOVES: u32 = 10;
+
+lazy_static! {
+    /// Zobrist keys of a pawn on each square, (`white`, `black`)
+    /// - Generated with splitmix64 from a fixed seed, so they're the same every run
+    static ref PAWN_KEYS: ([u64; 64], [u64; 64]) = {
+        let mut seed: u64 = 0;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        (from_fn(|_| next()), from_fn(|_| next()))
+    };
+}
+
+/// Entries in each thread's pawn hash table, a power of two
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+thread_local! {
+    /// (pawn key, score) of pawn structures already evaluated, indexed by the low bits of the key
+    /// - One per thread, since the agent and analysis can search at the same time
+    static PAWN_TABLE: RefCell<Vec<(u64, i32)>> = RefCell::new(vec![(0, 0); PAWN_TABLE_SIZE]);
+}
+
+/// Zobrist key of a pawn, [Board::pawn_hash] is all of these xored together
+pub(crate) fn pawn_key(color: ChessColor, loc: &Loc) -> u64 {
+    let keys = color_ternary!(color, &PAWN_KEYS.0, &PAWN_KEYS.1);
+    keys[loc.1 * 8 + loc.0]
+}
+
+pub(crate) fn piece_table(piece: &PieceNames, color: &ChessColor, phase: Phase) -> Table {
+    let table = match (piece, phase) {
+        (PieceNames::King, Phase::Endgame) => &KING_TABLE.1,
+        (PieceNames::King, _) => &KING_TABLE.0,
+        (PieceNames::Queen, Phase::Opening) => &OPENING_QUEEN_TABLE,
+        _ => &PIECE_TABLES[piece],
     };
 
     color_ternary!(*color, table.0, table.1)
 }
 
-fn table_value(piece: &Piece, endgame: bool) -> i32 {
-    let table = piece_table(&piece.name, &piece.color, endgame);
-    table[piece.pos.1][piece.pos.0]
+fn table_value(piece: &Piece, loc: &Loc, phase: Phase) -> i32 {
+    let table = piece_table(&piece.name, &piece.color, phase);
+    table[loc.1][loc.0]
+}
+
+/// Whether a piece is on a square it starts the game on, pawns and kings never are
+pub(crate) fn on_start_square(piece: &Piece) -> bool {
+    if piece.pos.1 != color_ternary!(piece.color, 7, 0) {
+        return false;
+    }
+
+    match piece.name {
+        PieceNames::Knight | PieceNames::Bishop => MINOR_STARTS
+            .iter()
+            .any(|(x, name)| *x == piece.pos.0 && *name == piece.name),
+        PieceNames::Rook => piece.pos.0 == 0 || piece.pos.0 == 7,
+        PieceNames::Queen => piece.pos.0 == 3,
+        PieceNames::Pawn | PieceNames::King => false,
+    }
 }
 
 pub(crate) fn piece_value(piece: &PieceNames) -> i32 {
@@ -127,15 +206,330 @@ pub(crate) fn piece_value(piece: &PieceNames) -> i32 {
     }
 }
 
-fn full_piece_value(piece: &Piece, endgame: bool) -> i32 {
-    piece.value() + table_value(piece, endgame)
+/// Every piece, in the order of [PieceNames], which indexes tables and weights
+#[cfg(feature = "gui")]
+pub(crate) const PIECES: [PieceNames; 6] = [
+    PieceNames::Pawn,
+    PieceNames::Bishop,
+    PieceNames::Knight,
+    PieceNames::Rook,
+    PieceNames::Queen,
+    PieceNames::King,
+];
+
+/// How much each piece's table counts, indexed by [PieceNames], tweaked live in the table editor
+/// - Stored as [f32] bits, so searches on other threads can read them
+static PST_WEIGHTS: [AtomicU32; 6] = [const { AtomicU32::new(1.0f32.to_bits()) }; 6];
+
+/// Weights of each piece's table, see [PST_WEIGHTS]
+pub(crate) fn pst_weights() -> [f32; 6] {
+    from_fn(|i| f32::from_bits(PST_WEIGHTS[i].load(Ordering::Relaxed)))
+}
+
+#[cfg(feature = "gui")]
+pub(crate) fn set_pst_weight(piece: &PieceNames, weight: f32) {
+    PST_WEIGHTS[*piece as usize].store(weight.to_bits(), Ordering::Relaxed);
+}
+
+/// `piece`'s table for `color` in `phase`, scaled by its weight
+#[cfg(feature = "gui")]
+pub(crate) fn weighted_table(piece: &PieceNames, color: &ChessColor, phase: Phase) -> Table {
+    let weight = pst_weights()[*piece as usize];
+    piece_table(piece, color, phase).map(|row| row.map(|value| weigh(value, weight)))
+}
+
+fn weigh(value: i32, weight: f32) -> i32 {
+    (value as f32 * weight).round() as i32
+}
+
+/// Material and piece-square sums for white, see [Board::material]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Material {
+    /// Piece values
+    pub(crate) values: i32,
+    /// Piece-square table bonuses by [Phase], then by [PieceNames]
+    /// - Kept apart so the [pst_weights] can change without summing them again
+    pub(crate) tables: [[i32; 6]; 3],
+}
+impl Material {
+    /// Adds `piece` on `loc`, or takes it off with a `sign` of `-1`
+    pub(crate) fn add(&mut self, piece: &Piece, loc: &Loc, sign: i32) {
+        let sign = color_ternary!(piece.color, sign, -sign);
+        self.values += sign * piece.value();
+        for phase in [Phase::Opening, Phase::Middlegame, Phase::Endgame] {
+            self.tables[phase as usize][piece.name as usize] +=
+                sign * table_value(piece, loc, phase);
+        }
+    }
+
+    /// Piece-square bonuses in `phase`, each scaled by its weight
+    pub(crate) fn tables(&self, phase: Phase, weights: &[f32; 6]) -> i32 {
+        self.tables[phase as usize]
+            .iter()
+            .zip(weights)
+            .map(|(sum, weight)| weigh(*sum, *weight))
+            .sum()
+    }
+}
+
+/// Each part of [Board::score] before scaling for the endgame, for white
+/// - Shown by the table editor, to see what a change of weights does
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct EvalTerms {
+    pub(crate) check: i32,
+    /// Piece values
+    pub(crate) material: i32,
+    /// Piece-square table bonuses, scaled by the [pst_weights]
+    pub(crate) tables: i32,
+    /// Penalties for early queen moves and moving pieces twice
+    pub(crate) opening: i32,
+    /// Squares attacked
+    pub(crate) mobility: i32,
+    pub(crate) pawns: i32,
+    /// Bonuses for what wins the variant being played, IE checks given in three-check
+    pub(crate) variant: i32,
+}
+impl EvalTerms {
+    pub(crate) fn total(&self) -> i32 {
+        self.check
+            + self.material
+            + self.tables
+            + self.opening
+            + self.mobility
+            + self.pawns
+            + self.variant
+    }
+
+    /// One line per term and the total, in pawns for white
+    #[cfg(feature = "gui")]
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let terms = [
+            ("Check", self.check),
+            ("Material", self.material),
+            ("Tables", self.tables),
+            ("Opening", self.opening),
+            ("Mobility", self.mobility),
+            ("Pawns", self.pawns),
+            ("Variant", self.variant),
+            ("Total", self.total()),
+        ];
+        terms
+            .iter()
+            .map(|(name, score)| format!("{:<9}{:+.2}", name, *score as f32 / 100.0))
+            .collect()
+    }
+}
+
+/// Rows of a table like they're written in this file, so exported tables can be pasted over them
+#[cfg(feature = "gui")]
+fn format_table(table: &Table) -> String {
+    let rows: Vec<_> = table
+        .iter()
+        .map(|row| {
+            let values: Vec<_> = row
+                .iter()
+                .map(|value| format!("{:<6}", format!("{},", value)))
+                .collect();
+            format!(
+                "    [{}],",
+                values.concat().trim_end().trim_end_matches(',')
+            )
+        })
+        .collect();
+    rows.join("\n")
+}
+
+/// Every table scaled by its weight, in the format of [PIECE_TABLES], [KING_TABLE], and [OPENING_QUEEN_TABLE]
+#[cfg(feature = "gui")]
+pub(crate) fn export_tables() -> String {
+    let weights = pst_weights();
+    let scaled = |table: &Table, piece: PieceNames| {
+        format_table(&table.map(|row| row.map(|value| weigh(value, weights[piece as usize]))))
+    };
+
+    let mut lines = vec!["// PIECE_TABLES".to_string()];
+    for piece in &PIECES[..5] {
+        lines.push(format!("PieceNames::{:?} => rev_arrays!([", piece));
+        lines.push(scaled(&PIECE_TABLES[piece].0, *piece));
+        lines.push("]),".to_string());
+    }
+    lines.push("// KING_TABLE, middle game then end game".to_string());
+    for table in [&KING_TABLE.0 .0, &KING_TABLE.1 .0] {
+        lines.push("rev_arrays!([".to_string());
+        lines.push(scaled(table, PieceNames::King));
+        lines.push("]),".to_string());
+    }
+    lines.push("// OPENING_QUEEN_TABLE".to_string());
+    lines.push("rev_arrays!([".to_string());
+    lines.push(scaled(&OPENING_QUEEN_TABLE.0, PieceNames::Queen));
+    lines.push("]);".to_string());
+    lines.join("\n")
 }
 
 const CHECK_VALUE: i32 = 50;
+/// Per minor piece still undeveloped, while the queen is off its starting square in the opening
+const QUEEN_SORTIE_VALUE: i32 = -15;
+/// Per move in the opening by a piece that had already moved
+const REPEATED_MOVE_VALUE: i32 = -15;
 const CHECKMATE_VALUE: i32 = 20000;
+/// Bonus in three-check by how many checks a side has given, the third wins
+const CHECKS_GIVEN_VALUES: [i32; 3] = [0, 150, 450];
+/// Per square around the other king a side attacks in atomic
+const KING_ZONE_ATTACK_VALUE: i32 = 25;
+/// Per rank a side's king is ahead of the other's in racing kings
+const KING_RACE_VALUE: i32 = 60;
 const STALEMATE_VALUE: i32 = -100;
+const DOUBLED_PAWN_VALUE: i32 = -15;
+const ISOLATED_PAWN_VALUE: i32 = -10;
+/// Bonus for a passed pawn by how many ranks it has advanced
+const PASSED_PAWN_VALUES: [i32; 6] = [0, 10, 20, 35, 60, 100];
+/// Scores this close to [CHECKMATE_VALUE] are mates, with the difference being the plies until mate
+const MATE_WINDOW: i32 = 256;
+
+/// Evaluation used at the leaves of the search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum EvalBackend {
+    /// [Board::score]
+    Classic,
+    /// The NNUE network, falling back to classic if there isn't one loaded
+    Nnue,
+}
+impl EvalBackend {
+    /// Score of the board for white
+    pub(crate) fn evaluate(&self, board: &Board) -> i32 {
+        match (self, NETWORK.as_ref()) {
+            // Finished games keep the classic mate and draw scores
+            (EvalBackend::Nnue, Some(network)) if !board.is_over() => {
+                board.scale_endgame(network.evaluate(&board.accumulator))
+            }
+            _ => board.score,
+        }
+    }
+}
+
+/// Wether a score is a forced mate, or a game won by mate, for either side
+#[cfg(feature = "gui")]
+pub(crate) fn is_mate(score: i32) -> bool {
+    score.unsigned_abs() > (CHECKMATE_VALUE - MATE_WINDOW) as u32
+}
+
+/// Moves until mate for a forced mate score, negative when the side it's against mates
+/// - `None` once the game is over, IE the score of a mated board
+#[cfg(feature = "gui")]
+pub(crate) fn mate_moves(score: i32) -> Option<i32> {
+    let plies = CHECKMATE_VALUE.saturating_sub_unsigned(score.unsigned_abs());
+    (is_mate(score) && plies > 0).then(|| score.signum() * (plies + 1) / 2)
+}
+
+/// Moves a mate score from a position `ply` half moves into a search to the search's first position, so quicker mates
+/// score higher, other scores are kept as they are
+/// - A negative `ply` moves it back, IE to keep it in the transposition table
+pub(crate) fn mate_from_root(score: i32, ply: i32) -> i32 {
+    ternary!(
+        score.abs() > CHECKMATE_VALUE - MATE_WINDOW && score.abs() <= CHECKMATE_VALUE,
+        score - score.signum() * ply,
+        score
+    )
+}
+
+/// Score for the side to move that mates in `moves`, negative when it's mated, the inverse of [mate_moves]
+#[cfg_attr(target_family = "wasm", allow(dead_code))]
+pub(crate) fn mate_score(moves: i32) -> i32 {
+    moves.signum() * (CHECKMATE_VALUE - moves.abs() * 2 + 1)
+}
+
+/// Formats a score (for white) in pawns from the perspective of `color`, IE `+1.5`, `-0.3`, `M2` for mates, or `#`
+/// once mated
+#[cfg(feature = "gui")]
+pub(crate) fn display_score(score: i32, color: ChessColor) -> String {
+    let score = color_ternary!(color, score, score.saturating_neg());
+
+    if let Some(moves) = mate_moves(score) {
+        return format!("{}M{}", ternary!(moves < 0, "-", ""), moves.abs());
+    }
+    if is_mate(score) {
+        return ternary!(score < 0, "-#", "#").to_owned();
+    }
+
+    format!("{:+.1}", score as f32 / 100.0)
+}
+
+/// Formats a score (for white) like the PGN `%eval` command, IE `1.50`, `-0.30`, `#-2` for mates, or `#0` once mated
+#[cfg(feature = "gui")]
+pub(crate) fn eval_tag(score: i32) -> String {
+    if let Some(moves) = mate_moves(score) {
+        return format!("#{}", moves);
+    }
+    if is_mate(score) {
+        return ternary!(score < 0, "#-0", "#0").to_owned();
+    }
+
+    format!("{:.2}", score as f32 / 100.0)
+}
+
+/// Starting squares (x only) of the minor pieces, the same for both colors
+const MINOR_STARTS: [(usize, PieceNames); 4] = [
+    (1, PieceNames::Knight),
+    (6, PieceNames::Knight),
+    (2, PieceNames::Bishop),
+    (5, PieceNames::Bishop),
+];
 
 impl Board {
+    pub(crate) fn phase(&self) -> Phase {
+        if self.endgame {
+            Phase::Endgame
+        } else if self.full_moves() < OPENING_MOVES {
+            Phase::Opening
+        } else {
+            Phase::Middlegame
+        }
+    }
+
+    /// Key for the transposition table, which needs to tell apart everything [Board::score] does
+    /// - In the opening that includes [Board::repeated_moves], which [Board::hash] leaves out so repeated positions
+    ///   still match
+    pub(crate) fn tt_key(&self) -> u64 {
+        if self.phase() != Phase::Opening {
+            return self.hash;
+        }
+        let mut hasher = FxHasher::default();
+        self.hash.hash(&mut hasher);
+        self.repeated_moves.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Penalties for `color` for early queen moves and moving the same pieces again, for white
+    fn opening_score(&self, color: ChessColor) -> i32 {
+        let mut score = 0;
+
+        let queen_home = self
+            .raw
+            .iter()
+            .flatten()
+            .flatten()
+            .any(|p| p.name == PieceNames::Queen && p.color == color && on_start_square(p));
+        if !queen_home {
+            score += QUEEN_SORTIE_VALUE * self.undeveloped_minors(color) as i32;
+        }
+
+        let repeated = color_ternary!(color, self.repeated_moves.0, self.repeated_moves.1);
+        score += REPEATED_MOVE_VALUE * repeated as i32;
+
+        color_ternary!(color, score, -score)
+    }
+
+    /// Number of knights and bishops of `color` still sitting on their starting squares
+    pub(crate) fn undeveloped_minors(&self, color: ChessColor) -> usize {
+        let y = color_ternary!(color, 7, 0);
+        MINOR_STARTS
+            .iter()
+            .filter(|(x, name)| {
+                matches!(self.get(&loc!(*x, y)), Some(piece) if &piece.name == name && piece.color == color)
+            })
+            .count()
+    }
+
     pub(crate) fn sorted_moves(&self, color: ChessColor) -> Vec<(Loc, Loc)> {
         let mut moves = self.moves(color);
 
@@ -156,30 +550,179 @@ impl Board {
 
     /// Calculates the score of the board, for the white
     pub(crate) fn score(&self) -> i32 {
-        let mut score = 0;
-
         match self.state {
-            BoardState::Checkmate(check_color) => {
-                return color_ternary!(check_color, -CHECKMATE_VALUE, CHECKMATE_VALUE);
+            BoardState::Checkmate(check_color)
+            | BoardState::ThreeChecks(check_color)
+            | BoardState::Exploded(check_color)
+            | BoardState::KingCaptured(check_color)
+            | BoardState::Outraced(check_color)
+            | BoardState::NoPieces(check_color) => {
+                color_ternary!(check_color, -CHECKMATE_VALUE, CHECKMATE_VALUE)
+            }
+            BoardState::Stalemate | BoardState::Draw(_) => STALEMATE_VALUE,
+            _ => self.scale_endgame(self.eval_terms().total()),
+        }
+    }
+
+    /// Score for white of a position where the side to move has no legal moves, checkmate or stalemate
+    /// - For searches, which play moves without looking for the end of the game
+    pub(crate) fn no_moves_score(&self) -> i32 {
+        match color_ternary!(self.turn, self.check_white, self.check_black) {
+            true => color_ternary!(self.turn, -CHECKMATE_VALUE, CHECKMATE_VALUE),
+            false => STALEMATE_VALUE,
+        }
+    }
+
+    /// Parts of [Board::score] for a game that isn't over
+    pub(crate) fn eval_terms(&self) -> EvalTerms {
+        let mut terms = EvalTerms::default();
+
+        if let BoardState::Check(check_color) = self.state {
+            terms.check = color_ternary!(check_color, -CHECK_VALUE, CHECK_VALUE);
+        }
+
+        // Add value based on pieces, kept up to date as they move
+        debug_assert_eq!(
+            self.material,
+            self.full_material(),
+            "material out of sync in {}",
+            self.as_fen()
+        );
+        let phase = self.phase();
+        terms.material = self.material.values;
+        terms.tables = self.material.tables(phase, &pst_weights());
+
+        if phase == Phase::Opening {
+            terms.opening =
+                self.opening_score(ChessColor::White) + self.opening_score(ChessColor::Black);
+        }
+
+        // Add value based on attacks
+        terms.mobility = self.attacks_white.len() as i32 - self.attacks_black.len() as i32;
+
+        terms.pawns = self.pawn_score();
+        terms.variant = self.variant_score();
+        terms
+    }
+
+    /// [EvalTerms::variant], for white
+    fn variant_score(&self) -> i32 {
+        match self.variant {
+            Variant::ThreeCheck => {
+                // The last check wins, so a finished game is only scored up to the one before
+                let given = |checks: u8| {
+                    CHECKS_GIVEN_VALUES[(checks as usize).min(CHECKS_GIVEN_VALUES.len() - 1)]
+                };
+                given(self.checks.0) - given(self.checks.1)
             }
-            BoardState::Stalemate | BoardState::Draw => {
-                return STALEMATE_VALUE;
+            // Pieces aimed next to a king can blow it up with any capture there
+            Variant::Atomic => {
+                let (white_king, black_king) = self.kings();
+                let zone_attacks = |king: Option<Loc>, attacks: SquareSet| {
+                    king.map_or(0, |king| {
+                        attacks
+                            .iter()
+                            .filter(|loc| {
+                                loc.0.abs_diff(king.0) <= 1 && loc.1.abs_diff(king.1) <= 1
+                            })
+                            .count() as i32
+                    })
+                };
+                KING_ZONE_ATTACK_VALUE
+                    * (zone_attacks(black_king, self.attacks_white)
+                        - zone_attacks(white_king, self.attacks_black))
             }
-            BoardState::Check(check_color) => {
-                color_ternary!(check_color, score -= CHECK_VALUE, score += CHECK_VALUE);
+            // Ranks are counted from the top, so the king further ahead has the lower one
+            Variant::RacingKings => {
+                let (white_king, black_king) = self.kings();
+                match (white_king, black_king) {
+                    (Some(white_king), Some(black_king)) => {
+                        KING_RACE_VALUE * (black_king.1 as i32 - white_king.1 as i32)
+                    }
+                    _ => 0,
+                }
             }
-            _ => {}
+            Variant::Standard | Variant::FogOfWar | Variant::Horde => 0,
         }
+    }
 
-        // Add value based on pieces
+    /// [Board::material] computed from scratch
+    pub(crate) fn full_material(&self) -> Material {
+        let mut material = Material::default();
         for piece in self.raw.iter().flatten().flatten() {
-            let value = full_piece_value(piece, self.endgame);
-            color_ternary!(piece.color, score += value, score -= value);
+            material.add(piece, &piece.pos, 1);
         }
+        material
+    }
 
-        // Add value based on attacks
-        score += self.attacks_white.len() as i32;
-        score -= self.attacks_black.len() as i32;
+    /// [Board::pawn_structure], cached in the pawn hash table by [Board::pawn_hash]
+    pub(crate) fn pawn_score(&self) -> i32 {
+        let index = self.pawn_hash as usize & (PAWN_TABLE_SIZE - 1);
+        if let Some(score) = PAWN_TABLE.with_borrow(|table| {
+            let (key, score) = table[index];
+            ternary!(key == self.pawn_hash, Some(score), None)
+        }) {
+            return score;
+        }
+
+        let score = self.pawn_structure();
+        PAWN_TABLE.with_borrow_mut(|table| table[index] = (self.pawn_hash, score));
+        score
+    }
+
+    /// Score of the pawn structure for white, from doubled, isolated, and passed pawns
+    pub(crate) fn pawn_structure(&self) -> i32 {
+        let mut files = [[0; 8]; 2];
+        let pawns: Vec<Piece> = self
+            .raw
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|piece| piece.name == PieceNames::Pawn)
+            .copied()
+            .collect();
+        for pawn in pawns.iter() {
+            files[color_ternary!(pawn.color, 0, 1)][pawn.pos.0] += 1;
+        }
+
+        let mut score = 0;
+        for pawn in pawns.iter() {
+            let own = color_ternary!(pawn.color, 0, 1);
+            let x = pawn.pos.0;
+            let neighbors = x.saturating_sub(1)..=(x + 1).min(7);
+            let mut value = 0;
+
+            if neighbors.clone().all(|f| f == x || files[own][f] == 0) {
+                value += ISOLATED_PAWN_VALUE;
+            }
+
+            // No enemy pawns in front, on this file or either side
+            let passed = !pawns.iter().any(|other| {
+                other.color != pawn.color
+                    && neighbors.contains(&other.pos.0)
+                    && color_ternary!(
+                        pawn.color,
+                        other.pos.1 < pawn.pos.1,
+                        other.pos.1 > pawn.pos.1
+                    )
+            });
+            if passed {
+                let advanced = color_ternary!(
+                    pawn.color,
+                    6usize.saturating_sub(pawn.pos.1),
+                    pawn.pos.1.saturating_sub(1)
+                );
+                value += PASSED_PAWN_VALUES[advanced.min(PASSED_PAWN_VALUES.len() - 1)];
+            }
+
+            color_ternary!(pawn.color, score += value, score -= value);
+        }
+
+        // Every pawn past the first on a file
+        for (white, black) in files[0].iter().zip(files[1].iter()) {
+            score += DOUBLED_PAWN_VALUE * (white - 1).max(0);
+            score -= DOUBLED_PAWN_VALUE * (black - 1).max(0);
+        }
 
         score
     }
@@ -204,15 +747,15 @@ impl Board {
             PieceNames::King if self.endgame && from.0.abs_diff(to.0) != 2 => {
                 score -= 20;
             }
-            // Moving king without developed minors
-            PieceNames::Queen if self.agent_developments != ((true, true), (true, true)) => {
+            // Moving queen without developed minors
+            PieceNames::Queen if self.undeveloped_minors(piece.color) != 0 => {
                 score -= 100;
             }
             _ => {}
         }
 
         // Position change
-        let table = piece_table(&piece.name, &piece.color, self.endgame);
+        let table = piece_table(&piece.name, &piece.color, self.phase());
         score += table[to.1][to.0] - table[from.1][from.0];
 
         // Add value based on capture
@@ -2,38 +2,111 @@
 //!
 //! Extra fen and util functions for [Board]
 
+#[cfg(feature = "gui")]
 use std::f32::consts::{FRAC_PI_2, FRAC_PI_3, PI};
 use std::hash::{Hash, Hasher};
 
-use macroquad::prelude::{vec2, WHITE};
+#[cfg(feature = "gui")]
+use macroquad::prelude::{vec2, Color, WHITE};
+#[cfg(feature = "gui")]
 use macroquad::shapes::{draw_circle, draw_circle_lines, draw_line, draw_rectangle, draw_triangle};
-#[cfg(not(target_family = "wasm"))]
+#[cfg(feature = "gui")]
+use macroquad::text::{draw_text_ex, TextParams};
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
 use macroquad::texture::draw_texture;
+#[cfg(feature = "gui")]
 use macroquad::texture::{draw_texture_ex, DrawTextureParams};
-use rustc_hash::{FxHashSet, FxHasher};
+use rustc_hash::FxHasher;
+#[cfg(feature = "gui")]
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use crate::board::{Board, BoardState, ChessColor};
+use crate::board::{Board, BoardState, CastleRights, ChessColor};
+use crate::board_eval::pawn_key;
+#[cfg(feature = "gui")]
 use crate::conf::{
-    COLOR_ARROW, COLOR_BLACK, COLOR_HIGHLIGHT, COLOR_LAST_MOVE, COLOR_SELECTED, COLOR_WHITE,
-    MARGIN, SQUARE_SIZE,
+    COLOR_ARROW, COLOR_FOG, COLOR_HIGHLIGHT, COLOR_HINT_ARROW, COLOR_ILLEGAL, COLOR_LAST_MOVE,
+    COLOR_SELECTED, COLOR_TABLE_VALUE, COLOR_THREAT_ARROW, THEMES,
 };
+use crate::error::ChessError;
+#[cfg(feature = "gui")]
+use crate::font;
+#[cfg(feature = "gui")]
+use crate::layout::layout;
+use crate::nnue::NETWORK;
 use crate::pieces::piece::{Piece, PieceNames};
-use crate::util::{angle, board_to_pos_center, distance, project, validate_fen, Loc, Tween};
-use crate::{color_ternary, hashset, loc};
-
+#[cfg(feature = "gui")]
+use crate::settings::{settings, Visibility};
+#[cfg(feature = "gui")]
+use crate::ternary;
+#[cfg(feature = "gui")]
+use crate::util::{angle, board_to_pos_center, distance, project, Tween};
+use crate::util::{validate_fen, Loc, SquareSet};
+use crate::variant::Variant;
+use crate::{color_ternary, loc};
+
+#[cfg(feature = "gui")]
 #[rustfmt::skip]
 const ENUMERATES: [(usize, usize); 64] = [(0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (0, 1), (1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (7, 1), (0, 2), (1, 2), (2, 2), (3, 2), (4, 2), (5, 2), (6, 2), (7, 2), (0, 3), (1, 3), (2, 3), (3, 3), (4, 3), (5, 3), (6, 3), (7, 3), (0, 4), (1, 4), (2, 4), (3, 4), (4, 4), (5, 4), (6, 4), (7, 4), (0, 5), (1, 5), (2, 5), (3, 5), (4, 5), (5, 5), (6, 5), (7, 5), (0, 6), (1, 6), (2, 6), (3, 6), (4, 6), (5, 6), (6, 6), (7, 6), (0, 7), (1, 7), (2, 7), (3, 7), (4, 7), (5, 7), (6, 7), (7, 7)];
 
+/// What [Board::draw] shows on top of the squares
+#[cfg(feature = "gui")]
+pub(crate) struct DrawOptions<'a> {
+    /// Squares the selected piece can move to
+    pub(crate) highlight_moves: &'a [Loc],
+    pub(crate) last_move: Option<(Loc, Loc)>,
+    pub(crate) highlights: &'a FxHashSet<Loc>,
+    /// Arrows the player drew, on top of the rest
+    /// - Arrows are drawn in layers, each in its own color, from the bottom: engine, threat, hint, and the player's
+    pub(crate) arrows: &'a [(Loc, Loc)],
+    /// Moves shown to the player, IE the right move in a drill
+    pub(crate) hint_arrows: &'a [(Loc, Loc)],
+    /// Move the agent threatens
+    pub(crate) threat_arrow: Option<(Loc, Loc)>,
+    /// Arrows in their own colors, IE what the agent considered
+    pub(crate) engine_arrows: &'a [((Loc, Loc), Color)],
+    /// Which pieces are drawn, from the side of [Board::player_color]
+    pub(crate) visibility: Visibility,
+    /// Squares that can be seen in a fog of war game, the rest are fogged over with the opponent's pieces on them hidden
+    pub(crate) revealed: Option<SquareSet>,
+    /// Square of a move that couldn't be made, and how strong its red flash still is from `1.0` to `0.0`
+    pub(crate) illegal: Option<(Loc, f32)>,
+    /// Wether the board is drawn from black's side, with rank 1 at the top
+    pub(crate) flipped: bool,
+    /// Number drawn in the corner of each square, IE a piece-square table in the table editor
+    pub(crate) values: Option<[[i32; 8]; 8]>,
+}
+#[cfg(feature = "gui")]
+impl DrawOptions<'_> {
+    /// Where the square at `loc` is drawn, turned around when [DrawOptions::flipped]
+    fn view(&self, loc: Loc) -> Loc {
+        ternary!(self.flipped, loc.rotated(), loc)
+    }
+
+    /// Wether the square at `loc` can be seen, see [DrawOptions::revealed]
+    fn seen(&self, loc: &Loc) -> bool {
+        self.revealed.is_none_or(|revealed| revealed.contains(loc))
+    }
+}
+
 impl Board {
     /// Generate a new board given a FEN string
+    /// - Panics on malformed FEN, use [Board::try_from_fen] for FEN from the user
     pub(crate) fn from_fen(fen: &str) -> Board {
+        let mut board = Board::parse_fen(fen).unwrap_or_else(|err| panic!("{}", err));
+        board.update_things(true);
+        board
+    }
+
+    /// Board from a FEN string without moves, checks, or the score worked out yet
+    pub(crate) fn parse_fen(fen: &str) -> Result<Board, ChessError> {
         let mut fen_parts = fen.split_whitespace();
+        let mut next_part = |part| fen_parts.next().ok_or(ChessError::Fen(part));
 
         /* -------------------------------- Board fen ------------------------------- */
-        let board_fen = fen_parts.next().unwrap_or_else(|| panic!("Invalid FEN!"));
+        let board_fen = next_part("board")?;
 
         if !validate_fen(board_fen) {
-            panic!("Invalid FEN! (board)");
+            return Err(ChessError::Fen("board"));
         }
 
         let mut board = Board::new();
@@ -59,59 +132,59 @@ impl Board {
             } else {
                 ChessColor::Black
             };
-            let name = char_to_piece(&c);
+            let name = char_to_piece(&c)?;
             board.set(&loc!(x, y), Some(Piece::new(name, color, loc!(x, y))));
             x += 1;
         }
 
         /* ----------------------------- Extra fen data ----------------------------- */
-        board.turn = match fen_parts.next().unwrap_or_else(|| panic!("Invalid FEN!")) {
+        board.turn = match next_part("turn")? {
             "w" => ChessColor::White,
             "b" => ChessColor::Black,
-            _ => panic!("Invalid FEN (turn)"),
+            _ => return Err(ChessError::Fen("turn")),
         };
 
-        let castle_fen = fen_parts.next().unwrap_or_else(|| panic!("Invalid FEN!"));
+        let castle_fen = next_part("castling")?;
+        board.castle = CastleRights::default();
         for char in castle_fen.chars() {
             match char {
-                'K' => board.castle_white.1 = true,
-                'Q' => board.castle_white.0 = true,
-                'k' => board.castle_black.1 = true,
-                'q' => board.castle_black.0 = true,
+                'K' => board.castle.white_kingside = true,
+                'Q' => board.castle.white_queenside = true,
+                'k' => board.castle.black_kingside = true,
+                'q' => board.castle.black_queenside = true,
                 '-' => {}
-                _ => panic!("Invalid FEN (castling)"),
+                _ => return Err(ChessError::Fen("castling")),
             }
         }
 
-        match fen_parts.next().unwrap_or_else(|| panic!("Invalid FEN!")) {
+        match next_part("en passant")? {
             "-" => {}
             en_passant => {
-                let loc = Loc::from_notation(en_passant);
-                board.en_passent = Some((
-                    loc,
-                    board
-                        .get(&loc)
-                        .unwrap_or_else(|| panic!("Invalid FEN! (en passent)"))
-                        .color,
-                ));
+                let loc =
+                    Loc::parse_notation(en_passant).map_err(|_| ChessError::Fen("en passant"))?;
+                if loc.1 != color_ternary!(board.turn, 2, 5) {
+                    return Err(ChessError::Fen("en passant"));
+                }
+                board.en_passent = Some(loc);
             }
         }
 
-        board.fifty_rule = fen_parts
-            .next()
-            .unwrap_or_else(|| panic!("Invalid FEN!"))
+        let half_move_clock: u32 = next_part("fifty rule")?
             .parse()
-            .unwrap_or_else(|_| panic!("Invalid FEN! (fifty rule)"));
-        let full_moves: u32 = fen_parts
-            .next()
-            .unwrap_or_else(|| panic!("Invalid FEN!"))
+            .map_err(|_| ChessError::Fen("fifty rule"))?;
+        let full_moves: u32 = next_part("full moves")?
             .parse()
-            .unwrap_or_else(|_| panic!("Invalid FEN! (full moves)"));
+            .ok()
+            .filter(|moves| *moves > 0)
+            .ok_or(ChessError::Fen("full moves"))?;
         board.half_moves =
             color_ternary!(board.turn, (full_moves - 1) * 2, (full_moves - 1) * 2 + 1);
+        board.fifty_rule = half_move_clock;
 
-        board.update_things(true);
-        board
+        // The starting position counts towards repetitions too
+        board.hash = board.hash();
+        board.prev_states.push(board.hash);
+        Ok(board)
     }
 
     /// Export the board into FEN
@@ -154,36 +227,30 @@ impl Board {
         fen.push(color_ternary!(self.turn, 'w', 'b'));
 
         fen.push(' ');
-        if !self.castle_white.0
-            && !self.castle_white.1
-            && !self.castle_black.0
-            && !self.castle_black.1
-        {
+        if self.castle.is_empty() {
             fen.push('-');
         } else {
-            if self.castle_white.0 {
-                fen.push('K');
-            }
-            if self.castle_white.1 {
-                fen.push('Q');
-            }
-            if self.castle_black.0 {
-                fen.push('k');
-            }
-            if self.castle_black.1 {
-                fen.push('q');
+            for (right, c) in [
+                (self.castle.white_kingside, 'K'),
+                (self.castle.white_queenside, 'Q'),
+                (self.castle.black_kingside, 'k'),
+                (self.castle.black_queenside, 'q'),
+            ] {
+                if right {
+                    fen.push(c);
+                }
             }
         }
 
         fen.push(' ');
         if let Some(en_passent) = self.en_passent {
-            fen.push_str(&en_passent.0.as_notation())
+            fen.push_str(&en_passent.as_notation())
         } else {
             fen.push('-');
         }
 
         fen.push(' ');
-        fen.push_str(&(self.half_moves - self.fifty_rule).to_string());
+        fen.push_str(&self.fifty_rule.to_string());
 
         fen.push(' ');
         fen.push_str(&(self.full_moves() + 1).to_string());
@@ -192,56 +259,77 @@ impl Board {
     }
 
     /// Draws the board to the screen
+    #[cfg(feature = "gui")]
     #[allow(unused_variables)]
-    pub(crate) fn draw(
-        &self,
-        highlight_moves: &[Loc],
-        last_move: &Option<(Loc, Loc)>,
-        highlights: &FxHashSet<Loc>,
-        arrows: &[(Loc, Loc)],
-        current_tween: &mut Option<(Loc, Tween)>,
-    ) {
+    pub(crate) fn draw(&self, options: &DrawOptions, current_tween: &mut [(Loc, Tween)]) {
+        let layout = layout();
         for (x, y) in ENUMERATES {
-            let color = if (x + y) % 2 == 0 {
-                COLOR_WHITE
-            } else {
-                COLOR_BLACK
-            };
+            let Loc(sx, sy) = options.view(loc!(x, y));
+            let (_, light, dark) = THEMES[settings().theme];
+            let color = if (x + y) % 2 == 0 { light } else { dark };
 
             draw_rectangle(
-                MARGIN + SQUARE_SIZE * x as f32,
-                MARGIN + SQUARE_SIZE * y as f32,
-                SQUARE_SIZE,
-                SQUARE_SIZE,
+                layout.margin + layout.square_size * sx as f32,
+                layout.top + layout.square_size * sy as f32,
+                layout.square_size,
+                layout.square_size,
                 color,
             );
 
-            if let Some(last_move) = last_move {
+            if let Some(last_move) = options.last_move.filter(|_| options.seen(&loc!(x, y))) {
                 if last_move.0 == loc!(x, y) || last_move.1 == loc!(x, y) {
                     draw_rectangle(
-                        MARGIN + SQUARE_SIZE * x as f32,
-                        MARGIN + SQUARE_SIZE * y as f32,
-                        SQUARE_SIZE,
-                        SQUARE_SIZE,
+                        layout.margin + layout.square_size * sx as f32,
+                        layout.top + layout.square_size * sy as f32,
+                        layout.square_size,
+                        layout.square_size,
                         COLOR_LAST_MOVE,
                     );
                 }
             }
+
+            if let Some((_, strength)) = options.illegal.filter(|(loc, _)| loc == &loc!(x, y)) {
+                draw_rectangle(
+                    layout.margin + layout.square_size * sx as f32,
+                    layout.top + layout.square_size * sy as f32,
+                    layout.square_size,
+                    layout.square_size,
+                    Color {
+                        a: COLOR_ILLEGAL.a * strength,
+                        ..COLOR_ILLEGAL
+                    },
+                );
+            }
+
+            if !options.seen(&loc!(x, y)) {
+                draw_rectangle(
+                    layout.margin + layout.square_size * sx as f32,
+                    layout.top + layout.square_size * sy as f32,
+                    layout.square_size,
+                    layout.square_size,
+                    COLOR_FOG,
+                );
+            }
         }
 
         for (y, row) in self.raw.iter().enumerate() {
             for (x, square) in row.iter().enumerate() {
-                // Draw piece
-                if let Some(piece) = square {
+                let Loc(sx, sy) = options.view(loc!(x, y));
+                // Draw piece, skipping ones whose image didn't load
+                if let Some(image) = square
+                    .filter(|piece| options.visibility.shows(piece.color, self.player_color))
+                    .filter(|_| options.seen(&loc!(x, y)))
+                    .and_then(|piece| piece.image().ok())
+                {
                     #[cfg(target_family = "wasm")]
                     {
                         draw_texture_ex(
-                            piece.image(),
-                            MARGIN + SQUARE_SIZE * x as f32,
-                            MARGIN + SQUARE_SIZE * y as f32,
+                            image,
+                            layout.margin + layout.square_size * sx as f32,
+                            layout.top + layout.square_size * sy as f32,
                             WHITE,
                             DrawTextureParams {
-                                dest_size: Some(vec2(SQUARE_SIZE, SQUARE_SIZE)),
+                                dest_size: Some(vec2(layout.square_size, layout.square_size)),
                                 ..Default::default()
                             },
                         )
@@ -251,13 +339,14 @@ impl Board {
                     {
                         let mut tweened = false;
 
-                        if let Some((loc, tween)) = current_tween {
+                        for (loc, tween) in current_tween.iter_mut() {
                             if loc == &loc!(x, y) {
                                 let (x, y) = tween.update();
+                                let (x, y) = ternary!(options.flipped, (7.0 - x, 7.0 - y), (x, y));
                                 draw_texture(
-                                    piece.image(),
-                                    MARGIN + SQUARE_SIZE * x,
-                                    MARGIN + SQUARE_SIZE * y,
+                                    image,
+                                    layout.margin + layout.square_size * x,
+                                    layout.top + layout.square_size * y,
                                     WHITE,
                                 );
                                 tweened = true;
@@ -266,12 +355,12 @@ impl Board {
 
                         if !tweened {
                             draw_texture_ex(
-                                piece.image(),
-                                MARGIN + SQUARE_SIZE * x as f32,
-                                MARGIN + SQUARE_SIZE * y as f32,
+                                image,
+                                layout.margin + layout.square_size * sx as f32,
+                                layout.top + layout.square_size * sy as f32,
                                 WHITE,
                                 DrawTextureParams {
-                                    dest_size: Some(vec2(SQUARE_SIZE, SQUARE_SIZE)),
+                                    dest_size: Some(vec2(layout.square_size, layout.square_size)),
                                     ..Default::default()
                                 },
                             )
@@ -281,63 +370,71 @@ impl Board {
             }
         }
 
+        if let Some(values) = options.values {
+            let params = TextParams {
+                font_size: 13,
+                color: COLOR_TABLE_VALUE,
+                font: font(),
+                ..Default::default()
+            };
+            for (x, y) in ENUMERATES {
+                let Loc(sx, sy) = options.view(loc!(x, y));
+                draw_text_ex(
+                    &values[y][x].to_string(),
+                    layout.margin + layout.square_size * sx as f32 + 3.0,
+                    layout.top + layout.square_size * sy as f32 + 12.0,
+                    params,
+                );
+            }
+        }
+
         for (x, y) in ENUMERATES {
+            let Loc(sx, sy) = options.view(loc!(x, y));
             // Draw highlight
-            if highlight_moves.contains(&loc!(x, y)) {
+            if options.highlight_moves.contains(&loc!(x, y)) {
                 draw_circle(
-                    MARGIN + SQUARE_SIZE * x as f32 + SQUARE_SIZE / 2.0,
-                    MARGIN + SQUARE_SIZE * y as f32 + SQUARE_SIZE / 2.0,
-                    SQUARE_SIZE / 5.0,
+                    layout.margin + layout.square_size * sx as f32 + layout.square_size / 2.0,
+                    layout.top + layout.square_size * sy as f32 + layout.square_size / 2.0,
+                    layout.square_size / 5.0,
                     COLOR_SELECTED,
                 );
             }
 
-            if highlights.contains(&loc!(x, y)) {
+            if options.highlights.contains(&loc!(x, y)) {
                 draw_circle_lines(
-                    MARGIN + SQUARE_SIZE * x as f32 + SQUARE_SIZE / 2.0,
-                    MARGIN + SQUARE_SIZE * y as f32 + SQUARE_SIZE / 2.0,
-                    SQUARE_SIZE / 2.0 - 2.5,
+                    layout.margin + layout.square_size * sx as f32 + layout.square_size / 2.0,
+                    layout.top + layout.square_size * sy as f32 + layout.square_size / 2.0,
+                    layout.square_size / 2.0 - 2.5,
                     5.0,
                     COLOR_HIGHLIGHT,
                 );
             }
         }
 
-        for arrow in arrows.iter() {
-            let start = board_to_pos_center(&arrow.0);
-            let end = board_to_pos_center(&arrow.1);
-            let angle = angle(start, end);
-
-            let left_angle = (angle - FRAC_PI_2 - FRAC_PI_3) % (2.0 * PI);
-            let left_point = project(end, left_angle, 25.0);
-
-            let right_angle = (angle + FRAC_PI_2 + FRAC_PI_3) % (2.0 * PI);
-            let right_point = project(end, right_angle, 25.0);
-
-            let top_end = project(end, angle, -7.0);
-
-            draw_triangle(
-                top_end.into(),
-                left_point.into(),
-                right_point.into(),
-                COLOR_ARROW,
+        for (arrow, color) in options.engine_arrows.iter() {
+            draw_arrow(options.view(arrow.0), options.view(arrow.1), *color);
+        }
+        if let Some(arrow) = options.threat_arrow {
+            draw_arrow(
+                options.view(arrow.0),
+                options.view(arrow.1),
+                COLOR_THREAT_ARROW,
             );
-
-            let new_start = project(start, angle, SQUARE_SIZE / 3.0);
-            let new_end = project(start, angle, distance(start, end) - 15.0);
-            draw_line(
-                new_start.0,
-                new_start.1,
-                new_end.0,
-                new_end.1,
-                10.0,
-                COLOR_ARROW,
+        }
+        for arrow in options.hint_arrows.iter() {
+            draw_arrow(
+                options.view(arrow.0),
+                options.view(arrow.1),
+                COLOR_HINT_ARROW,
             );
-            draw_circle(new_start.0, new_start.1, 5.0, COLOR_ARROW);
+        }
+        for arrow in options.arrows.iter() {
+            draw_arrow(options.view(arrow.0), options.view(arrow.1), COLOR_ARROW);
         }
     }
 
     /// Prints board to console
+    #[cfg(feature = "gui")]
     pub(crate) fn print(&self) {
         for row in self.raw.iter() {
             for piece in row.iter() {
@@ -375,22 +472,103 @@ impl Board {
         (white_king, black_king)
     }
 
-    pub(crate) fn attacks(&mut self, color: ChessColor) -> FxHashSet<Loc> {
-        let mut attacks = hashset! {};
+    pub(crate) fn attacks(&self, color: ChessColor) -> SquareSet {
+        let mut attacks = SquareSet::default();
         for piece in self.raw.iter().flatten().flatten() {
             if piece.color == color {
-                attacks.extend(piece.attacks(self));
+                attacks |= piece.attacks(self);
             }
         }
         attacks
     }
 
+    /// Pieces of either color that can take on `loc`, cheapest first, followed by the pieces lined up behind them (IE
+    /// a rook behind a queen) which join in once the ones in front have taken
+    #[cfg(feature = "gui")]
+    pub(crate) fn attackers(&self, loc: &Loc) -> Vec<Piece> {
+        let mut board = self.clone();
+        let mut attackers = vec![];
+        loop {
+            let mut found: Vec<Piece> = board
+                .raw
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|piece| piece.pos != *loc && piece.attacks(&board).contains(loc))
+                .copied()
+                .collect();
+            if found.is_empty() {
+                return attackers;
+            }
+            found.sort_by_key(|piece| piece.value());
+            for piece in &found {
+                board.set(&piece.pos, None);
+            }
+            attackers.extend(found);
+        }
+    }
+
+    /// Material the side that doesn't own the piece on `loc` wins by trading on it, both sides taking with their
+    /// cheapest [Board::attackers] and stopping once taking again would lose more (static exchange evaluation)
+    /// - `None` if the square is empty or nothing can take the piece
+    #[cfg(feature = "gui")]
+    pub(crate) fn exchange(&self, loc: &Loc) -> Option<i32> {
+        let target = self.get(loc)?;
+        let (takers, defenders): (Vec<_>, Vec<_>) = self
+            .attackers(loc)
+            .into_iter()
+            .partition(|piece| piece.color != target.color);
+        let mut sides = [takers.into_iter(), defenders.into_iter()];
+
+        // What each capture wins if the other side then stops
+        let mut gains = vec![];
+        let mut victim = target.value();
+        let mut side = 0;
+        // The sides take turns, so the iterator changes each capture
+        #[allow(clippy::while_let_on_iterator)]
+        while let Some(piece) = sides[side].next() {
+            gains.push(victim - gains.last().unwrap_or(&0));
+            victim = piece.value();
+            side = 1 - side;
+        }
+
+        // Either side can stop instead of taking back
+        let mut gain = gains.pop()?;
+        while let Some(before) = gains.pop() {
+            gain = -(-before).max(gain);
+        }
+        Some(gain)
+    }
+
     /* ----------------------------- Util functions ----------------------------- */
     pub(crate) fn get(&self, loc: &Loc) -> Option<Piece> {
         self.raw[loc.1][loc.0]
     }
 
+    /// Sets a square, keeping the NNUE accumulator, [Board::pawn_hash], and [Board::material] in sync
     pub(crate) fn set(&mut self, loc: &Loc, value: Option<Piece>) {
+        for piece in [self.raw[loc.1][loc.0], value].iter().flatten() {
+            if piece.name == PieceNames::Pawn {
+                self.pawn_hash ^= pawn_key(piece.color, loc);
+            }
+        }
+
+        if let Some(old) = &self.raw[loc.1][loc.0] {
+            self.material.add(old, loc, -1);
+        }
+        if let Some(new) = &value {
+            self.material.add(new, loc, 1);
+        }
+
+        if let Some(network) = NETWORK.as_ref() {
+            if let Some(old) = &self.raw[loc.1][loc.0] {
+                self.accumulator.remove(network, old, loc);
+            }
+            if let Some(new) = &value {
+                self.accumulator.add(network, new, loc);
+            }
+        }
+
         self.raw[loc.1][loc.0] = value;
     }
 
@@ -406,6 +584,20 @@ impl Board {
         moves
     }
 
+    /// Squares `color` can move to, each with the squares of the pieces that can move there
+    #[cfg(feature = "gui")]
+    pub(crate) fn movers(&self, color: ChessColor) -> FxHashMap<Loc, Vec<Loc>> {
+        let mut movers: FxHashMap<Loc, Vec<Loc>> = FxHashMap::default();
+        for (from, to) in self.moves(color) {
+            let from_squares = movers.entry(to).or_default();
+            // Promotions are one move per square, not per piece
+            if !from_squares.contains(&from) {
+                from_squares.push(from);
+            }
+        }
+        movers
+    }
+
     /// Returns the number of full moves
     pub(crate) fn full_moves(&self) -> u32 {
         self.half_moves / 2
@@ -415,23 +607,58 @@ impl Board {
     pub(crate) fn is_over(&self) -> bool {
         matches!(
             self.state,
-            BoardState::Checkmate(_) | BoardState::Stalemate | BoardState::Draw
+            BoardState::Checkmate(_)
+                | BoardState::Stalemate
+                | BoardState::Draw(_)
+                | BoardState::Timeout(_)
+                | BoardState::Resigned(_)
+                | BoardState::ThreeChecks(_)
+                | BoardState::Exploded(_)
+                | BoardState::KingCaptured(_)
+                | BoardState::Outraced(_)
+                | BoardState::NoPieces(_)
         )
     }
 
-    /// Returns a hash of the board, with castling and en passent included
+    /// Returns a hash of the board, with castling and en passent included, and checks given in three-check
     pub(crate) fn hash(&self) -> u64 {
         let mut hasher = FxHasher::default();
         self.raw.hash(&mut hasher);
-        self.castle_white.hash(&mut hasher);
-        self.castle_black.hash(&mut hasher);
+        self.castle.hash(&mut hasher);
         self.en_passent.hash(&mut hasher);
+        // Only in three-check, so the opening database's hashes still match
+        if self.variant == Variant::ThreeCheck {
+            self.checks.hash(&mut hasher);
+        }
         hasher.finish()
     }
 }
 
+/// Draws an arrow between the centers of two squares, as drawn on screen
+#[cfg(feature = "gui")]
+fn draw_arrow(start: Loc, end: Loc, color: Color) {
+    let start = board_to_pos_center(&start);
+    let end = board_to_pos_center(&end);
+    let angle = angle(start, end);
+
+    let left_angle = (angle - FRAC_PI_2 - FRAC_PI_3) % (2.0 * PI);
+    let left_point = project(end, left_angle, 25.0);
+
+    let right_angle = (angle + FRAC_PI_2 + FRAC_PI_3) % (2.0 * PI);
+    let right_point = project(end, right_angle, 25.0);
+
+    let top_end = project(end, angle, -7.0);
+
+    draw_triangle(top_end.into(), left_point.into(), right_point.into(), color);
+
+    let new_start = project(start, angle, layout().square_size / 3.0);
+    let new_end = project(start, angle, distance(start, end) - 15.0);
+    draw_line(new_start.0, new_start.1, new_end.0, new_end.1, 10.0, color);
+    draw_circle(new_start.0, new_start.1, 5.0, color);
+}
+
 /// Converts a piece name to a char
-fn piece_to_char(name: &PieceNames) -> char {
+pub(crate) fn piece_to_char(name: &PieceNames) -> char {
     match name {
         PieceNames::Pawn => 'p',
         PieceNames::Rook => 'r',
@@ -443,14 +670,14 @@ fn piece_to_char(name: &PieceNames) -> char {
 }
 
 /// Converts a string to a piece
-pub(crate) fn char_to_piece(c: &char) -> PieceNames {
-    match c.to_ascii_lowercase() {
+pub(crate) fn char_to_piece(c: &char) -> Result<PieceNames, ChessError> {
+    Ok(match c.to_ascii_lowercase() {
         'p' => PieceNames::Pawn,
         'n' => PieceNames::Knight,
         'b' => PieceNames::Bishop,
         'r' => PieceNames::Rook,
         'q' => PieceNames::Queen,
         'k' => PieceNames::King,
-        _ => panic!("Invalid piece"),
-    }
+        _ => return Err(ChessError::Notation(c.to_string())),
+    })
 }
@@ -0,0 +1,220 @@
+//! Broadcasting the game being played over HTTP, so others on the network can watch it
+//!
+//! The server sends an [Update] every time the position changes, as server-sent events on `/events`. A browser can
+//! watch the page on `/`, and other instances can watch with a [Spectator]
+//!
+//! - `/state` is the latest update on its own, for scripts
+//! - The broadcast keeps going across games, so spectators see the next game too
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::serve::{read_request, respond};
+
+pub(crate) const DEFAULT_PORT: u16 = 8080;
+
+/// Plies in [Update::tail]
+pub(crate) const TAIL_PLIES: usize = 10;
+
+/// How often the server checks for new spectators, and if it's been stopped
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Page that shows the broadcast in a browser
+const PAGE: &str = include_str!("../assets/broadcast.html");
+
+lazy_static! {
+    /// Broadcast started from the pause menu, outside of the game so it carries on into the next one
+    pub(crate) static ref BROADCAST: Mutex<Option<Broadcast>> = Mutex::new(None);
+}
+
+/// The game as it is after a move, sent to every spectator
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Update {
+    pub(crate) fen: String,
+    /// The whole game so far
+    pub(crate) pgn: String,
+    /// The last few moves, IE `12... Nc6 13. Bb5 a6`
+    pub(crate) tail: String,
+    /// Squares that changed since the last update, from [as_text](crate::diff::SquareChange::as_text), empty for the first one
+    pub(crate) changes: Vec<String>,
+}
+
+/// State shared with the threads serving spectators
+#[derive(Default)]
+struct Shared {
+    /// Sent to spectators as soon as they connect, as JSON
+    latest: Mutex<Option<String>>,
+    /// One for each spectator on `/events`, dropped once they leave
+    spectators: Mutex<Vec<Sender<String>>>,
+    stop: AtomicBool,
+}
+
+/// Server for the broadcast, stopped once it's dropped
+pub(crate) struct Broadcast {
+    pub(crate) port: u16,
+    /// Position of the last update, to work out [Update::changes]
+    last: Option<Board>,
+    shared: Arc<Shared>,
+}
+impl Broadcast {
+    /// Starts serving spectators on `port`, on its own thread
+    pub(crate) fn start(port: u16) -> io::Result<Broadcast> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        let broadcast = Broadcast {
+            // Port 0 picks a free one
+            port: listener.local_addr()?.port(),
+            last: None,
+            shared: Arc::new(Shared::default()),
+        };
+
+        let shared = broadcast.shared.clone();
+        spawn(move || {
+            while !shared.stop.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let shared = shared.clone();
+                        // Errors are spectators leaving, which is fine
+                        spawn(move || serve(stream, &shared).ok());
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => sleep(ACCEPT_INTERVAL),
+                    Err(_) => break,
+                }
+            }
+        });
+        Ok(broadcast)
+    }
+
+    /// Sends `board` to every spectator, along with the game so far and its last few moves
+    pub(crate) fn publish(&mut self, board: &Board, pgn: String, tail: String) {
+        let changes = match &self.last {
+            Some(last) => last.diff(board).iter().map(|c| c.as_text()).collect(),
+            None => vec![],
+        };
+        self.last = Some(board.clone());
+
+        let update = Update {
+            fen: board.as_fen(),
+            pgn,
+            tail,
+            changes,
+        };
+        let json = serde_json::to_string(&update).unwrap();
+        self.shared
+            .spectators
+            .lock()
+            .unwrap()
+            .retain(|spectator| spectator.send(json.clone()).is_ok());
+        *self.shared.latest.lock().unwrap() = Some(json);
+    }
+}
+impl Drop for Broadcast {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        // Ends the event streams
+        self.shared.spectators.lock().unwrap().clear();
+    }
+}
+
+/// Answers one request
+fn serve(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    match read_request(&stream)?.as_str() {
+        "/" => respond(&mut stream, "200 OK", "text/html; charset=utf-8", PAGE),
+        "/state" => match shared.latest.lock().unwrap().clone() {
+            Some(json) => respond(&mut stream, "200 OK", "application/json", &json),
+            None => respond(&mut stream, "404 Not Found", "text/plain", "No game yet"),
+        },
+        "/events" => {
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+                Access-Control-Allow-Origin: *\r\n\r\n"
+            )?;
+            let (sender, receiver) = unbounded();
+            if let Some(json) = shared.latest.lock().unwrap().clone() {
+                sender.send(json).unwrap();
+            }
+            shared.spectators.lock().unwrap().push(sender);
+
+            // Ends once the broadcast stops and drops the sender
+            for json in receiver {
+                write!(stream, "data: {}\n\n", json)?;
+                stream.flush()?;
+            }
+            Ok(())
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "Not found"),
+    }
+}
+
+/// Watches a broadcast from another instance, read only
+#[derive(Clone)]
+pub(crate) struct Spectator {
+    /// Updates, or why the stream ended
+    updates: Receiver<Result<Update, String>>,
+}
+impl Spectator {
+    /// Starts reading updates from `address`, IE `192.168.1.5:8080`, on its own thread
+    /// - The thread ends with the stream, or on the next update after the spectator is dropped
+    pub(crate) fn watch(address: &str) -> Spectator {
+        let (sender, updates) = unbounded();
+        let url = format!("http://{}/events", address);
+        spawn(move || {
+            let ended = match read_events(&url, &sender) {
+                Ok(()) => "The broadcast ended".to_string(),
+                Err(err) => err,
+            };
+            sender.send(Err(ended)).ok();
+        });
+
+        Spectator { updates }
+    }
+
+    /// Latest update since the last poll, or why watching ended
+    pub(crate) fn poll(&self) -> Result<Option<Update>, String> {
+        let mut latest = None;
+        loop {
+            match self.updates.try_recv() {
+                Ok(Ok(update)) => latest = Some(update),
+                Ok(Err(err)) => return Err(err),
+                Err(TryRecvError::Empty) => return Ok(latest),
+                Err(TryRecvError::Disconnected) => return Err("The broadcast ended".to_string()),
+            }
+        }
+    }
+}
+
+/// Forwards updates from the event stream at `url`, until it ends or nothing is listening
+fn read_events(url: &str, sender: &Sender<Result<Update, String>>) -> Result<(), String> {
+    // The stream stays open for the whole broadcast, so it can't time out
+    let client = reqwest::blocking::Client::builder()
+        .timeout(None)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let response = client
+        .get(url)
+        .send()
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| format!("Couldn't watch {}: {}", url, err))?;
+
+    for line in BufReader::new(response).lines() {
+        let line = line.map_err(|err| err.to_string())?;
+        let Some(data) = line.strip_prefix("data: ") else {
+            continue;
+        };
+        let update = serde_json::from_str(data).map_err(|err| err.to_string())?;
+        if sender.send(Ok(update)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -4,8 +4,12 @@ use macroquad::prelude::{
 };
 
 use crate::conf::{HEIGHT, WIDTH};
+use crate::layout::{layout, Layout};
+use crate::settings::settings;
 
 static mut CAMERA: Option<Camera> = None;
+// Only touched from the main thread
+#[allow(static_mut_refs)]
 pub(crate) fn camera() -> &'static mut Camera {
     unsafe {
         if CAMERA.is_none() {
@@ -24,9 +28,6 @@ pub(crate) struct Camera {
         ..Default::default()
     }")]
     camera: Camera2D,
-
-    #[new(value = "1.0")]
-    scale: f32,
 }
 impl Camera {
     /// Set the current camera to the macroquad camera
@@ -34,17 +35,21 @@ impl Camera {
         set_camera(&self.camera);
     }
 
-    /// Updates the camera, fits its to the screen size while keeping the aspect ratio
-    pub(crate) fn update(&mut self) {
-        let width_height_ratio = WIDTH as f32 / HEIGHT as f32;
-        if screen_width() / screen_height() > width_height_ratio {
-            self.scale = screen_height() / HEIGHT as f32;
-        } else {
-            self.scale = screen_width() / WIDTH as f32;
-        }
+    /// Updates the camera, scaling by [Settings::ui_scale](crate::settings::Settings::ui_scale) and fitting the
+    /// [layout] to the screen size, with room for the tab bar if `tabs`
+    pub(crate) fn update(&mut self, tabs: bool) {
+        let scale = settings().ui_scale;
+        let (width, height) = (screen_width() / scale, screen_height() / scale);
+        *layout() = Layout::fit(width, height, settings().compact_layout, tabs);
 
-        self.camera.zoom = vec2(2.0 / screen_width(), -2.0 / screen_height()) * self.scale;
+        self.camera.zoom = vec2(2.0 / width, -2.0 / height);
+        self.center_on(width / 2.0, height / 2.0);
+    }
 
+    /// Moves the camera so (`x`, `y`) is in the middle of the screen
+    /// - Overlays are laid out around the middle of the default window, so they're centered on it while drawn
+    pub(crate) fn center_on(&mut self, x: f32, y: f32) {
+        self.camera.target = vec2(x, y);
         self.update_camera();
     }
 
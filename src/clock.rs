@@ -0,0 +1,143 @@
+//! Chess clocks for timed games
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::ChessColor;
+use crate::color_ternary;
+
+/// Starting time and increment, in seconds
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TimeControl {
+    pub(crate) base: f64,
+    pub(crate) increment: f64,
+}
+impl TimeControl {
+    /// Name of the time control, IE `5+3`
+    pub(crate) fn name(&self) -> String {
+        format!("{}+{}", self.base / 60.0, self.increment)
+    }
+}
+
+/// Time controls that can be picked in the new game dialog, `None` being untimed
+pub(crate) const TIME_CONTROLS: [Option<TimeControl>; 5] = [
+    None,
+    Some(TimeControl {
+        base: 60.0,
+        increment: 0.0,
+    }),
+    Some(TimeControl {
+        base: 180.0,
+        increment: 2.0,
+    }),
+    Some(TimeControl {
+        base: 300.0,
+        increment: 3.0,
+    }),
+    Some(TimeControl {
+        base: 600.0,
+        increment: 5.0,
+    }),
+];
+
+/// Seconds left at which each of the last few seconds is ticked off
+const TICKS_FROM: u8 = 5;
+
+/// Seconds of network lag a LAN opponent isn't charged for, see [charged_time]
+pub(crate) const LAG_GRACE: f64 = 1.0;
+
+/// Seconds to charge a LAN opponent for a move that arrived `elapsed` seconds into their turn
+/// - `think` is the time they say they took, trusted as long as it's within [LAG_GRACE] of `elapsed`
+/// - Untimed moves, IE from before the move was sent with its time, are charged all of `elapsed`
+pub(crate) fn charged_time(think: Option<f64>, elapsed: f64) -> f64 {
+    match think {
+        Some(think) if think.is_finite() => think.clamp((elapsed - LAG_GRACE).max(0.0), elapsed),
+        _ => elapsed,
+    }
+}
+
+/// Time left that's announced as a clock runs down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ClockAlert {
+    OneMinute,
+    TenSeconds,
+    /// Each of the last [TICKS_FROM] seconds, with the seconds left
+    Tick(u8),
+}
+impl ClockAlert {
+    /// Alert for the clock going from `before` to `after` seconds left, the lowest if it passed several at once
+    pub(crate) fn crossed(before: f64, after: f64) -> Option<ClockAlert> {
+        let passed = |seconds: f64| before > seconds && after <= seconds;
+        if let Some(tick) = (1..=TICKS_FROM).find(|tick| passed(*tick as f64)) {
+            return Some(ClockAlert::Tick(tick));
+        }
+        if passed(10.0) {
+            Some(ClockAlert::TenSeconds)
+        } else if passed(60.0) {
+            Some(ClockAlert::OneMinute)
+        } else {
+            None
+        }
+    }
+}
+
+/// Remaining time for each color
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Clock {
+    pub(crate) white: f64,
+    pub(crate) black: f64,
+    pub(crate) increment: f64,
+}
+impl Clock {
+    pub(crate) fn new(control: &TimeControl) -> Clock {
+        Clock {
+            white: control.base,
+            black: control.base,
+            increment: control.increment,
+        }
+    }
+
+    /// Seconds `color` has left
+    pub(crate) fn remaining(&self, color: ChessColor) -> f64 {
+        color_ternary!(color, self.white, self.black)
+    }
+
+    fn remaining_mut(&mut self, color: ChessColor) -> &mut f64 {
+        color_ternary!(color, &mut self.white, &mut self.black)
+    }
+
+    /// Runs down `color`'s clock by `dt` seconds, returning the alert for the time it passed, if any
+    pub(crate) fn tick(&mut self, color: ChessColor, dt: f64) -> Option<ClockAlert> {
+        let remaining = self.remaining_mut(color);
+        let before = *remaining;
+        *remaining = (*remaining - dt).max(0.0);
+        ClockAlert::crossed(before, *remaining)
+    }
+
+    /// Adds the increment to `color`'s clock, call after they move
+    pub(crate) fn add_increment(&mut self, color: ChessColor) {
+        let increment = self.increment;
+        *self.remaining_mut(color) += increment;
+    }
+
+    /// The color that ran out of time, if any
+    pub(crate) fn flagged(&self) -> Option<ChessColor> {
+        if self.white <= 0.0 {
+            Some(ChessColor::White)
+        } else if self.black <= 0.0 {
+            Some(ChessColor::Black)
+        } else {
+            None
+        }
+    }
+
+    /// Formats `color`'s remaining time as `m:ss`, with tenths under 10 seconds
+    pub(crate) fn display(&self, color: ChessColor) -> String {
+        let remaining = self.remaining(color);
+        if remaining < 10.0 {
+            return format!("0:{:04.1}", remaining);
+        }
+
+        let seconds = remaining.ceil() as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
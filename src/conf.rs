@@ -1,28 +1,93 @@
 //! Static config values
 
+#[cfg(feature = "gui")]
 use macroquad::prelude::{color_u8, Color};
 
-// Config for screen
-pub(crate) const EXTRA_WIDTH: f32 = 125.0;
-pub(crate) const SQUARE_SIZE: f32 = 60.0;
-pub(crate) const MARGIN: f32 = 16.0;
-pub(crate) const WIDTH: i32 = SQUARE_SIZE as i32 * 8 + MARGIN as i32 * 3 + EXTRA_WIDTH as i32;
-pub(crate) const HEIGHT: i32 = SQUARE_SIZE as i32 * 8 + MARGIN as i32 * 2;
+// Config for screen, the board and side panel are fitted to the window as it's resized, see [layout](crate::layout)
+/// Size of the window when it opens, with 60 pixel squares
+#[cfg(feature = "gui")]
+pub(crate) const WIDTH: i32 = 653;
+#[cfg(feature = "gui")]
+pub(crate) const HEIGHT: i32 = 512;
+/// Middle of the window when it opens, which overlays are laid out around
+#[cfg(feature = "gui")]
 pub(crate) const CENTER_WIDTH: i32 = WIDTH / 2;
+#[cfg(feature = "gui")]
 pub(crate) const CENTER_HEIGHT: i32 = HEIGHT / 2;
 
 // Colors
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_WHITE: Color = color_u8!(235, 216, 183, 255);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_BLACK: Color = color_u8!(172, 136, 103, 255);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_BACKGROUND: Color = color_u8!(0, 0, 0, 255);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_SELECTED: Color = color_u8!(0, 0, 0, 128);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_LAST_MOVE: Color = color_u8!(204, 208, 119, 128);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_HIGHLIGHT: Color = color_u8!(238, 75, 43, 255);
+/// Flash over the square of a move that can't be made, fading out over [ILLEGAL_FLASH_TIME]
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_ILLEGAL: Color = color_u8!(220, 40, 40, 160);
+/// Piece-square table values drawn in the corner of each square by the table editor
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_TABLE_VALUE: Color = color_u8!(0, 0, 0, 200);
+/// Arrows the player draws
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_ARROW: Color = color_u8!(238, 75, 43, 255);
+/// Arrows showing the player a move, IE the right move in a drill
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_HINT_ARROW: Color = color_u8!(70, 170, 90, 210);
+/// Arrow for the move the agent threatens, along with the threat warning
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_THREAT_ARROW: Color = color_u8!(240, 150, 30, 170);
+/// Faint arrows for the agent's best alternatives to its move
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_ENGINE_ALTERNATIVE: Color = color_u8!(90, 140, 220, 110);
+/// Faint arrow for the reply the agent expects to its move
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_ENGINE_REPLY: Color = color_u8!(60, 60, 60, 110);
+/// Over squares the player can't see in a fog of war game
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_FOG: Color = color_u8!(40, 40, 48, 200);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_BUTTON: Color = color_u8!(127, 166, 80, 255);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_BUTTON_HOVER: Color = color_u8!(149, 187, 74, 255);
+#[cfg(feature = "gui")]
 pub(crate) const COLOR_BUTTON_PRESSED: Color = color_u8!(138, 172, 70, 255);
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_OVERLAY: Color = color_u8!(0, 0, 0, 160);
+/// Agent searches in the log panel, other messages are [COLOR_WHITE]
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_LOG_ENGINE: Color = color_u8!(140, 180, 240, 255);
+/// Opening book moves in the log panel
+#[cfg(feature = "gui")]
+pub(crate) const COLOR_LOG_BOOK: Color = color_u8!(160, 210, 120, 255);
+
+/// Seconds the flash over an illegal move's square lasts
+#[cfg(feature = "gui")]
+pub(crate) const ILLEGAL_FLASH_TIME: f64 = 0.4;
+
+/// Board themes, (name, light squares, dark squares)
+#[cfg(feature = "gui")]
+pub(crate) const THEMES: [(&str, Color, Color); 3] = [
+    ("Brown", COLOR_WHITE, COLOR_BLACK),
+    (
+        "Green",
+        color_u8!(238, 238, 210, 255),
+        color_u8!(118, 150, 86, 255),
+    ),
+    (
+        "Blue",
+        color_u8!(222, 227, 230, 255),
+        color_u8!(140, 162, 173, 255),
+    ),
+];
 
 // Config for board
 pub(crate) const DEFAULT_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+#[cfg(feature = "gui")]
 pub(crate) const FEN: &str = DEFAULT_FEN;
@@ -0,0 +1,87 @@
+//! Part of [Board], split for readability
+//!
+//! Works out which squares changed between two positions, so animation, network sync, and the bindings don't have to
+//! compare the raw arrays themselves
+
+use crate::board::Board;
+use crate::board_extras::piece_to_char;
+use crate::pieces::piece::Piece;
+#[cfg(feature = "gui")]
+use crate::pieces::piece::PieceNames;
+use crate::util::Loc;
+use crate::{color_ternary, loc};
+
+/// A square with a different piece, or no piece, in the second position
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SquareChange {
+    pub(crate) loc: Loc,
+    pub(crate) before: Option<Piece>,
+    pub(crate) after: Option<Piece>,
+}
+impl SquareChange {
+    /// The change as `square before after`, with FEN letters and `.` for an empty square, IE `e2 P .` and `e4 . P`
+    pub(crate) fn as_text(&self) -> String {
+        let letter = |piece: Option<Piece>| match piece {
+            Some(piece) => {
+                let char = piece_to_char(&piece.name);
+                color_ternary!(piece.color, char.to_ascii_uppercase(), char)
+            }
+            None => '.',
+        };
+        format!(
+            "{} {} {}",
+            self.loc.as_notation(),
+            letter(self.before),
+            letter(self.after)
+        )
+    }
+}
+
+/// The piece that moved between the positions `changes` is from, IE to animate it
+/// - Castling gives the king's move, and promoting the pawn's
+/// - `None` if no single piece moved, IE the positions are from different games
+#[cfg(feature = "gui")]
+pub(crate) fn moved_piece(changes: &[SquareChange]) -> Option<(Loc, Loc)> {
+    let mut moves: Vec<(Loc, Loc, PieceNames)> = vec![];
+    for from in changes.iter() {
+        let Some(piece) = from.before.filter(|_| from.after.is_none()) else {
+            continue;
+        };
+        for to in changes.iter() {
+            let Some(landed) = to.after else {
+                continue;
+            };
+            let promoted = piece.name == PieceNames::Pawn && (to.loc.1 == 0 || to.loc.1 == 7);
+            if landed.color == piece.color && (landed.name == piece.name || promoted) {
+                moves.push((from.loc, to.loc, piece.name));
+            }
+        }
+    }
+
+    match moves[..] {
+        [(from, to, _)] => Some((from, to)),
+        _ => moves
+            .iter()
+            .find(|(_, _, name)| *name == PieceNames::King)
+            .map(|(from, to, _)| (*from, *to)),
+    }
+}
+
+impl Board {
+    /// Squares that are different in `other`, from a8 to h1
+    pub(crate) fn diff(&self, other: &Board) -> Vec<SquareChange> {
+        let mut changes = vec![];
+        for y in 0..8 {
+            for x in 0..8 {
+                let loc = loc!(x, y);
+                let (before, after) = (self.get(&loc), other.get(&loc));
+                // Pieces are the same if they're the same kind, wherever they think they are
+                let kind = |piece: Option<Piece>| piece.map(|p| (p.name, p.color));
+                if kind(before) != kind(after) {
+                    changes.push(SquareChange { loc, before, after });
+                }
+            }
+        }
+        changes
+    }
+}
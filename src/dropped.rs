@@ -0,0 +1,117 @@
+//! Files dragged onto the window, FEN, EPD, or PGN, loaded as a position to play from or a game to replay
+//!
+//! Macroquad's window only hands over dropped files in the browser for now, so on desktop nothing arrives until it
+//! does
+
+use std::fs::read;
+use std::path::{Path, PathBuf};
+use std::ptr::addr_of_mut;
+
+use macroquad::prelude::get_internal_gl;
+
+use crate::board::Board;
+use crate::pgn::{parse_all, PgnGame};
+use crate::position::epd_to_fen;
+
+/// Most positions or games listed to pick from, the rest of a big file are left out
+pub(crate) const CHOOSER_ROWS: usize = 10;
+
+/// Longest a row of the chooser gets before it's cut off
+const LABEL_CHARS: usize = 24;
+
+/// A position or game read from a dropped file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Dropped {
+    /// FEN, with its EPD `id` if it had one
+    Position {
+        fen: String,
+        id: Option<String>,
+    },
+    Game(PgnGame),
+}
+impl Dropped {
+    /// Row in the chooser, IE `Carlsen - Nepomniachtchi` or `Position 3`
+    /// - `i` is its index in the file
+    pub(crate) fn label(&self, i: usize) -> String {
+        let label = match self {
+            Dropped::Position { id: Some(id), .. } => id.clone(),
+            Dropped::Position { id: None, .. } => format!("Position {}", i + 1),
+            Dropped::Game(game) => format!(
+                "{} - {}",
+                game.tag("White").unwrap_or("?"),
+                game.tag("Black").unwrap_or("?")
+            ),
+        };
+        if label.chars().count() <= LABEL_CHARS {
+            return label;
+        }
+        let cut: String = label.chars().take(LABEL_CHARS - 3).collect();
+        format!("{}...", cut)
+    }
+}
+
+/// Positions or games in a dropped file, read by its extension
+/// - Unreadable lines or games are skipped, it's only an error if nothing could be read
+pub(crate) fn read_dropped(name: &str, text: &str) -> Result<Vec<Dropped>, String> {
+    let extension = Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    let lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+
+    let (read, skipped) = match extension.as_deref() {
+        Some("pgn") => {
+            let (games, skipped) = parse_all(text)?;
+            (games.into_iter().map(Dropped::Game).collect(), skipped)
+        }
+        Some("fen") => read_positions(lines.map(|line| Some((line.to_string(), None)))),
+        Some("epd") => read_positions(lines.map(epd_to_fen)),
+        _ => return Err("Only .fen, .epd, and .pgn files can be loaded".to_string()),
+    };
+    if read.is_empty() && skipped > 0 {
+        return Err(format!("None of the {} entries could be read", skipped));
+    }
+    Ok(read)
+}
+
+/// Positions that are playable, and how many weren't
+fn read_positions(
+    positions: impl Iterator<Item = Option<(String, Option<String>)>>,
+) -> (Vec<Dropped>, usize) {
+    let mut read = vec![];
+    let mut skipped = 0;
+    for position in positions {
+        match position.filter(|(fen, _)| Board::try_from_fen(fen).is_ok()) {
+            Some((fen, id)) => read.push(Dropped::Position { fen, id }),
+            None => skipped += 1,
+        }
+    }
+    (read, skipped)
+}
+
+/// Paths of the files dropped last, checked so each drop is only loaded once
+static mut SEEN: Vec<PathBuf> = vec![];
+
+/// Files dropped onto the window since the last call, as (name, contents)
+/// - Drops are told apart by their paths, so dropping the same file twice in a row only loads it once
+pub(crate) fn take_dropped() -> Vec<(String, Vec<u8>)> {
+    let context = &mut unsafe { get_internal_gl() }.quad_context;
+    let paths: Vec<_> = (0..context.dropped_file_count())
+        .map(|i| context.dropped_file_path(i).unwrap_or_default())
+        .collect();
+    let seen = unsafe { &mut *addr_of_mut!(SEEN) };
+    if paths == *seen {
+        return vec![];
+    }
+    *seen = paths.clone();
+
+    paths
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, path)| {
+            // The browser hands over the contents, on desktop they're read from the path
+            let bytes = context.dropped_file_bytes(i).or_else(|| read(&path).ok())?;
+            let name = path.file_name()?.to_string_lossy().to_string();
+            Some((name, bytes))
+        })
+        .collect()
+}
@@ -0,0 +1,109 @@
+//! Part of [Board], split for readability
+//!
+//! Endgames recognized by the material left on the board, whose scores are scaled toward a draw when they're known to
+//! be drawish
+
+use lazy_static::lazy_static;
+use rustc_hash::FxHashMap;
+
+use crate::board::Board;
+use crate::pieces::piece::PieceNames;
+use crate::{color_ternary, hashmap};
+
+/// Bits for each piece count of a side in a [Board::material_key], pawns get 4 since there can be 8 of them
+/// - Counts past what fits, IE from promotions, are saturated
+const PIECE_BITS: [(PieceNames, u32); 5] = [
+    (PieceNames::Pawn, 4),
+    (PieceNames::Knight, 3),
+    (PieceNames::Bishop, 3),
+    (PieceNames::Rook, 3),
+    (PieceNames::Queen, 3),
+];
+
+/// Count of each piece in [PIECE_BITS] for one side
+type Counts = [u32; 5];
+
+/// Packs both sides' counts into a key, white in the low bits
+fn pack(white: Counts, black: Counts) -> u32 {
+    let mut key = 0;
+    let mut shift = 0;
+    for counts in [white, black] {
+        for (count, (_, bits)) in counts.iter().zip(PIECE_BITS) {
+            key |= (*count).min((1 << bits) - 1) << shift;
+            shift += bits;
+        }
+    }
+    key
+}
+
+/// Endgames that are drawish more often than their material suggests
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Endgame {
+    /// Only a bishop each and pawns, with the bishops on opposite colors
+    OppositeBishops,
+    /// Rook and pawn against a rook, which the defender can usually hold
+    RookPawnVsRook,
+}
+impl Endgame {
+    /// Sixteenths of the score kept
+    fn scale(&self) -> i32 {
+        match self {
+            Endgame::OppositeBishops => 8,
+            Endgame::RookPawnVsRook => 4,
+        }
+    }
+}
+
+lazy_static! {
+    /// Endgame for each [Board::material_key] that could be one
+    static ref ENDGAMES: FxHashMap<u32, Endgame> = {
+        let mut endgames = hashmap! {};
+        for white in 0..=8 {
+            for black in 0..=8 {
+                endgames.insert(pack([white, 0, 1, 0, 0], [black, 0, 1, 0, 0]), Endgame::OppositeBishops);
+            }
+        }
+        endgames.insert(pack([1, 0, 0, 1, 0], [0, 0, 0, 1, 0]), Endgame::RookPawnVsRook);
+        endgames.insert(pack([0, 0, 0, 1, 0], [1, 0, 0, 1, 0]), Endgame::RookPawnVsRook);
+        endgames
+    };
+}
+
+impl Board {
+    /// Counts of each piece besides kings, packed so boards with the same material have the same key
+    pub(crate) fn material_key(&self) -> u32 {
+        let mut counts = [[0; 5]; 2];
+        for piece in self.raw.iter().flatten().flatten() {
+            if let Some(i) = PIECE_BITS.iter().position(|(name, _)| *name == piece.name) {
+                counts[color_ternary!(piece.color, 0, 1)][i] += 1;
+            }
+        }
+        pack(counts[0], counts[1])
+    }
+
+    /// Drawish endgame the board is in, if any
+    pub(crate) fn endgame_class(&self) -> Option<Endgame> {
+        let endgame = *ENDGAMES.get(&self.material_key())?;
+        if endgame == Endgame::OppositeBishops {
+            let mut squares = self
+                .raw
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|piece| piece.name == PieceNames::Bishop)
+                .map(|bishop| (bishop.pos.0 + bishop.pos.1) % 2);
+            if squares.next() == squares.next() {
+                return None;
+            }
+        }
+        Some(endgame)
+    }
+
+    /// Scales a score (for white) toward a draw in drawish endgames
+    pub(crate) fn scale_endgame(&self, score: i32) -> i32 {
+        match self.endgame_class() {
+            Some(endgame) => score * endgame.scale() / 16,
+            None => score,
+        }
+    }
+}
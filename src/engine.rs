@@ -0,0 +1,262 @@
+//! External UCI engines as an agent, IE to play against Stockfish from the GUI
+//!
+//! Pass `--engine <path>` (or set [ENGINE_VAR]) to add an _Engine_ agent. The engine is started on its first move and
+//! kept running after, given each position with `position fen`, and asked for a move within the search limits with
+//! `go`. Stopping the search sends `stop`, and the engine is started again if it exits. Only standard chess is sent,
+//! since UCI engines don't know the other variants
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Mutex;
+use std::thread::spawn;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{unbounded, Receiver, RecvTimeoutError};
+use lazy_static::lazy_static;
+
+use crate::agent::{AgentMessage, AgentResult, MovePicker, Reply, SearchInfo, SearchLimits};
+use crate::board::Board;
+use crate::board_eval::mate_score;
+use crate::pgn::try_parse_move;
+use crate::variant::Variant;
+use crate::{color_ternary, ternary};
+
+pub(crate) const ENGINE_VAR: &str = "CHESS_AI_ENGINE";
+/// Time the engine is given when the search limits don't have one
+const DEFAULT_MS: u64 = 5000;
+/// How long the engine has to answer `uci` when it's started
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the search is checked for being stopped, while waiting on the engine
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+lazy_static! {
+    static ref ENGINE_PATH: Option<PathBuf> = {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        engine_arg(&args)
+    };
+}
+
+/// Engine from `--engine <path>` in `args`, or [ENGINE_VAR]
+pub(crate) fn engine_arg(args: &[String]) -> Option<PathBuf> {
+    let flag = args
+        .iter()
+        .position(|arg| arg == "--engine")
+        .and_then(|i| args.get(i + 1));
+    match flag {
+        Some(path) => Some(PathBuf::from(path)),
+        None => std::env::var_os(ENGINE_VAR).map(PathBuf::from),
+    }
+}
+
+/// Engine given to the binary, `None` if it wasn't given one
+pub(crate) fn engine_path() -> Option<&'static Path> {
+    ENGINE_PATH.as_deref()
+}
+
+/// The external engine agent, see the module docs
+pub(crate) struct UciEngine {
+    /// Engine to run, `None` if it wasn't given one
+    path: Option<PathBuf>,
+    /// Started on the first move, and again after it exits
+    process: Mutex<Option<Process>>,
+}
+impl UciEngine {
+    pub(crate) fn new(path: Option<PathBuf>) -> UciEngine {
+        UciEngine {
+            path,
+            process: Mutex::new(None),
+        }
+    }
+}
+impl MovePicker for UciEngine {
+    fn best_move(&self, board: &Board, limits: &SearchLimits) -> AgentResult {
+        if board.is_over() {
+            return AgentResult::Picked((None, None));
+        }
+        if board.variant != Variant::Standard {
+            return AgentResult::Failed(format!("External engines can't play {:?}", board.variant));
+        }
+        let Some(path) = &self.path else {
+            return AgentResult::Failed("No engine given, start with --engine <path>".to_string());
+        };
+
+        let mut process = self.process.lock().unwrap();
+        let reply = match process.as_mut() {
+            Some(running) => Ok(running),
+            None => Process::start(path).map(|started| process.insert(started)),
+        }
+        .and_then(|running| running.ask(board, limits));
+        match reply {
+            Ok(reply) => AgentResult::Picked(reply),
+            Err(err) => {
+                // Dropped so it's started again next move
+                *process = None;
+                AgentResult::Failed(format!("Engine {}: {}", path.display(), err))
+            }
+        }
+    }
+}
+
+/// A running engine
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    /// Lines the engine writes, read on their own thread so a search can be stopped while waiting on them
+    lines: Receiver<String>,
+}
+impl Process {
+    /// Starts the engine at `path`, waiting for it to answer `uci`
+    fn start(path: &Path) -> Result<Process, String> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| err.to_string())?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let (sender, lines) = unbounded();
+        spawn(move || {
+            // Ends once the engine exits
+            for line in stdout.lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut process = Process {
+            child,
+            stdin,
+            lines,
+        };
+        process.send("uci")?;
+        let start = Instant::now();
+        while process.next_line()?.as_deref().map(str::trim) != Some("uciok") {
+            if start.elapsed() > STARTUP_TIMEOUT {
+                return Err("didn't answer uci".to_string());
+            }
+        }
+        Ok(process)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command).map_err(|err| err.to_string())
+    }
+
+    /// Next line from the engine, `None` if it didn't write one within [POLL_INTERVAL]
+    fn next_line(&self) -> Result<Option<String>, String> {
+        match self.lines.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => Ok(Some(line)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err("exited without a move".to_string()),
+        }
+    }
+
+    /// Asks for a move on `board`, sending its `info` lines to [SearchLimits::progress] as they come
+    /// - Sends `stop` once the search is stopped, and still waits for the move the engine answers with
+    fn ask(&mut self, board: &Board, limits: &SearchLimits) -> Result<Reply, String> {
+        self.send(&format!("position fen {}", board.as_fen()))?;
+        self.send(&go_command(limits))?;
+
+        let mut info = None;
+        let mut stopped = false;
+        loop {
+            if !stopped && limits.stopped() {
+                self.send("stop")?;
+                stopped = true;
+            }
+            let Some(line) = self.next_line()? else {
+                continue;
+            };
+
+            if let Some(parsed) = parse_info(board, &line) {
+                if let Some(progress) = &limits.progress {
+                    progress.send(AgentMessage::from(parsed.clone())).ok();
+                }
+                info = Some(parsed);
+            } else if let Some(mov) = line.strip_prefix("bestmove") {
+                let mov = mov.split_whitespace().next().unwrap_or("(none)");
+                return match mov {
+                    "(none)" | "0000" => Ok((None, info)),
+                    _ => try_parse_move(board, mov)
+                        .map(|mov| (Some(mov), info))
+                        .map_err(|err| err.to_string()),
+                };
+            }
+        }
+    }
+}
+impl Drop for Process {
+    fn drop(&mut self) {
+        // The engine may have already exited
+        self.send("quit").ok();
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// `go` command for `limits`
+pub(crate) fn go_command(limits: &SearchLimits) -> String {
+    let ms = ternary!(
+        limits.max_time.is_finite(),
+        (limits.max_time * 1000.0).max(1.0) as u64,
+        DEFAULT_MS
+    );
+    match limits.max_depth {
+        Some(depth) => format!("go depth {} movetime {}", depth, ms),
+        None => format!("go movetime {}", ms),
+    }
+}
+
+/// Search info from an `info` line with a depth and score, IE
+/// `info depth 12 score cp 30 nodes 12345 pv e2e4 e7e5`
+/// - Scores are turned from the side to move's to white's, and the PV stops at the first move it can't read
+pub(crate) fn parse_info(board: &Board, line: &str) -> Option<SearchInfo> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "info" {
+        return None;
+    }
+
+    let mut depth = None;
+    let mut score = None;
+    let mut nodes = 0;
+    let mut pv = vec![];
+    while let Some(word) = words.next() {
+        match word {
+            "depth" => depth = words.next()?.parse::<u8>().ok(),
+            "nodes" => nodes = words.next()?.parse().unwrap_or(0),
+            "score" => {
+                let kind = words.next()?;
+                let value = words.next()?.parse::<i32>().ok()?;
+                score = match kind {
+                    "cp" => Some(value),
+                    "mate" => Some(mate_score(value)),
+                    _ => None,
+                };
+            }
+            "pv" => {
+                let mut test_board = board.clone();
+                for mov in words.by_ref() {
+                    let Ok((from, to)) = try_parse_move(&test_board, mov) else {
+                        break;
+                    };
+                    test_board.move_piece(&from, &to, false);
+                    pv.push((from, to));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let score = score?;
+    Some(SearchInfo {
+        depth: depth?,
+        score: color_ternary!(board.turn, score, -score),
+        pv,
+        nodes,
+        ..SearchInfo::default()
+    })
+}
@@ -0,0 +1,35 @@
+//! Errors shared across the crate, so the GUI and library users can show what went wrong instead of crashing
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ChessError {
+    /// FEN that can't be read, with the part that's wrong, IE `turn`
+    Fen(&'static str),
+    /// Square or piece that can't be read, IE `z9`
+    Notation(String),
+    /// Move that can't be played in the position, as it was given
+    IllegalMove(String),
+    /// The opening database couldn't be loaded
+    Book(String),
+    /// Reading a file or asset failed
+    Io(String),
+}
+impl Display for ChessError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ChessError::Fen(part) => write!(f, "Invalid FEN ({})", part),
+            ChessError::Notation(text) => write!(f, "Invalid notation \"{}\"", text),
+            ChessError::IllegalMove(mov) => write!(f, "Illegal move {}", mov),
+            ChessError::Book(err) => write!(f, "Couldn't load the opening book: {}", err),
+            ChessError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+impl std::error::Error for ChessError {}
+impl From<io::Error> for ChessError {
+    fn from(err: io::Error) -> ChessError {
+        ChessError::Io(err.to_string())
+    }
+}
@@ -0,0 +1,152 @@
+//! Events from moves being played, so sound, animation, network sync, and logging subscribe to them instead of each
+//! being wired into `Game::move_piece`
+//!
+//! - Events are worked out by comparing the positions before and after a move, see [move_events]
+//! - The clock running down and its increments are events too, so they're heard and seen the same way
+//! - Subscribers are called in the order they subscribed, for each event in turn
+
+use crate::board::{Board, BoardState, ChessColor};
+use crate::clock::ClockAlert;
+use crate::pgn::{coordinate, san};
+use crate::pieces::piece::{Piece, PieceNames};
+use crate::util::Loc;
+
+/// Something that happened in the game
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum GameEvent {
+    /// Every move, before the other events it caused
+    Move {
+        color: ChessColor,
+        from: Loc,
+        to: Loc,
+        san: String,
+        /// In coordinate notation, IE for sending over the network
+        coordinate: String,
+        capture: bool,
+    },
+    /// The piece taken, on its square, which isn't where the capturing piece went for en passant
+    Capture {
+        at: Loc,
+        piece: Piece,
+    },
+    /// `color`'s pawn took the pawn on `at` en passant, after the [GameEvent::Capture] of it
+    EnPassant {
+        color: ChessColor,
+        at: Loc,
+    },
+    /// The pawn that reached `at` became `piece`
+    Promotion {
+        at: Loc,
+        piece: PieceNames,
+    },
+    Castle {
+        color: ChessColor,
+        kingside: bool,
+    },
+    /// Attached color is who is in check
+    Check(ChessColor),
+    /// The move ended the game, IE with checkmate or a draw
+    GameEnd(BoardState),
+    /// Moves taken back, last played first
+    Takeback {
+        moves: Vec<(Loc, Loc)>,
+    },
+    /// `color`'s clock ran down past one of the times announced
+    ClockAlert {
+        color: ChessColor,
+        alert: ClockAlert,
+    },
+    /// Seconds added to `color`'s clock after their move
+    Increment {
+        color: ChessColor,
+        seconds: f64,
+    },
+}
+
+/// Events for moving `from` to `to` on `before`, which led to `after`
+pub(crate) fn move_events(before: &Board, from: &Loc, to: &Loc, after: &Board) -> Vec<GameEvent> {
+    let Some(piece) = before.get(from) else {
+        return vec![];
+    };
+    let taken = before
+        .is_capture(from, to)
+        .and_then(|at| before.get(&at).map(|piece| (at, piece)));
+
+    let mut events = vec![GameEvent::Move {
+        color: piece.color,
+        from: *from,
+        to: *to,
+        san: san(before, from, to),
+        coordinate: coordinate(before, from, to),
+        capture: taken.is_some(),
+    }];
+    if let Some((at, taken)) = taken {
+        events.push(GameEvent::Capture { at, piece: taken });
+        if at != *to {
+            events.push(GameEvent::EnPassant {
+                color: piece.color,
+                at,
+            });
+        }
+    }
+    if let Some(promoted) = after.get(to).filter(|landed| landed.name != piece.name) {
+        events.push(GameEvent::Promotion {
+            at: *to,
+            piece: promoted.name,
+        });
+    }
+    if piece.name == PieceNames::King && from.0.abs_diff(to.0) == 2 {
+        events.push(GameEvent::Castle {
+            color: piece.color,
+            kingside: to.0 > from.0,
+        });
+    }
+
+    match after.state {
+        BoardState::Check(color) => events.push(GameEvent::Check(color)),
+        state if after.is_over() && !before.is_over() => {
+            if let BoardState::Checkmate(color) | BoardState::ThreeChecks(color) = state {
+                events.push(GameEvent::Check(color));
+            }
+            events.push(GameEvent::GameEnd(state));
+        }
+        _ => {}
+    }
+    events
+}
+
+/// Handler for events, given whatever it needs to react to them, IE the game
+pub(crate) type Subscriber<T> = fn(&mut T, &GameEvent);
+
+/// Subscribers to call with each event, in order
+pub(crate) struct EventBus<T> {
+    subscribers: Vec<Subscriber<T>>,
+}
+// Derived clone would need `T: Clone`
+impl<T> Clone for EventBus<T> {
+    fn clone(&self) -> EventBus<T> {
+        EventBus {
+            subscribers: self.subscribers.clone(),
+        }
+    }
+}
+impl<T> EventBus<T> {
+    pub(crate) fn new() -> EventBus<T> {
+        EventBus {
+            subscribers: vec![],
+        }
+    }
+
+    pub(crate) fn subscribe(&mut self, subscriber: Subscriber<T>) {
+        self.subscribers.push(subscriber);
+    }
+
+    /// Calls every subscriber with each of `events`, giving them `target`
+    pub(crate) fn publish(&self, target: &mut T, events: &[GameEvent]) {
+        for event in events {
+            for subscriber in self.subscribers.iter() {
+                subscriber(target, event);
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Personal opening explorer, searching a PGN database for the games that reached a position and what was played next
+//!
+//! Positions are indexed by hash when the database is loaded, so transpositions are found as well
+
+use std::cmp::Reverse;
+#[cfg(not(target_family = "wasm"))]
+use std::fs::read_to_string;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use rustc_hash::{FxHashMap, FxHasher};
+
+use crate::board::Board;
+use crate::pgn::{parse_all, san, PgnGame};
+use crate::pieces::piece::PieceNames;
+use crate::ternary;
+use crate::util::Loc;
+
+/// Where the database is loaded from
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const DATABASE_PATH: &str = "games.pgn";
+
+lazy_static! {
+    /// Database shown in the explorer panel, outside of the game so it stays open into the next one
+    pub(crate) static ref EXPLORER: Mutex<Option<Database>> = Mutex::new(None);
+}
+
+/// Hash of a position, like [Board::hash] but with whose turn it is
+/// - The en passent square only counts when it can be taken, so transpositions ending in a double pawn push match
+fn position_key(board: &Board) -> u64 {
+    let en_passent = board.en_passent.filter(|square| {
+        board.moves(board.turn).iter().any(|(from, to)| {
+            to == square
+                && board
+                    .get(from)
+                    .is_some_and(|piece| piece.name == PieceNames::Pawn)
+        })
+    });
+
+    let mut hasher = FxHasher::default();
+    board.raw.hash(&mut hasher);
+    board.castle.hash(&mut hasher);
+    en_passent.hash(&mut hasher);
+    board.turn.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A game that reached a position, with the move played next, `None` if the game ended there
+type Reached = (usize, Option<(Loc, Loc)>);
+
+/// Who won a game, from its `Result` tag
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    White,
+    Black,
+    Draw,
+    /// Unfinished, or the tag is missing
+    Unknown,
+}
+impl Outcome {
+    fn from_tag(result: Option<&str>) -> Outcome {
+        match result {
+            Some("1-0") => Outcome::White,
+            Some("0-1") => Outcome::Black,
+            Some("1/2-1/2") => Outcome::Draw,
+            _ => Outcome::Unknown,
+        }
+    }
+
+    /// Result as written in PGN, IE `1-0`
+    pub(crate) fn result(&self) -> &'static str {
+        match self {
+            Outcome::White => "1-0",
+            Outcome::Black => "0-1",
+            Outcome::Draw => "1/2-1/2",
+            Outcome::Unknown => "*",
+        }
+    }
+}
+
+/// The tags of a game in the database that are listed in the explorer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct GameSummary {
+    pub(crate) white: String,
+    pub(crate) black: String,
+    pub(crate) outcome: Outcome,
+}
+impl GameSummary {
+    /// IE `Carlsen - Nakamura 1-0`
+    pub(crate) fn label(&self) -> String {
+        format!("{} - {} {}", self.white, self.black, self.outcome.result())
+    }
+}
+
+/// Games played on from a position with one move, and how they ended
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Continuation {
+    pub(crate) mov: (Loc, Loc),
+    pub(crate) san: String,
+    pub(crate) games: usize,
+    pub(crate) white_wins: usize,
+    pub(crate) draws: usize,
+    pub(crate) black_wins: usize,
+}
+impl Continuation {
+    /// IE `e4  12  50% 25% 25%`, the share of white wins, draws, and black wins
+    pub(crate) fn row(&self) -> String {
+        let share = |count: usize| count * 100 / self.games;
+        format!(
+            "{:<6}{:>4}  {}% {}% {}%",
+            self.san,
+            self.games,
+            share(self.white_wins),
+            share(self.draws),
+            share(self.black_wins)
+        )
+    }
+}
+
+/// What the database has on a position
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Search {
+    /// Games that reached the position, in the order of the database
+    pub(crate) games: Vec<usize>,
+    /// Moves played from the position, most played first
+    pub(crate) continuations: Vec<Continuation>,
+}
+
+/// Main lines of a PGN database, indexed by position
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Database {
+    pub(crate) games: Vec<GameSummary>,
+    /// Games that reached each position
+    positions: FxHashMap<u64, Vec<Reached>>,
+    /// Games that couldn't be read
+    pub(crate) skipped: usize,
+}
+impl Database {
+    pub(crate) fn from_pgn(text: &str) -> Result<Database, String> {
+        let (games, skipped) = parse_all(text)?;
+        let mut database = Database {
+            skipped,
+            ..Default::default()
+        };
+        for game in &games {
+            database.add(game);
+        }
+        Ok(database)
+    }
+
+    /// Indexes the positions of the main line of a game, variations are left out
+    pub(crate) fn add(&mut self, game: &PgnGame) {
+        let index = self.games.len();
+        self.games.push(GameSummary {
+            white: game.tag("White").unwrap_or("?").to_string(),
+            black: game.tag("Black").unwrap_or("?").to_string(),
+            outcome: Outcome::from_tag(game.tag("Result")),
+        });
+
+        let tree = &game.tree;
+        let moves = tree.moves(tree.line(None).last().copied());
+        let mut board = Board::from_fen(&game.start_fen);
+        for i in 0..=moves.len() {
+            let reached = self.positions.entry(position_key(&board)).or_default();
+            // A position repeated in a game only counts the first time
+            if reached.last().is_none_or(|(game, _)| *game != index) {
+                reached.push((index, moves.get(i).copied()));
+            }
+            if let Some((from, to)) = moves.get(i) {
+                board.move_piece(from, to, true);
+            }
+        }
+    }
+
+    pub(crate) fn search(&self, board: &Board) -> Search {
+        let Some(reached) = self.positions.get(&position_key(board)) else {
+            return Search::default();
+        };
+
+        let mut continuations: Vec<Continuation> = vec![];
+        for (game, mov) in reached {
+            let Some(mov) = mov else {
+                continue;
+            };
+            let i = match continuations.iter().position(|c| c.mov == *mov) {
+                Some(i) => i,
+                None => {
+                    continuations.push(Continuation {
+                        mov: *mov,
+                        san: san(board, &mov.0, &mov.1),
+                        games: 0,
+                        white_wins: 0,
+                        draws: 0,
+                        black_wins: 0,
+                    });
+                    continuations.len() - 1
+                }
+            };
+
+            let continuation = &mut continuations[i];
+            continuation.games += 1;
+            match self.games[*game].outcome {
+                Outcome::White => continuation.white_wins += 1,
+                Outcome::Black => continuation.black_wins += 1,
+                Outcome::Draw => continuation.draws += 1,
+                Outcome::Unknown => {}
+            }
+        }
+        // Stable, so ties stay in the order they were first played
+        continuations.sort_by_key(|c| Reverse(c.games));
+
+        Search {
+            games: reached.iter().map(|(game, _)| *game).collect(),
+            continuations,
+        }
+    }
+
+    /// Rows of the explorer panel, a count of the games, the moves played next, and then the games themselves
+    pub(crate) fn panel_rows(&self, board: &Board) -> Vec<String> {
+        let search = self.search(board);
+        if search.games.is_empty() {
+            return vec!["No games reached this position".to_string()];
+        }
+
+        let mut rows = vec![format!(
+            "{} game{}",
+            search.games.len(),
+            ternary!(search.games.len() == 1, "", "s")
+        )];
+        rows.extend(search.continuations.iter().map(Continuation::row));
+        rows.extend(search.games.iter().map(|game| self.games[*game].label()));
+        rows
+    }
+}
+#[cfg(not(target_family = "wasm"))]
+impl Database {
+    pub(crate) fn load(path: &str) -> Result<Database, String> {
+        let text = read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        Database::from_pgn(&text).map_err(|err| format!("Invalid database {}: {}", path, err))
+    }
+}
@@ -0,0 +1,199 @@
+//! C API for calling the engine from other languages, IE Python with `ctypes` or C++ GUIs
+//!
+//! Built as a shared library with `cargo rustc --release --lib --no-default-features --features ffi --crate-type cdylib`,
+//! with declarations in `include/chess_ai.h`
+//!
+//! - Every function returns a [ChessError] code instead of panicking, even if the engine does
+//! - Moves are in coordinate notation, IE `e2e4` or `e7e8q`
+//! - Text is written into caller owned buffers as NUL terminated UTF-8
+
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr::null_mut;
+
+use crate::agent::{AgentId, SearchLimits};
+use crate::board::Board;
+use crate::conf::DEFAULT_FEN;
+use crate::pgn::{coordinate, parse_move, promotion_piece};
+use crate::pieces::piece::PieceNames;
+
+/// Result of every function, the values are stable across versions
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChessError {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidUtf8 = 2,
+    /// Unreadable FEN, or an illegal position
+    InvalidFen = 3,
+    IllegalMove = 4,
+    GameOver = 5,
+    /// The text didn't fit, nothing was written
+    BufferTooSmall = 6,
+    /// The engine hit a bug, the board should be freed
+    Internal = 7,
+}
+
+/// Board handle given out to callers
+pub struct ChessBoard {
+    board: Board,
+}
+
+/// Runs `f`, turning panics into [ChessError::Internal]
+fn guard(f: impl FnOnce() -> Result<(), ChessError>) -> ChessError {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => ChessError::Ok,
+        Ok(Err(error)) => error,
+        Err(_) => ChessError::Internal,
+    }
+}
+
+unsafe fn read_str<'a>(text: *const c_char) -> Result<&'a str, ChessError> {
+    if text.is_null() {
+        return Err(ChessError::NullPointer);
+    }
+    CStr::from_ptr(text)
+        .to_str()
+        .map_err(|_| ChessError::InvalidUtf8)
+}
+
+unsafe fn write_str(text: &str, out: *mut c_char, len: usize) -> Result<(), ChessError> {
+    if out.is_null() {
+        return Err(ChessError::NullPointer);
+    }
+    if text.len() + 1 > len {
+        return Err(ChessError::BufferTooSmall);
+    }
+    std::ptr::copy_nonoverlapping(text.as_ptr(), out as *mut u8, text.len());
+    *out.add(text.len()) = 0;
+    Ok(())
+}
+
+unsafe fn board_mut<'a>(board: *mut ChessBoard) -> Result<&'a mut Board, ChessError> {
+    board
+        .as_mut()
+        .map(|board| &mut board.board)
+        .ok_or(ChessError::NullPointer)
+}
+
+/// Creates a board from `fen`, or the standard position if it's null, and stores it in `out`
+///
+/// # Safety
+///
+/// `fen` has to be null or a NUL terminated string, and `out` a valid pointer. The board has to be freed with
+/// [chess_board_free]
+#[no_mangle]
+pub unsafe extern "C" fn chess_board_new(
+    fen: *const c_char,
+    out: *mut *mut ChessBoard,
+) -> ChessError {
+    guard(|| {
+        if out.is_null() {
+            return Err(ChessError::NullPointer);
+        }
+        *out = null_mut();
+
+        let board = if fen.is_null() {
+            Board::from_fen(DEFAULT_FEN)
+        } else {
+            Board::try_from_fen(read_str(fen)?).map_err(|_| ChessError::InvalidFen)?
+        };
+        *out = Box::into_raw(Box::new(ChessBoard { board }));
+        Ok(())
+    })
+}
+
+/// Frees a board made by [chess_board_new], null is ignored
+///
+/// # Safety
+///
+/// `board` can't be used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn chess_board_free(board: *mut ChessBoard) {
+    if !board.is_null() {
+        drop(Box::from_raw(board));
+    }
+}
+
+/// Writes the board's FEN into `out`
+///
+/// # Safety
+///
+/// `board` has to be from [chess_board_new], and `out` has to have room for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn chess_board_fen(
+    board: *mut ChessBoard,
+    out: *mut c_char,
+    len: usize,
+) -> ChessError {
+    guard(|| write_str(&board_mut(board)?.as_fen(), out, len))
+}
+
+/// Writes the legal moves of the side to move into `out`, separated by spaces
+///
+/// # Safety
+///
+/// `board` has to be from [chess_board_new], and `out` has to have room for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn chess_board_moves(
+    board: *mut ChessBoard,
+    out: *mut c_char,
+    len: usize,
+) -> ChessError {
+    guard(|| {
+        let board = board_mut(board)?;
+        let moves: Vec<_> = board
+            .moves(board.turn)
+            .iter()
+            .map(|(from, to)| coordinate(board, from, to))
+            .collect();
+        write_str(&moves.join(" "), out, len)
+    })
+}
+
+/// Plays a move for the side to move, IE `e2e4`, promotions are always to a queen
+///
+/// # Safety
+///
+/// `board` has to be from [chess_board_new], and `mov` a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn chess_board_make_move(
+    board: *mut ChessBoard,
+    mov: *const c_char,
+) -> ChessError {
+    guard(|| {
+        let board = board_mut(board)?;
+        if board.is_over() {
+            return Err(ChessError::GameOver);
+        }
+
+        let text = read_str(mov)?;
+        let (from, to) = parse_move(board, text).ok_or(ChessError::IllegalMove)?;
+        board.promote_to = promotion_piece(text).unwrap_or(PieceNames::Queen);
+        board.move_piece(&from, &to, true);
+        Ok(())
+    })
+}
+
+/// Writes the minimax agent's move for the side to move into `out`, after thinking for up to `ms` milliseconds
+///
+/// # Safety
+///
+/// `board` has to be from [chess_board_new], and `out` has to have room for `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn chess_board_best_move(
+    board: *mut ChessBoard,
+    ms: u32,
+    out: *mut c_char,
+    len: usize,
+) -> ChessError {
+    guard(|| {
+        let board = board_mut(board)?;
+        let limits = SearchLimits::new(ms as f64 / 1000.0);
+        let (from, to) = AgentId::MINIMAX
+            .get_move(board, &limits)
+            .0
+            .ok_or(ChessError::GameOver)?;
+        write_str(&coordinate(board, &from, &to), out, len)
+    })
+}
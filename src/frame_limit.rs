@@ -0,0 +1,80 @@
+//! Lowers the frame rate while nothing on screen is changing, to save battery
+//!
+//! On desktop the loop sleeps until the next idle frame is due. On the web idle frames aren't drawn at all, which
+//! leaves the last drawn frame on the canvas
+//!
+//! - Turned off with [Settings::low_power](crate::settings::Settings::low_power)
+
+#[cfg(not(target_family = "wasm"))]
+use std::thread::sleep;
+#[cfg(not(target_family = "wasm"))]
+use std::time::Duration;
+
+use derive_new::new;
+use macroquad::input::{
+    get_last_key_pressed, is_mouse_button_down, is_mouse_button_pressed, is_mouse_button_released,
+    mouse_position, mouse_wheel, touches, MouseButton,
+};
+use macroquad::miniquad::date;
+use macroquad::window::{screen_height, screen_width};
+
+use crate::settings::settings;
+
+/// Frames a second while idle, enough to keep polling the network and update check
+pub(crate) const IDLE_FPS: f64 = 10.0;
+
+/// Wether a frame is due at `now` after one drawn at `last_frame`, always when `active` and otherwise [IDLE_FPS]
+/// times a second
+pub(crate) fn frame_due(last_frame: f64, now: f64, active: bool) -> bool {
+    active || now - last_frame >= 1.0 / IDLE_FPS
+}
+
+const MOUSE_BUTTONS: [MouseButton; 3] =
+    [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+/// Tracks input between frames to tell when the game is idle
+#[derive(Clone, Copy, Debug, new)]
+pub(crate) struct FrameLimiter {
+    /// When the last frame was drawn
+    #[new(value = "0.0")]
+    last_frame: f64,
+
+    /// Mouse position and screen size last frame, anything moving counts as input
+    #[new(value = "((0.0, 0.0), (0.0, 0.0))")]
+    last_seen: ((f32, f32), (f32, f32)),
+}
+impl FrameLimiter {
+    /// Wether anything was clicked, pressed, scrolled, touched, moved, or resized since last frame
+    fn input(&mut self) -> bool {
+        let seen = (mouse_position(), (screen_width(), screen_height()));
+        let moved = seen != self.last_seen;
+        self.last_seen = seen;
+
+        moved
+            || get_last_key_pressed().is_some()
+            || mouse_wheel() != (0.0, 0.0)
+            || !touches().is_empty()
+            || MOUSE_BUTTONS.iter().any(|button| {
+                is_mouse_button_down(*button)
+                    || is_mouse_button_pressed(*button)
+                    || is_mouse_button_released(*button)
+            })
+    }
+
+    /// Wether to draw this frame, `busy` when something on screen is moving or a search is running
+    /// - On desktop this sleeps until the next idle frame instead of skipping, so it's always `true`
+    pub(crate) fn frame(&mut self, busy: bool) -> bool {
+        let active = busy || !settings().low_power || self.input();
+        let now = date::now();
+        if !frame_due(self.last_frame, now, active) {
+            #[cfg(target_family = "wasm")]
+            return false;
+            #[cfg(not(target_family = "wasm"))]
+            sleep(Duration::from_secs_f64(
+                1.0 / IDLE_FPS - (now - self.last_frame),
+            ));
+        }
+        self.last_frame = date::now();
+        true
+    }
+}
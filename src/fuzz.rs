@@ -0,0 +1,156 @@
+//! Property tests that play random legal games and check invariants after every move
+//!
+//! - FEN export and import round-trip
+//! - The stored hash, pawn hash, material sums, and NNUE accumulator match ones computed from scratch
+//! - Legal moves match a slow reference generator, which checks every pseudo-legal move for king safety
+//! - Move lists built from the incremental move cache match ones generated from scratch
+//! - Nothing panics along the way
+
+use proptest::prelude::*;
+
+use crate::board::{Board, ChessColor};
+use crate::color_ternary;
+use crate::conf::DEFAULT_FEN;
+use crate::nnue::{Accumulator, NETWORK};
+use crate::pieces::bishop::bishop_moves;
+use crate::pieces::king::king_moves;
+use crate::pieces::knight::knight_moves;
+use crate::pieces::pawn::pawn_moves;
+use crate::pieces::piece::PieceNames;
+use crate::pieces::queen::queen_moves;
+use crate::pieces::rook::rook_moves;
+use crate::util::Loc;
+
+/// Starting positions, so castling, en passant, and promotion come up often
+const FENS: [&str; 4] = [
+    DEFAULT_FEN,
+    "r3k2r/pppq1ppp/2np1n2/2b1p3/2B1P1b1/2NP1N2/PPPQ1PPP/R3K2R w KQkq - 0 1",
+    "4k3/1P4p1/8/3pP3/8/8/1p4P1/4K3 w - d6 0 1",
+    "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 3 20",
+];
+
+/// Whether `color`'s king is attacked on `board`
+fn in_check(board: &Board, color: ChessColor) -> bool {
+    color_ternary!(color, board.check_white, board.check_black)
+}
+
+/// Slow legal move generator, plays every pseudo-legal move and keeps the ones that don't leave the king attacked
+fn reference_moves(board: &Board) -> Vec<(Loc, Loc)> {
+    let mut moves = vec![];
+
+    for piece in board.raw.iter().flatten().flatten() {
+        if piece.color != board.turn {
+            continue;
+        }
+
+        let pseudo = match piece.name {
+            PieceNames::Pawn => pawn_moves(piece, board),
+            PieceNames::Knight => knight_moves(piece, board),
+            PieceNames::Bishop => bishop_moves(piece, board),
+            PieceNames::Rook => rook_moves(piece, board),
+            PieceNames::Queen => queen_moves(piece, board),
+            PieceNames::King => king_moves(piece, board),
+        };
+
+        for to in pseudo {
+            let mut test_board = board.clone();
+            test_board.move_piece(&piece.pos, &to, false);
+            if in_check(&test_board, piece.color) {
+                continue;
+            }
+
+            // Castling also can't start in or pass through check
+            if piece.name == PieceNames::King && piece.pos.0.abs_diff(to.0) == 2 {
+                let through = Loc((piece.pos.0 + to.0) / 2, to.1);
+                let mut through_board = board.clone();
+                through_board.move_piece(&piece.pos, &through, false);
+                if in_check(board, piece.color) || in_check(&through_board, piece.color) {
+                    continue;
+                }
+            }
+
+            moves.push((piece.pos, to));
+        }
+    }
+
+    moves.sort_by_key(|(from, to)| (from.0, from.1, to.0, to.1));
+    moves
+}
+
+fn check_invariants(board: &Board) {
+    let fen = board.as_fen();
+
+    let reimported = Board::from_fen(&fen);
+    assert_eq!(reimported.as_fen(), fen, "FEN round-trip");
+    assert_eq!(
+        reimported.hash, board.hash,
+        "hash differs from a fresh board in {fen}"
+    );
+    assert_eq!(board.hash, board.hash(), "stale hash in {fen}");
+    assert_eq!(
+        reimported.pawn_hash, board.pawn_hash,
+        "pawn hash differs from a fresh board in {fen}"
+    );
+    assert_eq!(
+        board.pawn_score(),
+        board.pawn_structure(),
+        "stale pawn hash table entry in {fen}"
+    );
+
+    assert_eq!(
+        board.material,
+        board.full_material(),
+        "material out of sync in {fen}"
+    );
+
+    let network = NETWORK.as_ref().unwrap();
+    let accumulator = Accumulator::refresh(network, board.raw.iter().flatten().flatten());
+    assert_eq!(
+        board.accumulator, accumulator,
+        "accumulator out of sync in {fen}"
+    );
+
+    let mut moves = board.moves(board.turn);
+    moves.sort_by_key(|(from, to)| (from.0, from.1, to.0, to.1));
+    assert_eq!(moves, reference_moves(board), "legal moves differ in {fen}");
+
+    for (color, cached) in [
+        (ChessColor::White, &board.moves_white),
+        (ChessColor::Black, &board.moves_black),
+    ] {
+        let mut cached = cached.clone();
+        cached.sort_by_key(|(from, to)| (from.0, from.1, to.0, to.1));
+        let mut moves = board.moves(color);
+        moves.sort_by_key(|(from, to)| (from.0, from.1, to.0, to.1));
+        assert_eq!(cached, moves, "cached {color:?} moves differ in {fen}");
+    }
+
+    // Exercises the rest of the move and eval code for panics
+    board.sorted_moves(board.turn);
+    board.score();
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn random_games_keep_invariants(
+        start in 0..FENS.len(),
+        choices in prop::collection::vec(any::<prop::sample::Index>(), 0..60),
+    ) {
+        let mut board = Board::from_fen(FENS[start]);
+        check_invariants(&board);
+
+        for choice in choices {
+            if board.is_over() {
+                break;
+            }
+
+            let moves = board.moves(board.turn);
+            prop_assert!(!moves.is_empty(), "no moves but not over in {}", board.as_fen());
+            let (from, to) = moves[choice.index(moves.len())];
+            board.move_piece(&from, &to, true);
+            check_invariants(&board);
+        }
+    }
+}
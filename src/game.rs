@@ -1,36 +1,677 @@
+use std::mem::replace;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::atomic::AtomicBool;
+#[cfg(not(target_family = "wasm"))]
+use std::sync::Arc;
 #[cfg(not(target_family = "wasm"))]
 use std::thread::spawn;
 
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use derive_new::new;
-use macroquad::audio::{play_sound, PlaySoundParams};
+use macroquad::miniquad::date;
+#[cfg(not(target_family = "wasm"))]
+use macroquad::prelude::DARKGRAY;
 use macroquad::prelude::{
-    info, is_key_pressed, is_mouse_button_down, is_mouse_button_pressed, KeyCode, MouseButton,
-    TextParams, WHITE,
+    is_key_down, is_mouse_button_down, is_mouse_button_pressed, mouse_wheel, Color, KeyCode,
+    MouseButton, TextParams, WHITE,
 };
+use macroquad::rand::{gen_range, rand, srand};
 use macroquad::shapes::draw_rectangle;
+#[cfg(not(target_family = "wasm"))]
+use macroquad::text::draw_text_ex;
 use macroquad::text::measure_text;
+use macroquad::time::get_frame_time;
+use macroquad::window::get_internal_gl;
 use rustc_hash::FxHashSet;
 
-use crate::agent::{Agent, AGENTS};
-use crate::assets::get_audio;
-use crate::board::Board;
+#[cfg(target_family = "wasm")]
+use crate::agent::Thinking;
+#[cfg(not(target_family = "wasm"))]
+use crate::agent::MAX;
+use crate::agent::{
+    agents, in_book, AgentId, AgentMessage, Outlook, Proposal, SearchInfo, SearchLimits,
+};
+#[cfg(target_family = "wasm")]
+use crate::agent_opens::step_loading;
+#[cfg(not(target_family = "wasm"))]
+use crate::analysis::StopOnDrop;
+use crate::analysis::{Analysis, LiveAnalysis, Threat};
+#[cfg(not(target_family = "wasm"))]
+use crate::autosave::{autosave_path, remove_autosave, GameRecord, AUTOSAVE_INTERVAL};
+use crate::board::{Board, BoardState, ChessColor, DrawReason};
+use crate::board_eval::{display_score, export_tables, set_pst_weight, weighted_table, PIECES};
+use crate::board_extras::DrawOptions;
+#[cfg(not(target_family = "wasm"))]
+use crate::broadcast::{Broadcast, Spectator, Update, BROADCAST, TAIL_PLIES};
 use crate::camera::camera;
+#[cfg(not(target_family = "wasm"))]
+use crate::clock::{charged_time, LAG_GRACE};
+use crate::clock::{Clock, TimeControl, TIME_CONTROLS};
+#[cfg(not(target_family = "wasm"))]
+use crate::conf::THEMES;
 use crate::conf::{
-    CENTER_HEIGHT, CENTER_WIDTH, COLOR_BACKGROUND, COLOR_WHITE, EXTRA_WIDTH, FEN, HEIGHT, MARGIN,
-    SQUARE_SIZE,
+    CENTER_HEIGHT, CENTER_WIDTH, COLOR_BACKGROUND, COLOR_ENGINE_ALTERNATIVE, COLOR_ENGINE_REPLY,
+    COLOR_HIGHLIGHT, COLOR_OVERLAY, FEN, ILLEGAL_FLASH_TIME,
+};
+use crate::diff::moved_piece;
+use crate::dropped::{read_dropped, take_dropped, Dropped};
+use crate::events::{move_events, EventBus, GameEvent};
+use crate::explorer::EXPLORER;
+#[cfg(not(target_family = "wasm"))]
+use crate::explorer::{Database, DATABASE_PATH};
+use crate::handicap::{Handicap, HANDICAPS};
+use crate::keybinds::{key_name, pressed_actions, tooltip};
+#[cfg(not(target_family = "wasm"))]
+use crate::lan::{Handshake, Lan, LanEvent, LanRole};
+use crate::layout::layout;
+use crate::log::{log_lines, push_log, LogKind};
+use crate::mate::{MateSearch, MAX_MATE_MOVES};
+#[cfg(not(target_family = "wasm"))]
+use crate::menu::NetworkItem;
+use crate::menu::{
+    ConfirmItem, FindMateItem, MainMenuItem, NewGameItem, Overlay, PauseItem, SettingsItem,
+    TableItem,
+};
+#[cfg(not(target_family = "wasm"))]
+use crate::move_tree::STUDY_PATH;
+use crate::move_tree::{MoveTree, Replay, Study};
+#[cfg(not(target_family = "wasm"))]
+use crate::pgn::{attack_lines, coordinate, exchange_line, movetext_tail, parse_move, termination};
+use crate::pgn::{
+    export, export_tree, log_line, panel_line, parse, promotion_piece, san, san_line,
+    search_comment, search_summary, search_tree_lines, stats_comment, try_parse_move, MoveStats,
+    PgnGame,
 };
-use crate::pieces::piece::Piece;
-use crate::util::{multiline_text_ex, pos_to_board, Button, Loc, Tween};
-use crate::{font, hashset, ternary};
+use crate::pieces::piece::{Piece, PieceNames};
+use crate::platform::{info, warn};
+use crate::position::describe_issues;
+use crate::rating::Opponent;
+use crate::repertoire::Drill;
+#[cfg(not(target_family = "wasm"))]
+use crate::repertoire::{Repertoire, REPERTOIRE_PATH};
+use crate::repro::Decision;
+#[cfg(not(target_family = "wasm"))]
+use crate::repro::DECISION_PATH;
+#[cfg(not(target_family = "wasm"))]
+use crate::review::{export_analysis, move_analysis, ANALYSIS_CSV_PATH, ANALYSIS_JSON_PATH};
+use crate::review::{key_moments, Review};
+use crate::rush::{Rush, RushAnswer, RUSH_PAUSE};
+use crate::scheduler::{Scheduler, Task, FRAME_BUDGET};
+use crate::settings::{settings, Visibility};
+use crate::sound::{queue_sound, SoundKind};
+#[cfg(not(target_family = "wasm"))]
+use crate::stats::{suggested_opponent, PlayerStats, STATS_PATH};
+use crate::ui::{text_params, wrap, Stack};
+#[cfg(not(target_family = "wasm"))]
+use crate::updates::UPDATE;
+use crate::util::{multiline_text_ex, pos_to_board, touches, Loc, SquareSet, Tween};
+#[cfg(not(target_family = "wasm"))]
+use crate::util::{Button, SliderRange};
+use crate::variant::Variant;
+use crate::{color_ternary, font, hashset, ternary};
+
+/// Actions that can be done from the toolbar or with keybinds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MenuAction {
+    Reset,
+    Takeback,
+    CopyFen,
+    CopyPgn,
+    PrintBoard,
+    Pause,
+    Help,
+    ImportPgn,
+    /// Steps back through a replay
+    Back,
+    /// Steps forward through a replay, following the main line
+    Forward,
+    /// Switches to the previous alternative to the current move in a replay
+    PrevVariation,
+    NextVariation,
+    /// Turns the current game into a replay, to annotate it
+    Study,
+    /// Comments on the current position of a replay
+    Comment,
+    /// Jumps to the next position with annotations in a replay
+    NextAnnotation,
+    SaveStudy,
+    OpenStudy,
+    /// Adds the player's moves so far to the repertoire
+    AddRepertoire,
+    /// Shows the next position from the repertoire that's due for review
+    Drill,
+    TypeMove,
+    /// Searches the current position until stopped, or stops the search
+    Analyze,
+    /// Looks for a forced mate in a number of moves
+    FindMate,
+    /// Offers the agent a draw, which it takes in an equal or drawish endgame, or when it's worse off
+    OfferDraw,
+    /// Sends a message to the LAN opponent
+    Chat,
+    /// Turns the game into a replay where both colors are moved freely, with the eval bar running
+    AnalysisBoard,
+    /// Plays the engine's best move on the analysis board
+    EngineMove,
+    /// Turns the board around, to see it from the other side
+    FlipBoard,
+    /// Opens the piece-square table editor, to tweak the weights of the evaluation
+    Tables,
+    /// Loads the game database and searches it for the current position, or closes the explorer
+    Explorer,
+    /// Turns the finished game into a replay with its key moments listed, from the background analysis
+    Review,
+    /// Starts a game against the agent from the position on the board, keeping this one to return to
+    PlayFromHere,
+    /// Goes back to the game left with [MenuAction::PlayFromHere]
+    ReturnToGame,
+    /// Writes the background analysis of every move to CSV and JSON files
+    ExportAnalysis,
+    /// Opens another game in a new tab, see [Tabs](crate::tabs::Tabs)
+    NewTab,
+    CloseTab,
+    /// Switches to the tab on the right, wrapping around to the first
+    NextTab,
+    /// Shows how the agent searched its last move, see [Settings::search_tree](crate::settings::Settings::search_tree)
+    SearchTree,
+    /// Saves what the agent's last move depended on, to reproduce it with `chess-ai --repro`
+    SaveDecision,
+    /// Shows the engine's output and messages in the side panel, or hides them, see [log](crate::log)
+    Log,
+    /// Turns [Settings::sound](crate::settings::Settings::sound) off or back on
+    Mute,
+}
+impl MenuAction {
+    /// Shown in tooltips and the help overlay
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            MenuAction::Reset => "Reset",
+            MenuAction::Takeback => "Takeback",
+            MenuAction::CopyFen => "Copy FEN",
+            MenuAction::CopyPgn => "Copy PGN",
+            MenuAction::PrintBoard => "Print board",
+            MenuAction::Pause => "Pause menu",
+            MenuAction::Help => "Help",
+            MenuAction::ImportPgn => "Import PGN from clipboard",
+            MenuAction::Back => "Previous move",
+            MenuAction::Forward => "Next move",
+            MenuAction::PrevVariation => "Previous variation",
+            MenuAction::NextVariation => "Next variation",
+            MenuAction::Study => "Study this game",
+            MenuAction::Comment => "Comment on position",
+            MenuAction::NextAnnotation => "Next annotation",
+            MenuAction::SaveStudy => "Save study",
+            MenuAction::OpenStudy => "Open study",
+            MenuAction::AddRepertoire => "Add to repertoire",
+            MenuAction::Drill => "Drill repertoire",
+            MenuAction::TypeMove => "Type a move",
+            MenuAction::OfferDraw => "Offer a draw",
+            MenuAction::Analyze => "Analyze position",
+            MenuAction::FindMate => "Find mate",
+            MenuAction::Chat => "Chat",
+            MenuAction::AnalysisBoard => "Analysis board",
+            MenuAction::EngineMove => "Play engine move",
+            MenuAction::FlipBoard => "Flip board",
+            MenuAction::Tables => "Piece-square tables",
+            MenuAction::Explorer => "Opening explorer",
+            MenuAction::Review => "Review key moments",
+            MenuAction::PlayFromHere => "Play from here",
+            MenuAction::ReturnToGame => "Return to game",
+            MenuAction::ExportAnalysis => "Export analysis",
+            MenuAction::NewTab => "New tab",
+            MenuAction::CloseTab => "Close tab",
+            MenuAction::NextTab => "Next tab",
+            MenuAction::SearchTree => "Search tree",
+            MenuAction::SaveDecision => "Save engine decision",
+            MenuAction::Log => "Engine log",
+            MenuAction::Mute => "Mute sound",
+        }
+    }
+
+    /// Wether the action takes back moves or gives hints, so it's off while a rated game is being played
+    pub(crate) fn helps(&self) -> bool {
+        matches!(
+            self,
+            MenuAction::Takeback
+                | MenuAction::Study
+                | MenuAction::Analyze
+                | MenuAction::FindMate
+                | MenuAction::AnalysisBoard
+                | MenuAction::EngineMove
+                | MenuAction::Tables
+                | MenuAction::Explorer
+                | MenuAction::PlayFromHere
+                | MenuAction::SearchTree
+                | MenuAction::SaveDecision
+        )
+    }
+}
+
+/// (icon, action) for each toolbar button, in order
+pub(crate) const MENU_ACTIONS: [(&str, MenuAction); 6] = [
+    ("↺", MenuAction::Reset),
+    ("←", MenuAction::Takeback),
+    ("✎", MenuAction::CopyFen),
+    ("≡", MenuAction::CopyPgn),
+    ("▦", MenuAction::PrintBoard),
+    ("?", MenuAction::Help),
+];
+
+const TOOLBAR_GAP: f32 = 4.0;
+/// Squares a second pieces slide at with [Settings::animation_speed](crate::settings::Settings::animation_speed) at `1.0`
+const TWEEN_SPEED: f32 = 20.0;
+
+/// Seconds after a move before the board turns to the other player in a local two player game
+const FLIP_DELAY: f64 = 0.6;
+/// Seconds the board fades out before turning, and back in after
+const FLIP_FADE: f64 = 0.15;
+
+/// Seconds a clock flashes for after a [ClockAlert](crate::clock::ClockAlert)
+const CLOCK_FLASH_TIME: f64 = 0.5;
+/// Seconds an increment is shown next to the clock it was added to
+const INCREMENT_SHOW_TIME: f64 = 1.5;
+/// Seconds a special move is announced over the board for, the last of which it fades out
+const ANNOUNCEMENT_TIME: f64 = 2.0;
+
+/// Work [Game::update] shares [FRAME_BUDGET] between each frame
+const BACKGROUND_TASKS: &[Task<Game>] = &[
+    #[cfg(not(target_family = "wasm"))]
+    Game::poll_network,
+    #[cfg(target_family = "wasm")]
+    Game::update_thinking,
+    #[cfg(target_family = "wasm")]
+    Game::update_book,
+    Game::update_analysis,
+    Game::update_searches,
+];
+
+/// Bottom of the side panel with the agent buttons, which the move list and live analysis replace
+fn panel_section_height() -> f32 {
+    let margin = layout().margin;
+    (AGENT_BUTTON_HEIGHT + margin / 2.0) * agents().count() as f32 - margin / 2.0
+}
+const MOVE_LIST_FONT_SIZE: u16 = 12;
+const MOVE_LIST_ROW_HEIGHT: f32 = 16.0;
+const MOVE_LIST_ROWS: usize = 9;
+const VARIATION_BUTTON_HEIGHT: f32 = 24.0;
+/// Moves of the live analysis PV on each row
+const PV_ROW_MOVES: usize = 4;
+/// Chat messages past this many are dropped
+#[cfg(not(target_family = "wasm"))]
+const MAX_CHAT_LINES: usize = 50;
+/// Lines the log panel scrolls back by for each step of the mouse wheel
+const LOG_SCROLL_LINES: usize = 3;
+/// Variations past this aren't shown, but can still be reached with the keyboard
+const MAX_VARIATION_BUTTONS: usize = 4;
+/// Key moments listed at once, around the one being looked at
+const MAX_MOMENT_BUTTONS: usize = 5;
+/// Lines the agent searches when [Settings::engine_arrows](crate::settings::Settings::engine_arrows) is on, its
+/// move and the two best alternatives
+const ENGINE_ARROW_LINES: usize = 3;
+/// Banner over the top of the board when there's a newer release, and special moves are announced in
+const BANNER_HEIGHT: f32 = 32.0;
+#[cfg(not(target_family = "wasm"))]
+const BANNER_BUTTON_WIDTH: f32 = 72.0;
+
+/// Arrows for what the agent considered, from its search of the move it played
+/// - Its best alternatives in [COLOR_ENGINE_ALTERNATIVE], and the reply it expects in [COLOR_ENGINE_REPLY]
+fn engine_arrows(info: &SearchInfo) -> Vec<((Loc, Loc), Color)> {
+    let alternatives = info
+        .alternatives
+        .iter()
+        .filter_map(|(_, pv)| pv.first())
+        .map(|mov| (*mov, COLOR_ENGINE_ALTERNATIVE));
+    let reply = info.pv.get(1).map(|mov| (*mov, COLOR_ENGINE_REPLY));
+    alternatives.chain(reply).collect()
+}
+
+/// Logs `text` and copies it to the clipboard
+fn copy_to_clipboard(text: &str) {
+    info!("{}", text);
+    unsafe { get_internal_gl() }
+        .quad_context
+        .clipboard_set(text);
+}
+const AGENT_BUTTON_HEIGHT: f32 = 32.0;
+
+/// Choices for [GameOptions::max_depth], cycled through in the new game dialog
+const DEPTH_CAPS: [Option<u8>; 5] = [None, Some(2), Some(4), Some(6), Some(8)];
+
+/// Line of a mate, `None` if there isn't one
+type MateReply = Option<Vec<(Loc, Loc)>>;
+
+/// Result of a mate search, numbered one move per line
+/// - `checks_only` is wether the search only tried checks, see [MateSearch]
+fn mate_lines(board: &Board, n: u8, checks_only: bool, line: &MateReply) -> Vec<String> {
+    let Some(line) = line else {
+        let by = ternary!(checks_only, " by checks", "");
+        return vec![format!("No mate in {}{}", n, by)];
+    };
+
+    // Two plies per line, like the move list
+    let offset = ternary!(board.turn == ChessColor::Black, 1, 0);
+    let mut rows: Vec<String> = vec![];
+    for (i, san) in san_line(board, line).iter().enumerate() {
+        let black = (i + offset) % 2 == 1;
+        let number = board.full_moves() as usize + (i + offset) / 2 + 1;
+        match rows.last_mut() {
+            Some(row) if black && i != 0 => row.push_str(&format!(" {}", san)),
+            _ => rows.push(format!("{}{} {}", number, ternary!(black, "...", "."), san)),
+        }
+    }
+    [vec![format!("Mate in {}", line.len().div_ceil(2))], rows].concat()
+}
+
+/// Chance of white winning from a score for white, IE `0.5` when equal, how much of the eval bar is white
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn win_chance(score: i32) -> f32 {
+    1.0 / (1.0 + 10f32.powf(-score as f32 / 400.0))
+}
+
+/// How strong the flash over an illegal move's square is `elapsed` seconds after the move, `None` once it's gone
+pub(crate) fn flash_strength(elapsed: f64) -> Option<f32> {
+    (0.0..ILLEGAL_FLASH_TIME)
+        .contains(&elapsed)
+        .then(|| (1.0 - elapsed / ILLEGAL_FLASH_TIME) as f32)
+}
+
+/// How dark the board is `since` seconds from when it turns, fading out and back in around the turn
+pub(crate) fn flip_fade(since: f64) -> f32 {
+    (1.0 - since.abs() / FLIP_FADE).max(0.0) as f32
+}
+
+/// Wether either shift key is held, for the board's exchange tools
+fn shift_down() -> bool {
+    is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift)
+}
+
+/// Loads the database into the explorer panel, or closes the explorer
+#[cfg(not(target_family = "wasm"))]
+fn toggle_explorer() {
+    let mut explorer = EXPLORER.lock().unwrap();
+    if explorer.take().is_some() {
+        return;
+    }
+
+    match Database::load(DATABASE_PATH) {
+        Ok(database) => {
+            info!(
+                "Loaded {} games from {}, skipped {} unreadable",
+                database.games.len(),
+                DATABASE_PATH,
+                database.skipped
+            );
+            *explorer = Some(database);
+        }
+        Err(err) => info!("{}", err),
+    }
+}
+
+#[cfg(target_family = "wasm")]
+fn toggle_explorer() {
+    info!("The explorer can only load databases on desktop");
+}
+
+/// Network menu, showing wether a broadcast is running
+#[cfg(not(target_family = "wasm"))]
+fn network_menu() -> Overlay {
+    Overlay::network(BROADCAST.lock().unwrap().as_ref().map(|b| b.port))
+}
+
+/// Animation of a piece sliding from `from` to `to`, where it's drawn, if animations are on
+fn slide(from: Loc, to: Loc) -> Option<(Loc, Tween)> {
+    settings().animation.then(|| {
+        (
+            to,
+            Tween::new(
+                from.as_f32(),
+                to.as_f32(),
+                TWEEN_SPEED * settings().animation_speed,
+            ),
+        )
+    })
+}
+
+/// Animation of the piece that moved from `before` to `after`, if one did and animations are on
+fn tween_between(before: &Board, after: &Board) -> Vec<(Loc, Tween)> {
+    let Some((from, to)) = moved_piece(&before.diff(after)) else {
+        return vec![];
+    };
+    slide(from, to).into_iter().collect()
+}
+
+/// The game just before a move played by clicking, which [MenuAction::Takeback] rewinds to
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    board: Board,
+    last_move: Option<(Loc, Loc)>,
+    clock: Option<Clock>,
+    outlook: Outlook,
+    arrows: Vec<(Loc, Loc)>,
+    highlights: FxHashSet<Loc>,
+}
+
+/// Earlier position of the game shown instead of the live one, scrolled back to with the mouse wheel or
+/// [MenuAction::Back]
+#[derive(Clone)]
+pub(crate) struct Preview {
+    /// Moves played to reach it
+    pub(crate) ply: usize,
+    pub(crate) board: Board,
+    /// Move that led to it, IE `12. Nf3`, or `Start`
+    pub(crate) label: String,
+}
+
+/// What the board is waiting on, worked out each frame by [Game::turn_state] and run by [Game::update_turn]
+/// - Earlier states win, IE an agent thinking while the player scrolls back is still [TurnState::AgentThinking]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TurnState {
+    /// Picking the piece a pawn promotes to
+    PromotionPending,
+    /// Any other overlay is open, the board and the agent wait for it to close
+    Menu,
+    /// The game is over, or the drill or rush puzzle was answered
+    GameOver,
+    /// The move is coming over the network, see [Game::remote_turn]
+    RemoteTurn,
+    /// The agent's last move is still sliding into place, it thinks on the next one once it lands
+    Animating,
+    /// The agent is picking the side to move's move
+    AgentThinking(AgentId),
+    /// Scrolled back to an earlier position, clicking the board goes back to the current one
+    Replaying,
+    /// The side to move is played by clicking or typing moves
+    PlayerTurn,
+}
+
+/// Color for the player when [GameOptions::color] is random
+fn random_color() -> ChessColor {
+    ternary!(gen_range(0, 2) == 0, ChessColor::White, ChessColor::Black)
+}
+
+/// Choices from the new game dialog, used to construct a [Game]
+#[derive(Clone, Debug, PartialEq, new)]
+pub(crate) struct GameOptions {
+    /// Color the player plays as, `None` for a random color each game
+    #[new(value = "Some(ChessColor::White)")]
+    pub(crate) color: Option<ChessColor>,
+
+    #[new(value = "AgentId::MINIMAX")]
+    pub(crate) agent: AgentId,
+
+    /// Agent playing the player's color, `None` for a human, so two agents can play each other
+    #[new(value = "None")]
+    pub(crate) player_agent: Option<AgentId>,
+
+    /// Depth the opponent agent searches to, ignoring the difficulty's time limit, `None` to search by time
+    #[new(value = "None")]
+    pub(crate) max_depth: Option<u8>,
+
+    /// `None` for an untimed game
+    #[new(value = "None")]
+    pub(crate) time_control: Option<TimeControl>,
+
+    /// Starting position, `None` for the standard starting position
+    #[new(value = "None")]
+    pub(crate) fen: Option<String>,
+
+    /// Odds the opponent agent gives, which sets the starting position instead of [GameOptions::fen]
+    #[new(value = "None")]
+    pub(crate) handicap: Option<Handicap>,
+
+    #[new(value = "Variant::Standard")]
+    pub(crate) variant: Variant,
+
+    /// Wether takebacks and hints are off and the result counts towards the player's rating, see [Game::rated]
+    #[new(value = "false")]
+    pub(crate) rated: bool,
+}
+impl GameOptions {
+    pub(crate) fn next_color(&mut self) {
+        self.color = match self.color {
+            Some(ChessColor::White) => Some(ChessColor::Black),
+            Some(ChessColor::Black) => None,
+            None => Some(ChessColor::White),
+        };
+    }
+
+    pub(crate) fn next_agent(&mut self) {
+        let agents: Vec<_> = agents().map(|registered| registered.id).collect();
+        // The agent may not be available anymore, IE an engine from a game saved with one
+        let i = agents.iter().position(|agent| agent == &self.agent);
+        self.agent = agents[i.map_or(0, |i| (i + 1) % agents.len())];
+    }
+
+    /// Cycles between a human and every agent that plays by itself
+    pub(crate) fn next_player_agent(&mut self) {
+        let agents: Vec<_> = agents()
+            .map(|registered| registered.id)
+            .filter(|agent| agent != &AgentId::CONTROL)
+            .collect();
+        self.player_agent = match self.player_agent {
+            None => Some(agents[0]),
+            Some(current) => agents
+                .iter()
+                .position(|agent| agent == &current)
+                .and_then(|i| agents.get(i + 1).copied()),
+        };
+    }
+
+    pub(crate) fn next_max_depth(&mut self) {
+        let i = DEPTH_CAPS
+            .iter()
+            .position(|depth| depth == &self.max_depth)
+            .unwrap();
+        self.max_depth = DEPTH_CAPS[(i + 1) % DEPTH_CAPS.len()];
+    }
+
+    pub(crate) fn next_time_control(&mut self) {
+        let i = TIME_CONTROLS
+            .iter()
+            .position(|tc| tc == &self.time_control)
+            .unwrap();
+        self.time_control = TIME_CONTROLS[(i + 1) % TIME_CONTROLS.len()];
+    }
+
+    /// Cycles through the handicaps, replacing any pasted FEN
+    pub(crate) fn next_handicap(&mut self) {
+        let i = HANDICAPS
+            .iter()
+            .position(|handicap| handicap == &self.handicap)
+            .unwrap();
+        self.handicap = HANDICAPS[(i + 1) % HANDICAPS.len()];
+        self.fen = None;
+    }
+
+    /// [GameOptions::handicap], unless the variant has its own starting position
+    pub(crate) fn odds(&self) -> Option<Handicap> {
+        self.handicap.filter(|_| self.variant.start_fen().is_none())
+    }
+
+    /// Switches between the standard position and a FEN pasted from the clipboard
+    /// - A pasted FEN replaces any handicap
+    pub(crate) fn next_fen(&mut self) {
+        if self.fen.is_some() {
+            self.fen = None;
+            return;
+        }
+
+        let clipboard = unsafe { get_internal_gl() }.quad_context.clipboard_get();
+        let Some(fen) = clipboard.map(|fen| fen.trim().to_string()) else {
+            info!("Clipboard is empty");
+            return;
+        };
+        match Board::try_from_fen(&fen) {
+            Ok(_) => {
+                self.fen = Some(fen);
+                self.handicap = None;
+            }
+            Err(issues) => info!("Can't play from FEN: {}", describe_issues(&issues)),
+        }
+    }
+}
 
 #[derive(Clone, new)]
 pub(crate) struct Game {
     #[new(value = "Board::from_fen(FEN)")]
     pub(crate) board: Board,
 
+    /// Each position a move was played by clicking from, see [Snapshot]
     #[new(value = "vec![]")]
-    pub(crate) board_history: Vec<(Board, Option<(Loc, Loc)>)>,
+    pub(crate) board_history: Vec<Snapshot>,
+
+    /// FEN the game started from, for PGN export
+    #[new(value = "FEN.to_string()")]
+    pub(crate) start_fen: String,
+
+    /// Every move played so far, for PGN export
+    #[new(value = "vec![]")]
+    pub(crate) moves: Vec<(Loc, Loc)>,
+
+    /// PGN comment for each move in [Game::moves]
+    #[new(value = "vec![]")]
+    pub(crate) comments: Vec<Option<String>>,
+
+    /// Think time, depth, and score of each move in [Game::moves] an agent played
+    #[new(value = "vec![]")]
+    pub(crate) move_stats: Vec<Option<MoveStats>>,
+
+    /// Agent playing the player's color, see [GameOptions::player_agent]
+    #[new(value = "None")]
+    pub(crate) player_agent: Option<AgentId>,
+
+    /// Background analysis, when turned on in settings
+    #[new(value = "Analysis::new()")]
+    pub(crate) analysis: Analysis,
+
+    /// Game being replayed, `None` while playing normally
+    #[new(value = "None")]
+    pub(crate) replay: Option<Replay>,
+
+    /// Repertoire position being drilled, `None` while playing normally
+    #[new(value = "None")]
+    pub(crate) drill: Option<Drill>,
+
+    /// Puzzle rush the board is a puzzle of, `None` while playing normally
+    #[new(value = "None")]
+    pub(crate) rush: Option<Rush>,
+
+    /// Search of the current position shown in the panel, kept after it's stopped to show where it got to
+    #[new(value = "None")]
+    pub(crate) live_analysis: Option<LiveAnalysis>,
+
+    /// Wether this is an analysis board, a replay with the eval bar running, see [MenuAction::AnalysisBoard]
+    #[new(value = "false")]
+    pub(crate) analysis_board: bool,
+
+    /// Search of the current position for the eval bar of the analysis board, restarted after each move
+    #[cfg(not(target_family = "wasm"))]
+    #[new(value = "None")]
+    pub(crate) eval_search: Option<LiveAnalysis>,
+
+    /// (white, black) latest search of each agent when two agents play each other, shown in the panel
+    #[new(value = "(None, None)")]
+    pub(crate) engine_lines: (Option<String>, Option<String>),
 
     #[new(value = "None")]
     pub(crate) selected: Option<Piece>,
@@ -38,28 +679,40 @@ pub(crate) struct Game {
     #[new(value = "vec![]")]
     pub(crate) highlight_moves: Vec<Loc>,
 
-    #[new(value = "Agent::Minimax")]
-    pub(crate) agent: Agent,
+    /// Wether the board is drawn from black's side, see [MenuAction::FlipBoard]
+    #[new(value = "false")]
+    pub(crate) flipped: bool,
+
+    /// Piece whose table is drawn over the board, from the side at the bottom, see [MenuAction::Tables]
+    #[new(value = "None")]
+    pub(crate) table_view: Option<PieceNames>,
 
-    #[new(value = "{
-        let mut temp = vec![];
+    /// When the board turns to the side to move in a local two player game, see [Game::auto_flips]
+    #[new(value = "None")]
+    pub(crate) flip_at: Option<f64>,
 
-        for (i, (key, value)) in AGENTS.iter().enumerate() {
-            temp.push((
-                Button::new(
-                    SQUARE_SIZE * 8.0 + MARGIN * 2.0,
-                    HEIGHT as f32 - (50.0 + MARGIN) * (i as f32 + 1.0),
-                    EXTRA_WIDTH,
-                    50.0,
-                    key,
-                ),
-                *value,
-            ));
-        }
+    /// (square of the last move that couldn't be made, when it was tried), flashed red for a moment
+    #[new(value = "None")]
+    pub(crate) illegal_flash: Option<(Loc, f64)>,
+
+    /// Earlier position shown while scrolling through a game that isn't a replay, see [Game::step_preview]
+    #[new(value = "None")]
+    pub(crate) preview: Option<Preview>,
+
+    /// (color, when) of the last [ClockAlert](crate::clock::ClockAlert), whose clock flashes for a moment
+    #[new(value = "None")]
+    pub(crate) clock_flash: Option<(ChessColor, f64)>,
+
+    /// (color, seconds, when) of the last increment, shown next to the clock it was added to for a moment
+    #[new(value = "None")]
+    pub(crate) increment_shown: Option<(ChessColor, f64, f64)>,
+
+    /// (text, when) of the last castling, en passant, or promotion, announced over the board for a moment
+    #[new(value = "None")]
+    pub(crate) announcement: Option<(String, f64)>,
 
-        temp
-    }")]
-    pub(crate) agent_buttons: Vec<(Button, Agent)>,
+    #[new(value = "AgentId::MINIMAX")]
+    pub(crate) agent: AgentId,
 
     #[new(value = "false")]
     pub(crate) waiting_on_agent: bool,
@@ -67,13 +720,37 @@ pub(crate) struct Game {
     #[new(value = "None")]
     pub(crate) last_move: Option<(Loc, Loc)>,
 
-    /// (loc of piece that is being tweened, tween)
+    /// SAN of [Game::last_move], shown in the panel while pieces are hidden
     #[new(value = "None")]
-    pub(crate) current_tween: Option<(Loc, Tween)>,
+    pub(crate) last_san: Option<String>,
+
+    /// (loc of piece that is being tweened, tween) for each piece sliding, IE both moves of a takeback
+    #[new(value = "vec![]")]
+    pub(crate) current_tween: Vec<(Loc, Tween)>,
 
     #[new(value = "vec![]")]
     pub(crate) arrows: Vec<(Loc, Loc)>,
 
+    /// Moves shown to the player, IE the right move in a drill, see [DrawOptions::hint_arrows]
+    #[new(value = "vec![]")]
+    pub(crate) hint_arrows: Vec<(Loc, Loc)>,
+
+    /// What the agent considered for its last move, see [engine_arrows]
+    #[new(value = "vec![]")]
+    pub(crate) engine_arrows: Vec<((Loc, Loc), Color)>,
+
+    /// Key moments listed in the side panel, see [MenuAction::Review]
+    #[new(value = "None")]
+    pub(crate) review: Option<Review>,
+
+    /// Game left with [MenuAction::PlayFromHere], kept through new games until it's returned to
+    #[new(value = "None")]
+    pub(crate) original: Option<Box<Game>>,
+
+    /// Tab action for [Tabs](crate::tabs::Tabs) to carry out once the frame is over, since tabs hold the game
+    #[new(value = "None")]
+    pub(crate) tab_action: Option<MenuAction>,
+
     #[new(value = "None")]
     pub(crate) drag_start: Option<Loc>,
 
@@ -83,114 +760,2566 @@ pub(crate) struct Game {
     #[new(value = "hashset!{}")]
     pub(crate) highlights: FxHashSet<Loc>,
 
+    /// Overlay open over the game, IE the pause menu
+    #[new(value = "None")]
+    pub(crate) overlay: Option<Overlay>,
+
+    /// Options the game was started with, reused as the defaults for the next one
+    #[new(value = "GameOptions::new()")]
+    pub(crate) options: GameOptions,
+
+    /// `None` for an untimed game
+    #[new(value = "None")]
+    pub(crate) clock: Option<Clock>,
+
+    /// Scores of the opponent agent, to decide when it resigns or offers a draw
+    #[new(value = "Outlook::default()")]
+    pub(crate) outlook: Outlook,
+
+    #[new(value = "unbounded()")]
+    pub(crate) agent_channel: (Sender<AgentMessage>, Receiver<AgentMessage>),
+
+    /// Shares each frame's time between [BACKGROUND_TASKS]
+    #[new(value = "Scheduler::new(FRAME_BUDGET)")]
+    pub(crate) scheduler: Scheduler,
+
+    /// Agent picking its move a slice each frame, since the web has no threads to think on
+    #[cfg(target_family = "wasm")]
+    #[new(value = "None")]
+    pub(crate) thinking: Option<Thinking>,
+
+    /// Latest depth the agent thinking has finished
+    #[new(value = "None")]
+    pub(crate) agent_info: Option<SearchInfo>,
+
+    /// What the agent's last search depended on and found, see [repro](crate::repro)
+    #[new(value = "None")]
+    pub(crate) decision: Option<Decision>,
+
+    /// Position the agent last searched and what it found, with its search tree, see
+    /// [Settings::search_tree](crate::settings::Settings::search_tree)
+    #[new(value = "None")]
+    pub(crate) last_search: Option<(Board, SearchInfo)>,
+
+    /// When the agent started thinking about its move, for [MoveStats]
+    #[new(value = "0.0")]
+    pub(crate) think_start: f64,
+
+    /// Opening the agent thinking is playing from the book, if any
+    #[new(value = "None")]
+    pub(crate) agent_opening: Option<String>,
+
+    /// Position, number of moves, and wether only checks are tried, of the mate search running, if any
+    #[new(value = "None")]
+    pub(crate) mate_search: Option<(Board, u8, bool)>,
+
+    /// Mate search running a slice each frame, since the web has no threads to search on
+    #[cfg(target_family = "wasm")]
+    #[new(value = "None")]
+    pub(crate) mate_slices: Option<MateSearch>,
+
+    /// Stops the mate search's thread once dropped, IE when the game is reset
+    #[cfg(not(target_family = "wasm"))]
+    #[new(value = "None")]
+    pub(crate) mate_stop: Option<Arc<StopOnDrop>>,
+
     #[allow(clippy::type_complexity)]
     #[new(value = "unbounded()")]
-    pub(crate) agent_channel: (Sender<Option<(Loc, Loc)>>, Receiver<Option<(Loc, Loc)>>),
+    pub(crate) mate_channel: (Sender<MateReply>, Receiver<MateReply>),
+
+    /// Connection to the opponent of a LAN game, or to the one being set up
+    #[cfg(not(target_family = "wasm"))]
+    #[new(value = "None")]
+    pub(crate) lan: Option<Lan>,
+
+    /// Wether the other color is played over [Game::lan], only set once the opponent connects
+    #[new(value = "false")]
+    pub(crate) lan_game: bool,
+
+    /// (when, clock) at the start of the current turn of a LAN game, to time moves against
+    #[cfg(not(target_family = "wasm"))]
+    #[new(value = "None")]
+    pub(crate) lan_turn: Option<(f64, Clock)>,
+
+    /// Chat with the LAN opponent, and what happened on the connection, oldest first
+    #[new(value = "vec![]")]
+    pub(crate) chat: Vec<String>,
+
+    /// Wether the side panel shows the log, see [MenuAction::Log]
+    #[new(value = "false")]
+    pub(crate) show_log: bool,
+
+    /// Wrapped lines the log is scrolled back by, `0` for the newest
+    #[new(value = "0")]
+    pub(crate) log_scroll: usize,
+
+    /// Wether the log shows the agent's searches, hidden to make the rest easier to find
+    #[new(value = "true")]
+    pub(crate) log_searches: bool,
+
+    /// Broadcast being watched, `None` while playing normally
+    #[cfg(not(target_family = "wasm"))]
+    #[new(value = "None")]
+    pub(crate) watching: Option<Spectator>,
+
+    /// (hash, moves, over) of the position last sent to the broadcast, to only send changes
+    #[cfg(not(target_family = "wasm"))]
+    #[new(value = "None")]
+    pub(crate) published: Option<(u64, usize, bool)>,
+
+    /// What reacts to moves being played, see [Game::event_bus]
+    #[new(value = "Game::event_bus()")]
+    pub(crate) events: EventBus<Game>,
 }
 impl Game {
-    fn clicked_square(&self, button: MouseButton) -> Option<Loc> {
-        if is_mouse_button_pressed(button) {
-            return pos_to_board(camera().mouse_position().into());
+    /// Sound, clock alerts, animation, turning the board, returning from a preview, network sync, autosaving, and
+    /// logging, called in this order for each event
+    fn event_bus() -> EventBus<Game> {
+        let mut bus = EventBus::new();
+        bus.subscribe(Game::queue_move_sound);
+        bus.subscribe(Game::show_clock_event);
+        bus.subscribe(Game::animate_move);
+        bus.subscribe(Game::schedule_flip);
+        bus.subscribe(Game::end_preview);
+        bus.subscribe(Game::announce_special_move);
+        #[cfg(not(target_family = "wasm"))]
+        bus.subscribe(Game::send_lan_event);
+        // Games played in tests would otherwise be offered for restoring on the next launch
+        #[cfg(not(target_family = "wasm"))]
+        if !cfg!(test) {
+            bus.subscribe(Game::autosave);
+            bus.subscribe(Game::add_stats);
         }
+        bus.subscribe(Game::log_event);
+        bus
+    }
 
-        None
+    /// Queues the move, capture, check, or game end sound, or the move sound for a takeback, see [sound](crate::sound)
+    fn queue_move_sound(&mut self, event: &GameEvent) {
+        let kind = match event {
+            GameEvent::Move { capture, .. } => {
+                ternary!(*capture, SoundKind::Capture, SoundKind::Move)
+            }
+            GameEvent::Takeback { .. } => SoundKind::Move,
+            GameEvent::Check(_) => SoundKind::Check,
+            GameEvent::GameEnd(_) => SoundKind::GameEnd,
+            _ => return,
+        };
+        queue_sound(kind);
     }
 
-    fn move_piece(&mut self, from: &Loc, to: &Loc) {
-        if self.board.turn == self.board.player_color {
-            self.board_history
-                .push((self.board.clone(), self.last_move));
+    /// Buzzes when a move can't be made, if [Settings::illegal_sound](crate::settings::Settings::illegal_sound) is on
+    fn play_illegal_sound(&self) {
+        if settings().illegal_sound {
+            queue_sound(SoundKind::Illegal);
         }
+    }
 
-        let capture = self.board.move_piece(from, to, true);
+    /// Flashes `to` red and deselects the piece that couldn't move there
+    fn reject_move(&mut self, to: &Loc) {
+        self.illegal_flash = Some((*to, date::now()));
         self.selected = None;
         self.highlight_moves.clear();
-        self.highlights.clear();
-        self.arrows.clear();
-        self.last_move = Some((*from, *to));
-        self.current_tween = Some((*to, Tween::new(from.as_f32(), to.as_f32(), 20.0)));
+        self.play_illegal_sound();
+    }
 
-        // See if move was capture
-        if capture {
-            play_sound(
-                get_audio("assets/sounds/capture.wav"),
-                PlaySoundParams::default(),
-            );
-        } else {
-            play_sound(
-                get_audio("assets/sounds/move.wav"),
-                PlaySoundParams::default(),
-            );
+    /// Flashes the clock for an alert, ticking for people's clocks but not the agent's, and shows increments
+    fn show_clock_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::ClockAlert { color, .. } => {
+                self.clock_flash = Some((*color, date::now()));
+                if self.agent_for(*color).is_none() {
+                    queue_sound(SoundKind::Tick);
+                }
+            }
+            GameEvent::Increment { color, seconds } => {
+                self.increment_shown = Some((*color, *seconds, date::now()));
+            }
+            _ => {}
         }
     }
 
-    fn reset(&mut self) {
-        *self = Game::new();
+    /// Announces castling, en passant, and promotion, rules that are easy to miss when starting out
+    fn announce_special_move(&mut self, event: &GameEvent) {
+        if !settings().announce_moves {
+            return;
+        }
+        let text = match event {
+            GameEvent::Castle { color, kingside } => format!(
+                "{:?} castled {}",
+                color,
+                ternary!(*kingside, "kingside (O-O)", "queenside (O-O-O)")
+            ),
+            GameEvent::EnPassant { color, .. } => format!("{:?} took en passant", color),
+            GameEvent::Promotion { piece, .. } => {
+                format!(
+                    "Pawn promoted to a {}",
+                    format!("{:?}", piece).to_lowercase()
+                )
+            }
+            _ => return,
+        };
+        self.announcement = Some((text, date::now()));
     }
 
-    fn update_keys(&mut self) {
-        if is_key_pressed(KeyCode::F) {
-            self.board.print();
-        }
-        if is_key_pressed(KeyCode::T) {
-            info!("{}", self.board.as_fen());
+    /// Draws the last special move over the bottom of the board, see [Game::announce_special_move]
+    fn draw_announcement(&self) {
+        let Some((text, at)) = &self.announcement else {
+            return;
+        };
+        let elapsed = date::now() - at;
+        if elapsed >= ANNOUNCEMENT_TIME {
+            return;
         }
-        if is_key_pressed(KeyCode::R) {
-            if self.waiting_on_agent {
-                info!("Waiting on agent...");
-            } else {
-                self.reset();
-            }
+        let alpha = (ANNOUNCEMENT_TIME - elapsed).min(1.0) as f32;
+
+        let params = text_params(18);
+        let size = measure_text(text, Some(params.font), params.font_size, params.font_scale);
+        let layout = layout();
+        let (width, height) = (size.width + layout.margin * 2.0, BANNER_HEIGHT);
+        let x = layout.margin + (layout.square_size * 8.0 - width) / 2.0;
+        let y = layout.top + layout.square_size * 8.0 - height - layout.margin;
+        draw_rectangle(
+            x,
+            y,
+            width,
+            height,
+            Color {
+                a: COLOR_OVERLAY.a * alpha,
+                ..COLOR_OVERLAY
+            },
+        );
+        draw_text_ex(
+            text,
+            x + layout.margin,
+            y + height / 2.0 + size.offset_y / 2.0,
+            TextParams {
+                color: Color {
+                    a: alpha,
+                    ..params.color
+                },
+                ..params
+            },
+        );
+    }
+
+    /// Lists how many pieces attack and defend the hovered piece while shift is held
+    /// - Off in rated games, and in fog of war where it'd give away hidden pieces
+    fn draw_attack_tooltip(&self) {
+        if !shift_down() || self.overlay.is_some() || self.rated() || self.revealed().is_some() {
+            return;
         }
-        if is_key_pressed(KeyCode::L) {
-            if self.waiting_on_agent {
-                info!("Waiting on agent...");
-            } else if let Some((board, last_move)) = self.board_history.pop() {
-                self.board = board;
-                self.selected = None;
-                self.last_move = last_move;
+        let Some(lines) = self
+            .mouse_square()
+            .and_then(|loc| attack_lines(self.shown_board(), &loc))
+        else {
+            return;
+        };
+
+        let params = text_params(16);
+        let layout = layout();
+        let sizes = lines
+            .each_ref()
+            .map(|line| measure_text(line, Some(params.font), params.font_size, params.font_scale));
+        let line_height = sizes[0].offset_y + layout.margin / 2.0;
+        let width = sizes[0].width.max(sizes[1].width) + layout.margin * 2.0;
+        let height = line_height * 2.0 + layout.margin;
+        let (mouse_x, mouse_y) = camera().mouse_position().into();
+        let x = (mouse_x + layout.margin).min(layout.width - width);
+        let y = (mouse_y + layout.margin).min(layout.height - height);
+        draw_rectangle(x, y, width, height, COLOR_OVERLAY);
+        for (i, line) in lines.iter().enumerate() {
+            draw_text_ex(
+                line,
+                x + layout.margin,
+                y + layout.margin / 2.0 + line_height * (i + 1) as f32,
+                params,
+            );
+        }
+    }
+
+    /// Announces what taking the piece on `loc` wins, see [exchange_line]
+    fn announce_exchange(&mut self, loc: &Loc) {
+        if self.rated() || self.revealed().is_some() {
+            info!("The exchange readout is off in rated and fog of war games");
+            return;
+        }
+        if let Some(text) = exchange_line(self.shown_board(), loc) {
+            self.announcement = Some((text, date::now()));
+        }
+    }
+
+    /// Slides the piece that moved to its new square, or the pieces taken back to where they were
+    fn animate_move(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::Move { from, to, .. } => {
+                self.current_tween = slide(*from, *to).into_iter().collect();
+            }
+            GameEvent::Takeback { moves } => {
+                self.current_tween = moves
+                    .iter()
+                    .filter_map(|(from, to)| slide(*to, *from))
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+
+    /// Turns the board to the other player shortly after each move, when [Game::auto_flips]
+    fn schedule_flip(&mut self, event: &GameEvent) {
+        if matches!(event, GameEvent::Move { .. }) && self.auto_flips() {
+            self.flip_at = Some(date::now() + FLIP_DELAY);
+        }
+    }
+
+    /// Returns to the live game when a move is played or taken back
+    fn end_preview(&mut self, event: &GameEvent) {
+        if matches!(event, GameEvent::Move { .. } | GameEvent::Takeback { .. }) {
+            self.preview = None;
+        }
+    }
+
+    /// Shows the position `delta` moves from the one shown, back to the live game past the last move
+    pub(crate) fn step_preview(&mut self, delta: isize) {
+        let shown = self.preview.as_ref().map_or(self.moves.len(), |p| p.ply);
+        let ply = shown.saturating_add_signed(delta).min(self.moves.len());
+        if ply == shown {
+            return;
+        }
+        if ply == self.moves.len() {
+            self.preview = None;
+            return;
+        }
+
+        let mut board = self.start_board();
+        board.player_color = self.board.player_color;
+        board.agent_color = self.board.agent_color;
+        let mut label = "Start".to_string();
+        for (from, to) in self.moves[..ply].iter() {
+            label = format!(
+                "{}{} {}",
+                board.full_moves() + 1,
+                ternary!(board.turn == ChessColor::White, ".", "..."),
+                san(&board, from, to)
+            );
+            board.move_piece(from, to, true);
+        }
+        self.selected = None;
+        self.highlight_moves.clear();
+        self.preview = Some(Preview { ply, board, label });
+    }
+
+    /// Board drawn, the live one unless scrolled back to an earlier position
+    fn shown_board(&self) -> &Board {
+        self.preview.as_ref().map_or(&self.board, |p| &p.board)
+    }
+
+    /// Steps through the game with the mouse wheel over the board, and returns to the live game on a click
+    /// - Returns `true` if a click was used to return, so it isn't also taken as a move
+    fn update_preview(&mut self) -> bool {
+        if self.preview.is_some() && self.clicked_square(MouseButton::Left).is_some() {
+            self.preview = None;
+            return true;
+        }
+
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 && self.mouse_square().is_some() {
+            // Up goes back, like scrolling up a move list
+            self.run_action(ternary!(wheel > 0.0, MenuAction::Back, MenuAction::Forward));
+        }
+        false
+    }
+
+    /// Sends the player's moves to the LAN opponent with the time they took, and if hosting the clock after each
+    /// move and who ran out of time
+    #[cfg(not(target_family = "wasm"))]
+    fn send_lan_event(&mut self, event: &GameEvent) {
+        let Some(lan) = self.lan.as_ref().filter(|_| self.lan_game) else {
+            return;
+        };
+        let host = matches!(lan.role, LanRole::Host(_));
+        match event {
+            GameEvent::Move {
+                color, coordinate, ..
+            } => {
+                if *color == self.board.player_color {
+                    let think = self.lan_turn.map(|(start, _)| date::now() - start);
+                    lan.send_move(coordinate, think);
+                }
+                if let Some(clock) = self.clock.as_ref() {
+                    if host {
+                        lan.send_clock(clock);
+                    }
+                }
+                self.lan_turn = self.clock.map(|clock| (date::now(), clock));
+            }
+            GameEvent::GameEnd(BoardState::Timeout(color)) if host => lan.send_flag(*color),
+            _ => {}
+        }
+    }
+
+    /// Saves the game every [AUTOSAVE_INTERVAL] moves, and removes the save once it ends
+    /// - Replays, LAN games, and broadcasts being watched are saved elsewhere or not at all
+    #[cfg(not(target_family = "wasm"))]
+    fn autosave(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::Move { .. } => {
+                if self.replay.is_some() || self.lan_game || self.watching.is_some() {
+                    return;
+                }
+                if self.moves.len().is_multiple_of(AUTOSAVE_INTERVAL) {
+                    if let Err(err) = self.record().save(&autosave_path()) {
+                        warn!("Couldn't autosave: {}", err);
+                    }
+                }
+            }
+            GameEvent::GameEnd(_) => remove_autosave(),
+            _ => {}
+        }
+    }
+
+    /// Adds a finished game against the agent from the starting position to the player's rating and opening stats
+    #[cfg(not(target_family = "wasm"))]
+    fn add_stats(&mut self, event: &GameEvent) {
+        let GameEvent::GameEnd(state) = event else {
+            return;
+        };
+        if self.replay.is_some()
+            || self.lan_game
+            || self.watching.is_some()
+            || self.player_agent.is_some()
+            || self.agent == AgentId::CONTROL
+            || self.start_fen != FEN
+            || self.board.variant != Variant::Standard
+        {
+            return;
+        }
+
+        let opponent = Opponent::new(self.agent, self.options.max_depth, settings().difficulty);
+        let color = self.board.player_color;
+        let added = PlayerStats::load(STATS_PATH).and_then(|mut stats| {
+            let rated = self.options.rated && stats.add_rated(&opponent, color, state);
+            if stats.add_opening(&self.moves, color, state) || rated {
+                stats.save(STATS_PATH)?;
+            }
+            Ok(())
+        });
+        if let Err(err) = added {
+            warn!("Couldn't add to the stats: {}", err);
+        }
+    }
+
+    /// What's autosaved of the game, see [GameRecord]
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn record(&self) -> GameRecord {
+        GameRecord {
+            start_fen: self.start_fen.clone(),
+            moves: self.moves.clone(),
+            player_color: self.board.player_color,
+            agent: self.agent,
+            player_agent: self.player_agent,
+            max_depth: self.options.max_depth,
+            time_control: self.options.time_control,
+            variant: self.board.variant,
+            rated: self.options.rated,
+            analysis_board: self.analysis_board,
+            clock: self.clock,
+            settings: *settings(),
+        }
+    }
+
+    /// Starts an autosaved game again and replays its moves, restoring the settings it was played with
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn restore(record: &GameRecord) -> Game {
+        let mut restored = record.settings;
+        // Themes could have been removed since
+        restored.theme = restored.theme.min(THEMES.len() - 1);
+        *settings() = restored;
+
+        let mut options = GameOptions::new();
+        options.color = Some(record.player_color);
+        options.agent = record.agent;
+        options.player_agent = record.player_agent;
+        options.max_depth = record.max_depth;
+        options.time_control = record.time_control;
+        options.fen = Some(record.start_fen.clone());
+        options.variant = record.variant;
+        options.rated = record.rated;
+        let mut game = Game::from_options(options);
+        for (from, to) in record.moves.iter() {
+            if !game.board.moves(game.board.turn).contains(&(*from, *to)) {
+                info!("Autosave has an illegal move, restored up to it");
+                break;
+            }
+            game.move_piece(from, to);
+        }
+        game.current_tween.clear();
+        // The times left are saved, so increments from the moves above are already counted
+        game.clock = record.clock;
+        if record.analysis_board {
+            game.toggle_analysis_board();
+        }
+        game
+    }
+
+    fn log_event(&mut self, event: &GameEvent) {
+        if let GameEvent::GameEnd(state) = event {
+            info!("{}", state.message(self.board.player_color));
+        }
+    }
+
+    pub(crate) fn from_options(options: GameOptions) -> Game {
+        let mut game = Game::new();
+
+        let player_color = options.color.unwrap_or_else(random_color);
+        let fen = match options.odds() {
+            Some(handicap) => Some(handicap.fen(player_color.other())),
+            None => options
+                .fen
+                .clone()
+                .or(options.variant.start_fen().map(str::to_string)),
+        };
+        if let Some(fen) = fen {
+            game.board = Board::from_fen(&fen);
+            game.start_fen = fen;
+        }
+        game.board.player_color = player_color;
+        game.board.agent_color = player_color.other();
+        game.board.set_variant(options.variant);
+
+        game.agent = options.agent;
+        game.player_agent = options.player_agent;
+        game.clock = options.time_control.as_ref().map(Clock::new);
+        game.options = options;
+        if game.auto_flips() {
+            game.face_turn();
+        }
+        game
+    }
+
+    /// Board at the start of the game, played by the same rules
+    fn start_board(&self) -> Board {
+        let mut board = Board::from_fen(&self.start_fen);
+        board.set_variant(self.board.variant);
+        board
+    }
+
+    /// The game so far as PGN
+    pub(crate) fn pgn(&self) -> String {
+        if let Some(replay) = self.annotated_replay() {
+            // The result is from the end of the main line
+            let mut end = self.start_board();
+            for (from, to) in replay.tree.moves(replay.tree.line(None).last().copied()) {
+                end.move_piece(&from, &to, true);
+            }
+            return export_tree(
+                &self.start_board(),
+                &replay.tree,
+                &replay.white,
+                &replay.black,
+                &end.state,
+            );
+        }
+
+        let (white, black) = self.player_names();
+        let comments: Vec<_> = self
+            .comments
+            .iter()
+            .zip(self.move_stats.iter())
+            .map(|(comment, stats)| stats_comment(comment.as_deref(), stats.as_ref()))
+            .collect();
+        export(
+            &self.start_board(),
+            &self.moves,
+            &comments,
+            &self.analysis.evals,
+            &white,
+            &black,
+            &self.board.state,
+        )
+    }
+
+    /// (white, black) names for PGN, with the odds the agent gives, IE `Minimax (knight odds)`
+    fn player_names(&self) -> (String, String) {
+        let agent = match self.options.odds() {
+            Some(handicap) => format!("{} ({})", self.agent, handicap.name().to_lowercase()),
+            None => format!("{}", self.agent),
+        };
+        let player = match self.player_agent {
+            Some(player_agent) => format!("{}", player_agent),
+            None => "Player".to_string(),
+        };
+        color_ternary!(self.board.player_color, (player, agent), (agent, player))
+    }
+
+    /// Agent playing `color`, `None` if it's played by clicking
+    fn agent_for(&self, color: ChessColor) -> Option<AgentId> {
+        if self.replay.is_some() {
+            None
+        } else if color == self.board.agent_color {
+            ternary!(self.agent == AgentId::CONTROL, None, Some(self.agent))
+        } else {
+            self.player_agent
+        }
+    }
+
+    /// Wether the board turns to the side to move after each move, in a local game between two people with
+    /// [Settings::auto_flip](crate::settings::Settings::auto_flip) on
+    fn auto_flips(&self) -> bool {
+        settings().auto_flip
+            && self.replay.is_none()
+            && !self.lan_game
+            && !self.remote_turn()
+            && self.agent_for(ChessColor::White).is_none()
+            && self.agent_for(ChessColor::Black).is_none()
+    }
+
+    /// Turns the board so the side to move is at the bottom
+    fn face_turn(&mut self) {
+        self.flipped = self.board.turn == ChessColor::Black;
+    }
+
+    /// Turns the board once [Game::flip_at] comes, and stops fading after
+    fn update_flip(&mut self) {
+        let Some(at) = self.flip_at else {
+            return;
+        };
+        let since = date::now() - at;
+        if since >= 0.0 {
+            self.face_turn();
+        }
+        if since >= FLIP_FADE {
+            self.flip_at = None;
+        }
+    }
+
+    /// Wether something on screen is moving or a search is running, so frames aren't limited, see
+    /// [FrameLimiter](crate::frame_limit::FrameLimiter)
+    pub(crate) fn busy(&self) -> bool {
+        let flashing = self
+            .illegal_flash
+            .is_some_and(|(_, tried)| flash_strength(date::now() - tried).is_some());
+        let searching = self.waiting_on_agent
+            || self.mate_search.is_some()
+            || self
+                .live_analysis
+                .as_ref()
+                .is_some_and(|live| !live.finished);
+        #[cfg(not(target_family = "wasm"))]
+        let searching = searching || self.eval_search.is_some();
+        let ticking = self.clock.is_some() && !self.board.is_over();
+
+        self.sliding() || flashing || self.flip_at.is_some() || searching || ticking
+    }
+
+    /// Wether the pieces of the last move are still sliding into place
+    fn sliding(&self) -> bool {
+        // Pieces only slide on desktop
+        #[cfg(not(target_family = "wasm"))]
+        return self
+            .current_tween
+            .iter()
+            .any(|(_, tween)| !tween.finished());
+        #[cfg(target_family = "wasm")]
+        false
+    }
+
+    /// Square under the mouse, taking [Game::flipped] into account
+    fn mouse_square(&self) -> Option<Loc> {
+        let loc = pos_to_board(camera().mouse_position().into())?;
+        Some(ternary!(self.flipped, loc.rotated(), loc))
+    }
+
+    /// Whether two agents are playing each other
+    fn spectating(&self) -> bool {
+        self.player_agent.is_some()
+    }
+
+    /// Squares the player can see in a fog of war game, `None` when the whole board is shown
+    /// - In a local two player game that's the side at the bottom, which changes under the fade as the board turns
+    fn revealed(&self) -> Option<SquareSet> {
+        // Everything is shown once the game is over, and when going through one
+        if self.board.is_over() || self.replay.is_some() {
+            return None;
+        }
+
+        let bottom = ternary!(self.flipped, ChessColor::Black, ChessColor::White);
+        let viewer = ternary!(self.auto_flips(), bottom, self.board.player_color);
+        self.board.variant.revealed(self.shown_board(), viewer)
+    }
+
+    fn clicked_square(&self, button: MouseButton) -> Option<Loc> {
+        if is_mouse_button_pressed(button) {
+            return self.mouse_square();
+        }
+
+        None
+    }
+
+    pub(crate) fn move_piece(&mut self, from: &Loc, to: &Loc) {
+        // Takebacks rewind to whoever clicked last, IE past the agent's reply to the player
+        if self.agent_for(self.board.turn).is_none() {
+            self.board_history.push(Snapshot {
+                board: self.board.clone(),
+                last_move: self.last_move,
+                clock: self.clock,
+                outlook: self.outlook,
+                arrows: self.arrows.clone(),
+                highlights: self.highlights.clone(),
+            });
+        }
+
+        // Adding nothing isn't shown
+        let increment = self
+            .clock
+            .as_mut()
+            .filter(|clock| clock.increment > 0.0)
+            .map(|clock| {
+                clock.add_increment(self.board.turn);
+                GameEvent::Increment {
+                    color: self.board.turn,
+                    seconds: clock.increment,
+                }
+            });
+
+        self.moves.push((*from, *to));
+        self.comments.push(None);
+        self.move_stats.push(None);
+
+        // Moves played while replaying add a variation, or follow the existing one
+        self.store_annotations();
+        if let Some(replay) = self.replay.as_mut() {
+            let san = san(&self.board, from, to);
+            let node = replay.tree.add(replay.current, (*from, *to), san);
+            replay.current = Some(node);
+            *self.comments.last_mut().unwrap() = replay.tree.nodes[node].comment.clone();
+            *self.move_stats.last_mut().unwrap() = replay.tree.nodes[node].stats;
+        }
+
+        self.last_san = Some(san(&self.board, from, to));
+        let before = self.board.clone();
+        self.board.move_piece(from, to, true);
+        self.selected = None;
+        self.highlight_moves.clear();
+        self.highlights.clear();
+        self.arrows.clear();
+        self.hint_arrows.clear();
+        self.engine_arrows.clear();
+        self.last_move = Some((*from, *to));
+
+        let mut events = move_events(&before, from, to, &self.board);
+        events.extend(increment);
+        self.events.clone().publish(self, &events);
+    }
+
+    /// Resets everything to a new game
+    /// - A running agent search is abandoned, the new channel means its result is never received
+    fn reset(&mut self, options: GameOptions) {
+        #[cfg(not(target_family = "wasm"))]
+        remove_autosave();
+        let original = self.original.take();
+        *self = Game::from_options(options);
+        self.original = original;
+    }
+
+    /// Replaces the game with one from PGN on the clipboard, to step through
+    fn import_pgn(&mut self) {
+        let Some(text) = unsafe { get_internal_gl() }.quad_context.clipboard_get() else {
+            info!("Clipboard is empty");
+            return;
+        };
+
+        match parse(&text) {
+            Ok(pgn) => self.load_pgn(pgn),
+            Err(err) => info!("Couldn't import PGN: {}", err),
+        }
+    }
+
+    /// Replaces the game with a replay of `pgn`, from its start
+    fn load_pgn(&mut self, pgn: PgnGame) {
+        self.load_replay(
+            &pgn.start_fen,
+            Replay {
+                white: pgn.tag("White").unwrap_or("?").to_string(),
+                black: pgn.tag("Black").unwrap_or("?").to_string(),
+                tree: pgn.tree,
+                current: None,
+            },
+            Variant::Standard,
+        );
+    }
+
+    /// Loads files dropped onto the window, asking which one to load if a file has more than one position or game
+    fn update_dropped(&mut self) {
+        for (name, bytes) in take_dropped() {
+            if self.overlay.as_ref().is_some_and(Overlay::blocking) {
+                info!("Can't load {} right now", name);
+                continue;
+            }
+            if self.lan_game {
+                info!("Can't leave a LAN game");
+                continue;
+            }
+
+            let mut items = match read_dropped(&name, &String::from_utf8_lossy(&bytes)) {
+                Ok(items) => items,
+                Err(err) => {
+                    info!("Couldn't load {}: {}", name, err);
+                    continue;
+                }
+            };
+            match items.len() {
+                0 => info!("{} is empty", name),
+                1 => self.load_dropped(items.remove(0)),
+                _ => self.overlay = Some(Overlay::choose_dropped(&name, items)),
+            }
+        }
+    }
+
+    /// Opens the new game dialog from a dropped position, or replays a dropped game
+    fn load_dropped(&mut self, dropped: Dropped) {
+        match dropped {
+            Dropped::Position { fen, .. } => {
+                let mut options = self.options.clone();
+                options.fen = Some(fen);
+                options.handicap = None;
+                self.overlay = Some(Overlay::new_game(options));
+            }
+            Dropped::Game(pgn) => self.load_pgn(pgn),
+        }
+    }
+
+    /// Replaces the game with a replay, at its current node, played by the rules of `variant`
+    fn load_replay(&mut self, start_fen: &str, replay: Replay, variant: Variant) {
+        // Both sides are played by clicking, from white's side
+        let mut options = GameOptions::new();
+        options.agent = AgentId::CONTROL;
+        options.fen = Some(start_fen.to_string());
+        options.variant = variant;
+        let mut game = Game::from_options(options);
+        game.options = self.options.clone();
+
+        let current = replay.current;
+        game.replay = Some(replay);
+        game.goto(current);
+        *self = game;
+    }
+
+    /// Turns a finished game into a replay with its key moments listed, or closes the list
+    fn toggle_review(&mut self) {
+        if self.review.take().is_some() {
+            return;
+        }
+        if self.replay.is_some() {
+            info!("Only games played here can be reviewed");
+            return;
+        }
+        if !self.board.is_over() {
+            info!("Finish the game to review it");
+            return;
+        }
+        if self.analysis.evals.iter().all(Option::is_none) {
+            info!("Turn on analysis in the settings to review games");
+            return;
+        }
+
+        let moments = key_moments(
+            &self.start_board(),
+            &self.moves,
+            &self.analysis.evals,
+            &self.analysis.best,
+        );
+        if moments.is_empty() {
+            info!("No key moments in this game");
+            return;
+        }
+        self.study_game();
+        self.review = Some(Review {
+            moments,
+            selected: None,
+        });
+    }
+
+    /// Writes the agent's last decision to [DECISION_PATH], see [repro](crate::repro)
+    #[cfg(not(target_family = "wasm"))]
+    fn save_decision(&self) {
+        if self.waiting_on_agent {
+            info!("The agent is still thinking");
+            return;
+        }
+        let Some(decision) = &self.decision else {
+            info!("The agent hasn't moved yet");
+            return;
+        };
+        match decision.save(DECISION_PATH) {
+            Ok(()) => info!(
+                "Saved the agent's decision to {}, rerun it with `chess-ai --repro`",
+                DECISION_PATH
+            ),
+            Err(err) => info!("{}", err),
+        }
+    }
+
+    /// Writes the analysis of the moves played so far, see [export_analysis]
+    #[cfg(not(target_family = "wasm"))]
+    fn export_analysis(&self) {
+        // Moves made in a review are on the replay, the game's are still in `moves`
+        if self.replay.is_some() && self.review.is_none() {
+            info!("Only games played here can be exported");
+            return;
+        }
+        if self.analysis.evals.iter().all(Option::is_none) {
+            info!("Turn on analysis in the settings to export it");
+            return;
+        }
+
+        let rows = move_analysis(
+            &self.start_board(),
+            &self.moves,
+            &self.analysis.evals,
+            &self.analysis.best,
+            &self.analysis.depths,
+        );
+        match export_analysis(&rows) {
+            Ok(()) => info!(
+                "Exported analysis to {} and {}",
+                ANALYSIS_CSV_PATH, ANALYSIS_JSON_PATH
+            ),
+            Err(err) => info!("{}", err),
+        }
+    }
+
+    /// Moves the review to the position before the key moment `i`
+    fn goto_moment(&mut self, i: usize) {
+        let (Some(review), Some(replay)) = (self.review.as_mut(), &self.replay) else {
+            return;
+        };
+        review.selected = Some(i);
+        let ply = review.moments[i].ply;
+        let node = ply.checked_sub(1).map(|ply| replay.tree.line(None)[ply]);
+        self.goto(node);
+    }
+
+    /// Draws the move the analysis preferred over the key moment being looked at
+    fn show_best_move(&mut self) {
+        let Some(i) = self.review.as_ref().and_then(|review| review.selected) else {
+            return;
+        };
+        self.goto_moment(i);
+        if let Some(best) = self
+            .review
+            .as_ref()
+            .and_then(|review| review.moments[i].best)
+        {
+            self.hint_arrows = vec![best];
+        }
+    }
+
+    /// Starts a fresh game against the agent from the position on the board, with the player moving first
+    /// - This game is kept in [Game::original] to return to
+    pub(crate) fn play_from_here(&mut self) {
+        if self.lan_game {
+            info!("Can't leave a LAN game");
+            return;
+        }
+        if self.board.is_over() {
+            info!("The game is over here");
+            return;
+        }
+
+        let mut options = self.options.clone();
+        options.fen = Some(self.board.as_fen());
+        options.handicap = None;
+        options.color = Some(self.board.turn);
+        options.variant = self.board.variant;
+        options.player_agent = None;
+        // Trying a position again is practice
+        options.rated = false;
+        if options.agent == AgentId::CONTROL {
+            options.agent = AgentId::MINIMAX;
+        }
+        let mut game = Game::from_options(options);
+        // Checks given aren't part of the FEN
+        game.board.checks = self.board.checks;
+        game.board.set_variant(game.board.variant);
+
+        let original = replace(self, game);
+        self.original = Some(Box::new(original));
+        info!(
+            "Playing from here, press {} to return to the game",
+            key_name(MenuAction::ReturnToGame).unwrap()
+        );
+    }
+
+    /// Goes back to the game [Game::play_from_here] was used in, as it was left
+    pub(crate) fn return_to_game(&mut self) {
+        match self.original.take() {
+            Some(original) => *self = *original,
+            None => info!("There's no game to return to"),
+        }
+    }
+
+    /// Turns the game so far into a replay, so it can be annotated
+    fn study_game(&mut self) {
+        if self.replay.is_some() {
+            return;
+        }
+
+        let mut tree = MoveTree::new();
+        let mut board = self.start_board();
+        let mut current = None;
+        for (i, (from, to)) in self.moves.iter().enumerate() {
+            let node = tree.add(current, (*from, *to), san(&board, from, to));
+            tree.nodes[node].comment = self.comments[i].clone();
+            tree.nodes[node].stats = self.move_stats[i];
+            board.move_piece(from, to, true);
+            current = Some(node);
+        }
+
+        let (white, black) = self.player_names();
+        let start_fen = self.start_fen.clone();
+        self.load_replay(
+            &start_fen,
+            Replay {
+                tree,
+                current,
+                white,
+                black,
+            },
+            self.board.variant,
+        );
+    }
+
+    /// Saves the arrows and highlights on the board to the current node of the replay
+    fn store_annotations(&mut self) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        let Some(current) = replay.current else {
+            return;
+        };
+
+        let node = &mut replay.tree.nodes[current];
+        node.arrows = self.arrows.clone();
+        node.highlights = self.highlights.iter().copied().collect();
+        node.highlights.sort_by_key(|loc| (loc.1, loc.0));
+    }
+
+    /// The replay, including annotations on the board that aren't stored yet
+    fn annotated_replay(&self) -> Option<Replay> {
+        let mut game = self.clone();
+        game.store_annotations();
+        game.replay
+    }
+
+    /// Jumps to the next node after the current one with annotations, along the current line
+    fn next_annotation(&mut self) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+
+        let ply = replay.tree.path(replay.current).len();
+        let next = replay.tree.line(replay.current)[ply..]
+            .iter()
+            .find(|node| replay.tree.nodes[**node].annotated())
+            .copied();
+        match next {
+            Some(next) => self.goto(Some(next)),
+            None => info!("No more annotations in this line"),
+        }
+    }
+
+    /// Sets the comment of the current node, `None` if `text` is blank
+    fn set_comment(&mut self, text: &str) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        let Some(current) = replay.current else {
+            return;
+        };
+
+        let text = text.trim();
+        let comment = ternary!(text.is_empty(), None, Some(text.to_string()));
+        replay.tree.nodes[current].comment = comment.clone();
+        if let Some(last) = self.comments.last_mut() {
+            *last = comment;
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn save_study(&self) {
+        let Some(replay) = self.annotated_replay() else {
+            info!("Only replays can be saved, use Study this game first");
+            return;
+        };
+
+        let study = Study {
+            start_fen: self.start_fen.clone(),
+            replay,
+        };
+        match study.save(STUDY_PATH) {
+            Ok(()) => info!("Saved study to {}", STUDY_PATH),
+            Err(err) => info!("{}", err),
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn open_study(&mut self) {
+        match Study::load(STUDY_PATH) {
+            Ok(study) => self.load_replay(&study.start_fen, study.replay, Variant::Standard),
+            Err(err) => info!("{}", err),
+        }
+    }
+
+    /// Adds the player's moves of the current game or replay line to the repertoire
+    #[cfg(not(target_family = "wasm"))]
+    fn add_to_repertoire(&self) {
+        let mut repertoire = match Repertoire::load(REPERTOIRE_PATH) {
+            Ok(repertoire) => repertoire,
+            Err(err) => {
+                info!("{}", err);
+                return;
+            }
+        };
+
+        let color = self.board.player_color;
+        let added = repertoire.add_line(&self.start_fen, &self.moves, color, date::now());
+        match repertoire.save(REPERTOIRE_PATH) {
+            Ok(()) => info!(
+                "Added {} {:?} positions to the repertoire, {} total",
+                added,
+                color,
+                repertoire.positions.len()
+            ),
+            Err(err) => info!("{}", err),
+        }
+    }
+
+    /// Replaces the game with the repertoire position due the longest
+    #[cfg(not(target_family = "wasm"))]
+    fn next_drill(&mut self) {
+        let repertoire = match self.drill.take() {
+            Some(drill) => drill.repertoire,
+            None => match Repertoire::load(REPERTOIRE_PATH) {
+                Ok(repertoire) => repertoire,
+                Err(err) => {
+                    info!("{}", err);
+                    return;
+                }
+            },
+        };
+
+        let Some(key) = repertoire.next_due(date::now()).map(str::to_string) else {
+            info!(
+                "Nothing to drill, {} positions in the repertoire",
+                repertoire.positions.len()
+            );
+            return;
+        };
+
+        // Both sides are played by clicking, from the side to move
+        let board = Board::from_fen(&format!("{} 0 1", key));
+        let mut options = GameOptions::new();
+        options.agent = AgentId::CONTROL;
+        options.color = Some(board.turn);
+        options.fen = Some(board.as_fen());
+        let mut game = Game::from_options(options);
+        game.options = self.options.clone();
+        game.drill = Some(Drill {
+            repertoire,
+            key,
+            result: None,
+        });
+        *self = game;
+    }
+
+    /// Checks the move played in a drill, only playing it if it's the one in the repertoire
+    fn answer_drill(&mut self, from: &Loc, to: &Loc) {
+        let Some(drill) = self.drill.as_mut() else {
+            return;
+        };
+
+        let mov = drill.repertoire.positions[&drill.key].mov;
+        let correct = mov == (*from, *to);
+        drill.result = Some(correct);
+        drill.repertoire.review(&drill.key, correct, date::now());
+        #[cfg(not(target_family = "wasm"))]
+        if let Err(err) = drill.repertoire.save(REPERTOIRE_PATH) {
+            info!("{}", err);
+        }
+
+        if correct {
+            self.move_piece(from, to);
+        } else {
+            // Show the right move instead
+            self.selected = None;
+            self.highlight_moves.clear();
+            self.clear_arrows_highlights();
+            self.hint_arrows.push(mov);
+        }
+    }
+
+    /// Starts a puzzle rush of `minutes`, against the best saved in the stats
+    #[cfg(not(target_family = "wasm"))]
+    fn start_rush(&mut self, minutes: u32) {
+        let stats = PlayerStats::load(STATS_PATH).unwrap_or_default();
+        let best = stats.rush_best.get(&minutes).copied().unwrap_or(0);
+        self.next_rush_puzzle(Rush::new(minutes, best), minutes as f64 * 60.0);
+    }
+
+    /// Replaces the game with the rush's next puzzle, with `seconds` left on the clock
+    fn next_rush_puzzle(&mut self, mut rush: Rush, seconds: f64) {
+        // The opponent's replies are played along with the player's moves
+        let board = rush.next_puzzle();
+        let mut options = GameOptions::new();
+        options.agent = AgentId::CONTROL;
+        options.color = Some(board.turn);
+        options.fen = Some(board.as_fen());
+        let mut game = Game::from_options(options);
+        game.options = self.options.clone();
+        game.clock = Some(Clock::new(&TimeControl {
+            base: seconds,
+            increment: 0.0,
+        }));
+        game.rush = Some(rush);
+        *self = game;
+    }
+
+    /// Checks the move played in a rush puzzle, playing it and the opponent's reply if it still mates
+    fn answer_rush(&mut self, from: &Loc, to: &Loc) {
+        let Some(rush) = self.rush.as_mut() else {
+            return;
+        };
+
+        match rush.answer(&self.board, from, to) {
+            RushAnswer::Reply((reply_from, reply_to)) => {
+                self.move_piece(from, to);
+                self.move_piece(&reply_from, &reply_to);
+                return;
+            }
+            RushAnswer::Solved => self.move_piece(from, to),
+            RushAnswer::Wrong(mate) => {
+                // Show the mating move instead
+                self.selected = None;
                 self.highlight_moves.clear();
+                self.clear_arrows_highlights();
+                self.hint_arrows.extend(mate);
+            }
+        }
+
+        let rush = self.rush.as_mut().unwrap();
+        if rush.over {
+            self.end_rush();
+        } else {
+            rush.next_at = Some(date::now() + RUSH_PAUSE);
+        }
+    }
+
+    /// Goes on to the rush's next puzzle once the last one has been shown, or ends it once the time runs out
+    fn update_rush(&mut self) {
+        let Some(rush) = self.rush.as_ref().filter(|rush| !rush.over) else {
+            return;
+        };
+
+        if matches!(self.board.state, BoardState::Timeout(_)) {
+            self.end_rush();
+        } else if rush.next_at.is_some_and(|at| date::now() >= at) {
+            let seconds = self
+                .clock
+                .map_or(0.0, |clock| clock.remaining(self.board.player_color));
+            let rush = self.rush.take().unwrap();
+            self.next_rush_puzzle(rush, seconds);
+        }
+    }
+
+    /// Ends the rush, saving the score if it's a new best
+    fn end_rush(&mut self) {
+        let Some(rush) = self.rush.as_mut() else {
+            return;
+        };
+        rush.over = true;
+        rush.next_at = None;
+
+        let (minutes, solved) = (rush.minutes, rush.solved);
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let saved = PlayerStats::load(STATS_PATH).and_then(|mut stats| {
+                if stats.add_rush(minutes, solved) {
+                    stats.save(STATS_PATH)?;
+                }
+                Ok(())
+            });
+            if let Err(err) = saved {
+                warn!("Couldn't add to the stats: {}", err);
+            }
+        }
+        let text = format!("Puzzle rush over, {} solved in {} minutes", solved, minutes);
+        self.announcement = Some((text, date::now()));
+    }
+
+    /// Moves the board to `node` in the replay, along with its arrows and highlights
+    fn goto(&mut self, node: Option<usize>) {
+        self.store_annotations();
+        let mut board = self.start_board();
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        replay.current = node;
+
+        let path = replay.tree.path(node);
+        let moves = replay.tree.moves(node);
+        board.player_color = self.board.player_color;
+        board.agent_color = self.board.agent_color;
+        for (from, to) in moves.iter() {
+            board.move_piece(from, to, true);
+        }
+
+        // Evals are only still right for the moves in common
+        let common = self
+            .moves
+            .iter()
+            .zip(moves.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.analysis.truncate(common);
+
+        self.comments = path
+            .iter()
+            .map(|node| replay.tree.nodes[*node].comment.clone())
+            .collect();
+        self.move_stats = path
+            .iter()
+            .map(|node| replay.tree.nodes[*node].stats)
+            .collect();
+        self.last_move = moves.last().copied();
+        self.last_san = path.last().map(|node| replay.tree.nodes[*node].san.clone());
+        self.moves = moves;
+        self.current_tween = tween_between(&self.board, &board);
+        self.board = board;
+        self.selected = None;
+        self.highlight_moves.clear();
+        self.drag_start = None;
+        self.drag_end = None;
+        self.hint_arrows.clear();
+        self.engine_arrows.clear();
+        match node {
+            Some(node) => {
+                let node = &replay.tree.nodes[node];
+                self.arrows = node.arrows.clone();
+                self.highlights = node.highlights.iter().copied().collect();
+            }
+            None => {
+                self.arrows.clear();
+                self.highlights.clear();
+            }
+        }
+    }
+
+    /// Switches to an alternative to the current move, `offset` away
+    fn switch_variation(&mut self, offset: isize) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        let Some(current) = replay.current else {
+            return;
+        };
+
+        let siblings = replay.tree.siblings(current);
+        let i = siblings.iter().position(|node| *node == current).unwrap() as isize;
+        let next = siblings[(i + offset).rem_euclid(siblings.len() as isize) as usize];
+        self.goto(Some(next));
+    }
+
+    /// Starts a new game from the dialog, confirming first if the current one is in progress
+    fn start_new_game(&mut self, options: GameOptions) {
+        if self.last_move.is_none() {
+            self.reset(options);
+        } else {
+            self.overlay = Some(Overlay::confirm_reset(options));
+        }
+    }
+
+    /// Looks for a mate from the current position, shown in an overlay once found
+    /// - `checks_only` is wether the side mating only tries checks, see [MateSearch]
+    fn start_mate_search(&mut self, n: u8, checks_only: bool) {
+        self.mate_search = Some((self.board.clone(), n, checks_only));
+        let search = MateSearch::new(&self.board, n, checks_only);
+        #[cfg(target_family = "wasm")]
+        {
+            self.mate_slices = Some(search);
+        }
+        #[cfg(not(target_family = "wasm"))]
+        {
+            let stop = Arc::new(AtomicBool::new(false));
+            let mut limits = SearchLimits::new(f64::INFINITY);
+            limits.stop = Some(stop.clone());
+            self.mate_stop = Some(Arc::new(StopOnDrop(stop)));
+
+            let sender = self.mate_channel.0.clone();
+            let mut search = search;
+            spawn(move || {
+                // Errors if the game was reset while searching
+                if let Some(line) = search.run(&limits, f64::INFINITY) {
+                    sender.send(line).ok();
+                }
+            });
+        }
+    }
+
+    /// Starts analyzing the current position, or stops the search if it's running
+    fn toggle_live_analysis(&mut self) {
+        match &self.live_analysis {
+            Some(live) if !live.finished => live.stop(),
+            Some(_) => self.live_analysis = None,
+            #[cfg(not(target_family = "wasm"))]
+            None if self.board.is_over() => info!("The game is over"),
+            #[cfg(not(target_family = "wasm"))]
+            None => self.live_analysis = Some(LiveAnalysis::start(&self.board)),
+            #[cfg(target_family = "wasm")]
+            None => info!("Live analysis is only available on desktop"),
+        }
+    }
+
+    /// Turns the game into an analysis board, or back into a plain replay
+    pub(crate) fn toggle_analysis_board(&mut self) {
+        if self.analysis_board {
+            self.analysis_board = false;
+            #[cfg(not(target_family = "wasm"))]
+            {
+                self.eval_search = None;
+            }
+            return;
+        }
+        if self.lan_game || self.remote_turn() {
+            info!("Network games can't be analyzed while they're played");
+            return;
+        }
+
+        // Replays already move both colors by clicking, and branch when a move is taken back and replaced
+        self.study_game();
+        self.analysis_board = true;
+    }
+
+    /// Plays the first move of the eval bar's search, once it has finished a depth
+    fn play_engine_move(&mut self) {
+        if !self.analysis_board {
+            info!("The engine only plays on request on the analysis board");
+        } else if self.board.is_over() {
+            info!("The game is over");
+        } else {
+            #[cfg(target_family = "wasm")]
+            info!("The engine only runs on the analysis board on desktop");
+            #[cfg(not(target_family = "wasm"))]
+            match self
+                .eval_search
+                .as_ref()
+                .filter(|search| search.board.hash == self.board.hash)
+                .and_then(|search| search.latest.as_ref())
+                .and_then(|info| info.pv.first().copied())
+            {
+                Some((from, to)) => self.move_piece(&from, &to),
+                None => info!("The engine is still thinking..."),
+            }
+        }
+    }
+
+    /// Keeps the eval bar's search on the current position of the analysis board
+    #[cfg(not(target_family = "wasm"))]
+    fn update_eval_search(&mut self) {
+        if !self.analysis_board || self.board.is_over() {
+            self.eval_search = None;
+            return;
+        }
+        match self.eval_search.as_mut() {
+            Some(search) if search.board.hash == self.board.hash => search.update(),
+            // Dropping the old search stops it
+            _ => self.eval_search = Some(LiveAnalysis::start(&self.board)),
+        }
+    }
+
+    /// Fills the eval bar with white from the bottom by [win_chance], using the static score until the search has one
+    #[cfg(not(target_family = "wasm"))]
+    fn draw_eval_bar(&self) {
+        if !self.analysis_board {
+            return;
+        }
+        let score = self
+            .eval_search
+            .as_ref()
+            .and_then(|search| search.latest.as_ref())
+            .map(|info| info.score)
+            .filter(|score| *score != MAX)
+            .unwrap_or(self.board.score);
+
+        // In the margin left of the board
+        let layout = layout();
+        let width = layout.margin / 2.0;
+        let x = (layout.margin - width) / 2.0;
+        let height = layout.square_size * 8.0;
+        let white = height * win_chance(score);
+        draw_rectangle(x, layout.top, width, height - white, DARKGRAY);
+        draw_rectangle(x, layout.top + height - white, width, white, WHITE);
+    }
+
+    /// Starts waiting for a LAN opponent on `port`, the new game starts once they connect
+    #[cfg(not(target_family = "wasm"))]
+    fn host_lan(&mut self, port: u16) {
+        let color = self.options.color.unwrap_or_else(random_color);
+        let handshake = Handshake {
+            guest_color: color.other(),
+            fen: self.options.fen.clone().unwrap_or_else(|| FEN.to_string()),
+            moves: vec![],
+            clock: self.options.time_control.as_ref().map(Clock::new),
+        };
+        match Lan::host(port, handshake) {
+            Ok(lan) => {
+                self.overlay = Some(Overlay::lan_waiting(lan.status()));
+                self.lan = Some(lan);
+            }
+            Err(err) => info!("Can't host on port {}: {}", port, err),
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn join_lan(&mut self, address: &str) {
+        let lan = Lan::join(address);
+        self.overlay = Some(Overlay::lan_waiting(lan.status()));
+        self.lan = Some(lan);
+    }
+
+    /// The LAN game so far, sent when reconnecting so the guest picks up where it left off
+    #[cfg(not(target_family = "wasm"))]
+    fn lan_handshake(&self) -> Handshake {
+        let mut board = self.start_board();
+        let moves = self
+            .moves
+            .iter()
+            .map(|(from, to)| {
+                let mov = coordinate(&board, from, to);
+                board.move_piece(from, to, true);
+                mov
+            })
+            .collect();
+        Handshake {
+            guest_color: self.board.player_color.other(),
+            fen: self.start_fen.clone(),
+            moves,
+            clock: self.clock,
+        }
+    }
+
+    /// Starts the LAN game from `handshake`, or carries on with the current one after reconnecting
+    #[cfg(not(target_family = "wasm"))]
+    fn lan_connected(&mut self, handshake: &Handshake) {
+        let Some(lan) = self.lan.take() else {
+            return;
+        };
+        if self.lan_game
+            && self.start_fen == handshake.fen
+            && self.lan_handshake().moves == handshake.moves
+        {
+            self.lan = Some(lan);
+            self.overlay = None;
+            // Picks up from the host's clock, which was paused while the connection was down
+            self.clock = handshake.clock;
+            self.lan_turn = self.clock.map(|clock| (date::now(), clock));
+            self.add_chat("* Reconnected".to_string());
+            return;
+        }
+        // Dropping the connection closes it
+        if let Err(issues) = Board::try_from_fen(&handshake.fen) {
+            info!(
+                "The host sent an illegal position: {}",
+                describe_issues(&issues)
+            );
+            self.overlay = None;
+            return;
+        }
+
+        let mut options = GameOptions::new();
+        options.agent = AgentId::CONTROL;
+        options.color = Some(match lan.role {
+            LanRole::Host(_) => handshake.guest_color.other(),
+            LanRole::Guest(_) => handshake.guest_color,
+        });
+        options.fen = Some(handshake.fen.clone());
+        let mut game = Game::from_options(options);
+        for mov in handshake.moves.iter() {
+            let Some((from, to)) = parse_move(&game.board, mov) else {
+                info!("The host sent an illegal move {}", mov);
+                break;
+            };
+            game.board.promote_to = promotion_piece(mov).unwrap_or(PieceNames::Queen);
+            game.move_piece(&from, &to);
+        }
+        game.current_tween.clear();
+        // The times left are sent, so increments from the moves above are already counted
+        game.clock = handshake.clock;
+        game.lan_turn = game.clock.map(|clock| (date::now(), clock));
+        game.options = self.options.clone();
+        game.lan = Some(lan);
+        game.lan_game = true;
+        // Talking to the same opponent
+        game.chat = std::mem::take(&mut self.chat);
+        game.add_chat("* Connected".to_string());
+        *self = game;
+    }
+
+    /// Makes a new connection to the same opponent, with the game so far if it had started
+    #[cfg(not(target_family = "wasm"))]
+    fn reconnect_lan(&mut self) {
+        let Some(lan) = self.lan.take() else {
+            return;
+        };
+        let handshake = ternary!(self.lan_game, Some(self.lan_handshake()), None);
+        match lan.reconnect(handshake) {
+            Ok(lan) => {
+                self.overlay = Some(Overlay::lan_waiting(lan.status()));
+                self.lan = Some(lan);
+            }
+            Err(err) => self.overlay = Some(Overlay::lan_disconnected(&err.to_string())),
+        }
+    }
+
+    /// Drops the LAN connection, the rest of the game is played by clicking for both sides
+    #[cfg(not(target_family = "wasm"))]
+    fn leave_lan(&mut self) {
+        self.lan = None;
+        self.lan_game = false;
+        self.overlay = None;
+    }
+
+    /// Sends the game to the broadcast's spectators when it's changed
+    #[cfg(not(target_family = "wasm"))]
+    fn update_broadcast(&mut self) {
+        let mut broadcast = BROADCAST.lock().unwrap();
+        let Some(broadcast) = broadcast.as_mut() else {
+            return;
+        };
+        let key = (self.board.hash, self.moves.len(), self.board.is_over());
+        if self.published == Some(key) {
+            return;
+        }
+        self.published = Some(key);
+
+        let start = self.start_board();
+        broadcast.publish(
+            &self.board,
+            self.pgn(),
+            movetext_tail(&start, &self.moves, TAIL_PLIES),
+        );
+    }
+
+    /// Replaces the game with a read only one showing the broadcast at `address`
+    #[cfg(not(target_family = "wasm"))]
+    fn watch(&mut self, address: &str) {
+        let mut options = GameOptions::new();
+        options.agent = AgentId::CONTROL;
+        let mut game = Game::from_options(options);
+        game.options = self.options.clone();
+        game.watching = Some(Spectator::watch(address));
+        *self = game;
+        info!("Watching {}", address);
+    }
+
+    /// Shows the latest update of the broadcast being watched, the game is left as is once it ends
+    #[cfg(not(target_family = "wasm"))]
+    fn update_watching(&mut self) {
+        let Some(spectator) = &self.watching else {
+            return;
+        };
+        match spectator.poll() {
+            Ok(Some(update)) => self.show_update(&update),
+            Ok(None) => {}
+            Err(err) => {
+                info!("{}", err);
+                self.watching = None;
+            }
+        }
+    }
+
+    /// Shows the game from a broadcast, animating the move if it follows on from the position shown
+    #[cfg(not(target_family = "wasm"))]
+    fn show_update(&mut self, update: &Update) {
+        let pgn = match parse(&update.pgn) {
+            Ok(pgn) => pgn,
+            Err(err) => {
+                info!("Couldn't read the broadcast: {}", err);
+                return;
+            }
+        };
+
+        let moves = pgn.tree.moves(pgn.tree.line(None).last().copied());
+        if pgn.start_fen == self.start_fen
+            && moves.len() == self.moves.len() + 1
+            && moves.starts_with(&self.moves)
+        {
+            let (from, to) = moves[moves.len() - 1];
+            self.move_piece(&from, &to);
+        } else if pgn.start_fen != self.start_fen || moves != self.moves {
+            let start = Board::from_fen(&pgn.start_fen);
+            let mut board = start.clone();
+            board.player_color = self.board.player_color;
+            board.agent_color = self.board.agent_color;
+            for (from, to) in moves.iter() {
+                board.move_piece(from, to, true);
+            }
+
+            self.last_san = san_line(&start, &moves).pop();
+            self.last_move = moves.last().copied();
+            self.comments = vec![None; moves.len()];
+            self.move_stats = vec![None; moves.len()];
+            self.analysis = Analysis::new();
+            self.moves = moves;
+            self.preview = None;
+            self.start_fen = pgn.start_fen.clone();
+            self.current_tween = tween_between(&self.board, &board);
+            self.board = board;
+            self.selected = None;
+            self.highlight_moves.clear();
+        }
+
+        // Endings that don't come from the moves, IE resigning
+        if !self.board.is_over() {
+            let endings = [
+                BoardState::Timeout(ChessColor::White),
+                BoardState::Timeout(ChessColor::Black),
+                BoardState::Resigned(ChessColor::White),
+                BoardState::Resigned(ChessColor::Black),
+                BoardState::Draw(DrawReason::Agreement),
+            ];
+            if let Some(tag) = pgn.tag("Termination") {
+                if let Some(state) = endings.into_iter().find(|state| termination(state) == tag) {
+                    self.board.state = state;
+                }
+            }
+        }
+    }
+
+    /// Handles what happened on the LAN connection, even with an overlay open since the opponent isn't paused
+    #[cfg(not(target_family = "wasm"))]
+    fn update_lan(&mut self) {
+        while let Some(event) = self.lan.as_mut().and_then(Lan::poll) {
+            match event {
+                LanEvent::Connected(handshake) => self.lan_connected(&handshake),
+                LanEvent::Move(mov, think) => match parse_move(&self.board, &mov) {
+                    Some((from, to)) if self.remote_turn() && !self.board.is_over() => {
+                        if self.charge_lan_move(think) {
+                            self.board.promote_to =
+                                promotion_piece(&mov).unwrap_or(PieceNames::Queen);
+                            self.move_piece(&from, &to)
+                        }
+                    }
+                    _ => {
+                        info!("The opponent sent an illegal move {}", mov);
+                        self.add_chat(format!("* Illegal move {}", mov));
+                    }
+                },
+                LanEvent::Clock(clock) => {
+                    // Counts the time the current turn has run since, as the host does
+                    let mut clock = clock;
+                    if let Some((start, _)) = self.lan_turn.filter(|_| !self.board.is_over()) {
+                        clock.tick(self.board.turn, date::now() - start);
+                    }
+                    self.clock = Some(clock);
+                }
+                LanEvent::Flag(color) if !self.board.is_over() => {
+                    self.board.state = BoardState::Timeout(color);
+                    let events = [GameEvent::GameEnd(self.board.state)];
+                    self.events.clone().publish(self, &events);
+                }
+                LanEvent::Flag(_) => {}
+                LanEvent::Chat(text) => self.add_chat(format!("Opponent: {}", text)),
+                // Nothing is lost once the game is over
+                LanEvent::Disconnected(reason) if self.lan_game && self.board.is_over() => {
+                    info!("{}", reason);
+                    self.add_chat(format!("* {}", reason));
+                    self.lan = None;
+                }
+                LanEvent::Disconnected(reason) => {
+                    self.add_chat(format!("* {}", reason));
+                    self.overlay = Some(Overlay::lan_disconnected(&reason));
+                }
+            }
+        }
+    }
+
+    /// Sets the clock of the LAN opponent's move from the host's side, see [charged_time]. Returns wether the move
+    /// can be played, IE not if they ran out of time before making it
+    #[cfg(not(target_family = "wasm"))]
+    fn charge_lan_move(&mut self, think: Option<f64>) -> bool {
+        let host = self
+            .lan
+            .as_ref()
+            .is_some_and(|lan| matches!(lan.role, LanRole::Host(_)));
+        let Some((start, mut clock)) = self.lan_turn.filter(|_| host) else {
+            return true;
+        };
+
+        let color = self.board.turn;
+        clock.tick(color, charged_time(think, date::now() - start));
+        self.clock = Some(clock);
+        if clock.flagged() != Some(color) {
+            return true;
+        }
+        self.board.state = BoardState::Timeout(color);
+        let events = [GameEvent::GameEnd(self.board.state)];
+        self.events.clone().publish(self, &events);
+        false
+    }
+
+    /// Wether `color` running out of time ends the game. In LAN games only the host decides, and only once the
+    /// opponent's move couldn't still be on its way, see [LAG_GRACE]
+    #[cfg_attr(target_family = "wasm", allow(unused_variables))]
+    fn may_flag(&self, color: ChessColor) -> bool {
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(lan) = self.lan.as_ref().filter(|_| self.lan_game) {
+            if !matches!(lan.role, LanRole::Host(_)) {
+                return false;
+            }
+            if color != self.board.player_color {
+                return self.lan_turn.is_none_or(|(start, clock)| {
+                    date::now() - start > clock.remaining(color) + LAG_GRACE
+                });
+            }
+        }
+        true
+    }
+
+    /// Adds a line to the chat, dropping the oldest once there's too many
+    #[cfg(not(target_family = "wasm"))]
+    fn add_chat(&mut self, line: String) {
+        self.chat.push(line);
+        if self.chat.len() > MAX_CHAT_LINES {
+            self.chat.remove(0);
+        }
+    }
+
+    /// Opens the chat box, only while playing over LAN
+    fn open_chat(&mut self) {
+        #[cfg(not(target_family = "wasm"))]
+        if self.lan_game && self.lan.as_ref().is_some_and(|lan| lan.connected) {
+            self.overlay = Some(Overlay::chat());
+            return;
+        }
+        info!("Chat is only for LAN games");
+    }
+
+    /// Sends `text` to the LAN opponent, and adds it to the chat
+    #[cfg(not(target_family = "wasm"))]
+    fn send_chat(&mut self, text: &str) {
+        let text = text.trim();
+        let Some(lan) = self.lan.as_ref().filter(|_| !text.is_empty()) else {
+            return;
+        };
+        lan.send_chat(text);
+        self.add_chat(format!("You: {}", text));
+    }
+
+    fn offer_draw(&mut self) {
+        if self.board.is_over()
+            || self.replay.is_some()
+            || self.spectating()
+            || self.agent == AgentId::CONTROL
+        {
+            info!("There's no agent to offer a draw to");
+        } else if self.outlook.accepts_draw(Outlook::draw_score(&self.board)) {
+            info!("The agent accepted the draw");
+            self.board.state = BoardState::Draw(DrawReason::Agreement);
+        } else {
+            info!("The agent declined the draw");
+        }
+    }
+
+    /// The player makes the moves of the agent whose turn it is from now on
+    fn take_over_agent(&mut self) {
+        if self.board.turn == self.board.agent_color {
+            self.agent = AgentId::CONTROL;
+        } else {
+            self.player_agent = None;
+        }
+    }
+
+    /// Plays the move the agent thinking picked, with what it found as the comment
+    fn play_agent_move(&mut self, agent: AgentId, from: &Loc, to: &Loc) {
+        let color = self.board.turn;
+        let info = self.agent_info.take();
+        let proposal = self.agent_proposal(color, info.as_ref());
+        let stats = info
+            .as_ref()
+            .map(|info| MoveStats::from_info(info, date::now() - self.think_start));
+        let ideas = info.as_ref().map(engine_arrows).unwrap_or_default();
+        if let Some(decision) = &mut self.decision {
+            decision.finish(
+                Some((*from, *to)),
+                info.as_ref(),
+                self.agent_opening.clone(),
+            );
+        }
+        if let Some(info) = info.as_ref().filter(|info| !info.tree.is_empty()) {
+            self.last_search = Some((self.board.clone(), info.clone()));
+        }
+
+        let info = info.filter(|_| self.spectating());
+        let comment = info.as_ref().map(|info| {
+            let comment = search_comment(&self.board, agent, info);
+            match &self.agent_opening {
+                Some(name) => format!("{}, {}", comment, name),
+                None => comment,
+            }
+        });
+        let line = info.as_ref().map(|info| panel_line(&self.board, info));
+
+        self.move_piece(from, to);
+        if let Some(line) = line {
+            *color_ternary!(color, &mut self.engine_lines.0, &mut self.engine_lines.1) = Some(line);
+        }
+        *self.comments.last_mut().unwrap() = comment;
+        *self.move_stats.last_mut().unwrap() = stats;
+        self.engine_arrows = ideas;
+        if let Some(replay) = self.replay.as_mut() {
+            replay.tree.nodes[replay.current.unwrap()].stats = stats;
+        }
+        if proposal == Some(Proposal::OfferDraw) && !self.board.is_over() {
+            self.overlay = Some(Overlay::draw_offer());
+        }
+    }
+
+    /// What the opponent agent wants to do along with its move, from the score of its search
+    fn agent_proposal(&mut self, color: ChessColor, info: Option<&SearchInfo>) -> Option<Proposal> {
+        if self.spectating() || color != self.board.agent_color {
+            return None;
+        }
+        // Book moves don't have a real score
+        let info = info.filter(|info| info.depth != 0)?;
+        let score = color_ternary!(color, info.score, -info.score);
+        self.outlook.update(
+            score,
+            Outlook::draw_score(&self.board),
+            settings().resign_score,
+        )
+    }
+
+    fn run_action(&mut self, action: MenuAction) {
+        if action.helps() && self.rated() {
+            info!("{} is off in rated games", action.name());
+            return;
+        }
+        match action {
+            MenuAction::PrintBoard => self.board.print(),
+            MenuAction::CopyFen => copy_to_clipboard(&self.board.as_fen()),
+            MenuAction::CopyPgn => copy_to_clipboard(&self.pgn()),
+            MenuAction::Reset => self.overlay = Some(Overlay::new_game(self.options.clone())),
+            MenuAction::Pause => {
+                self.overlay = ternary!(self.overlay.is_some(), None, Some(Overlay::pause()));
+            }
+            MenuAction::Help => {
+                self.overlay = ternary!(
+                    matches!(self.overlay, Some(Overlay::Help(_))),
+                    None,
+                    Some(Overlay::help(0))
+                );
+            }
+            MenuAction::ImportPgn => self.import_pgn(),
+            MenuAction::Back | MenuAction::Takeback if self.replay.is_some() => {
+                let parent = self.replay.as_ref().and_then(|r| r.tree.parent(r.current));
+                if self.replay.as_ref().is_some_and(|r| r.current.is_some()) {
+                    self.goto(parent);
+                }
+            }
+            MenuAction::Forward if self.replay.is_none() => self.step_preview(1),
+            MenuAction::Forward => {
+                let next = self
+                    .replay
+                    .as_ref()
+                    .and_then(|r| r.tree.children(r.current).first().copied());
+                if next.is_some() {
+                    self.goto(next);
+                }
+            }
+            MenuAction::Study => self.study_game(),
+            MenuAction::Comment => match self.replay.as_ref().and_then(|r| r.current) {
+                Some(current) => {
+                    let comment = self.replay.as_ref().unwrap().tree.nodes[current]
+                        .comment
+                        .clone();
+                    self.overlay = Some(Overlay::comment(comment.as_deref().unwrap_or("")));
+                }
+                None => info!("Comments go on moves in a replay, use Study this game first"),
+            },
+            MenuAction::NextAnnotation => self.next_annotation(),
+            #[cfg(not(target_family = "wasm"))]
+            MenuAction::SaveStudy => self.save_study(),
+            #[cfg(not(target_family = "wasm"))]
+            MenuAction::OpenStudy => self.open_study(),
+            #[cfg(target_family = "wasm")]
+            MenuAction::SaveStudy | MenuAction::OpenStudy => {
+                info!("Studies can only be saved on desktop")
+            }
+            #[cfg(not(target_family = "wasm"))]
+            MenuAction::AddRepertoire => self.add_to_repertoire(),
+            #[cfg(not(target_family = "wasm"))]
+            MenuAction::Drill => self.next_drill(),
+            #[cfg(target_family = "wasm")]
+            MenuAction::AddRepertoire | MenuAction::Drill => {
+                info!("The repertoire can only be saved on desktop")
+            }
+            MenuAction::TypeMove => {
+                if self.awaiting_click() {
+                    self.overlay = Some(Overlay::type_move());
+                } else {
+                    info!("Moves can only be typed on your turn");
+                }
+            }
+            MenuAction::OfferDraw => self.offer_draw(),
+            MenuAction::Chat => self.open_chat(),
+            MenuAction::AnalysisBoard => self.toggle_analysis_board(),
+            MenuAction::EngineMove => self.play_engine_move(),
+            MenuAction::Analyze => self.toggle_live_analysis(),
+            MenuAction::FindMate => {
+                if self.mate_search.is_some() {
+                    info!("Already looking for a mate...");
+                } else if self.board.is_over() {
+                    info!("The game is over");
+                } else {
+                    self.overlay = Some(Overlay::find_mate());
+                }
+            }
+            MenuAction::PrevVariation => self.switch_variation(-1),
+            MenuAction::NextVariation => self.switch_variation(1),
+            MenuAction::Back => self.step_preview(-1),
+            MenuAction::Takeback => self.takeback(),
+            MenuAction::FlipBoard => {
+                self.flip_at = None;
+                self.flipped = !self.flipped;
+            }
+            MenuAction::Tables => {
+                self.overlay = Some(Overlay::tables(&self.board.eval_terms(), self.table_view));
+            }
+            #[cfg(not(target_family = "wasm"))]
+            MenuAction::SaveDecision => self.save_decision(),
+            #[cfg(target_family = "wasm")]
+            MenuAction::SaveDecision => info!("Decisions can only be saved on desktop"),
+            MenuAction::SearchTree => match &self.last_search {
+                Some((board, info)) => {
+                    self.overlay = Some(Overlay::search_tree(search_tree_lines(board, info)));
+                }
+                None if settings().search_tree => info!("The agent hasn't searched a move yet"),
+                None => info!("Turn on Search tree in the settings to record the agent's searches"),
+            },
+            MenuAction::Explorer => toggle_explorer(),
+            MenuAction::Log => {
+                self.show_log = !self.show_log;
+                self.log_scroll = 0;
+            }
+            MenuAction::Mute => {
+                settings().sound = !settings().sound;
+                let text = ternary!(settings().sound, "Sound on", "Sound off");
+                self.announcement = Some((text.to_string(), date::now()));
+            }
+            MenuAction::Review => self.toggle_review(),
+            MenuAction::PlayFromHere => self.play_from_here(),
+            MenuAction::ReturnToGame => self.return_to_game(),
+            #[cfg(not(target_family = "wasm"))]
+            MenuAction::ExportAnalysis => self.export_analysis(),
+            #[cfg(target_family = "wasm")]
+            MenuAction::ExportAnalysis => info!("Analysis can only be exported on desktop"),
+            MenuAction::NewTab | MenuAction::CloseTab | MenuAction::NextTab => {
+                self.tab_action = Some(action);
+            }
+        }
+    }
+
+    /// Rewinds to the last position a move was played by clicking from, sliding the pieces back and restoring the
+    /// clock and annotations there
+    pub(crate) fn takeback(&mut self) {
+        if self.waiting_on_agent {
+            info!("Waiting on agent...");
+            return;
+        }
+        if self.lan_game || self.remote_turn() {
+            info!("Moves can't be taken back in network games");
+            return;
+        }
+        let Some(snapshot) = self.board_history.pop() else {
+            return;
+        };
+
+        let undone = (self.board.half_moves - snapshot.board.half_moves) as usize;
+        let moves: Vec<_> = self.moves.drain(self.moves.len() - undone..).collect();
+        self.comments.truncate(self.moves.len());
+        self.move_stats.truncate(self.moves.len());
+        self.analysis.truncate(self.moves.len());
+        self.board = snapshot.board;
+        self.selected = None;
+        self.last_move = snapshot.last_move;
+        self.last_san = san_line(&self.start_board(), &self.moves).pop();
+        self.highlight_moves.clear();
+        self.clock = snapshot.clock;
+        self.outlook = snapshot.outlook;
+
+        self.clear_arrows_highlights();
+        self.arrows = snapshot.arrows;
+        self.highlights = snapshot.highlights;
+        let takeback = GameEvent::Takeback {
+            moves: moves.into_iter().rev().collect(),
+        };
+        self.events.clone().publish(self, &[takeback]);
+        if self.auto_flips() {
+            self.flip_at = None;
+            self.face_turn();
+        }
+    }
+
+    fn update_keys(&mut self) {
+        for action in pressed_actions() {
+            // Only the overlay toggles work while one is open, and only closing while typing
+            if self.overlay.is_some() && !matches!(action, MenuAction::Pause | MenuAction::Help) {
+                continue;
+            }
+            if self.overlay.as_ref().is_some_and(Overlay::typing) && action != MenuAction::Pause {
+                continue;
+            }
+            // Has to be answered, IE so the game isn't left waiting on an opponent that's gone
+            if self.overlay.as_ref().is_some_and(Overlay::blocking) {
+                continue;
+            }
+            self.run_action(action);
+        }
+        // if is_key_pressed(KeyCode::X) {
+        //     crate::agent_opens::create_openings();
+        // }
+    }
+
+    /// Updates and draws the side panel buttons, ignoring clicks while an overlay is open
+    fn update_buttons(&mut self) {
+        let mut panel = Stack::panel_bottom().active(self.overlay.is_none());
+        let mut section = panel.section(panel_section_height());
+
+        if self.show_log {
+            self.update_log(section);
+        } else if self.live_analysis.is_some() {
+            self.update_live_analysis(section);
+        } else if EXPLORER.lock().unwrap().is_some() {
+            self.update_explorer(section);
+        } else if self.review.is_some() {
+            self.update_review(section);
+        } else if self.replay.is_some() {
+            self.update_move_list(section);
+        } else if self.lan_game {
+            self.update_chat(section);
+        } else {
+            // The first agent is at the bottom
+            section.set_gap(layout().margin / 2.0);
+            for registered in agents().rev() {
+                if section.button(registered.id.name(), AGENT_BUTTON_HEIGHT) {
+                    self.agent = registered.id;
+                }
+            }
+        }
+
+        panel.space(layout().margin / 2.0);
+        let icons: Vec<_> = MENU_ACTIONS
+            .iter()
+            .map(|(icon, action)| (*icon, tooltip(*action)))
+            .collect();
+        if let Some(i) = panel.icons(&icons, TOOLBAR_GAP) {
+            self.run_action(MENU_ACTIONS[i].1);
+        }
+    }
+
+    /// Draws the deepest search of the live analysis, with a button to stop it or close it once stopped
+    fn update_live_analysis(&mut self, mut section: Stack) {
+        let Some(live) = &self.live_analysis else {
+            return;
+        };
+        let params = text_params(MOVE_LIST_FONT_SIZE);
+
+        let status = match (live.finished, live.stopping()) {
+            (true, _) => "stopped",
+            (false, true) => "stopping...",
+            (false, false) => "running...",
+        };
+        let mut rows = vec![format!("Analysis {}", status)];
+        if let Some(info) = &live.latest {
+            rows.push(search_summary(info));
+            let pv = san_line(&live.board, &info.pv);
+            rows.extend(pv.chunks(PV_ROW_MOVES).map(|moves| moves.join(" ")));
+        }
+        for row in rows.iter().take(MOVE_LIST_ROWS) {
+            section.label(row, MOVE_LIST_ROW_HEIGHT, params);
+        }
+        section.space(MOVE_LIST_ROW_HEIGHT * MOVE_LIST_ROWS.saturating_sub(rows.len()) as f32);
+
+        section.space(layout().margin);
+        if section.button(
+            ternary!(live.finished, "Close", "Stop"),
+            VARIATION_BUTTON_HEIGHT,
+        ) {
+            self.toggle_live_analysis();
+        }
+    }
+
+    /// Draws what the database has on the current position, with a button to close the explorer
+    fn update_explorer(&mut self, mut section: Stack) {
+        let Some(rows) = EXPLORER
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|database| database.panel_rows(&self.board))
+        else {
+            return;
+        };
+        let params = text_params(MOVE_LIST_FONT_SIZE);
+
+        for row in rows.iter().take(MOVE_LIST_ROWS) {
+            section.label(row, MOVE_LIST_ROW_HEIGHT, params);
+        }
+        section.space(MOVE_LIST_ROW_HEIGHT * MOVE_LIST_ROWS.saturating_sub(rows.len()) as f32);
+
+        section.space(layout().margin);
+        if section.button("Close", VARIATION_BUTTON_HEIGHT) {
+            toggle_explorer();
+        }
+    }
+
+    /// Draws the key moments of the review, and for the one being looked at its scores and a button to show the best
+    /// move
+    fn update_review(&mut self, mut section: Stack) {
+        let Some(review) = &self.review else {
+            return;
+        };
+        let params = text_params(MOVE_LIST_FONT_SIZE);
+        section.set_gap(layout().margin / 2.0);
+
+        let first = review
+            .selected
+            .unwrap_or(0)
+            .saturating_sub(MAX_MOMENT_BUTTONS / 2)
+            .min(review.moments.len().saturating_sub(MAX_MOMENT_BUTTONS));
+        let mut clicked = None;
+        for (i, moment) in review
+            .moments
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_MOMENT_BUTTONS)
+        {
+            let row = moment.row();
+            let row = ternary!(review.selected == Some(i), format!("[{}]", row), row);
+            if section.button(&row, VARIATION_BUTTON_HEIGHT) {
+                clicked = Some(i);
+            }
+        }
+
+        let selected = review.selected.map(|i| &review.moments[i]);
+        let mut show_best = false;
+        let mut play = false;
+        match selected {
+            Some(moment) => {
+                section.label(&moment.swing(), MOVE_LIST_ROW_HEIGHT, params);
+                if moment.best.is_some() {
+                    show_best = section.button("Show best move", VARIATION_BUTTON_HEIGHT);
+                }
+                play = section.button(&tooltip(MenuAction::PlayFromHere), VARIATION_BUTTON_HEIGHT);
+            }
+            None => section.label("Pick a moment", MOVE_LIST_ROW_HEIGHT, params),
+        }
+        let close = section.button("Close", VARIATION_BUTTON_HEIGHT);
+
+        if let Some(i) = clicked {
+            self.goto_moment(i);
+        }
+        if show_best {
+            self.show_best_move();
+        }
+        if play {
+            self.play_from_here();
+        } else if close {
+            self.review = None;
+        }
+    }
+
+    /// Draws the end of the log, scrolled back with the mouse wheel over it, with a button to close it
+    fn update_log(&mut self, mut section: Stack) {
+        let params = text_params(MOVE_LIST_FONT_SIZE);
+        let lines: Vec<_> = log_lines()
+            .into_iter()
+            .filter(|(kind, _)| self.log_searches || *kind != LogKind::Engine)
+            .flat_map(|(kind, text)| {
+                wrap(&text, layout().panel_width, params)
+                    .into_iter()
+                    .map(move |line| (kind, line))
+            })
+            .collect();
+
+        let rect = section.place(MOVE_LIST_ROW_HEIGHT * MOVE_LIST_ROWS as f32);
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 && self.overlay.is_none() && touches(camera().mouse_position().into(), rect)
+        {
+            // Up goes back, like the move list
+            self.log_scroll = ternary!(
+                wheel > 0.0,
+                self.log_scroll + LOG_SCROLL_LINES,
+                self.log_scroll.saturating_sub(LOG_SCROLL_LINES)
+            );
+        }
+        let shown = lines.len().min(MOVE_LIST_ROWS);
+        self.log_scroll = self.log_scroll.min(lines.len() - shown);
+
+        let (x, y, w, _) = rect;
+        let mut rows = Stack::down(x, y, w, 0.0);
+        if lines.is_empty() {
+            rows.label("Nothing logged yet", MOVE_LIST_ROW_HEIGHT, params);
+        }
+        let end = lines.len() - self.log_scroll;
+        for (kind, line) in &lines[end - shown..end] {
+            let params = TextParams {
+                color: kind.color(),
+                ..params
+            };
+            rows.label(line, MOVE_LIST_ROW_HEIGHT, params);
+        }
+
+        section.space(layout().margin);
+        section.set_gap(TOOLBAR_GAP);
+        if section.toggle("Searches", VARIATION_BUTTON_HEIGHT, &mut self.log_searches) {
+            self.log_scroll = 0;
+        }
+        let close = ternary!(
+            self.log_scroll == 0,
+            "Close".to_string(),
+            format!("Close ({} newer)", self.log_scroll)
+        );
+        if section.button(&close, VARIATION_BUTTON_HEIGHT) {
+            self.show_log = false;
+        }
+    }
+
+    /// Draws the end of the chat, with a button to send a message
+    fn update_chat(&mut self, mut section: Stack) {
+        let params = text_params(MOVE_LIST_FONT_SIZE);
+        let lines: Vec<String> = self
+            .chat
+            .iter()
+            .flat_map(|line| wrap(line, layout().panel_width, params))
+            .collect();
+        let shown = lines.len().min(MOVE_LIST_ROWS);
+        for line in &lines[lines.len() - shown..] {
+            section.label(line, MOVE_LIST_ROW_HEIGHT, params);
+        }
+
+        section.space(MOVE_LIST_ROW_HEIGHT * (MOVE_LIST_ROWS - shown) as f32 + layout().margin);
+        if section.button(&tooltip(MenuAction::Chat), VARIATION_BUTTON_HEIGHT) {
+            self.open_chat();
+        }
+    }
+
+    /// Draws the replayed line, with buttons for the alternatives to the current move
+    fn update_move_list(&mut self, mut section: Stack) {
+        let Some(replay) = &self.replay else {
+            return;
+        };
+        let params = text_params(MOVE_LIST_FONT_SIZE);
+
+        let start = self.start_board();
+        // Move number, and wether it's black's move, of a ply into the game
+        let number = |ply: usize| {
+            let half = start.half_moves as usize + ply;
+            (half / 2 + 1, half % 2 == 1)
+        };
+        let label = |ply: usize, node: usize| {
+            let (number, black) = number(ply);
+            format!(
+                "{}{} {}",
+                number,
+                ternary!(black, "...", "."),
+                replay.tree.nodes[node].san
+            )
+        };
+
+        // Two plies per row, with the current move in brackets
+        // - Agent moves have their stats on the row below, so the next move starts a new row
+        let current_ply = replay.tree.path(replay.current).len();
+        let mut rows: Vec<String> = vec![];
+        let mut current_row = 0;
+        let mut joinable = false;
+        for (i, node) in replay.tree.line(replay.current).iter().enumerate() {
+            // Annotated moves are marked with a `*`
+            let node = &replay.tree.nodes[*node];
+            let san = format!("{}{}", node.san, ternary!(node.annotated(), "*", ""));
+            let san = ternary!(i + 1 == current_ply, format!("[{}]", san), san);
+            let (number, black) = number(i);
+            match rows.last_mut() {
+                Some(row) if black && joinable => row.push_str(&format!(" {}", san)),
+                _ => rows.push(format!("{}{} {}", number, ternary!(black, "...", "."), san)),
+            }
+            if i + 1 == current_ply {
+                current_row = rows.len() - 1;
+            }
+            joinable = !black;
+            if let Some(stats) = &node.stats {
+                rows.push(format!("  {}", stats.short()));
+                joinable = false;
+            }
+        }
+
+        let first = current_row
+            .saturating_sub(MOVE_LIST_ROWS / 2)
+            .min(rows.len().saturating_sub(MOVE_LIST_ROWS));
+        for row in rows.iter().skip(first).take(MOVE_LIST_ROWS) {
+            section.label(row, MOVE_LIST_ROW_HEIGHT, params);
+        }
+
+        // Below the rows, even when there are fewer
+        let shown = rows.len().saturating_sub(first).min(MOVE_LIST_ROWS);
+        section.space(MOVE_LIST_ROW_HEIGHT * (MOVE_LIST_ROWS - shown) as f32 + layout().margin);
+        section.set_gap(TOOLBAR_GAP);
+
+        // Scrubs along the line, from the starting position to its last move
+        let line = replay.tree.line(replay.current);
+        let range = SliderRange {
+            min: 0.0,
+            max: line.len().max(1) as f32,
+            step: 1.0,
+            format: |ply| format!("{:.0}", ply),
+        };
+        let mut ply = current_ply as f32;
+        let mut clicked = None;
+        if section.slider("Ply", VARIATION_BUTTON_HEIGHT, range, &mut ply) {
+            clicked = Some(ternary!(ply < 1.0, None, Some(line[ply as usize - 1])));
+        }
+
+        // Alternatives to the current move, if it has any
+        let siblings: &[usize] = match replay.current {
+            Some(current) if replay.tree.siblings(current).len() > 1 => {
+                replay.tree.siblings(current)
+            }
+            _ => &[],
+        };
+        for node in siblings.iter().take(MAX_VARIATION_BUTTONS) {
+            let text = label(current_ply - 1, *node);
+            let text = ternary!(Some(*node) == replay.current, format!("▶ {}", text), text);
+            if section.button(&text, VARIATION_BUTTON_HEIGHT) {
+                clicked = Some(Some(*node));
+            }
+        }
+        if let Some(node) = clicked {
+            self.goto(node);
+        }
+    }
+
+    /// `color`'s line of the clock, IE `White: 4:57 +3` while an increment is shown, and the color it's drawn in
+    fn clock_line(&self, clock: &Clock, color: ChessColor) -> (String, Color) {
+        let now = date::now();
+        let increment = match self.increment_shown {
+            Some((shown, seconds, at)) if shown == color && now - at < INCREMENT_SHOW_TIME => {
+                format!(" +{}", seconds)
+            }
+            _ => "".to_string(),
+        };
+        let strength = match self.clock_flash {
+            Some((flashed, at)) if flashed == color && now - at < CLOCK_FLASH_TIME => {
+                (1.0 - (now - at) / CLOCK_FLASH_TIME) as f32
+            }
+            _ => 0.0,
+        };
+
+        let white = text_params(15).color;
+        let mix = |from: f32, to: f32| from + (to - from) * strength;
+        (
+            format!("{:?}: {}{}", color, clock.display(color), increment),
+            Color::new(
+                mix(white.r, COLOR_HIGHLIGHT.r),
+                mix(white.g, COLOR_HIGHLIGHT.g),
+                mix(white.b, COLOR_HIGHLIGHT.b),
+                1.0,
+            ),
+        )
+    }
+
+    fn draw_ui(&self) {
+        // Moves can't be seen on the board while pieces are hidden, but would give away the opponent's in fog of war
+        let last_move = ternary!(
+            settings().visibility == Visibility::Normal || self.revealed().is_some(),
+            "".to_string(),
+            format!("Last: {}\n", self.last_san.as_deref().unwrap_or("-"))
+        );
 
-                self.clear_arrows_highlights();
+        // Both agents' latest searches, white first
+        let mut engines = String::new();
+        if self.spectating() {
+            for (color, line) in [
+                (ChessColor::White, &self.engine_lines.0),
+                (ChessColor::Black, &self.engine_lines.1),
+            ] {
+                engines.push_str(&format!(
+                    "{:?}: {:?}\n{}\n",
+                    color,
+                    self.agent_for(color).unwrap(),
+                    line.as_deref().unwrap_or("-\n")
+                ));
             }
         }
-        // if is_key_pressed(KeyCode::X) {
-        //     crate::agent_opens::create_openings();
-        // }
-    }
 
-    fn update_buttons(&mut self) {
-        for (button, agent) in self.agent_buttons.iter_mut() {
-            if button.update() {
-                self.agent = *agent;
+        let params = text_params(15);
+        let mut stack = Stack::panel_top();
+        let status = format!(
+            "{}\nTurn: {:?}\nScore: {}\n{}{}{}{}",
+            match (&self.rush, &self.drill, &self.replay) {
+                (Some(rush), _, _) => rush.status(&self.board),
+                (None, Some(drill), _) => drill.status(date::now()),
+                (None, None, Some(_)) if self.analysis_board => "Analysis board".to_string(),
+                (None, None, Some(_)) => "Replay".to_string(),
+                (None, None, None) if self.lan_game => "Opponent: LAN".to_string(),
+                (None, None, None) if self.remote_turn() => "Watching".to_string(),
+                (None, None, None) => match self.options.odds() {
+                    Some(handicap) => format!("Agent: {}\n{}", self.agent, handicap.name()),
+                    None => format!("Agent: {}", self.agent),
+                },
+            },
+            self.board.turn,
+            display_score(self.board.score, self.board.player_color),
+            ternary!(in_book(&self.board), "In book\n", ""),
+            last_move,
+            match &self.preview {
+                Some(preview) => format!("Viewing: {}\n", preview.label),
+                None => "".to_string(),
+            },
+            match self.threat_warning() {
+                Some(warning) => format!("{}\n", warning),
+                None => "".to_string(),
+            },
+        );
+        stack.text(&status, params);
+
+        // Drawn on their own to flash, as high as the lines above
+        if let Some(clock) = &self.clock {
+            let height = measure_text(
+                &status,
+                Some(params.font),
+                params.font_size,
+                params.font_scale,
+            )
+            .height;
+            for color in [ChessColor::White, ChessColor::Black] {
+                let (line, color) = self.clock_line(clock, color);
+                stack.label(&line, height, TextParams { color, ..params });
             }
-            button.draw();
         }
-    }
 
-    fn draw_ui(&self) {
-        multiline_text_ex(
+        stack.text(
             &format!(
-                "Agent: {:?}\nTurn: {:?}\nScore: {}\n\n{}Keybinds:\nR-Reset\nL-Takeback",
-                self.agent,
-                self.board.turn,
-                self.board.score,
+                "\n{}{}",
+                engines,
                 ternary!(
-                    self.board.turn == self.board.agent_color,
+                    self.agent_for(self.board.turn).is_some(),
                     "Computer is\nthinking...\n\n",
                     ""
                 )
             ),
-            SQUARE_SIZE * 8.0 + MARGIN * 2.0,
-            MARGIN,
-            TextParams {
-                font_size: 15,
-                font_scale: 1.0,
-                color: COLOR_WHITE,
-                font: font(),
-                ..Default::default()
-            },
-        )
+            params,
+        );
     }
 
     fn draw_end(&self) {
@@ -212,41 +3341,99 @@ impl Game {
             height += dims.height;
         }
 
+        let layout = layout();
+        let (center_x, center_y) = (layout.width / 2.0, layout.height / 2.0);
         draw_rectangle(
-            CENTER_WIDTH as f32 - width / 2.0 - MARGIN / 2.0,
-            CENTER_HEIGHT as f32 - height / 2.0 - MARGIN / 4.0,
-            width + MARGIN,
-            height + MARGIN,
+            center_x - width / 2.0 - layout.margin / 2.0,
+            center_y - height / 2.0 - layout.margin / 4.0,
+            width + layout.margin,
+            height + layout.margin,
             WHITE,
         );
 
         multiline_text_ex(
             message,
-            (CENTER_WIDTH) as f32 - width / 2.0,
-            (CENTER_HEIGHT) as f32 - height / 2.0,
+            center_x - width / 2.0,
+            center_y - height / 2.0,
             params,
         );
     }
 
+    /// Draws the newer release found over the top of the board, with buttons to copy its link and dismiss it
+    #[cfg(not(target_family = "wasm"))]
+    fn update_banner(&self) {
+        let mut update = UPDATE.lock().unwrap();
+        let Some(release) = update.as_ref() else {
+            return;
+        };
+        let active = self.overlay.is_none();
+        let layout = layout();
+
+        draw_rectangle(
+            layout.margin,
+            layout.top,
+            layout.square_size * 8.0,
+            BANNER_HEIGHT,
+            COLOR_OVERLAY,
+        );
+        draw_text_ex(
+            &format!("Update available: {}", release.version),
+            layout.margin * 1.5,
+            layout.top + BANNER_HEIGHT / 2.0 + 5.0,
+            text_params(16),
+        );
+
+        let button = |i: f32, text: &str| {
+            Button::new(
+                layout.margin + layout.square_size * 8.0
+                    - (BANNER_BUTTON_WIDTH + layout.margin / 4.0) * i,
+                layout.top + layout.margin / 4.0,
+                BANNER_BUTTON_WIDTH,
+                BANNER_HEIGHT - layout.margin / 2.0,
+                text,
+            )
+        };
+        let (mut copy, mut dismiss) = (button(2.0, "Copy link"), button(1.0, "Dismiss"));
+        if active && copy.update() {
+            copy_to_clipboard(&release.url);
+        }
+        copy.draw();
+        if active && dismiss.update() {
+            *update = None;
+        }
+        dismiss.draw();
+    }
+
     fn clear_arrows_highlights(&mut self) {
         self.highlights.clear();
         self.arrows.clear();
+        self.hint_arrows.clear();
+        self.engine_arrows.clear();
         self.drag_end = None;
         self.drag_start = None;
     }
 
     pub(crate) fn update_arrows_highlights(&mut self) {
-        if is_mouse_button_down(MouseButton::Left) {
+        // Clicking the side panel, IE a variation, keeps them
+        if is_mouse_button_down(MouseButton::Left) && self.mouse_square().is_some() {
             self.clear_arrows_highlights();
         }
 
+        // Shift right click reads out the exchange instead
+        if shift_down() && self.drag_start.is_none() {
+            if let Some(loc) = self.clicked_square(MouseButton::Right) {
+                self.announce_exchange(&loc);
+            }
+            return;
+        }
+
         if is_mouse_button_down(MouseButton::Right) {
             if self.drag_start.is_none() {
-                self.drag_start = pos_to_board(camera().mouse_position().into());
+                self.drag_start = self.mouse_square();
                 return;
             }
 
-            let pos = pos_to_board(camera().mouse_position().into());
+            let pos = self.mouse_square();
             if self.drag_start != pos {
                 self.drag_end = pos;
             }
@@ -275,64 +3462,969 @@ impl Game {
         }
     }
 
-    pub(crate) fn update(&mut self) {
-        self.update_keys();
-        self.update_buttons();
-        self.update_arrows_highlights();
+    /// Handles the open overlay, if any
+    fn update_overlay(&mut self) {
+        let Some(overlay) = self.overlay.as_mut() else {
+            return;
+        };
+
+        match overlay {
+            Overlay::MainMenu(menu) => match menu.update() {
+                Some(MainMenuItem::Continue) => self.overlay = None,
+                Some(MainMenuItem::NewGame) => {
+                    self.overlay = Some(Overlay::new_game(self.options.clone()));
+                }
+                Some(MainMenuItem::LoadGame) => {
+                    self.overlay = None;
+                    self.run_action(MenuAction::ImportPgn);
+                }
+                #[cfg(not(target_family = "wasm"))]
+                Some(MainMenuItem::Drill) => {
+                    self.overlay = None;
+                    self.run_action(MenuAction::Drill);
+                }
+                #[cfg(not(target_family = "wasm"))]
+                Some(MainMenuItem::PuzzleRush) => {
+                    let stats = PlayerStats::load(STATS_PATH).unwrap_or_default();
+                    self.overlay = Some(Overlay::puzzle_rush(&stats.rush_best));
+                }
+                Some(MainMenuItem::Analysis) => {
+                    self.overlay = None;
+                    self.run_action(MenuAction::AnalysisBoard);
+                }
+                Some(MainMenuItem::Settings) => self.overlay = Some(Overlay::settings()),
+                #[cfg(not(target_family = "wasm"))]
+                Some(MainMenuItem::Quit) => unsafe { get_internal_gl() }.quad_context.order_quit(),
+                None => {}
+            },
+            Overlay::Pause(menu) => match menu.update() {
+                Some(PauseItem::Resume) => self.overlay = None,
+                Some(PauseItem::NewGame) => {
+                    self.overlay = None;
+                    self.run_action(MenuAction::Reset);
+                }
+                Some(PauseItem::ImportPgn) => {
+                    self.overlay = None;
+                    self.run_action(MenuAction::ImportPgn);
+                }
+                Some(PauseItem::Settings) => self.overlay = Some(Overlay::settings()),
+                #[cfg(not(target_family = "wasm"))]
+                Some(PauseItem::Stats) => match PlayerStats::load(STATS_PATH) {
+                    Ok(stats) => self.overlay = Some(Overlay::stats(stats.rows())),
+                    Err(err) => info!("{}", err),
+                },
+                #[cfg(not(target_family = "wasm"))]
+                Some(PauseItem::Network) => self.overlay = Some(network_menu()),
+                #[cfg(not(target_family = "wasm"))]
+                Some(PauseItem::Quit) => unsafe { get_internal_gl() }.quad_context.order_quit(),
+                None => {}
+            },
+            Overlay::Settings(menu) => {
+                let Some(item) = menu.update() else {
+                    return;
+                };
+
+                let settings = settings();
+                match item {
+                    SettingsItem::Theme => settings.next_theme(),
+                    SettingsItem::UiScale => settings.next_ui_scale(),
+                    // Toggles and sliders show their own value, rebuilding would stop a drag
+                    SettingsItem::CompactLayout => {
+                        settings.compact_layout = !settings.compact_layout;
+                        return;
+                    }
+                    SettingsItem::Sound => {
+                        settings.sound = !settings.sound;
+                        return;
+                    }
+                    SettingsItem::Volume => {
+                        settings.volume = menu.slider_value(item).unwrap();
+                        return;
+                    }
+                    SettingsItem::IllegalSound => {
+                        settings.illegal_sound = !settings.illegal_sound;
+                        return;
+                    }
+                    SettingsItem::AutoFlip => {
+                        settings.auto_flip = !settings.auto_flip;
+                        return;
+                    }
+                    SettingsItem::Animation => {
+                        settings.animation = !settings.animation;
+                        return;
+                    }
+                    SettingsItem::AnimationSpeed => {
+                        settings.animation_speed = menu.slider_value(item).unwrap();
+                        return;
+                    }
+                    SettingsItem::LowPower => {
+                        settings.low_power = !settings.low_power;
+                        return;
+                    }
+                    SettingsItem::Difficulty => settings.difficulty = settings.difficulty.next(),
+                    SettingsItem::Promotion => settings.promotion = settings.promotion.next(),
+                    SettingsItem::Analysis => {
+                        settings.analysis = !settings.analysis;
+                        return;
+                    }
+                    SettingsItem::EngineArrows => {
+                        settings.engine_arrows = !settings.engine_arrows;
+                        return;
+                    }
+                    SettingsItem::UserArrows => {
+                        settings.user_arrows = !settings.user_arrows;
+                        return;
+                    }
+                    SettingsItem::HintArrows => {
+                        settings.hint_arrows = !settings.hint_arrows;
+                        return;
+                    }
+                    SettingsItem::ThreatArrows => {
+                        settings.threat_arrows = !settings.threat_arrows;
+                        return;
+                    }
+                    SettingsItem::Arrows => {
+                        self.overlay = Some(Overlay::arrow_settings());
+                        return;
+                    }
+                    SettingsItem::ArrowsBack => {}
+                    SettingsItem::SearchTree => {
+                        settings.search_tree = !settings.search_tree;
+                        return;
+                    }
+                    SettingsItem::AnnounceMoves => {
+                        settings.announce_moves = !settings.announce_moves;
+                        return;
+                    }
+                    SettingsItem::SmartMoves => {
+                        settings.smart_moves = !settings.smart_moves;
+                        return;
+                    }
+                    SettingsItem::PauseClock => {
+                        settings.pause_clock = !settings.pause_clock;
+                        return;
+                    }
+                    SettingsItem::AgentClock => {
+                        settings.agent_clock = !settings.agent_clock;
+                        return;
+                    }
+                    SettingsItem::Visibility => settings.visibility = settings.visibility.next(),
+                    SettingsItem::ThreatWarning => settings.next_threat_warning(),
+                    SettingsItem::Resign => settings.next_resign_score(),
+                    #[cfg(not(target_family = "wasm"))]
+                    SettingsItem::CheckUpdates => {
+                        settings.check_updates = !settings.check_updates;
+                        return;
+                    }
+                    SettingsItem::Back => {
+                        self.overlay = Some(Overlay::pause());
+                        return;
+                    }
+                }
+
+                // Rebuild to update the labels
+                self.overlay = Some(Overlay::settings());
+            }
+            Overlay::NewGame(menu, options) => {
+                let Some(item) = menu.update() else {
+                    return;
+                };
+
+                let mut options = options.clone();
+                match item {
+                    NewGameItem::Color => options.next_color(),
+                    NewGameItem::Opponent => options.next_agent(),
+                    NewGameItem::PlayerAgent => options.next_player_agent(),
+                    NewGameItem::Difficulty => settings().difficulty = settings().difficulty.next(),
+                    NewGameItem::Depth => options.next_max_depth(),
+                    NewGameItem::TimeControl => options.next_time_control(),
+                    NewGameItem::Position => options.next_fen(),
+                    NewGameItem::Handicap => options.next_handicap(),
+                    NewGameItem::Variant => options.variant = options.variant.next(),
+                    NewGameItem::Mode => options.rated = !options.rated,
+                    #[cfg(not(target_family = "wasm"))]
+                    NewGameItem::Suggested => {
+                        let suggested = suggested_opponent();
+                        options.agent = suggested.agent;
+                        options.max_depth = suggested.max_depth;
+                        settings().difficulty = suggested.difficulty;
+                    }
+                    NewGameItem::Start => {
+                        self.start_new_game(options);
+                        return;
+                    }
+                    NewGameItem::Cancel => {
+                        self.overlay = None;
+                        return;
+                    }
+                }
+
+                // Rebuild to update the labels
+                self.overlay = Some(Overlay::new_game(options));
+            }
+            Overlay::Comment(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                match (entered, menu.update()) {
+                    (true, _) | (_, Some(ConfirmItem::Yes)) => {
+                        let text = input.text.clone();
+                        self.set_comment(&text);
+                        self.overlay = None;
+                    }
+                    (_, Some(ConfirmItem::No)) => self.overlay = None,
+                    _ => {}
+                }
+            }
+            Overlay::TypeMove(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                match (entered, menu.update()) {
+                    (true, _) | (_, Some(ConfirmItem::Yes)) => {
+                        match try_parse_move(&self.board, &input.text) {
+                            Ok((from, to)) => {
+                                // A typed promotion piece is played as is, otherwise it's picked like a click
+                                let typed = promotion_piece(&input.text)
+                                    .filter(|_| self.board.is_promotion(&from, &to));
+                                self.overlay = None;
+                                match typed {
+                                    Some(piece) => {
+                                        self.board.promote_to = piece;
+                                        self.finish_move(&from, &to);
+                                    }
+                                    None => self.play_move(&from, &to),
+                                }
+                            }
+                            Err(err) => {
+                                info!("{}", err);
+                                self.play_illegal_sound();
+                            }
+                        }
+                    }
+                    (_, Some(ConfirmItem::No)) => self.overlay = None,
+                    _ => {}
+                }
+            }
+            Overlay::FindMate(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                let item = match (entered, menu.update()) {
+                    (true, _) => Some(FindMateItem::Search),
+                    (_, item) => item,
+                };
+                let n = input.text.trim().parse();
+                match (item, n) {
+                    (Some(FindMateItem::Cancel), _) => self.overlay = None,
+                    (Some(item), Ok(n)) if (1..=MAX_MATE_MOVES).contains(&n) => {
+                        self.overlay = None;
+                        self.start_mate_search(n, item == FindMateItem::ChecksOnly);
+                    }
+                    (Some(_), _) => {
+                        info!("Mates can be looked for in 1 to {} moves", MAX_MATE_MOVES)
+                    }
+                    (None, _) => {}
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::Network(menu) => match menu.update() {
+                Some(NetworkItem::HostLan) => self.overlay = Some(Overlay::host_lan()),
+                Some(NetworkItem::JoinLan) => self.overlay = Some(Overlay::join_lan()),
+                Some(NetworkItem::Broadcast) => {
+                    // Stops the running one, or asks for the port to start one on
+                    if BROADCAST.lock().unwrap().take().is_some() {
+                        info!("Stopped broadcasting");
+                        self.overlay = Some(network_menu());
+                    } else {
+                        self.overlay = Some(Overlay::broadcast());
+                    }
+                }
+                Some(NetworkItem::Watch) => self.overlay = Some(Overlay::watch()),
+                Some(NetworkItem::Back) => self.overlay = Some(Overlay::pause()),
+                None => {}
+            },
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::Broadcast(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                match (entered, menu.update()) {
+                    (true, _) | (_, Some(ConfirmItem::Yes)) => match input.text.trim().parse() {
+                        Ok(port) => match Broadcast::start(port) {
+                            Ok(broadcast) => {
+                                info!("Broadcasting on port {}", broadcast.port);
+                                *BROADCAST.lock().unwrap() = Some(broadcast);
+                                self.published = None;
+                                self.overlay = Some(network_menu());
+                            }
+                            Err(err) => info!("Can't broadcast on port {}: {}", port, err),
+                        },
+                        Err(_) => info!("{} isn't a port", input.text.trim()),
+                    },
+                    (_, Some(ConfirmItem::No)) => self.overlay = Some(network_menu()),
+                    _ => {}
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::Watch(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                match (entered, menu.update()) {
+                    (true, _) | (_, Some(ConfirmItem::Yes)) => {
+                        let address = input.text.trim().to_string();
+                        self.watch(&address);
+                    }
+                    (_, Some(ConfirmItem::No)) => self.overlay = Some(network_menu()),
+                    _ => {}
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::Chat(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                match (entered, menu.update()) {
+                    (true, _) | (_, Some(ConfirmItem::Yes)) => {
+                        let text = input.text.clone();
+                        self.send_chat(&text);
+                        self.overlay = None;
+                    }
+                    (_, Some(ConfirmItem::No)) => self.overlay = None,
+                    _ => {}
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::HostLan(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                match (entered, menu.update()) {
+                    (true, _) | (_, Some(ConfirmItem::Yes)) => match input.text.trim().parse() {
+                        Ok(port) => {
+                            self.overlay = None;
+                            self.host_lan(port);
+                        }
+                        Err(_) => info!("{} isn't a port", input.text.trim()),
+                    },
+                    (_, Some(ConfirmItem::No)) => self.overlay = None,
+                    _ => {}
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::JoinLan(menu, input) => {
+                let entered = input.update();
+                input.draw();
+                match (entered, menu.update()) {
+                    (true, _) | (_, Some(ConfirmItem::Yes)) => {
+                        let address = input.text.trim().to_string();
+                        self.overlay = None;
+                        self.join_lan(&address);
+                    }
+                    (_, Some(ConfirmItem::No)) => self.overlay = None,
+                    _ => {}
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::LanWaiting(menu) => {
+                if menu.update().is_some() {
+                    self.leave_lan();
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::LanDisconnected(menu) => match menu.update() {
+                Some(ConfirmItem::Yes) => self.reconnect_lan(),
+                Some(ConfirmItem::No) => self.leave_lan(),
+                None => {}
+            },
+            Overlay::AgentError(menu) => match menu.update() {
+                Some(ConfirmItem::Yes) => {
+                    self.take_over_agent();
+                    self.overlay = None;
+                }
+                Some(ConfirmItem::No) => {
+                    self.overlay = Some(Overlay::new_game(self.options.clone()));
+                }
+                None => {}
+            },
+            Overlay::Tables(menu) => {
+                let Some(item) = menu.update() else {
+                    return;
+                };
 
-        if self.agent == Agent::Control || self.board.turn == self.board.player_color {
-            if let Some(clicked) = self.clicked_square(MouseButton::Left) {
-                // Click same place
-                if self.selected.is_some() && self.selected.unwrap().pos == clicked {
-                    self.selected = None;
-                    self.highlight_moves.clear();
-                // Move (Clicked highlighted piece)
-                } else if self.highlight_moves.contains(&clicked) {
-                    self.move_piece(&self.selected.unwrap().pos, &clicked);
-                    // Clicked a new place
-                } else if let Some(piece) = self.board.get(&clicked) {
-                    if piece.color == self.board.turn {
-                        self.selected = Some(piece);
-                        self.highlight_moves = self.selected.unwrap().moves(&self.board);
+                match item {
+                    // Sliders show their own value, rebuilding would stop a drag
+                    TableItem::Weight(piece) => {
+                        set_pst_weight(&piece, menu.slider_value(item).unwrap());
+                        self.board.score = self.board.score();
+                        menu.set_body(self.board.eval_terms().lines());
+                        // Restarted with the new weights
+                        #[cfg(not(target_family = "wasm"))]
+                        {
+                            self.eval_search = None;
+                        }
+                        return;
+                    }
+                    TableItem::Show => {
+                        let next = match self.table_view {
+                            None => Some(0),
+                            Some(piece) => Some(piece as usize + 1).filter(|i| *i < PIECES.len()),
+                        };
+                        self.table_view = next.map(|i| PIECES[i]);
+                    }
+                    TableItem::Export => {
+                        copy_to_clipboard(&export_tables());
+                        return;
                     }
+                    TableItem::Reset => {
+                        for piece in PIECES {
+                            set_pst_weight(&piece, 1.0);
+                        }
+                        self.board.score = self.board.score();
+                        #[cfg(not(target_family = "wasm"))]
+                        {
+                            self.eval_search = None;
+                        }
+                    }
+                    TableItem::Back => {
+                        self.overlay = None;
+                        return;
+                    }
+                }
+
+                // Rebuild to update the labels
+                self.overlay = Some(Overlay::tables(&self.board.eval_terms(), self.table_view));
+            }
+            Overlay::MateResult(menu) | Overlay::SearchTree(menu) => {
+                if menu.update().is_some() {
+                    self.overlay = None;
+                }
+            }
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::Stats(menu) => {
+                if menu.update().is_some() {
+                    self.overlay = Some(Overlay::pause());
                 }
             }
-        } else if self.waiting_on_agent {
-            if let Ok(mov) = self.agent_channel.1.try_recv() {
-                self.waiting_on_agent = false;
-                if let Some(m) = mov {
-                    self.move_piece(&m.0, &m.1);
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::PuzzleRush(menu) => match menu.update() {
+                Some(Some(minutes)) => {
+                    self.overlay = None;
+                    self.start_rush(minutes);
+                }
+                Some(None) => self.overlay = None,
+                None => {}
+            },
+            Overlay::Help(menu) => match menu.update() {
+                Some(Some(page)) => self.overlay = Some(Overlay::help(page)),
+                Some(None) => self.overlay = None,
+                None => {}
+            },
+            Overlay::DrawOffer(menu) => match menu.update() {
+                Some(ConfirmItem::Yes) => {
+                    self.board.state = BoardState::Draw(DrawReason::Agreement);
+                    self.overlay = None;
+                }
+                Some(ConfirmItem::No) => self.overlay = None,
+                None => {}
+            },
+            Overlay::ConfirmReset(menu, options) => match menu.update() {
+                Some(ConfirmItem::Yes) => {
+                    let options = options.clone();
+                    self.reset(options);
+                }
+                Some(ConfirmItem::No) => self.overlay = None,
+                None => {}
+            },
+            Overlay::ChooseDropped(menu, items) => match menu.update() {
+                Some(Some(i)) => {
+                    let dropped = items[i].clone();
+                    self.overlay = None;
+                    self.load_dropped(dropped);
+                }
+                Some(None) => self.overlay = None,
+                None => {}
+            },
+            Overlay::Promotion(menu, (from, to)) => match menu.update() {
+                Some(Some(piece)) => {
+                    let (from, to) = (*from, *to);
+                    self.overlay = None;
+                    self.board.promote_to = piece;
+                    self.finish_move(&from, &to);
+                }
+                Some(None) => self.overlay = None,
+                None => {}
+            },
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::RestoreGame(menu, record) => match menu.update() {
+                Some(ConfirmItem::Yes) => *self = Game::restore(record),
+                Some(ConfirmItem::No) => {
+                    remove_autosave();
+                    self.overlay = None;
+                }
+                None => {}
+            },
+        }
+    }
+
+    /// Wether the clock of the color to move runs down, see [Settings::pause_clock](crate::settings::Settings::pause_clock)
+    /// and [Settings::agent_clock](crate::settings::Settings::agent_clock)
+    fn clock_running(&self) -> bool {
+        if self.board.is_over() || self.drill_answered() {
+            return false;
+        }
+        // The LAN opponent's clock keeps running on their side
+        if self.overlay.is_some() && settings().pause_clock && !self.lan_game {
+            return false;
+        }
+        // Both clocks wait while the connection is down, and pick up from the host's after reconnecting
+        #[cfg(not(target_family = "wasm"))]
+        if self.lan_game && !self.lan.as_ref().is_some_and(|lan| lan.connected) {
+            return false;
+        }
+        settings().agent_clock || self.agent_for(self.board.turn).is_none()
+    }
+
+    /// Runs down the clock of the color to move, ending the game if it runs out
+    fn update_clock(&mut self) {
+        if !self.clock_running() {
+            return;
+        }
+        let Some(clock) = self.clock.as_mut() else {
+            return;
+        };
+
+        let color = self.board.turn;
+        let mut events = vec![];
+        if let Some(alert) = clock.tick(color, get_frame_time() as f64) {
+            events.push(GameEvent::ClockAlert { color, alert });
+        }
+        if let Some(color) = clock.flagged().filter(|color| self.may_flag(*color)) {
+            self.board.state = BoardState::Timeout(color);
+            events.push(GameEvent::GameEnd(self.board.state));
+        }
+        self.events.clone().publish(self, &events);
+    }
+
+    /// Drills wait on the next position once answered, and rushes on the next puzzle or for good once over
+    fn drill_answered(&self) -> bool {
+        self.drill.as_ref().is_some_and(|d| d.result.is_some())
+            || self
+                .rush
+                .as_ref()
+                .is_some_and(|r| r.next_at.is_some() || r.over)
+    }
+
+    /// Whether the side to move is played by clicking or typing moves
+    fn awaiting_click(&self) -> bool {
+        !self.board.is_over()
+            && !self.drill_answered()
+            && !self.remote_turn()
+            && self.agent_for(self.board.turn).is_none()
+    }
+
+    /// Whether the move is coming over the network, from the LAN opponent or a broadcast being watched
+    fn remote_turn(&self) -> bool {
+        #[cfg(not(target_family = "wasm"))]
+        if self.watching.is_some() {
+            return true;
+        }
+        self.lan_game && self.board.turn != self.board.player_color
+    }
+
+    /// Square of the only piece that can move to `to`, if [Settings::smart_moves](crate::settings::Settings::smart_moves) is on
+    fn smart_move(&self, to: &Loc) -> Option<Loc> {
+        if !settings().smart_moves {
+            return None;
+        }
+        match self.board.movers(self.board.turn).get(to)?.as_slice() {
+            [from] => Some(*from),
+            _ => None,
+        }
+    }
+
+    /// Plays a move clicked or typed by the player, or answers the drill
+    fn play_move(&mut self, from: &Loc, to: &Loc) {
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        if self.board.is_promotion(from, to) && settings().promotion.asks(ctrl) {
+            self.overlay = Some(Overlay::promotion(*from, *to));
+            return;
+        }
+        self.finish_move(from, to);
+    }
+
+    /// Plays the player's move, promoting to [Board::promote_to] if it's a promotion
+    fn finish_move(&mut self, from: &Loc, to: &Loc) {
+        if self.rush.is_some() {
+            self.answer_rush(from, to);
+        } else if self.drill.is_some() {
+            self.answer_drill(from, to);
+        } else {
+            self.move_piece(from, to);
+        }
+        // Wrong drill answers aren't played, and would leave it for the agent's move
+        self.board.promote_to = PieceNames::Queen;
+    }
+
+    /// What the board is waiting on this frame, see [TurnState]
+    pub(crate) fn turn_state(&self) -> TurnState {
+        match &self.overlay {
+            Some(Overlay::Promotion(..)) => return TurnState::PromotionPending,
+            Some(_) => return TurnState::Menu,
+            None => {}
+        }
+
+        if self.board.is_over() || self.drill_answered() {
+            TurnState::GameOver
+        } else if self.remote_turn() {
+            TurnState::RemoteTurn
+        } else if let Some(agent) = self.agent_for(self.board.turn) {
+            // Scrolled back positions are drawn still, so the slide wouldn't finish
+            let landing = !self.waiting_on_agent && self.preview.is_none() && self.sliding();
+            ternary!(
+                landing,
+                TurnState::Animating,
+                TurnState::AgentThinking(agent)
+            )
+        } else if self.preview.is_some() {
+            TurnState::Replaying
+        } else {
+            TurnState::PlayerTurn
+        }
+    }
+
+    /// Runs the board's input and the agent for the [TurnState]
+    fn update_turn(&mut self) {
+        let state = self.turn_state();
+        if matches!(state, TurnState::PromotionPending | TurnState::Menu) {
+            return;
+        }
+
+        self.update_arrows_highlights();
+        if self.update_preview() {
+            return;
+        }
+        match state {
+            TurnState::AgentThinking(agent) => self.update_agent(agent),
+            TurnState::PlayerTurn => self.update_player_turn(),
+            // Moves from the network come in through `update_lan` and `update_watching`
+            _ => {}
+        }
+    }
+
+    /// Plays the side to move's clicks on the board
+    fn update_player_turn(&mut self) {
+        let Some(clicked) = self.clicked_square(MouseButton::Left) else {
+            return;
+        };
+        // Click same place
+        if self.selected.is_some() && self.selected.unwrap().pos == clicked {
+            self.selected = None;
+            self.highlight_moves.clear();
+        // Move (Clicked highlighted piece)
+        } else if self.highlight_moves.contains(&clicked) {
+            self.play_move(&self.selected.unwrap().pos, &clicked);
+            // Clicked a new place
+        } else if let Some(piece) = self
+            .board
+            .get(&clicked)
+            .filter(|piece| piece.color == self.board.turn)
+        {
+            self.selected = Some(piece);
+            self.highlight_moves = piece.moves(&self.board);
+            // Only one piece can go there
+        } else if let Some(from) = self.smart_move(&clicked) {
+            self.selected = None;
+            self.highlight_moves.clear();
+            self.play_move(&from, &clicked);
+            // Tried moving the selected piece somewhere it can't go
+        } else if self.selected.is_some() {
+            self.reject_move(&clicked);
+        }
+    }
+
+    /// Starts `agent` thinking on the side to move's move, then plays what it picks
+    fn update_agent(&mut self, agent: AgentId) {
+        if self.waiting_on_agent {
+            while let Ok(message) = self.agent_channel.1.try_recv() {
+                match message {
+                    AgentMessage::Info { .. } => {
+                        self.agent_info = message.info();
+                        // The agent's evaluation would help in a rated game
+                        if let Some(info) = self.agent_info.as_ref().filter(|_| !self.rated()) {
+                            push_log(LogKind::Engine, log_line(&self.board, info));
+                        }
+                    }
+                    AgentMessage::BookHit { name } => self.agent_opening = Some(name),
+                    AgentMessage::Resign => {
+                        self.waiting_on_agent = false;
+                        if let Some(decision) = &mut self.decision {
+                            decision.finish(None, self.agent_info.as_ref(), None);
+                        }
+                        info!("The agent resigned");
+                        self.board.state = BoardState::Resigned(self.board.turn);
+                        return;
+                    }
+                    AgentMessage::Error(err) => {
+                        self.waiting_on_agent = false;
+                        if let Some(decision) = &mut self.decision {
+                            decision.finish(None, self.agent_info.as_ref(), None);
+                        }
+                        warn!("{}", err);
+                        self.overlay = Some(Overlay::agent_error(&err));
+                        return;
+                    }
+                    AgentMessage::BestMove(from, to) => {
+                        self.waiting_on_agent = false;
+                        self.play_agent_move(agent, &from, &to);
+                        return;
+                    }
                 }
             }
         } else {
-            let agent = self.agent;
             let board = self.board.clone();
+            let mut limits = SearchLimits::new(settings().difficulty.max_time());
+            if settings().engine_arrows && !self.rated() {
+                limits.multi_pv = ENGINE_ARROW_LINES;
+            }
+            limits.search_tree = settings().search_tree && !self.rated();
+            if self.board.turn == self.board.agent_color {
+                if let Some(depth) = self.options.max_depth {
+                    limits.max_time = f64::INFINITY;
+                    limits.max_depth = Some(depth);
+                }
+            }
+            // Don't let the agent flag itself
+            if let Some(clock) = &self.clock {
+                limits.max_time = limits.max_time.min(clock.remaining(self.board.turn) / 20.0);
+            }
+            // The agent resigns on its own thread, from a copy of the outlook
+            let resign = (!self.spectating() && self.board.turn == self.board.agent_color)
+                .then_some((self.outlook, settings().resign_score));
             self.waiting_on_agent = true;
+            self.agent_info = None;
+            self.agent_opening = None;
+            self.think_start = date::now();
+            // Reseeded so agents that pick at random can be reproduced
+            let seed = rand() as u64;
+            srand(seed);
+            self.decision = Some(Decision::start(&board, agent, &limits, seed));
+            let sender = self.agent_channel.0.clone();
             #[cfg(target_family = "wasm")]
             {
-                self.agent_channel.0.send(agent.get_move(&board)).unwrap();
+                self.thinking = Some(Thinking::new(agent, &board, limits, resign, sender));
             }
             #[cfg(not(target_family = "wasm"))]
-            {
-                let sender = self.agent_channel.0.clone();
-                spawn(move || {
-                    sender.send(agent.get_move(&board)).unwrap();
-                });
+            spawn(move || agent.think(&board, limits, resign, sender));
+        }
+    }
+
+    /// Polls the network games, see [BACKGROUND_TASKS]
+    #[cfg(not(target_family = "wasm"))]
+    fn poll_network(&mut self, _deadline: f64) {
+        self.update_lan();
+        self.update_watching();
+        self.update_broadcast();
+    }
+
+    /// Analyzes the positions reached, see [BACKGROUND_TASKS]
+    fn update_analysis(&mut self, deadline: f64) {
+        let threats = self.watching_threats();
+        if settings().analysis || threats {
+            self.analysis
+                .update(&self.board, self.moves.len(), deadline, threats);
+        }
+    }
+
+    /// Wether it's the player's turn against the agent, with [Settings::threat_warning](crate::settings::Settings::threat_warning) on
+    /// Wether a rated game is being played, with takebacks and hints off until it's over
+    pub(crate) fn rated(&self) -> bool {
+        self.options.rated && self.replay.is_none() && !self.board.is_over()
+    }
+
+    fn watching_threats(&self) -> bool {
+        settings().threat_warning.is_some()
+            && !self.rated()
+            && self.replay.is_none()
+            && !self.board.is_over()
+            && self.agent_for(self.board.turn).is_none()
+            && self.agent_for(self.board.turn.other()).is_some()
+    }
+
+    /// Warning of what the agent threatens, if it's enough to warn about
+    fn threat_warning(&self) -> Option<String> {
+        if !self.watching_threats() || self.preview.is_some() {
+            return None;
+        }
+        match self
+            .analysis
+            .threat(self.moves.len(), self.board.turn.other())?
+        {
+            Threat::Mate => Some("Watch out: mate threat".to_string()),
+            Threat::Gain(gain) if gain >= settings().threat_warning? => {
+                Some(format!("Watch out: threat of +{:.1}", gain as f32 / 100.0))
+            }
+            Threat::Gain(_) => None,
+        }
+    }
+
+    /// Collects what the mate search, live analysis, and eval bar have found, see [BACKGROUND_TASKS]
+    #[cfg_attr(not(target_family = "wasm"), allow(unused_variables))]
+    fn update_searches(&mut self, deadline: f64) {
+        #[cfg(target_family = "wasm")]
+        if let Some(search) = &mut self.mate_slices {
+            if let Some(line) = search.run(&SearchLimits::new(f64::INFINITY), deadline) {
+                self.mate_channel.0.send(line).unwrap();
+                self.mate_slices = None;
             }
         }
+        // Waits for other overlays to close before showing the result
+        if self.overlay.is_none() {
+            if let Ok(line) = self.mate_channel.1.try_recv() {
+                if let Some((board, n, checks_only)) = self.mate_search.take() {
+                    let lines = mate_lines(&board, n, checks_only, &line);
+                    self.overlay = Some(Overlay::mate_result(lines));
+                }
+                #[cfg(not(target_family = "wasm"))]
+                {
+                    self.mate_stop = None;
+                }
+            }
+        }
+        if let Some(live) = self.live_analysis.as_mut() {
+            live.update();
+            // Dropping the analysis stops its search
+            if live.board.hash != self.board.hash {
+                self.live_analysis = None;
+            }
+        }
+        #[cfg(not(target_family = "wasm"))]
+        self.update_eval_search();
+    }
+
+    /// Thinks on the agent's move until `deadline`, see [BACKGROUND_TASKS]
+    #[cfg(target_family = "wasm")]
+    fn update_thinking(&mut self, deadline: f64) {
+        if let Some(thinking) = &mut self.thinking {
+            if thinking.step(deadline) {
+                self.thinking = None;
+            }
+        }
+    }
+
+    /// Reads more of the opening book while it's loading, see [load_in_background](crate::agent_opens::load_in_background)
+    #[cfg(target_family = "wasm")]
+    fn update_book(&mut self, deadline: f64) {
+        step_loading(deadline);
+    }
+
+    /// Runs the background work only, for a game in a tab that isn't shown
+    /// - Its clock and agent wait until it's shown again
+    pub(crate) fn update_background(&mut self) {
+        let mut scheduler = self.scheduler;
+        scheduler.run(self, BACKGROUND_TASKS, date::now);
+        self.scheduler = scheduler;
+    }
+
+    /// Short name for the game's tab, IE `vs Minimax` or `Analysis`
+    pub(crate) fn tab_name(&self) -> String {
+        #[cfg(not(target_family = "wasm"))]
+        if self.watching.is_some() {
+            return "Watching".to_string();
+        }
+        if self.analysis_board {
+            "Analysis".to_string()
+        } else if self.replay.is_some() {
+            "Replay".to_string()
+        } else if self.lan_game {
+            "LAN game".to_string()
+        } else if self.agent == AgentId::CONTROL {
+            "Two players".to_string()
+        } else {
+            format!("vs {}", self.agent)
+        }
+    }
+
+    pub(crate) fn update(&mut self) {
+        self.update_keys();
+        self.update_dropped();
+        self.update_background();
+
+        // Board input and the agent are paused while an overlay is open, the clock depending on the settings
+        self.update_clock();
+        self.update_rush();
+        self.update_turn();
+        self.update_flip();
+        self.update_buttons();
 
         // Drawing
-        self.board.draw(
-            &self.highlight_moves,
-            &self.last_move,
-            &self.highlights,
-            &self.arrows,
-            &mut self.current_tween,
+        // Everything is shown once the game is over
+        let visibility = ternary!(
+            self.board.is_over(),
+            Visibility::Normal,
+            settings().visibility
+        );
+        let revealed = self.revealed();
+        let engine_shown = visibility == Visibility::Normal
+            && revealed.is_none()
+            && self.preview.is_none()
+            && !self.rated();
+        // Scrolled back positions are drawn still, with the move that led to them
+        let (board, last_move) = match &self.preview {
+            Some(preview) => (
+                &preview.board,
+                preview.ply.checked_sub(1).map(|i| self.moves[i]),
+            ),
+            None => (&self.board, self.last_move),
+        };
+        let mut still = vec![];
+        board.draw(
+            &DrawOptions {
+                highlight_moves: &self.highlight_moves,
+                last_move,
+                highlights: &self.highlights,
+                arrows: ternary!(settings().user_arrows, &self.arrows, &[]),
+                hint_arrows: ternary!(settings().hint_arrows, &self.hint_arrows, &[]),
+                // They'd give away where hidden pieces are, and are for the live position
+                threat_arrow: self
+                    .threat_warning()
+                    .and_then(|_| *self.analysis.threat_moves.get(self.moves.len())?)
+                    .filter(|_| settings().threat_arrows && engine_shown),
+                engine_arrows: ternary!(engine_shown, &self.engine_arrows, &[]),
+                visibility,
+                revealed,
+                illegal: self.illegal_flash.and_then(|(loc, tried)| {
+                    flash_strength(date::now() - tried).map(|strength| (loc, strength))
+                }),
+                flipped: self.flipped,
+                values: self.table_view.map(|piece| {
+                    let color = ternary!(self.flipped, ChessColor::Black, ChessColor::White);
+                    weighted_table(&piece, &color, board.phase())
+                }),
+            },
+            ternary!(self.preview.is_some(), &mut still, &mut self.current_tween),
         );
+        if let Some(at) = self.flip_at {
+            draw_rectangle(
+                layout().margin,
+                layout().top,
+                layout().square_size * 8.0,
+                layout().square_size * 8.0,
+                Color {
+                    a: flip_fade(date::now() - at),
+                    ..COLOR_BACKGROUND
+                },
+            );
+        }
+        #[cfg(not(target_family = "wasm"))]
+        self.draw_eval_bar();
         self.draw_ui();
+        self.draw_announcement();
+        self.draw_attack_tooltip();
 
         if self.board.is_over() {
             self.draw_end();
         }
+        #[cfg(not(target_family = "wasm"))]
+        self.update_banner();
+
+        // Overlays are laid out around the middle of the default window
+        camera().center_on(CENTER_WIDTH as f32, CENTER_HEIGHT as f32);
+        self.update_overlay();
+        camera().center_on(layout().width / 2.0, layout().height / 2.0);
     }
 }
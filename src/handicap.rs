@@ -0,0 +1,63 @@
+//! Odds games, where the agent starts without a piece or gives up the first move to make up for being stronger
+
+use crate::board::ChessColor;
+use crate::color_ternary;
+use crate::conf::DEFAULT_FEN;
+
+/// What the agent gives up at the start of an odds game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Handicap {
+    /// Without its queenside knight
+    Knight,
+    /// Without its queenside rook, and so without queenside castling
+    Rook,
+    Queen,
+    /// The player moves first, even as black
+    Move,
+}
+impl Handicap {
+    /// Name of the handicap, IE `Knight odds`
+    pub(crate) fn name(&self) -> String {
+        format!("{:?} odds", self)
+    }
+
+    /// File of the piece the agent starts without, `None` for [Handicap::Move]
+    fn missing_file(&self) -> Option<usize> {
+        match self {
+            Handicap::Knight => Some(1),
+            Handicap::Rook => Some(0),
+            Handicap::Queen => Some(3),
+            Handicap::Move => None,
+        }
+    }
+
+    /// Starting position where `giver` gives these odds to the other side
+    pub(crate) fn fen(&self, giver: ChessColor) -> String {
+        let mut parts: Vec<String> = DEFAULT_FEN.split(' ').map(str::to_string).collect();
+
+        if let Some(file) = self.missing_file() {
+            // The giver's back rank, from `a` to `h`
+            let mut ranks: Vec<String> = parts[0].split('/').map(str::to_string).collect();
+            let rank = color_ternary!(giver, 7, 0);
+            ranks[rank].replace_range(file..file + 1, "1");
+            parts[0] = ranks.join("/");
+        }
+        if *self == Handicap::Rook {
+            parts[2] = parts[2].replace(color_ternary!(giver, 'Q', 'q'), "");
+        }
+        if *self == Handicap::Move {
+            parts[1] = color_ternary!(giver, "b", "w").to_string();
+        }
+
+        parts.join(" ")
+    }
+}
+
+/// Handicaps that can be picked in the new game dialog, `None` being an even game
+pub(crate) const HANDICAPS: [Option<Handicap>; 5] = [
+    None,
+    Some(Handicap::Knight),
+    Some(Handicap::Rook),
+    Some(Handicap::Queen),
+    Some(Handicap::Move),
+];
@@ -0,0 +1,106 @@
+//! Central table of keybinds and mouse gestures
+//!
+//! Key handling, toolbar tooltips, and the help overlay are all generated from here, so new binds only need adding once
+
+use macroquad::prelude::{is_key_pressed, KeyCode};
+
+use crate::game::MenuAction;
+
+/// (key, name of the key, action)
+pub(crate) const KEYBINDS: [(KeyCode, &str, MenuAction); 40] = [
+    (KeyCode::Escape, "Esc", MenuAction::Pause),
+    (KeyCode::Slash, "?", MenuAction::Help),
+    (KeyCode::R, "R", MenuAction::Reset),
+    (KeyCode::L, "L", MenuAction::Takeback),
+    (KeyCode::T, "T", MenuAction::CopyFen),
+    (KeyCode::P, "P", MenuAction::CopyPgn),
+    (KeyCode::F, "F", MenuAction::PrintBoard),
+    (KeyCode::I, "I", MenuAction::ImportPgn),
+    (KeyCode::Left, "Left", MenuAction::Back),
+    (KeyCode::Right, "Right", MenuAction::Forward),
+    (KeyCode::Up, "Up", MenuAction::PrevVariation),
+    (KeyCode::Down, "Down", MenuAction::NextVariation),
+    (KeyCode::A, "A", MenuAction::Study),
+    (KeyCode::C, "C", MenuAction::Comment),
+    (KeyCode::N, "N", MenuAction::NextAnnotation),
+    (KeyCode::S, "S", MenuAction::SaveStudy),
+    (KeyCode::O, "O", MenuAction::OpenStudy),
+    (KeyCode::E, "E", MenuAction::AddRepertoire),
+    (KeyCode::D, "D", MenuAction::Drill),
+    (KeyCode::M, "M", MenuAction::TypeMove),
+    (KeyCode::G, "G", MenuAction::OfferDraw),
+    (KeyCode::Z, "Z", MenuAction::Analyze),
+    (KeyCode::K, "K", MenuAction::FindMate),
+    (KeyCode::H, "H", MenuAction::Chat),
+    (KeyCode::B, "B", MenuAction::AnalysisBoard),
+    (KeyCode::X, "X", MenuAction::EngineMove),
+    (KeyCode::V, "V", MenuAction::FlipBoard),
+    (KeyCode::W, "W", MenuAction::Tables),
+    (KeyCode::J, "J", MenuAction::Explorer),
+    (KeyCode::Y, "Y", MenuAction::Review),
+    (KeyCode::U, "U", MenuAction::PlayFromHere),
+    (KeyCode::Q, "Q", MenuAction::ReturnToGame),
+    (KeyCode::Period, ".", MenuAction::ExportAnalysis),
+    (KeyCode::Equal, "=", MenuAction::NewTab),
+    (KeyCode::Minus, "-", MenuAction::CloseTab),
+    (KeyCode::Tab, "Tab", MenuAction::NextTab),
+    (KeyCode::Backslash, "\\", MenuAction::SearchTree),
+    (KeyCode::Comma, ",", MenuAction::SaveDecision),
+    (KeyCode::GraveAccent, "`", MenuAction::Log),
+    (KeyCode::Semicolon, ";", MenuAction::Mute),
+];
+
+/// (gesture, what it does), only listed in the help overlay since they're handled by the board
+pub(crate) const MOUSE_GESTURES: [(&str, &str); 6] = [
+    ("Left click", "Select and move pieces"),
+    ("Right click", "Highlight a square"),
+    ("Right drag", "Draw an arrow"),
+    ("Left click", "Clear arrows and highlights"),
+    ("Shift hover", "Count a piece's attackers and defenders"),
+    ("Shift right click", "Show what taking a piece wins"),
+];
+
+/// Name of the key bound to `action`, if any
+pub(crate) fn key_name(action: MenuAction) -> Option<&'static str> {
+    KEYBINDS
+        .iter()
+        .find(|(_, _, a)| *a == action)
+        .map(|(_, name, _)| *name)
+}
+
+/// Tooltip for an action, IE `Reset (R)`
+pub(crate) fn tooltip(action: MenuAction) -> String {
+    match key_name(action) {
+        Some(key) => format!("{} ({})", action.name(), key),
+        None => action.name().to_string(),
+    }
+}
+
+/// Actions whose key was pressed this frame
+pub(crate) fn pressed_actions() -> Vec<MenuAction> {
+    KEYBINDS
+        .iter()
+        .filter(|(key, _, _)| is_key_pressed(*key))
+        .map(|(_, _, action)| *action)
+        .collect()
+}
+
+/// Lines of the help overlay, keys first and then mouse gestures
+pub(crate) fn help_lines() -> Vec<String> {
+    let width = KEYBINDS
+        .iter()
+        .map(|(_, name, _)| name.len())
+        .chain(MOUSE_GESTURES.iter().map(|(gesture, _)| gesture.len()))
+        .max()
+        .unwrap_or(0);
+
+    KEYBINDS
+        .iter()
+        .map(|(_, name, action)| format!("{:width$}  {}", name, action.name()))
+        .chain(
+            MOUSE_GESTURES
+                .iter()
+                .map(|(gesture, description)| format!("{:width$}  {}", gesture, description)),
+        )
+        .collect()
+}
@@ -0,0 +1,398 @@
+//! Playing another instance over the local network, one hosts on a port and the other joins by address
+//!
+//! Both sides send lines of text over TCP. The guest opens with [HELLO], and the host answers with the game as a
+//! [Handshake]. After that, each move is sent as `move e2e4 <seconds>` with the time the mover took, and each chat
+//! message as `chat <text>`
+//!
+//! - The host picks the colors, starting position, and clock
+//! - Moves so far are part of the handshake, so a dropped game can be picked back up by reconnecting
+//! - The host's clock is the one that counts. It sends it as `clock <white> <black> <increment>` after every move,
+//!   and ends the game when someone runs out of time with `flag <color>`, see [charged_time](crate::clock::charged_time)
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::spawn;
+use std::time::Duration;
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+
+use crate::board::ChessColor;
+use crate::clock::Clock;
+
+pub(crate) const DEFAULT_PORT: u16 = 7878;
+
+/// First line the guest sends, the version is bumped when the messages change
+pub(crate) const HELLO: &str = "hello chess-ai 2";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Game the host sets up for both sides
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Handshake {
+    /// Color the guest plays, the host plays the other
+    pub(crate) guest_color: ChessColor,
+    pub(crate) fen: String,
+    /// Moves played since [Handshake::fen] in coordinate notation, empty unless reconnecting
+    pub(crate) moves: Vec<String>,
+    /// `None` for an untimed game
+    pub(crate) clock: Option<Clock>,
+}
+impl Handshake {
+    /// Lines the host sends, ending with `ready`
+    pub(crate) fn lines(&self) -> Vec<String> {
+        vec![
+            format!("color {:?}", self.guest_color),
+            format!("clock {}", clock_text(self.clock.as_ref())),
+            format!("fen {}", self.fen),
+            format!("moves {}", self.moves.join(" ")),
+            "ready".to_string(),
+        ]
+    }
+
+    /// Reads the lines from [Handshake::lines], up to the `ready`
+    pub(crate) fn parse(lines: &[String]) -> Result<Handshake, String> {
+        let mut guest_color = None;
+        let mut clock = None;
+        let mut fen = None;
+        let mut moves = vec![];
+        for line in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "color" => guest_color = Some(parse_color(value)?),
+                "clock" => clock = Some(parse_clock(value)?),
+                "fen" => fen = Some(value.to_string()),
+                "moves" => moves = value.split_whitespace().map(str::to_string).collect(),
+                "ready" => break,
+                // Lines from newer versions
+                _ => {}
+            }
+        }
+
+        Ok(Handshake {
+            guest_color: guest_color.ok_or("Handshake is missing the color")?,
+            fen: fen.ok_or("Handshake is missing the FEN")?,
+            moves,
+            clock: clock.ok_or("Handshake is missing the clock")?,
+        })
+    }
+}
+
+/// `White` or `Black`, as colors are sent
+fn parse_color(text: &str) -> Result<ChessColor, String> {
+    match text {
+        "White" => Ok(ChessColor::White),
+        "Black" => Ok(ChessColor::Black),
+        _ => Err(format!("Unknown color {}", text)),
+    }
+}
+
+/// `white black increment` in seconds, or `none`, read by [parse_clock]
+fn clock_text(clock: Option<&Clock>) -> String {
+    match clock {
+        Some(clock) => format!("{} {} {}", clock.white, clock.black, clock.increment),
+        None => "none".to_string(),
+    }
+}
+
+/// `white black increment` in seconds, or `none`
+fn parse_clock(text: &str) -> Result<Option<Clock>, String> {
+    if text == "none" {
+        return Ok(None);
+    }
+
+    let times: Vec<f64> = text
+        .split_whitespace()
+        .map(|time| time.parse().map_err(|_| format!("Bad clock {}", text)))
+        .collect::<Result<_, _>>()?;
+    match times[..] {
+        [white, black, increment] => Ok(Some(Clock {
+            white,
+            black,
+            increment,
+        })),
+        _ => Err(format!("Bad clock {}", text)),
+    }
+}
+
+/// What happened on the connection, from [Lan::poll]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum LanEvent {
+    /// The handshake is done, with the game to play
+    Connected(Handshake),
+    /// The opponent's move in coordinate notation, with the seconds they took if the game is timed
+    Move(String, Option<f64>),
+    /// The host's clock after a move, only sent to the guest
+    Clock(Clock),
+    /// The host ran out of time or found the guest had, only sent to the guest
+    Flag(ChessColor),
+    /// Message the opponent typed
+    Chat(String),
+    /// Why the connection ended, or couldn't be made
+    Disconnected(String),
+}
+
+/// Which side of the connection this instance is
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum LanRole {
+    /// Port being listened on
+    Host(u16),
+    /// Address of the host, IE `192.168.1.5:7878`
+    Guest(String),
+}
+
+/// Writing half of the connection, closed once every copy of it is dropped so the reading thread ends
+struct Socket(Mutex<Option<TcpStream>>);
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Some(stream) = self.0.lock().unwrap().as_ref() {
+            stream.shutdown(Shutdown::Both).ok();
+        }
+    }
+}
+
+/// One connection to the other instance, a new one is made to reconnect
+#[derive(Clone)]
+pub(crate) struct Lan {
+    pub(crate) role: LanRole,
+    /// Wether the handshake is done and the connection hasn't dropped
+    pub(crate) connected: bool,
+    /// Game sent to the guest, only for the host
+    handshake: Option<Handshake>,
+    /// Waiting on the guest, `None` once one connects
+    listener: Option<Arc<TcpListener>>,
+    socket: Arc<Socket>,
+    events: (Sender<LanEvent>, Receiver<LanEvent>),
+}
+impl Lan {
+    /// Starts waiting for a guest on `port`, to play `handshake`
+    pub(crate) fn host(port: u16, handshake: Handshake) -> io::Result<Lan> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Lan {
+            // Port 0 picks a free one
+            role: LanRole::Host(listener.local_addr()?.port()),
+            connected: false,
+            handshake: Some(handshake),
+            listener: Some(Arc::new(listener)),
+            socket: Arc::new(Socket(Mutex::new(None))),
+            events: unbounded(),
+        })
+    }
+
+    /// Starts connecting to the host at `address` on its own thread
+    pub(crate) fn join(address: &str) -> Lan {
+        let lan = Lan {
+            role: LanRole::Guest(address.to_string()),
+            connected: false,
+            handshake: None,
+            listener: None,
+            socket: Arc::new(Socket(Mutex::new(None))),
+            events: unbounded(),
+        };
+
+        let address = address.to_string();
+        let socket = Arc::downgrade(&lan.socket);
+        let sender = lan.events.0.clone();
+        spawn(move || {
+            let connection = guest_handshake(&address);
+            run_connection(connection, &socket, &sender);
+        });
+        lan
+    }
+
+    /// Next thing that happened on the connection, accepting the guest first if hosting
+    pub(crate) fn poll(&mut self) -> Option<LanEvent> {
+        if let Some(listener) = &self.listener {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    // The port is freed for reconnecting, later guests are refused
+                    self.listener = None;
+                    let handshake = self.handshake.clone().unwrap();
+                    let socket = Arc::downgrade(&self.socket);
+                    let sender = self.events.0.clone();
+                    spawn(move || {
+                        let connection = host_handshake(stream, handshake);
+                        run_connection(connection, &socket, &sender);
+                    });
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => {
+                    self.listener = None;
+                    self.events
+                        .0
+                        .send(LanEvent::Disconnected(err.to_string()))
+                        .ok();
+                }
+            }
+        }
+
+        let event = self.events.1.try_recv().ok()?;
+        match &event {
+            LanEvent::Connected(_) => self.connected = true,
+            LanEvent::Disconnected(_) => self.connected = false,
+            _ => {}
+        }
+        Some(event)
+    }
+
+    /// Sends a line to the opponent, a dropped connection is noticed through [Lan::poll] instead
+    fn send(&self, line: &str) {
+        if let Some(mut stream) = self.socket.0.lock().unwrap().as_ref() {
+            writeln!(stream, "{}", line).ok();
+        }
+    }
+
+    /// Sends a move to the opponent, with the seconds it took if the game is timed
+    pub(crate) fn send_move(&self, mov: &str, think: Option<f64>) {
+        match think {
+            Some(think) => self.send(&format!("move {} {}", mov, think)),
+            None => self.send(&format!("move {}", mov)),
+        }
+    }
+
+    /// Sends the host's clock to the guest
+    pub(crate) fn send_clock(&self, clock: &Clock) {
+        self.send(&format!("clock {}", clock_text(Some(clock))));
+    }
+
+    /// Tells the guest `color` ran out of time
+    pub(crate) fn send_flag(&self, color: ChessColor) {
+        self.send(&format!("flag {:?}", color));
+    }
+
+    /// Sends a chat message to the opponent, on one line
+    pub(crate) fn send_chat(&self, text: &str) {
+        self.send(&format!("chat {}", text.replace(['\r', '\n'], " ")));
+    }
+
+    /// New connection to the same opponent, hosting `handshake` instead of the original game if given
+    pub(crate) fn reconnect(self, handshake: Option<Handshake>) -> io::Result<Lan> {
+        // Frees the port to listen on it again
+        drop(self.listener);
+        match self.role {
+            LanRole::Host(port) => Lan::host(port, handshake.or(self.handshake).unwrap()),
+            LanRole::Guest(address) => Ok(Lan::join(&address)),
+        }
+    }
+
+    /// Shown while waiting on the connection
+    pub(crate) fn status(&self) -> String {
+        match &self.role {
+            LanRole::Host(port) => format!("Waiting for an opponent on port {}", port),
+            LanRole::Guest(address) => format!("Connecting to {}", address),
+        }
+    }
+}
+
+/// Stream to write to, the buffered reader it was read from during the handshake, and the game
+type Connection = (TcpStream, BufReader<TcpStream>, Handshake);
+
+/// Host side of the handshake, checks the guest's hello then sends the game
+fn host_handshake(stream: TcpStream, handshake: Handshake) -> io::Result<Connection> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut hello = String::new();
+    reader.read_line(&mut hello)?;
+    if hello.trim() != HELLO {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "The opponent is running a different version",
+        ));
+    }
+
+    let mut writer = &stream;
+    for line in handshake.lines() {
+        writeln!(writer, "{}", line)?;
+    }
+    stream.set_read_timeout(None)?;
+    Ok((stream, reader, handshake))
+}
+
+/// Guest side of the handshake, connects and reads the game from the host
+fn guest_handshake(address: &str) -> io::Result<Connection> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "Couldn't find the host"))?;
+    let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+    stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+    let mut writer = &stream;
+    writeln!(writer, "{}", HELLO)?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut lines = vec![];
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "The host closed the connection",
+            ));
+        }
+        lines.push(line.trim().to_string());
+        if line.trim() == "ready" {
+            break;
+        }
+    }
+
+    let handshake =
+        Handshake::parse(&lines).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+    stream.set_read_timeout(None)?;
+    Ok((stream, reader, handshake))
+}
+
+/// Event for a line from the opponent, `None` if it isn't one this version knows
+fn parse_event(line: &str) -> Option<LanEvent> {
+    let (key, value) = line.split_once(' ')?;
+    match key {
+        "move" => {
+            let (mov, think) = value.split_once(' ').unwrap_or((value, ""));
+            Some(LanEvent::Move(mov.to_string(), think.parse().ok()))
+        }
+        "clock" => parse_clock(value).ok()?.map(LanEvent::Clock),
+        "flag" => parse_color(value).ok().map(LanEvent::Flag),
+        "chat" => Some(LanEvent::Chat(value.to_string())),
+        _ => None,
+    }
+}
+
+/// Stores the stream once connected, then forwards the opponent's moves until the connection drops
+/// - The socket is only held weakly, so dropping the [Lan] closes the connection and ends the thread
+/// - Sends can fail once the [Lan] is dropped, which is ignored since nothing is listening
+fn run_connection(
+    connection: io::Result<Connection>,
+    socket: &Weak<Socket>,
+    sender: &Sender<LanEvent>,
+) {
+    let (stream, reader, handshake) = match connection {
+        Ok(connection) => connection,
+        Err(err) => {
+            sender.send(LanEvent::Disconnected(err.to_string())).ok();
+            return;
+        }
+    };
+    let Some(socket) = socket.upgrade() else {
+        return;
+    };
+    *socket.0.lock().unwrap() = Some(stream);
+    drop(socket);
+    sender.send(LanEvent::Connected(handshake)).ok();
+
+    let mut reason = "The opponent left".to_string();
+    for line in reader.lines() {
+        match line {
+            Ok(line) => {
+                if let Some(event) = parse_event(line.trim()) {
+                    sender.send(event).ok();
+                }
+            }
+            Err(err) => {
+                reason = err.to_string();
+                break;
+            }
+        }
+    }
+    sender.send(LanEvent::Disconnected(reason)).ok();
+}
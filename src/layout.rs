@@ -0,0 +1,72 @@
+//! Sizes of the board, margins, and side panel, fitted to the window as it's resized
+//!
+//! Sizes are in pixels over [Settings::ui_scale](crate::settings::Settings::ui_scale), so the scale grows text and
+//! buttons along with everything else
+
+use std::ptr::addr_of_mut;
+
+use crate::conf::{HEIGHT, WIDTH};
+use crate::ternary;
+
+/// Space around the board, and between it and the side panel
+const MARGIN: f32 = 16.0;
+/// [MARGIN] in the compact layout, for small screens
+const COMPACT_MARGIN: f32 = 6.0;
+/// Narrowest the side panel gets, past that it's a quarter of the board's width
+const PANEL_WIDTH: f32 = 125.0;
+/// Smallest squares get, in a window too small for the board it's cut off instead
+const MIN_SQUARE_SIZE: f32 = 20.0;
+/// Tabs above the board, shown while more than one game is open
+pub(crate) const TAB_BAR_HEIGHT: f32 = 24.0;
+
+static mut LAYOUT: Option<Layout> = None;
+pub(crate) fn layout() -> &'static mut Layout {
+    unsafe {
+        (*addr_of_mut!(LAYOUT))
+            .get_or_insert_with(|| Layout::fit(WIDTH as f32, HEIGHT as f32, false, false))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Layout {
+    pub(crate) square_size: f32,
+    pub(crate) margin: f32,
+    /// Top of the board and side panel, under the tab bar if it's shown
+    pub(crate) top: f32,
+    pub(crate) panel_width: f32,
+    /// Size of the window
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+impl Layout {
+    /// Biggest board that fits a `width` by `height` window along with the side panel
+    /// - `compact` shrinks the margins, leaving more of a small screen to the board
+    /// - `tabs` makes room for the tab bar
+    pub(crate) fn fit(width: f32, height: f32, compact: bool, tabs: bool) -> Layout {
+        let margin = ternary!(compact, COMPACT_MARGIN, MARGIN);
+        let top = ternary!(tabs, margin * 1.5 + TAB_BAR_HEIGHT, margin);
+        let across = width - margin * 3.0;
+        let mut square_size = (across - PANEL_WIDTH) / 8.0;
+        if square_size * 2.0 > PANEL_WIDTH {
+            // The panel is a quarter of the board's width, taking two of the ten squares across
+            square_size = across / 10.0;
+        }
+        let square_size = square_size
+            .min((height - top - margin) / 8.0)
+            .max(MIN_SQUARE_SIZE);
+
+        Layout {
+            square_size,
+            margin,
+            top,
+            panel_width: (square_size * 2.0).max(PANEL_WIDTH),
+            width,
+            height,
+        }
+    }
+
+    /// Left of the side panel
+    pub(crate) fn panel_x(&self) -> f32 {
+        self.square_size * 8.0 + self.margin * 2.0
+    }
+}
@@ -0,0 +1,408 @@
+//! A fully rust chess engine + AI + GUI written in Rust and Macroquad (a graphics library)
+//!
+//! AI is a minimax search with alpha-beta pruning, move-ordering, and Tomasz Michniewski's simplified evaluation function
+//!
+//! - Release hosted at <https://chess.jamesalin.com>
+//! - Source here <https://github.com/jameslinimk/chess-ai>
+//!
+//! # Building
+//!
+//! Clone and build using `cargo build`, or `cargo build --no-default-features` for just the engine without the GUI
+//!
+//! See `src/bindings.rs` for using the engine from JS, and `src/ffi.rs` for using it from C
+
+#![cfg_attr(feature = "gui", feature(future_join))]
+
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use autosave::GameRecord;
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use colored::{Color, Colorize};
+#[cfg(feature = "gui")]
+use conf::{COLOR_BACKGROUND, HEIGHT, WIDTH};
+#[cfg(feature = "gui")]
+use frame_limit::FrameLimiter;
+#[cfg(feature = "gui")]
+use game::Game;
+#[cfg(feature = "gui")]
+use macroquad::prelude::{next_frame, Conf};
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use macroquad::prelude::{is_quit_requested, prevent_quit};
+#[cfg(feature = "gui")]
+use macroquad::text::Font;
+#[cfg(feature = "gui")]
+use macroquad::window::clear_background;
+#[cfg(feature = "gui")]
+use menu::Overlay;
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use settings::{settings, Settings, SETTINGS_PATH};
+#[cfg(feature = "gui")]
+use tabs::Tabs;
+
+#[cfg(feature = "gui")]
+use crate::camera::camera;
+
+pub(crate) mod agent;
+pub(crate) mod agent_opens;
+#[cfg(feature = "gui")]
+pub(crate) mod analysis;
+#[cfg(feature = "gui")]
+pub(crate) mod assets;
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+pub(crate) mod autosave;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod batch;
+#[cfg(feature = "bench")]
+pub mod bench;
+#[cfg(feature = "bindings")]
+pub(crate) mod bindings;
+pub(crate) mod board;
+pub(crate) mod board_eval;
+pub(crate) mod board_extras;
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+pub(crate) mod broadcast;
+#[cfg(feature = "gui")]
+pub(crate) mod camera;
+#[cfg(feature = "gui")]
+pub(crate) mod clock;
+pub(crate) mod conf;
+#[cfg(any(feature = "gui", feature = "bindings"))]
+pub(crate) mod diff;
+#[cfg(feature = "gui")]
+pub(crate) mod dropped;
+pub(crate) mod endgame;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod engine;
+pub(crate) mod error;
+#[cfg(feature = "gui")]
+pub(crate) mod events;
+#[cfg(feature = "gui")]
+pub(crate) mod explorer;
+#[cfg(feature = "ffi")]
+pub(crate) mod ffi;
+#[cfg(feature = "gui")]
+pub(crate) mod frame_limit;
+#[cfg(test)]
+mod fuzz;
+#[cfg(feature = "gui")]
+pub(crate) mod game;
+#[cfg(feature = "gui")]
+pub(crate) mod handicap;
+#[cfg(feature = "gui")]
+pub(crate) mod keybinds;
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+pub(crate) mod lan;
+#[cfg(feature = "gui")]
+pub(crate) mod layout;
+#[cfg(feature = "gui")]
+pub(crate) mod log;
+#[cfg(feature = "gui")]
+pub(crate) mod mate;
+pub(crate) mod mcts;
+#[cfg(feature = "gui")]
+pub(crate) mod menu;
+pub(crate) mod move_cache;
+#[cfg(feature = "gui")]
+pub(crate) mod move_tree;
+pub(crate) mod nnue;
+#[cfg(all(test, feature = "oracle"))]
+mod oracle;
+pub(crate) mod pgn;
+pub(crate) mod pieces;
+pub(crate) mod platform;
+pub(crate) mod position;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod protocol;
+#[cfg(feature = "gui")]
+pub(crate) mod rating;
+#[cfg(feature = "gui")]
+pub(crate) mod repertoire;
+pub(crate) mod repro;
+#[cfg(feature = "gui")]
+pub(crate) mod review;
+#[cfg(feature = "gui")]
+pub(crate) mod rush;
+#[cfg(feature = "gui")]
+pub(crate) mod scheduler;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod selfplay;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod serve;
+pub(crate) mod settings;
+#[cfg(feature = "gui")]
+pub(crate) mod sound;
+#[cfg(feature = "gui")]
+pub(crate) mod stats;
+#[cfg(feature = "gui")]
+pub(crate) mod tabs;
+#[cfg(test)]
+mod tests;
+#[cfg(feature = "gui")]
+pub(crate) mod ui;
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+pub(crate) mod updates;
+pub(crate) mod util;
+pub(crate) mod variant;
+#[cfg(not(target_family = "wasm"))]
+pub(crate) mod xboard;
+
+#[cfg(all(feature = "gui", not(windows)))]
+fn config() -> Conf {
+    Conf {
+        window_title: "Chess AI".to_string(),
+        window_width: WIDTH,
+        window_height: HEIGHT,
+        window_resizable: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(all(feature = "gui", windows))]
+fn config() -> Conf {
+    use std::io::Cursor;
+
+    use image::io::Reader;
+    use macroquad::miniquad::conf::Icon;
+
+    macro_rules! image {
+        ($path: expr) => {
+            Reader::new(Cursor::new(include_bytes!($path)))
+                .with_guessed_format()
+                .unwrap()
+                .decode()
+                .unwrap()
+                .to_rgba8()
+                .to_vec()
+                .try_into()
+                .unwrap()
+        };
+    }
+
+    Conf {
+        window_title: "Chess AI".to_string(),
+        window_width: WIDTH,
+        window_height: HEIGHT,
+        window_resizable: true,
+        icon: Some(Icon {
+            small: image!("../assets/icon-16.png"),
+            medium: image!("../assets/icon-32.png"),
+            big: image!("../assets/icon-64.png"),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Font used throughout GUI, stored as static for accessibility
+#[cfg(feature = "gui")]
+static mut FONT: Option<Font> = None;
+
+/// Safely get [FONT] in safe code
+#[cfg(feature = "gui")]
+pub(crate) fn font() -> Font {
+    unsafe { FONT.unwrap() }
+}
+
+#[cfg(feature = "gui")]
+async fn load_images() {
+    use std::future::join;
+
+    use crate::assets::{load_audio, load_font, load_image};
+
+    let font = load_font(
+        "assets/fonts/DejaVuSansMono-Bold.ttf",
+        include_bytes!("../assets/fonts/DejaVuSansMono-Bold.ttf"),
+    );
+    match font {
+        Ok(font) => unsafe {
+            FONT = Some(font);
+        },
+        Err(_) => panic!("Failed to load font"),
+    };
+
+    macro_rules! load {
+        ($path: expr) => {
+            load_image(
+                concat!("assets/pieces/", $path),
+                include_bytes!(concat!("../assets/pieces/", $path)),
+            )
+        };
+    }
+
+    macro_rules! load_audio {
+        ($path: expr) => {
+            load_audio(
+                concat!("assets/sounds/", $path),
+                include_bytes!(concat!("../assets/sounds/", $path)),
+            )
+        };
+    }
+
+    let (.., move_sound, capture_sound, check_sound, end_sound, illegal_sound, tick_sound) = join!(
+        load!("black_pawn.png"),
+        load!("black_knight.png"),
+        load!("black_bishop.png"),
+        load!("black_rook.png"),
+        load!("black_queen.png"),
+        load!("black_king.png"),
+        load!("white_pawn.png"),
+        load!("white_knight.png"),
+        load!("white_bishop.png"),
+        load!("white_rook.png"),
+        load!("white_queen.png"),
+        load!("white_king.png"),
+        load_audio!("move.wav"),
+        load_audio!("capture.wav"),
+        load_audio!("check.wav"),
+        load_audio!("game_end.wav"),
+        load_audio!("illegal.wav"),
+        load_audio!("tick.wav"),
+    )
+    .await;
+    // The game is still playable without sound
+    for sound in [move_sound, capture_sound, check_sound, end_sound, illegal_sound, tick_sound] {
+        if let Err(err) = sound {
+            platform::warn!("{}", err);
+        }
+    }
+}
+
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+fn color_convert(color: macroquad::prelude::Color) -> Color {
+    Color::TrueColor {
+        r: (color.r * 255.0) as u8,
+        g: (color.g * 255.0) as u8,
+        b: (color.b * 255.0) as u8,
+    }
+}
+
+/// Seeds the random generator shared with macroquad from the system time
+#[cfg(not(target_family = "wasm"))]
+fn seed_random() {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use quad_rand::srand;
+
+    let start = SystemTime::now();
+    let seed = start.duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+    srand(seed);
+}
+
+/// Entry point of the `chess-ai` binary
+pub fn main() {
+    // Headless subcommands, which don't open a window
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.first().is_some_and(|arg| arg == "selfplay") {
+            seed_random();
+            selfplay::run_cli(&args[1..]);
+            return;
+        }
+        if args.first().is_some_and(|arg| arg == "eval") {
+            batch::run_cli(&args[1..]);
+            return;
+        }
+        if args.first().is_some_and(|arg| arg == "xboard") {
+            protocol::run(&mut xboard::XBoard::new());
+            return;
+        }
+        if args.first().is_some_and(|arg| arg == "--serve") {
+            serve::run_cli(&args[1..]);
+            return;
+        }
+        if args.first().is_some_and(|arg| arg == "--repro") {
+            repro::run_cli(&args[1..]);
+            return;
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    macroquad::Window::from_config(config(), run());
+    #[cfg(all(not(feature = "gui"), not(target_family = "wasm")))]
+    eprintln!(
+        "Built without the GUI, only `chess-ai selfplay`, `chess-ai eval`, `chess-ai xboard`, `chess-ai --serve`, and `chess-ai --repro` are available"
+    );
+}
+
+#[cfg(feature = "gui")]
+async fn run() {
+    #[cfg(not(target_family = "wasm"))]
+    {
+        use macroquad::prelude::GRAY;
+
+        use crate::conf::{COLOR_BLACK, COLOR_WHITE};
+
+        println!(
+            "{}\n{}\n{}\n{}",
+            "=====================================================".color(color_convert(GRAY)),
+            "░█████╗░██╗░░██╗███████╗░██████╗░██████╗  ░█████╗░██╗\n██╔══██╗██║░░██║██╔════╝██╔════╝██╔════╝  ██╔══██╗██║\n██║░░╚═╝███████║█████╗░░╚█████╗░╚█████╗░  ███████║██║\n██║░░██╗██╔══██║██╔══╝░░░╚═══██╗░╚═══██╗  ██╔══██║██║\n╚█████╔╝██║░░██║███████╗██████╔╝██████╔╝  ██║░░██║██║\n░╚════╝░╚═╝░░╚═╝╚══════╝╚═════╝░╚═════╝░  ╚═╝░░╚═╝╚═╝"
+                .color(color_convert(COLOR_WHITE)),
+            "     █▄▄ █▄█   ░░█ ▄▀█ █▀▄▀█ █▀▀ █▀   █░░ █ █▄░█\n     █▄█ ░█░   █▄█ █▀█ █░▀░█ ██▄ ▄█   █▄▄ █ █░▀█"
+                .color(color_convert(COLOR_BLACK)),
+            "=====================================================".color(color_convert(GRAY))
+        );
+
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        match Settings::load(SETTINGS_PATH) {
+            Ok(saved) => *settings() = saved,
+            Err(err) => platform::warn!("Couldn't load the settings: {}", err),
+        }
+        if !updates::opted_out(&args) {
+            updates::start_check();
+        }
+        assets::set_asset_dir(assets::asset_dir(&args));
+
+        seed_random();
+    }
+
+    load_images().await;
+    agent_opens::load_in_background(assets::asset_bytes(
+        agent_opens::BOOK_PATH,
+        agent_opens::BAKED_BOOK,
+    ));
+
+    let mut tabs = Tabs::new(Game::new());
+    tabs.game().overlay = Some(Overlay::main_menu(false));
+    // Closing the window is handled below, so the autosave is only left behind by a crash
+    #[cfg(not(target_family = "wasm"))]
+    {
+        prevent_quit();
+        // Read before the tabs are restored, which autosave as their moves are played again
+        let crashed = GameRecord::load(&autosave::autosave_path());
+        match Tabs::load(tabs::TABS_PATH) {
+            Ok(Some(saved)) => {
+                tabs = saved;
+                tabs.game().overlay = Some(Overlay::main_menu(true));
+            }
+            Ok(None) => {}
+            Err(err) => platform::warn!("Couldn't restore the tabs: {}", err),
+        }
+        if let Some(record) = crashed {
+            tabs.game().overlay = Some(Overlay::restore_game(record));
+        }
+    }
+    let mut limiter = FrameLimiter::new();
+    loop {
+        #[cfg(not(target_family = "wasm"))]
+        if is_quit_requested() {
+            autosave::remove_autosave();
+            if let Err(err) = tabs.save(tabs::TABS_PATH) {
+                platform::warn!("Couldn't save the tabs: {}", err);
+            }
+            if let Err(err) = settings().save(SETTINGS_PATH) {
+                platform::warn!("Couldn't save the settings: {}", err);
+            }
+            break;
+        }
+        if limiter.frame(tabs.busy()) {
+            clear_background(COLOR_BACKGROUND);
+            tabs.update();
+            sound::play_queued();
+            camera().update(tabs.shown());
+        }
+        #[cfg(not(target_family = "wasm"))]
+        if cfg!(debug_assertions) {
+            assets::hot_reload().await;
+        }
+        next_frame().await;
+    }
+}
@@ -0,0 +1,50 @@
+//! Recent engine output, book hits, and messages, kept for the log panel so they can be read without a terminal or
+//! the browser's console
+//!
+//! Everything logged with [info](crate::platform::info) and [warn](crate::platform::warn) lands here, from any thread
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use macroquad::prelude::Color;
+
+use crate::conf::{COLOR_LOG_BOOK, COLOR_LOG_ENGINE, COLOR_WHITE};
+
+/// Lines past this many are dropped, oldest first
+pub(crate) const LOG_LINES: usize = 200;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LogKind {
+    /// Depth, score, and PV of the agent's searches
+    Engine,
+    /// Moves the agent took from the opening book
+    Book,
+    System,
+}
+impl LogKind {
+    pub(crate) fn color(&self) -> Color {
+        match self {
+            LogKind::Engine => COLOR_LOG_ENGINE,
+            LogKind::Book => COLOR_LOG_BOOK,
+            LogKind::System => COLOR_WHITE,
+        }
+    }
+}
+
+lazy_static! {
+    static ref LOG: Mutex<VecDeque<(LogKind, String)>> = Mutex::new(VecDeque::new());
+}
+
+pub(crate) fn push_log(kind: LogKind, text: String) {
+    let mut log = LOG.lock().unwrap();
+    if log.len() == LOG_LINES {
+        log.pop_front();
+    }
+    log.push_back((kind, text));
+}
+
+/// Lines logged so far, oldest first
+pub(crate) fn log_lines() -> Vec<(LogKind, String)> {
+    LOG.lock().unwrap().iter().cloned().collect()
+}
@@ -0,0 +1,192 @@
+//! Mate-in-N solver, for composing and checking puzzles
+//!
+//! Searches every move of both sides, so it finds forced mates the agent's evaluation might miss but gets slow past a
+//! few moves. Only trying checks for the attacker is much faster, but misses mates that start with a quiet move
+
+use crate::agent::SearchLimits;
+use crate::board::{Board, BoardState};
+use crate::platform::now;
+use crate::ternary;
+use crate::util::Loc;
+
+/// Most moves [solve_mate] is asked to look for a mate in from the GUI
+pub(crate) const MAX_MATE_MOVES: u8 = 5;
+
+/// Moves from a position to mate
+type Line = Vec<(Loc, Loc)>;
+
+/// Shortest forced mate for the side to move in at most `n` of its moves, as the line with the defender's longest
+/// resistance found
+pub(crate) fn solve_mate(board: &Board, n: u8) -> Option<Line> {
+    MateSearch::new(board, n, false).finish()
+}
+
+/// The defender's move that holds out the longest, if every move still gets mated in `n`
+pub(crate) fn defend(board: &Board, n: u8) -> Option<Line> {
+    let mut search = MateSearch::new(board, n, false);
+    // Already as deep as it goes, so it doesn't look further once settled
+    search.depth = n;
+    search.stack = vec![Node::new(board.clone(), n, false)];
+    search.finish()
+}
+
+/// Position on [MateSearch]'s stack, with the moves it has left to try
+#[derive(Clone)]
+struct Node {
+    board: Board,
+    /// Moves the attacker has left to mate in
+    n: u8,
+    /// Wether it's the attacker's turn, who needs one move to mate, instead of the defender's, who needs every move
+    /// to get mated
+    attacking: bool,
+    moves: Vec<(Loc, Loc)>,
+    /// Index into [Node::moves] of the move being tried
+    next: usize,
+    /// Defender's move that held out the longest so far, with the rest of its line
+    longest: Option<Line>,
+}
+impl Node {
+    fn new(board: Board, n: u8, attacking: bool) -> Node {
+        let moves = board.moves(board.turn);
+        Node {
+            board,
+            n,
+            attacking,
+            moves,
+            next: 0,
+            longest: None,
+        }
+    }
+}
+
+/// Looks for a mate in at most some number of moves, one more move at a time
+/// - Searches with its own stack instead of recursing, so it can pause between positions and resume later, IE to
+///   search a slice each frame on the web, like [Minimax](crate::agent::Minimax)
+#[derive(Clone)]
+pub(crate) struct MateSearch {
+    board: Board,
+    /// Most moves to look for a mate in
+    n: u8,
+    /// Wether the attacker only tries moves that give check
+    checks_only: bool,
+    /// Moves the search from the root is looking for a mate in
+    depth: u8,
+    stack: Vec<Node>,
+}
+impl MateSearch {
+    pub(crate) fn new(board: &Board, n: u8, checks_only: bool) -> MateSearch {
+        MateSearch {
+            board: board.clone(),
+            n,
+            checks_only,
+            depth: 1,
+            // Nothing to search for a mate in none
+            stack: ternary!(n == 0, vec![], vec![Node::new(board.clone(), 1, true)]),
+        }
+    }
+
+    /// Searches until finished with no time limit
+    fn finish(&mut self) -> Option<Line> {
+        self.run(&SearchLimits::new(f64::INFINITY), f64::INFINITY)
+            .flatten()
+    }
+
+    /// Searches until finished, returning the mating line if there is one, or `None` if it paused at `pause_at` (from
+    /// [now]) first
+    /// - Gives up without a line once stopped
+    pub(crate) fn run(&mut self, limits: &SearchLimits, pause_at: f64) -> Option<Option<Line>> {
+        loop {
+            if limits.stopped() {
+                self.stack.clear();
+            }
+            let Some(node) = self.stack.last_mut() else {
+                return Some(None);
+            };
+            if node.next == node.moves.len() {
+                // Every attacking move failed, or every defense got mated
+                let line = ternary!(node.attacking, None, node.longest.take());
+                if let Some(found) = self.settle(line) {
+                    return Some(found);
+                }
+                continue;
+            }
+
+            let (from, to) = node.moves[node.next];
+            let mut after = node.board.clone();
+            after.move_piece(&from, &to, true);
+
+            let child = if node.attacking {
+                let defender = node.board.turn.other();
+                if after.state == BoardState::Checkmate(defender) {
+                    if let Some(found) = self.settle(Some(vec![(from, to)])) {
+                        return Some(found);
+                    }
+                    continue;
+                }
+                // The last move has to mate, and other moves that end the game don't lead to one
+                let quiet = self.checks_only && after.state != BoardState::Check(defender);
+                if node.n == 1 || after.is_over() || quiet {
+                    node.next += 1;
+                    continue;
+                }
+                Node::new(after, node.n - 1, false)
+            } else {
+                // Escaping into a draw, or winning
+                if after.is_over() {
+                    if let Some(found) = self.settle(None) {
+                        return Some(found);
+                    }
+                    continue;
+                }
+                Node::new(after, node.n, true)
+            };
+            self.stack.push(child);
+
+            if now() >= pause_at {
+                return None;
+            }
+        }
+    }
+
+    /// Pops the top position once it's known to be mated in `line`, or not mated if `None`, and hands that to the
+    /// positions before it, returns the search's result once the first position is settled
+    fn settle(&mut self, mut line: Option<Line>) -> Option<Option<Line>> {
+        loop {
+            self.stack.pop();
+            let Some(parent) = self.stack.last_mut() else {
+                // Not mated in this many moves, so look one move further
+                if line.is_none() && self.depth < self.n {
+                    self.depth += 1;
+                    self.stack
+                        .push(Node::new(self.board.clone(), self.depth, true));
+                    return None;
+                }
+                return Some(line);
+            };
+
+            let mov = parent.moves[parent.next];
+            match (parent.attacking, line) {
+                // One mating move is enough
+                (true, Some(rest)) => line = Some([vec![mov], rest].concat()),
+                (true, None) => {
+                    parent.next += 1;
+                    return None;
+                }
+                // One defense that holds is enough
+                (false, None) => line = None,
+                (false, Some(rest)) => {
+                    let rest = [vec![mov], rest].concat();
+                    if parent
+                        .longest
+                        .as_ref()
+                        .is_none_or(|longest| rest.len() > longest.len())
+                    {
+                        parent.longest = Some(rest);
+                    }
+                    parent.next += 1;
+                    return None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Monte Carlo tree search agent
+//!
+//! - Grows a tree of moves one position at a time, picking which to try next by how well they've done and how little
+//!   they've been tried (UCT)
+//! - New positions are scored by the static evaluation instead of random playouts, which rarely end in chess
+//! - Plays the move tried the most, once out of time or after [MAX_PLAYOUTS]
+
+use crate::agent::{AgentMessage, AgentResult, MovePicker, SearchInfo, SearchLimits};
+use crate::board::Board;
+use crate::platform::now;
+#[cfg(feature = "gui")]
+use crate::settings::Difficulty;
+use crate::util::Loc;
+use crate::{color_ternary, ternary};
+
+/// Positions added to the tree before it stops, even with time left
+pub(crate) const MAX_PLAYOUTS: u32 = 5000;
+/// How much less tried moves are favored over ones that have done well
+const EXPLORATION: f64 = 1.4;
+/// Centipawns a side has to be ahead by to be expected to score about 90%
+const SCORE_SCALE: f64 = 400.0;
+
+/// A position in the tree
+struct Node {
+    /// Move played to reach it, `None` for the root
+    mov: Option<(Loc, Loc)>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// Moves not in the tree yet
+    untried: Vec<(Loc, Loc)>,
+    visits: u32,
+    /// Total score, from `0.0` to `1.0` per visit, for the side that played [Node::mov]
+    wins: f64,
+}
+impl Node {
+    fn new(board: &Board, mov: Option<(Loc, Loc)>, parent: Option<usize>) -> Node {
+        Node {
+            mov,
+            parent,
+            children: vec![],
+            untried: ternary!(board.is_over(), vec![], board.moves(board.turn)),
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+
+    /// UCT score of the node, from the side picking between it and its siblings
+    fn uct(&self, parent_visits: u32) -> f64 {
+        self.wins / self.visits as f64
+            + EXPLORATION * ((parent_visits as f64).ln() / self.visits as f64).sqrt()
+    }
+}
+
+/// Expected score, from `0.0` to `1.0`, of white in a position scored `score`
+fn win_chance(score: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-score as f64 / SCORE_SCALE))
+}
+
+/// Score for white expected to score `chance`, the inverse of [win_chance]
+fn chance_score(chance: f64) -> i32 {
+    let chance = chance.clamp(0.001, 0.999);
+    (-SCORE_SCALE * (1.0 / chance - 1.0).log10()).round() as i32
+}
+
+/// The MCTS agent, see the module docs
+pub(crate) struct Mcts;
+impl MovePicker for Mcts {
+    fn best_move(&self, board: &Board, limits: &SearchLimits) -> AgentResult {
+        if board.is_over() {
+            return AgentResult::Picked((None, None));
+        }
+
+        let start = now();
+        let mut tree = vec![Node::new(board, None, None)];
+        let mut deepest = 0;
+        let mut playouts = 0;
+        while playouts < MAX_PLAYOUTS && now() - start < limits.max_time && !limits.stopped() {
+            // Follow the best UCT scores down to a position with moves left to try
+            let mut node = 0;
+            let mut test_board = board.clone();
+            let mut depth = 0;
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                let visits = tree[node].visits;
+                node = *tree[node]
+                    .children
+                    .iter()
+                    .max_by(|a, b| tree[**a].uct(visits).total_cmp(&tree[**b].uct(visits)))
+                    .unwrap();
+                let (from, to) = tree[node].mov.unwrap();
+                test_board.move_piece(&from, &to, false);
+                depth += 1;
+            }
+
+            if let Some((from, to)) = tree[node].untried.pop() {
+                test_board.move_piece(&from, &to, false);
+                tree.push(Node::new(&test_board, Some((from, to)), Some(node)));
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                node = child;
+                depth += 1;
+            }
+            deepest = deepest.max(depth);
+            playouts += 1;
+
+            // Each position is scored for the side that moved into it, which alternates back up the tree
+            let white = win_chance(test_board.score);
+            let mut mover = test_board.turn.other();
+            let mut next = Some(node);
+            while let Some(i) = next {
+                tree[i].visits += 1;
+                tree[i].wins += color_ternary!(mover, white, 1.0 - white);
+                mover = mover.other();
+                next = tree[i].parent;
+            }
+        }
+
+        // Most tried moves, down from the root
+        let most_tried = |node: usize| {
+            tree[node]
+                .children
+                .iter()
+                .copied()
+                .max_by_key(|i| tree[*i].visits)
+        };
+        let Some(best) = most_tried(0) else {
+            return AgentResult::Picked((None, None));
+        };
+        let chance = tree[best].wins / tree[best].visits as f64;
+        let mut pv = vec![];
+        let mut node = Some(best);
+        while let Some(i) = node {
+            pv.push(tree[i].mov.unwrap());
+            node = most_tried(i);
+        }
+
+        let info = SearchInfo {
+            depth: deepest.clamp(1, u8::MAX as usize) as u8,
+            score: chance_score(color_ternary!(board.turn, chance, 1.0 - chance)),
+            pv,
+            nodes: playouts as u64,
+            ..SearchInfo::default()
+        };
+        if let Some(progress) = &limits.progress {
+            progress.send(AgentMessage::from(info.clone())).ok();
+        }
+        AgentResult::Picked((tree[best].mov, Some(info)))
+    }
+
+    #[cfg(feature = "gui")]
+    fn rating(&self, _max_depth: Option<u8>, _difficulty: Difficulty) -> Option<f64> {
+        Some(900.0)
+    }
+}
@@ -0,0 +1,1148 @@
+//! Modal overlays drawn on top of the game, such as the pause and settings menus
+//!
+//! While an overlay is open, board input and the agent are paused
+
+#[cfg(not(target_family = "wasm"))]
+use std::collections::BTreeMap;
+
+use macroquad::input::{is_key_pressed, KeyCode};
+use macroquad::shapes::{draw_rectangle, draw_rectangle_lines};
+use macroquad::text::{draw_text_ex, measure_text, TextParams};
+
+#[cfg(not(target_family = "wasm"))]
+use crate::autosave::GameRecord;
+use crate::board_eval::{pst_weights, EvalTerms, PIECES};
+use crate::conf::{CENTER_HEIGHT, CENTER_WIDTH, COLOR_OVERLAY, COLOR_WHITE};
+use crate::dropped::{Dropped, CHOOSER_ROWS};
+use crate::game::GameOptions;
+use crate::keybinds::help_lines;
+use crate::layout::layout;
+use crate::pieces::piece::PieceNames;
+#[cfg(not(target_family = "wasm"))]
+use crate::rush::RUSH_MINUTES;
+use crate::settings::settings;
+#[cfg(not(target_family = "wasm"))]
+use crate::stats::suggested_opponent;
+use crate::ui::Stack;
+use crate::util::{Button, Loc, Slider, SliderRange, TextInput, Toggle};
+#[cfg(not(target_family = "wasm"))]
+use crate::{broadcast, lan};
+use crate::{font, ternary};
+
+const MENU_BUTTON_WIDTH: f32 = 200.0;
+const MENU_BUTTON_HEIGHT: f32 = 40.0;
+const TITLE_FONT_SIZE: u16 = 30;
+const BODY_FONT_SIZE: u16 = 15;
+const BODY_LINE_HEIGHT: f32 = 20.0;
+const TEXT_INPUT_WIDTH: f32 = 400.0;
+/// Most lines of the search tree shown, the rest are counted on the last line
+const SEARCH_TREE_ROWS: usize = 17;
+/// Keybinds and mouse gestures listed on each page of the help
+const HELP_PAGE_LINES: usize = 16;
+
+/// [Settings::volume](crate::settings::Settings::volume) slider
+const VOLUME_RANGE: SliderRange = SliderRange {
+    min: 0.0,
+    max: 1.0,
+    step: 0.05,
+    format: |volume| format!("{:.0}%", volume * 100.0),
+};
+
+/// [Settings::animation_speed](crate::settings::Settings::animation_speed) slider
+const ANIMATION_SPEED_RANGE: SliderRange = SliderRange {
+    min: 0.25,
+    max: 3.0,
+    step: 0.25,
+    format: |speed| format!("{:.2}x", speed),
+};
+
+/// Piece-square table weights in the table editor
+const WEIGHT_RANGE: SliderRange = SliderRange {
+    min: 0.0,
+    max: 2.0,
+    step: 0.1,
+    format: |weight| format!("{:.1}x", weight),
+};
+
+/// What a row of a [Menu] is, before it's placed
+pub(crate) enum Row {
+    Button(String),
+    /// Label and wether it's on
+    Toggle(String, bool),
+    /// Label, range, and value
+    Slider(String, SliderRange, f32),
+}
+
+/// A placed row of a [Menu]
+#[derive(Clone)]
+enum Widget {
+    Button(Button),
+    Toggle(Toggle),
+    Slider(Slider),
+}
+impl Widget {
+    fn rect(&self) -> (f32, f32, f32, f32) {
+        match self {
+            Widget::Button(button) => button.rect(),
+            Widget::Toggle(toggle) => toggle.rect(),
+            Widget::Slider(slider) => slider.rect(),
+        }
+    }
+}
+
+/// Row of `rows` the keyboard focus moves to from `focus`, `1` being down, wrapping around the ends
+/// - Down starts at the top and up at the bottom when nothing had it
+pub(crate) fn move_focus(focus: Option<usize>, rows: usize, step: isize) -> usize {
+    match focus {
+        Some(i) => (i as isize + step).rem_euclid(rows as isize) as usize,
+        None => ternary!(step > 0, 0, rows - 1),
+    }
+}
+
+/// Darkens the whole screen under an overlay
+fn draw_backdrop() {
+    // Drawn centered on the middle of the default window, see [Camera::center_on](crate::camera::Camera::center_on)
+    let (width, height) = (layout().width, layout().height);
+    draw_rectangle(
+        CENTER_WIDTH as f32 - width / 2.0,
+        CENTER_HEIGHT as f32 - height / 2.0,
+        width,
+        height,
+        COLOR_OVERLAY,
+    );
+}
+
+fn title_params() -> TextParams {
+    TextParams {
+        font_size: TITLE_FONT_SIZE,
+        font_scale: 1.0,
+        color: COLOR_WHITE,
+        font: font(),
+        ..Default::default()
+    }
+}
+
+/// Title of an overlay, centered with its baseline on `y`
+fn draw_title(title: &str, y: f32) {
+    let params = title_params();
+    let dims = measure_text(title, Some(params.font), params.font_size, 1.0);
+    draw_text_ex(title, CENTER_WIDTH as f32 - dims.width / 2.0, y, params);
+}
+
+/// Outline around the row with the keyboard focus
+fn draw_focus((x, y, w, h): (f32, f32, f32, f32)) {
+    draw_rectangle_lines(x - 2.0, y - 2.0, w + 4.0, h + 4.0, 2.0, COLOR_WHITE);
+}
+
+/// Title and buttons of the main menu, laid out as a [Stack] every frame instead of placed once like a [Menu]
+#[derive(Clone)]
+pub(crate) struct MainMenu {
+    items: Vec<(String, MainMenuItem)>,
+    /// Row with the keyboard focus, the same as in a [Menu]
+    focus: Option<usize>,
+}
+impl MainMenu {
+    /// Draws the menu over the screen, returning the item clicked or pressed this frame
+    pub(crate) fn update(&mut self) -> Option<MainMenuItem> {
+        draw_backdrop();
+
+        let rows = self.items.len();
+        if is_key_pressed(KeyCode::Down) {
+            self.focus = Some(move_focus(self.focus, rows, 1));
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.focus = Some(move_focus(self.focus, rows, -1));
+        }
+        let pressed = is_key_pressed(KeyCode::Enter)
+            || is_key_pressed(KeyCode::KpEnter)
+            || is_key_pressed(KeyCode::Space);
+        let mut clicked = self
+            .focus
+            .filter(|_| pressed)
+            .map(|focus| self.items[focus].1);
+
+        let gap = layout().margin / 2.0;
+        let height = TITLE_FONT_SIZE as f32 + (MENU_BUTTON_HEIGHT + gap) * rows as f32;
+        let mut stack = Stack::down(
+            CENTER_WIDTH as f32 - MENU_BUTTON_WIDTH / 2.0,
+            CENTER_HEIGHT as f32 - height / 2.0,
+            MENU_BUTTON_WIDTH,
+            gap,
+        );
+        let (_, y, _, h) = stack.place(TITLE_FONT_SIZE as f32);
+        draw_title("Chess AI", y + h);
+
+        for (i, (label, item)) in self.items.iter().enumerate() {
+            let (x, y, w, h) = stack.place(MENU_BUTTON_HEIGHT);
+            let mut button = Button::new(x, y, w, h, label);
+            if button.update() {
+                clicked = Some(*item);
+            }
+            button.draw();
+            if self.focus == Some(i) {
+                draw_focus(button.rect());
+            }
+        }
+        clicked
+    }
+}
+
+/// A titled, vertical list of buttons centered on the screen
+/// - Up and down move a keyboard focus between the rows, which enter or space presses, and left and right slide
+#[derive(Clone)]
+pub(crate) struct Menu<T: Copy> {
+    title: &'static str,
+    /// Lines of text between the title and the buttons
+    body: Vec<String>,
+    /// Y of the first button
+    top: f32,
+    buttons: Vec<(Widget, T)>,
+    /// Row with the keyboard focus, `None` until up or down is pressed
+    focus: Option<usize>,
+    /// Wether the keys move the focus, off for menus with a text box since it takes the keys
+    keys: bool,
+}
+impl<T: Copy> Menu<T> {
+    pub(crate) fn new(title: &'static str, items: &[(String, T)]) -> Menu<T> {
+        Menu::with_body(title, vec![], items)
+    }
+
+    /// Menu with lines of text above the buttons
+    pub(crate) fn with_body(
+        title: &'static str,
+        body: Vec<String>,
+        items: &[(String, T)],
+    ) -> Menu<T> {
+        let rows = items
+            .iter()
+            .map(|(label, value)| (Row::Button(label.clone()), *value))
+            .collect();
+        Menu::with_rows(title, body, rows)
+    }
+
+    /// Menu with toggles and sliders as well as buttons
+    pub(crate) fn with_rows(
+        title: &'static str,
+        body: Vec<String>,
+        rows: Vec<(Row, T)>,
+    ) -> Menu<T> {
+        let body_height = BODY_LINE_HEIGHT * body.len() as f32;
+        // Rows shrink to leave room for the title when there are lots of them
+        let step = (MENU_BUTTON_HEIGHT + layout().margin / 2.0)
+            .min((layout().height - layout().margin * 4.0 - body_height) / rows.len() as f32);
+        let total_height = body_height + step * rows.len() as f32;
+        let top = CENTER_HEIGHT as f32 - total_height / 2.0 + layout().margin + body_height;
+
+        let buttons = rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, (row, value))| {
+                let (x, y, w, h) = (
+                    CENTER_WIDTH as f32 - MENU_BUTTON_WIDTH / 2.0,
+                    top + step * i as f32,
+                    MENU_BUTTON_WIDTH,
+                    step - layout().margin / 2.0,
+                );
+                let widget = match row {
+                    Row::Button(label) => Widget::Button(Button::new(x, y, w, h, &label)),
+                    Row::Toggle(label, on) => Widget::Toggle(Toggle::new(x, y, w, h, &label, on)),
+                    Row::Slider(label, range, v) => {
+                        Widget::Slider(Slider::new(x, y, w, h, &label, range, v))
+                    }
+                };
+                (widget, value)
+            })
+            .collect();
+
+        Menu {
+            title,
+            body,
+            top,
+            buttons,
+            focus: None,
+            keys: true,
+        }
+    }
+
+    /// Y of the first line of the body
+    pub(crate) fn body_top(&self) -> f32 {
+        self.top - BODY_LINE_HEIGHT * self.body.len() as f32
+    }
+
+    /// Replaces the lines of text, which should be as many as before since the buttons don't move
+    pub(crate) fn set_body(&mut self, body: Vec<String>) {
+        self.body = body;
+    }
+
+    /// Value of the slider for `item`, `None` if it isn't one
+    pub(crate) fn slider_value(&self, item: T) -> Option<f32>
+    where
+        T: PartialEq,
+    {
+        self.buttons
+            .iter()
+            .find_map(|(widget, value)| match widget {
+                Widget::Slider(slider) if *value == item => Some(slider.value),
+                _ => None,
+            })
+    }
+
+    /// Moves the keyboard focus, returning the value of the focused row if it was pressed or slid
+    fn update_keys(&mut self) -> Option<T> {
+        let rows = self.buttons.len();
+        if !self.keys || rows == 0 {
+            return None;
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.focus = Some(move_focus(self.focus, rows, 1));
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.focus = Some(move_focus(self.focus, rows, -1));
+        }
+
+        let (widget, value) = &mut self.buttons[self.focus?];
+        let pressed = is_key_pressed(KeyCode::Enter)
+            || is_key_pressed(KeyCode::KpEnter)
+            || is_key_pressed(KeyCode::Space);
+        let changed = match widget {
+            Widget::Button(_) => pressed,
+            Widget::Toggle(toggle) => {
+                if pressed {
+                    toggle.value = !toggle.value;
+                }
+                pressed
+            }
+            Widget::Slider(slider) => {
+                (is_key_pressed(KeyCode::Right) && slider.step(1.0))
+                    || (is_key_pressed(KeyCode::Left) && slider.step(-1.0))
+            }
+        };
+        changed.then_some(*value)
+    }
+
+    /// Draws the menu over the screen, returning the value of the row clicked, flipped, or slid this frame
+    pub(crate) fn update(&mut self) -> Option<T> {
+        draw_backdrop();
+        let body_top = self.body_top();
+        draw_title(self.title, body_top - layout().margin);
+
+        // Left aligned as a block, so columns line up
+        let params = TextParams {
+            font_size: BODY_FONT_SIZE,
+            ..title_params()
+        };
+        let width = self
+            .body
+            .iter()
+            .map(|line| measure_text(line, Some(params.font), params.font_size, 1.0).width)
+            .fold(0.0, f32::max);
+        for (i, line) in self.body.iter().enumerate() {
+            draw_text_ex(
+                line,
+                CENTER_WIDTH as f32 - width / 2.0,
+                body_top + BODY_LINE_HEIGHT * i as f32,
+                params,
+            );
+        }
+
+        let mut clicked = self.update_keys();
+        for (widget, value) in self.buttons.iter_mut() {
+            let changed = match widget {
+                Widget::Button(button) => button.update(),
+                Widget::Toggle(toggle) => toggle.update(),
+                Widget::Slider(slider) => slider.update(),
+            };
+            if changed {
+                clicked = Some(*value);
+            }
+            match widget {
+                Widget::Button(button) => button.draw(),
+                Widget::Toggle(toggle) => toggle.draw(),
+                Widget::Slider(slider) => slider.draw(),
+            }
+        }
+        if let Some((widget, _)) = self.focus.and_then(|i| self.buttons.get(i)) {
+            draw_focus(widget.rect());
+        }
+        clicked
+    }
+}
+
+/// Rows of the main menu, see [Overlay::main_menu]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MainMenuItem {
+    /// Back to the tabs left open last time
+    Continue,
+    NewGame,
+    /// Imports a PGN from the clipboard
+    LoadGame,
+    /// Drills the repertoire
+    #[cfg(not(target_family = "wasm"))]
+    Drill,
+    /// Picks the length of a [puzzle rush](crate::rush)
+    #[cfg(not(target_family = "wasm"))]
+    PuzzleRush,
+    Analysis,
+    Settings,
+    #[cfg(not(target_family = "wasm"))]
+    Quit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PauseItem {
+    Resume,
+    NewGame,
+    ImportPgn,
+    Settings,
+    #[cfg(not(target_family = "wasm"))]
+    Stats,
+    #[cfg(not(target_family = "wasm"))]
+    Network,
+    #[cfg(not(target_family = "wasm"))]
+    Quit,
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NetworkItem {
+    HostLan,
+    JoinLan,
+    Broadcast,
+    Watch,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SettingsItem {
+    Theme,
+    UiScale,
+    CompactLayout,
+    Sound,
+    Volume,
+    IllegalSound,
+    AutoFlip,
+    Animation,
+    AnimationSpeed,
+    AnnounceMoves,
+    LowPower,
+    Difficulty,
+    Analysis,
+    EngineArrows,
+    UserArrows,
+    HintArrows,
+    ThreatArrows,
+    /// Opens the arrow settings, which share this menu's items
+    Arrows,
+    /// Back from the arrow settings to the rest
+    ArrowsBack,
+    SearchTree,
+    Visibility,
+    SmartMoves,
+    Promotion,
+    PauseClock,
+    AgentClock,
+    ThreatWarning,
+    Resign,
+    #[cfg(not(target_family = "wasm"))]
+    CheckUpdates,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TableItem {
+    /// Slider for the weight of a piece's table
+    Weight(PieceNames),
+    /// Cycles which piece's table is drawn over the board
+    Show,
+    Export,
+    Reset,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NewGameItem {
+    Color,
+    PlayerAgent,
+    Opponent,
+    Difficulty,
+    Depth,
+    TimeControl,
+    Position,
+    Handicap,
+    Variant,
+    /// Switches between casual and rated games
+    Mode,
+    /// Sets the opponent to the one closest to the player's rating
+    #[cfg(not(target_family = "wasm"))]
+    Suggested,
+    Start,
+    Cancel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConfirmItem {
+    Yes,
+    No,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FindMateItem {
+    Search,
+    /// Only tries checks for the side mating, much faster but misses quiet first moves
+    ChecksOnly,
+    Cancel,
+}
+
+/// Text box with confirm and cancel buttons below it
+fn text_prompt(title: &'static str, confirm: &str, text: &str) -> (Menu<ConfirmItem>, TextInput) {
+    text_prompt_with(
+        title,
+        &[
+            (confirm.to_string(), ConfirmItem::Yes),
+            ("Cancel".to_string(), ConfirmItem::No),
+        ],
+        text,
+    )
+}
+
+/// Text box with `items` as buttons below it
+fn text_prompt_with<T: Copy>(
+    title: &'static str,
+    items: &[(String, T)],
+    text: &str,
+) -> (Menu<T>, TextInput) {
+    // Blank body lines leave room for the text box
+    let mut menu = Menu::with_body(title, vec![String::new(); 2], items);
+    menu.keys = false;
+    let input = TextInput::new(
+        CENTER_WIDTH as f32 - TEXT_INPUT_WIDTH / 2.0,
+        menu.body_top() - layout().margin,
+        TEXT_INPUT_WIDTH,
+        MENU_BUTTON_HEIGHT,
+        text,
+    );
+    (menu, input)
+}
+
+/// Overlays that can be open over the game
+#[derive(Clone)]
+pub(crate) enum Overlay {
+    /// Shown at startup, to pick what to do before playing
+    MainMenu(MainMenu),
+    Pause(Menu<PauseItem>),
+    Settings(Menu<SettingsItem>),
+    /// Options picked so far
+    NewGame(Menu<NewGameItem>, GameOptions),
+    /// Options to start the new game with
+    ConfirmReset(Menu<ConfirmItem>, GameOptions),
+    /// Picking which of the positions or games in a dropped file to load, `None` to load none
+    ChooseDropped(Menu<Option<usize>>, Vec<Dropped>),
+    /// Picking the piece a pawn promotes to, `None` to take the move back, with the (from, to) of the move
+    Promotion(Menu<Option<PieceNames>>, (Loc, Loc)),
+    /// Game autosaved before the last session crashed, asks wether to carry on with it
+    #[cfg(not(target_family = "wasm"))]
+    RestoreGame(Menu<ConfirmItem>, GameRecord),
+    /// Keybinds and mouse gestures, with a single button to close it
+    /// Page to go to, `None` to close the help
+    Help(Menu<Option<usize>>),
+    /// Editing the comment on the current position of a replay
+    Comment(Menu<ConfirmItem>, TextInput),
+    /// Typing a move to play, in SAN or as squares
+    TypeMove(Menu<ConfirmItem>, TextInput),
+    /// The agent offering a draw, accepting ends the game
+    DrawOffer(Menu<ConfirmItem>),
+    /// Typing how many moves to look for a mate in
+    FindMate(Menu<FindMateItem>, TextInput),
+    /// Whether a mate was found, and its line
+    MateResult(Menu<()>),
+    /// The player's rating and the openings of their games, see [PlayerStats](crate::stats::PlayerStats)
+    #[cfg(not(target_family = "wasm"))]
+    Stats(Menu<()>),
+    /// Picking how many minutes a puzzle rush lasts, `None` to cancel
+    #[cfg(not(target_family = "wasm"))]
+    PuzzleRush(Menu<Option<u32>>),
+    /// LAN games and broadcasts
+    #[cfg(not(target_family = "wasm"))]
+    Network(Menu<NetworkItem>),
+    /// Typing a chat message to the LAN opponent
+    #[cfg(not(target_family = "wasm"))]
+    Chat(Menu<ConfirmItem>, TextInput),
+    /// Typing the port to host a LAN game on
+    #[cfg(not(target_family = "wasm"))]
+    HostLan(Menu<ConfirmItem>, TextInput),
+    /// Typing the address of the host to join
+    #[cfg(not(target_family = "wasm"))]
+    JoinLan(Menu<ConfirmItem>, TextInput),
+    /// Typing the port to broadcast the game on
+    #[cfg(not(target_family = "wasm"))]
+    Broadcast(Menu<ConfirmItem>, TextInput),
+    /// Typing the address of a broadcast to watch
+    #[cfg(not(target_family = "wasm"))]
+    Watch(Menu<ConfirmItem>, TextInput),
+    /// Waiting on the LAN opponent to connect, with a button to cancel
+    #[cfg(not(target_family = "wasm"))]
+    LanWaiting(Menu<()>),
+    /// The LAN connection dropped, asks wether to reconnect or keep playing alone
+    #[cfg(not(target_family = "wasm"))]
+    LanDisconnected(Menu<ConfirmItem>),
+    /// The agent couldn't move, asks wether to take over its moves or restart
+    AgentError(Menu<ConfirmItem>),
+    /// Piece-square table weights, with the current position's score broken down above them
+    Tables(Menu<TableItem>),
+    /// How the root moves of the agent's last search were searched, see
+    /// [Settings::search_tree](crate::settings::Settings::search_tree)
+    SearchTree(Menu<()>),
+}
+impl Overlay {
+    /// Whether the overlay has a text box, so keybinds other than closing it are ignored
+    pub(crate) fn typing(&self) -> bool {
+        match self {
+            Overlay::Comment(..) | Overlay::TypeMove(..) | Overlay::FindMate(..) => true,
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::Chat(..)
+            | Overlay::HostLan(..)
+            | Overlay::JoinLan(..)
+            | Overlay::Broadcast(..)
+            | Overlay::Watch(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the overlay has to be answered before the game can go on, so it can't be closed with keybinds
+    pub(crate) fn blocking(&self) -> bool {
+        match self {
+            Overlay::AgentError(_) => true,
+            #[cfg(not(target_family = "wasm"))]
+            Overlay::LanDisconnected(_) => true,
+            _ => false,
+        }
+    }
+
+    /// What to do first, with `resumable` being wether there are tabs from last time to go back to
+    pub(crate) fn main_menu(resumable: bool) -> Overlay {
+        let mut items = vec![];
+        if resumable {
+            items.push(("Continue".to_string(), MainMenuItem::Continue));
+        }
+        items.extend([
+            ("New game".to_string(), MainMenuItem::NewGame),
+            ("Load game".to_string(), MainMenuItem::LoadGame),
+            #[cfg(not(target_family = "wasm"))]
+            ("Repertoire drill".to_string(), MainMenuItem::Drill),
+            #[cfg(not(target_family = "wasm"))]
+            ("Puzzle rush".to_string(), MainMenuItem::PuzzleRush),
+            ("Analysis".to_string(), MainMenuItem::Analysis),
+            ("Settings".to_string(), MainMenuItem::Settings),
+            #[cfg(not(target_family = "wasm"))]
+            ("Quit".to_string(), MainMenuItem::Quit),
+        ]);
+        Overlay::MainMenu(MainMenu { items, focus: None })
+    }
+
+    pub(crate) fn pause() -> Overlay {
+        Overlay::Pause(Menu::new(
+            "Paused",
+            &[
+                ("Resume".to_string(), PauseItem::Resume),
+                ("New game".to_string(), PauseItem::NewGame),
+                ("Import PGN".to_string(), PauseItem::ImportPgn),
+                ("Settings".to_string(), PauseItem::Settings),
+                #[cfg(not(target_family = "wasm"))]
+                ("Stats".to_string(), PauseItem::Stats),
+                #[cfg(not(target_family = "wasm"))]
+                ("Network".to_string(), PauseItem::Network),
+                #[cfg(not(target_family = "wasm"))]
+                ("Quit".to_string(), PauseItem::Quit),
+            ],
+        ))
+    }
+
+    /// Lists every keybind and mouse gesture from [crate::keybinds], [HELP_PAGE_LINES] at a time
+    pub(crate) fn help(page: usize) -> Overlay {
+        let lines = help_lines();
+        let pages = lines.chunks(HELP_PAGE_LINES).count();
+        let mut body = lines
+            .chunks(HELP_PAGE_LINES)
+            .nth(page)
+            .unwrap_or_default()
+            .to_vec();
+        // Padded so the buttons stay put on the last page
+        body.resize(HELP_PAGE_LINES, String::new());
+        body.push(format!("Page {} of {}", page + 1, pages));
+
+        Overlay::Help(Menu::with_body(
+            "Controls",
+            body,
+            &[
+                ("Next page".to_string(), Some((page + 1) % pages)),
+                ("Close".to_string(), None),
+            ],
+        ))
+    }
+
+    /// Text box for a comment, starting with the existing one
+    pub(crate) fn comment(text: &str) -> Overlay {
+        let (menu, input) = text_prompt("Comment", "Save", text);
+        Overlay::Comment(menu, input)
+    }
+
+    pub(crate) fn type_move() -> Overlay {
+        let (menu, input) = text_prompt("Type a move", "Play", "");
+        Overlay::TypeMove(menu, input)
+    }
+
+    pub(crate) fn find_mate() -> Overlay {
+        let (menu, input) = text_prompt_with(
+            "Find mate in",
+            &[
+                ("Search".to_string(), FindMateItem::Search),
+                ("Checks only".to_string(), FindMateItem::ChecksOnly),
+                ("Cancel".to_string(), FindMateItem::Cancel),
+            ],
+            "3",
+        );
+        Overlay::FindMate(menu, input)
+    }
+
+    pub(crate) fn mate_result(lines: Vec<String>) -> Overlay {
+        Overlay::MateResult(Menu::with_body(
+            "Find mate",
+            lines,
+            &[("Close".to_string(), ())],
+        ))
+    }
+
+    /// `lines` from [search_tree_lines](crate::pgn::search_tree_lines), cut down to [SEARCH_TREE_ROWS]
+    pub(crate) fn search_tree(mut lines: Vec<String>) -> Overlay {
+        if lines.len() > SEARCH_TREE_ROWS {
+            let more = lines.len() - SEARCH_TREE_ROWS + 1;
+            lines.truncate(SEARCH_TREE_ROWS - 1);
+            lines.push(format!(" ...and {} more", more));
+        }
+        Overlay::SearchTree(Menu::with_body(
+            "Search tree",
+            lines,
+            &[("Close".to_string(), ())],
+        ))
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn stats(rows: Vec<String>) -> Overlay {
+        Overlay::Stats(Menu::with_body("Stats", rows, &[("Close".to_string(), ())]))
+    }
+
+    /// Lengths to pick from, with `best` being the most puzzles solved in each, see
+    /// [PlayerStats::rush_best](crate::stats::PlayerStats::rush_best)
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn puzzle_rush(best: &BTreeMap<u32, u32>) -> Overlay {
+        let body = RUSH_MINUTES
+            .iter()
+            .map(|minutes| {
+                let solved = best.get(minutes).copied().unwrap_or(0);
+                format!("Best in {} minutes: {}", minutes, solved)
+            })
+            .collect();
+        let mut rows: Vec<_> = RUSH_MINUTES
+            .iter()
+            .map(|minutes| (format!("{} minutes", minutes), Some(*minutes)))
+            .collect();
+        rows.push(("Cancel".to_string(), None));
+        Overlay::PuzzleRush(Menu::with_body("Puzzle rush", body, &rows))
+    }
+
+    /// `broadcast_port` is the port of the running broadcast, if any
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn network(broadcast_port: Option<u16>) -> Overlay {
+        Overlay::Network(Menu::new(
+            "Network",
+            &[
+                ("Host LAN game".to_string(), NetworkItem::HostLan),
+                ("Join LAN game".to_string(), NetworkItem::JoinLan),
+                (
+                    match broadcast_port {
+                        Some(port) => format!("Broadcast: On ({})", port),
+                        None => "Broadcast: Off".to_string(),
+                    },
+                    NetworkItem::Broadcast,
+                ),
+                ("Watch broadcast".to_string(), NetworkItem::Watch),
+                ("Back".to_string(), NetworkItem::Back),
+            ],
+        ))
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn broadcast() -> Overlay {
+        let port = broadcast::DEFAULT_PORT.to_string();
+        let (menu, input) = text_prompt("Broadcast on port", "Start", &port);
+        Overlay::Broadcast(menu, input)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn watch() -> Overlay {
+        let address = format!("localhost:{}", broadcast::DEFAULT_PORT);
+        let (menu, input) = text_prompt("Watch broadcast at", "Watch", &address);
+        Overlay::Watch(menu, input)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn chat() -> Overlay {
+        let (menu, input) = text_prompt("Chat", "Send", "");
+        Overlay::Chat(menu, input)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn host_lan() -> Overlay {
+        let port = lan::DEFAULT_PORT.to_string();
+        let (menu, input) = text_prompt("Host on port", "Host", &port);
+        Overlay::HostLan(menu, input)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn join_lan() -> Overlay {
+        let address = format!("localhost:{}", lan::DEFAULT_PORT);
+        let (menu, input) = text_prompt("Join game at", "Join", &address);
+        Overlay::JoinLan(menu, input)
+    }
+
+    /// `status` is from [crate::lan::Lan::status]
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn lan_waiting(status: String) -> Overlay {
+        Overlay::LanWaiting(Menu::with_body(
+            "LAN game",
+            vec![status],
+            &[("Cancel".to_string(), ())],
+        ))
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn lan_disconnected(reason: &str) -> Overlay {
+        Overlay::LanDisconnected(Menu::with_body(
+            "Disconnected",
+            vec![reason.to_string()],
+            &[
+                ("Reconnect".to_string(), ConfirmItem::Yes),
+                ("Leave".to_string(), ConfirmItem::No),
+            ],
+        ))
+    }
+
+    /// `err` is from [AgentMessage::Error](crate::agent::AgentMessage::Error)
+    pub(crate) fn agent_error(err: &str) -> Overlay {
+        Overlay::AgentError(Menu::with_body(
+            "The agent stopped",
+            vec![err.to_string()],
+            &[
+                ("Take over".to_string(), ConfirmItem::Yes),
+                ("Restart".to_string(), ConfirmItem::No),
+            ],
+        ))
+    }
+
+    pub(crate) fn draw_offer() -> Overlay {
+        Overlay::DrawOffer(Menu::new(
+            "The agent offers a draw",
+            &[
+                ("Accept".to_string(), ConfirmItem::Yes),
+                ("Decline".to_string(), ConfirmItem::No),
+            ],
+        ))
+    }
+
+    /// Positions or games in the dropped file `name` to pick from, the first [CHOOSER_ROWS] of them
+    pub(crate) fn choose_dropped(name: &str, items: Vec<Dropped>) -> Overlay {
+        let mut body = vec![format!("{} has {} to pick from", name, items.len())];
+        if items.len() > CHOOSER_ROWS {
+            body.push(format!("Only the first {} are listed", CHOOSER_ROWS));
+        }
+        let mut rows: Vec<_> = items
+            .iter()
+            .take(CHOOSER_ROWS)
+            .enumerate()
+            .map(|(i, item)| (item.label(i), Some(i)))
+            .collect();
+        rows.push(("Cancel".to_string(), None));
+        Overlay::ChooseDropped(Menu::with_body("Load which?", body, &rows), items)
+    }
+
+    /// Piece to promote to for the move `from` to `to`
+    pub(crate) fn promotion(from: Loc, to: Loc) -> Overlay {
+        Overlay::Promotion(
+            Menu::new(
+                "Promote to",
+                &[
+                    ("Queen".to_string(), Some(PieceNames::Queen)),
+                    ("Rook".to_string(), Some(PieceNames::Rook)),
+                    ("Bishop".to_string(), Some(PieceNames::Bishop)),
+                    ("Knight".to_string(), Some(PieceNames::Knight)),
+                    ("Cancel".to_string(), None),
+                ],
+            ),
+            (from, to),
+        )
+    }
+
+    /// Asks before throwing away the current game
+    pub(crate) fn confirm_reset(options: GameOptions) -> Overlay {
+        Overlay::ConfirmReset(
+            Menu::new(
+                "Reset the game?",
+                &[
+                    ("Yes".to_string(), ConfirmItem::Yes),
+                    ("No".to_string(), ConfirmItem::No),
+                ],
+            ),
+            options,
+        )
+    }
+
+    /// Offers to restore the game autosaved before the last session crashed
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn restore_game(record: GameRecord) -> Overlay {
+        Overlay::RestoreGame(
+            Menu::with_body(
+                "Restore the unfinished game?",
+                vec![format!(
+                    "{} moves in, the last session didn't close cleanly",
+                    record.moves.len().div_ceil(2)
+                )],
+                &[
+                    ("Restore".to_string(), ConfirmItem::Yes),
+                    ("Discard".to_string(), ConfirmItem::No),
+                ],
+            ),
+            record,
+        )
+    }
+
+    /// New game dialog, with labels showing the currently picked options
+    pub(crate) fn new_game(options: GameOptions) -> Overlay {
+        let color = match options.color {
+            Some(color) => format!("{:?}", color),
+            None => "Random".to_string(),
+        };
+        let time_control = match options.time_control {
+            Some(tc) => tc.name(),
+            None => "Untimed".to_string(),
+        };
+        let position = ternary!(options.fen.is_some(), "FEN", "Standard");
+        let handicap = match options.handicap {
+            Some(handicap) => format!("{:?}", handicap),
+            None => "None".to_string(),
+        };
+        let depth = match options.max_depth {
+            Some(depth) => depth.to_string(),
+            None => "Any".to_string(),
+        };
+        let player = match options.player_agent {
+            Some(agent) => format!("{}", agent),
+            None => "Human".to_string(),
+        };
+
+        let menu = Menu::new(
+            "New game",
+            &[
+                (format!("Color: {}", color), NewGameItem::Color),
+                (format!("Player: {}", player), NewGameItem::PlayerAgent),
+                (
+                    format!("Opponent: {}", options.agent),
+                    NewGameItem::Opponent,
+                ),
+                (
+                    format!("Difficulty: {:?}", settings().difficulty),
+                    NewGameItem::Difficulty,
+                ),
+                (format!("Depth: {}", depth), NewGameItem::Depth),
+                (format!("Time: {}", time_control), NewGameItem::TimeControl),
+                (format!("Position: {}", position), NewGameItem::Position),
+                (format!("Odds: {}", handicap), NewGameItem::Handicap),
+                (
+                    format!("Variant: {}", options.variant.name()),
+                    NewGameItem::Variant,
+                ),
+                (
+                    format!("Mode: {}", ternary!(options.rated, "Rated", "Casual")),
+                    NewGameItem::Mode,
+                ),
+                #[cfg(not(target_family = "wasm"))]
+                (
+                    format!("Suggested: {}", suggested_opponent().name()),
+                    NewGameItem::Suggested,
+                ),
+                ("Start".to_string(), NewGameItem::Start),
+                ("Cancel".to_string(), NewGameItem::Cancel),
+            ],
+        );
+        Overlay::NewGame(menu, options)
+    }
+
+    /// Table editor, `terms` are the current position's and `showing` is the table drawn over the board
+    pub(crate) fn tables(terms: &EvalTerms, showing: Option<PieceNames>) -> Overlay {
+        let weights = pst_weights();
+        let mut rows: Vec<_> = PIECES
+            .iter()
+            .map(|piece| {
+                let label = format!("{:?} table", piece);
+                let slider = Row::Slider(label, WEIGHT_RANGE, weights[*piece as usize]);
+                (slider, TableItem::Weight(*piece))
+            })
+            .collect();
+        let showing = match showing {
+            Some(piece) => format!("{:?}", piece),
+            None => "Off".to_string(),
+        };
+        rows.extend([
+            (Row::Button(format!("Show: {}", showing)), TableItem::Show),
+            (Row::Button("Export".to_string()), TableItem::Export),
+            (Row::Button("Reset".to_string()), TableItem::Reset),
+            (Row::Button("Back".to_string()), TableItem::Back),
+        ]);
+
+        Overlay::Tables(Menu::with_rows("Piece-square tables", terms.lines(), rows))
+    }
+
+    /// Settings menu, with labels showing the current value of each setting
+    pub(crate) fn settings() -> Overlay {
+        let settings = settings();
+
+        Overlay::Settings(Menu::with_rows(
+            "Settings",
+            vec![],
+            vec![
+                (
+                    Row::Button(format!("Theme: {}", settings.theme_name())),
+                    SettingsItem::Theme,
+                ),
+                (
+                    Row::Button(format!("Scale: {:.2}x", settings.ui_scale)),
+                    SettingsItem::UiScale,
+                ),
+                (
+                    Row::Toggle("Compact layout".to_string(), settings.compact_layout),
+                    SettingsItem::CompactLayout,
+                ),
+                (
+                    Row::Toggle("Sound".to_string(), settings.sound),
+                    SettingsItem::Sound,
+                ),
+                (
+                    Row::Slider("Volume".to_string(), VOLUME_RANGE, settings.volume),
+                    SettingsItem::Volume,
+                ),
+                (
+                    Row::Toggle("Illegal move sound".to_string(), settings.illegal_sound),
+                    SettingsItem::IllegalSound,
+                ),
+                (
+                    Row::Toggle("Auto-flip board".to_string(), settings.auto_flip),
+                    SettingsItem::AutoFlip,
+                ),
+                (
+                    Row::Toggle("Animation".to_string(), settings.animation),
+                    SettingsItem::Animation,
+                ),
+                (
+                    Row::Slider(
+                        "Speed".to_string(),
+                        ANIMATION_SPEED_RANGE,
+                        settings.animation_speed,
+                    ),
+                    SettingsItem::AnimationSpeed,
+                ),
+                (
+                    Row::Toggle(
+                        "Announce special moves".to_string(),
+                        settings.announce_moves,
+                    ),
+                    SettingsItem::AnnounceMoves,
+                ),
+                (
+                    Row::Toggle("Save power when idle".to_string(), settings.low_power),
+                    SettingsItem::LowPower,
+                ),
+                (
+                    Row::Button(format!("Difficulty: {:?}", settings.difficulty)),
+                    SettingsItem::Difficulty,
+                ),
+                (
+                    Row::Toggle("Analysis".to_string(), settings.analysis),
+                    SettingsItem::Analysis,
+                ),
+                (Row::Button("Arrows".to_string()), SettingsItem::Arrows),
+                (
+                    Row::Toggle("Search tree (debug)".to_string(), settings.search_tree),
+                    SettingsItem::SearchTree,
+                ),
+                (
+                    Row::Button(format!("Pieces: {:?}", settings.visibility)),
+                    SettingsItem::Visibility,
+                ),
+                (
+                    Row::Toggle("Smart moves".to_string(), settings.smart_moves),
+                    SettingsItem::SmartMoves,
+                ),
+                (
+                    Row::Button(format!("Promotion: {}", settings.promotion.name())),
+                    SettingsItem::Promotion,
+                ),
+                (
+                    Row::Toggle("Pause clock in menus".to_string(), settings.pause_clock),
+                    SettingsItem::PauseClock,
+                ),
+                (
+                    Row::Toggle("Agent's clock runs".to_string(), settings.agent_clock),
+                    SettingsItem::AgentClock,
+                ),
+                (
+                    Row::Button(match settings.threat_warning {
+                        Some(gain) => format!("Warn of threats: +{:.1}", gain as f32 / 100.0),
+                        None => "Warn of threats: Off".to_string(),
+                    }),
+                    SettingsItem::ThreatWarning,
+                ),
+                (
+                    Row::Button(match settings.resign_score {
+                        Some(score) => format!("Resign at: -{:.1}", score as f32 / 100.0),
+                        None => "Resign at: Never".to_string(),
+                    }),
+                    SettingsItem::Resign,
+                ),
+                #[cfg(not(target_family = "wasm"))]
+                (
+                    Row::Toggle("Check for updates".to_string(), settings.check_updates),
+                    SettingsItem::CheckUpdates,
+                ),
+                (Row::Button("Back".to_string()), SettingsItem::Back),
+            ],
+        ))
+    }
+
+    /// Which layers of arrows are drawn, each drawn in its own color
+    pub(crate) fn arrow_settings() -> Overlay {
+        let settings = settings();
+
+        Overlay::Settings(Menu::with_rows(
+            "Arrows",
+            vec![],
+            vec![
+                (
+                    Row::Toggle("Your arrows".to_string(), settings.user_arrows),
+                    SettingsItem::UserArrows,
+                ),
+                (
+                    Row::Toggle("Hints".to_string(), settings.hint_arrows),
+                    SettingsItem::HintArrows,
+                ),
+                (
+                    Row::Toggle("Engine lines".to_string(), settings.engine_arrows),
+                    SettingsItem::EngineArrows,
+                ),
+                (
+                    Row::Toggle("Threats".to_string(), settings.threat_arrows),
+                    SettingsItem::ThreatArrows,
+                ),
+                (Row::Button("Back".to_string()), SettingsItem::ArrowsBack),
+            ],
+        ))
+    }
+}
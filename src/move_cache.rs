@@ -0,0 +1,98 @@
+//! Part of [Board], split for readability
+//!
+//! Legal moves of each piece, kept between moves so only the pieces a move could affect are regenerated
+
+use crate::board::{Board, ChessColor};
+use crate::color_ternary;
+use crate::pieces::piece::{Piece, PieceNames};
+use crate::util::{Loc, SquareSet};
+
+/// Pseudo-legal moves of pieces that didn't need a legality check, by square
+/// - Kings and pieces that need a legality check are never cached, since they depend on the whole board
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct MoveCache {
+    /// Bitset of squares the piece on each square can move to, `None` if it needs regenerating
+    targets: [Option<u64>; 64],
+    /// Bitset of squares whose contents the moves of the piece on each square depend on
+    depends: [u64; 64],
+}
+impl MoveCache {
+    pub(crate) fn new() -> MoveCache {
+        MoveCache {
+            targets: [None; 64],
+            depends: [0; 64],
+        }
+    }
+}
+
+/// Squares the moves of a piece depend on, besides its own
+fn depends(piece: &Piece, board: &Board) -> u64 {
+    // Attacks cover every square a piece looks at, except for where pawns can push
+    let mut depends = SquareSet::bit(&piece.pos) | piece.attacks(board).0;
+    if piece.name == PieceNames::Pawn {
+        let direction = color_ternary!(piece.color, -1, 1);
+        for loc in [
+            piece.pos.offset(0, direction),
+            piece.pos.offset(0, direction * 2),
+        ]
+        .iter()
+        .flatten()
+        {
+            depends |= SquareSet::bit(loc);
+        }
+    }
+
+    depends
+}
+
+impl Board {
+    /// Drops cached moves of pieces that could be affected by a move
+    /// - `changed` is the bitset of squares whose contents changed
+    /// - `en_passent` is whether an en passent target was set before or after, which every pawn depends on
+    pub(crate) fn invalidate_moves(&mut self, changed: u64, en_passent: bool) {
+        for i in 0..64 {
+            let pawn = self.raw[i / 8][i % 8].is_some_and(|p| p.name == PieceNames::Pawn);
+            if self.move_cache.depends[i] & changed != 0 || (en_passent && pawn) {
+                self.move_cache.targets[i] = None;
+            }
+        }
+    }
+
+    /// Legal moves of `color`, the same as [Board::moves] but reusing cached ones where possible
+    pub(crate) fn cached_moves(&mut self, color: ChessColor) -> Vec<(Loc, Loc)> {
+        let mut moves = vec![];
+        for y in 0..8 {
+            for x in 0..8 {
+                let Some(piece) = self.raw[y][x].filter(|piece| piece.color == color) else {
+                    continue;
+                };
+
+                let i = y * 8 + x;
+                if piece.name == PieceNames::King || piece.needs_legality_check(self) {
+                    self.move_cache.targets[i] = None;
+                    moves.extend(piece.moves(self).into_iter().map(|to| (piece.pos, to)));
+                    continue;
+                }
+
+                let targets = match self.move_cache.targets[i] {
+                    Some(targets) => targets,
+                    None => {
+                        let targets = piece
+                            .moves(self)
+                            .iter()
+                            .fold(0, |bits, to| bits | SquareSet::bit(to));
+                        self.move_cache.targets[i] = Some(targets);
+                        self.move_cache.depends[i] = depends(&piece, self);
+                        targets
+                    }
+                };
+                for to in 0..64 {
+                    if targets & (1 << to) != 0 {
+                        moves.push((piece.pos, Loc(to % 8, to / 8)));
+                    }
+                }
+            }
+        }
+        moves
+    }
+}
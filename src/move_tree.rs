@@ -0,0 +1,158 @@
+//! Tree of moves with variations and annotations, as imported from PGN or saved as a study
+//!
+//! Nodes are stored flat and referred to by index. `None` is used for the starting position, which isn't a node
+
+#[cfg(not(target_family = "wasm"))]
+use std::fs::{read_to_string, write};
+
+use derive_new::new;
+use serde::{Deserialize, Serialize};
+
+use crate::pgn::MoveStats;
+use crate::util::Loc;
+
+/// Where studies are saved to and opened from
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const STUDY_PATH: &str = "study.json";
+
+/// A game being stepped through, IE one imported from PGN
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Replay {
+    pub(crate) tree: MoveTree,
+    /// Node the board is at
+    pub(crate) current: Option<usize>,
+    /// Player names, for PGN export
+    pub(crate) white: String,
+    pub(crate) black: String,
+}
+
+/// A replay and its starting position, as saved to a study file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Study {
+    pub(crate) start_fen: String,
+    pub(crate) replay: Replay,
+}
+#[cfg(not(target_family = "wasm"))]
+impl Study {
+    pub(crate) fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write(path, json).map_err(|err| format!("Can't write {}: {}", path, err))
+    }
+
+    pub(crate) fn load(path: &str) -> Result<Study, String> {
+        let json = read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        serde_json::from_str(&json).map_err(|err| format!("Invalid study {}: {}", path, err))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct MoveNode {
+    pub(crate) mov: (Loc, Loc),
+    /// SAN of the move, stored since it needs the position before the move
+    pub(crate) san: String,
+    pub(crate) parent: Option<usize>,
+    /// Replies to this move, the first is the main line
+    pub(crate) children: Vec<usize>,
+    pub(crate) comment: Option<String>,
+    /// Arrows drawn on the position after the move
+    #[serde(default)]
+    pub(crate) arrows: Vec<(Loc, Loc)>,
+    /// Highlighted squares of the position after the move, sorted
+    #[serde(default)]
+    pub(crate) highlights: Vec<Loc>,
+    /// Think time, depth, and score if an agent played the move
+    #[serde(default)]
+    pub(crate) stats: Option<MoveStats>,
+}
+impl MoveNode {
+    /// Wether the node has a comment, arrows, or highlights
+    pub(crate) fn annotated(&self) -> bool {
+        self.comment.is_some() || !self.arrows.is_empty() || !self.highlights.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, new)]
+pub(crate) struct MoveTree {
+    #[new(value = "vec![]")]
+    pub(crate) nodes: Vec<MoveNode>,
+    /// First moves from the starting position, the first is the main line
+    #[new(value = "vec![]")]
+    pub(crate) roots: Vec<usize>,
+}
+impl MoveTree {
+    /// Moves that can follow `node`, the first is the main line
+    pub(crate) fn children(&self, node: Option<usize>) -> &[usize] {
+        match node {
+            Some(node) => &self.nodes[node].children,
+            None => &self.roots,
+        }
+    }
+
+    pub(crate) fn parent(&self, node: Option<usize>) -> Option<usize> {
+        node.and_then(|node| self.nodes[node].parent)
+    }
+
+    /// Alternatives to `node`, including itself
+    pub(crate) fn siblings(&self, node: usize) -> &[usize] {
+        self.children(self.nodes[node].parent)
+    }
+
+    /// Adds `mov` after `parent`, or returns the existing node if it's already there
+    pub(crate) fn add(&mut self, parent: Option<usize>, mov: (Loc, Loc), san: String) -> usize {
+        if let Some(existing) = self
+            .children(parent)
+            .iter()
+            .find(|child| self.nodes[**child].mov == mov)
+        {
+            return *existing;
+        }
+
+        let node = self.nodes.len();
+        self.nodes.push(MoveNode {
+            mov,
+            san,
+            parent,
+            children: vec![],
+            comment: None,
+            arrows: vec![],
+            highlights: vec![],
+            stats: None,
+        });
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(node),
+            None => self.roots.push(node),
+        }
+        node
+    }
+
+    /// Nodes from the starting position up to and including `node`
+    pub(crate) fn path(&self, node: Option<usize>) -> Vec<usize> {
+        let mut path = vec![];
+        let mut current = node;
+        while let Some(node) = current {
+            path.push(node);
+            current = self.nodes[node].parent;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Nodes of the whole line through `node`, continuing down the main line after it
+    pub(crate) fn line(&self, node: Option<usize>) -> Vec<usize> {
+        let mut line = self.path(node);
+        let mut current = node;
+        while let Some(next) = self.children(current).first() {
+            line.push(*next);
+            current = Some(*next);
+        }
+        line
+    }
+
+    /// Moves from the starting position up to and including `node`
+    pub(crate) fn moves(&self, node: Option<usize>) -> Vec<(Loc, Loc)> {
+        self.path(node)
+            .iter()
+            .map(|node| self.nodes[*node].mov)
+            .collect()
+    }
+}
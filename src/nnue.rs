@@ -0,0 +1,199 @@
+//! Small NNUE style evaluation network, used by the NNUE agent in place of [Board::score](crate::board::Board::score)
+//!
+//! # Network
+//!
+//! - 768 inputs, one for each (color, piece, square)
+//! - One hidden layer of [HIDDEN] neurons with clipped ReLU
+//! - A single output, the score for white in centipawns
+//!
+//! The hidden layer's pre-activations (the accumulator) are stored on the [Board](crate::board::Board) and updated
+//! as pieces are set and cleared, so evaluating a position doesn't need to look at the whole board.
+//!
+//! # File format
+//!
+//! Weights are loaded from [NETWORK_PATH] on native builds, all values little-endian. None are bundled, so without
+//! one the NNUE agent searches with the handcrafted evaluation and says so in the log, see [warn_if_missing]
+//!
+//! - `i16` feature weights, `[768][HIDDEN]`
+//! - `i16` hidden biases, `[HIDDEN]`
+//! - `i16` output weights, `[HIDDEN]`
+//! - `i32` output bias
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use lazy_static::lazy_static;
+
+use crate::board::ChessColor;
+use crate::pieces::piece::Piece;
+use crate::util::Loc;
+
+pub(crate) const HIDDEN: usize = 64;
+const INPUTS: usize = 768;
+/// Quantization of the hidden layer, activations are clipped to `0..=QA`
+const QA: i32 = 255;
+/// Quantization of the output weights
+const QB: i32 = 64;
+/// Scales the network output to centipawns
+const SCALE: i32 = 400;
+
+pub(crate) const NETWORK_PATH: &str = "assets/nnue.bin";
+
+lazy_static! {
+    /// The loaded network, `None` if there isn't one
+    pub(crate) static ref NETWORK: Option<Network> = load_network();
+}
+
+/// Warns once that the NNUE agent is falling back to the handcrafted evaluation, if there's no network
+pub(crate) fn warn_if_missing() {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    if NETWORK.is_none() && !WARNED.swap(true, Ordering::Relaxed) {
+        crate::platform::warn!(
+            "No network at {}, NNUE is using the handcrafted evaluation instead",
+            NETWORK_PATH
+        );
+    }
+}
+
+fn load_network() -> Option<Network> {
+    let network = read_network();
+
+    // Tests fall back to a seeded random network, so accumulator updates are always exercised
+    #[cfg(test)]
+    let network = network.or_else(|| Some(Network::random(0x2545F4914F6CDD1D)));
+
+    network
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn read_network() -> Option<Network> {
+    let bytes = std::fs::read(NETWORK_PATH).ok()?;
+    let network = Network::from_bytes(&bytes);
+    if network.is_none() {
+        crate::platform::warn!("Invalid network at {}", NETWORK_PATH);
+    }
+    network
+}
+
+#[cfg(target_family = "wasm")]
+fn read_network() -> Option<Network> {
+    None
+}
+
+pub(crate) struct Network {
+    feature_weights: Vec<[i16; HIDDEN]>,
+    hidden_bias: [i16; HIDDEN],
+    output_weights: [i16; HIDDEN],
+    output_bias: i32,
+}
+impl Network {
+    /// Parses a network in the format described in the [module docs](self)
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Network> {
+        if bytes.len() != (INPUTS * HIDDEN + HIDDEN * 2) * 2 + 4 {
+            return None;
+        }
+
+        let mut i16s = bytes
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]));
+        let mut next_layer = || {
+            let mut layer = [0; HIDDEN];
+            for value in layer.iter_mut() {
+                *value = i16s.next().unwrap();
+            }
+            layer
+        };
+
+        let feature_weights = (0..INPUTS).map(|_| next_layer()).collect();
+        let hidden_bias = next_layer();
+        let output_weights = next_layer();
+        let output_bias = i32::from_le_bytes(bytes[bytes.len() - 4..].try_into().unwrap());
+
+        Some(Network {
+            feature_weights,
+            hidden_bias,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    /// Small random weights from a xorshift generator
+    #[cfg(test)]
+    pub(crate) fn random(mut seed: u64) -> Network {
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % 64) as i16 - 32
+        };
+        let mut next_layer = || {
+            let mut layer = [0; HIDDEN];
+            for value in layer.iter_mut() {
+                *value = next();
+            }
+            layer
+        };
+
+        Network {
+            feature_weights: (0..INPUTS).map(|_| next_layer()).collect(),
+            hidden_bias: next_layer(),
+            output_weights: next_layer(),
+            output_bias: 0,
+        }
+    }
+
+    /// Score for white in centipawns
+    pub(crate) fn evaluate(&self, accumulator: &Accumulator) -> i32 {
+        let mut output = self.output_bias as i64;
+        for i in 0..HIDDEN {
+            let activation = (accumulator.0[i] + self.hidden_bias[i] as i32).clamp(0, QA);
+            output += activation as i64 * self.output_weights[i] as i64;
+        }
+        (output * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+}
+
+/// Index of the input for a piece on `loc`
+/// - Takes the square separately since `Piece.pos` is updated before the piece is moved
+fn feature(piece: &Piece, loc: &Loc) -> usize {
+    let color = match piece.color {
+        ChessColor::White => 0,
+        ChessColor::Black => 1,
+    };
+    color * 384 + piece.name as usize * 64 + loc.1 * 8 + loc.0
+}
+
+/// Sums of the feature weights of every piece on the board, without the hidden bias
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Accumulator(pub(crate) [i32; HIDDEN]);
+impl Accumulator {
+    pub(crate) fn new() -> Accumulator {
+        Accumulator([0; HIDDEN])
+    }
+
+    pub(crate) fn add(&mut self, network: &Network, piece: &Piece, loc: &Loc) {
+        let weights = &network.feature_weights[feature(piece, loc)];
+        for (value, weight) in self.0.iter_mut().zip(weights.iter()) {
+            *value += *weight as i32;
+        }
+    }
+
+    pub(crate) fn remove(&mut self, network: &Network, piece: &Piece, loc: &Loc) {
+        let weights = &network.feature_weights[feature(piece, loc)];
+        for (value, weight) in self.0.iter_mut().zip(weights.iter()) {
+            *value -= *weight as i32;
+        }
+    }
+
+    /// Builds the accumulator from scratch, to check the incremental updates against
+    #[cfg(test)]
+    pub(crate) fn refresh<'a>(
+        network: &Network,
+        pieces: impl Iterator<Item = &'a Piece>,
+    ) -> Accumulator {
+        let mut accumulator = Accumulator::new();
+        for piece in pieces {
+            accumulator.add(network, piece, &piece.pos);
+        }
+        accumulator
+    }
+}
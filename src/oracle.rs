@@ -0,0 +1,117 @@
+//! Differential tests against [shakmaty], run with `cargo test --features oracle`
+//!
+//! Plays random games and compares legal moves and game state with shakmaty on every position, reporting all
+//! mismatches at once. Promotions are only compared as queen promotions, since this crate always promotes to a queen
+
+use quad_rand::{srand, ChooseRandom};
+use shakmaty::fen::Fen;
+use shakmaty::{CastlingMode, Chess, Position, Role};
+
+use crate::board::{Board, BoardState};
+use crate::conf::DEFAULT_FEN;
+
+const GAMES: u64 = 100;
+const MAX_PLIES: usize = 80;
+
+/// Sorted legal moves for the side to move, as `e2e4`
+fn our_moves(board: &Board) -> Vec<String> {
+    let mut moves: Vec<_> = board
+        .moves(board.turn)
+        .iter()
+        .map(|(from, to)| format!("{}{}", from.as_notation(), to.as_notation()))
+        .collect();
+    moves.sort();
+    moves
+}
+
+fn shakmaty_moves(position: &Chess) -> Vec<String> {
+    let mut moves: Vec<_> = position
+        .legal_moves()
+        .iter()
+        .filter(|m| m.promotion().is_none_or(|role| role == Role::Queen))
+        .map(|m| {
+            let uci = m.to_uci(CastlingMode::Standard).to_string();
+            uci[..4].to_string()
+        })
+        .collect();
+    moves.sort();
+    moves
+}
+
+/// Differences between this crate and shakmaty for a position
+fn compare(board: &Board) -> Option<String> {
+    let fen = board.as_fen();
+    let position: Chess = match Fen::from_ascii(fen.as_bytes())
+        .map_err(|err| err.to_string())
+        .and_then(|f| {
+            f.into_position(CastlingMode::Standard)
+                .map_err(|err| err.to_string())
+        }) {
+        Ok(position) => position,
+        Err(err) => return Some(format!("{fen}: shakmaty rejected the FEN ({err})")),
+    };
+
+    let ours = our_moves(board);
+    let theirs = shakmaty_moves(&position);
+    if ours != theirs {
+        let extra: Vec<_> = ours.iter().filter(|m| !theirs.contains(m)).collect();
+        let missing: Vec<_> = theirs.iter().filter(|m| !ours.contains(m)).collect();
+        return Some(format!(
+            "{fen}: extra moves {extra:?}, missing moves {missing:?}"
+        ));
+    }
+
+    let state = match board.state {
+        BoardState::Checkmate(_) => "checkmate",
+        BoardState::Stalemate => "stalemate",
+        BoardState::Check(_) => "check",
+        _ => "normal",
+    };
+    let expected = if position.is_checkmate() {
+        "checkmate"
+    } else if position.is_stalemate() {
+        "stalemate"
+    } else if position.is_check() {
+        "check"
+    } else {
+        "normal"
+    };
+    // Draws by rule take priority over check here, but not in shakmaty
+    if state != expected && !matches!(board.state, BoardState::Draw(_)) {
+        return Some(format!("{fen}: state is {state}, expected {expected}"));
+    }
+
+    None
+}
+
+#[test]
+fn matches_shakmaty() {
+    let mut mismatches = vec![];
+    let mut positions = 0;
+
+    for seed in 0..GAMES {
+        srand(seed);
+        let mut board = Board::from_fen(DEFAULT_FEN);
+
+        for _ in 0..MAX_PLIES {
+            positions += 1;
+            if let Some(mismatch) = compare(&board) {
+                mismatches.push(mismatch);
+            }
+
+            if board.is_over() {
+                break;
+            }
+            let (from, to) = *board.moves(board.turn).choose().unwrap();
+            board.move_piece(&from, &to, true);
+        }
+    }
+
+    assert!(
+        mismatches.is_empty(),
+        "{} mismatches in {} positions:\n{}",
+        mismatches.len(),
+        positions,
+        mismatches.join("\n")
+    );
+}
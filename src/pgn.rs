@@ -0,0 +1,990 @@
+//! PGN import and export, with standard algebraic notation for moves
+
+#[cfg(feature = "gui")]
+use std::iter::Peekable;
+#[cfg(feature = "gui")]
+use std::vec::IntoIter;
+
+#[cfg(feature = "gui")]
+use derive_new::new;
+#[cfg(feature = "gui")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gui")]
+use crate::agent::{AgentId, SearchInfo, MAX};
+use crate::board::{Board, BoardState, ChessColor, DrawReason};
+#[cfg(feature = "gui")]
+use crate::board_eval::{display_score, eval_tag, piece_value, PIECES};
+#[cfg(feature = "gui")]
+use crate::color_ternary;
+#[cfg(feature = "gui")]
+use crate::conf::DEFAULT_FEN;
+use crate::error::ChessError;
+#[cfg(feature = "gui")]
+use crate::move_tree::MoveTree;
+#[cfg(feature = "gui")]
+use crate::pieces::piece::Piece;
+use crate::pieces::piece::PieceNames;
+#[cfg(feature = "gui")]
+use crate::position::describe_issues;
+use crate::ternary;
+use crate::util::Loc;
+#[cfg(feature = "gui")]
+use crate::variant::Variant;
+
+/// Longest line of movetext, as recommended by the PGN spec
+#[cfg(feature = "gui")]
+const LINE_LENGTH: usize = 79;
+
+/// (centipawns lost, NAG) for annotating moves, biggest first
+/// - `$4` is `??`, `$2` is `?`, and `$6` is `?!`
+#[cfg(feature = "gui")]
+const NAG_THRESHOLDS: [(i32, u8); 3] = [(300, 4), (150, 2), (70, 6)];
+
+/// Scores are clamped to this when comparing, so missing a mate counts as a big loss without overflowing
+#[cfg(feature = "gui")]
+const NAG_CLAMP: i32 = 1000;
+
+/// SAN of a legal move, IE `Nbd7`, `exd6`, `O-O`, `e8=Q#`
+/// - `board` is the position before the move
+pub(crate) fn san(board: &Board, from: &Loc, to: &Loc) -> String {
+    let piece = board.get(from).unwrap();
+    let capture = board.is_capture(from, to).is_some();
+    let destination = to.as_notation();
+
+    let mut san = match piece.name {
+        PieceNames::King if from.0.abs_diff(to.0) == 2 => {
+            if to.0 == 6 {
+                "O-O".to_string()
+            } else {
+                "O-O-O".to_string()
+            }
+        }
+        PieceNames::Pawn => {
+            let mut san = String::new();
+            if capture {
+                san.push_str(&from.as_notation()[..1]);
+                san.push('x');
+            }
+            san.push_str(&destination);
+            if to.1 == 0 || to.1 == 7 {
+                san.push_str(&format!("={}", piece_letter(board.promote_to)));
+            }
+            san
+        }
+        name => {
+            let mut san = piece_letter(name).to_string();
+
+            // Other pieces of the same type that can also move here
+            let others: Vec<_> = board
+                .moves(piece.color)
+                .into_iter()
+                .filter(|(other, other_to)| {
+                    other_to == to
+                        && other != from
+                        && board.get(other).is_some_and(|p| p.name == name)
+                })
+                .map(|(other, _)| other)
+                .collect();
+            if !others.is_empty() {
+                let notation = from.as_notation();
+                if others.iter().all(|other| other.0 != from.0) {
+                    san.push_str(&notation[..1]);
+                } else if others.iter().all(|other| other.1 != from.1) {
+                    san.push_str(&notation[1..]);
+                } else {
+                    san.push_str(&notation);
+                }
+            }
+
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&destination);
+            san
+        }
+    };
+
+    let mut after = board.clone();
+    after.move_piece(from, to, true);
+    match after.state {
+        BoardState::Checkmate(_) => san.push('#'),
+        BoardState::Check(_) | BoardState::ThreeChecks(_) => san.push('+'),
+        _ => {}
+    }
+
+    san
+}
+
+/// Move in coordinate notation, IE `g1f3`, with the piece a pawn promotes to, IE `e7e8q`
+/// - `board` is the position before the move
+pub(crate) fn coordinate(board: &Board, from: &Loc, to: &Loc) -> String {
+    let promotion = ternary!(
+        board.is_promotion(from, to),
+        piece_letter(board.promote_to)
+            .to_ascii_lowercase()
+            .to_string(),
+        "".to_string()
+    );
+    format!("{}{}{}", from.as_notation(), to.as_notation(), promotion)
+}
+
+/// SAN of each move in a line, stopping early at a move that isn't legal
+/// - `board` is the position before the first move
+pub(crate) fn san_line(board: &Board, moves: &[(Loc, Loc)]) -> Vec<String> {
+    let mut board = board.clone();
+    let mut sans = vec![];
+    for (from, to) in moves {
+        if board.is_over() || !board.moves(board.turn).contains(&(*from, *to)) {
+            break;
+        }
+        sans.push(san(&board, from, to));
+        board.move_piece(from, to, false);
+    }
+    sans
+}
+
+/// The last `plies` moves of a game as numbered movetext, IE `12... Nc6 13. Bb5 a6`
+/// - `start` is the position before the first move
+#[cfg(feature = "gui")]
+pub(crate) fn movetext_tail(start: &Board, moves: &[(Loc, Loc)], plies: usize) -> String {
+    let skip = moves.len().saturating_sub(plies);
+    let mut tokens = vec![];
+    for (i, san) in san_line(start, moves).into_iter().enumerate().skip(skip) {
+        let half = start.half_moves as usize + i;
+        let (number, black) = (half / 2 + 1, half % 2 == 1);
+        if !black {
+            tokens.push(format!("{}.", number));
+        } else if i == skip {
+            tokens.push(format!("{}...", number));
+        }
+        tokens.push(san);
+    }
+    tokens.join(" ")
+}
+
+/// Depth and score of a search, IE `depth 5, +0.3`, or `book` for book moves
+#[cfg(feature = "gui")]
+pub(crate) fn search_summary(info: &SearchInfo) -> String {
+    if info.score == MAX {
+        return "book".to_string();
+    }
+    format!(
+        "depth {}, {}",
+        info.depth,
+        display_score(info.score, ChessColor::White)
+    )
+}
+
+/// How long an agent thought about a move, and how deep it got
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, new)]
+pub(crate) struct MoveStats {
+    /// Milliseconds from starting to think to playing the move
+    pub(crate) millis: u32,
+    /// `0` for book moves
+    pub(crate) depth: u8,
+    /// Score for white, [MAX] for book moves
+    pub(crate) score: i32,
+}
+#[cfg(feature = "gui")]
+impl MoveStats {
+    /// Stats of a move from the last search of it, which took `seconds`
+    #[cfg(feature = "gui")]
+    pub(crate) fn from_info(info: &SearchInfo, seconds: f64) -> MoveStats {
+        MoveStats::new((seconds * 1000.0).round() as u32, info.depth, info.score)
+    }
+
+    /// Depth and score like [search_summary], IE `depth 5, +0.3`
+    #[cfg(feature = "gui")]
+    pub(crate) fn summary(&self) -> String {
+        search_summary(&SearchInfo {
+            depth: self.depth,
+            score: self.score,
+            ..Default::default()
+        })
+    }
+
+    /// Think time in seconds, IE `1.2s`
+    #[cfg(feature = "gui")]
+    pub(crate) fn time(&self) -> String {
+        format!("{:.1}s", self.millis as f64 / 1000.0)
+    }
+
+    /// Short version for the move list, IE `d5 +0.3 1.2s`
+    #[cfg(feature = "gui")]
+    pub(crate) fn short(&self) -> String {
+        format!(
+            "{} {}",
+            self.summary().replace("depth ", "d").replace(',', ""),
+            self.time()
+        )
+    }
+
+    /// Think time as the PGN `%emt` command, IE `[%emt 0:00:01.2]`
+    #[cfg(feature = "gui")]
+    pub(crate) fn emt_tag(&self) -> String {
+        let tenths = (self.millis + 50) / 100;
+        format!(
+            "[%emt {}:{:02}:{:02}.{}]",
+            tenths / 36000,
+            tenths / 600 % 60,
+            tenths / 10 % 60,
+            tenths % 10
+        )
+    }
+}
+
+/// Comment of a move with the stats of the agent that played it, IE `[%emt 0:00:01.2] depth 5, +0.3`
+/// - The depth and score are left out if the comment already has them, IE the search comments of spectated moves
+#[cfg(feature = "gui")]
+pub(crate) fn stats_comment(comment: Option<&str>, stats: Option<&MoveStats>) -> Option<String> {
+    let mut parts = vec![];
+    if let Some(stats) = stats {
+        parts.push(stats.emt_tag());
+        let summary = stats.summary();
+        if !comment.is_some_and(|comment| comment.contains(&summary)) {
+            parts.push(summary);
+        }
+    }
+    parts.extend(comment.map(str::to_string));
+    ternary!(parts.is_empty(), None, Some(parts.join(" ")))
+}
+
+/// Comment for a move an agent played, IE `Minimax: depth 5, +0.3, e4 e5 Nf3`
+/// - `board` is the position before the move
+#[cfg(feature = "gui")]
+pub(crate) fn search_comment(board: &Board, agent: AgentId, info: &SearchInfo) -> String {
+    format!(
+        "{}: {}, {}",
+        agent,
+        search_summary(info),
+        san_line(board, &info.pv).join(" ")
+    )
+}
+
+/// Short version of [search_comment] that fits the side panel, with the start of the PV on its own line
+#[cfg(feature = "gui")]
+pub(crate) fn panel_line(board: &Board, info: &SearchInfo) -> String {
+    let pv = san_line(board, &info.pv);
+    let pv = pv.iter().take(3).cloned().collect::<Vec<_>>().join(" ");
+    format!("{}\n{}", search_summary(info).replace("depth ", "d"), pv)
+}
+
+/// Line for the log panel, IE `depth 5, +0.4, 12034 nodes: e4 e5 Nf3`
+#[cfg(feature = "gui")]
+pub(crate) fn log_line(board: &Board, info: &SearchInfo) -> String {
+    if info.score == MAX {
+        return search_summary(info);
+    }
+    format!(
+        "{}, {} nodes: {}",
+        search_summary(info),
+        info.nodes,
+        san_line(board, &info.pv).join(" ")
+    )
+}
+
+/// How many pieces attack and defend the piece on `loc`, IE `["Attacked 2: N Q", "Defended 1: R"]`, see
+/// [Board::attackers]
+#[cfg(feature = "gui")]
+pub(crate) fn attack_lines(board: &Board, loc: &Loc) -> Option<[String; 2]> {
+    let color = board.get(loc)?.color;
+    let (defenders, attackers): (Vec<_>, Vec<_>) = board
+        .attackers(loc)
+        .into_iter()
+        .partition(|piece| piece.color == color);
+    let line = |label: &str, pieces: Vec<Piece>| {
+        let letters: Vec<_> = pieces
+            .iter()
+            .map(|piece| piece_letter(piece.name).to_string())
+            .collect();
+        match letters.is_empty() {
+            true => format!("{} 0", label),
+            false => format!("{} {}: {}", label, letters.len(), letters.join(" ")),
+        }
+    };
+    Some([line("Attacked", attackers), line("Defended", defenders)])
+}
+
+/// What taking the piece on `loc` wins, IE `"Taking the knight loses 2.2 pawns"`, see [Board::exchange]
+#[cfg(feature = "gui")]
+pub(crate) fn exchange_line(board: &Board, loc: &Loc) -> Option<String> {
+    let target = format!("{:?}", board.get(loc)?.name).to_lowercase();
+    let Some(gain) = board.exchange(loc) else {
+        return Some(format!("Nothing can take the {}", target));
+    };
+
+    let material = |value: i32| match PIECES.iter().find(|name| piece_value(name) == value) {
+        Some(name) => format!("a {}", format!("{:?}", name).to_lowercase()),
+        None => format!("{:.1} pawns", value as f32 / 100.0),
+    };
+    Some(match gain.signum() {
+        1 => format!("Taking the {} wins {}", target, material(gain)),
+        -1 => format!("Taking the {} loses {}", target, material(-gain)),
+        _ => format!("Taking the {} is an even trade", target),
+    })
+}
+
+/// [SearchInfo::tree] as text, a summary of the search followed by a line for each root move in the order they
+/// were searched, IE `Nf3 +0.3, 1520 nodes, 4/29 replies, cut by d5`
+/// - `*` marks the move picked, and `(table)` moves scored from the transposition table
+#[cfg(feature = "gui")]
+pub(crate) fn search_tree_lines(board: &Board, info: &SearchInfo) -> Vec<String> {
+    let nodes: u64 = info.tree.iter().map(|root| root.nodes).sum();
+    let mut lines = vec![format!(
+        "{}, {} root moves, {} nodes",
+        search_summary(info),
+        info.tree.len(),
+        nodes
+    )];
+    for root in info.tree.iter() {
+        let (from, to) = root.mov;
+        let mut line = format!(
+            "{}{} {}, {} nodes, {}/{} replies",
+            ternary!(info.pv.first() == Some(&root.mov), "*", " "),
+            san(board, &from, &to),
+            display_score(root.score, ChessColor::White),
+            root.nodes,
+            root.replies.0,
+            root.replies.1
+        );
+        if let Some((reply_from, reply_to)) = root.cutoff {
+            let mut after = board.clone();
+            after.move_piece(&from, &to, false);
+            line.push_str(&format!(", cut by {}", san(&after, &reply_from, &reply_to)));
+        }
+        if root.transposition {
+            line.push_str(" (table)");
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// NAG for a move by `color`, from the scores (for white) before and after it
+#[cfg(feature = "gui")]
+pub(crate) fn nag(before: i32, after: i32, color: ChessColor) -> Option<u8> {
+    let before = before.clamp(-NAG_CLAMP, NAG_CLAMP);
+    let after = after.clamp(-NAG_CLAMP, NAG_CLAMP);
+    let lost = color_ternary!(color, before - after, after - before);
+
+    NAG_THRESHOLDS
+        .iter()
+        .find(|(threshold, _)| lost >= *threshold)
+        .map(|(_, nag)| *nag)
+}
+
+/// Parses a SAN move for the side to move, IE `Nbd7`, `exd6`, `O-O`, `e8=Q#`
+/// - More lenient than [san], accepting extra disambiguation, `0-0`, and missing capture or check marks
+/// - Returns `None` if the move isn't legal or is ambiguous
+/// - Moves are only their squares, see [promotion_piece] for the piece a pawn promotes to
+pub(crate) fn from_san(board: &Board, san: &str) -> Option<(Loc, Loc)> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let moves = board.moves(board.turn);
+
+    let castle_x = match san {
+        "O-O" | "0-0" => Some(6),
+        "O-O-O" | "0-0-0" => Some(2),
+        _ => None,
+    };
+    if let Some(x) = castle_x {
+        return moves.into_iter().find(|(from, to)| {
+            board.get(from).unwrap().name == PieceNames::King
+                && from.0.abs_diff(to.0) == 2
+                && to.0 == x
+        });
+    }
+
+    // Promotion suffix, with or without `=`
+    let san = match san.find('=') {
+        Some(i) => &san[..i],
+        None if san.starts_with(|c: char| c.is_ascii_lowercase())
+            && san.ends_with(['Q', 'R', 'B', 'N']) =>
+        {
+            &san[..san.len() - 1]
+        }
+        None => san,
+    };
+
+    let (name, rest) = match san.chars().next()? {
+        'N' => (PieceNames::Knight, &san[1..]),
+        'B' => (PieceNames::Bishop, &san[1..]),
+        'R' => (PieceNames::Rook, &san[1..]),
+        'Q' => (PieceNames::Queen, &san[1..]),
+        'K' => (PieceNames::King, &san[1..]),
+        _ => (PieceNames::Pawn, san),
+    };
+    let rest: Vec<char> = rest.chars().filter(|c| *c != 'x' && *c != '-').collect();
+    if rest.len() < 2 {
+        return None;
+    }
+
+    let (hint, destination) = rest.split_at(rest.len() - 2);
+    if !matches!(destination, ['a'..='h', '1'..='8']) {
+        return None;
+    }
+    let to = Loc::from_notation(&destination.iter().collect::<String>());
+    let file = hint.iter().find(|c| c.is_ascii_lowercase()).copied();
+    let rank = hint.iter().find(|c| c.is_ascii_digit()).copied();
+
+    let mut candidates = moves.into_iter().filter(|(from, move_to)| {
+        let notation: Vec<char> = from.as_notation().chars().collect();
+        *move_to == to
+            && board.get(from).unwrap().name == name
+            && file.is_none_or(|file| notation[0] == file)
+            && rank.is_none_or(|rank| notation[1] == rank)
+    });
+    let found = candidates.next()?;
+    ternary!(candidates.next().is_none(), Some(found), None)
+}
+
+/// Parses a typed move, either SAN or the squares moved between, IE `Nf3` or `g1f3`
+pub(crate) fn parse_move(board: &Board, text: &str) -> Option<(Loc, Loc)> {
+    let text = text.trim();
+    if let Some(mov) = from_san(board, text) {
+        return Some(mov);
+    }
+
+    // Squares, with an optional promotion piece that's ignored
+    let squares = text.get(..4)?.to_ascii_lowercase();
+    if text.len() > 5 {
+        return None;
+    }
+    let mov = (
+        Loc::parse_notation(squares.get(..2)?).ok()?,
+        Loc::parse_notation(squares.get(2..)?).ok()?,
+    );
+    ternary!(board.moves(board.turn).contains(&mov), Some(mov), None)
+}
+
+/// Piece a move in SAN or coordinate notation promotes to, IE a knight for `e8=N` or `e7e8n`, `None` if it doesn't say
+/// - Set as [Board::promote_to] before playing the move [parse_move] read from the same text
+pub(crate) fn promotion_piece(text: &str) -> Option<PieceNames> {
+    let text = text.trim().trim_end_matches(['+', '#', '!', '?']);
+    let letter = match text.find('=') {
+        Some(i) => text[i + 1..].chars().next()?,
+        None if text.starts_with(|c: char| c.is_ascii_lowercase()) => text.chars().last()?,
+        None => return None,
+    };
+    match letter.to_ascii_uppercase() {
+        'Q' => Some(PieceNames::Queen),
+        'R' => Some(PieceNames::Rook),
+        'B' => Some(PieceNames::Bishop),
+        'N' => Some(PieceNames::Knight),
+        _ => None,
+    }
+}
+
+/// [parse_move], with an error to show if it isn't a legal move
+pub(crate) fn try_parse_move(board: &Board, text: &str) -> Result<(Loc, Loc), ChessError> {
+    parse_move(board, text).ok_or_else(|| ChessError::IllegalMove(text.trim().to_string()))
+}
+
+fn piece_letter(name: PieceNames) -> char {
+    match name {
+        PieceNames::Pawn => 'P',
+        PieceNames::Knight => 'N',
+        PieceNames::Bishop => 'B',
+        PieceNames::Rook => 'R',
+        PieceNames::Queen => 'Q',
+        PieceNames::King => 'K',
+    }
+}
+
+/// Result tag and movetext terminator for the state
+pub(crate) fn result(state: &BoardState) -> &'static str {
+    match state {
+        BoardState::Checkmate(color)
+        | BoardState::Timeout(color)
+        | BoardState::Resigned(color)
+        | BoardState::ThreeChecks(color)
+        | BoardState::Exploded(color)
+        | BoardState::KingCaptured(color)
+        | BoardState::Outraced(color)
+        | BoardState::NoPieces(color) => match color {
+            ChessColor::White => "0-1",
+            ChessColor::Black => "1-0",
+        },
+        BoardState::Stalemate | BoardState::Draw(_) => "1/2-1/2",
+        _ => "*",
+    }
+}
+
+/// Termination tag for the state, describing why the game ended
+pub(crate) fn termination(state: &BoardState) -> String {
+    let winner = |loser: &ChessColor| format!("{:?}", loser.other());
+    match state {
+        BoardState::Checkmate(color) => format!("{} won by checkmate", winner(color)),
+        BoardState::Timeout(color) => format!("{} won on time", winner(color)),
+        BoardState::Resigned(color) => format!("{} won by resignation", winner(color)),
+        BoardState::ThreeChecks(color) => format!("{} won by three checks", winner(color)),
+        BoardState::Exploded(color) => format!("{} won by exploding the king", winner(color)),
+        BoardState::KingCaptured(color) => format!("{} won by taking the king", winner(color)),
+        BoardState::Outraced(color) => format!("{} won the race", winner(color)),
+        BoardState::NoPieces(color) => format!("{} won by taking every piece", winner(color)),
+        BoardState::Stalemate => "Game drawn by stalemate".to_string(),
+        BoardState::Draw(reason) => match reason {
+            DrawReason::Repetition => "Game drawn by repetition".to_string(),
+            DrawReason::FiftyMove => "Game drawn by fifty-move rule".to_string(),
+            DrawReason::InsufficientMaterial => "Game drawn by insufficient material".to_string(),
+            DrawReason::Agreement => "Game drawn by agreement".to_string(),
+            DrawReason::RaceTie => "Game drawn by both kings reaching the eighth rank".to_string(),
+        },
+        _ => "Unterminated".to_string(),
+    }
+}
+
+/// Exports a game as PGN
+/// - `comments` are added after the move with the same index, missing ones are skipped
+/// - `evals` are scores for white of the position after each ply, starting with `start`. Moves with a score
+///   get a `[%eval]` comment, and a NAG if the score dropped enough from the position before
+/// - `state` is passed separately since some endings, IE timeouts, don't come from the moves
+#[cfg(feature = "gui")]
+pub(crate) fn export(
+    start: &Board,
+    moves: &[(Loc, Loc)],
+    comments: &[Option<String>],
+    evals: &[Option<i32>],
+    white: &str,
+    black: &str,
+    state: &BoardState,
+) -> String {
+    let result = result(state);
+    let mut pgn = tags(start, white, black, state);
+
+    // Movetext, wrapped at `LINE_LENGTH`
+    let mut board = start.clone();
+    let mut tokens = vec![];
+    for (i, (from, to)) in moves.iter().enumerate() {
+        let number = board.full_moves() + 1;
+        if board.turn == ChessColor::White {
+            tokens.push(format!("{}.", number));
+        } else if i == 0 {
+            tokens.push(format!("{}...", number));
+        }
+
+        tokens.push(san(&board, from, to));
+
+        let before = evals.get(i).copied().flatten();
+        let after = evals.get(i + 1).copied().flatten();
+        if let (Some(before), Some(after)) = (before, after) {
+            if let Some(nag) = nag(before, after, board.turn) {
+                tokens.push(format!("${}", nag));
+            }
+        }
+
+        let mut comment = vec![];
+        if let Some(after) = after {
+            comment.push(format!("[%eval {}]", eval_tag(after)));
+        }
+        if let Some(Some(text)) = comments.get(i) {
+            comment.push(text.clone());
+        }
+        if let Some(comment) = comment_token(&comment) {
+            tokens.push(comment);
+        }
+
+        board.move_piece(from, to, true);
+    }
+    tokens.push(result.to_string());
+
+    pgn.push_str(&wrap(&tokens));
+    pgn
+}
+
+/// Exports a whole tree of moves as PGN, with variations, comments, and arrows and highlights as `%cal` and `%csl`
+/// - `state` is the state at the end of the main line
+#[cfg(feature = "gui")]
+pub(crate) fn export_tree(
+    start: &Board,
+    tree: &MoveTree,
+    white: &str,
+    black: &str,
+    state: &BoardState,
+) -> String {
+    let mut pgn = tags(start, white, black, state);
+
+    let mut tokens = vec![];
+    tree_tokens(tree, start, None, true, &mut tokens);
+    tokens.push(result(state).to_string());
+
+    pgn.push_str(&wrap(&tokens));
+    pgn
+}
+
+/// Tokens for the moves after `parent`, with alternatives to each move as variations
+/// - `number` forces a move number before a black move, IE at the start of a variation
+#[cfg(feature = "gui")]
+fn tree_tokens(
+    tree: &MoveTree,
+    board: &Board,
+    parent: Option<usize>,
+    number: bool,
+    tokens: &mut Vec<String>,
+) {
+    let children = tree.children(parent);
+    let Some(main) = children.first() else {
+        return;
+    };
+
+    node_tokens(tree, board, *main, number, tokens);
+    for variation in &children[1..] {
+        tokens.push("(".to_string());
+        node_tokens(tree, board, *variation, true, tokens);
+        let mut board = board.clone();
+        let (from, to) = tree.nodes[*variation].mov;
+        board.move_piece(&from, &to, true);
+        tree_tokens(tree, &board, Some(*variation), false, tokens);
+        tokens.push(")".to_string());
+    }
+
+    let mut board = board.clone();
+    let (from, to) = tree.nodes[*main].mov;
+    board.move_piece(&from, &to, true);
+    tree_tokens(tree, &board, Some(*main), children.len() > 1, tokens);
+}
+
+/// Move number, SAN, and comment of a node
+#[cfg(feature = "gui")]
+fn node_tokens(
+    tree: &MoveTree,
+    board: &Board,
+    node: usize,
+    number: bool,
+    tokens: &mut Vec<String>,
+) {
+    let node = &tree.nodes[node];
+    let full_moves = board.full_moves() + 1;
+    if board.turn == ChessColor::White {
+        tokens.push(format!("{}.", full_moves));
+    } else if number {
+        tokens.push(format!("{}...", full_moves));
+    }
+    tokens.push(node.san.clone());
+
+    let mut comment = vec![];
+    if !node.arrows.is_empty() {
+        let arrows: Vec<_> = node
+            .arrows
+            .iter()
+            .map(|(from, to)| format!("G{}{}", from.as_notation(), to.as_notation()))
+            .collect();
+        comment.push(format!("[%cal {}]", arrows.join(",")));
+    }
+    if !node.highlights.is_empty() {
+        let highlights: Vec<_> = node
+            .highlights
+            .iter()
+            .map(|loc| format!("R{}", loc.as_notation()))
+            .collect();
+        comment.push(format!("[%csl {}]", highlights.join(",")));
+    }
+    if let Some(text) = stats_comment(node.comment.as_deref(), node.stats.as_ref()) {
+        comment.push(text);
+    }
+    if let Some(comment) = comment_token(&comment) {
+        tokens.push(comment);
+    }
+}
+
+/// Joins parts of a comment into a `{comment}` token, `None` if there aren't any
+#[cfg(feature = "gui")]
+fn comment_token(parts: &[String]) -> Option<String> {
+    if parts.is_empty() {
+        return None;
+    }
+    // Braces end a comment early, so they can't be inside one
+    Some(format!("{{{}}}", parts.join(" ").replace(['{', '}'], "")))
+}
+
+/// Tag pairs section, followed by a blank line
+#[cfg(feature = "gui")]
+fn tags(start: &Board, white: &str, black: &str, state: &BoardState) -> String {
+    let mut pgn = String::new();
+    let mut tag = |name: &str, value: &str| {
+        pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('"', "'")));
+    };
+    tag("Event", "Casual game");
+    tag("Site", "Chess AI");
+    tag("Date", "????.??.??");
+    tag("Round", "-");
+    tag("White", white);
+    tag("Black", black);
+    tag("Result", result(state));
+    let start_fen = start.as_fen();
+    if start_fen != DEFAULT_FEN {
+        tag("SetUp", "1");
+        tag("FEN", &start_fen);
+    }
+    if start.variant != Variant::Standard {
+        tag("Variant", start.variant.name());
+    }
+    tag("Termination", &termination(state));
+    pgn.push('\n');
+    pgn
+}
+
+/// Joins movetext tokens into lines of at most `LINE_LENGTH`, with no spaces inside variation parentheses
+#[cfg(feature = "gui")]
+fn wrap(tokens: &[String]) -> String {
+    let mut text = String::new();
+    let mut line = String::new();
+    let mut after_open = false;
+    for token in tokens {
+        let space = !line.is_empty() && !after_open && token != ")";
+        if !line.is_empty() && line.len() + token.len() + space as usize > LINE_LENGTH {
+            text.push_str(&line);
+            text.push('\n');
+            line.clear();
+        } else if space {
+            line.push(' ');
+        }
+        line.push_str(token);
+        after_open = token == "(";
+    }
+    text.push_str(&line);
+    text.push('\n');
+    text
+}
+
+/// A game read from PGN
+#[cfg(feature = "gui")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PgnGame {
+    /// (name, value) of every tag, in order
+    pub(crate) tags: Vec<(String, String)>,
+    /// From the `FEN` tag, or the standard starting position
+    pub(crate) start_fen: String,
+    /// Moves, with variations and comments
+    pub(crate) tree: MoveTree,
+}
+#[cfg(feature = "gui")]
+impl PgnGame {
+    #[cfg(feature = "gui")]
+    pub(crate) fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    /// Start of a variation
+    Open,
+    /// End of a variation
+    Close,
+    Move(String),
+    /// Game result, which ends the movetext
+    Result,
+}
+
+/// Splits PGN into tokens, dropping move numbers, NAGs, and escaped lines
+#[cfg(feature = "gui")]
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(c) = chars.next() {
+        let was_line_start = line_start;
+        line_start = c == '\n';
+
+        match c {
+            _ if c.is_whitespace() => {}
+            // Escaped lines and rest of line comments
+            '%' if was_line_start => for _ in chars.by_ref().take_while(|c| *c != '\n') {},
+            ';' => for _ in chars.by_ref().take_while(|c| *c != '\n') {},
+            '{' => {
+                let comment: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '[' => {
+                let tag: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                let (name, value) = tag
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| format!("Invalid tag [{}]", tag))?;
+                let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+                tokens.push(Token::Tag(name.to_string(), value));
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '$' => while chars.next_if(|c| c.is_ascii_digit()).is_some() {},
+            _ => {
+                let mut word = c.to_string();
+                while let Some(c) =
+                    chars.next_if(|c| !c.is_whitespace() && !"(){}[];$".contains(*c))
+                {
+                    word.push(c);
+                }
+
+                // Move numbers, possibly stuck to the move like `1.e4`
+                let text = word.trim_start_matches(|c: char| c.is_ascii_digit());
+                let text = ternary!(
+                    text.starts_with('.'),
+                    text.trim_start_matches('.'),
+                    word.as_str()
+                );
+
+                match text {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => tokens.push(Token::Result),
+                    // Annotations split from their move
+                    _ if text.chars().all(|c| c == '!' || c == '?') => {}
+                    _ => tokens.push(Token::Move(text.to_string())),
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Splits `[%command]`s out of a comment, returning (the rest of the text, `%cal` arrows, `%csl` highlights)
+/// - Other commands, IE `%eval` and `%clk`, are dropped
+#[cfg(feature = "gui")]
+#[allow(clippy::type_complexity)]
+fn comment_commands(comment: &str) -> (String, Vec<(Loc, Loc)>, Vec<Loc>) {
+    // Squares in a command, without their color letter
+    let square = |s: &str| Loc::parse_notation(s).ok();
+
+    let mut text = String::new();
+    let mut arrows = vec![];
+    let mut highlights = vec![];
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(']') else {
+            rest = "";
+            break;
+        };
+
+        let command = &rest[start + 2..start + end];
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        for arg in args.split(',').map(str::trim).filter(|arg| arg.len() > 1) {
+            let arg = &arg[1..];
+            match name {
+                "cal" if arg.len() == 4 => {
+                    if let (Some(from), Some(to)) = (square(&arg[..2]), square(&arg[2..])) {
+                        arrows.push((from, to));
+                    }
+                }
+                "csl" => highlights.extend(square(arg)),
+                _ => {}
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (text, arrows, highlights)
+}
+
+/// Reads the first game in PGN, including variations and comments
+#[cfg(feature = "gui")]
+pub(crate) fn parse(text: &str) -> Result<PgnGame, String> {
+    parse_game(&mut tokenize(text)?.into_iter().peekable())
+}
+
+/// Reads every game in PGN, IE a database, returning the games and how many were skipped for being unreadable
+#[cfg(feature = "gui")]
+pub(crate) fn parse_all(text: &str) -> Result<(Vec<PgnGame>, usize), String> {
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+    let mut games = vec![];
+    let mut skipped = 0;
+    while tokens.peek().is_some() {
+        match parse_game(&mut tokens) {
+            // Stray tokens after a result
+            Ok(game) if game.tags.is_empty() && game.tree.nodes.is_empty() => {}
+            Ok(game) => games.push(game),
+            Err(_) => {
+                skipped += 1;
+                // The rest of the game, up to the tags of the next one
+                while tokens.next_if(|t| !matches!(t, Token::Tag(..))).is_some() {}
+            }
+        }
+    }
+    Ok((games, skipped))
+}
+
+/// Reads one game, stopping after its result or at the tags of the next game
+#[cfg(feature = "gui")]
+fn parse_game(tokens: &mut Peekable<IntoIter<Token>>) -> Result<PgnGame, String> {
+    let mut tags = vec![];
+    while let Some(Token::Tag(name, value)) = tokens.next_if(|t| matches!(t, Token::Tag(..))) {
+        tags.push((name, value));
+    }
+    let start_fen = tags
+        .iter()
+        .find(|(name, _)| name == "FEN")
+        .map_or(DEFAULT_FEN.to_string(), |(_, fen)| fen.clone());
+    let mut board = Board::try_from_fen(&start_fen)
+        .map_err(|issues| format!("Invalid FEN {}: {}", start_fen, describe_issues(&issues)))?;
+
+    let mut tree = MoveTree::new();
+    let mut current = None;
+    // Position before the last move, which is where a variation branches from
+    let mut before_last = None;
+    // Where to return to at the end of each open variation
+    let mut stack = vec![];
+
+    while let Some(token) = tokens.next_if(|t| !matches!(t, Token::Tag(..))) {
+        match token {
+            Token::Move(text) => {
+                let (from, to) = from_san(&board, &text)
+                    .ok_or_else(|| format!("Illegal move {} in {}", text, board.as_fen()))?;
+                let node = tree.add(current, (from, to), san(&board, &from, &to));
+                before_last = Some((board.clone(), current));
+                board.move_piece(&from, &to, true);
+                current = Some(node);
+            }
+            Token::Open => {
+                let (variation_board, variation_node) = before_last
+                    .clone()
+                    .ok_or("Variation doesn't follow a move")?;
+                stack.push((board, current, before_last.take()));
+                board = variation_board;
+                current = variation_node;
+            }
+            Token::Close => {
+                (board, current, before_last) = stack.pop().ok_or("Unmatched )")?;
+            }
+            Token::Comment(text) => {
+                // Comments before the first move have nowhere to go
+                if let Some(node) = current {
+                    let (text, arrows, highlights) = comment_commands(&text);
+                    let node = &mut tree.nodes[node];
+                    node.arrows.extend(arrows);
+                    node.highlights.extend(highlights);
+
+                    if !text.is_empty() {
+                        node.comment = Some(match node.comment.take() {
+                            Some(existing) => format!("{} {}", existing, text),
+                            None => text,
+                        });
+                    }
+                }
+            }
+            Token::Result if stack.is_empty() => break,
+            Token::Result => return Err("Result inside a variation".to_string()),
+            // Left for the next game by `next_if`
+            Token::Tag(..) => unreachable!(),
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err("Unclosed variation".to_string());
+    }
+
+    Ok(PgnGame {
+        tags,
+        start_fen,
+        tree,
+    })
+}
@@ -1,35 +1,49 @@
-use super::piece::Piece;
+use super::piece::{Piece, PieceNames};
 use super::util::{static_attacks, static_moves};
 use crate::board::Board;
-use crate::util::Loc;
+use crate::util::{Loc, SquareSet};
+use crate::variant::Variant;
 use crate::{color_ternary, loc};
 
-pub(crate) fn king_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
-    let directions = vec![
-        (0, -1),
-        (0, 1),
-        (1, -1),
-        (1, 0),
-        (1, 1),
-        (-1, -1),
-        (-1, 0),
-        (-1, 1),
-    ];
+const KING_DIRECTIONS: [(i32, i32); 8] = [
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
 
-    let mut moves = static_moves(piece, board, &directions);
+pub(crate) fn king_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
+    let mut moves = static_moves(piece, board, &KING_DIRECTIONS);
 
-    // Castling
-    if color_ternary!(piece.color, board.check_white, board.check_black) {
+    // Castling, out of and through an attack too in fog of war
+    let fog = board.variant == Variant::FogOfWar;
+    if !fog && color_ternary!(piece.color, board.check_white, board.check_black) {
         return moves;
     }
 
-    let (queen_side, king_side) =
-        color_ternary!(piece.color, board.castle_white, board.castle_black);
+    let queen_side = board.castle.queenside(piece.color);
+    let king_side = board.castle.kingside(piece.color);
+    let attacks = color_ternary!(piece.color, &board.attacks_black, &board.attacks_white);
+    let attacked = |loc: Loc| !fog && attacks.contains(&loc);
+    let y = piece.pos.1;
+
+    // Rook still has to be there, even if the rights say otherwise
+    let has_rook = |x: usize| {
+        matches!(
+            board.get(&loc!(x, y)),
+            Some(rook) if rook.name == PieceNames::Rook && rook.color == piece.color
+        )
+    };
+
     macro_rules! clear_range {
         ($start: expr, $end: expr) => {
             'main: {
                 for i in $start..=$end {
-                    if board.get(&loc!(i, piece.pos.1)).is_some() {
+                    if board.get(&loc!(i, y)).is_some() {
                         break 'main true;
                     }
                 }
@@ -38,28 +52,18 @@ pub(crate) fn king_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
         };
     }
 
-    if queen_side && !clear_range!(1, 3) {
-        moves.push(loc!(2, piece.pos.1));
+    // King can't pass through an attacked square
+    if queen_side && !clear_range!(1, 3) && !attacked(loc!(3, y)) && has_rook(0) {
+        moves.push(loc!(2, y));
     }
 
-    if king_side && !clear_range!(5, 6) {
-        moves.push(loc!(6, piece.pos.1));
+    if king_side && !clear_range!(5, 6) && !attacked(loc!(5, y)) && has_rook(7) {
+        moves.push(loc!(6, y));
     }
 
     moves
 }
 
-pub(crate) fn king_attacks(piece: &Piece) -> Vec<Loc> {
-    let directions = vec![
-        (0, -1),
-        (0, 1),
-        (1, -1),
-        (1, 0),
-        (1, 1),
-        (-1, -1),
-        (-1, 0),
-        (-1, 1),
-    ];
-
-    static_attacks(piece, &directions)
+pub(crate) fn king_attacks(piece: &Piece) -> SquareSet {
+    static_attacks(piece, &KING_DIRECTIONS)
 }
@@ -1,34 +1,23 @@
 use super::piece::Piece;
 use super::util::{static_attacks, static_moves};
 use crate::board::Board;
-use crate::util::Loc;
+use crate::util::{Loc, SquareSet};
 
-pub(crate) fn knight_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
-    let directions = vec![
-        (1, 2),
-        (2, 1),
-        (2, -1),
-        (1, -2),
-        (-1, -2),
-        (-2, -1),
-        (-2, 1),
-        (-1, 2),
-    ];
+const KNIGHT_DIRECTIONS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
 
-    static_moves(piece, board, &directions)
+pub(crate) fn knight_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
+    static_moves(piece, board, &KNIGHT_DIRECTIONS)
 }
 
-pub(crate) fn knight_attacks(piece: &Piece) -> Vec<Loc> {
-    let directions = vec![
-        (1, 2),
-        (2, 1),
-        (2, -1),
-        (1, -2),
-        (-1, -2),
-        (-2, -1),
-        (-2, 1),
-        (-1, 2),
-    ];
-
-    static_attacks(piece, &directions)
+pub(crate) fn knight_attacks(piece: &Piece) -> SquareSet {
+    static_attacks(piece, &KNIGHT_DIRECTIONS)
 }
@@ -1,27 +1,30 @@
-use super::piece::Piece;
-use super::util::{add, add_ff, valid_pos};
+use super::piece::{Piece, PieceNames};
+use super::util::add;
 use crate::board::{Board, ChessColor};
-use crate::color_ternary;
-use crate::util::Loc;
+use crate::util::{Loc, SquareSet};
+use crate::variant::Variant;
+use crate::{color_ternary, loc};
 
 /// Adds to moves if the move is on the board and is empty
 /// - Returns true if added, false else
-pub(crate) fn add_if_empty(board: &Board, location: Loc, moves: &mut Vec<Loc>) -> bool {
-    if valid_pos(&location) && board.get(&location).is_none() {
-        moves.push(location);
-        return true;
+pub(crate) fn add_if_empty(board: &Board, location: Option<Loc>, moves: &mut Vec<Loc>) -> bool {
+    if let Some(location) = location {
+        if board.get(&location).is_none() {
+            moves.push(location);
+            return true;
+        }
     }
     false
 }
 
-/// Adds to moves if the move is a capture
+/// Adds to moves if the move is on the board and is a capture
 pub(crate) fn add_if_capture(
     board: &Board,
     color: &ChessColor,
-    location: Loc,
+    location: Option<Loc>,
     moves: &mut Vec<Loc>,
 ) {
-    if valid_pos(&location) {
+    if let Some(location) = location {
         if let Some(capture) = board.get(&location) {
             if &capture.color != color {
                 moves.push(location);
@@ -34,60 +37,46 @@ pub(crate) fn pawn_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
     let mut moves = vec![];
     let direction = color_ternary!(piece.color, -1, 1);
 
-    // Forward movement
-    let blocked = add_if_empty(board, piece.pos.copy_move_i32(0, direction).0, &mut moves);
-    if blocked && (piece.pos.1 == 1 || piece.pos.1 == 6) {
-        add_if_empty(
-            board,
-            piece.pos.copy_move_i32(0, direction * 2).0,
-            &mut moves,
-        );
+    let start_rank = color_ternary!(piece.color, 6, 1);
+
+    // Forward movement, two squares only from the pawn's own starting rank, or the first rank in horde
+    let single = add_if_empty(board, piece.pos.offset(0, direction), &mut moves);
+    let first_rank =
+        board.variant == Variant::Horde && piece.pos.1 == color_ternary!(piece.color, 7, 0);
+    if single && (piece.pos.1 == start_rank || first_rank) {
+        add_if_empty(board, piece.pos.offset(0, direction * 2), &mut moves);
     }
 
     // Diagonal captures
-    let left_side = piece.pos.copy_move_i32(-1, direction);
-    if !left_side.1 {
-        add_if_capture(board, &piece.color, left_side.0, &mut moves);
-    }
-    let right_side = piece.pos.copy_move_i32(1, direction);
-    if !right_side.1 {
-        add_if_capture(board, &piece.color, right_side.0, &mut moves);
+    for x in [-1, 1] {
+        add_if_capture(
+            board,
+            &piece.color,
+            piece.pos.offset(x, direction),
+            &mut moves,
+        );
     }
 
-    // En passent
-    if let Some(en_passent) = board.en_passent {
-        if en_passent.1 != piece.color && en_passent.0 .1 == piece.pos.1 {
-            if en_passent.0 .0 == piece.pos.0 + 1 {
-                add(
-                    board,
-                    &piece.color,
-                    piece.pos.copy_move_i32(1, direction).0,
-                    &mut moves,
-                );
-            } else if piece.pos.0 != 0 && en_passent.0 .0 == piece.pos.0 - 1 {
-                add(
-                    board,
-                    &piece.color,
-                    piece.pos.copy_move_i32(-1, direction).0,
-                    &mut moves,
-                );
-            }
+    // En passent, the pawn that skipped the target square has to be an enemy's
+    if let Some(target) = board.en_passent {
+        let skipped = board.get(&loc!(target.0, piece.pos.1));
+        if [-1, 1]
+            .map(|x| piece.pos.offset(x, direction))
+            .contains(&Some(target))
+            && skipped.is_some_and(|p| p.name == PieceNames::Pawn && p.color != piece.color)
+        {
+            add(board, &piece.color, target, &mut moves);
         }
     }
 
     moves
 }
 
-pub(crate) fn pawn_attacks(piece: &Piece) -> Vec<Loc> {
-    let mut moves = vec![];
+pub(crate) fn pawn_attacks(piece: &Piece) -> SquareSet {
     let direction = color_ternary!(piece.color, -1, 1);
 
-    for pos in [
-        piece.pos.copy_move_i32(1, direction).0,
-        piece.pos.copy_move_i32(-1, direction).0,
-    ] {
-        add_ff(pos, &mut moves)
-    }
-
-    moves
+    [-1, 1]
+        .iter()
+        .filter_map(|x| piece.pos.offset(*x, direction))
+        .collect()
 }
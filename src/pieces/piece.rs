@@ -1,4 +1,5 @@
 use derive_new::new;
+#[cfg(feature = "gui")]
 use macroquad::texture::Texture2D;
 
 use super::bishop::{bishop_attacks, bishop_moves};
@@ -7,11 +8,15 @@ use super::knight::{knight_attacks, knight_moves};
 use super::pawn::{pawn_attacks, pawn_moves};
 use super::queen::{queen_attacks, queen_moves};
 use super::rook::{rook_attacks, rook_moves};
+#[cfg(feature = "gui")]
 use crate::assets::get_image;
 use crate::board::{Board, ChessColor};
 use crate::board_eval::piece_value;
 use crate::color_ternary;
-use crate::util::Loc;
+#[cfg(feature = "gui")]
+use crate::error::ChessError;
+use crate::util::{Loc, SquareSet};
+use crate::variant::Variant;
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub(crate) enum PieceNames {
@@ -42,11 +47,18 @@ impl Piece {
             PieceNames::Knight => knight_moves(self, board),
             PieceNames::King => {
                 let mut moves = king_moves(self, board);
-                moves.retain(|&to| {
-                    let attacks =
-                        color_ternary!(self.color, &board.attacks_black, &board.attacks_white);
-                    !attacks.contains(&to)
-                });
+                // Kings can move into an attack in fog of war, and be taken
+                if board.variant != Variant::FogOfWar {
+                    moves.retain(|&to| {
+                        let attacks =
+                            color_ternary!(self.color, &board.attacks_black, &board.attacks_white);
+                        !attacks.contains(&to)
+                    });
+                }
+                // Kings would explode themselves in atomic
+                if board.variant == Variant::Atomic {
+                    moves.retain(|to| board.get(to).is_none());
+                }
                 moves
             }
             PieceNames::Rook => rook_moves(self, board),
@@ -54,22 +66,48 @@ impl Piece {
             PieceNames::Queen => queen_moves(self, board),
         };
 
-        if board.blockers.contains(&self.pos)
-            || color_ternary!(self.color, board.check_white, board.check_black)
-        {
+        if self.needs_legality_check(board) {
             let new_board = board.clone();
             temp_moves.retain(|&to| {
                 let mut new_board = new_board.clone();
                 new_board.move_piece(&self.pos, &to, false);
-                color_ternary!(self.color, !new_board.check_white, !new_board.check_black)
+                // Exploding the other king wins in atomic, even when it leaves a check
+                !new_board.exploded(self.color)
+                    && !new_board.gives_check(self.color)
+                    && (color_ternary!(self.color, !new_board.check_white, !new_board.check_black)
+                        || new_board.exploded(self.color.other()))
             });
         }
 
         temp_moves
     }
 
+    /// Whether some of the piece's moves could leave its king in check, so they have to be tried on a copy
+    pub(crate) fn needs_legality_check(&self, board: &Board) -> bool {
+        // Any move that can be made is legal in fog of war, even one leaving the king to be taken
+        if board.variant == Variant::FogOfWar {
+            return false;
+        }
+
+        // En passent removes two pieces from the rank, which can reveal a check the blockers don't catch
+        board.blockers.contains(&self.pos)
+            || color_ternary!(self.color, board.check_white, board.check_black)
+            || (self.name == PieceNames::Pawn
+                && board.en_passent.is_some_and(|t| {
+                    t.0.abs_diff(self.pos.0) == 1 && t.1.abs_diff(self.pos.1) == 1
+                }))
+            // Any move can give check in racing kings
+            || board.variant == Variant::RacingKings
+            // Captures in atomic can explode the piece's own king
+            || (board.variant == Variant::Atomic
+                && self
+                    .attacks(board)
+                    .iter()
+                    .any(|loc| board.get(&loc).is_some_and(|piece| piece.color != self.color)))
+    }
+
     /// Get squares that are attacked by this piece
-    pub(crate) fn attacks(&self, board: &Board) -> Vec<Loc> {
+    pub(crate) fn attacks(&self, board: &Board) -> SquareSet {
         match self.name {
             PieceNames::Pawn => pawn_attacks(self),
             PieceNames::Knight => knight_attacks(self),
@@ -80,8 +118,9 @@ impl Piece {
         }
     }
 
-    /// Get image texture for this piece
-    pub(crate) fn image(&self) -> Texture2D {
+    /// Get image texture for this piece, errors if it wasn't loaded
+    #[cfg(feature = "gui")]
+    pub(crate) fn image(&self) -> Result<Texture2D, ChessError> {
         let path = match self.color {
             ChessColor::White => match self.name {
                 PieceNames::Pawn => "assets/pieces/white_pawn.png",
@@ -1,7 +1,7 @@
 use super::piece::Piece;
 use super::util::{directional_attacks, directional_moves};
 use crate::board::Board;
-use crate::util::Loc;
+use crate::util::{Loc, SquareSet};
 
 pub(crate) fn rook_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
     let directions = [(0, -1), (0, 1), (1, 0), (-1, 0)];
@@ -9,7 +9,7 @@ pub(crate) fn rook_moves(piece: &Piece, board: &Board) -> Vec<Loc> {
     directional_moves(piece, board, &directions)
 }
 
-pub(crate) fn rook_attacks(piece: &Piece, board: &Board) -> Vec<Loc> {
+pub(crate) fn rook_attacks(piece: &Piece, board: &Board) -> SquareSet {
     let directions = [(0, -1), (0, 1), (1, 0), (-1, 0)];
 
     directional_attacks(piece, board, &directions)
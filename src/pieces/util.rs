@@ -5,55 +5,35 @@
 
 use super::piece::Piece;
 use crate::board::{Board, ChessColor};
-use crate::util::Loc;
+use crate::util::{Loc, SquareSet};
 
-pub(crate) fn valid_pos(location: &Loc) -> bool {
-    !(location.0 >= 8 || location.1 >= 8)
-}
-
-/// Adds to moves if the move is valid and doesn't capture friendly
+/// Adds to moves if the move doesn't capture friendly
 pub(crate) fn add(board: &Board, color: &ChessColor, location: Loc, moves: &mut Vec<Loc>) {
-    if valid_pos(&location) {
-        if let Some(piece) = board.get(&location) {
-            if &piece.color == color {
-                return;
-            }
+    if let Some(piece) = board.get(&location) {
+        if &piece.color == color {
+            return;
         }
-        moves.push(location);
-    }
-}
-
-/// Adds to moves if the move is valid
-pub(crate) fn add_ff(location: Loc, moves: &mut Vec<Loc>) {
-    if valid_pos(&location) {
-        moves.push(location);
     }
+    moves.push(location);
 }
 
 /// Get all moves for static pieces
 pub(crate) fn static_moves(piece: &Piece, board: &Board, directions: &[(i32, i32)]) -> Vec<Loc> {
     let mut moves = vec![];
     for (x, y) in directions.iter() {
-        let (loc, out) = piece.pos.copy_move_i32(*x, *y);
-        if out {
-            continue;
+        if let Some(loc) = piece.pos.offset(*x, *y) {
+            add(board, &piece.color, loc, &mut moves);
         }
-        add(board, &piece.color, loc, &mut moves);
     }
     moves
 }
 
 /// Get all attack squares for static pieces
-pub(crate) fn static_attacks(piece: &Piece, directions: &[(i32, i32)]) -> Vec<Loc> {
-    let mut moves = vec![];
-    for (x, y) in directions.iter() {
-        let (loc, out) = piece.pos.copy_move_i32(*x, *y);
-        if out {
-            continue;
-        }
-        add_ff(loc, &mut moves);
-    }
-    moves
+pub(crate) fn static_attacks(piece: &Piece, directions: &[(i32, i32)]) -> SquareSet {
+    directions
+        .iter()
+        .filter_map(|(x, y)| piece.pos.offset(*x, *y))
+        .collect()
 }
 
 /// Get all moves for directional pieces
@@ -64,11 +44,7 @@ pub(crate) fn directional_moves(
 ) -> Vec<Loc> {
     let mut moves = vec![];
     for (x, y) in directions.iter() {
-        let (mut loc, out) = piece.pos.copy_move_i32(*x, *y);
-        if out {
-            continue;
-        }
-        while valid_pos(&loc) {
+        for loc in piece.pos.ray(*x, *y) {
             if let Some(capture) = board.get(&loc) {
                 if capture.color != piece.color {
                     moves.push(loc);
@@ -76,10 +52,6 @@ pub(crate) fn directional_moves(
                 break;
             }
             moves.push(loc);
-            let end = loc.move_i32(*x, *y);
-            if !end {
-                break;
-            }
         }
     }
     moves
@@ -90,24 +62,15 @@ pub(crate) fn directional_attacks(
     piece: &Piece,
     board: &Board,
     directions: &[(i32, i32)],
-) -> Vec<Loc> {
-    let mut moves = vec![];
+) -> SquareSet {
+    let mut attacks = SquareSet::default();
     for (x, y) in directions.iter() {
-        let (mut loc, out) = piece.pos.copy_move_i32(*x, *y);
-        if out {
-            continue;
-        }
-        while valid_pos(&loc) {
+        for loc in piece.pos.ray(*x, *y) {
+            attacks.insert(loc);
             if board.get(&loc).is_some() {
-                moves.push(loc);
-                break;
-            }
-            moves.push(loc);
-            let end = loc.move_i32(*x, *y);
-            if !end {
                 break;
             }
         }
     }
-    moves
+    attacks
 }
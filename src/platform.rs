@@ -0,0 +1,92 @@
+//! Logging and time for the engine, which come from macroquad in the GUI, the browser in [bindings](crate::bindings)
+//! builds, and std otherwise
+//!
+//! The GUI also keeps what's logged for the [log panel](crate::log)
+//!
+//! Bindings builds can't use macroquad's, since those go through its own JS loader
+
+#[cfg(all(feature = "gui", not(feature = "bindings")))]
+pub(crate) use macroquad::miniquad::date::now;
+#[cfg(feature = "bindings")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Logs with macroquad, and to the [log panel](crate::log)
+#[cfg(all(feature = "gui", not(feature = "bindings")))]
+macro_rules! gui_info {
+    ($($arg: tt)*) => {{
+        let text = format!($($arg)*);
+        ::macroquad::prelude::info!("{}", text);
+        $crate::log::push_log($crate::log::LogKind::System, text);
+    }};
+}
+/// Warns with macroquad, and logs to the [log panel](crate::log)
+#[cfg(all(feature = "gui", not(feature = "bindings")))]
+macro_rules! gui_warn {
+    ($($arg: tt)*) => {{
+        let text = format!($($arg)*);
+        ::macroquad::prelude::warn!("{}", text);
+        $crate::log::push_log($crate::log::LogKind::System, text);
+    }};
+}
+#[cfg(all(feature = "gui", not(feature = "bindings")))]
+pub(crate) use {gui_info as info, gui_warn as warn};
+
+#[cfg(feature = "bindings")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console)]
+    pub(crate) fn log(text: &str);
+}
+
+/// Logs to the browser console, and to the [log panel](crate::log) when built with the GUI too
+#[cfg(feature = "bindings")]
+macro_rules! console_log {
+    ($($arg: tt)*) => {{
+        let text = format!($($arg)*);
+        $crate::platform::log(&text);
+        #[cfg(feature = "gui")]
+        $crate::log::push_log($crate::log::LogKind::System, text);
+    }};
+}
+#[cfg(feature = "bindings")]
+pub(crate) use {console_log as info, console_log as warn};
+
+/// Seconds since the epoch
+#[cfg(feature = "bindings")]
+pub(crate) fn now() -> f64 {
+    js_sys::Date::now() / 1000.0
+}
+
+/// Logs to stderr
+#[cfg(not(any(feature = "gui", feature = "bindings")))]
+macro_rules! stderr_log {
+    ($($arg: tt)*) => {
+        eprintln!($($arg)*)
+    };
+}
+#[cfg(not(any(feature = "gui", feature = "bindings")))]
+pub(crate) use {stderr_log as info, stderr_log as warn};
+
+/// Seconds since the epoch
+#[cfg(not(any(feature = "gui", feature = "bindings")))]
+pub(crate) fn now() -> f64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64()
+}
+
+/// Logs a move the agent took from the opening book, set apart from other messages in the log panel
+pub(crate) fn log_book_hit(name: &str) {
+    #[cfg(all(feature = "gui", not(feature = "bindings")))]
+    macroquad::prelude::info!("Opening found! {}", name);
+    #[cfg(feature = "bindings")]
+    log(&format!("Opening found! {}", name));
+    #[cfg(not(any(feature = "gui", feature = "bindings")))]
+    info!("Opening found! {}", name);
+
+    #[cfg(feature = "gui")]
+    crate::log::push_log(crate::log::LogKind::Book, format!("Book: {}", name));
+}
@@ -0,0 +1,180 @@
+//! Part of [Board], split for readability
+//!
+//! Checks that a position could come up in a real game, so broken positions are turned away before move generation
+//! sees them
+
+use crate::board::{Board, ChessColor};
+use crate::pieces::piece::PieceNames;
+use crate::util::{validate_full_fen, Loc};
+use crate::{color_ternary, loc, ternary};
+
+/// Something that makes a position impossible to reach in a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PositionIssue {
+    /// The FEN couldn't be read at all
+    InvalidFen,
+    MissingKing(ChessColor),
+    ExtraKings(ChessColor),
+    PawnOnBackRank(Loc),
+    /// The side not to move is in check, IE its king could be captured
+    OpponentInCheck(ChessColor),
+    /// No pawn could have just moved two squares past the en passent target
+    ImpossibleEnPassant(Loc),
+    /// Castling rights with the king or rook off its starting square, `true` for kingside
+    ImpossibleCastling(ChessColor, bool),
+}
+impl PositionIssue {
+    pub(crate) fn description(&self) -> String {
+        match self {
+            PositionIssue::InvalidFen => "Invalid FEN".to_string(),
+            PositionIssue::MissingKing(color) => format!("{:?} has no king", color),
+            PositionIssue::ExtraKings(color) => format!("{:?} has more than one king", color),
+            PositionIssue::PawnOnBackRank(loc) => format!("Pawn on {}", loc.as_notation()),
+            PositionIssue::OpponentInCheck(color) => {
+                format!(
+                    "{:?} is in check but it's {:?}'s move",
+                    color,
+                    color.other()
+                )
+            }
+            PositionIssue::ImpossibleEnPassant(loc) => {
+                format!("No pawn could have just passed {}", loc.as_notation())
+            }
+            PositionIssue::ImpossibleCastling(color, kingside) => format!(
+                "{:?} can't castle {} with the king or rook moved",
+                color,
+                ternary!(*kingside, "kingside", "queenside")
+            ),
+        }
+    }
+}
+
+/// Issues as one line, for logging or error messages
+pub(crate) fn describe_issues(issues: &[PositionIssue]) -> String {
+    issues
+        .iter()
+        .map(|issue| issue.description())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// FEN of an EPD line, with its `id` if it has one
+/// - EPD is the first four fields of FEN followed by `;` separated operations, the move counters are taken from the
+///   `hmvc` and `fmvn` operations if they're there
+pub(crate) fn epd_to_fen(line: &str) -> Option<(String, Option<String>)> {
+    let mut fields = line.splitn(5, ' ');
+    let position: Vec<_> = fields.by_ref().take(4).collect();
+    if position.len() < 4 {
+        return None;
+    }
+
+    let mut halfmoves = "0".to_string();
+    let mut fullmoves = "1".to_string();
+    let mut id = None;
+    for operation in fields.next().unwrap_or("").split(';') {
+        let Some((opcode, operand)) = operation.trim().split_once(' ') else {
+            continue;
+        };
+        let operand = operand.trim().trim_matches('"').to_string();
+        match opcode {
+            "hmvc" => halfmoves = operand,
+            "fmvn" => fullmoves = operand,
+            "id" => id = Some(operand),
+            _ => {}
+        }
+    }
+    Some((
+        format!("{} {} {}", position.join(" "), halfmoves, fullmoves),
+        id,
+    ))
+}
+
+impl Board {
+    /// Everything that makes this position unreachable in a game, empty if it's legal
+    /// - Doesn't need moves or the state worked out, so it works on boards straight from [Board::parse_fen]
+    pub(crate) fn validate_position(&self) -> Vec<PositionIssue> {
+        let mut issues = vec![];
+
+        for color in [ChessColor::White, ChessColor::Black] {
+            let kings = self
+                .raw
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|piece| piece.name == PieceNames::King && piece.color == color)
+                .count();
+            match kings {
+                0 => issues.push(PositionIssue::MissingKing(color)),
+                1 => {}
+                _ => issues.push(PositionIssue::ExtraKings(color)),
+            }
+        }
+
+        for piece in self.raw.iter().flatten().flatten() {
+            if piece.name == PieceNames::Pawn && (piece.pos.1 == 0 || piece.pos.1 == 7) {
+                issues.push(PositionIssue::PawnOnBackRank(piece.pos));
+            }
+        }
+
+        // Checks only make sense with one king each
+        if issues.is_empty() {
+            let other = self.turn.other();
+            let (white_king, black_king) = self.kings();
+            let king = color_ternary!(other, white_king, black_king).unwrap();
+            if self.attacks(self.turn).contains(&king) {
+                issues.push(PositionIssue::OpponentInCheck(other));
+            }
+        }
+
+        if let Some(target) = self.en_passent {
+            // The pawn that moved belongs to the side not to move, and is just past the target
+            let direction = color_ternary!(self.turn, 1, -1);
+            let pawn = target.offset(0, direction).and_then(|loc| self.get(&loc));
+            let start = target.offset(0, -direction);
+            let possible = target.1 == color_ternary!(self.turn, 2, 5)
+                && pawn.is_some_and(|p| p.name == PieceNames::Pawn && p.color != self.turn)
+                && self.get(&target).is_none()
+                && start.is_some_and(|loc| self.get(&loc).is_none());
+            if !possible {
+                issues.push(PositionIssue::ImpossibleEnPassant(target));
+            }
+        }
+
+        let rights = [
+            (ChessColor::White, true, self.castle.white_kingside),
+            (ChessColor::White, false, self.castle.white_queenside),
+            (ChessColor::Black, true, self.castle.black_kingside),
+            (ChessColor::Black, false, self.castle.black_queenside),
+        ];
+        for (color, kingside, right) in rights {
+            let y = color_ternary!(color, 7, 0);
+            let unmoved = |loc: Loc, name: PieceNames| {
+                self.get(&loc)
+                    .is_some_and(|p| p.name == name && p.color == color)
+            };
+            if right
+                && !(unmoved(loc!(4, y), PieceNames::King)
+                    && unmoved(loc!(ternary!(kingside, 7, 0), y), PieceNames::Rook))
+            {
+                issues.push(PositionIssue::ImpossibleCastling(color, kingside));
+            }
+        }
+
+        issues
+    }
+
+    /// Board from a FEN string, or why it can't be played from
+    pub(crate) fn try_from_fen(fen: &str) -> Result<Board, Vec<PositionIssue>> {
+        if !validate_full_fen(fen) {
+            return Err(vec![PositionIssue::InvalidFen]);
+        }
+
+        let mut board = Board::parse_fen(fen).map_err(|_| vec![PositionIssue::InvalidFen])?;
+        let issues = board.validate_position();
+        if !issues.is_empty() {
+            return Err(issues);
+        }
+        board.update_things(true);
+        Ok(board)
+    }
+}
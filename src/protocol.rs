@@ -0,0 +1,100 @@
+//! Shared pieces of the text protocols GUIs talk to engines with, IE [CECP](crate::xboard)
+//!
+//! The GUI sends one command per line on stdin, and the engine replies with lines on stdout. Logging goes to stderr so
+//! it doesn't get mixed in
+
+use std::io::{stdin, stdout, BufRead, Write};
+
+use crate::agent::{AgentId, SearchInfo, SearchLimits};
+use crate::board::Board;
+use crate::conf::DEFAULT_FEN;
+use crate::pgn::{parse_move, promotion_piece};
+use crate::pieces::piece::PieceNames;
+use crate::position::PositionIssue;
+use crate::util::Loc;
+
+/// Engine side of a protocol
+pub(crate) trait Protocol {
+    /// Handles one command, pushing the lines to reply with to `out`
+    /// - Returns `false` once the GUI wants the engine to quit
+    fn handle(&mut self, command: &str, out: &mut Vec<String>) -> bool;
+}
+
+/// Answers commands from stdin until the GUI quits or closes it
+pub(crate) fn run(protocol: &mut impl Protocol) {
+    let mut stdout = stdout();
+    for line in stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        let mut out = vec![];
+        let keep_going = protocol.handle(line.trim(), &mut out);
+        for reply in out {
+            writeln!(stdout, "{}", reply).unwrap();
+        }
+        stdout.flush().unwrap();
+
+        if !keep_going {
+            break;
+        }
+    }
+}
+
+/// Game a protocol plays, with the positions before each move so they can be taken back
+#[derive(Clone, Debug)]
+pub(crate) struct ProtocolGame {
+    pub(crate) board: Board,
+    history: Vec<Board>,
+}
+impl ProtocolGame {
+    pub(crate) fn new() -> ProtocolGame {
+        ProtocolGame {
+            board: Board::from_fen(DEFAULT_FEN),
+            history: vec![],
+        }
+    }
+
+    /// Starts over from `fen`, keeping the current game if it isn't a legal position
+    pub(crate) fn set_fen(&mut self, fen: &str) -> Result<(), Vec<PositionIssue>> {
+        self.board = Board::try_from_fen(fen)?;
+        self.history.clear();
+        Ok(())
+    }
+
+    /// Plays a move for the side to move, in coordinate notation or SAN
+    pub(crate) fn play(&mut self, text: &str) -> Result<(Loc, Loc), String> {
+        if self.board.is_over() {
+            return Err("game is over".to_string());
+        }
+        let (from, to) =
+            parse_move(&self.board, text).ok_or_else(|| "not a legal move".to_string())?;
+        self.board.promote_to = promotion_piece(text).unwrap_or(PieceNames::Queen);
+        self.apply(&from, &to);
+        Ok((from, to))
+    }
+
+    /// Plays a legal move, IE the engine's, keeping the position before it to take back
+    pub(crate) fn apply(&mut self, from: &Loc, to: &Loc) {
+        let mut before = self.board.clone();
+        before.promote_to = PieceNames::Queen;
+        self.history.push(before);
+        self.board.move_piece(from, to, true);
+    }
+
+    /// Takes back the last `plies` moves, returns `false` if there weren't that many
+    pub(crate) fn undo(&mut self, plies: usize) -> bool {
+        if plies > self.history.len() {
+            return false;
+        }
+        for _ in 0..plies {
+            self.board = self.history.pop().unwrap();
+        }
+        true
+    }
+
+    /// The minimax agent's move for the side to move, and what it found
+    pub(crate) fn think(&self, limits: &SearchLimits) -> (Option<(Loc, Loc)>, Option<SearchInfo>) {
+        AgentId::MINIMAX.get_move(&self.board, limits)
+    }
+}
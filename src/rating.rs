@@ -0,0 +1,131 @@
+//! Rough Elo rating of the player, from their results against the agents
+//!
+//! Each agent setting is given a fixed rating, guessed from how it plays, so the player's rating is only comparable
+//! to other players of this program
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent::AgentId;
+use crate::settings::Difficulty;
+use crate::ternary;
+
+/// Rating before any games are played
+pub(crate) const STARTING_RATING: f64 = 1200.0;
+
+/// Games the rating is provisional for, moving further with each result
+const PROVISIONAL_GAMES: u32 = 10;
+
+/// Most a single game moves the rating, once it's no longer provisional
+const K_FACTOR: f64 = 32.0;
+
+/// Agent settings that can be suggested, weakest first
+pub(crate) const OPPONENTS: [Opponent; 8] = [
+    Opponent::new(AgentId::RANDOM, None, Difficulty::Normal),
+    Opponent::new(AgentId::GREEDY, None, Difficulty::Normal),
+    Opponent::new(AgentId::FIXED_DEPTH, None, Difficulty::Normal),
+    Opponent::new(AgentId::MINIMAX, Some(2), Difficulty::Normal),
+    Opponent::new(AgentId::MINIMAX, Some(4), Difficulty::Normal),
+    Opponent::new(AgentId::MINIMAX, None, Difficulty::Easy),
+    Opponent::new(AgentId::MINIMAX, None, Difficulty::Normal),
+    Opponent::new(AgentId::MINIMAX, None, Difficulty::Hard),
+];
+
+/// How an agent was set up for a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Opponent {
+    pub(crate) agent: AgentId,
+    /// [GameOptions::max_depth](crate::game::GameOptions::max_depth)
+    pub(crate) max_depth: Option<u8>,
+    /// Only matters for agents that search by time
+    pub(crate) difficulty: Difficulty,
+}
+impl Opponent {
+    pub(crate) const fn new(
+        agent: AgentId,
+        max_depth: Option<u8>,
+        difficulty: Difficulty,
+    ) -> Opponent {
+        Opponent {
+            agent,
+            max_depth,
+            difficulty,
+        }
+    }
+
+    /// Guessed rating of the agent, `None` if it doesn't play on its own
+    pub(crate) fn rating(&self) -> Option<f64> {
+        self.agent.rating(self.max_depth, self.difficulty)
+    }
+
+    /// IE `Minimax, depth 4` or `Minimax, Hard`
+    pub(crate) fn name(&self) -> String {
+        match (self.agent.searches(), self.max_depth) {
+            (true, Some(depth)) => format!("{}, depth {}", self.agent, depth),
+            (true, None) => format!("{}, {:?}", self.agent, self.difficulty),
+            (false, _) => self.agent.to_string(),
+        }
+    }
+}
+
+/// Guessed rating of an agent that searches to `max_depth`, or by time at `difficulty`
+pub(crate) fn searched_rating(max_depth: Option<u8>, difficulty: Difficulty) -> f64 {
+    match (max_depth, difficulty) {
+        (Some(depth), _) => 900.0 + depth as f64 * 125.0,
+        (None, Difficulty::Easy) => 1500.0,
+        (None, Difficulty::Normal) => 1750.0,
+        (None, Difficulty::Hard) => 1900.0,
+    }
+}
+
+/// Expected score, from `0.0` to `1.0`, of a player rated `rating` against one rated `opponent`
+pub(crate) fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Rating {
+    pub(crate) rating: f64,
+    /// Rated games played
+    pub(crate) games: u32,
+}
+impl Default for Rating {
+    fn default() -> Rating {
+        Rating {
+            rating: STARTING_RATING,
+            games: 0,
+        }
+    }
+}
+impl Rating {
+    /// Updates the rating with a game against an agent rated `opponent`, `score` being `1.0` for a win, `0.5` for a
+    /// draw, and `0.0` for a loss
+    pub(crate) fn add(&mut self, opponent: f64, score: f64) {
+        // Moves twice as far at first, to get near the player's strength sooner
+        let k = K_FACTOR * ternary!(self.provisional(), 2.0, 1.0);
+        self.rating += k * (score - expected_score(self.rating, opponent));
+        self.games += 1;
+    }
+
+    pub(crate) fn provisional(&self) -> bool {
+        self.games < PROVISIONAL_GAMES
+    }
+
+    /// Agent setting closest in rating to the player, for an even game
+    pub(crate) fn suggested(&self) -> Opponent {
+        *OPPONENTS
+            .iter()
+            .min_by_key(|opponent| (opponent.rating().unwrap() - self.rating).abs() as i64)
+            .unwrap()
+    }
+
+    /// IE `Rating: 1450 (12 games)`, with a `?` while provisional
+    pub(crate) fn label(&self) -> String {
+        format!(
+            "Rating: {:.0}{} ({} game{})",
+            self.rating,
+            ternary!(self.provisional(), "?", ""),
+            self.games,
+            ternary!(self.games == 1, "", "s")
+        )
+    }
+}
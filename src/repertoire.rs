@@ -0,0 +1,164 @@
+//! Opening repertoire, the move chosen for each position reached in the lines added to it
+//!
+//! Positions are drilled with spaced repetition, each one is reviewed less often the more times in a row it's played
+//! right, and starts over when it's played wrong
+
+use std::collections::BTreeMap;
+#[cfg(not(target_family = "wasm"))]
+use std::fs::{read_to_string, write};
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{Board, ChessColor};
+use crate::pgn::san;
+use crate::util::Loc;
+
+/// Where the repertoire is saved to and loaded from
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const REPERTOIRE_PATH: &str = "repertoire.json";
+
+const DAY: f64 = 60.0 * 60.0 * 24.0;
+
+/// Days until the next review for each level, a position goes up a level when played right
+const INTERVALS: [f64; 6] = [0.0, 1.0, 3.0, 7.0, 14.0, 30.0];
+
+/// Seconds until a position played wrong comes back, so it's retried later in the same session
+const RETRY_DELAY: f64 = 60.0;
+
+/// FEN without the move counters, so transpositions share an entry
+pub(crate) fn position_key(board: &Board) -> String {
+    board
+        .as_fen()
+        .split(' ')
+        .take(4)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Entry {
+    pub(crate) mov: (Loc, Loc),
+    pub(crate) san: String,
+    /// Index into [INTERVALS]
+    pub(crate) level: usize,
+    /// Unix time the position is next reviewed
+    pub(crate) due: f64,
+    /// Times the position was played wrong
+    pub(crate) mistakes: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Repertoire {
+    /// [position_key] to the move chosen there
+    pub(crate) positions: BTreeMap<String, Entry>,
+}
+impl Repertoire {
+    /// Adds the moves of `color` in a line, returning how many positions were new or changed
+    /// - Changing the move of a position starts its reviews over
+    pub(crate) fn add_line(
+        &mut self,
+        start_fen: &str,
+        moves: &[(Loc, Loc)],
+        color: ChessColor,
+        now: f64,
+    ) -> usize {
+        let mut board = Board::from_fen(start_fen);
+        let mut added = 0;
+        for (from, to) in moves {
+            if board.turn == color {
+                let key = position_key(&board);
+                if self
+                    .positions
+                    .get(&key)
+                    .is_none_or(|entry| entry.mov != (*from, *to))
+                {
+                    self.positions.insert(
+                        key,
+                        Entry {
+                            mov: (*from, *to),
+                            san: san(&board, from, to),
+                            level: 0,
+                            due: now,
+                            mistakes: 0,
+                        },
+                    );
+                    added += 1;
+                }
+            }
+            board.move_piece(from, to, true);
+        }
+        added
+    }
+
+    /// Position due the longest, if any are due
+    pub(crate) fn next_due(&self, now: f64) -> Option<&str> {
+        self.positions
+            .iter()
+            .filter(|(_, entry)| entry.due <= now)
+            .min_by(|(_, a), (_, b)| a.due.total_cmp(&b.due))
+            .map(|(key, _)| key.as_str())
+    }
+
+    pub(crate) fn due_count(&self, now: f64) -> usize {
+        self.positions
+            .values()
+            .filter(|entry| entry.due <= now)
+            .count()
+    }
+
+    /// Schedules the next review of a position after it was played
+    pub(crate) fn review(&mut self, key: &str, correct: bool, now: f64) {
+        let Some(entry) = self.positions.get_mut(key) else {
+            return;
+        };
+
+        if correct {
+            entry.level = (entry.level + 1).min(INTERVALS.len() - 1);
+            entry.due = now + INTERVALS[entry.level] * DAY;
+        } else {
+            entry.level = 0;
+            entry.due = now + RETRY_DELAY;
+            entry.mistakes += 1;
+        }
+    }
+}
+#[cfg(not(target_family = "wasm"))]
+impl Repertoire {
+    pub(crate) fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write(path, json).map_err(|err| format!("Can't write {}: {}", path, err))
+    }
+
+    /// Loads the repertoire, or an empty one if it hasn't been saved yet
+    pub(crate) fn load(path: &str) -> Result<Repertoire, String> {
+        if !Path::new(path).exists() {
+            return Ok(Repertoire::default());
+        }
+        let json = read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        serde_json::from_str(&json).map_err(|err| format!("Invalid repertoire {}: {}", path, err))
+    }
+}
+
+/// A position from the repertoire being drilled
+#[derive(Clone, Debug)]
+pub(crate) struct Drill {
+    pub(crate) repertoire: Repertoire,
+    /// [position_key] of the position on the board
+    pub(crate) key: String,
+    /// Whether the move played was right, `None` until one is played
+    pub(crate) result: Option<bool>,
+}
+impl Drill {
+    /// Shown in the side panel
+    pub(crate) fn status(&self, now: f64) -> String {
+        let entry = &self.repertoire.positions[&self.key];
+        let result = match self.result {
+            None => "Your move".to_string(),
+            Some(true) => "Correct!".to_string(),
+            Some(false) => format!("Wrong, {}", entry.san),
+        };
+        format!("Drill: {} due\n{}", self.repertoire.due_count(now), result)
+    }
+}
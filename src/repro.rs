@@ -0,0 +1,238 @@
+//! Dumps of the agent's decisions, to reproduce engine bugs players report
+//!
+//! `,` in game saves everything the agent's last search depended on, and what it found, to [DECISION_PATH]. Running
+//! `chess-ai --repro [file]` searches the position again the same way and lists anything that came out differently
+//!
+//! Searches start from an empty transposition table, so rerunning to the depth the dump reached gives the same result
+//! on the same version, even if the original was cut off by time
+
+#[cfg(not(target_family = "wasm"))]
+use std::fs::read_to_string;
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use std::fs::write;
+#[cfg(not(target_family = "wasm"))]
+use std::process::exit;
+
+use quad_rand::srand;
+use serde::{Deserialize, Serialize};
+
+use crate::agent::{in_book, AgentId, SearchInfo, SearchLimits};
+use crate::board::{Board, ChessColor};
+use crate::pgn::coordinate;
+use crate::position::{describe_issues, PositionIssue};
+use crate::util::Loc;
+use crate::variant::Variant;
+
+/// Where decisions are dumped to, and read from by default
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const DECISION_PATH: &str = "decision.json";
+
+/// What an agent searched and found for one move, see the module docs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Decision {
+    /// Version that made the decision, searches can change between versions
+    pub(crate) version: String,
+    pub(crate) fen: String,
+    pub(crate) variant: Variant,
+    /// Side the agent played
+    pub(crate) turn: ChessColor,
+    pub(crate) agent: AgentId,
+    /// Wether the position was in the opening book
+    pub(crate) in_book: bool,
+    /// Opening the move was taken from the book as
+    pub(crate) opening: Option<String>,
+    /// Seed the random generator was given just before the agent started, for agents that pick moves at random
+    pub(crate) seed: u64,
+    /// Seconds the search could take, `None` for no limit
+    pub(crate) max_time: Option<f64>,
+    pub(crate) max_depth: Option<u8>,
+    pub(crate) multi_pv: usize,
+    /// Move picked in coordinate notation, `None` if the agent didn't pick one
+    pub(crate) mov: Option<String>,
+    /// Deepest depth finished, `0` for book moves and agents that don't search
+    pub(crate) depth: u8,
+    /// Score for white, [MAX](crate::agent::MAX) for book moves
+    pub(crate) score: i32,
+    pub(crate) pv: Vec<String>,
+    pub(crate) nodes: u64,
+    /// Positions in the transposition table once the deepest depth finished
+    pub(crate) tt_entries: usize,
+}
+impl Decision {
+    /// The agent starting to search `board` within `limits`, with the random generator seeded with `seed`
+    pub(crate) fn start(board: &Board, agent: AgentId, limits: &SearchLimits, seed: u64) -> Decision {
+        Decision {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            fen: board.as_fen(),
+            variant: board.variant,
+            turn: board.turn,
+            agent,
+            in_book: in_book(board),
+            opening: None,
+            seed,
+            max_time: Some(limits.max_time).filter(|time| time.is_finite()),
+            max_depth: limits.max_depth,
+            multi_pv: limits.multi_pv,
+            mov: None,
+            depth: 0,
+            score: 0,
+            pv: vec![],
+            nodes: 0,
+            tt_entries: 0,
+        }
+    }
+
+    /// Fills in what the search found, `opening` being the book opening it was taken from
+    pub(crate) fn finish(
+        &mut self,
+        mov: Option<(Loc, Loc)>,
+        info: Option<&SearchInfo>,
+        opening: Option<String>,
+    ) {
+        // Decisions are only started on boards that were played on
+        let Ok(board) = self.board() else {
+            return;
+        };
+        self.mov = mov.map(|(from, to)| coordinate(&board, &from, &to));
+        self.opening = opening;
+        if let Some(info) = info {
+            self.depth = info.depth;
+            self.score = info.score;
+            self.pv = coordinate_line(&board, &info.pv);
+            self.nodes = info.nodes;
+            self.tt_entries = info.tt_entries;
+        }
+    }
+
+    /// Position the agent searched, with the agent playing the side to move, or why the dumped FEN can't be played
+    pub(crate) fn board(&self) -> Result<Board, Vec<PositionIssue>> {
+        let mut board = match Board::try_from_fen(&self.fen) {
+            Ok(board) => board,
+            // White plays without a king in horde
+            Err(issues)
+                if self.variant == Variant::Horde
+                    && issues == [PositionIssue::MissingKing(ChessColor::White)] =>
+            {
+                Board::from_fen(&self.fen)
+            }
+            Err(issues) => return Err(issues),
+        };
+        board.set_variant(self.variant);
+        board.agent_color = self.turn;
+        board.player_color = self.turn.other();
+        Ok(board)
+    }
+
+    /// Searches the position again, to the depth the decision reached so running out of time doesn't change it
+    pub(crate) fn rerun(&self) -> Result<Decision, Vec<PositionIssue>> {
+        let mut limits = SearchLimits::new(self.max_time.unwrap_or(f64::INFINITY));
+        limits.multi_pv = self.multi_pv;
+        limits.max_depth = self.max_depth;
+        if self.depth != 0 {
+            limits.max_time = f64::INFINITY;
+            limits.max_depth = Some(self.depth);
+        }
+
+        let board = self.board()?;
+        srand(self.seed);
+        let mut rerun = Decision::start(&board, self.agent, &limits, self.seed);
+        // Kept as they were, so only what the search found is compared
+        rerun.max_time = self.max_time;
+        rerun.max_depth = self.max_depth;
+        let (mov, info) = self.agent.get_move(&board, &limits);
+        rerun.finish(mov, info.as_ref(), self.opening.clone());
+        Ok(rerun)
+    }
+
+    /// What came out differently in `other`, IE `score: 30 -> 25`, empty if it was reproduced
+    pub(crate) fn differences(&self, other: &Decision) -> Vec<String> {
+        let fields = [
+            ("version", self.version.clone(), other.version.clone()),
+            (
+                "move",
+                format!("{:?}", self.mov),
+                format!("{:?}", other.mov),
+            ),
+            ("depth", self.depth.to_string(), other.depth.to_string()),
+            ("score", self.score.to_string(), other.score.to_string()),
+            ("pv", self.pv.join(" "), other.pv.join(" ")),
+            ("nodes", self.nodes.to_string(), other.nodes.to_string()),
+            (
+                "tt entries",
+                self.tt_entries.to_string(),
+                other.tt_entries.to_string(),
+            ),
+        ];
+        fields
+            .into_iter()
+            .filter(|(_, before, after)| before != after)
+            .map(|(name, before, after)| format!("{}: {} -> {}", name, before, after))
+            .collect()
+    }
+
+    #[cfg(feature = "gui")]
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write(path, json).map_err(|err| format!("Can't write {}: {}", path, err))
+    }
+
+    /// Reads a decision from `path`, IE a dump the player sent, checking its position can be played
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn load(path: &str) -> Result<Decision, String> {
+        let json = read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        let decision: Decision = serde_json::from_str(&json)
+            .map_err(|err| format!("Invalid decision in {}: {}", path, err))?;
+        decision.board().map_err(|issues| {
+            format!("Invalid position in {}: {}", path, describe_issues(&issues))
+        })?;
+        Ok(decision)
+    }
+}
+
+/// `moves` played out from `board`, in coordinate notation
+fn coordinate_line(board: &Board, moves: &[(Loc, Loc)]) -> Vec<String> {
+    let mut board = board.clone();
+    moves
+        .iter()
+        .map(|(from, to)| {
+            let text = coordinate(&board, from, to);
+            board.move_piece(from, to, false);
+            text
+        })
+        .collect()
+}
+
+/// Entry point for `chess-ai --repro [file]`, exits with `1` if the decision can't be read or came out differently
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn run_cli(args: &[String]) {
+    let path = args.first().map_or(DECISION_PATH, String::as_str);
+    let decision = Decision::load(path).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+
+    println!(
+        "Rerunning {:?} for {:?} on {}",
+        decision.agent, decision.turn, decision.fen
+    );
+    let rerun = decision.rerun().unwrap_or_else(|issues| {
+        eprintln!("Invalid position: {}", describe_issues(&issues));
+        exit(1);
+    });
+    let differences = decision.differences(&rerun);
+    if differences.is_empty() {
+        println!(
+            "Reproduced {} at depth {}, score {}",
+            decision.mov.as_deref().unwrap_or("no move"),
+            decision.depth,
+            decision.score
+        );
+        return;
+    }
+    println!("Came out differently:");
+    for difference in differences {
+        println!("  {}", difference);
+    }
+    exit(1);
+}
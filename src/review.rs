@@ -0,0 +1,252 @@
+//! Game review, picking the moments of a finished game worth a second look out of its background analysis
+//!
+//! The analysis of every move can also be exported as CSV or JSON, for statistics in other programs
+
+#[cfg(not(target_family = "wasm"))]
+use std::fs::write;
+
+use serde::Serialize;
+
+use crate::board::{Board, ChessColor};
+use crate::board_eval::{display_score, is_mate, mate_moves};
+use crate::pgn::{nag, san};
+use crate::util::Loc;
+use crate::{color_ternary, ternary};
+
+/// Where the per-move analysis is exported to, see [MoveAnalysis]
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const ANALYSIS_CSV_PATH: &str = "analysis.csv";
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const ANALYSIS_JSON_PATH: &str = "analysis.json";
+
+/// Advantage in centipawns a side needs to count as ahead, for [MomentKind::TurningPoint]
+const AHEAD_THRESHOLD: i32 = 100;
+
+/// Why a move was picked as a key moment, the first that fits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MomentKind {
+    /// The side to move had a forced mate, and lost it
+    MissedMate,
+    /// The side that was ahead is behind after the move
+    TurningPoint,
+    /// The move lost as much as a blunder, `??` in PGN
+    Blunder,
+}
+impl MomentKind {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            MomentKind::MissedMate => "Missed mate",
+            MomentKind::TurningPoint => "Turning point",
+            MomentKind::Blunder => "Blunder",
+        }
+    }
+}
+
+/// A move worth a second look
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct KeyMoment {
+    /// Moves into the game before the move
+    pub(crate) ply: usize,
+    pub(crate) kind: MomentKind,
+    /// IE `14... Qxd4`
+    pub(crate) label: String,
+    /// Scores for white before and after the move
+    pub(crate) before: i32,
+    pub(crate) after: i32,
+    /// What the analysis would have played instead, if it was found
+    pub(crate) best: Option<(Loc, Loc)>,
+}
+impl KeyMoment {
+    /// IE `14... Qxd4: Blunder`
+    pub(crate) fn row(&self) -> String {
+        format!("{}: {}", self.label, self.kind.name())
+    }
+
+    /// IE `+2.1 to -1.5`, for white
+    pub(crate) fn swing(&self) -> String {
+        format!(
+            "{} to {}",
+            display_score(self.before, ChessColor::White),
+            display_score(self.after, ChessColor::White)
+        )
+    }
+}
+
+/// Key moments of the moves played from `start`, in order
+/// - `evals` and `best` are [Analysis::evals](crate::analysis::Analysis::evals) and
+///   [Analysis::best](crate::analysis::Analysis::best), moves without a score on both sides are skipped
+pub(crate) fn key_moments(
+    start: &Board,
+    moves: &[(Loc, Loc)],
+    evals: &[Option<i32>],
+    best: &[Option<(Loc, Loc)>],
+) -> Vec<KeyMoment> {
+    let mut board = start.clone();
+    let mut moments = vec![];
+    for (ply, (from, to)) in moves.iter().enumerate() {
+        let scores = evals
+            .get(ply)
+            .copied()
+            .flatten()
+            .zip(evals.get(ply + 1).copied().flatten());
+        if let Some((before, after)) = scores {
+            // From the side that moved
+            let color = board.turn;
+            let (ours_before, ours_after) =
+                color_ternary!(color, (before, after), (-before, -after));
+
+            let mating = |score: i32| score > 0 && is_mate(score);
+
+            let kind = if mating(ours_before) && !mating(ours_after) {
+                Some(MomentKind::MissedMate)
+            } else if ours_before >= AHEAD_THRESHOLD && ours_after <= -AHEAD_THRESHOLD {
+                Some(MomentKind::TurningPoint)
+            } else if nag(before, after, color) == Some(4) {
+                Some(MomentKind::Blunder)
+            } else {
+                None
+            };
+
+            if let Some(kind) = kind {
+                moments.push(KeyMoment {
+                    ply,
+                    kind,
+                    label: format!(
+                        "{}{} {}",
+                        board.full_moves() + 1,
+                        ternary!(color == ChessColor::Black, "...", "."),
+                        san(&board, from, to)
+                    ),
+                    before,
+                    after,
+                    best: best
+                        .get(ply)
+                        .copied()
+                        .flatten()
+                        .filter(|best| best != &(*from, *to)),
+                });
+            }
+        }
+        board.move_piece(from, to, true);
+    }
+    moments
+}
+
+/// Key moments of a finished game, shown in the side panel while stepping through it
+#[derive(Clone, Debug)]
+pub(crate) struct Review {
+    pub(crate) moments: Vec<KeyMoment>,
+    /// Index of the moment being looked at
+    pub(crate) selected: Option<usize>,
+}
+
+/// Columns of the exported CSV, the fields of [MoveAnalysis]
+const CSV_HEADER: &str =
+    "ply,move_number,color,played,best,eval_before,mate_before,eval_after,mate_after,depth,classification";
+
+/// Analysis of one move, a row of the exported analysis
+/// - Scores are for white, in centipawns, or moves until mate in the `mate` fields
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct MoveAnalysis {
+    pub(crate) ply: usize,
+    pub(crate) move_number: u32,
+    pub(crate) color: ChessColor,
+    /// SAN of the move played
+    pub(crate) played: String,
+    /// SAN of what the analysis would have played
+    pub(crate) best: Option<String>,
+    pub(crate) eval_before: Option<i32>,
+    pub(crate) mate_before: Option<i32>,
+    pub(crate) eval_after: Option<i32>,
+    pub(crate) mate_after: Option<i32>,
+    /// Depth the position before the move was searched to
+    pub(crate) depth: Option<u8>,
+    /// `Best`, `Good`, `Inaccuracy`, `Mistake`, or `Blunder`, `None` without scores on both sides
+    pub(crate) classification: Option<&'static str>,
+}
+
+/// How good a move was, from the scores for white before and after it
+fn classify(before: i32, after: i32, color: ChessColor) -> &'static str {
+    match nag(before, after, color) {
+        Some(4) => "Blunder",
+        Some(2) => "Mistake",
+        Some(6) => "Inaccuracy",
+        _ => "Good",
+    }
+}
+
+/// Analysis of each move played from `start`, from the background analysis like [key_moments]
+pub(crate) fn move_analysis(
+    start: &Board,
+    moves: &[(Loc, Loc)],
+    evals: &[Option<i32>],
+    best: &[Option<(Loc, Loc)>],
+    depths: &[Option<u8>],
+) -> Vec<MoveAnalysis> {
+    let mut board = start.clone();
+    let mut rows = vec![];
+    for (ply, (from, to)) in moves.iter().enumerate() {
+        let before = evals.get(ply).copied().flatten();
+        let after = evals.get(ply + 1).copied().flatten();
+        let best = best.get(ply).copied().flatten();
+        // Mates are kept out of the evals, so they can be averaged
+        let eval = |score: Option<i32>| score.filter(|score| !is_mate(*score));
+        let mate = |score: Option<i32>| score.and_then(mate_moves);
+
+        rows.push(MoveAnalysis {
+            ply,
+            move_number: board.full_moves() + 1,
+            color: board.turn,
+            played: san(&board, from, to),
+            best: best.map(|(from, to)| san(&board, &from, &to)),
+            eval_before: eval(before),
+            mate_before: mate(before),
+            eval_after: eval(after),
+            mate_after: mate(after),
+            depth: depths.get(ply).copied().flatten(),
+            classification: match (before, after) {
+                _ if best == Some((*from, *to)) => Some("Best"),
+                (Some(before), Some(after)) => Some(classify(before, after, board.turn)),
+                _ => None,
+            },
+        });
+        board.move_piece(from, to, true);
+    }
+    rows
+}
+
+/// Exported analysis as CSV, with a header row
+pub(crate) fn analysis_csv(rows: &[MoveAnalysis]) -> String {
+    let field = |value: Option<String>| value.unwrap_or_default();
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{:?},{},{},{},{},{},{},{},{}\n",
+            row.ply,
+            row.move_number,
+            row.color,
+            row.played,
+            field(row.best.clone()),
+            field(row.eval_before.map(|eval| eval.to_string())),
+            field(row.mate_before.map(|mate| mate.to_string())),
+            field(row.eval_after.map(|eval| eval.to_string())),
+            field(row.mate_after.map(|mate| mate.to_string())),
+            field(row.depth.map(|depth| depth.to_string())),
+            row.classification.unwrap_or_default(),
+        ));
+    }
+    csv
+}
+
+/// Writes the analysis to [ANALYSIS_CSV_PATH] and [ANALYSIS_JSON_PATH]
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn export_analysis(rows: &[MoveAnalysis]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(rows).map_err(|err| err.to_string())?;
+    for (path, text) in [
+        (ANALYSIS_CSV_PATH, analysis_csv(rows)),
+        (ANALYSIS_JSON_PATH, json),
+    ] {
+        write(path, text).map_err(|err| format!("Can't write {}: {}", path, err))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Puzzle rush, solving as many mates as possible before the clock runs out
+//!
+//! - Puzzles are mates in one to three, given easiest first and shuffled within each length, then the longest ones
+//!   again once every puzzle has been given
+//! - Any move that still mates in time counts, checked with the [mate solver](crate::mate), and the opponent answers
+//!   with its longest defense
+//! - A wrong move costs one of the [RUSH_LIVES] and skips the puzzle. The best score for each length is kept in the
+//!   player's [stats](crate::stats::PlayerStats)
+
+use quad_rand::ChooseRandom;
+
+use crate::board::{Board, BoardState};
+use crate::mate::{defend, solve_mate};
+use crate::util::Loc;
+
+/// Lengths of a rush that can be picked, in minutes
+pub(crate) const RUSH_MINUTES: [u32; 2] = [3, 5];
+/// Wrong moves allowed before the rush ends
+pub(crate) const RUSH_LIVES: u8 = 3;
+/// Seconds the last move of a puzzle is shown for before the next one
+pub(crate) const RUSH_PAUSE: f64 = 0.8;
+
+/// (FEN, moves to mate in), the shortest mates first
+pub(crate) const PUZZLES: [(&str, u8); 17] = [
+    ("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1),
+    ("6rk/6pp/7N/8/8/8/8/6K1 w - - 0 1", 1),
+    ("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", 1),
+    (
+        "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+        1,
+    ),
+    (
+        "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2",
+        1,
+    ),
+    (
+        "rnbqkbnr/ppppp2p/5p2/6p1/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3",
+        1,
+    ),
+    ("k7/8/2K5/8/8/8/8/7R w - - 0 1", 2),
+    ("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", 2),
+    ("1r4k1/r7/8/8/8/8/8/7K b - - 0 1", 2),
+    ("k7/8/8/1K6/8/8/8/3Q4 w - - 0 1", 2),
+    ("3k4/8/2K5/8/8/8/8/7R w - - 0 1", 2),
+    ("1k6/8/8/8/8/8/8/R3K2R w KQ - 0 1", 2),
+    ("k7/8/8/8/2K5/8/8/1Q6 w - - 0 1", 3),
+    ("k7/8/8/3K4/8/8/8/7R w - - 0 1", 3),
+    ("7r/8/8/8/3k4/8/8/K7 b - - 0 1", 3),
+    ("7k/8/8/8/5K2/8/8/6Q1 w - - 0 1", 3),
+    ("1q6/8/8/2k5/8/8/8/K7 b - - 0 1", 3),
+];
+
+/// What a move played in a rush puzzle did, see [Rush::answer]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RushAnswer {
+    /// Mated, on to the next puzzle
+    Solved,
+    /// Still mates, with the opponent's reply to play
+    Reply((Loc, Loc)),
+    /// Doesn't mate in time, with the move that would have
+    Wrong(Option<(Loc, Loc)>),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Rush {
+    pub(crate) minutes: u32,
+    pub(crate) solved: u32,
+    pub(crate) lives: u8,
+    /// Best score for [Rush::minutes] before this rush
+    pub(crate) best: u32,
+    /// Index in [PUZZLES] of the puzzle on the board
+    pub(crate) puzzle: usize,
+    /// Moves the player has left to mate in
+    pub(crate) moves_left: u8,
+    /// Puzzles still to give, popped from the end
+    queue: Vec<usize>,
+    /// When to go on to the next puzzle, while the last move of one is shown
+    pub(crate) next_at: Option<f64>,
+    /// Wether the time or lives ran out
+    pub(crate) over: bool,
+}
+impl Rush {
+    /// Call [Rush::next_puzzle] for the first puzzle
+    pub(crate) fn new(minutes: u32, best: u32) -> Rush {
+        let mut rush = Rush {
+            minutes,
+            solved: 0,
+            lives: RUSH_LIVES,
+            best,
+            puzzle: 0,
+            moves_left: 0,
+            queue: vec![],
+            next_at: None,
+            over: false,
+        };
+        for moves in (1..=3).rev() {
+            rush.queue_puzzles(moves);
+        }
+        rush
+    }
+
+    /// Queues the puzzles that mate in `moves` to be given next, in a random order
+    fn queue_puzzles(&mut self, moves: u8) {
+        let mut puzzles: Vec<usize> = (0..PUZZLES.len())
+            .filter(|i| PUZZLES[*i].1 == moves)
+            .collect();
+        puzzles.shuffle();
+        self.queue.extend(puzzles);
+    }
+
+    /// Moves on to the next puzzle, returning its board
+    pub(crate) fn next_puzzle(&mut self) -> Board {
+        if self.queue.is_empty() {
+            self.queue_puzzles(3);
+        }
+        self.puzzle = self.queue.pop().unwrap();
+        self.moves_left = PUZZLES[self.puzzle].1;
+        self.next_at = None;
+        Board::from_fen(PUZZLES[self.puzzle].0)
+    }
+
+    /// Checks the move `from` to `to` played on `board`, taking a life if it doesn't mate in time
+    pub(crate) fn answer(&mut self, board: &Board, from: &Loc, to: &Loc) -> RushAnswer {
+        let mut after = board.clone();
+        after.move_piece(from, to, true);
+        if after.state == BoardState::Checkmate(board.turn.other()) {
+            self.solved += 1;
+            return RushAnswer::Solved;
+        }
+
+        if self.moves_left > 1 && !after.is_over() {
+            if let Some(line) = defend(&after, self.moves_left - 1) {
+                self.moves_left -= 1;
+                return RushAnswer::Reply(line[0]);
+            }
+        }
+        self.lives = self.lives.saturating_sub(1);
+        self.over = self.lives == 0;
+        let mate = solve_mate(board, self.moves_left).and_then(|line| line.first().copied());
+        RushAnswer::Wrong(mate)
+    }
+
+    /// Shown in the side panel
+    pub(crate) fn status(&self, board: &Board) -> String {
+        let puzzle = match self.over {
+            true => match self.solved > self.best {
+                true => "Rush over, new best!".to_string(),
+                false => "Rush over".to_string(),
+            },
+            false => format!("{:?} mates in {}", board.turn, self.moves_left),
+        };
+        format!(
+            "Puzzle rush: {} solved\nLives: {}, best: {}\n{}",
+            self.solved,
+            self.lives,
+            self.best.max(self.solved),
+            puzzle
+        )
+    }
+}
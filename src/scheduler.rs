@@ -0,0 +1,48 @@
+//! Shares a time budget each frame between the background work in [Game::update](crate::game::Game::update), IE
+//! network polling and analysis, so several at once don't drop frames
+//!
+//! - Tasks run in turn until the budget is spent, and the rest go first next frame
+//! - Tasks that can work in slices, IE searches on the web, stop at the deadline they're given
+//! - Input, animation, and drawing aren't tasks, they run every frame
+
+use derive_new::new;
+
+/// Seconds of background work each frame, half a frame at 60fps
+pub(crate) const FRAME_BUDGET: f64 = 0.008;
+
+/// Background work, given when it should be done by (from [date::now](macroquad::miniquad::date::now))
+pub(crate) type Task<T> = fn(&mut T, f64);
+
+#[derive(Clone, Copy, Debug, new)]
+pub(crate) struct Scheduler {
+    /// Seconds of background work each frame, see [FRAME_BUDGET]
+    budget: f64,
+
+    /// Index of the task that runs first next frame
+    #[new(value = "0")]
+    next: usize,
+}
+impl Scheduler {
+    /// Runs `tasks` on `target` in turn until this frame's budget is spent, returns how many ran
+    /// - At least one runs each frame, and the first one moves along each frame so none are always last
+    /// - `clock` is the current time in seconds
+    pub(crate) fn run<T>(
+        &mut self,
+        target: &mut T,
+        tasks: &[Task<T>],
+        clock: impl Fn() -> f64,
+    ) -> usize {
+        let deadline = clock() + self.budget;
+        for ran in 0..tasks.len() {
+            let task = (self.next + ran) % tasks.len();
+            if ran > 0 && clock() >= deadline {
+                self.next = task;
+                return ran;
+            }
+            tasks[task](target, deadline);
+        }
+
+        self.next = (self.next + 1) % tasks.len().max(1);
+        tasks.len()
+    }
+}
@@ -0,0 +1,192 @@
+//! Self-play training data generator, run with `chess-ai selfplay`
+//!
+//! Plays the minimax agent against itself at a fixed depth and appends every searched position to a file, one per line:
+//!
+//! `game;fen;score;result`
+//!
+//! - `game` is the id of the game the position is from
+//! - `score` is the search score for white, in centipawns
+//! - `result` is the game result for white, `1.0`, `0.5`, or `0.0`
+//!
+//! Games are only written once they finish, so rerunning with the same output file resumes where it left off
+
+use std::fs::{read_to_string, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::{available_parallelism, scope};
+
+use quad_rand::ChooseRandom;
+use rustc_hash::FxHashSet;
+
+use crate::agent::{search_depth, MAX};
+use crate::board::{Board, BoardState, ChessColor};
+use crate::conf::DEFAULT_FEN;
+use crate::hashset;
+
+const USAGE: &str = "Usage: chess-ai selfplay [options]
+
+Options:
+  --games <n>         Total games the output file should have (default 100)
+  --depth <n>         Search depth for every move (default 3)
+  --threads <n>       Games played at once (default all cores)
+  --random-plies <n>  Random moves at the start of each game, for variety (default 4)
+  --max-plies <n>     Games longer than this are scored as draws (default 300)
+  --out <path>        File to append to (default selfplay.txt)";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SelfPlayOptions {
+    pub(crate) games: usize,
+    pub(crate) depth: u8,
+    pub(crate) threads: usize,
+    pub(crate) random_plies: usize,
+    pub(crate) max_plies: usize,
+    pub(crate) out: PathBuf,
+}
+impl SelfPlayOptions {
+    pub(crate) fn parse(args: &[String]) -> Result<SelfPlayOptions, String> {
+        let mut options = SelfPlayOptions {
+            games: 100,
+            depth: 3,
+            threads: available_parallelism().map_or(1, |n| n.get()),
+            random_plies: 4,
+            max_plies: 300,
+            out: PathBuf::from("selfplay.txt"),
+        };
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            let number = || {
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid number for {}: {}", flag, value))
+            };
+
+            match flag.as_str() {
+                "--games" => options.games = number()?,
+                "--depth" => options.depth = number()?.clamp(1, u8::MAX as usize) as u8,
+                "--threads" => options.threads = number()?.max(1),
+                "--random-plies" => options.random_plies = number()?,
+                "--max-plies" => options.max_plies = number()?,
+                "--out" => options.out = PathBuf::from(value),
+                _ => return Err(format!("Unknown option {}", flag)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Entry point for `chess-ai selfplay`, exits on invalid arguments
+pub(crate) fn run_cli(args: &[String]) {
+    let options = SelfPlayOptions::parse(args).unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        exit(1);
+    });
+
+    let played = run(&options);
+    println!("Played {} games into {}", played, options.out.display());
+}
+
+/// Ids of the games already in the output file
+fn completed_games(options: &SelfPlayOptions) -> FxHashSet<usize> {
+    let mut games = hashset! {};
+    if let Ok(contents) = read_to_string(&options.out) {
+        for line in contents.lines() {
+            if let Some(Ok(id)) = line.split(';').next().map(str::parse) {
+                games.insert(id);
+            }
+        }
+    }
+    games
+}
+
+/// Plays games until the output file has `options.games` of them, returns how many were played
+pub(crate) fn run(options: &SelfPlayOptions) -> usize {
+    let completed = completed_games(options);
+    let remaining = options.games.saturating_sub(completed.len());
+    if remaining == 0 {
+        return 0;
+    }
+
+    let next_id = AtomicUsize::new(completed.iter().max().map_or(0, |id| id + 1));
+    let left = AtomicUsize::new(remaining);
+    let file = Mutex::new(
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&options.out)
+            .unwrap_or_else(|err| panic!("Can't open {}: {}", options.out.display(), err)),
+    );
+
+    scope(|s| {
+        for _ in 0..options.threads.min(remaining) {
+            s.spawn(|| {
+                while left
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok()
+                {
+                    let id = next_id.fetch_add(1, Ordering::SeqCst);
+                    let lines = play_game(options, id);
+                    file.lock().unwrap().write_all(lines.as_bytes()).unwrap();
+                    println!("Game {} done", id);
+                }
+            });
+        }
+    });
+
+    remaining
+}
+
+/// Plays one game, returning its lines for the output file
+fn play_game(options: &SelfPlayOptions, id: usize) -> String {
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    let mut positions = vec![];
+
+    for ply in 0..options.max_plies {
+        if board.is_over() {
+            break;
+        }
+
+        let (from, to) = if ply < options.random_plies {
+            *board.moves(board.turn).choose().unwrap()
+        } else {
+            let (score, best) = search_depth(&board, options.depth);
+            let Some(best) = best else {
+                break;
+            };
+
+            // Book moves aren't searched, so they have no real score
+            if score != MAX {
+                positions.push((board.as_fen(), score));
+            }
+            best
+        };
+        board.move_piece(&from, &to, true);
+    }
+
+    let result = match board.state {
+        BoardState::Checkmate(ChessColor::Black)
+        | BoardState::ThreeChecks(ChessColor::Black)
+        | BoardState::Exploded(ChessColor::Black)
+        | BoardState::KingCaptured(ChessColor::Black)
+        | BoardState::Outraced(ChessColor::Black)
+        | BoardState::NoPieces(ChessColor::Black) => "1.0",
+        BoardState::Checkmate(ChessColor::White)
+        | BoardState::ThreeChecks(ChessColor::White)
+        | BoardState::Exploded(ChessColor::White)
+        | BoardState::KingCaptured(ChessColor::White)
+        | BoardState::Outraced(ChessColor::White)
+        | BoardState::NoPieces(ChessColor::White) => "0.0",
+        _ => "0.5",
+    };
+    positions
+        .iter()
+        .map(|(fen, score)| format!("{};{};{};{}\n", id, fen, score, result))
+        .collect()
+}
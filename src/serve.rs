@@ -0,0 +1,277 @@
+//! HTTP API for the engine, run with `chess-ai --serve [port] [--host <address>]`, so scripts can use it without
+//! linking it
+//!
+//! Every endpoint takes the position as `fen`, and answers with JSON:
+//!
+//! - `/bestmove?fen=...&ms=1000` searches for `ms` milliseconds, IE
+//!   `{"bestmove":"e2e4","san":"e4","depth":5,"score":30,"pv":["e2e4","e7e5"],"nodes":12345}`
+//! - `/legal?fen=...` lists the legal moves, IE `{"moves":[{"move":"e2e4","san":"e4"}]}`
+//! - Errors are `{"error":"..."}` with a `400`, `404`, or `503`
+//!
+//! Each request is answered on its own thread, so slow searches don't hold up the rest
+//!
+//! - Only this machine can connect unless `--host` is given, IE `--host 0.0.0.0` for the whole network
+//! - Connections and searches past [MAX_CONNECTIONS] and [MAX_SEARCHES] are turned away with a `503`
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::spawn;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::agent::{AgentId, SearchLimits};
+use crate::board::Board;
+use crate::pgn::{coordinate, san};
+use crate::position::describe_issues;
+
+pub(crate) const DEFAULT_PORT: u16 = 8000;
+/// Address served on when `--host` isn't given, only reachable from this machine
+const DEFAULT_HOST: &str = "127.0.0.1";
+
+/// Search time when `ms` isn't given
+const DEFAULT_MS: u64 = 1000;
+/// Longest search a request can ask for
+const MAX_MS: u64 = 60_000;
+/// Most requests answered at once
+const MAX_CONNECTIONS: usize = 32;
+/// Most searches run at once, each can take [MAX_MS]
+pub(crate) const MAX_SEARCHES: usize = 4;
+/// How long a client has to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const BUSY: &str = "503 Service Unavailable";
+
+/// Requests being answered
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+/// Searches running for `/bestmove`
+pub(crate) static SEARCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// One of a limited number of things running at once, given back when dropped
+pub(crate) struct Slot(&'static AtomicUsize);
+impl Slot {
+    /// Takes a slot from `count`, `None` if `max` are already taken
+    pub(crate) fn take(count: &'static AtomicUsize, max: usize) -> Option<Slot> {
+        count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |taken| {
+                (taken < max).then_some(taken + 1)
+            })
+            .ok()
+            .map(|_| Slot(count))
+    }
+}
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Reply to `/bestmove`, moves are in coordinate notation
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct BestMove {
+    pub(crate) bestmove: String,
+    pub(crate) san: String,
+    /// `0` for book moves
+    pub(crate) depth: u8,
+    /// Centipawns for white, or `20000` less the half moves until white mates, negative when black does
+    /// - `null` for book moves, which aren't searched
+    pub(crate) score: Option<i32>,
+    pub(crate) pv: Vec<String>,
+    pub(crate) nodes: u64,
+}
+
+/// A legal move from `/legal`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct LegalMove {
+    #[serde(rename = "move")]
+    pub(crate) mov: String,
+    pub(crate) san: String,
+}
+
+/// Host and port from `[port] [--host <address>]`, `None` if they can't be read
+pub(crate) fn parse_args(args: &[String]) -> Option<(String, u16)> {
+    let (mut host, mut port) = (DEFAULT_HOST.to_string(), DEFAULT_PORT);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--host" => host = args.next()?.clone(),
+            _ => port = arg.parse().ok()?,
+        }
+    }
+    Some((host, port))
+}
+
+/// Serves the API until the process is killed
+pub(crate) fn run_cli(args: &[String]) {
+    let Some((host, port)) = parse_args(args) else {
+        eprintln!("Usage: chess-ai --serve [port] [--host <address>]");
+        exit(1);
+    };
+
+    let listener = TcpListener::bind((host.as_str(), port)).unwrap_or_else(|err| {
+        eprintln!("Can't serve on {}:{}: {}", host, port, err);
+        exit(1);
+    });
+    eprintln!("Serving the engine on http://{}:{}", host, port);
+    serve(listener);
+}
+
+/// Answers requests from `listener` until it fails
+pub(crate) fn serve(listener: TcpListener) {
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            break;
+        };
+        let Some(slot) = Slot::take(&CONNECTIONS, MAX_CONNECTIONS) else {
+            let body = json!({ "error": "Too many requests, try again later" }).to_string();
+            respond(&mut stream, BUSY, "application/json", &body).ok();
+            continue;
+        };
+        // Errors are clients leaving early, which is fine
+        spawn(move || {
+            answer(stream).ok();
+            drop(slot);
+        });
+    }
+}
+
+fn answer(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let (status, body) = match handle(&read_request(&stream)?) {
+        Ok(body) => ("200 OK", body),
+        Err((status, err)) => (status, json!({ "error": err }).to_string()),
+    };
+    respond(&mut stream, status, "application/json", &body)
+}
+
+/// Reads a request from `stream`, returning what it asked for, IE `/state`
+/// - Also used by [broadcasts](crate::broadcast)
+pub(crate) fn read_request(stream: &TcpStream) -> io::Result<String> {
+    stream.set_nonblocking(false)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Headers aren't needed, but are read so closing doesn't reset the connection
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    Ok(request.split_whitespace().nth(1).unwrap_or("/").to_string())
+}
+
+/// Sends a whole response and closes the connection, `status` is IE `200 OK`
+pub(crate) fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// JSON answer to a request for `target`, IE `/legal?fen=...`, or the status and why it failed
+pub(crate) fn handle(target: &str) -> Result<String, (&'static str, String)> {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let param = |name: &str| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| decode(value))
+    };
+    let bad = |err: String| ("400 Bad Request", err);
+
+    let board = || -> Result<Board, (&'static str, String)> {
+        let fen = param("fen").ok_or_else(|| bad("Missing fen".to_string()))?;
+        Board::try_from_fen(&fen).map_err(|issues| bad(describe_issues(&issues)))
+    };
+
+    match path {
+        "/bestmove" => {
+            let board = board()?;
+            let ms = match param("ms") {
+                Some(ms) => ms
+                    .parse::<u64>()
+                    .map_err(|_| bad(format!("Bad ms {}", ms)))?,
+                None => DEFAULT_MS,
+            };
+            let _search = Slot::take(&SEARCHES, MAX_SEARCHES)
+                .ok_or_else(|| (BUSY, "Too many searches, try again later".to_string()))?;
+            let limits = SearchLimits::new(ms.min(MAX_MS) as f64 / 1000.0);
+            let (mov, info) = AgentId::MINIMAX.get_move(&board, &limits);
+            let (from, to) = mov.ok_or_else(|| bad("The game is over".to_string()))?;
+
+            // Searches too short to finish a depth still have the move
+            let info = info.unwrap_or_default();
+            let mut after = board.clone();
+            let pv = info
+                .pv
+                .iter()
+                .map(|(from, to)| {
+                    let text = coordinate(&after, from, to);
+                    after.move_piece(from, to, false);
+                    text
+                })
+                .collect();
+            let reply = BestMove {
+                bestmove: coordinate(&board, &from, &to),
+                san: san(&board, &from, &to),
+                depth: info.depth,
+                score: (info.depth > 0).then_some(info.score),
+                pv,
+                nodes: info.nodes,
+            };
+            Ok(serde_json::to_string(&reply).unwrap())
+        }
+        "/legal" => {
+            let board = board()?;
+            let moves: Vec<_> = board
+                .moves(board.turn)
+                .iter()
+                .map(|(from, to)| LegalMove {
+                    mov: coordinate(&board, from, to),
+                    san: san(&board, from, to),
+                })
+                .collect();
+            Ok(json!({ "moves": moves }).to_string())
+        }
+        _ => Err(("404 Not Found", format!("Unknown endpoint {}", path))),
+    }
+}
+
+/// Undoes URL encoding, with `+` as a space
+fn decode(text: &str) -> String {
+    let mut bytes = vec![];
+    let mut chars = text.bytes();
+    while let Some(byte) = chars.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = chars.by_ref().take(2).collect();
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => bytes.push(byte),
+                    None => {
+                        bytes.push(b'%');
+                        bytes.extend(hex);
+                    }
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
@@ -0,0 +1,305 @@
+//! User settings, changed through the settings overlay and read throughout the GUI
+
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use std::fs::{read_to_string, write};
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+use std::path::Path;
+use std::ptr::addr_of_mut;
+
+use derive_new::new;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gui")]
+use crate::board::ChessColor;
+#[cfg(feature = "gui")]
+use crate::conf::THEMES;
+
+/// Choices for [Settings::resign_score], cycled through in the settings overlay
+#[cfg(feature = "gui")]
+const RESIGN_SCORES: [Option<i32>; 4] = [Some(500), Some(900), Some(1500), None];
+
+/// Choices for [Settings::threat_warning], cycled through in the settings overlay
+#[cfg(feature = "gui")]
+const THREAT_GAINS: [Option<i32>; 4] = [None, Some(150), Some(300), Some(500)];
+
+/// Choices for [Settings::ui_scale], cycled through in the settings overlay
+#[cfg(feature = "gui")]
+const UI_SCALES: [f32; 5] = [0.75, 1.0, 1.25, 1.5, 2.0];
+
+/// Where the settings are kept between sessions
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+pub(crate) const SETTINGS_PATH: &str = "settings.json";
+
+static mut SETTINGS: Option<Settings> = None;
+pub(crate) fn settings() -> &'static mut Settings {
+    unsafe { (*addr_of_mut!(SETTINGS)).get_or_insert_with(Settings::new) }
+}
+
+/// How long the agent gets to think
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+impl Difficulty {
+    /// Max time in seconds the minimax agent can search for
+    pub(crate) fn max_time(&self) -> f64 {
+        match self {
+            Difficulty::Easy => 1.0,
+            Difficulty::Normal => 4.0,
+            Difficulty::Hard => 8.0,
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn next(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+}
+
+/// Which pieces are drawn, hiding some to practice visualizing the board
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Visibility {
+    Normal,
+    /// Only the player's own pieces
+    Fog,
+    /// No pieces, only the board
+    Blindfold,
+}
+impl Visibility {
+    #[cfg(feature = "gui")]
+    pub(crate) fn next(&self) -> Visibility {
+        match self {
+            Visibility::Normal => Visibility::Fog,
+            Visibility::Fog => Visibility::Blindfold,
+            Visibility::Blindfold => Visibility::Normal,
+        }
+    }
+
+    /// Wether pieces of `color` are drawn for someone playing `player`
+    #[cfg(feature = "gui")]
+    pub(crate) fn shows(&self, color: ChessColor, player: ChessColor) -> bool {
+        match self {
+            Visibility::Normal => true,
+            Visibility::Fog => color == player,
+            Visibility::Blindfold => false,
+        }
+    }
+}
+
+/// How the piece a pawn promotes to is picked, when it's played by clicking
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Promotion {
+    AutoQueen,
+    /// From a menu every time
+    Ask,
+    /// A queen, or from a menu while Ctrl is held
+    CtrlToAsk,
+}
+impl Promotion {
+    #[cfg(feature = "gui")]
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Promotion::AutoQueen => "Auto-queen",
+            Promotion::Ask => "Ask",
+            Promotion::CtrlToAsk => "Ctrl to pick",
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn next(&self) -> Promotion {
+        match self {
+            Promotion::AutoQueen => Promotion::Ask,
+            Promotion::Ask => Promotion::CtrlToAsk,
+            Promotion::CtrlToAsk => Promotion::AutoQueen,
+        }
+    }
+
+    /// Wether the piece is picked from a menu, with `ctrl` being wether Ctrl is held
+    #[cfg(feature = "gui")]
+    pub(crate) fn asks(&self, ctrl: bool) -> bool {
+        match self {
+            Promotion::AutoQueen => false,
+            Promotion::Ask => true,
+            Promotion::CtrlToAsk => ctrl,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, new)]
+pub(crate) struct Settings {
+    /// Index into [THEMES]
+    #[new(value = "0")]
+    pub(crate) theme: usize,
+
+    /// How big text, buttons, and margins are, `1.0` is normal, the board grows with the window either way
+    #[new(value = "1.0")]
+    pub(crate) ui_scale: f32,
+
+    /// Wether the margins around the board are thinner, for small screens
+    #[new(value = "false")]
+    pub(crate) compact_layout: bool,
+
+    /// Wether move and capture sounds are played
+    #[new(value = "true")]
+    pub(crate) sound: bool,
+
+    /// Wether a buzz plays when a move can't be made, along with the square flashing red
+    #[new(value = "true")]
+    pub(crate) illegal_sound: bool,
+
+    /// Wether the board turns to face the side to move after each move, in local games between two people
+    #[new(value = "false")]
+    pub(crate) auto_flip: bool,
+
+    /// Wether pieces slide to their new square
+    #[new(value = "true")]
+    pub(crate) animation: bool,
+
+    /// Loudness of move and capture sounds, from `0.0` to `1.0`
+    #[new(value = "1.0")]
+    pub(crate) volume: f32,
+
+    /// How fast pieces slide, `1.0` is normal
+    #[new(value = "1.0")]
+    pub(crate) animation_speed: f32,
+
+    /// Wether castling, en passant, and promotion are announced over the board when they're played
+    #[new(value = "true")]
+    pub(crate) announce_moves: bool,
+
+    /// Wether the frame rate drops while nothing on screen is changing, to save battery
+    #[new(value = "true")]
+    pub(crate) low_power: bool,
+
+    #[new(value = "Difficulty::Normal")]
+    pub(crate) difficulty: Difficulty,
+
+    /// Wether every position is analyzed in the background, to annotate exported PGN
+    #[new(value = "false")]
+    pub(crate) analysis: bool,
+
+    /// Wether the agent's best alternatives to its move, and the reply it expects, are drawn as faint arrows after it
+    /// moves
+    #[new(value = "false")]
+    pub(crate) engine_arrows: bool,
+
+    /// Wether the arrows the player draws are shown, see [DrawOptions](crate::board_extras::DrawOptions) for the
+    /// arrow layers
+    #[new(value = "true")]
+    pub(crate) user_arrows: bool,
+
+    /// Wether moves shown to the player are drawn as arrows, IE the right move in a drill
+    #[new(value = "true")]
+    pub(crate) hint_arrows: bool,
+
+    /// Wether the move the agent threatens is drawn as an arrow along with the threat warning
+    #[new(value = "true")]
+    pub(crate) threat_arrows: bool,
+
+    /// Wether the agent records how it searched the root moves of its last move, for the search tree view, off since
+    /// it slows the search a little
+    #[new(value = "false")]
+    pub(crate) search_tree: bool,
+
+    /// Training mode hiding pieces, moves can still be clicked or typed
+    #[new(value = "Visibility::Normal")]
+    pub(crate) visibility: Visibility,
+
+    /// Wether clicking a square only one piece can move to plays that move, without selecting the piece first
+    #[new(value = "false")]
+    pub(crate) smart_moves: bool,
+
+    #[new(value = "Promotion::AutoQueen")]
+    pub(crate) promotion: Promotion,
+
+    /// Wether clocks stop while a menu is open, they always run in LAN games
+    #[new(value = "true")]
+    pub(crate) pause_clock: bool,
+
+    /// Wether the agent's clock runs while it thinks, off to give it as long as it needs
+    #[new(value = "true")]
+    pub(crate) agent_clock: bool,
+
+    /// How much, in centipawns, the agent has to threaten to win for a warning to show on the player's turn, `None`
+    /// to never warn
+    /// - Threatened mates are always warned about
+    #[new(value = "None")]
+    pub(crate) threat_warning: Option<i32>,
+
+    /// How far behind, in centipawns, the agent has to be for a few moves in a row before resigning, `None` to
+    /// never resign
+    #[new(value = "Some(900)")]
+    pub(crate) resign_score: Option<i32>,
+
+    /// Wether GitHub is asked for a newer release on startup, see [updates](crate::updates)
+    /// - Settings saved before this was added keep checking
+    #[new(value = "true")]
+    #[serde(default = "checks_updates")]
+    pub(crate) check_updates: bool,
+}
+
+/// Default of [Settings::check_updates]
+fn checks_updates() -> bool {
+    true
+}
+impl Settings {
+    /// Name of the current theme
+    #[cfg(feature = "gui")]
+    pub(crate) fn theme_name(&self) -> &'static str {
+        THEMES[self.theme].0
+    }
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn next_theme(&mut self) {
+        self.theme = (self.theme + 1) % THEMES.len();
+    }
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn next_resign_score(&mut self) {
+        let index = RESIGN_SCORES
+            .iter()
+            .position(|score| *score == self.resign_score)
+            .unwrap_or(0);
+        self.resign_score = RESIGN_SCORES[(index + 1) % RESIGN_SCORES.len()];
+    }
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn next_ui_scale(&mut self) {
+        let index = UI_SCALES
+            .iter()
+            .position(|scale| *scale == self.ui_scale)
+            .unwrap_or(0);
+        self.ui_scale = UI_SCALES[(index + 1) % UI_SCALES.len()];
+    }
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn next_threat_warning(&mut self) {
+        let index = THREAT_GAINS
+            .iter()
+            .position(|gain| *gain == self.threat_warning)
+            .unwrap_or(0);
+        self.threat_warning = THREAT_GAINS[(index + 1) % THREAT_GAINS.len()];
+    }
+}
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+impl Settings {
+    pub(crate) fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write(path, json).map_err(|err| format!("Can't write {}: {}", path, err))
+    }
+
+    /// Loads the settings, or the defaults if none have been saved yet
+    pub(crate) fn load(path: &str) -> Result<Settings, String> {
+        if !Path::new(path).exists() {
+            return Ok(Settings::new());
+        }
+        let json = read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        serde_json::from_str(&json).map_err(|err| format!("Invalid settings {}: {}", path, err))
+    }
+}
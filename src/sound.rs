@@ -0,0 +1,116 @@
+//! Sounds for moves, checks, the end of the game, and the clock, played through one queue
+//!
+//! - Sounds are queued as they happen and played once a frame, so a move that gives check or ends the game only plays
+//!   the sound for that
+//! - Browsers only let a page play audio after a click or key press, so on the web sounds are dropped until then
+//!   instead of all playing at once when it's allowed
+//! - Sounds queued more than [STALE_AFTER] seconds ago, IE while the tab was in the background, are dropped too
+//! - Nothing is played while [Settings::sound](crate::settings::Settings::sound) is off
+
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use macroquad::audio::{play_sound, PlaySoundParams};
+#[cfg(target_family = "wasm")]
+use macroquad::input::{get_last_key_pressed, is_mouse_button_pressed, touches, MouseButton};
+
+use crate::assets::get_audio;
+use crate::platform::now;
+use crate::settings::settings;
+
+/// Seconds a sound can wait in the queue before it's dropped
+pub(crate) const STALE_AFTER: f64 = 0.25;
+
+/// Sounds of a move come first, in order of importance
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum SoundKind {
+    Move,
+    Capture,
+    Check,
+    GameEnd,
+    /// A move that can't be made
+    Illegal,
+    /// A clock running down, see [ClockAlert](crate::clock::ClockAlert)
+    Tick,
+}
+impl SoundKind {
+    pub(crate) fn path(&self) -> &'static str {
+        match self {
+            SoundKind::Move => "assets/sounds/move.wav",
+            SoundKind::Capture => "assets/sounds/capture.wav",
+            SoundKind::Check => "assets/sounds/check.wav",
+            SoundKind::GameEnd => "assets/sounds/game_end.wav",
+            SoundKind::Illegal => "assets/sounds/illegal.wav",
+            SoundKind::Tick => "assets/sounds/tick.wav",
+        }
+    }
+
+    /// Wether it's the sound of a move, only the most important of which is played at once
+    fn of_move(&self) -> bool {
+        *self <= SoundKind::GameEnd
+    }
+}
+
+struct SoundQueue {
+    /// (sound, when it was queued)
+    queued: Vec<(SoundKind, f64)>,
+    /// Wether the browser lets the page play audio, after the first click or key press
+    unlocked: bool,
+}
+
+lazy_static! {
+    static ref QUEUE: Mutex<SoundQueue> = Mutex::new(SoundQueue {
+        queued: vec![],
+        unlocked: cfg!(not(target_family = "wasm")),
+    });
+}
+
+/// Queues `kind` to play on this frame, see the module docs
+pub(crate) fn queue_sound(kind: SoundKind) {
+    if settings().sound {
+        QUEUE.lock().unwrap().queued.push((kind, now()));
+    }
+}
+
+/// Sounds to play from `queued` at `now`, the most important sound of a move first and then each other sound once
+pub(crate) fn to_play(queued: &[(SoundKind, f64)], now: f64) -> Vec<SoundKind> {
+    let fresh = queued
+        .iter()
+        .filter(|(_, at)| now - at <= STALE_AFTER)
+        .map(|(kind, _)| *kind);
+    let mut sounds: Vec<_> = fresh.clone().filter(|kind| !kind.of_move()).collect();
+    sounds.sort();
+    sounds.dedup();
+    if let Some(kind) = fresh.filter(SoundKind::of_move).max() {
+        sounds.insert(0, kind);
+    }
+    sounds
+}
+
+/// Plays the sounds queued since the last frame, sounds that didn't load are skipped
+pub(crate) fn play_queued() {
+    let mut queue = QUEUE.lock().unwrap();
+    #[cfg(target_family = "wasm")]
+    if !queue.unlocked {
+        queue.unlocked = is_mouse_button_pressed(MouseButton::Left)
+            || is_mouse_button_pressed(MouseButton::Right)
+            || get_last_key_pressed().is_some()
+            || !touches().is_empty();
+    }
+
+    let queued = std::mem::take(&mut queue.queued);
+    if !queue.unlocked || !settings().sound {
+        return;
+    }
+    for kind in to_play(&queued, now()) {
+        if let Ok(sound) = get_audio(kind.path()) {
+            play_sound(
+                sound,
+                PlaySoundParams {
+                    volume: settings().volume,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
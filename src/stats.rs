@@ -0,0 +1,282 @@
+//! The player's stats from their games against the agent, their [Rating] and the openings they reached
+//!
+//! Openings are named from the ECO database the agent's book is built from. Each finished game is added with how it
+//! went for the player, and where it left the book, to see which openings go well and where they tend to stray from
+//! theory
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+#[cfg(not(target_family = "wasm"))]
+use std::fs::{read_to_string, write};
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agent_opens::openings;
+use crate::board::{Board, BoardState, ChessColor};
+use crate::conf::DEFAULT_FEN;
+use crate::pgn::{result, san};
+use crate::rating::{Opponent, Rating};
+use crate::ternary;
+use crate::util::Loc;
+
+/// Where the stats are saved to and loaded from
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const STATS_PATH: &str = "stats.json";
+
+/// Openings listed on the stats screen, the most played first
+pub(crate) const SHOWN_OPENINGS: usize = 8;
+
+/// Deviation points listed under each opening
+const SHOWN_DEVIATIONS: usize = 2;
+
+/// `1.0` for a win, `0.5` for a draw, and `0.0` for a loss for `color`, `None` if the game isn't over
+pub(crate) fn score_for(state: &BoardState, color: ChessColor) -> Option<f32> {
+    let white_score = match result(state) {
+        "1-0" => 1.0,
+        "0-1" => 0.0,
+        "1/2-1/2" => 0.5,
+        _ => return None,
+    };
+    Some(ternary!(
+        color == ChessColor::White,
+        white_score,
+        1.0 - white_score
+    ))
+}
+
+/// Name of the opening `moves` from the starting position are in, and how many of them were book moves
+/// - `None` if the first move isn't in the book
+pub(crate) fn opening(moves: &[(Loc, Loc)]) -> Option<(String, usize)> {
+    let openings = openings();
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    let mut names: Vec<&String> = vec![];
+    let mut book_moves = 0;
+    for mov in moves {
+        let Some(entries) = openings.get(&board.hash) else {
+            break;
+        };
+        let played: Vec<_> = entries
+            .iter()
+            .filter(|(book, _)| book == mov)
+            .map(|(_, name)| name)
+            .collect();
+        if played.is_empty() {
+            break;
+        }
+
+        names = played;
+        book_moves += 1;
+        board.move_piece(&mov.0, &mov.1, true);
+    }
+
+    // Lines that go on from here have longer names, the one ending here is the name of the position
+    let continuing = openings.get(&board.hash);
+    let ending = names.iter().find(|name| {
+        continuing.is_none_or(|entries| entries.iter().all(|(_, other)| other != **name))
+    });
+    let name = ending.or_else(|| names.iter().min_by_key(|name| name.len()))?;
+    Some((name.to_string(), book_moves))
+}
+
+/// A game added to the stats
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PlayedGame {
+    pub(crate) opening: String,
+    /// Color the player had
+    pub(crate) color: ChessColor,
+    /// `1.0` for a win, `0.5` for a draw, and `0.0` for a loss, for the player
+    pub(crate) score: f32,
+    /// First move out of the book, IE `6... a6`, `None` if the game ended in it
+    pub(crate) deviation: Option<String>,
+}
+
+/// How the player did with an opening, over every game of it
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OpeningSummary {
+    pub(crate) name: String,
+    pub(crate) games: usize,
+    /// Share of the points the player got, from `0.0` to `1.0`
+    pub(crate) score: f32,
+    /// Moves that left the book, with how many games did, most common first
+    pub(crate) deviations: Vec<(String, usize)>,
+}
+impl OpeningSummary {
+    /// IE `Sicilian, Najdorf: 5 games, 60%`, with the most common deviation points under it
+    pub(crate) fn rows(&self) -> Vec<String> {
+        let mut rows = vec![format!(
+            "{}: {} game{}, {:.0}%",
+            self.name,
+            self.games,
+            ternary!(self.games == 1, "", "s"),
+            self.score * 100.0
+        )];
+        rows.extend(
+            self.deviations
+                .iter()
+                .take(SHOWN_DEVIATIONS)
+                .map(|(deviation, count)| format!("  Left book at {} ({})", deviation, count)),
+        );
+        rows
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct PlayerStats {
+    pub(crate) rating: Rating,
+    /// Games from the starting position, for the openings
+    pub(crate) games: Vec<PlayedGame>,
+    /// Most puzzles solved in a [puzzle rush](crate::rush), by its length in minutes
+    #[serde(default)]
+    pub(crate) rush_best: BTreeMap<u32, u32>,
+}
+impl PlayerStats {
+    /// Updates the rating with a finished game against `opponent`, returning wether it could be rated
+    pub(crate) fn add_rated(
+        &mut self,
+        opponent: &Opponent,
+        player_color: ChessColor,
+        state: &BoardState,
+    ) -> bool {
+        let (Some(rating), Some(score)) = (opponent.rating(), score_for(state, player_color))
+        else {
+            return false;
+        };
+        self.rating.add(rating, score as f64);
+        true
+    }
+
+    /// Adds a finished game from the starting position, returning wether it was in an opening to add it under
+    pub(crate) fn add_opening(
+        &mut self,
+        moves: &[(Loc, Loc)],
+        player_color: ChessColor,
+        state: &BoardState,
+    ) -> bool {
+        let Some(score) = score_for(state, player_color) else {
+            return false;
+        };
+        let Some((name, book_moves)) = opening(moves) else {
+            return false;
+        };
+
+        let deviation = moves.get(book_moves).map(|(from, to)| {
+            let mut board = Board::from_fen(DEFAULT_FEN);
+            for (from, to) in &moves[..book_moves] {
+                board.move_piece(from, to, true);
+            }
+            format!(
+                "{}{} {}",
+                board.full_moves() + 1,
+                ternary!(board.turn == ChessColor::Black, "...", "."),
+                san(&board, from, to)
+            )
+        });
+        self.games.push(PlayedGame {
+            opening: name,
+            color: player_color,
+            score,
+            deviation,
+        });
+        true
+    }
+
+    /// Adds a finished puzzle rush, returning wether it was a new best
+    pub(crate) fn add_rush(&mut self, minutes: u32, solved: u32) -> bool {
+        let best = self.rush_best.entry(minutes).or_default();
+        let new_best = solved > *best;
+        *best = (*best).max(solved);
+        new_best
+    }
+
+    /// Every opening played, the most played first
+    pub(crate) fn summaries(&self) -> Vec<OpeningSummary> {
+        let mut summaries: Vec<OpeningSummary> = vec![];
+        for game in &self.games {
+            let i = match summaries.iter().position(|s| s.name == game.opening) {
+                Some(i) => i,
+                None => {
+                    summaries.push(OpeningSummary {
+                        name: game.opening.clone(),
+                        games: 0,
+                        score: 0.0,
+                        deviations: vec![],
+                    });
+                    summaries.len() - 1
+                }
+            };
+
+            let summary = &mut summaries[i];
+            // Averaged once every game is counted
+            summary.score += game.score;
+            summary.games += 1;
+            if let Some(deviation) = &game.deviation {
+                match summary.deviations.iter_mut().find(|(d, _)| d == deviation) {
+                    Some((_, count)) => *count += 1,
+                    None => summary.deviations.push((deviation.clone(), 1)),
+                }
+            }
+        }
+
+        for summary in summaries.iter_mut() {
+            summary.score /= summary.games as f32;
+            // Stable, so ties stay in the order they were first played
+            summary.deviations.sort_by_key(|(_, count)| Reverse(*count));
+        }
+        summaries.sort_by_key(|summary| Reverse(summary.games));
+        summaries
+    }
+
+    /// Lines of the stats screen, the rating and then the openings
+    pub(crate) fn rows(&self) -> Vec<String> {
+        let mut rows = vec![
+            self.rating.label(),
+            format!("Suggested opponent: {}", self.rating.suggested().name()),
+            String::new(),
+        ];
+        if !self.rush_best.is_empty() {
+            let best: Vec<_> = self
+                .rush_best
+                .iter()
+                .map(|(minutes, solved)| format!("{} in {} min", solved, minutes))
+                .collect();
+            rows.insert(2, format!("Puzzle rush best: {}", best.join(", ")));
+        }
+        if self.games.is_empty() {
+            rows.push("Finish a game against the agent to see your openings".to_string());
+        }
+        rows.extend(
+            self.summaries()
+                .iter()
+                .take(SHOWN_OPENINGS)
+                .flat_map(OpeningSummary::rows),
+        );
+        rows
+    }
+}
+#[cfg(not(target_family = "wasm"))]
+impl PlayerStats {
+    pub(crate) fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        write(path, json).map_err(|err| format!("Can't write {}: {}", path, err))
+    }
+
+    /// Loads the stats, or empty ones if none have been saved yet
+    pub(crate) fn load(path: &str) -> Result<PlayerStats, String> {
+        if !Path::new(path).exists() {
+            return Ok(PlayerStats::default());
+        }
+        let json = read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        serde_json::from_str(&json).map_err(|err| format!("Invalid stats {}: {}", path, err))
+    }
+}
+
+/// Agent setting closest to the player's rating, from the saved stats
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn suggested_opponent() -> Opponent {
+    PlayerStats::load(STATS_PATH)
+        .unwrap_or_default()
+        .rating
+        .suggested()
+}
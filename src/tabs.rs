@@ -0,0 +1,179 @@
+//! Several games open at once, each in its own tab with its own state and background search
+//!
+//! Only the shown tab is drawn and takes input, the others keep analyzing in the background with their clocks and agents
+//! waiting
+
+#[cfg(not(target_family = "wasm"))]
+use std::fs::{read_to_string, remove_file, write};
+#[cfg(not(target_family = "wasm"))]
+use std::path::Path;
+
+use macroquad::prelude::WHITE;
+use macroquad::shapes::draw_rectangle;
+#[cfg(not(target_family = "wasm"))]
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_family = "wasm"))]
+use crate::autosave::GameRecord;
+use crate::game::{Game, MenuAction};
+use crate::keybinds::tooltip;
+use crate::layout::{layout, TAB_BAR_HEIGHT};
+use crate::platform::info;
+use crate::util::Button;
+
+/// Where the open tabs are kept between sessions
+#[cfg(not(target_family = "wasm"))]
+pub(crate) const TABS_PATH: &str = "tabs.json";
+
+/// Widest a tab gets, they shrink to fit once there's too many
+const TAB_WIDTH: f32 = 110.0;
+/// Line under the shown tab
+const SHOWN_LINE_HEIGHT: f32 = 3.0;
+
+pub(crate) struct Tabs {
+    pub(crate) games: Vec<Game>,
+    /// Index of the shown tab
+    pub(crate) current: usize,
+}
+impl Tabs {
+    pub(crate) fn new(game: Game) -> Tabs {
+        Tabs {
+            games: vec![game],
+            current: 0,
+        }
+    }
+
+    pub(crate) fn game(&mut self) -> &mut Game {
+        &mut self.games[self.current]
+    }
+
+    /// Wether the tab bar is shown, which it is once there's more than one tab
+    pub(crate) fn shown(&self) -> bool {
+        self.games.len() > 1
+    }
+
+    /// Wether any tab needs frames, see [Game::busy]
+    pub(crate) fn busy(&self) -> bool {
+        self.games.iter().any(Game::busy)
+    }
+
+    /// Runs the background work of every tab, then updates and draws the shown one with the tab bar over it
+    pub(crate) fn update(&mut self) {
+        for (i, game) in self.games.iter_mut().enumerate() {
+            if i != self.current {
+                game.update_background();
+            }
+        }
+        self.game().update();
+        if let Some(action) = self.game().tab_action.take() {
+            self.run_action(action);
+        }
+        if self.shown() {
+            self.update_bar();
+        }
+    }
+
+    pub(crate) fn run_action(&mut self, action: MenuAction) {
+        match action {
+            MenuAction::NewTab => {
+                self.games.push(Game::new());
+                self.current = self.games.len() - 1;
+            }
+            MenuAction::CloseTab => {
+                if !self.shown() {
+                    info!("Can't close the last tab");
+                    return;
+                }
+                self.games.remove(self.current);
+                self.current = self.current.min(self.games.len() - 1);
+            }
+            MenuAction::NextTab => self.current = (self.current + 1) % self.games.len(),
+            _ => {}
+        }
+    }
+
+    /// Draws a button for each tab, and one to open another, switching to the tab clicked
+    fn update_bar(&mut self) {
+        let active = self.game().overlay.is_none();
+        let layout = layout();
+        let (x, y) = (layout.margin, layout.margin);
+        // Room for the new tab button on the right
+        let room = layout.width - layout.margin * 2.0 - TAB_BAR_HEIGHT;
+        let width = TAB_WIDTH.min(room / self.games.len() as f32);
+
+        let mut clicked = None;
+        for (i, game) in self.games.iter().enumerate() {
+            let x = x + width * i as f32;
+            let mut button = Button::new(x, y, width - 2.0, TAB_BAR_HEIGHT, &game.tab_name());
+            if active && button.update() {
+                clicked = Some(i);
+            }
+            button.draw();
+            if i == self.current {
+                let line_y = y + TAB_BAR_HEIGHT - SHOWN_LINE_HEIGHT;
+                draw_rectangle(x, line_y, width - 2.0, SHOWN_LINE_HEIGHT, WHITE);
+            }
+        }
+        if let Some(i) = clicked {
+            self.current = i;
+        }
+
+        let x = x + width * self.games.len() as f32;
+        let mut new = Button::icon(x, y, TAB_BAR_HEIGHT, "+", &tooltip(MenuAction::NewTab));
+        if active && new.update() {
+            self.run_action(MenuAction::NewTab);
+        }
+        new.draw();
+    }
+
+    /// Saves the tabs to come back to next time, or removes the save if there's only one
+    /// - Finished and network games aren't kept, since there's nothing left to do in them
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn save(&self, path: &str) -> Result<(), String> {
+        let games: Vec<_> = self
+            .games
+            .iter()
+            .filter(|game| !game.board.is_over() && !game.lan_game && game.watching.is_none())
+            .map(Game::record)
+            .collect();
+        if games.len() <= 1 {
+            // Nothing to remove if there wasn't a save
+            let _ = remove_file(path);
+            return Ok(());
+        }
+
+        let saved = SavedTabs {
+            current: self.current.min(games.len() - 1),
+            games,
+        };
+        let json = serde_json::to_string(&saved).map_err(|err| err.to_string())?;
+        write(path, json).map_err(|err| format!("Can't write {}: {}", path, err))
+    }
+
+    /// Tabs saved last session, `None` if there weren't any
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) fn load(path: &str) -> Result<Option<Tabs>, String> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        let json = read_to_string(path).map_err(|err| format!("Can't read {}: {}", path, err))?;
+        let saved: SavedTabs =
+            serde_json::from_str(&json).map_err(|err| format!("Invalid tabs {}: {}", path, err))?;
+        if saved.games.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Tabs {
+            current: saved.current.min(saved.games.len() - 1),
+            games: saved.games.iter().map(Game::restore).collect(),
+        }))
+    }
+}
+
+/// What's saved of the open tabs
+#[cfg(not(target_family = "wasm"))]
+#[derive(Serialize, Deserialize)]
+struct SavedTabs {
+    games: Vec<GameRecord>,
+    current: usize,
+}
@@ -0,0 +1,3911 @@
+//! Rules tests for [Board], driven by FEN fixtures
+
+use crate::board::{Board, BoardState, ChessColor, DrawReason};
+use crate::conf::DEFAULT_FEN;
+use crate::pieces::piece::PieceNames;
+use crate::util::Loc;
+
+/// Shorthand for a move in notation, IE `mov("e2", "e4")`
+fn mov(from: &str, to: &str) -> (Loc, Loc) {
+    (Loc::from_notation(from), Loc::from_notation(to))
+}
+
+/// Plays the moves on the board in order, panicking if any of them are illegal
+fn play(board: &mut Board, moves: &[(&str, &str)]) {
+    for (from, to) in moves.iter() {
+        assert!(
+            is_legal(board, from, to),
+            "{from}{to} is illegal in {}",
+            board.as_fen()
+        );
+        board.move_piece(&Loc::from_notation(from), &Loc::from_notation(to), true);
+    }
+}
+
+fn is_legal(board: &Board, from: &str, to: &str) -> bool {
+    board.moves(board.turn).contains(&mov(from, to))
+}
+
+fn piece_at(board: &Board, notation: &str) -> Option<(PieceNames, ChessColor)> {
+    board
+        .get(&Loc::from_notation(notation))
+        .map(|piece| (piece.name, piece.color))
+}
+
+/* ------------------------------- En passant ------------------------------- */
+#[test]
+fn en_passant_capture() {
+    let mut board = Board::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1");
+    play(&mut board, &[("d7", "d5")]);
+
+    assert!(is_legal(&board, "e5", "d6"));
+    play(&mut board, &[("e5", "d6")]);
+
+    assert_eq!(piece_at(&board, "d5"), None);
+    assert_eq!(
+        piece_at(&board, "d6"),
+        Some((PieceNames::Pawn, ChessColor::White))
+    );
+}
+
+#[test]
+fn en_passant_expires() {
+    let mut board = Board::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1");
+    play(&mut board, &[("d7", "d5"), ("e1", "f1"), ("e8", "f8")]);
+
+    assert!(!is_legal(&board, "e5", "d6"));
+}
+
+#[test]
+fn en_passant_single_push() {
+    let mut board = Board::from_fen("4k3/8/3p4/4P3/8/8/8/4K3 b - - 0 1");
+    play(&mut board, &[("d6", "d5")]);
+
+    assert!(!is_legal(&board, "e5", "d6"));
+}
+
+#[test]
+fn en_passant_revealing_check() {
+    // Capturing would remove both pawns from the rank, exposing the king to the rook
+    let mut board = Board::from_fen("4k3/2p5/8/KP5r/8/8/8/8 b - - 0 1");
+    play(&mut board, &[("c7", "c5")]);
+
+    assert!(!is_legal(&board, "b5", "c6"));
+    assert!(is_legal(&board, "b5", "b6"));
+}
+
+/* -------------------------------- Castling -------------------------------- */
+#[test]
+fn castling_both_sides() {
+    let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+    assert!(is_legal(&board, "e1", "g1"));
+    assert!(is_legal(&board, "e1", "c1"));
+}
+
+#[test]
+fn castling_moves_rook() {
+    let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    play(&mut board, &[("e1", "g1"), ("e8", "c8")]);
+
+    assert_eq!(
+        piece_at(&board, "f1"),
+        Some((PieceNames::Rook, ChessColor::White))
+    );
+    assert_eq!(piece_at(&board, "h1"), None);
+    assert_eq!(
+        piece_at(&board, "d8"),
+        Some((PieceNames::Rook, ChessColor::Black))
+    );
+    assert_eq!(piece_at(&board, "a8"), None);
+}
+
+#[test]
+fn castling_out_of_check() {
+    let board = Board::from_fen("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+
+    assert!(!is_legal(&board, "e1", "g1"));
+    assert!(!is_legal(&board, "e1", "c1"));
+}
+
+#[test]
+fn castling_through_check() {
+    // f1 and d1 are attacked
+    let board = Board::from_fen("3rkr2/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+
+    assert!(!is_legal(&board, "e1", "g1"));
+    assert!(!is_legal(&board, "e1", "c1"));
+}
+
+#[test]
+fn castling_into_check() {
+    // g1 and c1 are attacked
+    let board = Board::from_fen("2r1k1r1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+
+    assert!(!is_legal(&board, "e1", "g1"));
+    assert!(!is_legal(&board, "e1", "c1"));
+}
+
+#[test]
+fn castling_blocked() {
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/RN2K1NR w KQ - 0 1");
+
+    assert!(!is_legal(&board, "e1", "g1"));
+    assert!(!is_legal(&board, "e1", "c1"));
+}
+
+#[test]
+fn castling_rights_lost() {
+    let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    play(
+        &mut board,
+        &[("h1", "h2"), ("a8", "a7"), ("h2", "h1"), ("a7", "a8")],
+    );
+
+    assert!(!is_legal(&board, "e1", "g1"));
+    assert!(is_legal(&board, "e1", "c1"));
+
+    play(&mut board, &[("e1", "d1")]);
+    assert!(!is_legal(&board, "e8", "c8"));
+    assert!(is_legal(&board, "e8", "g8"));
+}
+
+#[test]
+fn castling_without_rights() {
+    let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1");
+
+    assert!(!is_legal(&board, "e1", "g1"));
+    assert!(!is_legal(&board, "e1", "c1"));
+}
+
+/* -------------------------------- Promotion ------------------------------- */
+#[test]
+fn promotion_with_capture() {
+    let mut board = Board::from_fen("4k2r/6P1/8/8/8/8/8/4K3 w - - 0 1");
+    play(&mut board, &[("g7", "h8")]);
+
+    assert_eq!(
+        piece_at(&board, "h8"),
+        Some((PieceNames::Queen, ChessColor::White))
+    );
+    assert_eq!(piece_at(&board, "g7"), None);
+}
+
+#[test]
+fn promotion_blocked() {
+    let board = Board::from_fen("4k1r1/6P1/8/8/8/8/8/4K3 w - - 0 1");
+
+    assert!(!is_legal(&board, "g7", "g8"));
+}
+
+#[test]
+fn promotion_black() {
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/1p6/4K3 b - - 0 1");
+    play(&mut board, &[("b2", "b1")]);
+
+    assert_eq!(
+        piece_at(&board, "b1"),
+        Some((PieceNames::Queen, ChessColor::Black))
+    );
+}
+
+/* ---------------------------------- Draws --------------------------------- */
+#[test]
+fn fifty_move_draw() {
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80");
+    assert_eq!(board.state, BoardState::Normal);
+
+    play(&mut board, &[("a1", "a2")]);
+    assert_eq!(board.state, BoardState::Draw(DrawReason::FiftyMove));
+}
+
+#[test]
+fn fifty_move_mate() {
+    // The hundredth half move mates, which wins instead of drawing
+    let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80");
+    play(&mut board, &[("a1", "a8")]);
+    assert_eq!(board.state, BoardState::Checkmate(ChessColor::Black));
+}
+
+#[test]
+fn fifty_move_reset() {
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/P7/R3K3 w - - 98 80");
+    play(&mut board, &[("a2", "a3"), ("e8", "e7")]);
+
+    assert_eq!(board.state, BoardState::Normal);
+    assert!(board.as_fen().ends_with(" 1 81"));
+}
+
+#[test]
+fn fifty_move_clock_fen() {
+    // Positions set up mid game can have a clock past the move number, both are kept as given
+    for fen in [
+        "4k3/8/8/8/8/8/8/R3K3 w - - 60 12",
+        "4k3/8/8/8/8/8/8/R3K3 b - - 99 1",
+    ] {
+        assert_eq!(Board::from_fen(fen).as_fen(), fen);
+    }
+
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 b - - 99 1");
+    play(&mut board, &[("e8", "e7")]);
+    assert_eq!(board.state, BoardState::Draw(DrawReason::FiftyMove));
+}
+
+#[test]
+fn threefold_repetition() {
+    let mut board = Board::from_fen("4k1n1/8/8/8/8/8/8/4K1N1 w - - 0 1");
+    let cycle = [("g1", "f3"), ("g8", "f6"), ("f3", "g1"), ("f6", "g8")];
+
+    // The starting position counts as the first occurrence
+    play(&mut board, &cycle);
+    assert_eq!(board.state, BoardState::Normal);
+
+    play(&mut board, &cycle);
+    assert_eq!(board.state, BoardState::Draw(DrawReason::Repetition));
+}
+
+#[test]
+fn insufficient_material() {
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/3q4/4K3 w - - 0 1");
+    play(&mut board, &[("e1", "d2")]);
+
+    assert_eq!(
+        board.state,
+        BoardState::Draw(DrawReason::InsufficientMaterial)
+    );
+}
+
+/* --------------------------- Stalemate and mate --------------------------- */
+#[test]
+fn stalemate() {
+    let board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+
+    assert_eq!(board.state, BoardState::Stalemate);
+    assert!(board.is_over());
+}
+
+#[test]
+fn stalemate_not_check() {
+    let board = Board::from_fen("7k/8/6K1/8/8/8/8/5Q2 b - - 0 1");
+
+    assert_eq!(board.state, BoardState::Normal);
+}
+
+#[test]
+fn back_rank_mate() {
+    let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1");
+    play(&mut board, &[("a1", "a8")]);
+
+    assert_eq!(board.state, BoardState::Checkmate(ChessColor::Black));
+}
+
+#[test]
+fn pinned_piece() {
+    let board = Board::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1");
+
+    assert!(board
+        .moves(ChessColor::White)
+        .iter()
+        .all(|(from, _)| from != &Loc::from_notation("e2")));
+}
+
+#[test]
+fn en_passant_fen_target() {
+    let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    play(&mut board, &[("e2", "e4")]);
+
+    assert_eq!(board.en_passent, Some(Loc::from_notation("e3")));
+    assert_eq!(
+        board.as_fen(),
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+    );
+}
+
+#[test]
+fn en_passant_fen_import() {
+    let fen = "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2";
+    let mut board = Board::from_fen(fen);
+    assert_eq!(board.as_fen(), fen);
+
+    play(&mut board, &[("e5", "d6")]);
+    assert_eq!(piece_at(&board, "d5"), None);
+}
+
+/* ---------------------------------- Pawns --------------------------------- */
+#[test]
+fn pawn_double_push_from_start() {
+    let board = Board::from_fen("4k3/3p4/8/8/8/8/4P3/4K3 w - - 0 1");
+    assert!(is_legal(&board, "e2", "e4"));
+
+    let board = Board::from_fen("4k3/3p4/8/8/8/8/4P3/4K3 b - - 0 1");
+    assert!(is_legal(&board, "d7", "d5"));
+}
+
+#[test]
+fn pawn_double_push_blocked() {
+    let board = Board::from_fen("4k3/8/8/8/4n3/8/4P3/4K3 w - - 0 1");
+    assert!(is_legal(&board, "e2", "e3"));
+    assert!(!is_legal(&board, "e2", "e4"));
+
+    let board = Board::from_fen("4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1");
+    assert!(!is_legal(&board, "e2", "e3"));
+    assert!(!is_legal(&board, "e2", "e4"));
+}
+
+#[test]
+fn pawn_double_push_other_start_rank() {
+    // Black pawn on white's starting rank, and white pawn on black's
+    let board = Board::from_fen("4k3/8/8/8/8/8/p7/4K3 b - - 0 1");
+    assert_eq!(board.moves(ChessColor::Black).len(), 6);
+    assert!(is_legal(&board, "a2", "a1"));
+
+    let board = Board::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(board.moves(ChessColor::White).len(), 6);
+    assert!(is_legal(&board, "a7", "a8"));
+}
+
+#[test]
+fn pawn_double_push_after_moving() {
+    let board = Board::from_fen("4k3/8/8/8/8/4P3/8/4K3 w - - 0 1");
+    assert!(is_legal(&board, "e3", "e4"));
+    assert!(!is_legal(&board, "e3", "e5"));
+}
+
+#[test]
+fn castling_rights_fen() {
+    for fen in [
+        "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R b Qk - 0 1",
+        "r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1",
+    ] {
+        assert_eq!(Board::from_fen(fen).as_fen(), fen);
+    }
+
+    let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1");
+    assert!(is_legal(&board, "e1", "g1"));
+    assert!(!is_legal(&board, "e1", "c1"));
+
+    let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b Kq - 0 1");
+    assert!(!is_legal(&board, "e8", "g8"));
+    assert!(is_legal(&board, "e8", "c8"));
+}
+
+#[test]
+fn castling_rook_captured() {
+    let mut board = Board::from_fen("r3k2r/8/8/8/8/8/6b1/R3K2R b KQkq - 0 1");
+    play(&mut board, &[("g2", "h1")]);
+
+    assert!(board.as_fen().contains(" Qkq "));
+    assert!(!is_legal(&board, "e1", "g1"));
+}
+
+/* ------------------------------- Evaluation ------------------------------- */
+#[test]
+fn undeveloped_minors() {
+    let mut board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    assert_eq!(board.undeveloped_minors(ChessColor::White), 4);
+    assert_eq!(board.undeveloped_minors(ChessColor::Black), 4);
+
+    play(
+        &mut board,
+        &[("g1", "f3"), ("b8", "c6"), ("e2", "e4"), ("g8", "f6")],
+    );
+    play(&mut board, &[("f1", "c4")]);
+    assert_eq!(board.undeveloped_minors(ChessColor::White), 2);
+    assert_eq!(board.undeveloped_minors(ChessColor::Black), 2);
+}
+
+#[test]
+fn opening_penalties() {
+    use crate::board_eval::Phase;
+
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    assert_eq!(board.phase(), Phase::Opening);
+
+    // Knight out and back in a few times
+    play(
+        &mut board,
+        &[("g1", "f3"), ("g8", "f6"), ("f3", "g5"), ("f6", "g8")],
+    );
+    assert_eq!(board.repeated_moves, (1, 1));
+    play(&mut board, &[("g5", "f3"), ("b8", "c6"), ("f3", "e5")]);
+    assert_eq!(board.repeated_moves, (3, 1));
+    // Recaptures don't count
+    play(&mut board, &[("c6", "e5")]);
+    assert_eq!(board.repeated_moves, (3, 1));
+
+    // The same position reached with fewer wasted moves scores differently, so it's stored apart
+    let mut direct = Board::from_fen(DEFAULT_FEN);
+    play(
+        &mut direct,
+        &[("g1", "f3"), ("e7", "e5"), ("b1", "c3"), ("a7", "a5")],
+    );
+    let mut wasted = Board::from_fen(DEFAULT_FEN);
+    play(
+        &mut wasted,
+        &[("g1", "h3"), ("e7", "e6"), ("h3", "g1"), ("e6", "e5")],
+    );
+    play(
+        &mut wasted,
+        &[("g1", "f3"), ("a7", "a6"), ("b1", "c3"), ("a6", "a5")],
+    );
+    // Past the double push, so there's no en passent square
+    for board in [&mut direct, &mut wasted] {
+        play(board, &[("d2", "d3"), ("b8", "c6")]);
+    }
+    assert_eq!(
+        direct.as_fen().split(' ').next(),
+        wasted.as_fen().split(' ').next()
+    );
+    assert_eq!(direct.turn, wasted.turn);
+    assert_eq!(direct.hash, wasted.hash);
+    assert_ne!(direct.score(), wasted.score());
+    assert_ne!(direct.tt_key(), wasted.tt_key());
+
+    // An early queen move costs more than the same move after developing
+    let sortie = Board::from_fen("rnbqkbnr/pppppppp/8/7Q/4P3/8/PPPP1PPP/RNB1KBNR b KQkq - 1 2");
+    let home = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2");
+    assert!(sortie.score() < home.score());
+
+    let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 30");
+    assert_eq!(board.phase(), Phase::Middlegame);
+    let board = Board::from_fen("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1");
+    assert_eq!(board.phase(), Phase::Endgame);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn score_display() {
+    use crate::board_eval::display_score;
+
+    assert_eq!(display_score(150, ChessColor::White), "+1.5");
+    assert_eq!(display_score(150, ChessColor::Black), "-1.5");
+    assert_eq!(display_score(-30, ChessColor::White), "-0.3");
+    assert_eq!(display_score(0, ChessColor::White), "+0.0");
+    assert_eq!(display_score(20000 - 3, ChessColor::White), "M2");
+    assert_eq!(display_score(20000 - 3, ChessColor::Black), "-M2");
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn score_display_once_mated() {
+    use crate::board_eval::{display_score, eval_tag, mate_moves};
+
+    // Black is mated, which is over instead of a mate in none
+    let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    play(&mut board, &[("a1", "a8")]);
+    assert_eq!(mate_moves(board.score), None);
+    assert_eq!(display_score(board.score, ChessColor::White), "#");
+    assert_eq!(display_score(board.score, ChessColor::Black), "-#");
+    assert_eq!(eval_tag(board.score), "#0");
+    assert_eq!(eval_tag(-board.score), "#-0");
+
+    // Out of range scores don't overflow
+    for score in [i32::MIN, i32::MAX] {
+        assert_eq!(mate_moves(score), None);
+        for color in [ChessColor::White, ChessColor::Black] {
+            assert!(display_score(score, color).ends_with('#'));
+        }
+    }
+    assert_eq!(display_score(i32::MIN, ChessColor::White), "-#");
+    assert_eq!(display_score(i32::MIN, ChessColor::Black), "#");
+    assert_eq!(eval_tag(i32::MAX), "#0");
+}
+
+#[test]
+fn pawn_structure() {
+    // Isolated pawns
+    let board = Board::from_fen("4k3/8/8/8/8/8/P1P1P3/4K3 w - - 0 1");
+    assert_eq!(board.pawn_structure(), -30);
+    // Doubled and isolated, the front one is passed and a rank up
+    let board = Board::from_fen("4k3/8/8/8/8/4P3/4P3/4K3 w - - 0 1");
+    assert_eq!(board.pawn_structure(), -25);
+    let board = Board::from_fen("4k3/4p3/4p3/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(board.pawn_structure(), 25);
+    // Far advanced passed pawn, and one that's stopped by a pawn on the next file
+    let board = Board::from_fen("4k3/8/4P3/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(board.pawn_structure(), 50);
+    let board = Board::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 w - - 0 1");
+    assert_eq!(board.pawn_structure(), 0);
+
+    // The pawn hash only changes with the pawns, and matches a fresh board
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    let start = board.pawn_hash;
+    play(&mut board, &[("g1", "f3"), ("g8", "f6")]);
+    assert_eq!(board.pawn_hash, start);
+    play(&mut board, &[("e2", "e4"), ("f6", "e4")]);
+    assert_ne!(board.pawn_hash, start);
+    assert_eq!(board.pawn_hash, Board::from_fen(&board.as_fen()).pawn_hash);
+    assert_eq!(board.pawn_score(), board.pawn_structure());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn drawish_endgames() {
+    use crate::agent::Outlook;
+    use crate::endgame::Endgame;
+
+    // Same material, same key, whatever the squares
+    let a = Board::from_fen("4k3/5b2/8/8/8/8/3BP3/4K3 w - - 0 1");
+    let b = Board::from_fen("4k3/8/2b5/8/4B3/4P3/8/4K3 w - - 0 1");
+    assert_eq!(a.material_key(), b.material_key());
+    assert_ne!(
+        a.material_key(),
+        Board::from_fen(DEFAULT_FEN).material_key()
+    );
+
+    // A pawn up with opposite bishops is scaled down, same colored bishops aren't
+    assert_eq!(a.endgame_class(), Some(Endgame::OppositeBishops));
+    assert_eq!(b.endgame_class(), None);
+    assert_eq!(a.scale_endgame(100), 50);
+
+    let rook = Board::from_fen("4k3/8/8/4r3/8/4P3/8/R3K3 w - - 0 1");
+    assert_eq!(rook.endgame_class(), Some(Endgame::RookPawnVsRook));
+    assert_eq!(
+        Board::from_fen("4k3/8/8/4r3/8/3PP3/8/R3K3 w - - 0 1").endgame_class(),
+        None
+    );
+
+    // The agent wants a draw in a wider window when the endgame is drawish
+    assert!(Outlook::draw_score(&a).unwrap() > Outlook::draw_score(&b).unwrap());
+    assert_eq!(Outlook::draw_score(&Board::from_fen(DEFAULT_FEN)), None);
+}
+
+/* ---------------------------------- FEN ----------------------------------- */
+#[test]
+fn full_fen_validation() {
+    use crate::util::validate_full_fen;
+
+    assert!(validate_full_fen(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+    ));
+    assert!(validate_full_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2"));
+
+    // Missing fields, bad turn, bad castling, wrong en passant rank, zero full moves
+    assert!(!validate_full_fen("4k3/8/8/8/8/8/8/4K3 w - -"));
+    assert!(!validate_full_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1"));
+    assert!(!validate_full_fen("4k3/8/8/8/8/8/8/4K3 w KX - 0 1"));
+    assert!(!validate_full_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d3 0 1"));
+    assert!(!validate_full_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0"));
+}
+
+#[test]
+fn position_validation() {
+    use crate::board::{Board, ChessColor};
+    use crate::conf::DEFAULT_FEN;
+    use crate::loc;
+    use crate::position::PositionIssue;
+
+    let issues = |fen| Board::try_from_fen(fen).err().unwrap_or_default();
+    assert_eq!(issues(DEFAULT_FEN), vec![]);
+    assert_eq!(issues("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2"), vec![]);
+    assert_eq!(
+        issues("4k3/8/8/8/8/8/8/4K3 w - - 0"),
+        vec![PositionIssue::InvalidFen]
+    );
+
+    assert_eq!(
+        issues("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+        vec![PositionIssue::MissingKing(ChessColor::Black)]
+    );
+    assert_eq!(
+        issues("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+        vec![PositionIssue::ExtraKings(ChessColor::White)]
+    );
+    assert_eq!(
+        issues("P3k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+        vec![PositionIssue::PawnOnBackRank(loc!(0, 0))]
+    );
+    // Black's king is attacked with white to move
+    assert_eq!(
+        issues("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+        vec![PositionIssue::OpponentInCheck(ChessColor::Black)]
+    );
+    // No black pawn on d5 to have just skipped d6
+    assert_eq!(
+        issues("4k3/8/8/4P3/8/8/8/4K3 w - d6 0 2"),
+        vec![PositionIssue::ImpossibleEnPassant(loc!(3, 2))]
+    );
+    // White's king has moved, and black's kingside rook is gone
+    assert_eq!(
+        issues("r3k3/8/8/8/8/8/8/R2K3R w KQq - 0 1"),
+        vec![
+            PositionIssue::ImpossibleCastling(ChessColor::White, true),
+            PositionIssue::ImpossibleCastling(ChessColor::White, false),
+        ]
+    );
+    assert_eq!(
+        issues("r3k3/8/8/8/8/8/8/R3K2R w KQkq - 0 1"),
+        vec![PositionIssue::ImpossibleCastling(ChessColor::Black, true)]
+    );
+}
+
+/* ---------------------------------- Clock --------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn clock_flag_and_increment() {
+    use crate::clock::{Clock, TimeControl};
+
+    let mut clock = Clock::new(&TimeControl {
+        base: 60.0,
+        increment: 2.0,
+    });
+    clock.tick(ChessColor::White, 10.0);
+    clock.add_increment(ChessColor::White);
+    assert_eq!(clock.remaining(ChessColor::White), 52.0);
+    assert_eq!(clock.display(ChessColor::White), "0:52");
+    assert_eq!(clock.flagged(), None);
+
+    clock.tick(ChessColor::Black, 55.5);
+    assert_eq!(clock.display(ChessColor::Black), "0:04.5");
+
+    clock.tick(ChessColor::Black, 100.0);
+    assert_eq!(clock.remaining(ChessColor::Black), 0.0);
+    assert_eq!(clock.flagged(), Some(ChessColor::Black));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn lan_moves_are_charged_within_the_grace() {
+    use crate::clock::{charged_time, LAG_GRACE};
+
+    // The mover's own time is trusted, but not to hide more than the grace of the time the move took to arrive
+    assert_eq!(charged_time(Some(4.5), 5.0), 4.5);
+    assert_eq!(charged_time(Some(1.0), 5.0), 5.0 - LAG_GRACE);
+    assert_eq!(charged_time(Some(9.0), 5.0), 5.0);
+    assert_eq!(charged_time(Some(2.0), 0.5), 0.5);
+    assert_eq!(charged_time(Some(f64::NAN), 3.0), 3.0);
+    assert_eq!(charged_time(None, 3.0), 3.0);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn clock_alerts() {
+    use crate::clock::{Clock, ClockAlert, TimeControl};
+    use crate::game::{Game, GameOptions};
+
+    let mut clock = Clock::new(&TimeControl {
+        base: 61.0,
+        increment: 0.0,
+    });
+    assert_eq!(clock.tick(ChessColor::White, 0.5), None);
+    assert_eq!(
+        clock.tick(ChessColor::White, 0.5),
+        Some(ClockAlert::OneMinute)
+    );
+    assert_eq!(clock.tick(ChessColor::White, 0.5), None);
+    assert_eq!(
+        clock.tick(ChessColor::White, 49.5),
+        Some(ClockAlert::TenSeconds)
+    );
+    // Each of the last seconds ticks, the lowest when a frame passes several
+    assert_eq!(
+        clock.tick(ChessColor::White, 5.0),
+        Some(ClockAlert::Tick(5))
+    );
+    assert_eq!(
+        clock.tick(ChessColor::White, 2.5),
+        Some(ClockAlert::Tick(3))
+    );
+    assert_eq!(clock.tick(ChessColor::White, 0.2), None);
+    assert_eq!(ClockAlert::crossed(1.5, 0.0), Some(ClockAlert::Tick(1)));
+
+    // Increments are published with the move, and shown next to the clock
+    let mut options = GameOptions::new();
+    options.time_control = Some(TimeControl {
+        base: 60.0,
+        increment: 2.0,
+    });
+    let mut game = Game::from_options(options);
+    let (from, to) = mov("e2", "e4");
+    game.move_piece(&from, &to);
+    assert!(matches!(
+        game.increment_shown,
+        Some((ChessColor::White, seconds, _)) if seconds == 2.0
+    ));
+}
+
+/* --------------------------------- Agents --------------------------------- */
+#[test]
+fn greedy_takes_material() {
+    use crate::agent::{AgentId, SearchLimits};
+
+    let board = Board::from_fen("4k3/8/8/8/3r3Q/8/8/4K3 b - - 0 1");
+    assert_eq!(
+        AgentId::GREEDY.get_move(&board, &SearchLimits::new(0.0)).0,
+        Some(mov("d4", "h4"))
+    );
+
+    // Agents pick for the side to move, whichever side they were set up to play
+    let board = Board::from_fen("4k3/8/8/8/3R3q/8/8/4K3 w - - 0 1");
+    assert_eq!(board.agent_color, ChessColor::Black);
+    assert_eq!(
+        AgentId::GREEDY.get_move(&board, &SearchLimits::new(0.0)).0,
+        Some(mov("d4", "h4"))
+    );
+    for agent in [AgentId::RANDOM, AgentId::FIXED_DEPTH] {
+        let (from, to) = agent.get_move(&board, &SearchLimits::new(0.0)).0.unwrap();
+        assert_eq!(board.get(&from).unwrap().color, ChessColor::White);
+        assert!(board.moves(ChessColor::White).contains(&(from, to)));
+    }
+}
+
+#[test]
+fn fixed_depth_sees_recapture() {
+    use crate::agent::{AgentId, SearchLimits};
+
+    // Qxe4 wins a pawn but loses the queen to fxe4
+    let board = Board::from_fen("4k3/8/8/3q4/4P3/5P2/8/4K3 b - - 0 1");
+    assert_eq!(
+        AgentId::GREEDY.get_move(&board, &SearchLimits::new(0.0)).0,
+        Some(mov("d5", "e4"))
+    );
+    assert_ne!(
+        AgentId::FIXED_DEPTH
+            .get_move(&board, &SearchLimits::new(0.0))
+            .0,
+        Some(mov("d5", "e4"))
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn mcts_takes_free_queen() {
+    use crate::agent::{agents, AgentId, SearchLimits, AGENTS};
+
+    let board = Board::from_fen("4k3/8/8/8/3r3Q/8/8/4K3 b - - 0 1");
+    let (best, info) = AgentId::MCTS.get_move(&board, &SearchLimits::new(1.0));
+    assert_eq!(best, Some(mov("d4", "h4")));
+    let info = info.unwrap();
+    assert_eq!(info.pv.first(), best.as_ref());
+    assert!(info.score < 0 && info.nodes > 0);
+
+    // Each agent is registered once, and only the engine needs something to be available
+    for registered in AGENTS.iter() {
+        let count = AGENTS
+            .iter()
+            .filter(|other| other.id == registered.id)
+            .count();
+        assert_eq!(count, 1);
+    }
+    assert_eq!(
+        agents().count(),
+        AGENTS.len() - usize::from(crate::engine::engine_path().is_none())
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn engine_info_is_read_for_white() {
+    use crate::agent::SearchLimits;
+    use crate::board_eval::mate_moves;
+    use crate::engine::{go_command, parse_info};
+
+    let board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1");
+    let line = "info depth 12 seldepth 15 score cp 30 nodes 1234 pv e8d7 e2e4 zz";
+    let info = parse_info(&board, line).unwrap();
+    assert_eq!(info.depth, 12);
+    assert_eq!(info.score, -30);
+    assert_eq!(info.nodes, 1234);
+    assert_eq!(info.pv, vec![mov("e8", "d7"), mov("e2", "e4")]);
+
+    let mate = parse_info(&board, "info depth 5 score mate 2 pv e8d8").unwrap();
+    assert_eq!(mate_moves(-mate.score), Some(2));
+    assert_eq!(parse_info(&board, "info string hello"), None);
+    assert_eq!(parse_info(&board, "bestmove e8d8"), None);
+
+    let mut limits = SearchLimits::new(1.5);
+    assert_eq!(go_command(&limits), "go movetime 1500");
+    limits.max_depth = Some(4);
+    assert_eq!(go_command(&limits), "go depth 4 movetime 1500");
+}
+
+#[cfg(all(feature = "gui", unix))]
+#[test]
+fn engine_is_kept_running() {
+    use std::fs::{read_to_string, write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use crate::agent::{AgentResult, MovePicker, SearchLimits};
+    use crate::engine::UciEngine;
+
+    // Thinks until it's told to stop, and notes each time it's started
+    let dir = std::env::temp_dir().join(format!("chess-ai-engine-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let starts = dir.join("starts");
+    let script = dir.join("engine.sh");
+    let engine = r#"#!/bin/sh
+echo started >> STARTS
+while read -r line; do
+    case "$line" in
+        uci) echo uciok ;;
+        go*) echo "info depth 1 score cp 10 pv e2e4" ;;
+        stop) echo "bestmove e2e4" ;;
+        quit) exit 0 ;;
+    esac
+done
+"#;
+    let starts_path = format!("'{}'", starts.display());
+    write(&script, engine.replace("STARTS", &starts_path)).unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let engine = UciEngine::new(Some(script));
+    let mut limits = SearchLimits::new(f64::INFINITY);
+    limits.stop = Some(Arc::new(AtomicBool::new(true)));
+    let board = Board::from_fen(DEFAULT_FEN);
+    for _ in 0..2 {
+        let AgentResult::Picked((best, info)) = engine.best_move(&board, &limits) else {
+            panic!("engine didn't pick a move");
+        };
+        assert_eq!(best, Some(mov("e2", "e4")));
+        assert_eq!(info.unwrap().score, 10);
+    }
+    assert_eq!(read_to_string(&starts).unwrap().lines().count(), 1);
+    drop(engine);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn agents_are_saved_by_name() {
+    use crate::agent::AgentId;
+
+    assert_eq!(
+        serde_json::to_string(&AgentId::FIXED_DEPTH).unwrap(),
+        "\"FixedDepth\""
+    );
+    assert_eq!(
+        serde_json::from_str::<AgentId>("\"Nnue\"").unwrap(),
+        AgentId::NNUE
+    );
+    assert_eq!(AgentId::NNUE.to_string(), "NNUE");
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn minimax_reports_search() {
+    use crate::agent::{AgentId, SearchLimits};
+    use crate::pgn::search_comment;
+
+    let board = Board::from_fen("7k/6pp/8/8/8/8/8/R3K3 w - - 0 1");
+    let (best, info) = AgentId::MINIMAX.get_move(&board, &SearchLimits::new(0.5));
+    let info = info.unwrap();
+    assert_eq!(best, Some(mov("a1", "a8")));
+    assert_eq!(info.pv.first(), best.as_ref());
+    assert!(info.depth >= 1);
+    assert!(search_comment(&board, AgentId::MINIMAX, &info).starts_with("Minimax: depth "));
+    assert!(search_comment(&board, AgentId::MINIMAX, &info).contains(", Ra8#"));
+
+    assert_eq!(
+        AgentId::RANDOM.get_move(&board, &SearchLimits::new(0.5)).1,
+        None
+    );
+}
+
+#[test]
+fn search_scores_mates_by_distance() {
+    use crate::agent::search_depth;
+    use crate::board_eval::mate_score;
+
+    // Ra7 then Rb8#, found by searching instead of scored by hand
+    let board = Board::from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+    assert_eq!(search_depth(&board, 4).0, mate_score(2));
+    let board = Board::from_fen("1r4k1/r7/8/8/8/8/8/7K b - - 0 1");
+    assert_eq!(search_depth(&board, 4).0, -mate_score(2));
+    // Mate in one scores higher than mate in two
+    let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    assert_eq!(
+        search_depth(&board, 2),
+        (mate_score(1), Some(mov("a1", "a8")))
+    );
+
+    // Stalemating with Qc7 is a draw, not a win
+    let board = Board::from_fen("k7/8/3Q4/8/8/2K5/8/8 w - - 0 1");
+    for depth in 2..=4 {
+        let (score, best) = search_depth(&board, depth);
+        assert_ne!(best, Some(mov("d6", "c7")));
+        assert!(score > 0 && score <= mate_score(1));
+    }
+}
+
+#[test]
+fn book_moves_for_either_color() {
+    use crate::agent::{book_transpositions, in_book, search_depth, AgentId, SearchLimits, MAX};
+
+    // White's first move, and black's reply to 1. e4
+    let start = Board::from_fen(DEFAULT_FEN);
+    let mut e4 = start.clone();
+    play(&mut e4, &[("e2", "e4")]);
+    for board in [start, e4] {
+        let (mov, info) = AgentId::MINIMAX.get_move(&board, &SearchLimits::new(0.5));
+        let info = info.unwrap();
+        assert_eq!((info.depth, info.score), (0, MAX));
+        let (from, to) = mov.unwrap();
+        assert!(is_legal(&board, &from.as_notation(), &to.as_notation()));
+    }
+
+    // 1. g3 d5 2. Nf3 is out of book, but transposes into 1. Nf3 d5 2. g3
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    play(&mut board, &[("g2", "g3"), ("d7", "d5")]);
+    assert!(!in_book(&board));
+    assert!(book_transpositions(&board).contains(&mov("g1", "f3")));
+    play(&mut board, &[("g1", "f3")]);
+    assert!(in_book(&board));
+
+    // Only the root is played from the book, positions searched into are scored normally
+    let board = Board::from_fen(DEFAULT_FEN);
+    assert_ne!(search_depth(&board, 2).0, MAX);
+}
+
+#[test]
+fn book_loads_in_slices() {
+    use std::borrow::Cow;
+
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+
+    use crate::agent_opens::{
+        openings, parse_openings, write_openings, BookLoader, Openings, BAKED_BOOK,
+    };
+    use crate::hashmap;
+
+    // A deadline in the past reads one position per step, like a slow frame on the web
+    let mut loader = BookLoader::new(Cow::Borrowed(BAKED_BOOK));
+    let mut steps = 0;
+    let book = loop {
+        steps += 1;
+        if let Some(book) = loader.step(0.0).unwrap() {
+            break book;
+        }
+    };
+    assert_eq!(book, *openings());
+    assert!(steps > book.len());
+
+    // Names are stored once and shared between the moves that have them
+    let book: Openings = hashmap! {
+        1 => vec![
+            (mov("e2", "e4"), "King's Pawn".to_string()),
+            (mov("g1", "f3"), "Réti".to_string()),
+            (mov("e2", "e4"), "King's Pawn".to_string()),
+        ],
+        2 => vec![],
+    };
+    let bytes = write_openings(&book);
+    assert_eq!(parse_openings(&bytes).unwrap(), book);
+    assert!(BAKED_BOOK.len() * 10 < serde_json::to_vec(&*openings()).unwrap().len());
+
+    // Cut off or foreign files are errors rather than partial books
+    assert!(parse_openings(&bytes[..bytes.len() / 2]).is_err());
+    assert!(parse_openings(&compress_to_vec_zlib(b"{}", 10)).is_err());
+    assert!(parse_openings(&compress_to_vec_zlib(b"BOOK1\x00\x00\x01\x00\x00\x00", 10)).is_err());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn search_limits() {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use crossbeam_channel::unbounded;
+
+    use crate::agent::{AgentId, SearchLimits};
+    use crate::analysis::LiveAnalysis;
+
+    // Stops at the depth cap, however long it's given, reporting each depth on the way
+    let board = Board::from_fen("4k3/pp3ppp/8/8/8/8/PP3PPP/4K3 w - - 0 1");
+    let (sender, progress) = unbounded();
+    let mut limits = SearchLimits::new(f64::INFINITY);
+    limits.max_depth = Some(3);
+    limits.progress = Some(sender);
+    let (best, info) = AgentId::MINIMAX.get_move(&board, &limits);
+    assert!(best.is_some());
+    assert_eq!(info.unwrap().depth, 3);
+    drop(limits);
+    let depths: Vec<_> = progress
+        .iter()
+        .filter_map(|message| message.info())
+        .map(|info| info.depth)
+        .collect();
+    assert_eq!(depths, vec![1, 2, 3]);
+
+    // Already stopped, so no depth finishes
+    let mut limits = SearchLimits::new(f64::INFINITY);
+    limits.stop = Some(Arc::new(AtomicBool::new(true)));
+    assert_eq!(AgentId::MINIMAX.get_move(&board, &limits), (None, None));
+
+    // Live analysis keeps its last PV after being stopped
+    let mut live = LiveAnalysis::start(&board);
+    for _ in 0..200 {
+        live.update();
+        if live.latest.is_some() {
+            break;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    live.stop();
+    for _ in 0..500 {
+        live.update();
+        if live.finished {
+            break;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert!(live.finished);
+    assert!(!live.latest.unwrap().pv.is_empty());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn agent_messages() {
+    use crossbeam_channel::unbounded;
+
+    use crate::agent::{AgentId, AgentMessage, Outlook, SearchLimits};
+
+    let think = |board: &Board, limits: SearchLimits, resign: Option<(Outlook, Option<i32>)>| {
+        let (sender, messages) = unbounded();
+        AgentId::MINIMAX.think(board, limits, resign, sender);
+        messages.iter().collect::<Vec<_>>()
+    };
+
+    // Each depth as it finishes, then the move
+    let board = Board::from_fen("7k/6pp/8/8/8/8/8/R3K3 w - - 0 1");
+    let mut limits = SearchLimits::new(f64::INFINITY);
+    limits.max_depth = Some(3);
+    let messages = think(&board, limits.clone(), None);
+    let infos: Vec<_> = messages
+        .iter()
+        .filter_map(|message| message.info())
+        .collect();
+    assert_eq!(
+        infos.iter().map(|info| info.depth).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert!(infos.iter().all(|info| info.nodes > 0));
+    assert!(infos[0].nodes < infos[2].nodes);
+    let (from, to) = mov("a1", "a8");
+    assert_eq!(messages.last(), Some(&AgentMessage::BestMove(from, to)));
+
+    // Book moves say which opening they're from
+    let messages = think(&Board::from_fen(DEFAULT_FEN), limits.clone(), None);
+    assert!(matches!(messages[0], AgentMessage::BookHit { .. }));
+    assert_eq!(messages[1].info().unwrap().depth, 0);
+    assert!(matches!(messages[2], AgentMessage::BestMove(..)));
+
+    // Resigns instead of moving once hopeless for long enough
+    let board = Board::from_fen("4k3/8/8/8/8/8/8/QQQ1K3 b - - 0 1");
+    let outlook = Outlook {
+        hopeless: 2,
+        ..Outlook::default()
+    };
+    let messages = think(&board, limits.clone(), Some((outlook, Some(500))));
+    assert_eq!(messages.last(), Some(&AgentMessage::Resign));
+    let messages = think(&board, limits.clone(), Some((outlook, None)));
+    assert!(matches!(messages.last(), Some(AgentMessage::BestMove(..))));
+
+    // Nothing to play once the game is over
+    let board = Board::from_fen("R6k/6pp/8/8/8/8/8/4K3 b - - 0 1");
+    assert!(matches!(
+        think(&board, limits.clone(), None)[..],
+        [AgentMessage::Error(_)]
+    ));
+
+    // Crashing while searching is an error too, IE a piece that's lost track of where it is
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+    board.raw[6][4].as_mut().unwrap().pos = Loc::from_notation("a5");
+    match &think(&board, limits, None)[..] {
+        [AgentMessage::Error(err)] => assert!(err.starts_with("Minimax crashed: ")),
+        messages => panic!("{:?}", messages),
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn sliced_thinking() {
+    use crossbeam_channel::unbounded;
+
+    use crate::agent::{AgentId, SearchLimits, Thinking};
+
+    let board =
+        Board::from_fen("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5");
+    let mut limits = SearchLimits::new(f64::INFINITY);
+    limits.max_depth = Some(3);
+
+    let (sender, messages) = unbounded();
+    AgentId::MINIMAX.think(&board, limits.clone(), None, sender);
+    let whole: Vec<_> = messages.iter().collect();
+
+    // Pausing after every position finds the same thing, a bit at a time
+    let (sender, messages) = unbounded();
+    let mut thinking = Thinking::new(AgentId::MINIMAX, &board, limits, None, sender);
+    let mut slices = 1;
+    while !thinking.step(0.0) {
+        slices += 1;
+    }
+    drop(thinking);
+    assert!(slices > 100, "{} slices", slices);
+    assert_eq!(messages.iter().collect::<Vec<_>>(), whole);
+}
+
+#[test]
+fn shuffling_penalized_when_ahead() {
+    use crate::agent::shuffle_penalty;
+
+    fn penalty(board: &Board, from: &str, to: &str) -> i32 {
+        let (from, to) = mov(from, to);
+        let mut after = board.clone();
+        after.move_piece(&from, &to, false);
+        shuffle_penalty(board, &after, &from, &to)
+    }
+
+    // White is a rook up, and just played Ra1-a2
+    let mut board = Board::from_fen("7k/6pp/8/8/8/8/8/R3K3 w - - 0 1");
+    play(&mut board, &[("a1", "a2"), ("h8", "g8")]);
+    assert!(penalty(&board, "a2", "a1") > 0);
+    assert_eq!(penalty(&board, "a2", "a3"), 0);
+    assert_eq!(penalty(&board, "e1", "d1"), 0);
+
+    // Black is behind, so undoing a move is fine
+    play(&mut board, &[("a2", "a3")]);
+    assert_eq!(penalty(&board, "g8", "h8"), 0);
+
+    // Coming back to a position a second time
+    play(&mut board, &[("g8", "h8"), ("a3", "a2"), ("h8", "g8")]);
+    assert!(penalty(&board, "a2", "a3") > 0);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn resigning_and_draw_offers() {
+    use crate::agent::{Outlook, Proposal};
+    use crate::pgn::{result, termination};
+
+    // Only resigns after being hopeless a few moves in a row
+    let mut outlook = Outlook::default();
+    assert_eq!(outlook.update(-1000, None, Some(900)), None);
+    assert_eq!(outlook.update(-1000, None, Some(900)), None);
+    assert_eq!(outlook.update(-200, None, Some(900)), None);
+    assert_eq!(outlook.update(-1000, None, Some(900)), None);
+    assert_eq!(outlook.update(-1000, None, Some(900)), None);
+    assert_eq!(
+        outlook.update(-1000, None, Some(900)),
+        Some(Proposal::Resign)
+    );
+    assert_eq!(outlook.update(-1000, None, None), None);
+
+    // Offers a draw in a long equal endgame, then waits before offering again
+    let mut outlook = Outlook::default();
+    for _ in 0..4 {
+        assert_eq!(outlook.update(10, Some(30), Some(900)), None);
+    }
+    assert_eq!(
+        outlook.update(10, Some(30), Some(900)),
+        Some(Proposal::OfferDraw)
+    );
+    assert_eq!(outlook.update(10, Some(30), Some(900)), None);
+    assert!(outlook.accepts_draw(Some(30)));
+    assert!(!outlook.accepts_draw(None));
+    outlook.update(200, Some(30), Some(900));
+    assert!(!outlook.accepts_draw(Some(30)));
+
+    assert_eq!(result(&BoardState::Resigned(ChessColor::Black)), "1-0");
+    assert_eq!(
+        termination(&BoardState::Resigned(ChessColor::Black)),
+        "White won by resignation"
+    );
+    assert_eq!(result(&BoardState::Draw(DrawReason::Agreement)), "1/2-1/2");
+    assert_eq!(
+        termination(&BoardState::Draw(DrawReason::Agreement)),
+        "Game drawn by agreement"
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn mate_solver() {
+    use crate::mate::solve_mate;
+
+    let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    assert_eq!(solve_mate(&board, 3), Some(vec![mov("a1", "a8")]));
+
+    // Needs the king's help, so there's no mate in one
+    let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+    assert_eq!(solve_mate(&board, 1), None);
+    let line = solve_mate(&board, 2).unwrap();
+    assert_eq!(line.len(), 3);
+    let mut end = board.clone();
+    for (from, to) in line.iter() {
+        end.move_piece(from, to, true);
+    }
+    assert_eq!(end.state, BoardState::Checkmate(ChessColor::Black));
+
+    assert_eq!(solve_mate(&Board::from_fen(DEFAULT_FEN), 2), None);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn mate_search_options() {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use crate::agent::SearchLimits;
+    use crate::mate::{solve_mate, MateSearch};
+
+    let limits = SearchLimits::new(f64::INFINITY);
+
+    // Starts with a quiet king move, so only trying checks misses it
+    let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+    let mut checks = MateSearch::new(&board, 2, true);
+    assert_eq!(checks.run(&limits, f64::INFINITY), Some(None));
+    let back_rank = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    let mut checks = MateSearch::new(&back_rank, 2, true);
+    assert_eq!(
+        checks.run(&limits, f64::INFINITY),
+        Some(Some(vec![mov("a1", "a8")]))
+    );
+
+    // Paused every position, then resumed to the same line
+    let mut search = MateSearch::new(&board, 2, false);
+    let mut slices = 0;
+    let line = loop {
+        slices += 1;
+        if let Some(line) = search.run(&limits, f64::NEG_INFINITY) {
+            break line;
+        }
+    };
+    assert!(slices > 1);
+    assert_eq!(line, solve_mate(&board, 2));
+
+    // Stopped searches give up
+    let mut stopped = limits.clone();
+    stopped.stop = Some(Arc::new(AtomicBool::new(true)));
+    let mut search = MateSearch::new(&board, 2, false);
+    assert_eq!(search.run(&stopped, f64::INFINITY), Some(None));
+}
+
+/* ---------------------------------- NNUE ---------------------------------- */
+/// The accumulator built up move by move should match one built from scratch
+fn assert_accumulator_synced(board: &Board) {
+    use crate::nnue::{Accumulator, NETWORK};
+
+    let network = NETWORK.as_ref().unwrap();
+    let fresh = Accumulator::refresh(network, board.raw.iter().flatten().flatten());
+    assert_eq!(
+        board.accumulator,
+        fresh,
+        "out of sync in {}",
+        board.as_fen()
+    );
+}
+
+#[test]
+fn accumulator_incremental_updates() {
+    // Castling
+    let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    assert_accumulator_synced(&board);
+    play(&mut board, &[("e1", "g1"), ("e8", "c8")]);
+    assert_accumulator_synced(&board);
+
+    // En passant
+    let mut board = Board::from_fen("4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1");
+    play(&mut board, &[("d7", "d5"), ("e5", "d6")]);
+    assert_accumulator_synced(&board);
+
+    // Promotion with capture
+    let mut board = Board::from_fen("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+    play(&mut board, &[("a7", "b8")]);
+    assert_eq!(
+        piece_at(&board, "b8"),
+        Some((PieceNames::Queen, ChessColor::White))
+    );
+    assert_accumulator_synced(&board);
+}
+
+#[test]
+fn material_incremental_updates() {
+    // Castling, en passant, and promotion with capture
+    let games: [(&str, &[(&str, &str)]); 3] = [
+        (
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            &[("e1", "g1"), ("e8", "c8")],
+        ),
+        (
+            "4k3/3p4/8/4P3/8/8/8/4K3 b - - 0 1",
+            &[("d7", "d5"), ("e5", "d6")],
+        ),
+        ("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", &[("a7", "b8")]),
+    ];
+    for (fen, moves) in games {
+        let mut board = Board::from_fen(fen);
+        play(&mut board, moves);
+        assert_eq!(board.material, board.full_material(), "{}", board.as_fen());
+        assert_eq!(board.material, Board::from_fen(&board.as_fen()).material);
+    }
+
+    // Only the piece-square tables differ between phases
+    let board = Board::from_fen(DEFAULT_FEN);
+    assert_eq!(board.material.values, 0);
+    assert_eq!(board.material.tables, [[0; 6]; 3]);
+}
+
+#[test]
+fn network_from_bytes() {
+    use crate::nnue::{Accumulator, Network, HIDDEN};
+
+    let len = (768 * HIDDEN + HIDDEN * 2) * 2 + 4;
+    assert!(Network::from_bytes(&vec![0; len - 1]).is_none());
+
+    // All zero weights leave only the output bias
+    let mut bytes = vec![0; len];
+    bytes[len - 4..].copy_from_slice(&(255 * 64i32).to_le_bytes());
+    let network = Network::from_bytes(&bytes).unwrap();
+    assert_eq!(network.evaluate(&Accumulator::new()), 400);
+}
+
+/* -------------------------------- Self-play ------------------------------- */
+#[test]
+fn selfplay_writes_and_resumes() {
+    use std::collections::BTreeSet;
+    use std::fs::{read_to_string, remove_file};
+
+    use crate::selfplay::{run, SelfPlayOptions};
+    use crate::util::validate_full_fen;
+
+    let out = std::env::temp_dir().join(format!("chess-ai-selfplay-{}.txt", std::process::id()));
+    let mut options = SelfPlayOptions::parse(&[
+        "--games".to_string(),
+        "2".to_string(),
+        "--depth".to_string(),
+        "1".to_string(),
+        "--max-plies".to_string(),
+        "10".to_string(),
+        "--out".to_string(),
+        out.display().to_string(),
+    ])
+    .unwrap();
+    assert_eq!(run(&options), 2);
+    assert_eq!(run(&options), 0);
+
+    options.games = 3;
+    assert_eq!(run(&options), 1);
+
+    let contents = read_to_string(&out).unwrap();
+    remove_file(&out).unwrap();
+
+    let mut games = BTreeSet::new();
+    for line in contents.lines() {
+        let parts: Vec<_> = line.split(';').collect();
+        assert_eq!(parts.len(), 4, "{line}");
+        games.insert(parts[0].parse::<usize>().unwrap());
+        assert!(validate_full_fen(parts[1]), "{line}");
+        parts[2].parse::<i32>().unwrap();
+        assert!(["1.0", "0.5", "0.0"].contains(&parts[3]), "{line}");
+    }
+    assert_eq!(games, BTreeSet::from([0, 1, 2]));
+}
+
+/* ----------------------------------- PGN ---------------------------------- */
+#[test]
+fn san_notation() {
+    use crate::pgn::san;
+
+    let san_of = |fen: &str, from: &str, to: &str| {
+        let board = Board::from_fen(fen);
+        let (from, to) = mov(from, to);
+        san(&board, &from, &to)
+    };
+
+    assert_eq!(san_of(DEFAULT_FEN, "g1", "f3"), "Nf3");
+    assert_eq!(san_of(DEFAULT_FEN, "e2", "e4"), "e4");
+    assert_eq!(
+        san_of("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1", "g1"),
+        "O-O"
+    );
+    assert_eq!(
+        san_of("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8", "c8"),
+        "O-O-O"
+    );
+
+    // Disambiguation by file, rank, then both
+    assert_eq!(
+        san_of("4k3/8/8/8/R6R/8/8/4K3 w - - 0 1", "a4", "d4"),
+        "Rad4"
+    );
+    assert_eq!(
+        san_of("4k3/R7/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a4"),
+        "R1a4"
+    );
+    assert_eq!(
+        san_of("7k/8/8/8/2Q1Q3/8/2Q5/4K3 w - - 0 1", "c4", "d3"),
+        "Qc4d3"
+    );
+
+    // Captures, en passant, promotion and mate
+    assert_eq!(
+        san_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5", "d6"),
+        "exd6"
+    );
+    assert_eq!(
+        san_of("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7", "b8"),
+        "axb8=Q+"
+    );
+    assert_eq!(
+        san_of("7k/6pp/8/8/8/8/8/R3K3 w - - 0 1", "a1", "a8"),
+        "Ra8#"
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn pgn_export() {
+    use crate::pgn::export;
+
+    let cycle = [
+        mov("g1", "f3"),
+        mov("g8", "f6"),
+        mov("f3", "g1"),
+        mov("f6", "g8"),
+    ];
+    let moves = [cycle, cycle].concat();
+    let fen = "4k1n1/8/8/8/8/8/8/4K1N1 w - - 0 1";
+
+    let mut board = Board::from_fen(fen);
+    for (from, to) in moves.iter() {
+        board.move_piece(from, to, true);
+    }
+    let pgn = export(
+        &Board::from_fen(fen),
+        &moves,
+        &[],
+        &[],
+        "Player",
+        "Minimax",
+        &board.state,
+    );
+
+    assert!(pgn.contains("[Result \"1/2-1/2\"]"));
+    assert!(pgn.contains("[FEN \"4k1n1/8/8/8/8/8/8/4K1N1 w - - 0 1\"]"));
+    assert!(pgn.contains("[Termination \"Game drawn by repetition\"]"));
+    assert!(pgn.ends_with("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 Ng8 1/2-1/2\n"));
+
+    // Starting with black to move
+    let fen = "4k3/8/8/8/8/8/8/R3K3 b - - 0 10";
+    let pgn = export(
+        &Board::from_fen(fen),
+        &[mov("e8", "d7")],
+        &[],
+        &[],
+        "Minimax",
+        "Player",
+        &BoardState::Normal,
+    );
+    assert!(pgn.ends_with("10... Kd7 *\n"));
+
+    // Comments go after their move
+    let pgn = export(
+        &Board::from_fen(DEFAULT_FEN),
+        &[mov("e2", "e4"), mov("e7", "e5")],
+        &[None, Some("Minimax: book, e5".to_string())],
+        &[],
+        "Player",
+        "Minimax",
+        &BoardState::Normal,
+    );
+    assert!(pgn.ends_with("1. e4 e5 {Minimax: book, e5} *\n"));
+
+    // Analysis adds evals and NAGs for moves that lose enough
+    let pgn = export(
+        &Board::from_fen(DEFAULT_FEN),
+        &[mov("e2", "e4"), mov("e7", "e5"), mov("d1", "h5")],
+        &[],
+        &[Some(20), Some(30), Some(25), Some(-150)],
+        "Player",
+        "Minimax",
+        &BoardState::Normal,
+    );
+    assert!(pgn.ends_with("1. e4 {[%eval 0.30]} e5 {[%eval 0.25]} 2. Qh5 $2 {[%eval -1.50]} *\n"));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn nags_from_eval_swings() {
+    use crate::pgn::nag;
+
+    assert_eq!(nag(0, -50, ChessColor::White), None);
+    assert_eq!(nag(0, -80, ChessColor::White), Some(6));
+    assert_eq!(nag(0, 200, ChessColor::Black), Some(2));
+    assert_eq!(nag(100, -400, ChessColor::White), Some(4));
+    // Gaining is never a mistake
+    assert_eq!(nag(0, 500, ChessColor::White), None);
+    // Missing a mate
+    assert_eq!(nag(19999, 200, ChessColor::White), Some(4));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn keybinds_in_help() {
+    use crate::keybinds::{help_lines, key_name, tooltip, KEYBINDS, MOUSE_GESTURES};
+
+    let lines = help_lines();
+    assert_eq!(lines.len(), KEYBINDS.len() + MOUSE_GESTURES.len());
+    for (i, (key, name, action)) in KEYBINDS.iter().enumerate() {
+        assert!(lines[i].starts_with(name) && lines[i].ends_with(action.name()));
+        assert_eq!(key_name(*action), Some(*name));
+        assert!(
+            KEYBINDS.iter().filter(|(k, _, _)| k == key).count() == 1,
+            "{} is bound twice",
+            name
+        );
+    }
+    assert_eq!(tooltip(crate::game::MenuAction::Reset), "Reset (R)");
+}
+
+#[test]
+fn san_parsing() {
+    use crate::pgn::from_san;
+
+    let board = Board::from_fen(DEFAULT_FEN);
+    assert_eq!(from_san(&board, "e4"), Some(mov("e2", "e4")));
+    assert_eq!(from_san(&board, "Nf3!?"), Some(mov("g1", "f3")));
+    assert_eq!(from_san(&board, "Ng1f3"), Some(mov("g1", "f3")));
+    assert_eq!(from_san(&board, "e5"), None);
+    assert_eq!(from_san(&board, "Zz9"), None);
+
+    // Ambiguous without disambiguation
+    let board = Board::from_fen("4k3/8/8/8/R6R/8/8/4K3 w - - 0 1");
+    assert_eq!(from_san(&board, "Rd4"), None);
+    assert_eq!(from_san(&board, "Rad4"), Some(mov("a4", "d4")));
+
+    let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+    assert_eq!(from_san(&board, "0-0-0"), Some(mov("e8", "c8")));
+    assert_eq!(from_san(&board, "O-O+"), Some(mov("e8", "g8")));
+
+    let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(from_san(&board, "b8=Q+"), Some(mov("b7", "b8")));
+    assert_eq!(from_san(&board, "b8N"), Some(mov("b7", "b8")));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn typed_moves() {
+    use crate::pgn::parse_move;
+    use crate::settings::Visibility;
+
+    let board = Board::from_fen(DEFAULT_FEN);
+    assert_eq!(parse_move(&board, " Nf3 "), Some(mov("g1", "f3")));
+    assert_eq!(parse_move(&board, "g1f3"), Some(mov("g1", "f3")));
+    assert_eq!(parse_move(&board, "G1F3"), Some(mov("g1", "f3")));
+    assert_eq!(parse_move(&board, "g1g3"), None);
+    assert_eq!(parse_move(&board, "g1f3xx"), None);
+    assert_eq!(parse_move(&board, "é"), None);
+
+    let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(parse_move(&board, "b7b8q"), Some(mov("b7", "b8")));
+
+    let (white, black) = (ChessColor::White, ChessColor::Black);
+    assert!(Visibility::Normal.shows(black, white));
+    assert!(Visibility::Fog.shows(white, white) && !Visibility::Fog.shows(black, white));
+    assert!(!Visibility::Blindfold.shows(white, white));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn pgn_variations() {
+    use crate::pgn::parse;
+
+    let pgn = parse(
+        "[Event \"Test\"]\n[White \"A\"]\n[Black \"B\"]\n\n\
+         1. e4 {Best by test} e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 $1 ; rest of line\n\
+         Nc6 (2...d6) 3.Bb5 1-0\n\n[Event \"Next game\"]\n1. d4 *",
+    )
+    .unwrap();
+    assert_eq!(pgn.tag("White"), Some("A"));
+    assert_eq!(pgn.start_fen, DEFAULT_FEN);
+
+    let tree = &pgn.tree;
+    let san = |nodes: Vec<usize>| -> Vec<String> {
+        nodes.iter().map(|n| tree.nodes[*n].san.clone()).collect()
+    };
+    assert_eq!(san(tree.line(None)), ["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+    assert_eq!(
+        tree.nodes[tree.roots[0]].comment.as_deref(),
+        Some("Best by test")
+    );
+
+    // 1... c5 branches from after 1. e4
+    let e5 = tree.children(Some(tree.roots[0]))[0];
+    let siblings = tree.siblings(e5);
+    assert_eq!(san(siblings.to_vec()), ["e5", "c5"]);
+    assert_eq!(san(tree.line(Some(siblings[1]))), ["e4", "c5", "Nf3", "d6"]);
+    let nf3 = tree.children(Some(siblings[1]))[0];
+    assert_eq!(san(tree.siblings(nf3).to_vec()), ["Nf3", "c3"]);
+    assert_eq!(
+        tree.moves(Some(nf3)),
+        [mov("e2", "e4"), mov("c7", "c5"), mov("g1", "f3")]
+    );
+
+    // Only the first game is read
+    assert_eq!(tree.roots.len(), 1);
+
+    assert!(parse("1. e4 e4").is_err());
+    assert!(parse("1. e4 (1. d4").is_err());
+    assert!(parse("(1. e4)").is_err());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn study_round_trip() {
+    use std::fs::remove_file;
+
+    use crate::move_tree::{Replay, Study};
+    use crate::pgn::{export_tree, parse};
+
+    let pgn =
+        parse("1. e4 {[%cal Ge2e4,Rd2d4] [%csl Re4] King's pawn} e5 (1... c5 {Sicilian}) 2. Nf3 *")
+            .unwrap();
+    let tree = &pgn.tree;
+    let e4 = &tree.nodes[tree.roots[0]];
+    assert_eq!(e4.comment.as_deref(), Some("King's pawn"));
+    assert_eq!(e4.arrows, [mov("e2", "e4"), mov("d2", "d4")]);
+    assert_eq!(e4.highlights, [Loc::from_notation("e4")]);
+    assert!(e4.annotated());
+    assert!(!tree.nodes[e4.children[0]].annotated());
+
+    let exported = export_tree(
+        &Board::from_fen(DEFAULT_FEN),
+        tree,
+        "A",
+        "B",
+        &BoardState::Normal,
+    );
+    let movetext = exported.split_whitespace().collect::<Vec<_>>().join(" ");
+    assert!(movetext.ends_with(
+        "1. e4 {[%cal Ge2e4,Gd2d4] [%csl Re4] King's pawn} e5 (1... c5 {Sicilian}) 2. Nf3 *"
+    ));
+    assert_eq!(parse(&exported).unwrap().tree, *tree);
+
+    let study = Study {
+        start_fen: DEFAULT_FEN.to_string(),
+        replay: Replay {
+            tree: tree.clone(),
+            current: Some(tree.roots[0]),
+            white: "A".to_string(),
+            black: "B".to_string(),
+        },
+    };
+    let path = std::env::temp_dir().join(format!("chess-ai-study-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    study.save(path).unwrap();
+    assert_eq!(Study::load(path).unwrap(), study);
+    remove_file(path).unwrap();
+    assert!(Study::load(path).is_err());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn repertoire_scheduling() {
+    use crate::repertoire::{position_key, Repertoire};
+
+    const DAY: f64 = 60.0 * 60.0 * 24.0;
+
+    let mut repertoire = Repertoire::default();
+    // 1. e4 e5 2. Nf3, only white's moves are added
+    let line = [mov("e2", "e4"), mov("e7", "e5"), mov("g1", "f3")];
+    assert_eq!(
+        repertoire.add_line(DEFAULT_FEN, &line, ChessColor::White, 0.0),
+        2
+    );
+    assert_eq!(
+        repertoire.add_line(DEFAULT_FEN, &line, ChessColor::White, 0.0),
+        0
+    );
+    assert_eq!(repertoire.positions.len(), 2);
+
+    let start = position_key(&Board::from_fen(DEFAULT_FEN));
+    assert_eq!(
+        start,
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -"
+    );
+    assert_eq!(repertoire.positions[&start].san, "e4");
+    assert_eq!(repertoire.due_count(0.0), 2);
+
+    // Right answers push the review back further each time
+    repertoire.review(&start, true, 0.0);
+    assert_eq!(repertoire.positions[&start].due, DAY);
+    repertoire.review(&start, true, DAY);
+    assert_eq!(repertoire.positions[&start].due, 4.0 * DAY);
+    assert_ne!(repertoire.next_due(0.0), Some(start.as_str()));
+
+    // A wrong answer starts over, and comes back soon
+    repertoire.review(&start, false, 4.0 * DAY);
+    let entry = &repertoire.positions[&start];
+    assert_eq!((entry.level, entry.mistakes), (0, 1));
+    assert!(entry.due < 4.0 * DAY + DAY);
+
+    // Choosing a different move replaces the old one
+    assert_eq!(
+        repertoire.add_line(DEFAULT_FEN, &[mov("d2", "d4")], ChessColor::White, 0.0),
+        1
+    );
+    let entry = &repertoire.positions[&start];
+    assert_eq!((entry.san.as_str(), entry.mistakes), ("d4", 0));
+    assert_eq!(repertoire.due_count(0.0), 2);
+}
+
+/* ------------------------------- Move cache ------------------------------- */
+fn sorted(mut moves: Vec<(Loc, Loc)>) -> Vec<(Loc, Loc)> {
+    moves.sort_by_key(|(from, to)| (from.0, from.1, to.0, to.1));
+    moves
+}
+
+#[test]
+fn move_cache_matches_full_generation() {
+    // Pins, discovered checks, castling, en passant, and promotion, with some moves not updating the move lists
+    let mut board =
+        Board::from_fen("r3k2r/1P1q1ppp/2n2n2/1B1pP3/8/5N2/PPP2PPP/R3K2R w KQkq d6 0 1");
+    let line = [
+        ("e5", "d6"),
+        ("c6", "e5"),
+        ("b5", "d7"),
+        ("f6", "d7"),
+        ("b7", "a8"),
+        ("d7", "b8"),
+        ("e1", "c1"),
+        ("e5", "f3"),
+        ("g2", "f3"),
+        ("e8", "g8"),
+    ];
+    for (i, (from, to)) in line.iter().enumerate() {
+        assert!(
+            is_legal(&board, from, to),
+            "{from}{to} in {}",
+            board.as_fen()
+        );
+        let check_stale = i % 3 != 1;
+        board.move_piece(
+            &Loc::from_notation(from),
+            &Loc::from_notation(to),
+            check_stale,
+        );
+
+        for color in [ChessColor::White, ChessColor::Black] {
+            let cached = sorted(board.cached_moves(color));
+            assert_eq!(
+                cached,
+                sorted(board.moves(color)),
+                "{color:?} after {from}{to} in {}",
+                board.as_fen()
+            );
+        }
+    }
+}
+
+/* --------------------------------- XBoard --------------------------------- */
+#[test]
+fn xboard_protocol() {
+    use crate::conf::DEFAULT_FEN;
+    use crate::protocol::Protocol;
+    use crate::xboard::XBoard;
+
+    let mut engine = XBoard::new();
+    let mut send = |command: &str| {
+        let mut out = vec![];
+        assert!(engine.handle(command, &mut out));
+        out
+    };
+
+    assert!(send("protover 2")[0].ends_with("done=1"));
+    assert_eq!(send("ping 7"), vec!["pong 7"]);
+    assert!(send("foo")[0].starts_with("Error (unknown command)"));
+
+    // Force mode only plays the moves it's given, which can be taken back
+    send("new");
+    send("force");
+    assert_eq!(send("usermove e2e4"), Vec::<String>::new());
+    assert_eq!(send("e7e5"), Vec::<String>::new());
+    assert_eq!(
+        send("usermove e1e3"),
+        vec!["Illegal move (not a legal move): e1e3"]
+    );
+    send("remove");
+    assert_eq!(send("usermove e2e4"), Vec::<String>::new());
+    send("undo");
+
+    // The engine's replies are taken back too, `remove` takes back both sides and `undo` just the reply
+    send("new");
+    send("sd 1");
+    assert!(send("usermove e2e4")[0].starts_with("move "));
+    send("remove");
+    send("force");
+    assert!(send("usermove e7e5")[0].starts_with("Illegal move"));
+    send("new");
+    send("sd 1");
+    assert!(send("usermove e2e4")[0].starts_with("move "));
+    send("force");
+    send("undo");
+    assert_eq!(send("usermove e7e5"), Vec::<String>::new());
+
+    // `go` makes the engine play the side to move
+    send("sd 2");
+    let out = send("go");
+    assert!(out[0].starts_with("move "), "{:?}", out);
+
+    assert!(send("setboard 8/8/8/8/8/8/8/4K3 w - - 0 1")[0].starts_with("tellusererror"));
+    assert_eq!(
+        send(&format!("setboard {}", DEFAULT_FEN)),
+        Vec::<String>::new()
+    );
+
+    // The result follows the mating move
+    send("setboard rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2");
+    assert_eq!(
+        send("go"),
+        vec!["move d8h4", "0-1 {Black won by checkmate}"]
+    );
+
+    let mut out = vec![];
+    assert!(!engine.handle("quit", &mut out));
+}
+
+/* ---------------------------------- FFI ----------------------------------- */
+#[cfg(feature = "ffi")]
+#[test]
+fn ffi_error_codes() {
+    use std::ffi::{c_char, CStr};
+    use std::ptr::null_mut;
+
+    use crate::ffi::*;
+
+    unsafe {
+        let mut board = null_mut();
+        assert_eq!(
+            chess_board_new(c"8/8/8/8/8/8/8/4K3 w - - 0 1".as_ptr(), &mut board),
+            ChessError::InvalidFen
+        );
+        assert!(board.is_null());
+        assert_eq!(
+            chess_board_new(std::ptr::null(), &mut board),
+            ChessError::Ok
+        );
+
+        let mut out = [0 as c_char; 8];
+        assert_eq!(
+            chess_board_fen(board, out.as_mut_ptr(), out.len()),
+            ChessError::BufferTooSmall
+        );
+        assert_eq!(
+            chess_board_make_move(board, c"e2e5".as_ptr()),
+            ChessError::IllegalMove
+        );
+        assert_eq!(
+            chess_board_make_move(board, c"e2e4".as_ptr()),
+            ChessError::Ok
+        );
+        assert_eq!(
+            chess_board_best_move(board, 100, out.as_mut_ptr(), out.len()),
+            ChessError::Ok
+        );
+        let best = CStr::from_ptr(out.as_ptr()).to_str().unwrap().to_string();
+        assert_eq!(
+            chess_board_make_move(board, out.as_ptr()),
+            ChessError::Ok,
+            "{}",
+            best
+        );
+
+        assert_eq!(
+            chess_board_moves(null_mut(), out.as_mut_ptr(), out.len()),
+            ChessError::NullPointer
+        );
+        chess_board_free(board);
+    }
+}
+
+/* ----------------------------------- LAN ---------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn lan_handshake() {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use crate::board::ChessColor;
+    use crate::clock::Clock;
+    use crate::conf::DEFAULT_FEN;
+    use crate::lan::{Handshake, Lan, LanEvent, LanRole};
+
+    let handshake = Handshake {
+        guest_color: ChessColor::Black,
+        fen: DEFAULT_FEN.to_string(),
+        moves: vec!["e2e4".to_string(), "e7e5".to_string()],
+        clock: Some(Clock {
+            white: 120.5,
+            black: 97.0,
+            increment: 2.0,
+        }),
+    };
+    assert_eq!(Handshake::parse(&handshake.lines()), Ok(handshake.clone()));
+    assert!(Handshake::parse(&["color Red".to_string()]).is_err());
+
+    // Waits for the next event, accepting the guest while polling the host
+    let next = |lan: &mut Lan| {
+        for _ in 0..500 {
+            if let Some(event) = lan.poll() {
+                return event;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        panic!("No event");
+    };
+
+    let mut host = Lan::host(0, handshake.clone()).unwrap();
+    let LanRole::Host(port) = host.role else {
+        panic!("Not hosting");
+    };
+    let mut guest = Lan::join(&format!("127.0.0.1:{}", port));
+    assert_eq!(next(&mut host), LanEvent::Connected(handshake.clone()));
+    assert_eq!(next(&mut guest), LanEvent::Connected(handshake));
+    assert!(host.connected && guest.connected);
+
+    guest.send_move("g1f3", Some(3.25));
+    assert_eq!(
+        next(&mut host),
+        LanEvent::Move("g1f3".to_string(), Some(3.25))
+    );
+    host.send_move("b8c6", None);
+    assert_eq!(next(&mut guest), LanEvent::Move("b8c6".to_string(), None));
+    // The host's clock and flags
+    let clock = Clock {
+        white: 118.25,
+        black: 97.0,
+        increment: 2.0,
+    };
+    host.send_clock(&clock);
+    assert_eq!(next(&mut guest), LanEvent::Clock(clock));
+    host.send_flag(ChessColor::White);
+    assert_eq!(next(&mut guest), LanEvent::Flag(ChessColor::White));
+    // Chat is one line, even if typed with newlines
+    guest.send_chat("good luck\nhave fun");
+    assert_eq!(
+        next(&mut host),
+        LanEvent::Chat("good luck have fun".to_string())
+    );
+
+    // Dropping one side closes the connection
+    drop(guest);
+    assert!(matches!(next(&mut host), LanEvent::Disconnected(_)));
+    assert!(!host.connected);
+
+    // Nothing is listening anymore
+    let mut refused = Lan::join(&format!("127.0.0.1:{}", port));
+    assert!(matches!(next(&mut refused), LanEvent::Disconnected(_)));
+}
+
+/* -------------------------------- Broadcast ------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn broadcast_updates() {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use crate::board::Board;
+    use crate::broadcast::{Broadcast, Spectator, Update};
+    use crate::conf::DEFAULT_FEN;
+    use crate::pgn::{movetext_tail, parse_move};
+
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    let start = board.clone();
+    let mut moves = vec![];
+    for text in ["e4", "e5", "Nf3", "Nc6", "Bb5"] {
+        let mov = parse_move(&board, text).unwrap();
+        board.move_piece(&mov.0, &mov.1, true);
+        moves.push(mov);
+    }
+    assert_eq!(movetext_tail(&start, &moves, 3), "2. Nf3 Nc6 3. Bb5");
+    assert_eq!(movetext_tail(&start, &moves, 2), "2... Nc6 3. Bb5");
+    assert_eq!(movetext_tail(&start, &[], 10), "");
+
+    // Spectators get the latest update when they connect, then each one after
+    let mut broadcast = Broadcast::start(0).unwrap();
+    broadcast.publish(&start, "*".to_string(), "".to_string());
+    let spectator = Spectator::watch(&format!("127.0.0.1:{}", broadcast.port));
+    let next = || {
+        for _ in 0..500 {
+            if let Some(update) = spectator.poll().unwrap() {
+                return update;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        panic!("No update");
+    };
+    assert_eq!(next().changes, Vec::<String>::new());
+    let tail = movetext_tail(&start, &moves, 10);
+    broadcast.publish(
+        &board,
+        "1. e4 e5 2. Nf3 Nc6 3. Bb5 *".to_string(),
+        tail.clone(),
+    );
+    let update = Update {
+        fen: board.as_fen(),
+        pgn: "1. e4 e5 2. Nf3 Nc6 3. Bb5 *".to_string(),
+        tail,
+        changes: [
+            "b8 n .", "c6 . n", "e7 p .", "e5 . p", "e4 . P", "f3 . N", "b5 . B", "e2 P .",
+            "f1 B .", "g1 N .",
+        ]
+        .iter()
+        .map(|change| change.to_string())
+        .collect(),
+    };
+    let mut received = next();
+    received.changes.sort();
+    let mut expected = update.clone();
+    expected.changes.sort();
+    assert_eq!(received, expected);
+
+    let state = reqwest::blocking::get(format!("http://127.0.0.1:{}/state", broadcast.port))
+        .unwrap()
+        .text()
+        .unwrap();
+    assert_eq!(
+        serde_json::from_str::<Update>(&state).unwrap().fen,
+        update.fen
+    );
+
+    // Stopping ends the stream
+    drop(broadcast);
+    for _ in 0..500 {
+        if spectator.poll().is_err() {
+            return;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    panic!("Broadcast didn't end");
+}
+
+/* ---------------------------------- Diff ---------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn board_diff() {
+    use crate::board::Board;
+    use crate::conf::DEFAULT_FEN;
+    use crate::diff::moved_piece;
+    use crate::pgn::parse_move;
+
+    // (position, move, changes)
+    let cases = [
+        (DEFAULT_FEN, "e4", vec!["e4 . P", "e2 P ."]),
+        (
+            "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+            "O-O",
+            vec!["e1 K .", "f1 . R", "g1 . K", "h1 R ."],
+        ),
+        (
+            "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+            "exd6",
+            vec!["d6 . P", "d5 p .", "e5 P ."],
+        ),
+        (
+            "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1",
+            "b8=Q",
+            vec!["b8 . Q", "b7 P ."],
+        ),
+        (
+            "4k3/8/8/8/8/8/5q2/4K3 w - - 0 1",
+            "Kxf2",
+            vec!["f2 q K", "e1 K ."],
+        ),
+    ];
+    for (fen, text, expected) in cases {
+        let before = Board::from_fen(fen);
+        let (from, to) = parse_move(&before, text).unwrap();
+        let mut after = before.clone();
+        after.move_piece(&from, &to, true);
+
+        let changes = before.diff(&after);
+        let texts: Vec<String> = changes.iter().map(|c| c.as_text()).collect();
+        assert_eq!(texts, expected, "{}", text);
+        assert_eq!(moved_piece(&changes), Some((from, to)), "{}", text);
+        assert!(after.diff(&after).is_empty());
+    }
+
+    // Unrelated positions don't have a single moved piece
+    let start = Board::from_fen(DEFAULT_FEN);
+    let other = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    assert_eq!(moved_piece(&start.diff(&other)), None);
+    assert_eq!(moved_piece(&other.diff(&start)), None);
+}
+
+/* -------------------------------- Updates --------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn update_check() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::spawn;
+
+    use crate::updates::{latest_release, Release};
+
+    let json =
+        r#"{"tag_name": "v0.10.1", "html_url": "https://example.com/v0.10.1", "draft": false}"#;
+    let release = Release::parse(json).unwrap();
+    assert_eq!(release.version, "0.10.1");
+    assert!(release.is_newer("0.2.0"));
+    assert!(release.is_newer("0.9.9"));
+    assert!(!release.is_newer("0.10.1"));
+    assert!(!release.is_newer("1.0.0"));
+    assert!(Release::parse("{}").is_err());
+
+    // The second check sends the ETag from the first, and gets back the cached release
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/latest", listener.local_addr().unwrap());
+    let server = spawn(move || {
+        let mut etags = vec![];
+        for stream in listener.incoming().take(2) {
+            let mut stream = stream.unwrap();
+            let mut etag = None;
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(": ") {
+                    if name.eq_ignore_ascii_case("if-none-match") {
+                        etag = Some(value.to_string());
+                    }
+                }
+            }
+            let response = match &etag {
+                Some(_) => "HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n".to_string(),
+                None => format!(
+                    "HTTP/1.1 200 OK\r\nETag: \"abc\"\r\nContent-Length: {}\r\n\r\n{}",
+                    json.len(),
+                    json
+                ),
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+            etags.push(etag);
+        }
+        etags
+    });
+
+    let cache = std::env::temp_dir().join(format!("chess-ai-update-{}.json", std::process::id()));
+    std::fs::remove_file(&cache).ok();
+    assert_eq!(latest_release(&url, &cache).unwrap(), release);
+    assert_eq!(latest_release(&url, &cache).unwrap(), release);
+    std::fs::remove_file(&cache).ok();
+    assert_eq!(
+        server.join().unwrap(),
+        vec![None, Some("\"abc\"".to_string())]
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn settings_are_saved() {
+    use crate::settings::Settings;
+
+    let path = std::env::temp_dir().join(format!("chess-ai-settings-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    std::fs::remove_file(path).ok();
+    assert_eq!(Settings::load(path).unwrap(), Settings::new());
+
+    let mut settings = Settings::new();
+    settings.check_updates = false;
+    settings.volume = 0.5;
+    settings.save(path).unwrap();
+    assert_eq!(Settings::load(path).unwrap(), settings);
+
+    // Saved before the update check could be turned off
+    let mut json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    json.as_object_mut().unwrap().remove("check_updates");
+    std::fs::write(path, json.to_string()).unwrap();
+    assert!(Settings::load(path).unwrap().check_updates);
+    std::fs::remove_file(path).ok();
+}
+
+/* ---------------------------------- Serve --------------------------------- */
+#[test]
+fn serve_api() {
+    use std::net::TcpListener;
+    use std::thread::spawn;
+
+    use serde_json::Value;
+
+    use crate::serve::{handle, parse_args, serve, Slot, MAX_SEARCHES, SEARCHES};
+
+    let get = |target: &str| serde_json::from_str::<Value>(&handle(target).unwrap()).unwrap();
+
+    // Spaces can be `+` or `%20`
+    let legal = get("/legal?fen=rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR+w+KQkq+-+0+1");
+    let moves = legal["moves"].as_array().unwrap();
+    assert_eq!(moves.len(), 20);
+    assert!(moves.contains(&serde_json::json!({ "move": "g1f3", "san": "Nf3" })));
+    let legal = get("/legal?fen=4k3/P7/8/8/8/8/8/4K3%20w%20-%20-%200%201");
+    assert!(legal["moves"]
+        .as_array()
+        .unwrap()
+        .contains(&serde_json::json!({ "move": "a7a8q", "san": "a8=Q+" })));
+
+    let best = get("/bestmove?fen=7k/6pp/8/8/8/8/8/R3K3+w+-+-+0+1&ms=500");
+    assert_eq!(best["bestmove"], "a1a8");
+    assert_eq!(best["san"], "Ra8#");
+    assert_eq!(best["pv"][0], "a1a8");
+    assert_eq!(best["score"], 20000 - 1);
+    assert!(best["nodes"].as_u64().unwrap() > 0);
+    let book = get("/bestmove?fen=rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR+w+KQkq+-+0+1");
+    assert_eq!((&book["depth"], &book["score"]), (&0.into(), &Value::Null));
+
+    // Searches past the limit are turned away until one finishes
+    let taken: Vec<_> = (0..MAX_SEARCHES)
+        .map(|_| Slot::take(&SEARCHES, MAX_SEARCHES).unwrap())
+        .collect();
+    let busy = handle("/bestmove?fen=7k/6pp/8/8/8/8/8/R3K3+w+-+-+0+1&ms=10");
+    assert_eq!(busy.unwrap_err().0, "503 Service Unavailable");
+    drop(taken);
+    assert!(handle("/bestmove?fen=7k/6pp/8/8/8/8/8/R3K3+w+-+-+0+1&ms=10").is_ok());
+
+    // Only this machine can connect unless a host is given
+    let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+    assert_eq!(parse_args(&[]), Some(("127.0.0.1".to_string(), 8000)));
+    assert_eq!(
+        parse_args(&args(&["9000", "--host", "0.0.0.0"])),
+        Some(("0.0.0.0".to_string(), 9000))
+    );
+    assert_eq!(parse_args(&args(&["--host"])), None);
+    assert_eq!(parse_args(&args(&["port"])), None);
+
+    assert_eq!(handle("/legal").unwrap_err().0, "400 Bad Request");
+    assert_eq!(
+        handle("/legal?fen=nonsense").unwrap_err().0,
+        "400 Bad Request"
+    );
+    assert_eq!(
+        handle("/bestmove?fen=7k/6pp/8/8/8/8/8/R3K3+w+-+-+0+1&ms=soon").unwrap_err(),
+        ("400 Bad Request", "Bad ms soon".to_string())
+    );
+    assert_eq!(
+        handle("/bestmove?fen=R6k/6pp/8/8/8/8/8/4K3+b+-+-+0+1").unwrap_err(),
+        ("400 Bad Request", "The game is over".to_string())
+    );
+    assert_eq!(handle("/").unwrap_err().0, "404 Not Found");
+
+    // Over HTTP, with errors as JSON
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    spawn(move || serve(listener));
+    let response = reqwest::blocking::get(format!(
+        "http://{}/legal?fen=8/8/8/8/8/8/8/8+w+-+-+0+1",
+        address
+    ))
+    .unwrap();
+    assert_eq!(response.status(), 400);
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+    let body: Value = serde_json::from_str(&response.text().unwrap()).unwrap();
+    assert!(body["error"].is_string());
+}
+
+/* --------------------------------- Errors --------------------------------- */
+#[test]
+fn structured_errors() {
+    use crate::agent_opens::{parse_openings, write_openings};
+    use crate::board_extras::char_to_piece;
+    use crate::error::ChessError;
+    use crate::pgn::try_parse_move;
+
+    // The part of the FEN that's wrong
+    for (fen, part) in [
+        ("", "board"),
+        (
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "board",
+        ),
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR", "turn"),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+            "turn",
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQz - 0 1",
+            "castling",
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1",
+            "en passant",
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq z9 0 1",
+            "en passant",
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+            "fifty rule",
+        ),
+        (
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0",
+            "full moves",
+        ),
+    ] {
+        assert_eq!(
+            Board::parse_fen(fen).unwrap_err(),
+            ChessError::Fen(part),
+            "{}",
+            fen
+        );
+    }
+    assert!(Board::parse_fen(DEFAULT_FEN).is_ok());
+    assert_eq!(ChessError::Fen("turn").to_string(), "Invalid FEN (turn)");
+
+    assert_eq!(Loc::parse_notation("e4"), Ok(Loc::from_notation("e4")));
+    for notation in ["", "e", "e9", "i1", "e44", "E4"] {
+        assert_eq!(
+            Loc::parse_notation(notation),
+            Err(ChessError::Notation(notation.to_string()))
+        );
+    }
+    assert_eq!(char_to_piece(&'N'), Ok(PieceNames::Knight));
+    assert_eq!(
+        char_to_piece(&'x'),
+        Err(ChessError::Notation("x".to_string()))
+    );
+
+    let board = Board::from_fen(DEFAULT_FEN);
+    assert_eq!(try_parse_move(&board, " e4 "), Ok(mov("e2", "e4")));
+    assert_eq!(
+        try_parse_move(&board, " e5 ").unwrap_err().to_string(),
+        "Illegal move e5"
+    );
+    assert_eq!(
+        try_parse_move(&board, "aé1"),
+        Err(ChessError::IllegalMove("aé1".to_string()))
+    );
+
+    assert!(matches!(parse_openings(b"{"), Err(ChessError::Book(_))));
+    assert!(matches!(parse_openings(&[0xff]), Err(ChessError::Book(_))));
+    let empty = write_openings(&Default::default());
+    assert_eq!(parse_openings(&empty).unwrap().len(), 0);
+    let missing = std::fs::read("missing/file.txt").unwrap_err();
+    assert!(matches!(ChessError::from(missing), ChessError::Io(_)));
+}
+
+/* --------------------------------- Assets --------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn asset_folder() {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::assets::{asset_bytes, asset_dir, set_asset_dir};
+
+    let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+    assert_eq!(
+        asset_dir(&args(&["--no-update-check", "--assets", "packs/wood"])),
+        Some(PathBuf::from("packs/wood"))
+    );
+
+    let dir = std::env::temp_dir().join(format!("chess_ai_assets_{}", std::process::id()));
+    fs::create_dir_all(dir.join("pieces")).unwrap();
+    fs::write(dir.join("pieces/white_king.png"), b"custom").unwrap();
+
+    set_asset_dir(Some(dir.clone()));
+    assert_eq!(
+        &*asset_bytes("assets/pieces/white_king.png", b"baked"),
+        b"custom"
+    );
+    // Anything the pack doesn't have is built in
+    assert_eq!(&*asset_bytes("assets/sounds/move.wav", b"baked"), b"baked");
+
+    set_asset_dir(None);
+    assert_eq!(
+        &*asset_bytes("assets/pieces/white_king.png", b"baked"),
+        b"baked"
+    );
+    fs::remove_dir_all(dir).ok();
+}
+
+/* ----------------------------------- UI ----------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn ui_stack() {
+    use crate::ui::Stack;
+
+    let mut down = Stack::down(10.0, 20.0, 100.0, 5.0);
+    assert_eq!(down.place(30.0), (10.0, 20.0, 100.0, 30.0));
+    down.space(10.0);
+    assert_eq!(down.place(15.0), (10.0, 65.0, 100.0, 15.0));
+
+    // The first widget is the lowest
+    let mut up = Stack::up(10.0, 200.0, 100.0, 5.0);
+    assert_eq!(up.place(30.0), (10.0, 170.0, 100.0, 30.0));
+    assert_eq!(up.place(30.0), (10.0, 135.0, 100.0, 30.0));
+
+    let mut section = up.section(50.0);
+    assert_eq!(section.place(20.0), (10.0, 80.0, 100.0, 20.0));
+    section.set_gap(2.0);
+    assert_eq!(section.place(20.0), (10.0, 100.0, 100.0, 20.0));
+    assert_eq!(section.place(20.0), (10.0, 122.0, 100.0, 20.0));
+    assert_eq!(up.place(10.0), (10.0, 65.0, 100.0, 10.0));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn slider_range() {
+    use crate::util::SliderRange;
+
+    let range = SliderRange {
+        min: 0.25,
+        max: 3.0,
+        step: 0.25,
+        format: |speed| format!("{:.2}x", speed),
+    };
+    assert_eq!(range.snap(1.1), 1.0);
+    assert_eq!(range.snap(1.2), 1.25);
+    assert_eq!(range.snap(-5.0), 0.25);
+    assert_eq!(range.snap(10.0), 3.0);
+    assert_eq!(range.fraction(0.25), 0.0);
+    assert_eq!(range.fraction(3.0), 1.0);
+    assert_eq!(range.fraction(1.625), 0.5);
+    assert_eq!((range.format)(1.5), "1.50x");
+}
+
+/* --------------------------------- Events --------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn game_events() {
+    use crate::events::{move_events, EventBus, GameEvent};
+
+    let events = |fen: &str, from: &str, to: &str| {
+        let before = Board::from_fen(fen);
+        let (from, to) = mov(from, to);
+        let mut after = before.clone();
+        after.move_piece(&from, &to, true);
+        move_events(&before, &from, &to, &after)
+    };
+
+    // Quiet moves are only a move
+    let quiet = events(DEFAULT_FEN, "e2", "e4");
+    assert_eq!(
+        quiet,
+        vec![GameEvent::Move {
+            color: ChessColor::White,
+            from: Loc::from_notation("e2"),
+            to: Loc::from_notation("e4"),
+            san: "e4".to_string(),
+            coordinate: "e2e4".to_string(),
+            capture: false,
+        }]
+    );
+
+    // En passant takes the pawn beside the one moving
+    let en_passant = events("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5", "d6");
+    assert!(matches!(
+        en_passant[0],
+        GameEvent::Move { capture: true, .. }
+    ));
+    assert!(matches!(
+        en_passant[1],
+        GameEvent::Capture { at, piece } if at == Loc::from_notation("d5") && piece.name == PieceNames::Pawn
+    ));
+    assert_eq!(
+        en_passant[2],
+        GameEvent::EnPassant {
+            color: ChessColor::White,
+            at: Loc::from_notation("d5"),
+        }
+    );
+    // Other captures aren't
+    let capture = events("4k3/8/8/3p4/4P3/8/8/4K3 w - - 0 1", "e4", "d5");
+    assert!(!capture
+        .iter()
+        .any(|event| matches!(event, GameEvent::EnPassant { .. })));
+
+    let promotion = events("3rk3/2P5/8/8/8/8/8/4K3 w - - 0 1", "c7", "d8");
+    assert!(matches!(promotion[1], GameEvent::Capture { .. }));
+    assert!(matches!(
+        promotion[2],
+        GameEvent::Promotion {
+            piece: PieceNames::Queen,
+            ..
+        }
+    ));
+    assert_eq!(promotion[3], GameEvent::Check(ChessColor::Black));
+
+    let castle = events("4k3/8/8/8/8/8/8/R3K2R w KQ - 0 1", "e1", "c1");
+    assert_eq!(
+        castle[1],
+        GameEvent::Castle {
+            color: ChessColor::White,
+            kingside: false,
+        }
+    );
+
+    let mate = events("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1", "a8");
+    assert_eq!(
+        mate[1..],
+        [
+            GameEvent::Check(ChessColor::Black),
+            GameEvent::GameEnd(BoardState::Checkmate(ChessColor::Black)),
+        ]
+    );
+
+    // Each event goes to every subscriber, in order
+    let mut bus: EventBus<Vec<String>> = EventBus::new();
+    bus.subscribe(|log, event| log.push(format!("first {:?}", std::mem::discriminant(event))));
+    bus.subscribe(|log, event| {
+        if let GameEvent::Check(color) = event {
+            log.push(format!("check {:?}", color));
+        }
+    });
+    let mut log = vec![];
+    bus.publish(&mut log, &mate);
+    assert_eq!(log.len(), 4);
+    assert!(log[0].starts_with("first") && log[1].starts_with("first"));
+    assert_eq!(log[2], "check Black");
+}
+
+#[test]
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+fn eval_bar_win_chance() {
+    use crate::game::win_chance;
+
+    assert_eq!(win_chance(0), 0.5);
+    assert!(win_chance(400) > 0.9 && win_chance(-400) < 0.1);
+    assert!((win_chance(150) + win_chance(-150) - 1.0).abs() < 1e-6);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn illegal_flash_fades() {
+    use crate::conf::ILLEGAL_FLASH_TIME;
+    use crate::game::flash_strength;
+
+    assert_eq!(flash_strength(0.0), Some(1.0));
+    let half = flash_strength(ILLEGAL_FLASH_TIME / 2.0).unwrap();
+    assert!((half - 0.5).abs() < 1e-6);
+    assert_eq!(flash_strength(ILLEGAL_FLASH_TIME), None);
+    assert_eq!(flash_strength(-1.0), None);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn flipped_board() {
+    use crate::game::flip_fade;
+
+    assert_eq!(Loc::from_notation("a8").rotated(), Loc::from_notation("h1"));
+    assert_eq!(Loc::from_notation("e2").rotated(), Loc::from_notation("d7"));
+    let loc = Loc::from_notation("c6");
+    assert_eq!(loc.rotated().rotated(), loc);
+
+    // Darkest as the board turns, clear before and after
+    assert_eq!(flip_fade(0.0), 1.0);
+    assert!(flip_fade(-0.05) > 0.0 && flip_fade(-0.05) == flip_fade(0.05));
+    assert_eq!(flip_fade(-1.0), 0.0);
+    assert_eq!(flip_fade(1.0), 0.0);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn idle_frame_limit() {
+    use crate::frame_limit::{frame_due, IDLE_FPS};
+
+    let interval = 1.0 / IDLE_FPS;
+    assert!(!frame_due(1.0, 1.0 + interval / 2.0, false));
+    assert!(frame_due(1.0, 1.0 + interval / 2.0, true));
+    assert!(frame_due(1.0, 1.0 + interval, false));
+}
+
+#[test]
+fn attack_sets_match_cached() {
+    let board =
+        Board::from_fen("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5");
+    let white = board.attacks(ChessColor::White);
+    let black = board.attacks(ChessColor::Black);
+    assert_eq!((white, black), (board.attacks_white, board.attacks_black));
+    assert!(white.contains(&Loc::from_notation("f7")) && black.contains(&Loc::from_notation("f2")));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn background_budget() {
+    use std::cell::Cell;
+
+    use crate::scheduler::{Scheduler, Task};
+
+    thread_local! {
+        static CLOCK: Cell<f64> = const { Cell::new(0.0) };
+    }
+    let clock = || CLOCK.with(Cell::get);
+
+    /// Each task takes `ID` ms, and records that it ran
+    fn task<const ID: usize>(ran: &mut Vec<usize>, _deadline: f64) {
+        CLOCK.with(|clock| clock.set(clock.get() + ID as f64 / 1000.0));
+        ran.push(ID);
+    }
+    let tasks: [Task<Vec<usize>>; 3] = [task::<5>, task::<6>, task::<7>];
+
+    // Tasks that didn't fit in the 8ms budget go first next frame
+    let mut scheduler = Scheduler::new(0.008);
+    let mut ran = vec![];
+    assert_eq!(scheduler.run(&mut ran, &tasks, clock), 2);
+    assert_eq!(scheduler.run(&mut ran, &tasks, clock), 2);
+    assert_eq!(ran, vec![5, 6, 7, 5]);
+
+    // All run when there's time, starting with a different one each frame
+    let mut scheduler = Scheduler::new(1.0);
+    let mut ran = vec![];
+    assert_eq!(scheduler.run(&mut ran, &tasks, clock), 3);
+    assert_eq!(scheduler.run(&mut ran, &tasks, clock), 3);
+    assert_eq!(ran, vec![5, 6, 7, 6, 7, 5]);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn table_weights_and_export() {
+    use crate::board_eval::{export_tables, Phase};
+
+    let board =
+        Board::from_fen("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5");
+    assert_eq!(board.scale_endgame(board.eval_terms().total()), board.score);
+
+    // Weights scale each piece's tables on their own, without summing the board again
+    let material = board.material;
+    let tables = |weights| material.tables(Phase::Opening, &weights);
+    assert_eq!(tables([0.0; 6]), 0);
+    let knights = tables([0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+    assert_eq!(tables([0.0, 0.0, 2.0, 0.0, 0.0, 0.0]), knights * 2);
+    assert_eq!(
+        tables([1.0; 6]),
+        material.tables[Phase::Opening as usize].iter().sum::<i32>()
+    );
+
+    // Unweighted tables export as they're written, so they can be pasted back in
+    let source = include_str!("board_eval.rs");
+    let exported = export_tables();
+    let rows: Vec<_> = exported
+        .lines()
+        .filter(|line| line.starts_with("    ["))
+        .collect();
+    assert_eq!(rows.len(), 8 * 8);
+    for row in rows {
+        assert!(source.contains(&format!("    {}\n", row)), "{}", row);
+    }
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn move_stats_in_pgn() {
+    use crate::agent::MAX;
+    use crate::move_tree::MoveTree;
+    use crate::pgn::{export, export_tree, stats_comment, MoveStats};
+
+    let stats = MoveStats::new(1240, 5, 30);
+    assert_eq!(stats.emt_tag(), "[%emt 0:00:01.2]");
+    assert_eq!(
+        MoveStats::new(3_723_450, 5, 30).emt_tag(),
+        "[%emt 1:02:03.5]"
+    );
+    assert_eq!(stats.short(), "d5 +0.3 1.2s");
+    assert_eq!(MoveStats::new(0, 0, MAX).short(), "book 0.0s");
+
+    // Spectated moves already have the depth and score in their search comment
+    assert_eq!(
+        stats_comment(Some("Minimax: depth 5, +0.3, e5"), Some(&stats)).unwrap(),
+        "[%emt 0:00:01.2] Minimax: depth 5, +0.3, e5"
+    );
+    assert_eq!(stats_comment(Some("Nice"), None).unwrap(), "Nice");
+    assert_eq!(stats_comment(None, None), None);
+
+    let moves = [mov("e2", "e4"), mov("e7", "e5")];
+    let pgn = export(
+        &Board::from_fen(DEFAULT_FEN),
+        &moves,
+        &[None, stats_comment(None, Some(&stats))],
+        &[],
+        "Player",
+        "Minimax",
+        &BoardState::Normal,
+    );
+    assert!(pgn.ends_with("1. e4 e5 {[%emt 0:00:01.2] depth 5, +0.3} *\n"));
+
+    // Studied games keep them on their nodes
+    let mut tree = MoveTree::new();
+    let e4 = tree.add(None, moves[0], "e4".to_string());
+    let e5 = tree.add(Some(e4), moves[1], "e5".to_string());
+    tree.nodes[e5].stats = Some(stats);
+    let pgn = export_tree(
+        &Board::from_fen(DEFAULT_FEN),
+        &tree,
+        "Player",
+        "Minimax",
+        &BoardState::Normal,
+    );
+    assert!(pgn.ends_with("1. e4 e5 {[%emt 0:00:01.2] depth 5, +0.3} *\n"));
+}
+
+#[test]
+fn multi_pv_alternatives() {
+    use crate::agent::{AgentId, SearchLimits};
+
+    let board =
+        Board::from_fen("r2q1rk1/ppp2ppp/2np1n2/2b1p1B1/2B1P1b1/2NP1N2/PPP2PPP/R2Q1RK1 w - - 0 8");
+    let search = |multi_pv| {
+        let mut limits = SearchLimits::new(f64::INFINITY);
+        limits.max_depth = Some(3);
+        limits.multi_pv = multi_pv;
+        AgentId::MINIMAX.get_move(&board, &limits)
+    };
+
+    let (best, info) = search(1);
+    let info = info.unwrap();
+    assert!(info.alternatives.is_empty());
+
+    // Same move and score, with the alternatives scored as if nothing at the root was pruned
+    let (multi_best, multi) = search(3);
+    let multi = multi.unwrap();
+    let (_, all) = search(usize::MAX);
+    let all = all.unwrap();
+    assert_eq!(multi_best, best);
+    assert_eq!(multi.score, info.score);
+    assert_eq!(multi.alternatives.len(), 2);
+    assert_eq!(multi.alternatives, all.alternatives[..2]);
+    assert!(multi
+        .alternatives
+        .iter()
+        .all(|(score, pv)| *score <= info.score && pv.first() != best.as_ref()));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn handicap_positions() {
+    use crate::handicap::{Handicap, HANDICAPS};
+
+    for handicap in HANDICAPS.iter().flatten() {
+        for giver in [ChessColor::White, ChessColor::Black] {
+            assert!(Board::try_from_fen(&handicap.fen(giver)).is_ok());
+        }
+    }
+
+    assert_eq!(
+        Handicap::Knight.fen(ChessColor::White),
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1"
+    );
+    assert_eq!(
+        Handicap::Rook.fen(ChessColor::Black),
+        "1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1"
+    );
+    let board = Board::from_fen(&Handicap::Queen.fen(ChessColor::Black));
+    assert!(board.get(&Loc::parse_notation("d8").unwrap()).is_none());
+    assert!(board.score > 0);
+
+    // The player moves first either way
+    assert_eq!(
+        Board::from_fen(&Handicap::Move.fen(ChessColor::White)).turn,
+        ChessColor::Black
+    );
+    assert_eq!(Handicap::Move.fen(ChessColor::Black), DEFAULT_FEN);
+    assert_eq!(Handicap::Queen.name(), "Queen odds");
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn takeback_rewinds_to_the_player() {
+    use crate::agent::AgentId;
+    use crate::clock::TIME_CONTROLS;
+    use crate::game::{Game, GameOptions};
+
+    let play = |game: &mut Game, from: &str, to: &str| {
+        let (from, to) = mov(from, to);
+        game.move_piece(&from, &to);
+    };
+
+    // Against an agent, both the reply and the player's move are undone and slide back together
+    let mut options = GameOptions::new();
+    options.time_control = TIME_CONTROLS[1];
+    let mut game = Game::from_options(options);
+    // Back to the clock as the player moved, the time the agent used is given back
+    game.clock.as_mut().unwrap().tick(ChessColor::White, 5.0);
+    let clock = game.clock;
+    play(&mut game, "e2", "e4");
+    game.clock.as_mut().unwrap().tick(ChessColor::Black, 5.0);
+    play(&mut game, "e7", "e5");
+    game.arrows.push(mov("d7", "d5"));
+    play(&mut game, "g1", "f3");
+    play(&mut game, "b8", "c6");
+
+    game.takeback();
+    assert_eq!(game.moves.len(), 2);
+    assert_eq!(game.arrows, vec![mov("d7", "d5")]);
+    game.takeback();
+    assert_eq!(game.board.as_fen(), DEFAULT_FEN);
+    assert!(game.moves.is_empty() && game.comments.is_empty() && game.last_move.is_none());
+    assert_eq!(game.clock, clock);
+    assert_eq!(
+        game.current_tween
+            .iter()
+            .map(|(loc, _)| *loc)
+            .collect::<Vec<_>>(),
+        vec![Loc::from_notation("e7"), Loc::from_notation("e2")]
+    );
+    // Nothing left to take back
+    game.takeback();
+    assert_eq!(game.board.as_fen(), DEFAULT_FEN);
+
+    // Between two people, one move at a time
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    let mut game = Game::from_options(options);
+    play(&mut game, "e2", "e4");
+    play(&mut game, "e7", "e5");
+    game.takeback();
+    assert_eq!(game.moves, vec![mov("e2", "e4")]);
+    assert_eq!(game.board.turn, ChessColor::Black);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn explorer_search() {
+    use crate::explorer::Database;
+    use crate::pgn::parse_all;
+
+    let database = Database::from_pgn(
+        "[White \"A\"]\n[Black \"B\"]\n[Result \"1-0\"]\n1. d4 Nf6 2. c4 e6 1-0\n\n\
+         [White \"C\"]\n[Black \"D\"]\n[Result \"1/2-1/2\"]\n1. c4 Nf6 2. d4 g6 1/2-1/2\n\n\
+         [White \"E\"]\n[Black \"F\"]\n[Result \"0-1\"]\n1. d4 Nf6 2. Nf3 Ng8 3. Ng1 Nf6 0-1\n\n\
+         [White \"G\"]\n[Black \"H\"]\n1. e4 e4 *\n\n\
+         [White \"I\"]\n[Black \"J\"]\n1. e4 *",
+    )
+    .unwrap();
+    assert_eq!(database.games.len(), 4);
+    assert_eq!(database.skipped, 1);
+
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    let search = database.search(&board);
+    assert_eq!(search.games, vec![0, 1, 2, 3]);
+    let sans: Vec<_> = search
+        .continuations
+        .iter()
+        .map(|c| c.san.as_str())
+        .collect();
+    assert_eq!(sans, ["d4", "c4", "e4"]);
+    assert_eq!(
+        (
+            search.continuations[0].white_wins,
+            search.continuations[0].black_wins
+        ),
+        (1, 1)
+    );
+    assert_eq!(search.continuations[0].row(), "d4       2  50% 0% 50%");
+
+    // Both move orders reach the same position, and the position repeated in the third game counts once
+    play(&mut board, &[("c2", "c4"), ("g8", "f6"), ("d2", "d4")]);
+    let search = database.search(&board);
+    assert_eq!(search.games, vec![0, 1]);
+    let sans: Vec<_> = search
+        .continuations
+        .iter()
+        .map(|c| c.san.as_str())
+        .collect();
+    assert_eq!(sans, ["e6", "g6"]);
+    assert_eq!(search.continuations[1].draws, 1);
+
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    play(&mut board, &[("d2", "d4"), ("g8", "f6")]);
+    assert_eq!(database.search(&board).games, vec![0, 2]);
+    let rows = database.panel_rows(&board);
+    assert_eq!(rows[0], "2 games");
+    assert_eq!(rows.last().unwrap(), "E - F 0-1");
+
+    play(&mut board, &[("e2", "e4")]);
+    assert_eq!(
+        database.panel_rows(&board),
+        vec!["No games reached this position"]
+    );
+
+    // Stray comments after a result aren't a game of their own
+    let (games, skipped) = parse_all("1. e4 e5 1-0 {Resigned}\n\n1. d4 *").unwrap();
+    assert_eq!((games.len(), skipped), (2, 0));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn fog_of_war_reveals_reachable_squares() {
+    use crate::variant::Variant;
+
+    let mut board = Board::from_fen(DEFAULT_FEN);
+    assert_eq!(Variant::Standard.revealed(&board, ChessColor::White), None);
+
+    // Own pieces, and the two ranks in front of them
+    let revealed = Variant::FogOfWar
+        .revealed(&board, ChessColor::White)
+        .unwrap();
+    assert_eq!(revealed.len(), 32);
+    assert!(revealed.contains(&Loc::from_notation("e4")));
+    assert!(!revealed.contains(&Loc::from_notation("e5")));
+
+    // A piece that can be captured is seen, one that can't stays hidden
+    play(&mut board, &[("e2", "e4"), ("d7", "d5")]);
+    let revealed = Variant::FogOfWar
+        .revealed(&board, ChessColor::White)
+        .unwrap();
+    assert!(revealed.contains(&Loc::from_notation("d5")));
+    assert!(!revealed.contains(&Loc::from_notation("d7")));
+    assert!(!revealed.contains(&Loc::from_notation("e8")));
+
+    // From black's side while it's white's turn
+    let revealed = Variant::FogOfWar
+        .revealed(&board, ChessColor::Black)
+        .unwrap();
+    assert!(revealed.contains(&Loc::from_notation("e4")));
+    assert!(!revealed.contains(&Loc::from_notation("e2")));
+}
+
+#[test]
+fn fog_of_war_king_capture() {
+    use crate::pgn::{result, termination};
+    use crate::variant::Variant;
+
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1");
+    assert_eq!(board.state, BoardState::Check(ChessColor::White));
+    assert!(!is_legal(&board, "e1", "f1"));
+
+    board.set_variant(Variant::FogOfWar);
+    // Check isn't announced, and staying in it is allowed
+    assert_eq!(board.state, BoardState::Normal);
+    assert!(is_legal(&board, "e1", "f1"));
+    assert!(is_legal(&board, "e1", "e2"));
+
+    play(&mut board, &[("e1", "f1"), ("a1", "f1")]);
+    assert_eq!(board.state, BoardState::KingCaptured(ChessColor::White));
+    assert!(board.is_over());
+    assert_eq!(result(&board.state), "0-1");
+    assert_eq!(termination(&board.state), "Black won by taking the king");
+}
+
+#[test]
+fn three_check_and_atomic() {
+    use crate::variant::Variant;
+
+    // Three checks win, even with the king never mated
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2Q w - - 0 1");
+    board.set_variant(Variant::ThreeCheck);
+    play(&mut board, &[("h1", "h5"), ("e8", "d8"), ("h5", "h8")]);
+    assert_eq!(board.checks, (2, 0));
+    assert_eq!(board.state, BoardState::Check(ChessColor::Black));
+    play(&mut board, &[("d8", "c7"), ("h8", "h7")]);
+    assert_eq!(board.state, BoardState::ThreeChecks(ChessColor::Black));
+
+    // A capture takes the pieces around it but the pawns
+    let mut board = Board::from_fen("4k3/8/2npb3/3r4/8/8/8/3RK3 w - - 0 1");
+    board.set_variant(Variant::Atomic);
+    play(&mut board, &[("d1", "d5")]);
+    for square in ["d5", "d1", "c6", "e6"] {
+        assert!(board.get(&Loc::from_notation(square)).is_none());
+    }
+    assert!(board.get(&Loc::from_notation("d6")).is_some());
+
+    // Kings can't capture, or explode themselves
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/3r4/3QK3 w - - 0 1");
+    board.set_variant(Variant::Atomic);
+    let moves = board.moves(ChessColor::White);
+    assert!(!moves.contains(&mov("e1", "d2")));
+    assert!(!moves.contains(&mov("d1", "d2")));
+
+    // Blowing up the king wins
+    let mut board = Board::from_fen("3qk3/8/8/8/8/8/8/3RK3 w - - 0 1");
+    board.set_variant(Variant::Atomic);
+    play(&mut board, &[("d1", "d8")]);
+    assert_eq!(board.state, BoardState::Exploded(ChessColor::Black));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn horde_and_racing_kings() {
+    use crate::variant::{Variant, HORDE_FEN, RACING_KINGS_FEN};
+
+    // White has no king, but can still move
+    let mut board = Board::from_fen(HORDE_FEN);
+    board.set_variant(Variant::Horde);
+    assert_eq!(board.state, BoardState::Normal);
+    assert!(is_legal(&board, "b5", "b6"));
+
+    // Pawns on the first rank move two squares too, without en passent
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/P7 w - - 0 1");
+    board.set_variant(Variant::Horde);
+    play(&mut board, &[("a1", "a3")]);
+    assert_eq!(board.en_passent, None);
+
+    // Taking the last white piece wins for black
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/3rP3 b - - 0 1");
+    board.set_variant(Variant::Horde);
+    play(&mut board, &[("d1", "e1")]);
+    assert_eq!(board.state, BoardState::NoPieces(ChessColor::White));
+
+    // Giving check is illegal
+    let mut board = Board::from_fen(RACING_KINGS_FEN);
+    board.set_variant(Variant::RacingKings);
+    assert_eq!(board.state, BoardState::Normal);
+    let mut board = Board::from_fen("8/8/8/8/8/8/k7/6RK w - - 0 1");
+    board.set_variant(Variant::RacingKings);
+    assert!(!is_legal(&board, "g1", "g2"));
+    assert!(is_legal(&board, "g1", "g3"));
+
+    // The first king to the eighth rank wins, unless black catches up right after
+    let mut board = Board::from_fen("8/6K1/8/8/8/8/k7/8 w - - 0 1");
+    board.set_variant(Variant::RacingKings);
+    play(&mut board, &[("g7", "g8")]);
+    assert_eq!(board.state, BoardState::Outraced(ChessColor::Black));
+    let mut board = Board::from_fen("8/k5K1/8/8/8/8/8/8 w - - 0 1");
+    board.set_variant(Variant::RacingKings);
+    play(&mut board, &[("g7", "g8")]);
+    assert_eq!(board.state, BoardState::Normal);
+    play(&mut board, &[("a7", "a8")]);
+    assert_eq!(board.state, BoardState::Draw(DrawReason::RaceTie));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn key_moments_from_analysis() {
+    use crate::review::{key_moments, MomentKind};
+
+    let moves = [
+        mov("e2", "e4"),
+        mov("e7", "e5"),
+        mov("d1", "h5"),
+        mov("b8", "c6"),
+        mov("f1", "c4"),
+        mov("g8", "f6"),
+        mov("g1", "f3"),
+    ];
+    let evals = [150, -150, -140, -130, -120, 40, 19999, 50].map(Some);
+    let mut best = [None; 8];
+    best[0] = Some(mov("e2", "e4"));
+    best[6] = Some(mov("h5", "f7"));
+
+    let moments = key_moments(&Board::from_fen(DEFAULT_FEN), &moves, &evals, &best);
+    let summary: Vec<_> = moments
+        .iter()
+        .map(|moment| (moment.ply, moment.kind, moment.row(), moment.best))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            // No best move when it was the one played
+            (
+                0,
+                MomentKind::TurningPoint,
+                "1. e4: Turning point".to_string(),
+                None
+            ),
+            (
+                5,
+                MomentKind::Blunder,
+                "3... Nf6: Blunder".to_string(),
+                None
+            ),
+            (
+                6,
+                MomentKind::MissedMate,
+                "4. Nf3: Missed mate".to_string(),
+                Some(mov("h5", "f7"))
+            ),
+        ]
+    );
+    assert_eq!(moments[2].swing(), "M1 to +0.5");
+
+    // Moves without a score on both sides are skipped
+    let evals = [Some(150), None, Some(-150)];
+    assert!(key_moments(&Board::from_fen(DEFAULT_FEN), &moves[..2], &evals, &[]).is_empty());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn play_from_here_and_return() {
+    use crate::agent::AgentId;
+    use crate::game::{Game, GameOptions};
+
+    let play = |game: &mut Game, from: &str, to: &str| {
+        let (from, to) = mov(from, to);
+        game.move_piece(&from, &to);
+    };
+
+    // Between two people, so the position after black's move has white to move
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    let mut game = Game::from_options(options);
+    play(&mut game, "e2", "e4");
+    play(&mut game, "e7", "e5");
+    play(&mut game, "g1", "f3");
+    let fen = game.board.as_fen();
+
+    // The fork is against the agent, with the player on the side to move
+    game.play_from_here();
+    assert_eq!(game.board.as_fen(), fen);
+    assert_eq!(game.board.player_color, ChessColor::Black);
+    assert_eq!(game.agent, AgentId::MINIMAX);
+    assert!(game.moves.is_empty());
+    play(&mut game, "b8", "c6");
+    assert_eq!(game.moves.len(), 1);
+
+    game.return_to_game();
+    assert_eq!(game.board.as_fen(), fen);
+    assert_eq!(game.moves.len(), 3);
+    assert!(game.original.is_none());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn menu_focus_wraps() {
+    use crate::menu::move_focus;
+
+    assert_eq!(move_focus(None, 5, 1), 0);
+    assert_eq!(move_focus(None, 5, -1), 4);
+    assert_eq!(move_focus(Some(1), 5, 1), 2);
+    assert_eq!(move_focus(Some(4), 5, 1), 0);
+    assert_eq!(move_focus(Some(0), 5, -1), 4);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn turn_states() {
+    use crate::agent::AgentId;
+    use crate::board::BoardState;
+    use crate::game::{Game, GameOptions, TurnState};
+    use crate::util::Tween;
+
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    let mut game = Game::from_options(options);
+    assert_eq!(game.turn_state(), TurnState::PlayerTurn);
+    let (from, to) = mov("e2", "e4");
+    game.move_piece(&from, &to);
+    game.step_preview(-1);
+    assert_eq!(game.turn_state(), TurnState::Replaying);
+    game.preview = None;
+
+    // The agent waits for the player's move to land before thinking
+    game.agent = AgentId::MINIMAX;
+    game.current_tween = vec![(to, Tween::new((0.0, 0.0), (100.0, 0.0), 10.0))];
+    assert_eq!(game.turn_state(), TurnState::Animating);
+    game.current_tween.clear();
+    assert_eq!(
+        game.turn_state(),
+        TurnState::AgentThinking(AgentId::MINIMAX)
+    );
+
+    game.board.state = BoardState::Resigned(ChessColor::Black);
+    assert_eq!(game.turn_state(), TurnState::GameOver);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn autosave_restores_game() {
+    use std::fs::remove_file;
+
+    use crate::agent::AgentId;
+    use crate::autosave::GameRecord;
+    use crate::clock::TimeControl;
+    use crate::game::{Game, GameOptions};
+    use crate::variant::Variant;
+
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    options.variant = Variant::ThreeCheck;
+    options.time_control = Some(TimeControl {
+        base: 300.0,
+        increment: 3.0,
+    });
+    let mut game = Game::from_options(options);
+    for (from, to) in [("e2", "e4"), ("f7", "f6"), ("f1", "c4"), ("g8", "h6")] {
+        let (from, to) = mov(from, to);
+        game.move_piece(&from, &to);
+    }
+    game.clock.as_mut().unwrap().white = 120.0;
+
+    let path = std::env::temp_dir().join(format!("chess-ai-autosave-{}.json", std::process::id()));
+    game.record().save(&path).unwrap();
+    let record = GameRecord::load(&path).unwrap();
+    remove_file(&path).unwrap();
+    assert_eq!(record, game.record());
+
+    // Moves are replayed by the same rules, with the clock as it was saved
+    let restored = Game::restore(&record);
+    assert_eq!(restored.board.as_fen(), game.board.as_fen());
+    assert_eq!(restored.moves, game.moves);
+    assert_eq!(restored.board.variant, Variant::ThreeCheck);
+    assert_eq!(restored.clock.unwrap().white, 120.0);
+    assert_eq!(restored.agent, AgentId::CONTROL);
+
+    // A save that can't be read is the same as none
+    assert!(GameRecord::load(&path).is_none());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn underpromotion_preferences() {
+    use crate::pgn::{coordinate, promotion_piece, san};
+    use crate::settings::Promotion;
+
+    let mut board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    let (from, to) = mov("b7", "b8");
+    assert!(board.is_promotion(&from, &to));
+    let (king_from, king_to) = mov("e1", "e2");
+    assert!(!board.is_promotion(&king_from, &king_to));
+
+    board.promote_to = PieceNames::Knight;
+    assert_eq!(san(&board, &from, &to), "b8=N");
+    assert_eq!(coordinate(&board, &from, &to), "b7b8n");
+    board.move_piece(&from, &to, true);
+    assert_eq!(board.get(&to).unwrap().name, PieceNames::Knight);
+    // Only the one move underpromotes
+    assert_eq!(board.promote_to, PieceNames::Queen);
+
+    assert_eq!(promotion_piece("b8=N+"), Some(PieceNames::Knight));
+    assert_eq!(promotion_piece("b7b8r"), Some(PieceNames::Rook));
+    assert_eq!(promotion_piece("bxa8B"), Some(PieceNames::Bishop));
+    assert_eq!(promotion_piece("b8"), None);
+    assert_eq!(promotion_piece("Nf3"), None);
+
+    assert!(!Promotion::AutoQueen.asks(true));
+    assert!(Promotion::Ask.asks(false));
+    assert!(Promotion::CtrlToAsk.asks(true) && !Promotion::CtrlToAsk.asks(false));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn preview_steps_through_history() {
+    use crate::agent::AgentId;
+    use crate::game::{Game, GameOptions};
+
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    let mut game = Game::from_options(options);
+    for (from, to) in [("e2", "e4"), ("e7", "e5"), ("g1", "f3")] {
+        let (from, to) = mov(from, to);
+        game.move_piece(&from, &to);
+    }
+    let live = game.board.as_fen();
+
+    game.step_preview(-1);
+    let preview = game.preview.as_ref().unwrap();
+    assert_eq!(preview.ply, 2);
+    assert_eq!(preview.label, "1... e5");
+    game.step_preview(-5);
+    let preview = game.preview.as_ref().unwrap();
+    assert_eq!(
+        preview.board.as_fen(),
+        Board::from_fen(DEFAULT_FEN).as_fen()
+    );
+    assert_eq!(preview.label, "Start");
+    // The live board is left as it was
+    assert_eq!(game.board.as_fen(), live);
+
+    // Past the last move is the live game again
+    game.step_preview(5);
+    assert!(game.preview.is_none());
+
+    // So is playing a move
+    game.step_preview(-2);
+    let (from, to) = mov("b8", "c6");
+    game.move_piece(&from, &to);
+    assert!(game.preview.is_none());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn movers_index_target_squares() {
+    let board = Board::from_fen(DEFAULT_FEN);
+    let movers = board.movers(ChessColor::White);
+    let at = |square: &str| movers.get(&Loc::from_notation(square)).cloned();
+
+    assert_eq!(at("e4"), Some(vec![Loc::from_notation("e2")]));
+    assert_eq!(at("f3").map(|from| from.len()), Some(2));
+    assert_eq!(at("e5"), None);
+
+    // A promoting pawn is one mover, however many pieces it can become
+    let board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    let movers = board.movers(ChessColor::White);
+    assert_eq!(
+        movers.get(&Loc::from_notation("b8")),
+        Some(&vec![Loc::from_notation("b7")])
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn threats_from_passing() {
+    use crate::analysis::{passed, Analysis, Threat};
+    use crate::mate::solve_mate;
+
+    // Black threatens Ra1#, which white has to see on its own move
+    let board = Board::from_fen("r5k1/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+    let threat = passed(&board).unwrap();
+    assert_eq!(threat.turn, ChessColor::Black);
+    assert!(solve_mate(&threat, 1).is_some());
+
+    // Can't pass out of check
+    let board = Board::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1");
+    assert!(passed(&board).is_none());
+
+    let mut analysis = Analysis::new();
+    analysis.evals = vec![Some(-50)];
+    analysis.threats = vec![Some(-350)];
+    assert_eq!(
+        analysis.threat(0, ChessColor::Black),
+        Some(Threat::Gain(300))
+    );
+    analysis.threats = vec![None];
+    assert_eq!(analysis.threat(0, ChessColor::Black), None);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn analysis_worker() {
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
+
+    use crate::analysis::Analysis;
+
+    /// Updates `analysis` with `board` until the position after `ply` moves is analyzed
+    fn wait(analysis: &mut Analysis, board: &Board, ply: usize) {
+        let start = Instant::now();
+        while analysis.evals.get(ply).copied().flatten().is_none() {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "ply {} never analyzed",
+                ply
+            );
+            analysis.update(board, ply, 0.0, false);
+            sleep(Duration::from_millis(10));
+        }
+    }
+
+    let mut board =
+        Board::from_fen("r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5");
+    let mut analysis = Analysis::new();
+    analysis.update(&board, 0, 0.0, false);
+    board.move_piece(&Loc::from_notation("e1"), &Loc::from_notation("g1"), true);
+
+    // Both positions are queued on the one worker, and searched in turn
+    wait(&mut analysis, &board, 1);
+    assert!(analysis.evals[0].is_some());
+
+    // A takeback stops the worker, and the next position starts a new one
+    analysis.truncate(0);
+    assert_eq!(analysis.evals.len(), 1);
+    wait(&mut analysis, &board, 1);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn threat_arrow_is_the_threatened_move() {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use crate::analysis::Analysis;
+
+    // Black threatens Ra1#, drawn as the threat arrow on white's move
+    let board = Board::from_fen("r5k1/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+    let mut analysis = Analysis::new();
+    for _ in 0..500 {
+        analysis.update(&board, 0, 0.0, true);
+        if analysis.threat_moves.first().copied().flatten().is_some() {
+            break;
+        }
+        sleep(Duration::from_millis(10));
+    }
+    assert_eq!(analysis.threat_moves, vec![Some(mov("a8", "a1"))]);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn analysis_export_rows() {
+    use crate::review::{analysis_csv, move_analysis};
+
+    let start = Board::from_fen(DEFAULT_FEN);
+    let moves = [mov("f2", "f3"), mov("e7", "e5"), mov("g2", "g4")];
+    let evals = [Some(20), Some(-60), Some(-40), Some(-19999)];
+    let best = [Some(mov("e2", "e4")), None, None, Some(mov("d8", "h4"))];
+    let depths = [Some(6), Some(5), Some(5), Some(1)];
+    let rows = move_analysis(&start, &moves, &evals, &best, &depths);
+
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].played, "f3");
+    assert_eq!(rows[0].best.as_deref(), Some("e4"));
+    assert_eq!(rows[0].classification, Some("Inaccuracy"));
+    assert_eq!(rows[1].color, ChessColor::Black);
+    assert_eq!(rows[2].move_number, 2);
+    // Allowing mate is a blunder, and the mate is kept out of the eval
+    assert_eq!(rows[2].classification, Some("Blunder"));
+    assert_eq!(rows[2].eval_after, None);
+    assert_eq!(rows[2].mate_after, Some(-1));
+
+    let csv = analysis_csv(&rows);
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("ply,move_number,color,played"));
+    assert_eq!(lines[3], "2,2,White,g4,,-40,,,-1,5,Blunder");
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn opening_stats_by_opening() {
+    use crate::stats::{opening, PlayerStats};
+
+    let sicilian = [mov("e2", "e4"), mov("c7", "c5"), mov("a2", "a3")];
+    let (name, book_moves) = opening(&sicilian).unwrap();
+    assert!(name.starts_with("Sicilian"), "{name}");
+    assert_eq!(book_moves, 2);
+    assert_eq!(opening(&[mov("a2", "a3")]), None);
+
+    let mut stats = PlayerStats::default();
+    let white_wins = BoardState::Checkmate(ChessColor::Black);
+    assert!(stats.add_opening(&sicilian, ChessColor::White, &white_wins));
+    assert!(stats.add_opening(&sicilian, ChessColor::Black, &white_wins));
+    assert!(stats.add_opening(&sicilian[..2], ChessColor::White, &BoardState::Stalemate));
+    // Unfinished games and ones out of book from the first move aren't added
+    assert!(!stats.add_opening(&sicilian, ChessColor::White, &BoardState::Normal));
+    assert!(!stats.add_opening(&[mov("a2", "a3")], ChessColor::White, &white_wins));
+
+    let summaries = stats.summaries();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].games, 3);
+    assert_eq!(summaries[0].score, 0.5);
+    assert_eq!(summaries[0].deviations, vec![("2. a3".to_string(), 2)]);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn rating_follows_results() {
+    use crate::agent::AgentId;
+    use crate::rating::{expected_score, Opponent, Rating, STARTING_RATING};
+    use crate::settings::Difficulty;
+    use crate::stats::PlayerStats;
+
+    assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+    assert!((expected_score(1600.0, 1200.0) - 0.909).abs() < 0.001);
+
+    // Beating a stronger agent gains more than beating a weaker one
+    let (mut upset, mut expected) = (Rating::default(), Rating::default());
+    upset.add(1800.0, 1.0);
+    expected.add(600.0, 1.0);
+    assert!(upset.rating - STARTING_RATING > expected.rating - STARTING_RATING);
+    assert_eq!(upset.games, 1);
+
+    let hard = Opponent::new(AgentId::MINIMAX, None, Difficulty::Hard);
+    let mut stats = PlayerStats::default();
+    for _ in 0..30 {
+        let win = BoardState::Checkmate(ChessColor::Black);
+        assert!(stats.add_rated(&hard, ChessColor::White, &win));
+    }
+    assert!(!stats.rating.provisional());
+    assert_eq!(stats.rating.suggested(), hard);
+
+    // Agents that don't play can't be rated against, nor can unfinished games
+    let control = Opponent::new(AgentId::CONTROL, None, Difficulty::Normal);
+    let win = BoardState::Checkmate(ChessColor::Black);
+    assert!(!stats.add_rated(&control, ChessColor::White, &win));
+    assert!(!stats.add_rated(&hard, ChessColor::White, &BoardState::Normal));
+    assert_eq!(stats.rating.games, 30);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn special_moves_announced() {
+    use crate::agent::AgentId;
+    use crate::game::{Game, GameOptions};
+
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    options.fen = Some("4k3/1P6/8/8/8/8/8/4K2R w K - 0 1".to_string());
+    let mut game = Game::from_options(options);
+
+    let (from, to) = mov("e1", "g1");
+    game.move_piece(&from, &to);
+    let (text, _) = game.announcement.clone().unwrap();
+    assert_eq!(text, "White castled kingside (O-O)");
+
+    let (from, to) = mov("e8", "d7");
+    game.move_piece(&from, &to);
+    // Quiet moves leave the last announcement to fade out
+    assert_eq!(game.announcement.as_ref().unwrap().0, text);
+
+    let (from, to) = mov("b7", "b8");
+    game.move_piece(&from, &to);
+    assert_eq!(
+        game.announcement.as_ref().unwrap().0,
+        "Pawn promoted to a queen"
+    );
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn rated_games_turn_off_help() {
+    use crate::agent::AgentId;
+    use crate::game::{Game, GameOptions, MenuAction};
+
+    assert!(MenuAction::Takeback.helps());
+    assert!(MenuAction::Analyze.helps());
+    assert!(!MenuAction::CopyPgn.helps());
+
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    options.fen = Some("k7/8/1K6/8/8/8/8/7R w - - 0 1".to_string());
+    let casual = Game::from_options(options.clone());
+    assert!(!casual.rated());
+
+    options.rated = true;
+    let mut game = Game::from_options(options);
+    assert!(game.rated());
+    assert!(game.record().rated);
+
+    // Everything is back once the game is over, to go through it
+    let (from, to) = mov("h1", "h8");
+    game.move_piece(&from, &to);
+    assert!(game.board.is_over());
+    assert!(!game.rated());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn layout_fits_window() {
+    use crate::conf::{HEIGHT, WIDTH};
+    use crate::layout::Layout;
+
+    // The window opens at the size the board used to be fixed at
+    let opening = Layout::fit(WIDTH as f32, HEIGHT as f32, false, false);
+    assert_eq!(opening.square_size, 60.0);
+    assert_eq!(opening.margin, 16.0);
+    assert_eq!(opening.panel_width, 125.0);
+    assert_eq!(opening.panel_x(), 512.0);
+
+    // Wide windows are limited by their height, and the panel grows with the board
+    let big = Layout::fit(1920.0, 1080.0, false, false);
+    assert_eq!(big.square_size, (1080.0 - 32.0) / 8.0);
+    assert_eq!(big.panel_width, big.square_size * 2.0);
+
+    // Compact margins leave more of a small window to the board
+    let small = Layout::fit(480.0, 400.0, false, false);
+    let compact = Layout::fit(480.0, 400.0, true, false);
+    assert!(compact.margin < small.margin);
+    assert!(compact.square_size > small.square_size);
+    assert!(compact.panel_x() + compact.panel_width <= 480.0);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn tabs_open_switch_and_save() {
+    use crate::agent::AgentId;
+    use crate::game::{Game, GameOptions, MenuAction};
+    use crate::tabs::Tabs;
+
+    let mut options = GameOptions::new();
+    options.agent = AgentId::CONTROL;
+    let mut tabs = Tabs::new(Game::from_options(options));
+    assert!(!tabs.shown());
+    tabs.run_action(MenuAction::CloseTab);
+    assert_eq!(tabs.games.len(), 1);
+
+    tabs.run_action(MenuAction::NewTab);
+    assert!(tabs.shown());
+    assert_eq!(tabs.current, 1);
+    tabs.game().toggle_analysis_board();
+    let (from, to) = mov("e2", "e4");
+    tabs.game().move_piece(&from, &to);
+    assert_eq!(tabs.game().tab_name(), "Analysis");
+    tabs.run_action(MenuAction::NextTab);
+    assert_eq!(tabs.current, 0);
+    assert_eq!(tabs.game().tab_name(), "Two players");
+
+    let path = std::env::temp_dir().join("chess-ai-test-tabs.json");
+    let path = path.to_str().unwrap();
+    tabs.save(path).unwrap();
+    let restored = Tabs::load(path).unwrap().unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(restored.games.len(), 2);
+    assert_eq!(restored.current, 0);
+    assert!(restored.games[1].analysis_board);
+    assert_eq!(restored.games[1].moves, vec![(from, to)]);
+
+    tabs.run_action(MenuAction::CloseTab);
+    assert_eq!(tabs.games.len(), 1);
+    assert_eq!(tabs.game().tab_name(), "Analysis");
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn dropped_files_are_read_by_extension() {
+    use crate::dropped::{read_dropped, Dropped};
+    use crate::position::epd_to_fen;
+
+    let fen = "4k3/8/8/8/8/8/8/4K2R w K - 0 1";
+    let positions = read_dropped("mates.FEN", &format!("{}\nnot a fen\n\n", fen)).unwrap();
+    assert_eq!(
+        positions,
+        vec![Dropped::Position {
+            fen: fen.to_string(),
+            id: None
+        }]
+    );
+    assert_eq!(positions[0].label(0), "Position 1");
+
+    let (fen, id) =
+        epd_to_fen("4k3/8/8/8/8/8/8/4K2R w K - bm Rh8+; hmvc 3; id \"Rook mate\";").unwrap();
+    assert_eq!(fen, "4k3/8/8/8/8/8/8/4K2R w K - 3 1");
+    assert_eq!(id.as_deref(), Some("Rook mate"));
+    assert_eq!(epd_to_fen("4k3/8 w"), None);
+
+    let games = read_dropped(
+        "games.pgn",
+        "[White \"Alice\"]\n[Black \"Bob\"]\n\n1. e4 e5 1-0\n\n1. d4 *",
+    )
+    .unwrap();
+    assert_eq!(games.len(), 2);
+    assert_eq!(games[0].label(0), "Alice - Bob");
+    assert!(matches!(games[1], Dropped::Game(_)));
+
+    assert!(read_dropped("board.png", "").is_err());
+    assert!(read_dropped("broken.fen", "not a fen").is_err());
+}
+
+#[test]
+fn batch_eval_reads_fen_and_epd() {
+    use crate::batch::{evaluate_all, parse_args, BatchLimit};
+
+    let args: Vec<String> = ["mates.epd", "--depth", "2"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args),
+        Ok(("mates.epd".to_string(), BatchLimit::Depth(2)))
+    );
+    assert!(parse_args(&[]).is_err());
+
+    let text = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\n6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"back rank\";\nnonsense\nR5k1/5ppp/8/8/8/8/8/6K1 b - - 1 1";
+    let evaluations: Vec<_> = evaluate_all(text, BatchLimit::Depth(2)).collect();
+    assert_eq!(evaluations.len(), 4);
+
+    assert_eq!(evaluations[0].line, 1);
+    assert_eq!(evaluations[0].bestmove.as_deref(), Some("a1a8"));
+    assert_eq!(evaluations[0].san.as_deref(), Some("Ra8#"));
+    assert!(evaluations[0].score.unwrap() > 0);
+
+    assert_eq!(evaluations[1].line, 3);
+    assert_eq!(evaluations[1].id.as_deref(), Some("back rank"));
+    assert_eq!(evaluations[1].fen, "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+    assert_eq!(evaluations[1].bestmove, evaluations[0].bestmove);
+
+    assert!(evaluations[2].error.is_some());
+    assert_eq!(evaluations[3].error.as_deref(), Some("The game is over"));
+    let json = serde_json::to_string(&evaluations[3]).unwrap();
+    assert!(!json.contains("bestmove"));
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn search_tree_records_root_moves() {
+    use crate::agent::{AgentId, SearchLimits};
+    use crate::pgn::search_tree_lines;
+
+    let board = Board::from_fen("4k3/2p5/8/3p4/8/2N5/5PPP/4K3 w - - 0 1");
+    let mut limits = SearchLimits::new(f64::INFINITY);
+    limits.max_depth = Some(3);
+    let (_, info) = AgentId::MINIMAX.get_move(&board, &limits);
+    assert!(info.unwrap().tree.is_empty());
+
+    limits.search_tree = true;
+    let (best, info) = AgentId::MINIMAX.get_move(&board, &limits);
+    let info = info.unwrap();
+    assert_eq!(info.depth, 3);
+    assert_eq!(info.tree.len(), board.moves(board.turn).len());
+    assert!(info.tree.iter().any(|root| Some(root.mov) == best));
+    assert!(info.tree.iter().map(|root| root.nodes).sum::<u64>() < info.nodes);
+    // Later root moves are refuted without searching every reply
+    assert!(info
+        .tree
+        .iter()
+        .any(|root| root.cutoff.is_some() && root.replies.0 < root.replies.1));
+
+    let lines = search_tree_lines(&board, &info);
+    assert_eq!(lines.len(), info.tree.len() + 1);
+    assert!(lines[0].starts_with("depth 3"));
+    assert_eq!(lines.iter().filter(|line| line.starts_with('*')).count(), 1);
+}
+
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
+#[test]
+fn decisions_are_saved_and_reproduced() {
+    use crate::agent::{AgentId, SearchLimits};
+    use crate::repro::Decision;
+
+    let board = Board::from_fen("4k3/2p5/8/3p4/8/2N5/5PPP/4K3 w - - 0 1");
+    let mut limits = SearchLimits::new(f64::INFINITY);
+    limits.max_depth = Some(3);
+    let mut decision = Decision::start(&board, AgentId::MINIMAX, &limits, 42);
+    let (mov, info) = AgentId::MINIMAX.get_move(&board, &limits);
+    decision.finish(mov, info.as_ref(), None);
+    assert_eq!(decision.depth, 3);
+    assert_eq!(decision.mov.as_ref(), decision.pv.first());
+
+    let path = std::env::temp_dir().join("chess-ai-test-decision.json");
+    let path = path.to_str().unwrap();
+    decision.save(path).unwrap();
+    let loaded = Decision::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(loaded, decision);
+    assert!(loaded.differences(&loaded.rerun().unwrap()).is_empty());
+
+    // A hand edited dump is refused instead of crashing
+    let mut broken = loaded.clone();
+    broken.fen = "4k3/8/8/8/8/8/8/8 w - - 0 1".to_string();
+    broken.save(path).unwrap();
+    let err = Decision::load(path).unwrap_err();
+    std::fs::remove_file(path).unwrap();
+    assert!(err.starts_with("Invalid position"));
+    broken.fen = "not a fen".to_string();
+    assert!(broken.rerun().is_err());
+
+    let mut changed = loaded.clone();
+    changed.score += 1;
+    assert_eq!(
+        loaded.differences(&changed),
+        vec![format!("score: {} -> {}", loaded.score, changed.score)]
+    );
+}
+
+/* ----------------------------------- Log ---------------------------------- */
+#[cfg(feature = "gui")]
+#[test]
+fn log_keeps_the_newest_lines() {
+    use crate::agent::SearchInfo;
+    use crate::log::{log_lines, push_log, LogKind, LOG_LINES};
+    use crate::pgn::log_line;
+
+    for i in 0..LOG_LINES + 5 {
+        push_log(LogKind::System, format!("log test {}", i));
+    }
+    let lines = log_lines();
+    assert_eq!(lines.len(), LOG_LINES);
+    assert!(!lines.contains(&(LogKind::System, "log test 4".to_string())));
+    assert!(lines.contains(&(LogKind::System, format!("log test {}", LOG_LINES + 4))));
+
+    let board = Board::from_fen(DEFAULT_FEN);
+    let info = SearchInfo {
+        depth: 2,
+        score: 30,
+        pv: vec![mov("e2", "e4"), mov("e7", "e5")],
+        nodes: 100,
+        ..Default::default()
+    };
+    assert_eq!(log_line(&board, &info), "depth 2, +0.3, 100 nodes: e4 e5");
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn attackers_count_batteries() {
+    use crate::pgn::attack_lines;
+
+    let board = Board::from_fen("4k3/8/4p3/3n4/8/8/3Q4/3RK3 w - - 0 1");
+    let d5 = Loc::from_notation("d5");
+    let names: Vec<_> = board.attackers(&d5).iter().map(|p| p.name).collect();
+    assert_eq!(
+        names,
+        vec![PieceNames::Pawn, PieceNames::Queen, PieceNames::Rook]
+    );
+    assert_eq!(
+        attack_lines(&board, &d5).unwrap(),
+        ["Attacked 2: Q R".to_string(), "Defended 1: P".to_string()]
+    );
+    assert_eq!(
+        attack_lines(&board, &Loc::from_notation("e1")).unwrap(),
+        ["Attacked 0".to_string(), "Defended 2: R Q".to_string()]
+    );
+    assert!(attack_lines(&board, &Loc::from_notation("a4")).is_none());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn exchanges_stop_when_taking_back_loses() {
+    use crate::pgn::exchange_line;
+
+    // The knight is defended by a pawn, so the queen is lost for it and the pawn
+    let board = Board::from_fen("4k3/8/4p3/3n4/8/8/3Q4/3RK3 w - - 0 1");
+    let d5 = Loc::from_notation("d5");
+    assert_eq!(board.exchange(&d5), Some(-480));
+    assert_eq!(
+        exchange_line(&board, &d5).unwrap(),
+        "Taking the knight loses 4.8 pawns"
+    );
+
+    let board = Board::from_fen("4k3/8/8/3n4/8/8/3Q4/3RK3 w - - 0 1");
+    assert_eq!(board.exchange(&d5), Some(320));
+    assert_eq!(
+        exchange_line(&board, &d5).unwrap(),
+        "Taking the knight wins a knight"
+    );
+    assert_eq!(
+        exchange_line(&board, &Loc::from_notation("e8")).unwrap(),
+        "Nothing can take the king"
+    );
+    assert!(exchange_line(&board, &Loc::from_notation("a4")).is_none());
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn puzzle_rush() {
+    use crate::mate::solve_mate;
+    use crate::rush::{Rush, RushAnswer, PUZZLES, RUSH_LIVES};
+    use crate::stats::PlayerStats;
+
+    for (fen, moves) in PUZZLES {
+        let board = Board::from_fen(fen);
+        assert!(solve_mate(&board, moves).is_some(), "{}", fen);
+        assert!(solve_mate(&board, moves - 1).is_none(), "{}", fen);
+    }
+
+    // Mates in one come first
+    let mut rush = Rush::new(3, 5);
+    let board = rush.next_puzzle();
+    assert_eq!(rush.moves_left, 1);
+    let (from, to) = solve_mate(&board, 1).unwrap()[0];
+    assert_eq!(rush.answer(&board, &from, &to), RushAnswer::Solved);
+    assert_eq!(rush.solved, 1);
+
+    // A mate in two answered with the wrong move, then the right one
+    let board = Board::from_fen("k7/8/2K5/8/8/8/8/7R w - - 0 1");
+    rush.moves_left = 2;
+    assert_eq!(
+        rush.answer(&board, &Loc::from_notation("h1"), &Loc::from_notation("h2")),
+        RushAnswer::Wrong(Some(solve_mate(&board, 2).unwrap()[0]))
+    );
+    assert_eq!(rush.lives, RUSH_LIVES - 1);
+    let (from, to) = solve_mate(&board, 2).unwrap()[0];
+    assert!(matches!(
+        rush.answer(&board, &from, &to),
+        RushAnswer::Reply(_)
+    ));
+    assert_eq!(rush.moves_left, 1);
+
+    for _ in 1..RUSH_LIVES {
+        rush.answer(&board, &Loc::from_notation("h1"), &Loc::from_notation("h2"));
+    }
+    assert!(rush.over);
+
+    let mut stats = PlayerStats::default();
+    assert!(stats.add_rush(3, 4));
+    assert!(!stats.add_rush(3, 2));
+    assert_eq!(stats.rush_best[&3], 4);
+}
+
+#[cfg(feature = "gui")]
+#[test]
+fn sounds_of_a_move_are_merged() {
+    use crate::sound::{to_play, SoundKind, STALE_AFTER};
+
+    let queued = [
+        (SoundKind::Capture, 10.0),
+        (SoundKind::Tick, 10.0),
+        (SoundKind::Check, 10.0),
+        (SoundKind::Tick, 10.0),
+    ];
+    assert_eq!(
+        to_play(&queued, 10.0),
+        vec![SoundKind::Check, SoundKind::Tick]
+    );
+
+    // Left over from while the tab was in the background
+    let queued = [
+        (SoundKind::GameEnd, 10.0),
+        (SoundKind::Move, 10.0 + STALE_AFTER * 2.0),
+    ];
+    assert_eq!(
+        to_play(&queued, 10.0 + STALE_AFTER * 2.0),
+        vec![SoundKind::Move]
+    );
+    assert!(to_play(&[], 10.0).is_empty());
+}
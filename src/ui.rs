@@ -0,0 +1,236 @@
+//! Layout for the side panel, widgets are stacked in a column instead of placed with coordinates
+//!
+//! Widgets are immediate mode, they're created, updated, and drawn in the same call every frame
+
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use macroquad::text::{draw_text_ex, measure_text, TextParams};
+
+use crate::conf::COLOR_WHITE;
+use crate::layout::layout;
+use crate::util::{Button, Slider, SliderRange, Toggle};
+use crate::{font, ternary};
+
+lazy_static! {
+    /// Label of the slider being dragged, the drag lasts several frames but the slider is made again each one
+    static ref DRAGGED: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// Text params used by panel widgets, IE `text_params(15)`
+pub(crate) fn text_params(font_size: u16) -> TextParams {
+    TextParams {
+        font_size,
+        font_scale: 1.0,
+        color: COLOR_WHITE,
+        font: font(),
+        ..Default::default()
+    }
+}
+
+/// Splits `text` into lines that fit in `width`, between words unless one is too long on its own
+pub(crate) fn wrap(text: &str, width: f32, params: TextParams) -> Vec<String> {
+    let fits = |line: &str| {
+        measure_text(line, Some(params.font), params.font_size, params.font_scale).width <= width
+    };
+
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let joined = ternary!(
+            line.is_empty(),
+            word.to_string(),
+            format!("{} {}", line, word)
+        );
+        if fits(&joined) {
+            line = joined;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        line = String::new();
+        for c in word.chars() {
+            line.push(c);
+            if !fits(&line) && line.chars().count() > 1 {
+                line.pop();
+                lines.push(line);
+                line = c.to_string();
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// (x, y, w, h) of a placed widget
+pub(crate) type Rect = (f32, f32, f32, f32);
+
+/// Column of widgets, growing down from its top or up from its bottom
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Stack {
+    x: f32,
+    w: f32,
+    /// Edge the next widget is placed against
+    cursor: f32,
+    /// Space left between widgets
+    gap: f32,
+    up: bool,
+    /// Wether widgets take clicks, `false` while an overlay is open
+    active: bool,
+}
+impl Stack {
+    /// Column growing down from `y`
+    pub(crate) fn down(x: f32, y: f32, w: f32, gap: f32) -> Stack {
+        Stack {
+            x,
+            w,
+            cursor: y,
+            gap,
+            up: false,
+            active: true,
+        }
+    }
+
+    /// Column growing up from `bottom`, the first widget is the lowest
+    pub(crate) fn up(x: f32, bottom: f32, w: f32, gap: f32) -> Stack {
+        Stack {
+            up: true,
+            ..Stack::down(x, bottom, w, gap)
+        }
+    }
+
+    /// Top of the side panel, level with the board
+    pub(crate) fn panel_top() -> Stack {
+        let layout = layout();
+        Stack::down(layout.panel_x(), layout.top, layout.panel_width, 0.0)
+    }
+
+    /// Bottom of the side panel, half a margin below the bottom of the board
+    pub(crate) fn panel_bottom() -> Stack {
+        let layout = layout();
+        Stack::up(
+            layout.panel_x(),
+            layout.top + layout.square_size * 8.0 + layout.margin / 2.0,
+            layout.panel_width,
+            layout.margin / 2.0,
+        )
+    }
+
+    /// Sets wether widgets take clicks
+    pub(crate) fn active(mut self, active: bool) -> Stack {
+        self.active = active;
+        self
+    }
+
+    /// Takes the next `h` of the column
+    pub(crate) fn place(&mut self, h: f32) -> Rect {
+        if self.up {
+            self.cursor -= h;
+            let y = self.cursor;
+            self.cursor -= self.gap;
+            (self.x, y, self.w, h)
+        } else {
+            let y = self.cursor;
+            self.cursor += h + self.gap;
+            (self.x, y, self.w, h)
+        }
+    }
+
+    /// Sets the space left between the next widgets
+    pub(crate) fn set_gap(&mut self, gap: f32) {
+        self.gap = gap;
+    }
+
+    /// Leaves `h` empty, on top of the gap
+    pub(crate) fn space(&mut self, h: f32) {
+        self.cursor += ternary!(self.up, -h, h);
+    }
+
+    /// Takes the next `h` of the column for a column of its own, growing down
+    pub(crate) fn section(&mut self, h: f32) -> Stack {
+        let (x, y, w, _) = self.place(h);
+        Stack::down(x, y, w, 0.0).active(self.active)
+    }
+
+    /// Line of text, `h` high with the text on its bottom
+    pub(crate) fn label(&mut self, text: &str, h: f32, params: TextParams) {
+        let (x, y, _, h) = self.place(h);
+        draw_text_ex(text, x, y + h, params);
+    }
+
+    /// Lines of text, spaced by the height of the tallest
+    pub(crate) fn text(&mut self, text: &str, params: TextParams) {
+        let height =
+            measure_text(text, Some(params.font), params.font_size, params.font_scale).height;
+        let (x, y, _, _) = self.place(height * text.lines().count() as f32);
+        for (i, line) in text.lines().enumerate() {
+            draw_text_ex(line, x, y + height * (i as f32 + 1.0), params);
+        }
+    }
+
+    /// Full width button, returns `true` if it was clicked
+    pub(crate) fn button(&mut self, text: &str, h: f32) -> bool {
+        let (x, y, w, h) = self.place(h);
+        let mut button = Button::new(x, y, w, h, text);
+        let clicked = self.active && button.update();
+        button.draw();
+        clicked
+    }
+
+    /// Full width [Toggle] for `value`, returns `true` if it was flipped
+    pub(crate) fn toggle(&mut self, label: &str, h: f32, value: &mut bool) -> bool {
+        let (x, y, w, h) = self.place(h);
+        let mut toggle = Toggle::new(x, y, w, h, label, *value);
+        let flipped = self.active && toggle.update();
+        *value = toggle.value;
+        toggle.draw();
+        flipped
+    }
+
+    /// Full width [Slider] for `value`, returns `true` if it was dragged to a new value
+    /// - Labels have to be unique among the sliders on screen, they tell which one is being dragged
+    pub(crate) fn slider(
+        &mut self,
+        label: &str,
+        h: f32,
+        range: SliderRange,
+        value: &mut f32,
+    ) -> bool {
+        let (x, y, w, h) = self.place(h);
+        let mut dragged = DRAGGED.lock().unwrap();
+        let mut slider = Slider::new(x, y, w, h, label, range, *value)
+            .dragging(dragged.as_deref() == Some(label));
+
+        let changed = self.active && slider.update();
+        if slider.is_dragging() {
+            *dragged = Some(label.to_string());
+        } else if dragged.as_deref() == Some(label) {
+            *dragged = None;
+        }
+        if changed {
+            *value = slider.value;
+        }
+        slider.draw();
+        changed
+    }
+
+    /// Row of square icon buttons filling the width, `(icon, tooltip)` each
+    /// - Returns the index of the one clicked
+    pub(crate) fn icons(&mut self, icons: &[(&'static str, String)], gap: f32) -> Option<usize> {
+        let size = (self.w - gap * (icons.len() - 1) as f32) / icons.len() as f32;
+        let (x, y, _, _) = self.place(size);
+
+        let mut clicked = None;
+        for (i, (icon, tooltip)) in icons.iter().enumerate() {
+            let mut button = Button::icon(x + (size + gap) * i as f32, y, size, icon, tooltip);
+            if self.active && button.update() {
+                clicked = Some(i);
+            }
+            button.draw();
+        }
+        clicked
+    }
+}
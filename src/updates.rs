@@ -0,0 +1,135 @@
+//! Checking GitHub Releases for a newer version, on its own thread so startup doesn't wait on the network
+//!
+//! The last release seen is cached at [cache_path] along with its ETag, so GitHub only sends it again once there's
+//! a new one. Turn off _Check for updates_ in the settings, set [OPT_OUT_VAR], or pass `--no-update-check` to skip the
+//! check
+//!
+//! - A newer release is stored in [UPDATE], and shown as a banner in the GUI until it's dismissed
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::spawn;
+
+use lazy_static::lazy_static;
+use reqwest::header::{ETAG, IF_NONE_MATCH, USER_AGENT};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::platform::info;
+use crate::settings::settings;
+
+pub(crate) const RELEASES_LINK: &str =
+    "https://api.github.com/repos/jameslinimk/chess-ai/releases/latest";
+
+/// Where the last response is kept between runs, in the temp directory with the autosave
+pub(crate) fn cache_path() -> PathBuf {
+    std::env::temp_dir().join("chess-ai-update-check.json")
+}
+
+/// Environment variable that turns the check off when set to anything
+pub(crate) const OPT_OUT_VAR: &str = "CHESS_AI_NO_UPDATE_CHECK";
+
+lazy_static! {
+    /// Newer release found by the check, `None` until then and once the banner is dismissed
+    pub(crate) static ref UPDATE: Mutex<Option<Release>> = Mutex::new(None);
+}
+
+/// A published version of the game
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Release {
+    /// Without the `v`, IE `0.3.0`
+    pub(crate) version: String,
+    /// Page to download it from
+    pub(crate) url: String,
+}
+impl Release {
+    /// Reads the parts needed from a GitHub release, IE from [RELEASES_LINK]
+    pub(crate) fn parse(json: &str) -> Result<Release, String> {
+        #[derive(Deserialize)]
+        struct Raw {
+            tag_name: String,
+            html_url: String,
+        }
+
+        let raw: Raw = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        Ok(Release {
+            version: raw.tag_name.trim_start_matches('v').to_string(),
+            url: raw.html_url,
+        })
+    }
+
+    /// Wether this release comes after `current`, comparing each number in turn
+    pub(crate) fn is_newer(&self, current: &str) -> bool {
+        let numbers = |version: &str| -> Vec<u32> {
+            version
+                .split('.')
+                .map(|part| part.parse().unwrap_or(0))
+                .collect()
+        };
+        numbers(&self.version) > numbers(current)
+    }
+}
+
+/// Response from the last check, read from and saved to [cache_path]
+#[derive(Default, Serialize, Deserialize)]
+struct Cache {
+    etag: Option<String>,
+    release: Option<Release>,
+}
+
+/// Wether the player has turned the check off, in the settings, with [OPT_OUT_VAR], or with `--no-update-check` in
+/// `args`
+pub(crate) fn opted_out(args: &[String]) -> bool {
+    !settings().check_updates
+        || std::env::var_os(OPT_OUT_VAR).is_some()
+        || args.iter().any(|arg| arg == "--no-update-check")
+}
+
+/// Starts checking for a newer release on its own thread, storing it in [UPDATE] if there is one
+pub(crate) fn start_check() {
+    spawn(|| match latest_release(RELEASES_LINK, &cache_path()) {
+        Ok(release) if release.is_newer(env!("CARGO_PKG_VERSION")) => {
+            info!("Update available: {}", release.version);
+            *UPDATE.lock().unwrap() = Some(release);
+        }
+        Ok(_) => info!("Up to date!"),
+        Err(err) => info!("Couldn't check for updates: {}", err),
+    });
+}
+
+/// Latest release from `url`, asking for it only if it changed since the one cached at `cache_path`
+pub(crate) fn latest_release(url: &str, cache_path: &Path) -> Result<Release, String> {
+    let cache: Cache = fs::read_to_string(cache_path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default();
+
+    // GitHub turns away requests without a user agent
+    let mut request = reqwest::blocking::Client::new()
+        .get(url)
+        .header(USER_AGENT, concat!("chess-ai/", env!("CARGO_PKG_VERSION")));
+    if let (Some(etag), Some(_)) = (&cache.etag, &cache.release) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request.send().map_err(|err| err.to_string())?;
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return cache.release.ok_or_else(|| "Nothing cached".to_string());
+    }
+    let response = response.error_for_status().map_err(|err| err.to_string())?;
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+    let release = Release::parse(&response.text().map_err(|err| err.to_string())?)?;
+
+    let cache = Cache {
+        etag,
+        release: Some(release.clone()),
+    };
+    // Only costs a full response next time
+    fs::write(cache_path, serde_json::to_string(&cache).unwrap()).ok();
+    Ok(release)
+}
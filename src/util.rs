@@ -1,19 +1,31 @@
 use std::f32::consts::PI;
+use std::ops::BitOrAssign;
 
 use derive_new::new;
-use macroquad::prelude::{is_mouse_button_down, is_mouse_button_pressed, MouseButton};
-use macroquad::rand::gen_range;
+#[cfg(feature = "gui")]
+use macroquad::prelude::{
+    get_char_pressed, is_key_pressed, is_mouse_button_down, is_mouse_button_pressed, KeyCode,
+    MouseButton,
+};
+#[cfg(feature = "gui")]
 use macroquad::shapes::draw_rectangle;
+#[cfg(feature = "gui")]
 use macroquad::text::{draw_text_ex, measure_text, TextDimensions, TextParams};
-#[cfg(not(target_family = "wasm"))]
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
 use macroquad::time::get_frame_time;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "gui")]
 use crate::camera::camera;
+#[cfg(feature = "gui")]
 use crate::conf::{
-    COLOR_BUTTON, COLOR_BUTTON_HOVER, COLOR_BUTTON_PRESSED, COLOR_WHITE, MARGIN, SQUARE_SIZE,
+    COLOR_BACKGROUND, COLOR_BUTTON, COLOR_BUTTON_HOVER, COLOR_BUTTON_PRESSED, COLOR_WHITE,
 };
+use crate::error::ChessError;
+#[cfg(feature = "gui")]
 use crate::font;
+#[cfg(feature = "gui")]
+use crate::layout::layout;
 
 /// Makes sure the board part of fen is valid, doesn't check if there are 5 kings, 500 pawns, etc
 pub(crate) fn validate_fen(fen: &str) -> bool {
@@ -43,6 +55,44 @@ pub(crate) fn validate_fen(fen: &str) -> bool {
     true
 }
 
+/// Makes sure a whole fen can be loaded by [Board::from_fen](crate::board::Board::from_fen) without panicking
+/// - Only checks the format, [Board::validate_position](crate::board::Board::validate_position) checks the position
+pub(crate) fn validate_full_fen(fen: &str) -> bool {
+    let parts: Vec<_> = fen.split_whitespace().collect();
+    if parts.len() != 6 || !validate_fen(parts[0]) {
+        return false;
+    }
+
+    if !parts[0]
+        .chars()
+        .all(|c| c == '/' || c.is_ascii_digit() || "pnbrqkPNBRQK".contains(c))
+    {
+        return false;
+    }
+
+    let en_passant_rank = match parts[1] {
+        "w" => '6',
+        "b" => '3',
+        _ => return false,
+    };
+
+    if parts[2] != "-" && !parts[2].chars().all(|c| "KQkq".contains(c)) {
+        return false;
+    }
+
+    if parts[3] != "-" {
+        let mut chars = parts[3].chars();
+        if !matches!(chars.next(), Some('a'..='h'))
+            || chars.next() != Some(en_passant_rank)
+            || chars.next().is_some()
+        {
+            return false;
+        }
+    }
+
+    parts[4].parse::<u32>().is_ok() && parts[5].parse::<u32>().is_ok_and(|n| n >= 1)
+}
+
 /// Shorthand for creating a `Loc`
 #[macro_export]
 macro_rules! loc {
@@ -93,34 +143,27 @@ macro_rules! hashset {
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub(crate) struct Loc(pub(crate) usize, pub(crate) usize);
 impl Loc {
-    /// Create a new `Loc`, shifted `x_diff` and `y_diff` away from the current pos
-    pub(crate) fn copy_move_i32(&self, x_diff: i32, y_diff: i32) -> (Loc, bool) {
-        let mut new_x = self.0 as i32 + x_diff;
-        let mut new_y = self.1 as i32 + y_diff;
-
-        if new_x < 0 || new_y < 0 {
-            new_x = new_x.clamp(0, i32::MAX);
-            new_y = new_y.clamp(0, i32::MAX);
-            return (loc!(new_x as usize, new_y as usize), true);
+    /// Create a new `Loc`, shifted `dx` and `dy` away from the current pos
+    /// - Returns `None` if the new pos is off the board
+    pub(crate) fn offset(&self, dx: i32, dy: i32) -> Option<Loc> {
+        let x = self.0 as i32 + dx;
+        let y = self.1 as i32 + dy;
+
+        if !(0..8).contains(&x) || !(0..8).contains(&y) {
+            return None;
         }
 
-        (loc!(new_x as usize, new_y as usize), false)
+        Some(loc!(x as usize, y as usize))
     }
 
-    /// Move the current pos by `x_diff` and `y_diff`
-    pub(crate) fn move_i32(&mut self, x_diff: i32, y_diff: i32) -> bool {
-        let new_x = self.0 as i32 + x_diff;
-        let new_y = self.1 as i32 + y_diff;
-
-        if new_x < 0 || new_y < 0 {
-            self.0 = new_x.clamp(0, i32::MAX) as usize;
-            self.1 = new_y.clamp(0, i32::MAX) as usize;
-            return false;
+    /// Iterate over the squares in the direction of `dx` and `dy`, stopping at the edge of the board
+    /// - Doesn't include the current pos
+    pub(crate) fn ray(&self, dx: i32, dy: i32) -> Ray {
+        Ray {
+            current: *self,
+            dx,
+            dy,
         }
-
-        self.0 = new_x as usize;
-        self.1 = new_y as usize;
-        true
     }
 
     /// Get the location as chess notation IE (`(0, 0)` becomes `"A8"`)
@@ -140,31 +183,99 @@ impl Loc {
         format!("{x}{y}")
     }
 
-    /// Creates a `Loc` from a chess notation string IE (`"A8"` becomes `(0, 0)`)
+    /// Creates a `Loc` from a chess notation string IE (`"a8"` becomes `(0, 0)`)
+    /// - Panics on anything that isn't a square, use [Loc::parse_notation] for notation from the user
     pub(crate) fn from_notation(notation: &str) -> Loc {
-        let mut chars = notation.chars();
-        let x = chars.next().unwrap() as u32 - 97;
-        let y = match chars.next().unwrap() {
-            '8' => 0,
-            '7' => 1,
-            '6' => 2,
-            '5' => 3,
-            '4' => 4,
-            '3' => 5,
-            '2' => 6,
-            '1' => 7,
-            _ => panic!(),
+        Loc::parse_notation(notation).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// [Loc::from_notation], or an error if `notation` isn't a square
+    pub(crate) fn parse_notation(notation: &str) -> Result<Loc, ChessError> {
+        let chars: Vec<char> = notation.chars().collect();
+        let ['a'..='h', '1'..='8'] = chars[..] else {
+            return Err(ChessError::Notation(notation.to_string()));
         };
-        loc!(x as usize, y)
+        Ok(loc!(
+            chars[0] as usize - 'a' as usize,
+            '8' as usize - chars[1] as usize
+        ))
+    }
+
+    /// The same square with the board turned around, IE `a8` becomes `h1`
+    #[cfg(feature = "gui")]
+    pub(crate) fn rotated(&self) -> Loc {
+        loc!(7 - self.0, 7 - self.1)
     }
 
     /// Convert the `Loc` to a `(f32, f32)`
+    #[cfg(feature = "gui")]
     pub(crate) fn as_f32(&self) -> (f32, f32) {
         (self.0 as f32, self.1 as f32)
     }
 }
 
+/// Iterator over the squares in a direction from a [Loc], created with [Loc::ray]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Ray {
+    current: Loc,
+    dx: i32,
+    dy: i32,
+}
+impl Iterator for Ray {
+    type Item = Loc;
+
+    fn next(&mut self) -> Option<Loc> {
+        self.current = self.current.offset(self.dx, self.dy)?;
+        Some(self.current)
+    }
+}
+
+/// Set of squares as one bit per square, so building attack sets every move doesn't allocate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub(crate) struct SquareSet(pub(crate) u64);
+impl SquareSet {
+    /// Bit for `loc`, IE `a8` is the lowest bit
+    pub(crate) fn bit(loc: &Loc) -> u64 {
+        1 << (loc.1 * 8 + loc.0)
+    }
+
+    pub(crate) fn insert(&mut self, loc: Loc) {
+        self.0 |= SquareSet::bit(&loc);
+    }
+
+    pub(crate) fn contains(&self, loc: &Loc) -> bool {
+        self.0 & SquareSet::bit(loc) != 0
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Squares in the set, row by row from `a8`
+    pub(crate) fn iter(&self) -> impl Iterator<Item = Loc> {
+        let bits = self.0;
+        (0..64)
+            .filter(move |i| bits & (1 << i) != 0)
+            .map(|i| loc!(i % 8, i / 8))
+    }
+}
+impl BitOrAssign for SquareSet {
+    fn bitor_assign(&mut self, other: SquareSet) {
+        self.0 |= other.0;
+    }
+}
+impl FromIterator<Loc> for SquareSet {
+    fn from_iter<I: IntoIterator<Item = Loc>>(iter: I) -> SquareSet {
+        let mut set = SquareSet::default();
+        for loc in iter {
+            set.insert(loc);
+        }
+        set
+    }
+}
+
 /// Sees if a rectangle contains a point
+#[cfg(feature = "gui")]
 pub(crate) fn touches(point: (f32, f32), rect: (f32, f32, f32, f32)) -> bool {
     point.0 >= rect.0
         && point.0 <= rect.0 + rect.2
@@ -173,6 +284,7 @@ pub(crate) fn touches(point: (f32, f32), rect: (f32, f32, f32, f32)) -> bool {
 }
 
 /// Write multiple lines of text that are automatically spaced
+#[cfg(feature = "gui")]
 pub(crate) fn multiline_text_ex(text: &str, x: f32, y: f32, params: TextParams) {
     let height = measure_text(text, Some(params.font), params.font_size, params.font_scale).height;
     for (i, line) in text.lines().enumerate() {
@@ -180,12 +292,6 @@ pub(crate) fn multiline_text_ex(text: &str, x: f32, y: f32, params: TextParams)
     }
 }
 
-/// Get a random element from an array
-pub(crate) fn choose_array<T>(arr: &[T]) -> &T {
-    let index = gen_range(0, arr.len());
-    &arr[index]
-}
-
 /// If `cond` is `ChessColor::White`, then do `if_white`, else `if_black`
 #[macro_export]
 macro_rules! color_ternary {
@@ -211,9 +317,11 @@ macro_rules! ternary {
 }
 
 /// Convert a position on the screen to a board location
+#[cfg(feature = "gui")]
 pub(crate) fn pos_to_board(pos: (f32, f32)) -> Option<Loc> {
-    let x = (pos.0 - MARGIN) / SQUARE_SIZE;
-    let y = (pos.1 - MARGIN) / SQUARE_SIZE;
+    let layout = layout();
+    let x = (pos.0 - layout.margin) / layout.square_size;
+    let y = (pos.1 - layout.top) / layout.square_size;
 
     if x < 0.0 || y < 0.0 {
         return None;
@@ -230,28 +338,34 @@ pub(crate) fn pos_to_board(pos: (f32, f32)) -> Option<Loc> {
 }
 
 /// Converts a board location to a position on the screen
+#[cfg(feature = "gui")]
 pub(crate) fn board_to_pos_center(loc: &Loc) -> (f32, f32) {
+    let layout = layout();
     (
-        loc.0 as f32 * SQUARE_SIZE + MARGIN + SQUARE_SIZE / 2.0,
-        loc.1 as f32 * SQUARE_SIZE + MARGIN + SQUARE_SIZE / 2.0,
+        loc.0 as f32 * layout.square_size + layout.margin + layout.square_size / 2.0,
+        loc.1 as f32 * layout.square_size + layout.top + layout.square_size / 2.0,
     )
 }
 
 /// Creates a button that can be clicked
-#[derive(Clone, Copy)]
+#[cfg(feature = "gui")]
+#[derive(Clone)]
 pub(crate) struct Button {
     x: f32,
     y: f32,
     w: f32,
     h: f32,
-    text: &'static str,
+    text: String,
+    /// Shown next to the mouse while hovering
+    tooltip: Option<String>,
     hover: bool,
     pressed: bool,
     dims: TextDimensions,
     params: TextParams,
 }
+#[cfg(feature = "gui")]
 impl Button {
-    pub(crate) fn new(x: f32, y: f32, w: f32, h: f32, text: &'static str) -> Button {
+    pub(crate) fn new(x: f32, y: f32, w: f32, h: f32, text: &str) -> Button {
         let params = TextParams {
             font_size: 15,
             font_scale: 1.0,
@@ -264,7 +378,8 @@ impl Button {
             y,
             w,
             h,
-            text,
+            text: text.to_string(),
+            tooltip: None,
             hover: false,
             pressed: false,
             params,
@@ -272,6 +387,21 @@ impl Button {
         }
     }
 
+    /// Creates a square button with a single glyph as its icon, and a tooltip explaining it
+    pub(crate) fn icon(x: f32, y: f32, size: f32, icon: &'static str, tooltip: &str) -> Button {
+        let mut button = Button::new(x, y, size, size, icon);
+        button.params.font_size = 20;
+        button.dims = measure_text(
+            icon,
+            Some(button.params.font),
+            button.params.font_size,
+            button.params.font_scale,
+        );
+        button.tooltip = Some(tooltip.to_string());
+        button
+    }
+
+    #[allow(clippy::needless_bool_assign)]
     pub(crate) fn update(&mut self) -> bool {
         self.hover = touches(
             camera().mouse_position().into(),
@@ -293,6 +423,11 @@ impl Button {
         false
     }
 
+    /// (x, y, w, h) of the button
+    pub(crate) fn rect(&self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, self.w, self.h)
+    }
+
     pub(crate) fn draw(&self) {
         let color = match (self.hover, self.pressed) {
             (true, true) => COLOR_BUTTON_PRESSED,
@@ -304,11 +439,319 @@ impl Button {
 
         // Draw centered text
         draw_text_ex(
-            self.text,
+            &self.text,
             self.x + self.w / 2.0 - self.dims.width / 2.0,
             self.y + self.h / 2.0 + self.dims.height / 2.0,
             self.params,
         );
+
+        if let (true, Some(tooltip)) = (self.hover, &self.tooltip) {
+            let params = TextParams {
+                font_size: 12,
+                ..self.params
+            };
+            let dims = measure_text(tooltip, Some(params.font), params.font_size, 1.0);
+
+            // Keep the tooltip inside the window
+            let x = self
+                .x
+                .min(layout().width - dims.width - layout().margin / 2.0);
+            let y = self.y - dims.height - layout().margin / 2.0;
+            draw_rectangle(
+                x - 4.0,
+                y - 4.0,
+                dims.width + 8.0,
+                dims.height + 8.0,
+                COLOR_BACKGROUND,
+            );
+            draw_text_ex(tooltip, x, y + dims.offset_y, params);
+        }
+    }
+}
+
+/// Button with a switch on its right showing wether it's on, flipped when clicked
+#[cfg(feature = "gui")]
+#[derive(Clone)]
+pub(crate) struct Toggle {
+    /// Drawn without text, the label is drawn on the left instead
+    button: Button,
+    label: String,
+    pub(crate) value: bool,
+}
+#[cfg(feature = "gui")]
+impl Toggle {
+    pub(crate) fn new(x: f32, y: f32, w: f32, h: f32, label: &str, value: bool) -> Toggle {
+        Toggle {
+            button: Button::new(x, y, w, h, ""),
+            label: label.to_string(),
+            value,
+        }
+    }
+
+    /// Returns `true` if it was flipped
+    pub(crate) fn update(&mut self) -> bool {
+        let flipped = self.button.update();
+        if flipped {
+            self.value = !self.value;
+        }
+        flipped
+    }
+
+    pub(crate) fn rect(&self) -> (f32, f32, f32, f32) {
+        self.button.rect()
+    }
+
+    pub(crate) fn draw(&self) {
+        self.button.draw();
+        let Button {
+            x, y, w, h, params, ..
+        } = self.button;
+
+        let dims = measure_text(&self.label, Some(params.font), params.font_size, 1.0);
+        draw_text_ex(
+            &self.label,
+            x + layout().margin / 2.0,
+            y + h / 2.0 + dims.height / 2.0,
+            params,
+        );
+
+        // Knob on the right when on
+        let (track_w, track_h) = (h, h / 2.0);
+        let (track_x, track_y) = (
+            x + w - track_w - layout().margin / 2.0,
+            y + h / 2.0 - track_h / 2.0,
+        );
+        let (track, knob) = ternary!(
+            self.value,
+            (COLOR_WHITE, COLOR_BACKGROUND),
+            (COLOR_BACKGROUND, COLOR_WHITE)
+        );
+        draw_rectangle(track_x, track_y, track_w, track_h, track);
+        draw_rectangle(
+            track_x + ternary!(self.value, track_w - track_h, 0.0) + 2.0,
+            track_y + 2.0,
+            track_h - 4.0,
+            track_h - 4.0,
+            knob,
+        );
+    }
+}
+
+/// Values a [Slider] can be set to, and how they're shown
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SliderRange {
+    pub(crate) min: f32,
+    pub(crate) max: f32,
+    /// Values are rounded to a multiple of this from `min`
+    pub(crate) step: f32,
+    /// Text for a value, IE `80%`
+    pub(crate) format: fn(f32) -> String,
+}
+#[cfg(feature = "gui")]
+impl SliderRange {
+    /// Closest value in the range to `value`
+    pub(crate) fn snap(&self, value: f32) -> f32 {
+        let steps = ((value - self.min) / self.step).round();
+        (self.min + steps * self.step).clamp(self.min, self.max)
+    }
+
+    /// How far `value` is along the range, from `0.0` to `1.0`
+    pub(crate) fn fraction(&self, value: f32) -> f32 {
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+}
+
+/// Button sized bar with a knob that can be dragged along a [SliderRange]
+#[cfg(feature = "gui")]
+#[derive(Clone)]
+pub(crate) struct Slider {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    label: String,
+    range: SliderRange,
+    pub(crate) value: f32,
+    hover: bool,
+    /// Wether the knob follows the mouse, from a click on the slider until the button is let go
+    dragging: bool,
+    params: TextParams,
+}
+#[cfg(feature = "gui")]
+impl Slider {
+    pub(crate) fn new(
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        label: &str,
+        range: SliderRange,
+        value: f32,
+    ) -> Slider {
+        Slider {
+            x,
+            y,
+            w,
+            h,
+            label: label.to_string(),
+            value: range.snap(value),
+            range,
+            hover: false,
+            dragging: false,
+            params: TextParams {
+                font_size: 15,
+                font_scale: 1.0,
+                color: COLOR_WHITE,
+                font: font(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Carries on a drag from the last frame, for sliders that are made again every frame
+    pub(crate) fn dragging(mut self, dragging: bool) -> Slider {
+        self.dragging = dragging;
+        self
+    }
+
+    /// Wether the knob follows the mouse
+    pub(crate) fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// (x, width) of the bar the knob moves along
+    fn track(&self) -> (f32, f32) {
+        (self.x + layout().margin / 2.0, self.w - layout().margin)
+    }
+
+    /// Returns `true` if the value changed
+    pub(crate) fn update(&mut self) -> bool {
+        let mouse: (f32, f32) = camera().mouse_position().into();
+        self.hover = touches(mouse, (self.x, self.y, self.w, self.h));
+        if self.hover && is_mouse_button_pressed(MouseButton::Left) {
+            self.dragging = true;
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.dragging = false;
+        }
+        if !self.dragging {
+            return false;
+        }
+
+        let (track_x, track_w) = self.track();
+        let range = self.range;
+        let value = range.snap(range.min + (mouse.0 - track_x) / track_w * (range.max - range.min));
+        let changed = value != self.value;
+        self.value = value;
+        changed
+    }
+
+    /// Moves the value `steps` steps along the range, returns `true` if it changed
+    pub(crate) fn step(&mut self, steps: f32) -> bool {
+        let value = self.range.snap(self.value + steps * self.range.step);
+        let changed = value != self.value;
+        self.value = value;
+        changed
+    }
+
+    pub(crate) fn rect(&self) -> (f32, f32, f32, f32) {
+        (self.x, self.y, self.w, self.h)
+    }
+
+    pub(crate) fn draw(&self) {
+        let color = match (self.hover || self.dragging, self.dragging) {
+            (true, true) => COLOR_BUTTON_PRESSED,
+            (true, false) => COLOR_BUTTON_HOVER,
+            _ => COLOR_BUTTON,
+        };
+        draw_rectangle(self.x, self.y, self.w, self.h, color);
+
+        let text = format!("{}: {}", self.label, (self.range.format)(self.value));
+        let dims = measure_text(&text, Some(self.params.font), self.params.font_size, 1.0);
+        draw_text_ex(
+            &text,
+            self.x + layout().margin / 2.0,
+            self.y + self.h * 0.4 + dims.height / 2.0,
+            self.params,
+        );
+
+        let (track_x, track_w) = self.track();
+        let track_y = self.y + self.h * 0.75;
+        draw_rectangle(track_x, track_y - 1.0, track_w, 2.0, COLOR_BACKGROUND);
+        let knob_x = track_x + track_w * self.range.fraction(self.value);
+        draw_rectangle(knob_x - 3.0, track_y - 5.0, 6.0, 10.0, COLOR_WHITE);
+    }
+}
+
+/// Single line text box that takes typed characters, for short text like comments
+#[cfg(feature = "gui")]
+#[derive(Clone)]
+pub(crate) struct TextInput {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    pub(crate) text: String,
+    params: TextParams,
+}
+#[cfg(feature = "gui")]
+impl TextInput {
+    const MAX_LENGTH: usize = 200;
+
+    pub(crate) fn new(x: f32, y: f32, w: f32, h: f32, text: &str) -> TextInput {
+        // Drops the key that opened the input, IE `C` for comments
+        while get_char_pressed().is_some() {}
+
+        TextInput {
+            x,
+            y,
+            w,
+            h,
+            text: text.to_string(),
+            params: TextParams {
+                font_size: 15,
+                font_scale: 1.0,
+                color: COLOR_WHITE,
+                font: font(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Takes the characters typed this frame, returns `true` if enter was pressed
+    pub(crate) fn update(&mut self) -> bool {
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() && self.text.chars().count() < TextInput::MAX_LENGTH {
+                self.text.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.text.pop();
+        }
+
+        is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter)
+    }
+
+    pub(crate) fn draw(&self) {
+        draw_rectangle(self.x, self.y, self.w, self.h, COLOR_BUTTON);
+
+        // Only the end of the text is shown if it doesn't fit, so the cursor stays visible
+        let mut shown = format!("{}_", self.text);
+        while shown.chars().count() > 1
+            && measure_text(&shown, Some(self.params.font), self.params.font_size, 1.0).width
+                > self.w - layout().margin
+        {
+            shown.remove(0);
+        }
+
+        let dims = measure_text(&shown, Some(self.params.font), self.params.font_size, 1.0);
+        draw_text_ex(
+            &shown,
+            self.x + layout().margin / 2.0,
+            self.y + self.h / 2.0 + dims.height / 2.0,
+            self.params,
+        );
     }
 }
 
@@ -321,6 +764,7 @@ pub(crate) fn angle(origin: (f32, f32), dest: (f32, f32)) -> f32 {
 }
 
 /// Returns a new point that is the distance away from the original point in the direction of the angle
+#[cfg(feature = "gui")]
 pub(crate) fn project(origin: (f32, f32), angle: f32, distance: f32) -> (f32, f32) {
     (
         origin.0 + (angle.cos() * distance),
@@ -329,6 +773,7 @@ pub(crate) fn project(origin: (f32, f32), angle: f32, distance: f32) -> (f32, f3
 }
 
 /// Gets the distance between two points
+#[cfg(feature = "gui")]
 pub(crate) fn distance(p1: (f32, f32), p2: (f32, f32)) -> f32 {
     ((p1.0 - p2.0).powf(2.0) + (p1.1 - p2.1).powf(2.0)).sqrt()
 }
@@ -343,8 +788,13 @@ pub(crate) struct Tween {
     angle: f32,
     speed: f32,
 }
-#[cfg(not(target_family = "wasm"))]
+#[cfg(all(feature = "gui", not(target_family = "wasm")))]
 impl Tween {
+    /// Wether it has reached the end
+    pub(crate) fn finished(&self) -> bool {
+        self.speed == 0.0
+    }
+
     pub(crate) fn update(&mut self) -> (f32, f32) {
         self.start = project(self.start, self.angle, self.speed * get_frame_time());
         if distance(self.start, self.end) <= self.speed * get_frame_time() {
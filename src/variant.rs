@@ -0,0 +1,213 @@
+//! Rule variants picked in the new game dialog, for something different from standard chess
+//!
+//! Also the parts of [Board] for the variants that change the rules, split for readability
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::{Board, BoardState, ChessColor, DrawReason};
+use crate::move_cache::MoveCache;
+use crate::pieces::piece::PieceNames;
+use crate::util::Loc;
+#[cfg(feature = "gui")]
+use crate::util::SquareSet;
+use crate::{color_ternary, ternary};
+
+/// Checks a side has to give to win three-check
+pub(crate) const CHECKS_TO_WIN: u8 = 3;
+
+/// Starting position of horde, 36 white pawns against black's usual army
+#[cfg(feature = "gui")]
+pub(crate) const HORDE_FEN: &str =
+    "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+
+/// Starting position of racing kings, both sides side by side on the first two ranks
+#[cfg(feature = "gui")]
+pub(crate) const RACING_KINGS_FEN: &str = "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1";
+
+/// Which rules a game is played by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Variant {
+    Standard,
+    /// The opponent's pieces are hidden unless one of the player's pieces can move to their square, see
+    /// [Variant::revealed]
+    /// - There's no check, so a king can move into an attack or be left in one, and taking the king wins
+    FogOfWar,
+    /// Giving check three times wins, counted in [Board::checks]
+    ThreeCheck,
+    /// Captures explode, taking the capturing piece and every piece around the square except pawns with them
+    /// - Kings can't capture, a move can't explode its own king, and exploding the other king wins
+    Atomic,
+    /// White has a horde of pawns and no king, and wins by checkmate while black wins by taking every piece
+    /// - Pawns on the first rank can move two squares too, but can't be taken en passent after
+    Horde,
+    /// First king to the eighth rank wins, and giving check is illegal
+    /// - If black's king can reach it right after white's did, the game goes on for one more move and is drawn if it
+    ///   does
+    RacingKings,
+}
+impl Variant {
+    #[cfg(feature = "gui")]
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::FogOfWar => "Fog of war",
+            Variant::ThreeCheck => "Three-check",
+            Variant::Atomic => "Atomic",
+            Variant::Horde => "Horde",
+            Variant::RacingKings => "Racing Kings",
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub(crate) fn next(&self) -> Variant {
+        match self {
+            Variant::Standard => Variant::FogOfWar,
+            Variant::FogOfWar => Variant::ThreeCheck,
+            Variant::ThreeCheck => Variant::Atomic,
+            Variant::Atomic => Variant::Horde,
+            Variant::Horde => Variant::RacingKings,
+            Variant::RacingKings => Variant::Standard,
+        }
+    }
+
+    /// Position the variant starts from, `None` for the standard one
+    #[cfg(feature = "gui")]
+    pub(crate) fn start_fen(&self) -> Option<&'static str> {
+        match self {
+            Variant::Horde => Some(HORDE_FEN),
+            Variant::RacingKings => Some(RACING_KINGS_FEN),
+            _ => None,
+        }
+    }
+
+    /// Squares `color` can see, `None` if the whole board can be seen
+    /// - Those of their own pieces and every square one of them can move to, so an opponent's piece is seen when it can
+    ///   be captured
+    #[cfg(feature = "gui")]
+    pub(crate) fn revealed(&self, board: &Board, color: ChessColor) -> Option<SquareSet> {
+        if *self != Variant::FogOfWar {
+            return None;
+        }
+
+        let mut revealed = SquareSet::default();
+        for piece in board.raw.iter().flatten().flatten() {
+            if piece.color == color {
+                revealed.insert(piece.pos);
+            }
+        }
+        for (_, to) in board.moves(color) {
+            revealed.insert(to);
+        }
+        Some(revealed)
+    }
+}
+
+impl Board {
+    /// Switches the rules the board is played by, before any moves are played on it
+    pub(crate) fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        // Cached moves and the hash are from the old rules
+        self.move_cache = MoveCache::new();
+        self.hash = self.hash();
+        self.prev_states.clear();
+        self.prev_states.push(self.hash);
+        self.update_things(true);
+    }
+
+    /// Counts a check given by `color`'s last move in three-check
+    pub(crate) fn count_check(&mut self, color: ChessColor) {
+        let (white_king, black_king) = self.kings();
+        let king = color_ternary!(color, black_king, white_king);
+        if !king.is_some_and(|king| self.attacks(color).contains(&king)) {
+            return;
+        }
+
+        *color_ternary!(color, &mut self.checks.0, &mut self.checks.1) += 1;
+        // Positions from before the check have fewer checks, so they can't repeat
+        self.prev_states.clear();
+    }
+
+    /// Side that gave [CHECKS_TO_WIN] checks in three-check, if either has
+    pub(crate) fn three_checks(&self) -> Option<ChessColor> {
+        match self.checks {
+            (white, _) if white >= CHECKS_TO_WIN => Some(ChessColor::White),
+            (_, black) if black >= CHECKS_TO_WIN => Some(ChessColor::Black),
+            _ => None,
+        }
+    }
+
+    /// Removes the piece that captured on `center` in atomic, along with every piece around it but pawns
+    pub(crate) fn explode(&mut self, center: &Loc) {
+        self.set(center, None);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let Some(loc) = center.offset(dx, dy) else {
+                    continue;
+                };
+                if self
+                    .get(&loc)
+                    .is_some_and(|piece| piece.name != PieceNames::Pawn)
+                {
+                    self.set(&loc, None);
+                    self.castle.remove_square(&loc);
+                }
+            }
+        }
+    }
+
+    /// Wether `color`'s king was exploded in atomic
+    pub(crate) fn exploded(&self, color: ChessColor) -> bool {
+        let (white_king, black_king) = self.kings();
+        self.variant == Variant::Atomic && color_ternary!(color, white_king, black_king).is_none()
+    }
+
+    /// Wether `color`'s king was taken in fog of war
+    pub(crate) fn king_captured(&self, color: ChessColor) -> bool {
+        let (white_king, black_king) = self.kings();
+        self.variant == Variant::FogOfWar && color_ternary!(color, white_king, black_king).is_none()
+    }
+
+    /// Wether black took every white piece in horde
+    pub(crate) fn horde_captured(&self) -> bool {
+        self.variant == Variant::Horde
+            && !self
+                .raw
+                .iter()
+                .flatten()
+                .flatten()
+                .any(|piece| piece.color == ChessColor::White)
+    }
+
+    /// Wether `color` would give check with a move, which is illegal in racing kings
+    pub(crate) fn gives_check(&self, color: ChessColor) -> bool {
+        self.variant == Variant::RacingKings
+            && color_ternary!(color, self.check_black, self.check_white)
+    }
+
+    /// How a racing kings game ended, if a king reached the eighth rank
+    pub(crate) fn race_state(&self) -> Option<BoardState> {
+        if self.variant != Variant::RacingKings {
+            return None;
+        }
+
+        let (white_king, black_king) = self.kings();
+        let reached = |king: Option<Loc>| king.is_some_and(|king| king.1 == 0);
+        match (reached(white_king), reached(black_king)) {
+            (true, true) => Some(BoardState::Draw(DrawReason::RaceTie)),
+            (false, true) => Some(BoardState::Outraced(ChessColor::White)),
+            (true, false) => {
+                // White moved first, so black gets one move to catch up
+                let catches_up = self.turn == ChessColor::Black
+                    && black_king
+                        .and_then(|king| self.get(&king))
+                        .is_some_and(|king| king.moves(self).iter().any(|to| to.1 == 0));
+                ternary!(
+                    catches_up,
+                    None,
+                    Some(BoardState::Outraced(ChessColor::Black))
+                )
+            }
+            (false, false) => None,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! XBoard/WinBoard protocol (CECP), run with `chess-ai xboard`
+//!
+//! Supports what's needed to play and set up games: `new`, `force`, `go`, `playother`, `usermove`, `setboard`,
+//! `undo`, `remove`, `result`, and time limits through `st`, `sd`, and `time`
+//!
+//! - The engine thinks while the GUI waits, so `?` and pondering aren't supported
+//! - Commands for features the engine doesn't have, IE `level` or `hard`, are accepted and ignored
+
+use crate::agent::SearchLimits;
+use crate::board::ChessColor;
+use crate::pgn::{coordinate, parse_move, result, san_line, termination};
+use crate::platform::now;
+use crate::position::describe_issues;
+use crate::protocol::{Protocol, ProtocolGame};
+use crate::settings::settings;
+use crate::{color_ternary, ternary};
+
+/// Sent in reply to `protover`
+const FEATURES: &str =
+    "feature myname=\"Chess AI\" ping=1 setboard=1 usermove=1 playother=1 san=0 \
+    colors=0 sigint=0 sigterm=0 done=1";
+
+/// Commands that are accepted but change nothing
+const IGNORED: [&str; 12] = [
+    "xboard", "accepted", "rejected", "random", "easy", "hard", "computer", "otim", "level",
+    "name", "rating", "ics",
+];
+
+pub(crate) struct XBoard {
+    game: ProtocolGame,
+    /// Side the engine plays, `None` in force mode
+    engine: Option<ChessColor>,
+    /// Seconds per move, from `st`
+    move_time: Option<f64>,
+    /// From `sd`
+    max_depth: Option<u8>,
+    /// Seconds left on the engine's clock, from `time`
+    clock: Option<f64>,
+    /// Wether to send what the engine found with each move, from `post` and `nopost`
+    post: bool,
+}
+impl XBoard {
+    pub(crate) fn new() -> XBoard {
+        XBoard {
+            game: ProtocolGame::new(),
+            engine: Some(ChessColor::Black),
+            move_time: None,
+            max_depth: None,
+            clock: None,
+            post: false,
+        }
+    }
+
+    fn limits(&self) -> SearchLimits {
+        let mut limits = SearchLimits::new(
+            self.move_time
+                .unwrap_or_else(|| settings().difficulty.max_time()),
+        );
+        limits.max_depth = self.max_depth;
+        // Don't let the engine flag itself, same as in the GUI
+        if let Some(clock) = self.clock {
+            limits.max_time = limits.max_time.min(clock / 20.0);
+        }
+        limits
+    }
+
+    /// Sends the result if the game just ended
+    fn report_result(&mut self, out: &mut Vec<String>) {
+        let state = self.game.board.state;
+        if self.game.board.is_over() {
+            out.push(format!("{} {{{}}}", result(&state), termination(&state)));
+            self.engine = None;
+        }
+    }
+
+    /// Plays the engine's move if it's its turn
+    fn engine_move(&mut self, out: &mut Vec<String>) {
+        let board = &self.game.board;
+        if self.engine != Some(board.turn) || board.is_over() {
+            return;
+        }
+
+        let start = now();
+        let (Some((from, to)), info) = self.game.think(&self.limits()) else {
+            return;
+        };
+        if let Some(info) = info.filter(|info| self.post && info.depth > 0) {
+            // Thinking output is `depth score time nodes pv`, with the score for the engine and time in centiseconds
+            out.push(format!(
+                "{} {} {} 0 {}",
+                info.depth,
+                color_ternary!(board.turn, info.score, -info.score),
+                ((now() - start) * 100.0) as u64,
+                san_line(board, &info.pv).join(" ")
+            ));
+        }
+
+        out.push(format!("move {}", coordinate(board, &from, &to)));
+        self.game.apply(&from, &to);
+        self.report_result(out);
+    }
+
+    fn user_move(&mut self, text: &str, out: &mut Vec<String>) {
+        match self.game.play(text) {
+            Ok(_) => {
+                self.report_result(out);
+                self.engine_move(out);
+            }
+            Err(reason) => out.push(format!("Illegal move ({}): {}", reason, text)),
+        }
+    }
+}
+impl Protocol for XBoard {
+    fn handle(&mut self, command: &str, out: &mut Vec<String>) -> bool {
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+        let number = || args.trim().parse::<f64>().ok();
+
+        match name {
+            "" => {}
+            "quit" => return false,
+            "protover" => out.push(FEATURES.to_string()),
+            // Only the depth limit is reset, the GUI sends time controls once per session
+            "new" => {
+                *self = XBoard {
+                    move_time: self.move_time,
+                    clock: self.clock,
+                    post: self.post,
+                    ..XBoard::new()
+                }
+            }
+            "force" | "result" => self.engine = None,
+            "go" => {
+                self.engine = Some(self.game.board.turn);
+                self.engine_move(out);
+            }
+            "playother" => self.engine = Some(self.game.board.turn.other()),
+            "setboard" => {
+                if let Err(issues) = self.game.set_fen(args) {
+                    out.push(format!(
+                        "tellusererror Illegal position: {}",
+                        describe_issues(&issues)
+                    ));
+                }
+            }
+            "usermove" => self.user_move(args, out),
+            "undo" | "remove" => {
+                self.game.undo(ternary!(name == "undo", 1, 2));
+            }
+            "ping" => out.push(format!("pong {}", args)),
+            "post" | "nopost" => self.post = name == "post",
+            "st" => self.move_time = number(),
+            "sd" => self.max_depth = number().map(|depth| depth.clamp(1.0, u8::MAX as f64) as u8),
+            // Centiseconds
+            "time" => self.clock = number().map(|time| time / 100.0),
+            _ if IGNORED.contains(&name) => {}
+            // Protocol version 1 GUIs send moves without `usermove`
+            _ if parse_move(&self.game.board, command).is_some() => self.user_move(command, out),
+            _ => out.push(format!("Error (unknown command): {}", name)),
+        }
+        true
+    }
+}